@@ -0,0 +1,5 @@
+/// The annotations of the document models, without the native bindings,
+/// imported by the code generator.
+library polodb.annotations;
+
+export 'src/model.dart';
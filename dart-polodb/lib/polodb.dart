@@ -0,0 +1,14 @@
+/// Dart bindings of PoloDB, an embedded document database.
+///
+/// The documents are maps of strings to the values of JSON, or [ObjectId],
+/// [DateTime] and [Uint8List] values. [Database] calls PoloDB on the calling
+/// isolate, [AsyncDatabase] on a background isolate.
+library polodb;
+
+export 'dart:typed_data' show Uint8List;
+
+export 'src/async.dart' show AsyncDatabase, AsyncCollection;
+export 'src/database.dart'
+    show Database, Collection, TypedCollection, Cursor, UpdateResult, PoloDbException;
+export 'src/ext_json.dart' show ObjectId;
+export 'src/model.dart';
@@ -0,0 +1,86 @@
+import 'dart:isolate';
+
+import 'database.dart';
+
+/// A database whose calls run on background isolates, so they don't block the
+/// isolate of the caller, such as the UI isolate of Flutter.
+///
+/// Each call runs on a short-lived isolate given the address of the database. The
+/// finds read all their documents on that isolate and return them as a list.
+class AsyncDatabase {
+  final Database database;
+
+  AsyncDatabase(this.database);
+
+  /// Open the database at [path] on a background isolate.
+  static Future<AsyncDatabase> open(String path) async {
+    final address = await Isolate.run(() => Database.open(path).address);
+    return AsyncDatabase(Database.fromAddress(address));
+  }
+
+  /// Open the snapshot file at [path] on a background isolate.
+  static Future<AsyncDatabase> openSnapshot(String path) async {
+    final address = await Isolate.run(() => Database.openSnapshot(path).address);
+    return AsyncDatabase(Database.fromAddress(address));
+  }
+
+  /// Close the database, once the calls are complete.
+  void close() => database.close();
+
+  Future<R> _run<R>(R Function(Database database) body) {
+    final address = database.address;
+    return Isolate.run(() => body(Database.fromAddress(address)));
+  }
+
+  Future<List<String>> listCollectionNames() => _run((db) => db.listCollectionNames());
+
+  Future<void> createCollection(String name) => _run((db) => db.createCollection(name));
+
+  Future<void> dropCollection(String name) => _run((db) => db.dropCollection(name));
+
+  AsyncCollection collection(String name) => AsyncCollection._(this, name);
+}
+
+/// A collection of an [AsyncDatabase], whose methods are those of [Collection].
+class AsyncCollection {
+  final AsyncDatabase database;
+  final String name;
+
+  AsyncCollection._(this.database, this.name);
+
+  Future<R> _run<R>(R Function(Collection collection) body) {
+    final name = this.name;
+    return database._run((db) => body(db.collection(name)));
+  }
+
+  Future<Object?> insertOne(Map<String, dynamic> document) => _run((col) => col.insertOne(document));
+
+  Future<List<Object?>> insertMany(List<Map<String, dynamic>> documents) =>
+      _run((col) => col.insertMany(documents));
+
+  Future<List<Map<String, dynamic>>> find(
+          {Map<String, dynamic>? filter, Map<String, int>? sort, int? skip, int? limit}) =>
+      _run((col) => col.find(filter: filter, sort: sort, skip: skip, limit: limit).toList());
+
+  Future<Map<String, dynamic>?> findOne([Map<String, dynamic>? filter]) => _run((col) => col.findOne(filter));
+
+  Future<List<Map<String, dynamic>>> aggregate(List<Map<String, dynamic>> pipeline) =>
+      _run((col) => col.aggregate(pipeline).toList());
+
+  Future<int> countDocuments() => _run((col) => col.countDocuments());
+
+  Future<UpdateResult> updateOne(Map<String, dynamic> filter, Map<String, dynamic> update) =>
+      _run((col) => col.updateOne(filter, update));
+
+  Future<UpdateResult> updateMany(Map<String, dynamic> filter, Map<String, dynamic> update) =>
+      _run((col) => col.updateMany(filter, update));
+
+  Future<int> deleteOne(Map<String, dynamic> filter) => _run((col) => col.deleteOne(filter));
+
+  Future<int> deleteMany(Map<String, dynamic> filter) => _run((col) => col.deleteMany(filter));
+
+  Future<void> createIndex(Map<String, int> keys, {String? name, Map<String, dynamic>? options}) =>
+      _run((col) => col.createIndex(keys, name: name, options: options));
+
+  Future<void> dropIndex(String name) => _run((col) => col.dropIndex(name));
+}
@@ -0,0 +1,310 @@
+import 'dart:ffi';
+
+import 'package:ffi/ffi.dart';
+
+import 'ext_json.dart';
+import 'model.dart';
+import 'native.dart';
+
+/// An error returned by PoloDB.
+class PoloDbException implements Exception {
+  /// The code of the error, such as `6` for a duplicate key, listed in `polodb.h`.
+  final int code;
+  final String message;
+
+  const PoloDbException(this.code, this.message);
+
+  @override
+  String toString() => 'PoloDbException($code): $message';
+}
+
+void _check(int code) {
+  if (code == 0) {
+    return;
+  }
+  // the message is kept by the thread of the call, read it before the isolate yields
+  final message = Native.instance.lastError();
+  throw PoloDbException(code, message == nullptr ? 'unknown error' : message.toDartString());
+}
+
+/// Take a string returned by PoloDB, null if the pointer is null.
+String? _take(Pointer<Utf8> value) {
+  if (value == nullptr) {
+    return null;
+  }
+  try {
+    return value.toDartString();
+  } finally {
+    Native.instance.freeString(value);
+  }
+}
+
+Pointer<Utf8> _json(Object? value, Allocator arena) =>
+    value == null ? nullptr : encodeExtJson(value).toNativeUtf8(allocator: arena);
+
+/// A database opened on the calling isolate, whose calls block the isolate.
+///
+/// The database can be used by [AsyncDatabase] from other isolates through its
+/// [address], and must stay open while they do.
+class Database {
+  Pointer<PldbDatabase> _handle;
+
+  Database._(this._handle);
+
+  /// Open the database at [path], creating it if it doesn't exist.
+  factory Database.open(String path) => Database._(_open(Native.instance.open, path));
+
+  /// Open the snapshot file at [path], read-only.
+  factory Database.openSnapshot(String path) => Database._(_open(Native.instance.openSnapshot, path));
+
+  /// The database opened by another isolate at [address].
+  Database.fromAddress(int address) : _handle = Pointer.fromAddress(address);
+
+  static Pointer<PldbDatabase> _open(
+      int Function(Pointer<Utf8>, Pointer<Pointer<PldbDatabase>>) open, String path) {
+    return using((arena) {
+      final out = arena<Pointer<PldbDatabase>>();
+      _check(open(path.toNativeUtf8(allocator: arena), out));
+      return out.value;
+    });
+  }
+
+  /// The version of PoloDB.
+  static String get version => Native.instance.version().toDartString();
+
+  /// The address of the database, shared with the other isolates.
+  int get address => _handle.address;
+
+  Pointer<PldbDatabase> get _db {
+    if (_handle == nullptr) {
+      throw StateError('the database is closed');
+    }
+    return _handle;
+  }
+
+  /// Close the database, once the cursors opened on it are closed.
+  void close() {
+    if (_handle != nullptr) {
+      Native.instance.close(_handle);
+      _handle = nullptr;
+    }
+  }
+
+  List<String> listCollectionNames() {
+    return using((arena) {
+      final out = arena<Pointer<Utf8>>();
+      _check(Native.instance.listCollectionNames(_db, out));
+      return (decodeExtJson(_take(out.value)!) as List).cast<String>();
+    });
+  }
+
+  void createCollection(String name) {
+    using((arena) => _check(Native.instance.createCollection(_db, name.toNativeUtf8(allocator: arena))));
+  }
+
+  void dropCollection(String name) {
+    using((arena) => _check(Native.instance.dropCollection(_db, name.toNativeUtf8(allocator: arena))));
+  }
+
+  Collection collection(String name) => Collection._(this, name);
+}
+
+/// The counts of an update.
+class UpdateResult {
+  final int matchedCount;
+  final int modifiedCount;
+
+  const UpdateResult(this.matchedCount, this.modifiedCount);
+}
+
+/// A collection of documents.
+class Collection {
+  final Database database;
+  final String name;
+
+  Collection._(this.database, this.name);
+
+  /// The collection of the documents of the model [T].
+  TypedCollection<T> typed<T>(DocumentModel<T> model) => TypedCollection._(this, model);
+
+  /// Insert the document, returning its `_id`.
+  Object? insertOne(Map<String, dynamic> document) {
+    return using((arena) {
+      final out = arena<Pointer<Utf8>>();
+      _check(Native.instance.insertOne(
+          database._db, name.toNativeUtf8(allocator: arena), _json(document, arena), out));
+      return decodeExtJson(_take(out.value)!);
+    });
+  }
+
+  /// Insert the documents, returning their `_id` in the order of the documents.
+  List<Object?> insertMany(Iterable<Map<String, dynamic>> documents) {
+    return using((arena) {
+      final out = arena<Pointer<Utf8>>();
+      _check(Native.instance.insertMany(
+          database._db, name.toNativeUtf8(allocator: arena), _json(documents.toList(), arena), out));
+      return decodeExtJson(_take(out.value)!) as List<Object?>;
+    });
+  }
+
+  /// Find the documents matching [filter], all the documents if it's null.
+  Cursor find({Map<String, dynamic>? filter, Map<String, int>? sort, int? skip, int? limit}) {
+    final options = <String, dynamic>{
+      if (sort != null) 'sort': sort,
+      if (skip != null) 'skip': skip,
+      if (limit != null) 'limit': limit,
+    };
+    return using((arena) {
+      final out = arena<Pointer<PldbCursor>>();
+      _check(Native.instance.find(database._db, name.toNativeUtf8(allocator: arena), _json(filter, arena),
+          options.isEmpty ? nullptr : _json(options, arena), out));
+      return Cursor._(out.value);
+    });
+  }
+
+  /// Find the first document matching [filter], null if there is none.
+  Map<String, dynamic>? findOne([Map<String, dynamic>? filter]) {
+    return using((arena) {
+      final out = arena<Pointer<Utf8>>();
+      _check(Native.instance.findOne(
+          database._db, name.toNativeUtf8(allocator: arena), _json(filter, arena), out));
+      final document = _take(out.value);
+      return document == null ? null : decodeExtJson(document) as Map<String, dynamic>;
+    });
+  }
+
+  /// Run the aggregation [pipeline].
+  Cursor aggregate(List<Map<String, dynamic>> pipeline) {
+    return using((arena) {
+      final out = arena<Pointer<PldbCursor>>();
+      _check(Native.instance.aggregate(
+          database._db, name.toNativeUtf8(allocator: arena), _json(pipeline, arena), out));
+      return Cursor._(out.value);
+    });
+  }
+
+  int countDocuments() {
+    return using((arena) {
+      final out = arena<Uint64>();
+      _check(Native.instance.countDocuments(database._db, name.toNativeUtf8(allocator: arena), out));
+      return out.value;
+    });
+  }
+
+  UpdateResult updateOne(Map<String, dynamic> filter, Map<String, dynamic> update) =>
+      _update(Native.instance.updateOne, filter, update);
+
+  UpdateResult updateMany(Map<String, dynamic> filter, Map<String, dynamic> update) =>
+      _update(Native.instance.updateMany, filter, update);
+
+  UpdateResult _update(
+    int Function(Pointer<PldbDatabase>, Pointer<Utf8>, Pointer<Utf8>, Pointer<Utf8>, Pointer<Uint64>,
+            Pointer<Uint64>)
+        call,
+    Map<String, dynamic> filter,
+    Map<String, dynamic> update,
+  ) {
+    return using((arena) {
+      final matched = arena<Uint64>();
+      final modified = arena<Uint64>();
+      _check(call(database._db, name.toNativeUtf8(allocator: arena), _json(filter, arena),
+          _json(update, arena), matched, modified));
+      return UpdateResult(matched.value, modified.value);
+    });
+  }
+
+  /// Delete the first document matching [filter], returning the count of the deleted documents.
+  int deleteOne(Map<String, dynamic> filter) => _delete(Native.instance.deleteOne, filter);
+
+  /// Delete the documents matching [filter], returning the count of the deleted documents.
+  int deleteMany(Map<String, dynamic> filter) => _delete(Native.instance.deleteMany, filter);
+
+  int _delete(int Function(Pointer<PldbDatabase>, Pointer<Utf8>, Pointer<Utf8>, Pointer<Uint64>) call,
+      Map<String, dynamic> filter) {
+    return using((arena) {
+      final deleted = arena<Uint64>();
+      _check(call(database._db, name.toNativeUtf8(allocator: arena), _json(filter, arena), deleted));
+      return deleted.value;
+    });
+  }
+
+  /// Create the index of [keys], such as `{'code': 1}`. The [options] are those of
+  /// `IndexOptions` in camel case, such as `{'unique': true}`.
+  void createIndex(Map<String, int> keys, {String? name, Map<String, dynamic>? options}) {
+    final allOptions = {...?options, if (name != null) 'name': name};
+    using((arena) => _check(Native.instance.createIndex(database._db, this.name.toNativeUtf8(allocator: arena),
+        _json(keys, arena), allOptions.isEmpty ? nullptr : _json(allOptions, arena))));
+  }
+
+  void dropIndex(String name) {
+    using((arena) => _check(Native.instance.dropIndex(
+        database._db, this.name.toNativeUtf8(allocator: arena), name.toNativeUtf8(allocator: arena))));
+  }
+}
+
+/// A collection of the documents of the model [T].
+class TypedCollection<T> {
+  final Collection collection;
+  final DocumentModel<T> model;
+
+  TypedCollection._(this.collection, this.model);
+
+  Object? insertOne(T value) => collection.insertOne(model.toDocument(value));
+
+  List<Object?> insertMany(Iterable<T> values) => collection.insertMany(values.map(model.toDocument));
+
+  Iterable<T> find({Map<String, dynamic>? filter, Map<String, int>? sort, int? skip, int? limit}) =>
+      collection.find(filter: filter, sort: sort, skip: skip, limit: limit).documents.map(model.fromDocument);
+
+  T? findOne([Map<String, dynamic>? filter]) {
+    final document = collection.findOne(filter);
+    return document == null ? null : model.fromDocument(document);
+  }
+}
+
+/// The documents of a find or an aggregation.
+///
+/// The cursor is freed once it's read to its end, [close] frees it before.
+class Cursor {
+  static final _finalizer = NativeFinalizer(Native.instance.cursorFree.cast());
+
+  Pointer<PldbCursor> _handle;
+
+  Cursor._(this._handle) {
+    _finalizer.attach(this, _handle.cast(), detach: this);
+  }
+
+  /// The next document, null after the last one.
+  Map<String, dynamic>? next() {
+    if (_handle == nullptr) {
+      return null;
+    }
+    final document = using((arena) {
+      final out = arena<Pointer<Utf8>>();
+      _check(Native.instance.cursorNext(_handle, out));
+      return _take(out.value);
+    });
+    if (document == null) {
+      close();
+      return null;
+    }
+    return decodeExtJson(document) as Map<String, dynamic>;
+  }
+
+  /// The documents left, read as they are iterated.
+  Iterable<Map<String, dynamic>> get documents sync* {
+    for (var document = next(); document != null; document = next()) {
+      yield document;
+    }
+  }
+
+  List<Map<String, dynamic>> toList() => documents.toList();
+
+  void close() {
+    if (_handle != nullptr) {
+      _finalizer.detach(this);
+      Native.instance.cursorFree.asFunction<void Function(Pointer<PldbCursor>)>()(_handle);
+      _handle = nullptr;
+    }
+  }
+}
@@ -0,0 +1,90 @@
+import 'dart:convert';
+import 'dart:typed_data';
+
+/// The ObjectId of BSON, the default `_id` of a document.
+class ObjectId {
+  /// The 24 hexadecimal characters of the ObjectId.
+  final String hex;
+
+  ObjectId(String hex) : hex = hex.toLowerCase() {
+    if (!RegExp(r'^[0-9a-f]{24}$').hasMatch(this.hex)) {
+      throw FormatException('invalid ObjectId', hex);
+    }
+  }
+
+  @override
+  bool operator ==(Object other) => other is ObjectId && other.hex == hex;
+
+  @override
+  int get hashCode => hex.hashCode;
+
+  @override
+  String toString() => 'ObjectId("$hex")';
+}
+
+/// Encode a value as Extended JSON.
+String encodeExtJson(Object? value) => jsonEncode(_encode(value));
+
+/// Decode the relaxed Extended JSON written by PoloDB.
+Object? decodeExtJson(String text) => _decode(jsonDecode(text));
+
+Object? _encode(Object? value) {
+  if (value is ObjectId) {
+    return {'\$oid': value.hex};
+  }
+  if (value is DateTime) {
+    return {
+      '\$date': {'\$numberLong': value.millisecondsSinceEpoch.toString()}
+    };
+  }
+  if (value is Uint8List) {
+    return {
+      '\$binary': {'base64': base64Encode(value), 'subType': '00'}
+    };
+  }
+  if (value is double && !value.isFinite) {
+    final text = value.isNaN ? 'NaN' : (value > 0 ? 'Infinity' : '-Infinity');
+    return {'\$numberDouble': text};
+  }
+  if (value is Map) {
+    return value.map((key, item) => MapEntry(key as String, _encode(item)));
+  }
+  if (value is Iterable) {
+    return value.map(_encode).toList();
+  }
+  return value;
+}
+
+Object? _decode(Object? value) {
+  if (value is List) {
+    return value.map(_decode).toList();
+  }
+  if (value is! Map<String, dynamic>) {
+    return value;
+  }
+  if (value.length == 1) {
+    final wrapped = value.values.first;
+    switch (value.keys.first) {
+      case '\$oid':
+        return ObjectId(wrapped as String);
+      case '\$date':
+        return _decodeDate(wrapped);
+      case '\$numberLong':
+      case '\$numberInt':
+        return int.parse(wrapped as String);
+      case '\$numberDouble':
+        return double.parse(wrapped as String);
+      case '\$binary':
+        return base64Decode((wrapped as Map<String, dynamic>)['base64'] as String);
+    }
+  }
+  return value.map((key, item) => MapEntry(key, _decode(item)));
+}
+
+DateTime _decodeDate(Object? value) {
+  if (value is String) {
+    return DateTime.parse(value);
+  }
+  final millis = (value as Map<String, dynamic>)['\$numberLong'] as String;
+  return DateTime.fromMillisecondsSinceEpoch(int.parse(millis), isUtc: true);
+}
@@ -0,0 +1,33 @@
+import 'package:meta/meta_meta.dart';
+
+/// Generate the conversions of the annotated class to and from a document.
+///
+/// For a class `Country` of the library `country.dart`, holding `part 'country.g.dart';`,
+/// `polodb_generator` writes the functions `_$CountryToDocument` and
+/// `_$CountryFromDocument`, and the [DocumentModel] `_$CountryModel`. The fields are
+/// those of the parameters of the unnamed constructor.
+@Target({TargetKind.classType})
+class PoloModel {
+  const PoloModel();
+}
+
+/// The options of a field of a [PoloModel].
+@Target({TargetKind.field, TargetKind.getter, TargetKind.parameter})
+class PoloField {
+  /// The key of the field in the document, the name of the field by default.
+  final String? name;
+
+  /// Whether the field is left out of the document when it's null, such as an
+  /// `_id` assigned by the database.
+  final bool omitIfNull;
+
+  const PoloField({this.name, this.omitIfNull = false});
+}
+
+/// The conversions of the model [T] to and from a document.
+class DocumentModel<T> {
+  final Map<String, dynamic> Function(T value) toDocument;
+  final T Function(Map<String, dynamic> document) fromDocument;
+
+  const DocumentModel({required this.toDocument, required this.fromDocument});
+}
@@ -0,0 +1,104 @@
+import 'dart:ffi';
+import 'dart:io';
+
+import 'package:ffi/ffi.dart';
+
+/// `PldbDatabase` of `polodb.h`.
+final class PldbDatabase extends Opaque {}
+
+/// `PldbCursor` of `polodb.h`.
+final class PldbCursor extends Opaque {}
+
+typedef _OpenC = Int32 Function(Pointer<Utf8> path, Pointer<Pointer<PldbDatabase>> out);
+typedef _OpenDart = int Function(Pointer<Utf8> path, Pointer<Pointer<PldbDatabase>> out);
+typedef _NamesC = Int32 Function(Pointer<PldbDatabase> db, Pointer<Pointer<Utf8>> out);
+typedef _NamesDart = int Function(Pointer<PldbDatabase> db, Pointer<Pointer<Utf8>> out);
+typedef _NameC = Int32 Function(Pointer<PldbDatabase> db, Pointer<Utf8> name);
+typedef _NameDart = int Function(Pointer<PldbDatabase> db, Pointer<Utf8> name);
+typedef _InsertC = Int32 Function(
+    Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> doc, Pointer<Pointer<Utf8>> out);
+typedef _InsertDart = int Function(
+    Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> doc, Pointer<Pointer<Utf8>> out);
+typedef _FindC = Int32 Function(Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> filter,
+    Pointer<Utf8> options, Pointer<Pointer<PldbCursor>> out);
+typedef _FindDart = int Function(Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> filter,
+    Pointer<Utf8> options, Pointer<Pointer<PldbCursor>> out);
+typedef _AggregateC = Int32 Function(
+    Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> pipeline, Pointer<Pointer<PldbCursor>> out);
+typedef _AggregateDart = int Function(
+    Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> pipeline, Pointer<Pointer<PldbCursor>> out);
+typedef _NextC = Int32 Function(Pointer<PldbCursor> cursor, Pointer<Pointer<Utf8>> out);
+typedef _NextDart = int Function(Pointer<PldbCursor> cursor, Pointer<Pointer<Utf8>> out);
+typedef _CountC = Int32 Function(Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Uint64> out);
+typedef _CountDart = int Function(Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Uint64> out);
+typedef _UpdateC = Int32 Function(Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> filter,
+    Pointer<Utf8> update, Pointer<Uint64> matched, Pointer<Uint64> modified);
+typedef _UpdateDart = int Function(Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> filter,
+    Pointer<Utf8> update, Pointer<Uint64> matched, Pointer<Uint64> modified);
+typedef _DeleteC = Int32 Function(
+    Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> filter, Pointer<Uint64> deleted);
+typedef _DeleteDart = int Function(
+    Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> filter, Pointer<Uint64> deleted);
+typedef _IndexC = Int32 Function(
+    Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> keys, Pointer<Utf8> options);
+typedef _IndexDart = int Function(
+    Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> keys, Pointer<Utf8> options);
+typedef _DropIndexC = Int32 Function(Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> name);
+typedef _DropIndexDart = int Function(Pointer<PldbDatabase> db, Pointer<Utf8> col, Pointer<Utf8> name);
+
+/// The library of the C ABI: the path in the `POLODB_LIBRARY` environment variable,
+/// otherwise the library of the platform, linked statically on iOS.
+DynamicLibrary _openLibrary() {
+  final path = Platform.environment['POLODB_LIBRARY'];
+  if (path != null) {
+    return DynamicLibrary.open(path);
+  }
+  if (Platform.isIOS) {
+    return DynamicLibrary.process();
+  }
+  if (Platform.isMacOS) {
+    return DynamicLibrary.open('libpolodb_capi.dylib');
+  }
+  if (Platform.isWindows) {
+    return DynamicLibrary.open('polodb_capi.dll');
+  }
+  return DynamicLibrary.open('libpolodb_capi.so');
+}
+
+/// The functions of `polodb.h`, loaded once by each isolate.
+class Native {
+  static final Native instance = Native._(_openLibrary());
+
+  final DynamicLibrary _lib;
+
+  Native._(this._lib);
+
+  late final version = _lib.lookupFunction<Pointer<Utf8> Function(), Pointer<Utf8> Function()>('polodb_version');
+  late final lastError =
+      _lib.lookupFunction<Pointer<Utf8> Function(), Pointer<Utf8> Function()>('polodb_last_error');
+  late final freeString =
+      _lib.lookupFunction<Void Function(Pointer<Utf8>), void Function(Pointer<Utf8>)>('polodb_free_string');
+
+  late final open = _lib.lookupFunction<_OpenC, _OpenDart>('polodb_open');
+  late final openSnapshot = _lib.lookupFunction<_OpenC, _OpenDart>('polodb_open_snapshot');
+  late final close = _lib.lookupFunction<Void Function(Pointer<PldbDatabase>), void Function(Pointer<PldbDatabase>)>(
+      'polodb_close');
+  late final listCollectionNames = _lib.lookupFunction<_NamesC, _NamesDart>('polodb_list_collection_names');
+  late final createCollection = _lib.lookupFunction<_NameC, _NameDart>('polodb_create_collection');
+  late final dropCollection = _lib.lookupFunction<_NameC, _NameDart>('polodb_drop_collection');
+
+  late final insertOne = _lib.lookupFunction<_InsertC, _InsertDart>('polodb_insert_one');
+  late final insertMany = _lib.lookupFunction<_InsertC, _InsertDart>('polodb_insert_many');
+  late final find = _lib.lookupFunction<_FindC, _FindDart>('polodb_find');
+  late final findOne = _lib.lookupFunction<_InsertC, _InsertDart>('polodb_find_one');
+  late final aggregate = _lib.lookupFunction<_AggregateC, _AggregateDart>('polodb_aggregate');
+  late final cursorNext = _lib.lookupFunction<_NextC, _NextDart>('polodb_cursor_next');
+  late final cursorFree = _lib.lookup<NativeFunction<Void Function(Pointer<PldbCursor>)>>('polodb_cursor_free');
+  late final countDocuments = _lib.lookupFunction<_CountC, _CountDart>('polodb_count_documents');
+  late final updateOne = _lib.lookupFunction<_UpdateC, _UpdateDart>('polodb_update_one');
+  late final updateMany = _lib.lookupFunction<_UpdateC, _UpdateDart>('polodb_update_many');
+  late final deleteOne = _lib.lookupFunction<_DeleteC, _DeleteDart>('polodb_delete_one');
+  late final deleteMany = _lib.lookupFunction<_DeleteC, _DeleteDart>('polodb_delete_many');
+  late final createIndex = _lib.lookupFunction<_IndexC, _IndexDart>('polodb_create_index');
+  late final dropIndex = _lib.lookupFunction<_DropIndexC, _DropIndexDart>('polodb_drop_index');
+}
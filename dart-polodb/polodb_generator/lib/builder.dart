@@ -0,0 +1,7 @@
+import 'package:build/build.dart';
+import 'package:source_gen/source_gen.dart';
+
+import 'src/model_generator.dart';
+
+/// The builder of the `.g.dart` parts, declared in `build.yaml`.
+Builder polodbBuilder(BuilderOptions options) => SharedPartBuilder([ModelGenerator()], 'polodb');
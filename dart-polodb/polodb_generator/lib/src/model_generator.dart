@@ -0,0 +1,137 @@
+import 'package:analyzer/dart/element/element.dart';
+import 'package:analyzer/dart/element/nullability_suffix.dart';
+import 'package:analyzer/dart/element/type.dart';
+import 'package:build/build.dart';
+import 'package:polodb/annotations.dart';
+import 'package:source_gen/source_gen.dart';
+
+const _modelChecker = TypeChecker.fromRuntime(PoloModel);
+const _fieldChecker = TypeChecker.fromRuntime(PoloField);
+
+/// Generate the conversions of a class annotated with [PoloModel].
+///
+/// The values of the fields are kept as they are, except the models, their lists
+/// and the enums, converted by their name.
+class ModelGenerator extends GeneratorForAnnotation<PoloModel> {
+  @override
+  String generateForAnnotatedElement(Element element, ConstantReader annotation, BuildStep buildStep) {
+    if (element is! ClassElement) {
+      throw InvalidGenerationSourceError('@PoloModel only annotates classes', element: element);
+    }
+    final constructor = element.unnamedConstructor;
+    if (constructor == null) {
+      throw InvalidGenerationSourceError('@PoloModel needs an unnamed constructor', element: element);
+    }
+    final name = element.name;
+    final fields = constructor.parameters.map((param) => _Field.of(element, param)).toList();
+
+    final toDocument = StringBuffer()
+      ..writeln('Map<String, dynamic> _\$${name}ToDocument($name instance) => <String, dynamic>{');
+    for (final field in fields) {
+      final value = _toDocument('instance.${field.name}', field.type);
+      final condition = field.omitIfNull ? 'if (instance.${field.name} != null) ' : '';
+      toDocument.writeln("  $condition'${field.key}': $value,");
+    }
+    toDocument.writeln('};');
+
+    final fromDocument = StringBuffer()..writeln('$name _\$${name}FromDocument(Map<String, dynamic> document) => $name(');
+    for (final field in fields) {
+      final value = _fromDocument("document['${field.key}']", field.type);
+      fromDocument.writeln(field.named ? '  ${field.name}: $value,' : '  $value,');
+    }
+    fromDocument.writeln(');');
+
+    return '''
+$toDocument
+$fromDocument
+const _\$${name}Model = DocumentModel<$name>(
+  toDocument: _\$${name}ToDocument,
+  fromDocument: _\$${name}FromDocument,
+);
+''';
+  }
+}
+
+class _Field {
+  final String name;
+  final String key;
+  final DartType type;
+  final bool named;
+  final bool omitIfNull;
+
+  _Field(this.name, this.key, this.type, this.named, this.omitIfNull);
+
+  /// The field of the parameter [param] of the constructor, annotated on the
+  /// parameter or on the field.
+  factory _Field.of(ClassElement model, ParameterElement param) {
+    final field = model.getField(param.name);
+    if (field == null || field.getter == null) {
+      throw InvalidGenerationSourceError('the parameter ${param.name} has no field of the same name',
+          element: param);
+    }
+    final annotation = _fieldChecker.firstAnnotationOfExact(param) ?? _fieldChecker.firstAnnotationOfExact(field);
+    final reader = annotation == null ? null : ConstantReader(annotation);
+    final key = reader?.peek('name')?.stringValue ?? param.name;
+    final omitIfNull = reader?.read('omitIfNull').boolValue ?? false;
+    return _Field(param.name, key, param.type, param.isNamed, omitIfNull);
+  }
+}
+
+bool _isNullable(DartType type) => type.nullabilitySuffix == NullabilitySuffix.question;
+
+bool _isModel(DartType type) => type.element != null && _modelChecker.hasAnnotationOfExact(type.element!);
+
+bool _isEnum(DartType type) => type.element is EnumElement;
+
+String _typeName(DartType type) => type.getDisplayString(withNullability: true);
+
+String _elementName(DartType type) => type.element!.name!;
+
+String _toDocument(String value, DartType type) {
+  final nullable = _isNullable(type);
+  if (_isModel(type)) {
+    final convert = '_\$${_elementName(type)}ToDocument';
+    return nullable ? '($value == null ? null : $convert($value!))' : '$convert($value)';
+  }
+  if (_isEnum(type)) {
+    return nullable ? '$value?.name' : '$value.name';
+  }
+  if (type.isDartCoreList) {
+    final item = (type as InterfaceType).typeArguments.first;
+    final convert = _toDocument('item', item);
+    if (convert == 'item') {
+      return value;
+    }
+    return '$value${nullable ? '?' : ''}.map((item) => $convert).toList()';
+  }
+  return value;
+}
+
+String _fromDocument(String value, DartType type) {
+  final nullable = _isNullable(type);
+  final q = nullable ? '?' : '';
+  if (_isModel(type)) {
+    final convert = '_\$${_elementName(type)}FromDocument($value as Map<String, dynamic>)';
+    return nullable ? '($value == null ? null : $convert)' : convert;
+  }
+  if (_isEnum(type)) {
+    final convert = '${_elementName(type)}.values.byName($value as String)';
+    return nullable ? '($value == null ? null : $convert)' : convert;
+  }
+  if (type.isDartCoreDouble) {
+    // a double such as 1.0 may be read as an int
+    return '($value as num$q)$q.toDouble()';
+  }
+  if (type.isDartCoreList) {
+    final item = (type as InterfaceType).typeArguments.first;
+    return '($value as List<dynamic>$q)$q.map((item) => ${_fromDocument('item', item)}).toList()';
+  }
+  if (type.isDartCoreMap) {
+    final args = (type as InterfaceType).typeArguments;
+    return '($value as Map<String, dynamic>$q)$q.cast<String, ${_typeName(args.last)}>()';
+  }
+  if (type is DynamicType) {
+    return value;
+  }
+  return '$value as ${_typeName(type)}';
+}
@@ -0,0 +1,113 @@
+import 'dart:io';
+
+import 'package:polodb/polodb.dart';
+import 'package:test/test.dart';
+
+class Country {
+  final String code;
+  final int population;
+
+  Country(this.code, this.population);
+}
+
+const countryModel = DocumentModel<Country>(
+  toDocument: _countryToDocument,
+  fromDocument: _countryFromDocument,
+);
+
+Map<String, dynamic> _countryToDocument(Country country) => {'code': country.code, 'population': country.population};
+
+Country _countryFromDocument(Map<String, dynamic> document) =>
+    Country(document['code'] as String, document['population'] as int);
+
+/// The library is the one of `POLODB_LIBRARY`, such as `target/debug/libpolodb_capi.so`.
+void main() {
+  late Directory dir;
+  late Database db;
+
+  setUp(() {
+    dir = Directory.systemTemp.createTempSync('polodb-dart');
+    db = Database.open('${dir.path}/test.db');
+  });
+
+  tearDown(() {
+    db.close();
+    dir.deleteSync(recursive: true);
+  });
+
+  test('insert and find', () {
+    final col = db.collection('countries');
+    final id = col.insertOne({'code': 'FR', 'population': 68000000});
+    expect(id, isA<ObjectId>());
+    expect(col.insertMany([
+      {'_id': 2, 'code': 'DE'},
+      {'_id': 3, 'code': 'JP', 'founded': DateTime.utc(660, 2, 11)},
+    ]), [2, 3]);
+    expect(col.countDocuments(), 3);
+
+    final found = col.find(sort: {'code': -1}, limit: 2).toList();
+    expect(found.map((doc) => doc['code']), ['JP', 'FR']);
+    expect(found.first['founded'], DateTime.utc(660, 2, 11));
+    expect(col.findOne({'_id': id})!['population'], 68000000);
+    expect(col.findOne({'code': 'IT'}), isNull);
+    expect(db.listCollectionNames(), ['countries']);
+  });
+
+  test('update, delete and aggregate', () {
+    final col = db.collection('countries');
+    col.insertMany([
+      {'code': 'FR'},
+      {'code': 'DE'},
+      {'code': 'JP'},
+    ]);
+    final result = col.updateMany({
+      'code': {'\$in': ['FR', 'DE']}
+    }, {
+      '\$set': {'europe': true}
+    });
+    expect([result.matchedCount, result.modifiedCount], [2, 2]);
+    expect(col.aggregate([
+      {'\$match': {'europe': true}},
+      {'\$count': 'count'},
+    ]).toList(), [
+      {'count': 2}
+    ]);
+    expect(col.deleteMany({'europe': true}), 2);
+    expect(col.countDocuments(), 1);
+  });
+
+  test('errors', () {
+    final col = db.collection('countries');
+    col.createIndex({'code': 1}, name: 'code_unique', options: {'unique': true});
+    col.insertOne({'code': 'FR'});
+    expect(
+      () => col.insertOne({'code': 'FR'}),
+      throwsA(isA<PoloDbException>()
+          .having((err) => err.code, 'code', 6)
+          .having((err) => err.message, 'message', contains('code_unique'))),
+    );
+    col.dropIndex('code_unique');
+    col.insertOne({'code': 'FR'});
+    expect(col.countDocuments(), 2);
+  });
+
+  test('typed collection', () {
+    final col = db.collection('countries').typed(countryModel);
+    col.insertMany([Country('FR', 68000000), Country('DE', 84000000)]);
+    expect(col.find(sort: {'population': -1}).map((country) => country.code), ['DE', 'FR']);
+    expect(col.findOne({'code': 'FR'})!.population, 68000000);
+  });
+
+  test('async database', () async {
+    final asyncDb = AsyncDatabase(db);
+    final col = asyncDb.collection('countries');
+    await col.insertMany([
+      {'code': 'FR'},
+      {'code': 'DE'},
+    ]);
+    expect(await col.countDocuments(), 2);
+    final found = await col.find(filter: {'code': 'DE'});
+    expect(found.single['code'], 'DE');
+    expect(await asyncDb.listCollectionNames(), ['countries']);
+  });
+}
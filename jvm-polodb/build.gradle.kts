@@ -0,0 +1,74 @@
+plugins {
+    `java-library`
+    `maven-publish`
+}
+
+group = "org.polodb"
+version = "5.1.4"
+
+java {
+    sourceCompatibility = JavaVersion.VERSION_1_8
+    targetCompatibility = JavaVersion.VERSION_1_8
+    withSourcesJar()
+    withJavadocJar()
+}
+
+repositories {
+    mavenCentral()
+}
+
+dependencies {
+    api("org.mongodb:bson:4.11.1")
+    testImplementation("org.junit.jupiter:junit-jupiter:5.10.2")
+}
+
+// The JNI library built by `cargo build --release -p polodb_jni`, bundled in the jar
+// under native/<os>-<arch>, the platform of the build by default.
+val nativePlatform = providers.gradleProperty("polodb.platform").orElse(
+    run {
+        val os = System.getProperty("os.name").lowercase()
+        val arch = when (val arch = System.getProperty("os.arch")) {
+            "amd64", "x86_64" -> "x86_64"
+            "arm64", "aarch64" -> "aarch64"
+            else -> arch
+        }
+        when {
+            os.contains("win") -> "windows"
+            os.contains("mac") -> "macos"
+            else -> "linux"
+        } + "-" + arch
+    }
+)
+val nativeDir = providers.gradleProperty("polodb.nativeDir").orElse("../target/release")
+
+tasks.processResources {
+    from(nativeDir) {
+        include("libpolodb_jni.so", "libpolodb_jni.dylib", "polodb_jni.dll")
+        into("native/${nativePlatform.get()}")
+    }
+}
+
+tasks.test {
+    useJUnitPlatform()
+    providers.systemProperty("polodb.library.path").orNull?.let { systemProperty("polodb.library.path", it) }
+}
+
+publishing {
+    publications {
+        create<MavenPublication>("maven") {
+            artifactId = "polodb"
+            from(components["java"])
+            pom {
+                name.set("PoloDB")
+                description.set("The JVM bindings of PoloDB, an embedded document database")
+                url.set("https://github.com/PoloDB/PoloDB")
+                licenses {
+                    license {
+                        name.set("Apache-2.0")
+                        url.set("http://www.apache.org/licenses/LICENSE-2.0")
+                    }
+                }
+            }
+        }
+    }
+}
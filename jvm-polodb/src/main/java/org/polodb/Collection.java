@@ -0,0 +1,122 @@
+/*
+ * Copyright 2024 Vincent Chan
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *	http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package org.polodb;
+
+import java.util.List;
+import org.bson.BsonValue;
+import org.bson.Document;
+
+/**
+ * A collection of documents of a {@link Database}.
+ *
+ * <p>The filters, the updates and the stages of the pipelines are those of the
+ * query language of MongoDB supported by PoloDB.
+ */
+public final class Collection {
+
+    private final Database database;
+    private final String name;
+    private final byte[] nameBytes;
+
+    Collection(Database database, String name) {
+        this.database = database;
+        this.name = name;
+        this.nameBytes = Json.utf8(name);
+    }
+
+    public String getName() {
+        return name;
+    }
+
+    /** Insert the document, returning its {@code _id}. */
+    public BsonValue insertOne(Document document) {
+        return Json.decodeValue(NativeLib.insertOne(database.handle(), nameBytes, Json.encode(document)));
+    }
+
+    /** Insert the documents, returning their {@code _id} in the order of the documents. */
+    public List<BsonValue> insertMany(List<Document> documents) {
+        return Json.decodeValues(NativeLib.insertMany(database.handle(), nameBytes, Json.encodeArray(documents)));
+    }
+
+    /** Find all the documents. */
+    public Cursor find() {
+        return find(null, null);
+    }
+
+    /** Find the documents matching {@code filter}. */
+    public Cursor find(Document filter) {
+        return find(filter, null);
+    }
+
+    /** Find the documents matching {@code filter}, all the documents if it's null. */
+    public Cursor find(Document filter, FindOptions options) {
+        Document optionsDocument = options == null ? null : options.toDocument();
+        long cursor = NativeLib.find(database.handle(), nameBytes, Json.encode(filter), Json.encode(optionsDocument));
+        return new Cursor(cursor);
+    }
+
+    /** Find the first document matching {@code filter}, null if there is none. */
+    public Document findOne(Document filter) {
+        return Json.decode(NativeLib.findOne(database.handle(), nameBytes, Json.encode(filter)));
+    }
+
+    /** Run the aggregation {@code pipeline}. */
+    public Cursor aggregate(List<Document> pipeline) {
+        return new Cursor(NativeLib.aggregate(database.handle(), nameBytes, Json.encodeArray(pipeline)));
+    }
+
+    public long countDocuments() {
+        return NativeLib.countDocuments(database.handle(), nameBytes);
+    }
+
+    public UpdateResult updateOne(Document filter, Document update) {
+        return new UpdateResult(NativeLib.updateOne(database.handle(), nameBytes, Json.encode(filter), Json.encode(update)));
+    }
+
+    public UpdateResult updateMany(Document filter, Document update) {
+        return new UpdateResult(NativeLib.updateMany(database.handle(), nameBytes, Json.encode(filter), Json.encode(update)));
+    }
+
+    /** Delete the first document matching {@code filter}, returning the count of the deleted documents. */
+    public long deleteOne(Document filter) {
+        return NativeLib.deleteOne(database.handle(), nameBytes, Json.encode(filter));
+    }
+
+    /** Delete the documents matching {@code filter}, returning the count of the deleted documents. */
+    public long deleteMany(Document filter) {
+        return NativeLib.deleteMany(database.handle(), nameBytes, Json.encode(filter));
+    }
+
+    /** Create the index of {@code keys}, such as {@code {"code": 1}}. */
+    public void createIndex(Document keys) {
+        createIndex(keys, null);
+    }
+
+    /**
+     * Create the index of {@code keys}, such as {@code {"code": 1}}. The {@code options}
+     * are those of the indexes of MongoDB supported by PoloDB, such as
+     * {@code {"name": "code_1", "unique": true}}.
+     */
+    public void createIndex(Document keys, Document options) {
+        NativeLib.createIndex(database.handle(), nameBytes, Json.encode(keys), Json.encode(options));
+    }
+
+    public void dropIndex(String name) {
+        NativeLib.dropIndex(database.handle(), nameBytes, Json.utf8(name));
+    }
+
+}
@@ -0,0 +1,76 @@
+/*
+ * Copyright 2024 Vincent Chan
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *	http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package org.polodb;
+
+import java.util.ArrayList;
+import java.util.Iterator;
+import java.util.List;
+import java.util.NoSuchElementException;
+import org.bson.Document;
+
+/**
+ * The documents of a find or an aggregation, read as they are iterated.
+ *
+ * <p>A cursor is used by one thread at a time. It's freed once it's read to its end,
+ * {@link #close()} frees it before.
+ */
+public final class Cursor implements Iterator<Document>, AutoCloseable {
+
+    private long handle;
+    private Document next;
+
+    Cursor(long handle) {
+        this.handle = handle;
+    }
+
+    @Override
+    public boolean hasNext() {
+        if (next == null && handle != 0) {
+            next = Json.decode(NativeLib.cursorNext(handle));
+            if (next == null) {
+                close();
+            }
+        }
+        return next != null;
+    }
+
+    @Override
+    public Document next() {
+        if (!hasNext()) {
+            throw new NoSuchElementException();
+        }
+        Document document = next;
+        next = null;
+        return document;
+    }
+
+    /** The documents left. */
+    public List<Document> toList() {
+        List<Document> documents = new ArrayList<>();
+        forEachRemaining(documents::add);
+        return documents;
+    }
+
+    @Override
+    public void close() {
+        if (handle != 0) {
+            NativeLib.cursorFree(handle);
+            handle = 0;
+        }
+    }
+
+}
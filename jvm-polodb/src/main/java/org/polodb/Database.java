@@ -0,0 +1,89 @@
+/*
+ * Copyright 2024 Vincent Chan
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *	http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package org.polodb;
+
+import java.nio.charset.StandardCharsets;
+import java.util.List;
+
+/**
+ * A PoloDB database, embedded in the process.
+ *
+ * <p>A database can be used by several threads at once. It must be closed once the
+ * calls and the cursors opened on it are complete.
+ *
+ * <pre>{@code
+ * try (Database db = Database.open("/tmp/test.db")) {
+ *     Collection books = db.getCollection("books");
+ *     books.insertOne(new Document("title", "The Three-Body Problem"));
+ * }
+ * }</pre>
+ */
+public final class Database implements AutoCloseable {
+
+    private long handle;
+
+    private Database(long handle) {
+        this.handle = handle;
+    }
+
+    /** Open the database at {@code path}, creating it if it doesn't exist. */
+    public static Database open(String path) {
+        return new Database(NativeLib.open(Json.utf8(path)));
+    }
+
+    /** Open the snapshot file at {@code path}, read-only. */
+    public static Database openSnapshot(String path) {
+        return new Database(NativeLib.openSnapshot(Json.utf8(path)));
+    }
+
+    /** The version of PoloDB. */
+    public static String version() {
+        return new String(NativeLib.version(), StandardCharsets.UTF_8);
+    }
+
+    synchronized long handle() {
+        if (handle == 0) {
+            throw new IllegalStateException("the database is closed");
+        }
+        return handle;
+    }
+
+    public Collection getCollection(String name) {
+        return new Collection(this, name);
+    }
+
+    public List<String> listCollectionNames() {
+        return Json.decodeStrings(NativeLib.listCollectionNames(handle()));
+    }
+
+    public void createCollection(String name) {
+        NativeLib.createCollection(handle(), Json.utf8(name));
+    }
+
+    public void dropCollection(String name) {
+        NativeLib.dropCollection(handle(), Json.utf8(name));
+    }
+
+    @Override
+    public synchronized void close() {
+        if (handle != 0) {
+            NativeLib.close(handle);
+            handle = 0;
+        }
+    }
+
+}
@@ -0,0 +1,61 @@
+/*
+ * Copyright 2024 Vincent Chan
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *	http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package org.polodb;
+
+import org.bson.Document;
+
+/**
+ * The options of {@link Collection#find(Document, FindOptions)}.
+ */
+public final class FindOptions {
+
+    private Document sort;
+    private Long skip;
+    private Long limit;
+
+    /** Sort the documents by the fields of {@code sort}, such as {@code {"code": 1}}. */
+    public FindOptions sort(Document sort) {
+        this.sort = sort;
+        return this;
+    }
+
+    public FindOptions skip(long skip) {
+        this.skip = skip;
+        return this;
+    }
+
+    public FindOptions limit(long limit) {
+        this.limit = limit;
+        return this;
+    }
+
+    /** The options of the C ABI, null if there is none. */
+    Document toDocument() {
+        Document options = new Document();
+        if (sort != null) {
+            options.append("sort", sort);
+        }
+        if (skip != null) {
+            options.append("skip", skip);
+        }
+        if (limit != null) {
+            options.append("limit", limit);
+        }
+        return options.isEmpty() ? null : options;
+    }
+
+}
@@ -0,0 +1,87 @@
+/*
+ * Copyright 2024 Vincent Chan
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *	http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package org.polodb;
+
+import java.nio.charset.StandardCharsets;
+import java.util.ArrayList;
+import java.util.List;
+import org.bson.BsonArray;
+import org.bson.BsonDocument;
+import org.bson.BsonString;
+import org.bson.BsonValue;
+import org.bson.Document;
+import org.bson.json.JsonMode;
+import org.bson.json.JsonWriterSettings;
+
+/**
+ * The conversions of the documents to and from the Extended JSON of the JNI library.
+ * The documents are written in the canonical form, which keeps their types.
+ */
+final class Json {
+
+    private static final JsonWriterSettings SETTINGS = JsonWriterSettings.builder()
+            .outputMode(JsonMode.EXTENDED)
+            .build();
+
+    private Json() {
+    }
+
+    static byte[] utf8(String value) {
+        return value.getBytes(StandardCharsets.UTF_8);
+    }
+
+    /** The JSON of the document, null if it's null. */
+    static byte[] encode(Document document) {
+        return document == null ? null : utf8(document.toJson(SETTINGS));
+    }
+
+    static byte[] encodeArray(List<Document> documents) {
+        StringBuilder json = new StringBuilder("[");
+        for (Document document : documents) {
+            if (json.length() > 1) {
+                json.append(',');
+            }
+            json.append(document.toJson(SETTINGS));
+        }
+        return utf8(json.append(']').toString());
+    }
+
+    /** The document of the JSON, null if it's null. */
+    static Document decode(byte[] json) {
+        return json == null ? null : Document.parse(new String(json, StandardCharsets.UTF_8));
+    }
+
+    /** The value of the JSON, which isn't a document. */
+    static BsonValue decodeValue(byte[] json) {
+        String wrapped = "{\"value\": " + new String(json, StandardCharsets.UTF_8) + "}";
+        return BsonDocument.parse(wrapped).get("value");
+    }
+
+    static List<BsonValue> decodeValues(byte[] json) {
+        BsonArray array = decodeValue(json).asArray();
+        return new ArrayList<>(array.getValues());
+    }
+
+    static List<String> decodeStrings(byte[] json) {
+        List<String> strings = new ArrayList<>();
+        for (BsonValue value : decodeValue(json).asArray()) {
+            strings.add(((BsonString) value).getValue());
+        }
+        return strings;
+    }
+
+}
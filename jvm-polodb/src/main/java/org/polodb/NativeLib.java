@@ -0,0 +1,77 @@
+/*
+ * Copyright 2024 Vincent Chan
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *	http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package org.polodb;
+
+/**
+ * The native methods of the JNI library {@code polodb_jni}. The strings are UTF-8
+ * byte arrays, the documents Extended JSON, the handles the addresses of the
+ * native objects.
+ */
+final class NativeLib {
+
+    static {
+        NativeLoader.load();
+    }
+
+    private NativeLib() {
+    }
+
+    static native byte[] version();
+
+    static native long open(byte[] path);
+
+    static native long openSnapshot(byte[] path);
+
+    static native void close(long db);
+
+    static native byte[] listCollectionNames(long db);
+
+    static native void createCollection(long db, byte[] name);
+
+    static native void dropCollection(long db, byte[] name);
+
+    static native byte[] insertOne(long db, byte[] col, byte[] doc);
+
+    static native byte[] insertMany(long db, byte[] col, byte[] docs);
+
+    static native long find(long db, byte[] col, byte[] filter, byte[] options);
+
+    static native byte[] findOne(long db, byte[] col, byte[] filter);
+
+    static native long aggregate(long db, byte[] col, byte[] pipeline);
+
+    static native byte[] cursorNext(long cursor);
+
+    static native void cursorFree(long cursor);
+
+    static native long countDocuments(long db, byte[] col);
+
+    /** Returns the matched and the modified counts. */
+    static native long[] updateOne(long db, byte[] col, byte[] filter, byte[] update);
+
+    /** Returns the matched and the modified counts. */
+    static native long[] updateMany(long db, byte[] col, byte[] filter, byte[] update);
+
+    static native long deleteOne(long db, byte[] col, byte[] filter);
+
+    static native long deleteMany(long db, byte[] col, byte[] filter);
+
+    static native void createIndex(long db, byte[] col, byte[] keys, byte[] options);
+
+    static native void dropIndex(long db, byte[] col, byte[] name);
+
+}
@@ -0,0 +1,80 @@
+/*
+ * Copyright 2024 Vincent Chan
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *	http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package org.polodb;
+
+import java.io.IOException;
+import java.io.InputStream;
+import java.io.UncheckedIOException;
+import java.nio.file.Files;
+import java.nio.file.Path;
+import java.nio.file.StandardCopyOption;
+import java.util.Locale;
+
+/**
+ * Loads the JNI library: the file of the {@code polodb.library.path} system property,
+ * otherwise the library of the platform bundled in the jar, otherwise the library on
+ * {@code java.library.path}, such as the {@code jniLibs} of an Android app.
+ */
+final class NativeLoader {
+
+    private static final String LIBRARY_NAME = "polodb_jni";
+
+    private NativeLoader() {
+    }
+
+    static void load() {
+        String path = System.getProperty("polodb.library.path");
+        if (path != null) {
+            System.load(path);
+            return;
+        }
+        String fileName = System.mapLibraryName(LIBRARY_NAME);
+        String resource = "/native/" + platform() + "/" + fileName;
+        try (InputStream in = NativeLoader.class.getResourceAsStream(resource)) {
+            if (in == null) {
+                System.loadLibrary(LIBRARY_NAME);
+                return;
+            }
+            // a library can't be loaded from a jar, it's extracted to a temporary file
+            Path file = Files.createTempFile(LIBRARY_NAME, fileName);
+            file.toFile().deleteOnExit();
+            Files.copy(in, file, StandardCopyOption.REPLACE_EXISTING);
+            System.load(file.toAbsolutePath().toString());
+        } catch (IOException e) {
+            throw new UncheckedIOException("failed to extract " + resource, e);
+        }
+    }
+
+    /** The platform of the directory of the bundled library, such as {@code linux-x86_64}. */
+    static String platform() {
+        String os = System.getProperty("os.name").toLowerCase(Locale.ROOT);
+        String arch = System.getProperty("os.arch");
+        if (arch.equals("amd64")) {
+            arch = "x86_64";
+        } else if (arch.equals("arm64")) {
+            arch = "aarch64";
+        }
+        if (os.contains("win")) {
+            return "windows-" + arch;
+        }
+        if (os.contains("mac")) {
+            return "macos-" + arch;
+        }
+        return "linux-" + arch;
+    }
+
+}
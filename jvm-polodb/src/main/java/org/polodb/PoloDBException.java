@@ -0,0 +1,48 @@
+/*
+ * Copyright 2024 Vincent Chan
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *	http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package org.polodb;
+
+import java.nio.charset.StandardCharsets;
+
+/**
+ * An error returned by PoloDB.
+ */
+public class PoloDBException extends RuntimeException {
+
+    private static final long serialVersionUID = 1L;
+
+    private final int code;
+
+    public PoloDBException(int code, String message) {
+        super(message);
+        this.code = code;
+    }
+
+    /** Called by the JNI library, the message is UTF-8. */
+    PoloDBException(int code, byte[] message) {
+        this(code, new String(message, StandardCharsets.UTF_8));
+    }
+
+    /**
+     * The code of the error, such as {@code 6} for a duplicate key, listed in
+     * {@code polodb.h} of the C ABI.
+     */
+    public int getCode() {
+        return code;
+    }
+
+}
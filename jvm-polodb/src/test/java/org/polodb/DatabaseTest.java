@@ -0,0 +1,111 @@
+/*
+ * Copyright 2024 Vincent Chan
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *	http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+package org.polodb;
+
+import static org.junit.jupiter.api.Assertions.assertEquals;
+import static org.junit.jupiter.api.Assertions.assertNull;
+import static org.junit.jupiter.api.Assertions.assertThrows;
+import static org.junit.jupiter.api.Assertions.assertTrue;
+
+import java.nio.file.Files;
+import java.nio.file.Path;
+import java.util.Arrays;
+import java.util.Collections;
+import java.util.Date;
+import java.util.List;
+import java.util.stream.Collectors;
+import org.bson.BsonInt32;
+import org.bson.BsonValue;
+import org.bson.Document;
+import org.bson.types.ObjectId;
+import org.junit.jupiter.api.AfterEach;
+import org.junit.jupiter.api.BeforeEach;
+import org.junit.jupiter.api.Test;
+
+class DatabaseTest {
+
+    private Database db;
+
+    @BeforeEach
+    void open() throws Exception {
+        Path dir = Files.createTempDirectory("polodb-jvm");
+        db = Database.open(dir.resolve("test.db").toString());
+    }
+
+    @AfterEach
+    void close() {
+        db.close();
+    }
+
+    @Test
+    void insertAndFind() {
+        Collection countries = db.getCollection("countries");
+        Date founded = new Date(-41_747_000_000_000L);
+        BsonValue id = countries.insertOne(new Document("code", "FR").append("population", 68_000_000L));
+        assertTrue(id.isObjectId());
+        List<BsonValue> ids = countries.insertMany(Arrays.asList(
+                new Document("_id", 2).append("code", "DE"),
+                new Document("_id", 3).append("code", "JP").append("founded", founded)));
+        assertEquals(Arrays.asList(new BsonInt32(2), new BsonInt32(3)), ids);
+        assertEquals(3, countries.countDocuments());
+
+        List<Document> found = countries.find(null, new FindOptions().sort(new Document("code", -1)).limit(2)).toList();
+        assertEquals(Arrays.asList("JP", "FR"), found.stream().map(doc -> doc.getString("code")).collect(Collectors.toList()));
+        assertEquals(founded, found.get(0).getDate("founded"));
+        Document france = countries.findOne(new Document("_id", id.asObjectId().getValue()));
+        assertEquals(68_000_000, france.get("population", Number.class).longValue());
+        assertTrue(france.get("_id") instanceof ObjectId);
+        assertNull(countries.findOne(new Document("code", "IT")));
+        assertEquals(Collections.singletonList("countries"), db.listCollectionNames());
+    }
+
+    @Test
+    void updateDeleteAndAggregate() {
+        Collection countries = db.getCollection("countries");
+        countries.insertMany(Arrays.asList(new Document("code", "FR"), new Document("code", "DE"), new Document("code", "JP")));
+        UpdateResult result = countries.updateMany(
+                new Document("code", new Document("$in", Arrays.asList("FR", "DE"))),
+                new Document("$set", new Document("europe", true)));
+        assertEquals(2, result.getMatchedCount());
+        assertEquals(2, result.getModifiedCount());
+
+        List<Document> counts = countries.aggregate(Arrays.asList(
+                new Document("$match", new Document("europe", true)),
+                new Document("$count", "count"))).toList();
+        assertEquals(Collections.singletonList(new Document("count", 2)), counts);
+        assertEquals(2, countries.deleteMany(new Document("europe", true)));
+        assertEquals(1, countries.countDocuments());
+    }
+
+    @Test
+    void errors() {
+        Collection countries = db.getCollection("countries");
+        countries.createIndex(new Document("code", 1), new Document("name", "code_unique").append("unique", true));
+        countries.insertOne(new Document("code", "FR"));
+        PoloDBException err = assertThrows(PoloDBException.class, () -> countries.insertOne(new Document("code", "FR")));
+        assertEquals(6, err.getCode());
+        assertTrue(err.getMessage().contains("code_unique"));
+
+        countries.dropIndex("code_unique");
+        countries.insertOne(new Document("code", "FR"));
+        assertEquals(2, countries.countDocuments());
+
+        db.close();
+        assertThrows(IllegalStateException.class, countries::countDocuments);
+    }
+
+}
@@ -0,0 +1,43 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::fs;
+use std::path::Path;
+use anyhow::Result;
+use polodb_core::Database;
+
+/// Compact the database and report the size of the data directory
+/// before and after.
+pub(crate) fn compact(db: &Database, path: &Path) -> Result<()> {
+    let before = dir_size(path)?;
+    db.compact()?;
+    let after = dir_size(path)?;
+    println!("compacted: {} bytes -> {} bytes", before, after);
+    Ok(())
+}
+
+fn dir_size(path: &Path) -> Result<u64> {
+    let mut size: u64 = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
@@ -0,0 +1,76 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use anyhow::Result;
+use bson::Document;
+use polodb_core::{CollectionT, Database, IndexModel};
+use crate::commands::{document_to_json, CollectionMetadata, DATA_FILE_SUFFIX, METADATA_FILE_SUFFIX};
+
+/// Write every collection of the database into `out_dir`.
+///
+/// For each collection, the documents are written into `<name>.jsonl`,
+/// and the index definitions into `<name>.metadata.json`.
+pub(crate) fn dump(db: &Database, out_dir: &Path) -> Result<()> {
+    fs::create_dir_all(out_dir)?;
+
+    for name in db.list_collection_names()? {
+        let collection = db.collection::<Document>(&name);
+
+        let metadata = dump_metadata(&collection)?;
+        let metadata_file = File::create(out_dir.join(format!("{}{}", name, METADATA_FILE_SUFFIX)))?;
+        serde_json::to_writer_pretty(metadata_file, &metadata)?;
+
+        let data_file = File::create(out_dir.join(format!("{}{}", name, DATA_FILE_SUFFIX)))?;
+        let mut writer = BufWriter::new(data_file);
+        let mut count: u64 = 0;
+        for doc in collection.find(Document::new()).run()? {
+            let value = document_to_json(doc?, false);
+            serde_json::to_writer(&mut writer, &value)?;
+            writer.write_all(b"\n")?;
+            count += 1;
+        }
+        writer.flush()?;
+
+        println!("dumped {} documents from {}", count, name);
+    }
+
+    Ok(())
+}
+
+fn dump_metadata(collection: &impl CollectionT<Document>) -> Result<CollectionMetadata> {
+    let mut metadata = CollectionMetadata::default();
+
+    for index_name in collection.list_index_names()? {
+        let info = match collection.describe_index(&index_name)? {
+            Some(info) => info,
+            None => continue,
+        };
+        let mut keys = Document::new();
+        for (key, order) in info.keys.iter() {
+            keys.insert(key.clone(), *order as i32);
+        }
+        let mut options = info.options.unwrap_or_default();
+        options.name = Some(index_name);
+        metadata.indexes.push(IndexModel {
+            keys,
+            options: Some(options),
+        });
+    }
+
+    Ok(metadata)
+}
@@ -0,0 +1,126 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! Offline maintenance commands operating directly on a database path,
+//! without starting the server.
+
+mod dump;
+mod restore;
+mod stats;
+mod compact;
+mod verify;
+mod query;
+mod shell;
+
+use std::convert::TryFrom;
+use anyhow::{anyhow, Result};
+use bson::{Bson, Document};
+use serde::{Deserialize, Serialize};
+use polodb_core::IndexModel;
+
+pub(crate) use dump::dump;
+pub(crate) use restore::restore;
+pub(crate) use stats::stats;
+pub(crate) use compact::compact;
+pub(crate) use verify::verify;
+pub(crate) use query::query;
+pub(crate) use shell::shell;
+
+/// Suffix of the file holding the documents of a dumped collection.
+/// Each line of the file is a document in canonical extended JSON.
+pub(crate) const DATA_FILE_SUFFIX: &str = ".jsonl";
+
+/// Suffix of the file holding the metadata of a dumped collection.
+pub(crate) const METADATA_FILE_SUFFIX: &str = ".metadata.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct CollectionMetadata {
+    pub(crate) indexes: Vec<IndexModel>,
+}
+
+pub(crate) fn document_to_json(doc: Document, relaxed: bool) -> serde_json::Value {
+    let bson = Bson::Document(doc);
+    if relaxed {
+        bson.into_relaxed_extjson()
+    } else {
+        bson.into_canonical_extjson()
+    }
+}
+
+pub(crate) fn json_to_document(value: serde_json::Value) -> Result<Document> {
+    match Bson::try_from(value)? {
+        Bson::Document(doc) => Ok(doc),
+        other => Err(anyhow!("expected a document, got: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{doc, Document};
+    use polodb_core::{CollectionT, Database, IndexModel, IndexOptions};
+
+    fn mk_path(name: &str) -> std::path::PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!("{}-cli", name));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    #[test]
+    fn test_dump_and_restore() {
+        let src_path = mk_path("test-dump-src");
+        let dump_path = mk_path("test-dump-out");
+        let dst_path = mk_path("test-dump-dst");
+
+        {
+            let db = Database::open_path(&src_path).unwrap();
+            let col = db.collection::<Document>("books");
+            col.create_index(IndexModel {
+                keys: doc! { "title": 1 },
+                options: Some(IndexOptions {
+                    name: Some("title_idx".to_string()),
+                    unique: Some(true),
+                    ..Default::default()
+                }),
+            }).unwrap();
+            let docs = (0..10).map(|i| doc! {
+                "title": format!("book-{}", i),
+                "price": i as i64,
+                "rate": 1.5,
+            });
+            col.insert_many(docs).unwrap();
+
+            super::dump(&db, &dump_path).unwrap();
+            super::verify(&db).unwrap();
+        }
+
+        let db = Database::open_path(&dst_path).unwrap();
+        super::restore(&db, &dump_path, false).unwrap();
+
+        let col = db.collection::<Document>("books");
+        assert_eq!(col.count_documents().unwrap(), 10);
+        assert_eq!(col.list_index_names().unwrap(), vec!["title_idx".to_string()]);
+        let book = col.find_one(doc! { "title": "book-3" }).unwrap().unwrap();
+        assert_eq!(book.get_i64("price").unwrap(), 3);
+
+        let found = super::query(&db, "books", doc! { "price": { "$gt": 7_i64 } }, None).unwrap();
+        assert_eq!(found.len(), 2);
+
+        // restoring twice conflicts with the unique index unless the collection is dropped
+        assert!(super::restore(&db, &dump_path, false).is_err());
+        super::restore(&db, &dump_path, true).unwrap();
+        assert_eq!(col.count_documents().unwrap(), 10);
+    }
+}
@@ -0,0 +1,29 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use anyhow::Result;
+use bson::Document;
+use polodb_core::{CollectionT, Database};
+
+/// Find the documents of `col_name` matching `filter`.
+pub(crate) fn query(db: &Database, col_name: &str, filter: Document, limit: Option<u64>) -> Result<Vec<Document>> {
+    let collection = db.collection::<Document>(col_name);
+    let mut find = collection.find(filter);
+    if let Some(limit) = limit {
+        find = find.limit(limit);
+    }
+    let result = find.run()?.collect::<polodb_core::Result<Vec<Document>>>()?;
+    Ok(result)
+}
@@ -0,0 +1,93 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use anyhow::{anyhow, Result};
+use bson::Document;
+use polodb_core::{CollectionT, Database, TransactionalCollection};
+use crate::commands::{json_to_document, CollectionMetadata, DATA_FILE_SUFFIX, METADATA_FILE_SUFFIX};
+
+const INSERT_BATCH_SIZE: usize = 1000;
+
+/// Load the collections written by [`dump`](super::dump) from `in_dir`.
+///
+/// Each collection is restored in its own transaction. If `drop` is true,
+/// the existing collection is dropped before the documents are inserted.
+pub(crate) fn restore(db: &Database, in_dir: &Path, drop: bool) -> Result<()> {
+    let mut names = Vec::<String>::new();
+    for entry in fs::read_dir(in_dir)? {
+        let file_name = entry?.file_name();
+        let file_name = file_name.to_str().ok_or(anyhow!("invalid file name: {:?}", file_name))?;
+        if let Some(name) = file_name.strip_suffix(DATA_FILE_SUFFIX) {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+
+    for name in names {
+        let txn = db.start_transaction()?;
+        let collection = txn.collection::<Document>(&name);
+        match restore_collection(&collection, in_dir, &name, drop) {
+            Ok(count) => {
+                txn.commit()?;
+                println!("restored {} documents into {}", count, name);
+            }
+            Err(err) => {
+                txn.rollback()?;
+                return Err(anyhow!("restore collection {} failed: {}", name, err));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn restore_collection(collection: &TransactionalCollection<Document>, in_dir: &Path, name: &str, drop: bool) -> Result<u64> {
+    if drop {
+        collection.drop()?;
+    }
+
+    let metadata_path = in_dir.join(format!("{}{}", name, METADATA_FILE_SUFFIX));
+    if metadata_path.exists() {
+        let metadata: CollectionMetadata = serde_json::from_reader(File::open(metadata_path)?)?;
+        for index in metadata.indexes {
+            collection.create_index(index)?;
+        }
+    }
+
+    let data_file = File::open(in_dir.join(format!("{}{}", name, DATA_FILE_SUFFIX)))?;
+    let reader = BufReader::new(data_file);
+    let mut batch = Vec::<Document>::with_capacity(INSERT_BATCH_SIZE);
+    let mut count: u64 = 0;
+    for (line_no, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: serde_json::Value = serde_json::from_str(&line)
+            .map_err(|err| anyhow!("line {}: {}", line_no + 1, err))?;
+        batch.push(json_to_document(value)?);
+        if batch.len() >= INSERT_BATCH_SIZE {
+            count += collection.insert_many(batch.drain(..))?.inserted_ids.len() as u64;
+        }
+    }
+    if !batch.is_empty() {
+        count += collection.insert_many(batch)?.inserted_ids.len() as u64;
+    }
+
+    Ok(count)
+}
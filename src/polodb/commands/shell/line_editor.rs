@@ -0,0 +1,399 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reads the lines of the shell.
+//!
+//! On a terminal of a Unix system, the line is edited in raw mode with a history
+//! and the completion of the tab key. Otherwise the lines are read as they are,
+//! so a script can be piped to the shell.
+
+use std::io::{self, BufRead, Read, Write};
+
+/// Completes the text before the cursor, returning the index of the char where
+/// the completed word starts and the candidates replacing it.
+pub(crate) type Completer<'a> = dyn Fn(&str) -> (usize, Vec<String>) + 'a;
+
+pub(crate) struct LineEditor {
+    history: Vec<String>,
+    interactive: bool,
+}
+
+impl LineEditor {
+
+    pub(crate) fn new() -> LineEditor {
+        LineEditor {
+            history: vec![],
+            interactive: is_terminal(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    /// Read a line, none at the end of the input.
+    pub(crate) fn read_line(&mut self, prompt: &str, completer: &Completer) -> io::Result<Option<String>> {
+        #[cfg(unix)]
+        if self.interactive {
+            let _raw = raw_mode::RawMode::enable()?;
+            return self.edit_line(prompt, completer);
+        }
+        let _ = completer;
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let len = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(len);
+        Ok(Some(line))
+    }
+
+    #[cfg(unix)]
+    fn edit_line(&mut self, prompt: &str, completer: &Completer) -> io::Result<Option<String>> {
+        let mut out = io::stdout();
+        let mut state = EditState {
+            prompt,
+            chars: vec![],
+            cursor: 0,
+        };
+        // the index of the line of the history shown, the history length for the new line
+        let mut history_pos = self.history.len();
+        let mut pending = String::new();
+        let mut last_tab = false;
+        state.redraw(&mut out)?;
+        loop {
+            let key = match read_key()? {
+                Some(key) => key,
+                None => return Ok(None),
+            };
+            let is_tab = key == Key::Tab;
+            match key {
+                Key::Char(c) => state.insert(&[c]),
+                Key::Enter => {
+                    out.write_all(b"\r\n")?;
+                    let line: String = state.chars.iter().collect();
+                    if !line.trim().is_empty() && self.history.last() != Some(&line) {
+                        self.history.push(line.clone());
+                    }
+                    return Ok(Some(line));
+                }
+                Key::Interrupt => {
+                    out.write_all(b"^C\r\n")?;
+                    return Ok(Some(String::new()));
+                }
+                Key::EndOfInput if state.chars.is_empty() => {
+                    out.write_all(b"\r\n")?;
+                    return Ok(None);
+                }
+                Key::EndOfInput | Key::Delete => {
+                    if state.cursor < state.chars.len() {
+                        state.chars.remove(state.cursor);
+                    }
+                }
+                Key::Backspace => {
+                    if state.cursor > 0 {
+                        state.cursor -= 1;
+                        state.chars.remove(state.cursor);
+                    }
+                }
+                Key::Left => state.cursor = state.cursor.saturating_sub(1),
+                Key::Right => state.cursor = (state.cursor + 1).min(state.chars.len()),
+                Key::Home => state.cursor = 0,
+                Key::End => state.cursor = state.chars.len(),
+                Key::KillToStart => {
+                    state.chars.drain(..state.cursor);
+                    state.cursor = 0;
+                }
+                Key::KillToEnd => state.chars.truncate(state.cursor),
+                Key::KillWord => {
+                    let mut start = state.cursor;
+                    while start > 0 && state.chars[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    while start > 0 && !state.chars[start - 1].is_whitespace() {
+                        start -= 1;
+                    }
+                    state.chars.drain(start..state.cursor);
+                    state.cursor = start;
+                }
+                Key::Up | Key::Down => {
+                    if history_pos == self.history.len() {
+                        pending = state.chars.iter().collect();
+                    }
+                    history_pos = if key == Key::Up {
+                        history_pos.saturating_sub(1)
+                    } else {
+                        (history_pos + 1).min(self.history.len())
+                    };
+                    let line = self.history.get(history_pos).unwrap_or(&pending);
+                    state.chars = line.chars().collect();
+                    state.cursor = state.chars.len();
+                }
+                Key::ClearScreen => out.write_all(b"\x1b[H\x1b[2J")?,
+                Key::Tab => {
+                    let before: String = state.chars[..state.cursor].iter().collect();
+                    let (start, candidates) = completer(&before);
+                    let start = start.min(state.cursor);
+                    let word: String = state.chars[start..state.cursor].iter().collect();
+                    let common = common_prefix(&candidates);
+                    if candidates.len() == 1 {
+                        let completed: Vec<char> = candidates[0].chars().collect();
+                        state.replace_word(start, &completed);
+                    } else if common.chars().count() > word.chars().count() {
+                        let completed: Vec<char> = common.chars().collect();
+                        state.replace_word(start, &completed);
+                    } else if last_tab && !candidates.is_empty() {
+                        // a second tab lists the candidates
+                        out.write_all(b"\r\n")?;
+                        out.write_all(candidates.join("  ").as_bytes())?;
+                        out.write_all(b"\r\n")?;
+                    }
+                }
+                Key::Ignored => (),
+            }
+            last_tab = is_tab;
+            state.redraw(&mut out)?;
+        }
+    }
+
+}
+
+#[cfg(unix)]
+struct EditState<'a> {
+    prompt: &'a str,
+    chars: Vec<char>,
+    cursor: usize,
+}
+
+#[cfg(unix)]
+impl EditState<'_> {
+
+    fn insert(&mut self, chars: &[char]) {
+        for (offset, c) in chars.iter().enumerate() {
+            self.chars.insert(self.cursor + offset, *c);
+        }
+        self.cursor += chars.len();
+    }
+
+    fn replace_word(&mut self, start: usize, completed: &[char]) {
+        self.chars.drain(start..self.cursor);
+        self.cursor = start;
+        self.insert(completed);
+    }
+
+    fn redraw(&self, out: &mut impl Write) -> io::Result<()> {
+        let line: String = self.chars.iter().collect();
+        write!(out, "\r{}{}\x1b[K", self.prompt, line)?;
+        let after = self.chars.len() - self.cursor;
+        if after > 0 {
+            write!(out, "\x1b[{}D", after)?;
+        }
+        out.flush()
+    }
+
+}
+
+fn common_prefix(candidates: &[String]) -> String {
+    let mut prefix = match candidates.first() {
+        Some(first) => first.clone(),
+        None => return String::new(),
+    };
+    for candidate in &candidates[1..] {
+        let len = prefix.chars()
+            .zip(candidate.chars())
+            .take_while(|(a, b)| a == b)
+            .map(|(a, _)| a.len_utf8())
+            .sum();
+        prefix.truncate(len);
+    }
+    prefix
+}
+
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Interrupt,
+    EndOfInput,
+    KillToStart,
+    KillToEnd,
+    KillWord,
+    ClearScreen,
+    Ignored,
+}
+
+#[cfg(unix)]
+fn read_byte() -> io::Result<Option<u8>> {
+    let mut byte = [0_u8; 1];
+    match io::stdin().lock().read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// Read a key, none at the end of the input.
+#[cfg(unix)]
+fn read_key() -> io::Result<Option<Key>> {
+    let byte = match read_byte()? {
+        Some(byte) => byte,
+        None => return Ok(None),
+    };
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        127 | 8 => Key::Backspace,
+        1 => Key::Home,
+        3 => Key::Interrupt,
+        4 => Key::EndOfInput,
+        5 => Key::End,
+        11 => Key::KillToEnd,
+        12 => Key::ClearScreen,
+        21 => Key::KillToStart,
+        23 => Key::KillWord,
+        0x1b => read_escape()?,
+        byte if byte < 0x20 => Key::Ignored,
+        byte if byte < 0x80 => Key::Char(byte as char),
+        lead => {
+            let len = match lead {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                _ => 4,
+            };
+            let mut bytes = vec![lead];
+            for _ in 1..len {
+                match read_byte()? {
+                    Some(byte) => bytes.push(byte),
+                    None => break,
+                }
+            }
+            std::str::from_utf8(&bytes)
+                .ok()
+                .and_then(|text| text.chars().next())
+                .map_or(Key::Ignored, Key::Char)
+        }
+    };
+    Ok(Some(key))
+}
+
+/// The key of an escape sequence, such as `ESC [ A` for the up arrow.
+#[cfg(unix)]
+fn read_escape() -> io::Result<Key> {
+    let kind = read_byte()?;
+    if kind != Some(b'[') && kind != Some(b'O') {
+        return Ok(Key::Ignored);
+    }
+    let key = match read_byte()? {
+        Some(b'A') => Key::Up,
+        Some(b'B') => Key::Down,
+        Some(b'C') => Key::Right,
+        Some(b'D') => Key::Left,
+        Some(b'H') => Key::Home,
+        Some(b'F') => Key::End,
+        Some(digit @ b'0'..=b'9') => {
+            // such as `ESC [ 3 ~` for delete
+            let mut code = vec![digit];
+            while let Some(byte) = read_byte()? {
+                if byte == b'~' || !byte.is_ascii_digit() {
+                    break;
+                }
+                code.push(byte);
+            }
+            match code.as_slice() {
+                b"3" => Key::Delete,
+                b"1" | b"7" => Key::Home,
+                b"4" | b"8" => Key::End,
+                _ => Key::Ignored,
+            }
+        }
+        _ => Key::Ignored,
+    };
+    Ok(key)
+}
+
+#[cfg(unix)]
+fn is_terminal() -> bool {
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 && libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+#[cfg(not(unix))]
+fn is_terminal() -> bool {
+    false
+}
+
+#[cfg(unix)]
+mod raw_mode {
+    use std::io;
+    use std::mem::MaybeUninit;
+
+    /// The raw mode of the terminal, restored when dropped.
+    pub(super) struct RawMode {
+        original: libc::termios,
+    }
+
+    impl RawMode {
+
+        pub(super) fn enable() -> io::Result<RawMode> {
+            unsafe {
+                let mut termios = MaybeUninit::<libc::termios>::uninit();
+                if libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let original = termios.assume_init();
+                let mut raw = original;
+                raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+                raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+                raw.c_cc[libc::VMIN] = 1;
+                raw.c_cc[libc::VTIME] = 0;
+                if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(RawMode { original })
+            }
+        }
+
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.original);
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::common_prefix;
+
+    #[test]
+    fn test_common_prefix() {
+        let candidates = vec!["books".to_string(), "bookmarks".to_string()];
+        assert_eq!(common_prefix(&candidates), "book");
+        assert_eq!(common_prefix(&[]), "");
+    }
+
+}
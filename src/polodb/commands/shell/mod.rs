@@ -0,0 +1,468 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The interactive shell of `polodb shell <file>`, running a subset of the
+//! statements of mongosh on a database file and printing the results as
+//! pretty extended JSON.
+
+mod parser;
+mod line_editor;
+
+use std::convert::TryFrom;
+use std::io::Write;
+use std::iter::Peekable;
+use anyhow::{anyhow, Result};
+use bson::{Bson, Document};
+use serde_json::Value;
+use polodb_core::{ClientCursor, CollectionT, Database, IndexModel, IndexOptions};
+use parser::{is_incomplete, parse_statement, Call, Statement};
+use line_editor::LineEditor;
+
+/// The count of the documents printed at once, `it` prints the next ones.
+const BATCH_SIZE: usize = 20;
+
+const COLLECTION_METHODS: &[&str] = &[
+    "aggregate", "countDocuments", "createIndex", "deleteMany", "deleteOne", "drop",
+    "dropIndex", "find", "findOne", "getIndexes", "insertMany", "insertOne",
+    "updateMany", "updateOne",
+];
+
+const DATABASE_METHODS: &[&str] = &["createCollection", "getCollection", "getCollectionNames"];
+
+const HELP: &str = "\
+Statements:
+  show collections                        list the collections
+  db.getCollectionNames()                 list the collections
+  db.createCollection(name)               create a collection
+  db.<col>.find(filter?)                  find the documents, followed by .sort(doc), .skip(n), .limit(n)
+  db.<col>.findOne(filter?)               find a document
+  db.<col>.countDocuments(filter?)        count the documents
+  db.<col>.aggregate([stages])            run an aggregation pipeline
+  db.<col>.insertOne(doc)                 insert a document
+  db.<col>.insertMany([docs])             insert documents
+  db.<col>.updateOne(filter, update)      update a document
+  db.<col>.updateMany(filter, update)     update the documents
+  db.<col>.deleteOne(filter)              delete a document
+  db.<col>.deleteMany(filter)             delete the documents
+  db.<col>.createIndex(keys, options?)    create an index
+  db.<col>.dropIndex(name)                drop an index
+  db.<col>.getIndexes()                   list the indexes
+  db.<col>.drop()                         drop the collection
+  it                                      print the next documents of the last find
+  exit                                    quit the shell
+The collections are also named by db.getCollection(name). Press tab to complete the names.";
+
+pub(crate) fn shell(db: Database) -> Result<()> {
+    let mut shell = Shell::new(db);
+    let mut editor = LineEditor::new();
+    if editor.is_interactive() {
+        println!("PoloDB shell {}, type 'help' for the statements", Database::get_version());
+    }
+    let mut out = std::io::stdout();
+    let mut input = String::new();
+    loop {
+        let prompt = if input.is_empty() { "> " } else { "... " };
+        let completer = |line: &str| shell.complete(line);
+        let line = match editor.read_line(prompt, &completer)? {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+        input.push_str(&line);
+        input.push('\n');
+        if is_incomplete(&input) {
+            continue;
+        }
+        let statement = std::mem::take(&mut input);
+        if statement.trim().is_empty() {
+            continue;
+        }
+        match shell.eval(&statement, &mut out) {
+            Ok(true) => return Ok(()),
+            Ok(false) => (),
+            Err(err) => eprintln!("error: {}", err),
+        }
+        out.flush()?;
+    }
+}
+
+pub(crate) struct Shell {
+    db: Database,
+    /// the cursor of the last find or aggregation, whose documents are printed by `it`
+    cursor: Option<Peekable<ClientCursor<Document>>>,
+}
+
+impl Shell {
+
+    pub(crate) fn new(db: Database) -> Shell {
+        Shell { db, cursor: None }
+    }
+
+    /// Run a statement, printing its result to `out`. Returns whether the shell exits.
+    pub(crate) fn eval(&mut self, input: &str, out: &mut dyn Write) -> Result<bool> {
+        match parse_statement(input)? {
+            Statement::Help => writeln!(out, "{}", HELP)?,
+            Statement::Exit => return Ok(true),
+            Statement::ShowCollections => {
+                for name in self.db.list_collection_names()? {
+                    writeln!(out, "{}", name)?;
+                }
+            }
+            Statement::More => match self.cursor.take() {
+                Some(cursor) => self.print_batch(cursor, out)?,
+                None => writeln!(out, "no cursor")?,
+            },
+            Statement::Database(call) => self.eval_database(call, out)?,
+            Statement::Collection { name, call, chain } => self.eval_collection(&name, call, chain, out)?,
+        }
+        Ok(false)
+    }
+
+    fn eval_database(&mut self, call: Call, out: &mut dyn Write) -> Result<()> {
+        match call.name.as_str() {
+            "getCollectionNames" => {
+                let names = self.db.list_collection_names()?;
+                print_value(out, names.into())?;
+            }
+            "createCollection" => {
+                let [name] = args::<1>(&call, 1)?;
+                self.db.create_collection(string_arg(&call, name)?)?;
+                print_value(out, bson::bson!({ "ok": 1 }))?;
+            }
+            _ => return Err(anyhow!("unknown method db.{}()", call.name)),
+        }
+        Ok(())
+    }
+
+    fn eval_collection(&mut self, name: &str, call: Call, chain: Vec<Call>, out: &mut dyn Write) -> Result<()> {
+        let collection = self.db.collection::<Document>(name);
+        let is_cursor = call.name == "find" || call.name == "aggregate";
+        if !is_cursor {
+            if let Some(next) = chain.first() {
+                return Err(anyhow!("{}() has no method {}()", call.name, next.name));
+            }
+        }
+        match call.name.as_str() {
+            "find" => {
+                let [filter, projection] = args::<2>(&call, 0)?;
+                if projection.is_some() {
+                    return Err(anyhow!("find() doesn't take a projection"));
+                }
+                let mut find = collection.find(opt_doc_arg(&call, filter)?);
+                let mut print_all = false;
+                for next in &chain {
+                    find = match next.name.as_str() {
+                        "sort" => find.sort(doc_arg(next, args::<1>(next, 1)?[0])?),
+                        "skip" => find.skip(count_arg(next, args::<1>(next, 1)?[0])?),
+                        "limit" => find.limit(count_arg(next, args::<1>(next, 1)?[0])?),
+                        "toArray" => {
+                            print_all = true;
+                            find
+                        }
+                        _ => return Err(anyhow!("find() has no method {}()", next.name)),
+                    };
+                }
+                let cursor = find.run()?;
+                self.print_cursor(cursor, print_all, out)?;
+            }
+            "aggregate" => {
+                let [pipeline] = args::<1>(&call, 1)?;
+                let stages = match pipeline.map(|value| json_to_bson(&call, value)).transpose()? {
+                    Some(Bson::Array(stages)) => stages.into_iter()
+                        .map(|stage| match stage {
+                            Bson::Document(stage) => Ok(stage),
+                            _ => Err(anyhow!("aggregate() takes an array of stages")),
+                        })
+                        .collect::<Result<Vec<Document>>>()?,
+                    _ => return Err(anyhow!("aggregate() takes an array of stages")),
+                };
+                let mut print_all = false;
+                for next in &chain {
+                    match next.name.as_str() {
+                        "toArray" => print_all = true,
+                        _ => return Err(anyhow!("aggregate() has no method {}()", next.name)),
+                    }
+                }
+                let cursor = collection.aggregate(stages).run()?;
+                self.print_cursor(cursor, print_all, out)?;
+            }
+            "findOne" => {
+                let [filter] = args::<1>(&call, 0)?;
+                match collection.find_one(opt_doc_arg(&call, filter)?)? {
+                    Some(doc) => print_value(out, Bson::Document(doc))?,
+                    None => writeln!(out, "null")?,
+                }
+            }
+            "countDocuments" => {
+                let [filter] = args::<1>(&call, 0)?;
+                let filter = opt_doc_arg(&call, filter)?;
+                let count = if filter.is_empty() {
+                    collection.count_documents()?
+                } else {
+                    collection.find(filter).run()?.count() as u64
+                };
+                writeln!(out, "{}", count)?;
+            }
+            "insertOne" => {
+                let [doc] = args::<1>(&call, 1)?;
+                let result = collection.insert_one(doc_arg(&call, doc)?)?;
+                print_value(out, bson::to_bson(&result)?)?;
+            }
+            "insertMany" => {
+                let [docs] = args::<1>(&call, 1)?;
+                let docs = match docs.map(|value| json_to_bson(&call, value)).transpose()? {
+                    Some(Bson::Array(docs)) => docs.into_iter()
+                        .map(|doc| match doc {
+                            Bson::Document(doc) => Ok(doc),
+                            _ => Err(anyhow!("insertMany() takes an array of documents")),
+                        })
+                        .collect::<Result<Vec<Document>>>()?,
+                    _ => return Err(anyhow!("insertMany() takes an array of documents")),
+                };
+                let result = collection.insert_many(docs)?;
+                print_value(out, bson::to_bson(&result)?)?;
+            }
+            "updateOne" | "updateMany" => {
+                let [filter, update] = args::<2>(&call, 2)?;
+                let filter = doc_arg(&call, filter)?;
+                let update = doc_arg(&call, update)?;
+                let result = if call.name == "updateOne" {
+                    collection.update_one(filter, update)?
+                } else {
+                    collection.update_many(filter, update)?
+                };
+                print_value(out, bson::to_bson(&result)?)?;
+            }
+            "deleteOne" | "deleteMany" => {
+                let [filter] = args::<1>(&call, 1)?;
+                let filter = doc_arg(&call, filter)?;
+                let result = if call.name == "deleteOne" {
+                    collection.delete_one(filter)?
+                } else {
+                    collection.delete_many(filter)?
+                };
+                print_value(out, bson::to_bson(&result)?)?;
+            }
+            "createIndex" => {
+                let [keys, options] = args::<2>(&call, 1)?;
+                let options = match options {
+                    Some(options) => Some(bson::from_document::<IndexOptions>(doc_arg(&call, Some(options))?)
+                        .map_err(|err| anyhow!("invalid index options: {}", err))?),
+                    None => None,
+                };
+                let name = collection.create_index(IndexModel {
+                    keys: doc_arg(&call, keys)?,
+                    options,
+                })?;
+                print_value(out, Bson::String(name))?;
+            }
+            "dropIndex" => {
+                let [index] = args::<1>(&call, 1)?;
+                collection.drop_index(string_arg(&call, index)?)?;
+                print_value(out, bson::bson!({ "ok": 1 }))?;
+            }
+            "getIndexes" => {
+                args::<0>(&call, 0)?;
+                let mut indexes = vec![];
+                for index in collection.list_index_names()? {
+                    if let Some(info) = collection.describe_index(&index)? {
+                        indexes.push(bson::to_bson(&info)?);
+                    }
+                }
+                print_value(out, Bson::Array(indexes))?;
+            }
+            "drop" => {
+                args::<0>(&call, 0)?;
+                collection.drop()?;
+                writeln!(out, "true")?;
+            }
+            _ => return Err(anyhow!("unknown method db.{}.{}()", name, call.name)),
+        }
+        Ok(())
+    }
+
+    fn print_cursor(&mut self, mut cursor: ClientCursor<Document>, print_all: bool, out: &mut dyn Write) -> Result<()> {
+        if print_all {
+            let docs = cursor.by_ref()
+                .map(|doc| doc.map(Bson::Document))
+                .collect::<polodb_core::Result<Vec<Bson>>>()?;
+            return print_value(out, Bson::Array(docs));
+        }
+        self.print_batch(cursor.peekable(), out)
+    }
+
+    /// Print the next documents of the cursor, keeping it for `it` if there are more.
+    fn print_batch(&mut self, mut cursor: Peekable<ClientCursor<Document>>, out: &mut dyn Write) -> Result<()> {
+        for _ in 0..BATCH_SIZE {
+            match cursor.next() {
+                Some(doc) => print_value(out, Bson::Document(doc?))?,
+                None => return Ok(()),
+            }
+        }
+        if cursor.peek().is_some() {
+            writeln!(out, "Type \"it\" for more")?;
+            self.cursor = Some(cursor);
+        }
+        Ok(())
+    }
+
+    /// Complete the names of the collections after `db.` and the names of the
+    /// methods, for the line before the cursor.
+    pub(crate) fn complete(&self, line: &str) -> (usize, Vec<String>) {
+        let chars: Vec<char> = line.chars().collect();
+        let mut start = chars.len();
+        while start > 0 && (chars[start - 1].is_alphanumeric() || chars[start - 1] == '_' || chars[start - 1] == '$') {
+            start -= 1;
+        }
+        let word: String = chars[start..].iter().collect();
+        let before: String = chars[..start].iter().collect();
+
+        let mut candidates: Vec<String> = if before.ends_with("db.") && !before.ends_with(".db.") {
+            let names = self.db.list_collection_names().unwrap_or_default();
+            names.into_iter()
+                .chain(DATABASE_METHODS.iter().map(|name| name.to_string()))
+                .collect()
+        } else if before.ends_with("getCollection(\"") || before.ends_with("getCollection('") {
+            self.db.list_collection_names().unwrap_or_default()
+        } else if before.ends_with('.') && before.trim_start().starts_with("db.") {
+            COLLECTION_METHODS.iter().map(|name| name.to_string()).collect()
+        } else if before.trim().is_empty() {
+            ["db", "exit", "help", "it", "show"].iter().map(|name| name.to_string()).collect()
+        } else if before.trim() == "show" {
+            vec!["collections".to_string()]
+        } else {
+            vec![]
+        };
+        candidates.retain(|candidate| candidate.starts_with(&word));
+        candidates.sort();
+        candidates.dedup();
+        (start, candidates)
+    }
+
+}
+
+/// The `N` arguments of the call, of which the first `required` are required.
+fn args<const N: usize>(call: &Call, required: usize) -> Result<[Option<&Value>; N]> {
+    if call.args.len() < required || call.args.len() > N {
+        return Err(anyhow!("{}() takes {} arguments, got {}", call.name, N, call.args.len()));
+    }
+    let mut args = [None; N];
+    for (arg, value) in args.iter_mut().zip(&call.args) {
+        *arg = Some(value);
+    }
+    Ok(args)
+}
+
+fn json_to_bson(call: &Call, value: &Value) -> Result<Bson> {
+    Bson::try_from(value.clone()).map_err(|err| anyhow!("invalid argument of {}(): {}", call.name, err))
+}
+
+fn doc_arg(call: &Call, value: Option<&Value>) -> Result<Document> {
+    match value.map(|value| json_to_bson(call, value)).transpose()? {
+        Some(Bson::Document(doc)) => Ok(doc),
+        _ => Err(anyhow!("{}() takes a document", call.name)),
+    }
+}
+
+fn opt_doc_arg(call: &Call, value: Option<&Value>) -> Result<Document> {
+    match value {
+        None => Ok(Document::new()),
+        Some(_) => doc_arg(call, value),
+    }
+}
+
+fn string_arg<'a>(call: &Call, value: Option<&'a Value>) -> Result<&'a str> {
+    match value {
+        Some(Value::String(value)) => Ok(value),
+        _ => Err(anyhow!("{}() takes a string", call.name)),
+    }
+}
+
+fn count_arg(call: &Call, value: Option<&Value>) -> Result<u64> {
+    value.and_then(Value::as_u64).ok_or_else(|| anyhow!("{}() takes a count", call.name))
+}
+
+fn print_value(out: &mut dyn Write, value: Bson) -> Result<()> {
+    writeln!(out, "{}", serde_json::to_string_pretty(&value.into_relaxed_extjson())?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use polodb_core::Database;
+    use super::Shell;
+
+    fn mk_shell(name: &str) -> Shell {
+        let mut path = std::env::temp_dir();
+        path.push(format!("{}-cli", name));
+        let _ = std::fs::remove_dir_all(&path);
+        Shell::new(Database::open_path(&path).unwrap())
+    }
+
+    fn eval(shell: &mut Shell, input: &str) -> String {
+        let mut out = vec![];
+        shell.eval(input, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_shell_statements() {
+        let mut shell = mk_shell("test-shell");
+        let out = eval(&mut shell, "db.books.insertOne({ _id: 1, title: 'Dune', price: 10 })");
+        assert_eq!(out, "{\n  \"insertedId\": 1\n}\n");
+        eval(&mut shell, "db.books.insertMany([{ _id: 2, title: 'Emma', price: 5 }, { _id: 3, title: 'Ulysses', price: 20 }])");
+        eval(&mut shell, "db.books.createIndex({ price: 1 }, { name: 'price_1' })");
+
+        let out = eval(&mut shell, "db.books.find({ price: { $gte: 10 } }).sort({ price: -1 }).limit(1)");
+        assert_eq!(out, "{\n  \"_id\": 3,\n  \"title\": \"Ulysses\",\n  \"price\": 20\n}\n");
+        let out = eval(&mut shell, "db.getCollection('books').findOne({ title: /^em/i })");
+        assert!(out.contains("\"Emma\""));
+        assert_eq!(eval(&mut shell, "db.books.countDocuments({ price: { $lt: 15 } })"), "2\n");
+
+        let out = eval(&mut shell, "db.books.updateMany({}, { $inc: { price: 1 } })");
+        assert!(out.contains("\"modifiedCount\": 3"));
+        let out = eval(&mut shell, "db.books.deleteOne({ _id: 2 })");
+        assert!(out.contains("\"deletedCount\": 1"));
+        assert_eq!(eval(&mut shell, "show collections"), "books\n");
+
+        assert!(shell.eval("db.books.find().count()", &mut vec![]).is_err());
+        assert!(shell.eval("db.books.insertOne('Dune')", &mut vec![]).is_err());
+        assert!(shell.eval("exit", &mut vec![]).unwrap());
+    }
+
+    #[test]
+    fn test_shell_batches() {
+        let mut shell = mk_shell("test-shell-batches");
+        let docs: Vec<String> = (0..25).map(|i| format!("{{ _id: {} }}", i)).collect();
+        eval(&mut shell, &format!("db.items.insertMany([{}])", docs.join(", ")));
+        let out = eval(&mut shell, "db.items.find()");
+        assert_eq!(out.matches("\"_id\"").count(), 20);
+        assert!(out.ends_with("Type \"it\" for more\n"));
+        let out = eval(&mut shell, "it");
+        assert_eq!(out.matches("\"_id\"").count(), 5);
+        assert_eq!(eval(&mut shell, "it"), "no cursor\n");
+    }
+
+    #[test]
+    fn test_shell_complete() {
+        let mut shell = mk_shell("test-shell-complete");
+        eval(&mut shell, "db.books.insertOne({})");
+        eval(&mut shell, "db.bookmarks.insertOne({})");
+        assert_eq!(shell.complete("db.bo"), (3, vec!["bookmarks".to_string(), "books".to_string()]));
+        assert_eq!(shell.complete("db.getCollection('bookm"), (18, vec!["bookmarks".to_string()]));
+        assert_eq!(shell.complete("db.books.ins"), (9, vec!["insertMany".to_string(), "insertOne".to_string()]));
+        assert_eq!(shell.complete("sh"), (0, vec!["show".to_string()]));
+        assert_eq!(shell.complete("show c"), (5, vec!["collections".to_string()]));
+    }
+
+}
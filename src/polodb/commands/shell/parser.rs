@@ -0,0 +1,471 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The parser of the statements of the shell, a subset of the syntax of mongosh.
+//!
+//! The values are the literals of JavaScript, whose keys may be unquoted and whose
+//! strings may be single-quoted, along with the constructors of mongosh such as
+//! `ObjectId("...")` and `ISODate("...")` and the regular expression literals.
+//! They are parsed to extended JSON, converted to BSON by the caller.
+
+use anyhow::{anyhow, Result};
+use bson::oid::ObjectId;
+use serde_json::{json, Map, Number, Value};
+
+/// A method call of a statement, such as `find({"a": 1})`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Call {
+    pub(crate) name: String,
+    pub(crate) args: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Statement {
+    Help,
+    Exit,
+    ShowCollections,
+    /// `it`, printing the next documents of the last cursor
+    More,
+    /// a method of `db`, such as `db.getCollectionNames()`
+    Database(Call),
+    /// a method of a collection followed by the methods of its cursor, such as
+    /// `db.books.find({}).sort({"price": 1}).limit(10)`
+    Collection {
+        name: String,
+        call: Call,
+        chain: Vec<Call>,
+    },
+}
+
+pub(crate) fn parse_statement(input: &str) -> Result<Statement> {
+    let mut parser = Parser::new(input);
+    let statement = parser.statement()?;
+    parser.skip_whitespace();
+    parser.eat(';');
+    parser.skip_whitespace();
+    if let Some(c) = parser.peek() {
+        return Err(parser.error(&format!("unexpected '{}'", c)));
+    }
+    Ok(statement)
+}
+
+/// Whether the input has brackets or a string left open, so the statement
+/// continues on the next line.
+pub(crate) fn is_incomplete(input: &str) -> bool {
+    let mut depth = 0_i32;
+    let mut quote = None;
+    let mut escaped = false;
+    for c in input.chars() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => (),
+        }
+    }
+    quote.is_some() || depth > 0
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+
+    fn new(input: &'a str) -> Parser<'a> {
+        Parser { input, pos: 0 }
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!("{} at column {}", message, self.input[..self.pos].chars().count() + 1)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        self.skip_whitespace();
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$') {
+            self.bump();
+        }
+        if start == self.pos {
+            return Err(self.error("expected a name"));
+        }
+        Ok(self.input[start..self.pos].to_string())
+    }
+
+    fn statement(&mut self) -> Result<Statement> {
+        self.skip_whitespace();
+        let first = self.ident()?;
+        match first.as_str() {
+            "help" => return Ok(Statement::Help),
+            "exit" | "quit" => {
+                // `exit()` and `quit()` too
+                self.skip_whitespace();
+                if self.eat('(') {
+                    self.expect(')')?;
+                }
+                return Ok(Statement::Exit);
+            }
+            "it" => return Ok(Statement::More),
+            "show" => {
+                let what = self.ident()?;
+                return match what.as_str() {
+                    "collections" | "tables" => Ok(Statement::ShowCollections),
+                    _ => Err(anyhow!("unknown 'show {}', try 'show collections'", what)),
+                };
+            }
+            "db" => (),
+            _ => return Err(anyhow!("unknown statement '{}', try 'help'", first)),
+        }
+
+        self.expect('.')?;
+        let name = self.ident()?;
+        let name = match self.call_args(&name)? {
+            Some(call) if call.name == "getCollection" => match call.args.as_slice() {
+                [Value::String(name)] => name.clone(),
+                _ => return Err(anyhow!("getCollection() takes the name of the collection")),
+            },
+            Some(call) => return Ok(Statement::Database(call)),
+            None => name,
+        };
+
+        self.expect('.')?;
+        let method = self.ident()?;
+        let call = self.call_args(&method)?
+            .ok_or_else(|| self.error(&format!("expected '(' after '{}'", method)))?;
+        let mut chain = vec![];
+        loop {
+            self.skip_whitespace();
+            if !self.eat('.') {
+                break;
+            }
+            let method = self.ident()?;
+            let next = self.call_args(&method)?
+                .ok_or_else(|| self.error(&format!("expected '(' after '{}'", method)))?;
+            chain.push(next);
+        }
+        Ok(Statement::Collection { name, call, chain })
+    }
+
+    /// The arguments of the call of `name`, none if `name` isn't followed by '('.
+    fn call_args(&mut self, name: &str) -> Result<Option<Call>> {
+        self.skip_whitespace();
+        if !self.eat('(') {
+            return Ok(None);
+        }
+        let args = self.list(')')?;
+        Ok(Some(Call { name: name.to_string(), args }))
+    }
+
+    /// The values up to `close`, separated by commas, allowing a trailing comma.
+    fn list(&mut self, close: char) -> Result<Vec<Value>> {
+        let mut values = vec![];
+        loop {
+            self.skip_whitespace();
+            if self.eat(close) {
+                return Ok(values);
+            }
+            values.push(self.value()?);
+            self.skip_whitespace();
+            if self.eat(close) {
+                return Ok(values);
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => {
+                self.bump();
+                self.object()
+            }
+            Some('[') => {
+                self.bump();
+                Ok(Value::Array(self.list(']')?))
+            }
+            Some(c @ ('"' | '\'')) => {
+                self.bump();
+                Ok(Value::String(self.string(c)?))
+            }
+            Some('/') => {
+                self.bump();
+                self.regex()
+            }
+            Some(c) if c == '-' || c == '.' || c.is_ascii_digit() => self.number(),
+            Some(_) => {
+                let name = self.ident()?;
+                self.keyword(&name)
+            }
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value> {
+        let mut map = Map::new();
+        loop {
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(Value::Object(map));
+            }
+            let key = match self.peek() {
+                Some(c @ ('"' | '\'')) => {
+                    self.bump();
+                    self.string(c)?
+                }
+                _ => self.ident()?,
+            };
+            self.expect(':')?;
+            let value = self.value()?;
+            map.insert(key, value);
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(Value::Object(map));
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn string(&mut self, quote: char) -> Result<String> {
+        let mut value = String::new();
+        loop {
+            let c = self.bump().ok_or_else(|| self.error("unterminated string"))?;
+            if c == quote {
+                return Ok(value);
+            }
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+            let escaped = self.bump().ok_or_else(|| self.error("unterminated string"))?;
+            value.push(match escaped {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                '0' => '\0',
+                'u' => {
+                    let hex = self.input.get(self.pos..self.pos + 4)
+                        .ok_or_else(|| self.error("invalid \\u escape"))?;
+                    let code = u32::from_str_radix(hex, 16)
+                        .map_err(|_| self.error("invalid \\u escape"))?;
+                    self.pos += 4;
+                    char::from_u32(code).ok_or_else(|| self.error("invalid \\u escape"))?
+                }
+                other => other,
+            });
+        }
+    }
+
+    fn regex(&mut self) -> Result<Value> {
+        let mut pattern = String::new();
+        loop {
+            let c = self.bump().ok_or_else(|| self.error("unterminated regular expression"))?;
+            match c {
+                '/' => break,
+                '\\' => {
+                    pattern.push(c);
+                    let escaped = self.bump().ok_or_else(|| self.error("unterminated regular expression"))?;
+                    pattern.push(escaped);
+                }
+                _ => pattern.push(c),
+            }
+        }
+        let mut options = vec![];
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_alphabetic()) {
+            self.bump();
+            options.push(c);
+        }
+        // the options of extended JSON are sorted
+        options.sort_unstable();
+        Ok(json!({ "$regularExpression": { "pattern": pattern, "options": options.into_iter().collect::<String>() } }))
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let start = self.pos;
+        self.eat('-');
+        if self.input[self.pos..].starts_with("Infinity") {
+            self.pos += "Infinity".len();
+            return Ok(json!({ "$numberDouble": "-Infinity" }));
+        }
+        while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '.' || c == '+' || c == '-') {
+            self.bump();
+        }
+        let text = &self.input[start..self.pos];
+        if let Ok(value) = text.parse::<i64>() {
+            return Ok(Value::Number(value.into()));
+        }
+        text.parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .ok_or_else(|| anyhow!("invalid number '{}'", text))
+    }
+
+    /// The value of a keyword or of a constructor, such as `ObjectId("...")`.
+    fn keyword(&mut self, name: &str) -> Result<Value> {
+        match name {
+            "true" => return Ok(Value::Bool(true)),
+            "false" => return Ok(Value::Bool(false)),
+            "null" | "undefined" => return Ok(Value::Null),
+            "Infinity" => return Ok(json!({ "$numberDouble": "Infinity" })),
+            "NaN" => return Ok(json!({ "$numberDouble": "NaN" })),
+            "new" => {
+                let name = self.ident()?;
+                return self.keyword(&name);
+            }
+            _ => (),
+        }
+        let call = self.call_args(name)?
+            .ok_or_else(|| anyhow!("unknown value '{}'", name))?;
+        let arg = call.args.first();
+        let text_arg = || match arg {
+            Some(Value::String(text)) => Ok(text.clone()),
+            Some(Value::Number(number)) => Ok(number.to_string()),
+            _ => Err(anyhow!("{}() takes a string or a number", name)),
+        };
+        let value = match name {
+            "ObjectId" => match arg {
+                None => json!({ "$oid": ObjectId::new().to_hex() }),
+                Some(_) => json!({ "$oid": text_arg()? }),
+            },
+            "ISODate" | "Date" => match arg {
+                None => json!({ "$date": { "$numberLong": bson::DateTime::now().timestamp_millis().to_string() } }),
+                Some(Value::String(date)) => json!({ "$date": date }),
+                Some(_) => json!({ "$date": { "$numberLong": text_arg()? } }),
+            },
+            "NumberLong" => json!({ "$numberLong": text_arg()? }),
+            "NumberInt" => json!({ "$numberInt": text_arg()? }),
+            "NumberDecimal" => json!({ "$numberDecimal": text_arg()? }),
+            "UUID" => json!({ "$uuid": text_arg()? }),
+            _ => return Err(anyhow!("unknown function '{}'", name)),
+        };
+        Ok(value)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::{is_incomplete, parse_statement, Call, Statement};
+
+    #[test]
+    fn test_parse_statement() {
+        let statement = parse_statement(
+            "db.books.find({ price: { $gt: 10 }, 'title': \"Dune\", tags: ['a', 'b',], }).sort({price: -1}).limit(2);"
+        ).unwrap();
+        assert_eq!(statement, Statement::Collection {
+            name: "books".to_string(),
+            call: Call {
+                name: "find".to_string(),
+                args: vec![json!({ "price": { "$gt": 10 }, "title": "Dune", "tags": ["a", "b"] })],
+            },
+            chain: vec![
+                Call { name: "sort".to_string(), args: vec![json!({ "price": -1 })] },
+                Call { name: "limit".to_string(), args: vec![json!(2)] },
+            ],
+        });
+
+        let statement = parse_statement("db.getCollection('my books').insertOne({ _id: ObjectId('65f1c1a2b3c4d5e6f7a8b9c0') })").unwrap();
+        assert_eq!(statement, Statement::Collection {
+            name: "my books".to_string(),
+            call: Call {
+                name: "insertOne".to_string(),
+                args: vec![json!({ "_id": { "$oid": "65f1c1a2b3c4d5e6f7a8b9c0" } })],
+            },
+            chain: vec![],
+        });
+
+        assert_eq!(parse_statement("db.getCollectionNames()").unwrap(), Statement::Database(Call {
+            name: "getCollectionNames".to_string(),
+            args: vec![],
+        }));
+        assert_eq!(parse_statement(" show collections ").unwrap(), Statement::ShowCollections);
+        assert_eq!(parse_statement("exit()").unwrap(), Statement::Exit);
+
+        assert!(parse_statement("db.books").is_err());
+        assert!(parse_statement("db.books.find({)").is_err());
+        assert!(parse_statement("books.find()").is_err());
+    }
+
+    #[test]
+    fn test_parse_values() {
+        let statement = parse_statement(
+            "db.t.insertOne({ d: ISODate('2024-01-02T03:04:05Z'), l: NumberLong(5), r: /^ab\\/c/xi, f: -1.5, n: null })"
+        ).unwrap();
+        let Statement::Collection { call, .. } = statement else { panic!("not a collection statement") };
+        assert_eq!(call.args, vec![json!({
+            "d": { "$date": "2024-01-02T03:04:05Z" },
+            "l": { "$numberLong": "5" },
+            "r": { "$regularExpression": { "pattern": "^ab\\/c", "options": "ix" } },
+            "f": -1.5,
+            "n": null,
+        })]);
+    }
+
+    #[test]
+    fn test_is_incomplete() {
+        assert!(is_incomplete("db.books.find({"));
+        assert!(is_incomplete("db.books.insertOne({ title: 'a)"));
+        assert!(!is_incomplete("db.books.find({ title: '{' })"));
+    }
+
+}
@@ -0,0 +1,49 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use anyhow::Result;
+use bson::Document;
+use polodb_core::{CollectionT, Database};
+
+/// Print the document count and the indexes of every collection.
+pub(crate) fn stats(db: &Database) -> Result<()> {
+    let names = db.list_collection_names()?;
+    println!("collections: {}", names.len());
+
+    for name in names {
+        let collection = db.collection::<Document>(&name);
+        let count = collection.count_documents()?;
+        println!("{}:", name);
+        println!("  documents: {}", count);
+
+        let index_names = collection.list_index_names()?;
+        println!("  indexes: {}", index_names.len());
+        for index_name in index_names {
+            let info = match collection.describe_index(&index_name)? {
+                Some(info) => info,
+                None => continue,
+            };
+            let keys = info.keys
+                .iter()
+                .map(|(key, order)| format!("{}: {}", key, order))
+                .collect::<Vec<String>>()
+                .join(", ");
+            let unique = if info.is_unique() { " unique" } else { "" };
+            println!("    {} {{ {} }}{}", index_name, keys, unique);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use anyhow::{anyhow, Result};
+use bson::Document;
+use polodb_core::{CollectionT, Database};
+
+/// Read back every document of every collection, checking that
+/// each one decodes and carries an `_id`, and that the number of
+/// documents read agrees with the collection count.
+pub(crate) fn verify(db: &Database) -> Result<()> {
+    let mut problems: usize = 0;
+
+    for name in db.list_collection_names()? {
+        let collection = db.collection::<Document>(&name);
+        let expected = collection.count_documents()?;
+
+        let mut read: u64 = 0;
+        for (pos, doc) in collection.find(Document::new()).run()?.enumerate() {
+            match doc {
+                Ok(doc) => {
+                    read += 1;
+                    if !doc.contains_key("_id") {
+                        problems += 1;
+                        eprintln!("{}: document #{} has no _id", name, pos);
+                    }
+                }
+                Err(err) => {
+                    problems += 1;
+                    eprintln!("{}: failed to read document #{}: {}", name, pos, err);
+                    break;
+                }
+            }
+        }
+
+        if read != expected {
+            problems += 1;
+            eprintln!("{}: count is {}, but {} documents are read", name, expected, read);
+        }
+
+        println!("{}: {} documents checked", name, read);
+    }
+
+    if problems > 0 {
+        return Err(anyhow!("verify failed, {} problems found", problems));
+    }
+
+    println!("ok");
+    Ok(())
+}
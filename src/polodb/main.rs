@@ -27,6 +27,18 @@
 //! And the official rust driver is also supported.
 //! You can check the [official driver](https://crates.io/crates/mongodb) for more information.
 //!
+//! # Maintenance
+//!
+//! Besides `serve`, the tool offers commands working on a database path directly:
+//!
+//! - `dump --path /path/to/db --out /path/to/dump`: write every collection as JSON lines
+//! - `restore --path /path/to/db --dir /path/to/dump [--drop]`: load a dump back
+//! - `stats --path /path/to/db`: print document counts and indexes
+//! - `compact --path /path/to/db`: compact the storage
+//! - `verify --path /path/to/db`: read back every document and check the counts
+//! - `query --path /path/to/db --collection books --filter '{"price": {"$gt": 10}}'`
+//! - `shell /path/to/db`: run the statements of mongosh such as `db.books.find({price: {$gt: 10}})`
+//!
 
 mod wire;
 mod bson_util;
@@ -38,11 +50,13 @@ mod handlers;
 mod app_context;
 mod utils;
 mod session_context;
+mod commands;
 
 use std::net::SocketAddr;
+use std::path::Path;
 use polodb_core::Database;
 use bson::{rawdoc, Document, RawBsonRef};
-use clap::{Arg, Command as App};
+use clap::{Arg, ArgAction, ArgMatches, Command as App};
 use anyhow::{Result, anyhow};
 use tokio::io::{AsyncRead, AsyncWrite};
 use log::{info, warn, error, debug};
@@ -93,6 +107,87 @@ async fn main() {
                     .short('l')
             )
         )
+        .subcommand(App::new("dump")
+            .about("write all the collections into a directory")
+            .arg(path_arg())
+            .arg(
+                Arg::new("out")
+                    .help("the output directory")
+                    .short('o')
+                    .long("out")
+                    .value_name("DIR")
+                    .required(true)
+                    .num_args(1)
+            )
+        )
+        .subcommand(App::new("restore")
+            .about("load the collections from a directory written by dump")
+            .arg(path_arg())
+            .arg(
+                Arg::new("dir")
+                    .help("the directory written by dump")
+                    .short('d')
+                    .long("dir")
+                    .value_name("DIR")
+                    .required(true)
+                    .num_args(1)
+            )
+            .arg(
+                Arg::new("drop")
+                    .help("drop the existing collections before restoring")
+                    .long("drop")
+                    .action(ArgAction::SetTrue)
+            )
+        )
+        .subcommand(App::new("stats")
+            .about("print the statistics of the collections")
+            .arg(path_arg())
+        )
+        .subcommand(App::new("compact")
+            .about("compact the storage of the database")
+            .arg(path_arg())
+        )
+        .subcommand(App::new("verify")
+            .about("check that all the documents can be read back")
+            .arg(path_arg())
+        )
+        .subcommand(App::new("query")
+            .about("print the documents matching a filter")
+            .arg(path_arg())
+            .arg(
+                Arg::new("collection")
+                    .help("the collection name")
+                    .short('c')
+                    .long("collection")
+                    .required(true)
+                    .num_args(1)
+            )
+            .arg(
+                Arg::new("filter")
+                    .help("the filter in extended JSON")
+                    .short('f')
+                    .long("filter")
+                    .default_value("{}")
+                    .num_args(1)
+            )
+            .arg(
+                Arg::new("limit")
+                    .help("the max number of documents to print")
+                    .long("limit")
+                    .value_parser(clap::value_parser!(u64))
+                    .num_args(1)
+            )
+        )
+        .subcommand(App::new("shell")
+            .about("run an interactive shell on the database")
+            .arg(
+                Arg::new("path")
+                    .help("the path of the database")
+                    .value_name("PATH")
+                    .required(true)
+                    .num_args(1)
+            )
+        )
         .arg(
             Arg::new("log")
                 .help("print log")
@@ -102,6 +197,16 @@ async fn main() {
 
     let matches = app.get_matches();
 
+    if let Some((name, sub)) = matches.subcommand() {
+        if name != "serve" {
+            if let Err(e) = run_command(name, sub) {
+                eprintln!("error: {:?}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+    }
+
     if let Some(sub) = matches.subcommand_matches("serve") {
         let should_log = sub.contains_id("log");
         Database::set_log(should_log);
@@ -130,6 +235,51 @@ async fn main() {
 
 }
 
+fn path_arg() -> Arg {
+    Arg::new("path")
+        .help("the path of the database")
+        .short('p')
+        .long("path")
+        .value_name("PATH")
+        .required(true)
+        .num_args(1)
+}
+
+fn run_command(name: &str, sub: &ArgMatches) -> Result<()> {
+    let path = Path::new(sub.get_one::<String>("path").unwrap());
+    // only restore is allowed to create a new database
+    if name != "restore" && !path.exists() {
+        return Err(anyhow!("database not found: {}", path.display()));
+    }
+    let db = Database::open_path(path)?;
+
+    match name {
+        "dump" => {
+            let out = sub.get_one::<String>("out").unwrap();
+            commands::dump(&db, Path::new(out))
+        }
+        "restore" => {
+            let dir = sub.get_one::<String>("dir").unwrap();
+            commands::restore(&db, Path::new(dir), sub.get_flag("drop"))
+        }
+        "stats" => commands::stats(&db),
+        "compact" => commands::compact(&db, path),
+        "verify" => commands::verify(&db),
+        "shell" => commands::shell(db),
+        "query" => {
+            let col_name = sub.get_one::<String>("collection").unwrap();
+            let filter = sub.get_one::<String>("filter").unwrap();
+            let filter = commands::json_to_document(serde_json::from_str(filter)?)?;
+            let limit = sub.get_one::<u64>("limit").copied();
+            for doc in commands::query(&db, col_name, filter, limit)? {
+                println!("{}", commands::document_to_json(doc, true));
+            }
+            Ok(())
+        }
+        _ => Err(anyhow!("unknown command: {}", name)),
+    }
+}
+
 pub(crate) async fn start_socket_server(path: String, socket: String, token: CancellationToken) -> Result<(SocketAddr, JoinHandle<()>)> {
     let db = Database::open_path(&path)?;
 
@@ -233,10 +383,13 @@ async fn handle_message<W: AsyncWrite + Unpin>(ctx: AppContext, conn_id: u64, st
             }
             Err(e) => {
                 log::error!("handler error: {:?}", e);
+                let code = e.downcast_ref::<polodb_core::Error>()
+                    .map_or(polodb_core::ErrorCode::Internal, polodb_core::Error::code);
                 let doc = rawdoc! {
                     "ok": 0,
                     "errmsg": e.to_string(),
-                    "code": 1,
+                    "code": code.as_u32() as i32,
+                    "codeName": code.name(),
                 };
                 let reply = Reply::new(message.request_id.unwrap(), doc);
                 reply.write_to(stream).await?;
@@ -0,0 +1,121 @@
+/*
+ * Copyright 2024 Vincent Chan
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *	http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+/*
+ * The C ABI of PoloDB.
+ *
+ * The documents are exchanged as Extended JSON strings, nul-terminated and encoded
+ * in UTF-8: the arguments accept the canonical and the relaxed forms, the results
+ * are in the relaxed form. The strings returned are freed by polodb_free_string.
+ *
+ * The functions returning an int return 0 on success, otherwise the code of the
+ * error, whose message is given by polodb_last_error:
+ *
+ *   1 Internal, 2 InvalidArgument, 3 TypeMismatch, 4 NotFound, 5 AlreadyExists,
+ *   6 DuplicateKey, 7 WriteConflict, 8 Corruption, 9 QuotaExceeded,
+ *   10 ValidationFailed, 11 Killed, 12 Closed, 13 Busy, 14 Transaction, 15 Io,
+ *   16 Encoding, 17 Unsupported, 18 HistoryLost
+ *
+ * A database handle can be used by several threads at once, a cursor by one
+ * thread at a time.
+ */
+
+#ifndef POLODB_H
+#define POLODB_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct PldbDatabase PldbDatabase;
+typedef struct PldbCursor PldbCursor;
+
+/* The version of PoloDB, a static string. */
+const char* polodb_version(void);
+
+/* The message of the last error of the calling thread, NULL if none. The string is
+ * valid until the next failing call of the thread, it's not freed by the caller. */
+const char* polodb_last_error(void);
+
+/* Free a string returned by the functions below. */
+void polodb_free_string(char* value);
+
+/* Open the database at path, creating it if it doesn't exist. */
+int polodb_open(const char* path, PldbDatabase** out);
+
+/* Open the snapshot file at path, read-only. */
+int polodb_open_snapshot(const char* path, PldbDatabase** out);
+
+/* Close the database, once the cursors opened on it are freed. */
+void polodb_close(PldbDatabase* db);
+
+/* The names of the collections, as a JSON array of strings. */
+int polodb_list_collection_names(const PldbDatabase* db, char** out);
+
+int polodb_create_collection(const PldbDatabase* db, const char* name);
+
+int polodb_drop_collection(const PldbDatabase* db, const char* name);
+
+/* Insert the document, out_id is set to the _id of the document if it's not NULL. */
+int polodb_insert_one(const PldbDatabase* db, const char* col, const char* doc, char** out_id);
+
+/* Insert the documents of the JSON array docs, out_ids is set to
+ * the array of their _id if it's not NULL. */
+int polodb_insert_many(const PldbDatabase* db, const char* col, const char* docs, char** out_ids);
+
+/* Find the documents matching filter, all the documents if it's NULL. The options,
+ * such as {"sort": {"code": 1}, "skip": 10, "limit": 10}, may be NULL. */
+int polodb_find(const PldbDatabase* db, const char* col, const char* filter,
+                const char* options, PldbCursor** out);
+
+/* Find the first document matching filter, out is set to NULL if there is none. */
+int polodb_find_one(const PldbDatabase* db, const char* col, const char* filter, char** out);
+
+/* Run the aggregation pipeline, a JSON array of stages. */
+int polodb_aggregate(const PldbDatabase* db, const char* col, const char* pipeline, PldbCursor** out);
+
+/* Read the next document of the cursor, out is set to NULL after the last one. */
+int polodb_cursor_next(PldbCursor* cursor, char** out);
+
+void polodb_cursor_free(PldbCursor* cursor);
+
+int polodb_count_documents(const PldbDatabase* db, const char* col, uint64_t* out);
+
+/* Update the documents matching filter, the counts are set if their pointers are not NULL. */
+int polodb_update_one(const PldbDatabase* db, const char* col, const char* filter,
+                      const char* update, uint64_t* out_matched, uint64_t* out_modified);
+
+int polodb_update_many(const PldbDatabase* db, const char* col, const char* filter,
+                       const char* update, uint64_t* out_matched, uint64_t* out_modified);
+
+/* Delete the documents matching filter, the count is set if its pointer is not NULL. */
+int polodb_delete_one(const PldbDatabase* db, const char* col, const char* filter, uint64_t* out_deleted);
+
+int polodb_delete_many(const PldbDatabase* db, const char* col, const char* filter, uint64_t* out_deleted);
+
+/* Create the index of keys, such as {"code": 1}. The options, such as
+ * {"name": "code_1", "unique": true}, may be NULL. */
+int polodb_create_index(const PldbDatabase* db, const char* col, const char* keys, const char* options);
+
+int polodb_drop_index(const PldbDatabase* db, const char* col, const char* name);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* POLODB_H */
@@ -0,0 +1,519 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The C ABI of PoloDB, declared in `include/polodb.h`, on which the bindings
+//! of the other languages are built.
+//!
+//! The documents are exchanged as Extended JSON strings: the arguments accept the
+//! canonical and the relaxed forms, the results are in the relaxed form. A function
+//! returns `0` on success, otherwise the [`ErrorCode`] of the error, whose message is
+//! given by [`polodb_last_error`]. The strings returned are freed by [`polodb_free_string`].
+//!
+//! The pointers passed to the functions must be valid, the strings nul-terminated.
+//! A database handle can be used by several threads at once, a cursor by one thread
+//! at a time. The handles must not be used after they are freed.
+
+#![allow(clippy::missing_safety_doc)]
+
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use polodb_core::bson::{Bson, Document};
+use polodb_core::{ClientCursor, CollectionT, Database, ErrorCode, IndexModel, IndexOptions};
+
+/// A database opened by [`polodb_open`].
+pub struct PldbDatabase {
+    db: Database,
+}
+
+/// The documents of a find or an aggregation, read by [`polodb_cursor_next`].
+pub struct PldbCursor {
+    cursor: ClientCursor<Document>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+enum CallError {
+    Db(polodb_core::Error),
+    Argument(String),
+    Panic,
+}
+
+impl CallError {
+
+    fn code(&self) -> ErrorCode {
+        match self {
+            CallError::Db(err) => err.code(),
+            CallError::Argument(_) => ErrorCode::InvalidArgument,
+            CallError::Panic => ErrorCode::Internal,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            CallError::Db(err) => err.to_string(),
+            CallError::Argument(message) => message.clone(),
+            CallError::Panic => "the call panicked".to_string(),
+        }
+    }
+
+}
+
+impl From<polodb_core::Error> for CallError {
+    fn from(err: polodb_core::Error) -> Self {
+        CallError::Db(err)
+    }
+}
+
+type CallResult = std::result::Result<(), CallError>;
+
+/// Run the body of a function of the ABI, keeping its error for [`polodb_last_error`].
+fn call(body: impl FnOnce() -> CallResult) -> c_int {
+    let result = catch_unwind(AssertUnwindSafe(body)).unwrap_or(Err(CallError::Panic));
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            let message = CString::new(err.message().replace('\0', " ")).unwrap_or_default();
+            LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+            err.code().as_u32() as c_int
+        }
+    }
+}
+
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<&'a str, CallError> {
+    if ptr.is_null() {
+        return Err(CallError::Argument(format!("'{}' is null", name)));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| CallError::Argument(format!("'{}' is not UTF-8", name)))
+}
+
+unsafe fn json_arg(ptr: *const c_char, name: &str) -> Result<Bson, CallError> {
+    let text = str_arg(ptr, name)?;
+    let value: serde_json::Value = serde_json::from_str(text)
+        .map_err(|err| CallError::Argument(format!("'{}' is not JSON: {}", name, err)))?;
+    Bson::try_from(value)
+        .map_err(|err| CallError::Argument(format!("'{}' is not Extended JSON: {}", name, err)))
+}
+
+unsafe fn doc_arg(ptr: *const c_char, name: &str) -> Result<Document, CallError> {
+    match json_arg(ptr, name)? {
+        Bson::Document(doc) => Ok(doc),
+        _ => Err(CallError::Argument(format!("'{}' is not a document", name))),
+    }
+}
+
+/// The document of an optional argument, an empty document if the pointer is null.
+unsafe fn opt_doc_arg(ptr: *const c_char, name: &str) -> Result<Document, CallError> {
+    if ptr.is_null() {
+        return Ok(Document::new());
+    }
+    doc_arg(ptr, name)
+}
+
+unsafe fn docs_arg(ptr: *const c_char, name: &str) -> Result<Vec<Document>, CallError> {
+    match json_arg(ptr, name)? {
+        Bson::Array(items) => items.into_iter()
+            .map(|item| match item {
+                Bson::Document(doc) => Ok(doc),
+                _ => Err(CallError::Argument(format!("'{}' holds a value which is not a document", name))),
+            })
+            .collect(),
+        _ => Err(CallError::Argument(format!("'{}' is not an array", name))),
+    }
+}
+
+unsafe fn db_arg<'a>(db: *const PldbDatabase) -> Result<&'a Database, CallError> {
+    db.as_ref()
+        .map(|db| &db.db)
+        .ok_or_else(|| CallError::Argument("'db' is null".to_string()))
+}
+
+unsafe fn out_arg<'a, T>(out: *mut T, name: &str) -> Result<&'a mut T, CallError> {
+    out.as_mut().ok_or_else(|| CallError::Argument(format!("'{}' is null", name)))
+}
+
+fn to_json(value: Bson) -> *mut c_char {
+    let text = value.into_relaxed_extjson().to_string();
+    // the JSON escapes the nul characters of the strings
+    CString::new(text).unwrap_or_default().into_raw()
+}
+
+/// The version of PoloDB, a static string.
+#[no_mangle]
+pub extern "C" fn polodb_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// The message of the last error of the calling thread, null if none. The string is
+/// valid until the next failing call of the thread, it's not freed by the caller.
+#[no_mangle]
+pub extern "C" fn polodb_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// Free a string returned by the functions of the ABI.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_free_string(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
+
+/// Open the database at `path`, creating it if it doesn't exist.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_open(path: *const c_char, out: *mut *mut PldbDatabase) -> c_int {
+    call(|| {
+        let out = out_arg(out, "out")?;
+        let db = Database::open_path(str_arg(path, "path")?)?;
+        *out = Box::into_raw(Box::new(PldbDatabase { db }));
+        Ok(())
+    })
+}
+
+/// Open the snapshot file at `path`, read-only.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_open_snapshot(path: *const c_char, out: *mut *mut PldbDatabase) -> c_int {
+    call(|| {
+        let out = out_arg(out, "out")?;
+        let db = Database::open_snapshot(str_arg(path, "path")?)?;
+        *out = Box::into_raw(Box::new(PldbDatabase { db }));
+        Ok(())
+    })
+}
+
+/// Close the database, once the cursors opened on it are freed.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_close(db: *mut PldbDatabase) {
+    if !db.is_null() {
+        drop(Box::from_raw(db));
+    }
+}
+
+/// The names of the collections, as a JSON array of strings.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_list_collection_names(db: *const PldbDatabase, out: *mut *mut c_char) -> c_int {
+    call(|| {
+        let out = out_arg(out, "out")?;
+        let names = db_arg(db)?.list_collection_names()?;
+        *out = to_json(Bson::Array(names.into_iter().map(Bson::String).collect()));
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn polodb_create_collection(db: *const PldbDatabase, name: *const c_char) -> c_int {
+    call(|| {
+        db_arg(db)?.create_collection(str_arg(name, "name")?)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn polodb_drop_collection(db: *const PldbDatabase, name: *const c_char) -> c_int {
+    call(|| {
+        db_arg(db)?.collection::<Document>(str_arg(name, "name")?).drop()?;
+        Ok(())
+    })
+}
+
+/// Insert the document, `out_id` is set to the `_id` of the document if it's not null.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_insert_one(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    doc: *const c_char,
+    out_id: *mut *mut c_char,
+) -> c_int {
+    call(|| {
+        let collection = db_arg(db)?.collection::<Document>(str_arg(col, "col")?);
+        let result = collection.insert_one(doc_arg(doc, "doc")?)?;
+        if let Some(out_id) = out_id.as_mut() {
+            *out_id = to_json(result.inserted_id);
+        }
+        Ok(())
+    })
+}
+
+/// Insert the documents of the JSON array `docs`, `out_ids` is set to
+/// the array of their `_id` if it's not null.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_insert_many(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    docs: *const c_char,
+    out_ids: *mut *mut c_char,
+) -> c_int {
+    call(|| {
+        let collection = db_arg(db)?.collection::<Document>(str_arg(col, "col")?);
+        let docs = docs_arg(docs, "docs")?;
+        let count = docs.len();
+        let mut result = collection.insert_many(docs)?;
+        if let Some(out_ids) = out_ids.as_mut() {
+            let ids = (0..count)
+                .map(|i| result.inserted_ids.remove(&i).unwrap_or(Bson::Null))
+                .collect();
+            *out_ids = to_json(Bson::Array(ids));
+        }
+        Ok(())
+    })
+}
+
+/// Find the documents matching `filter`, all the documents if it's null. The
+/// `options` may give the `sort`, `skip` and `limit` of the find, it may be null.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_find(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    filter: *const c_char,
+    options: *const c_char,
+    out: *mut *mut PldbCursor,
+) -> c_int {
+    call(|| {
+        let out = out_arg(out, "out")?;
+        let collection = db_arg(db)?.collection::<Document>(str_arg(col, "col")?);
+        let options = opt_doc_arg(options, "options")?;
+        let mut find = collection.find(opt_doc_arg(filter, "filter")?);
+        for (key, value) in options {
+            let count = || match value.as_i64().or_else(|| value.as_i32().map(i64::from)) {
+                Some(count) if count >= 0 => Ok(count as u64),
+                _ => Err(CallError::Argument(format!("'{}' is not a count", key))),
+            };
+            find = match (key.as_str(), &value) {
+                ("sort", Bson::Document(sort)) => find.sort(sort.clone()),
+                ("skip", _) => find.skip(count()?),
+                ("limit", _) => find.limit(count()?),
+                _ => return Err(CallError::Argument(format!("unknown option '{}'", key))),
+            };
+        }
+        *out = Box::into_raw(Box::new(PldbCursor { cursor: find.run()? }));
+        Ok(())
+    })
+}
+
+/// Find the first document matching `filter`, `out` is set to null if there is none.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_find_one(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    filter: *const c_char,
+    out: *mut *mut c_char,
+) -> c_int {
+    call(|| {
+        let out = out_arg(out, "out")?;
+        let collection = db_arg(db)?.collection::<Document>(str_arg(col, "col")?);
+        *out = match collection.find_one(opt_doc_arg(filter, "filter")?)? {
+            Some(doc) => to_json(Bson::Document(doc)),
+            None => ptr::null_mut(),
+        };
+        Ok(())
+    })
+}
+
+/// Run the aggregation `pipeline`, a JSON array of stages.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_aggregate(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    pipeline: *const c_char,
+    out: *mut *mut PldbCursor,
+) -> c_int {
+    call(|| {
+        let out = out_arg(out, "out")?;
+        let collection = db_arg(db)?.collection::<Document>(str_arg(col, "col")?);
+        let cursor = collection.aggregate(docs_arg(pipeline, "pipeline")?).run()?;
+        *out = Box::into_raw(Box::new(PldbCursor { cursor }));
+        Ok(())
+    })
+}
+
+/// Read the next document of the cursor, `out` is set to null after the last one.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_cursor_next(cursor: *mut PldbCursor, out: *mut *mut c_char) -> c_int {
+    call(|| {
+        let out = out_arg(out, "out")?;
+        let cursor = cursor.as_mut().ok_or_else(|| CallError::Argument("'cursor' is null".to_string()))?;
+        *out = match cursor.cursor.next() {
+            Some(doc) => to_json(Bson::Document(doc?)),
+            None => ptr::null_mut(),
+        };
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn polodb_cursor_free(cursor: *mut PldbCursor) {
+    if !cursor.is_null() {
+        drop(Box::from_raw(cursor));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn polodb_count_documents(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    out: *mut u64,
+) -> c_int {
+    call(|| {
+        let out = out_arg(out, "out")?;
+        *out = db_arg(db)?.collection::<Document>(str_arg(col, "col")?).count_documents()?;
+        Ok(())
+    })
+}
+
+unsafe fn update(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    filter: *const c_char,
+    update: *const c_char,
+    many: bool,
+    out_matched: *mut u64,
+    out_modified: *mut u64,
+) -> c_int {
+    call(|| {
+        let collection = db_arg(db)?.collection::<Document>(str_arg(col, "col")?);
+        let filter = opt_doc_arg(filter, "filter")?;
+        let update = doc_arg(update, "update")?;
+        let result = if many {
+            collection.update_many(filter, update)?
+        } else {
+            collection.update_one(filter, update)?
+        };
+        if let Some(out_matched) = out_matched.as_mut() {
+            *out_matched = result.matched_count;
+        }
+        if let Some(out_modified) = out_modified.as_mut() {
+            *out_modified = result.modified_count;
+        }
+        Ok(())
+    })
+}
+
+/// Update the first document matching `filter`, the counts are set if their pointers are not null.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_update_one(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    filter: *const c_char,
+    update_doc: *const c_char,
+    out_matched: *mut u64,
+    out_modified: *mut u64,
+) -> c_int {
+    update(db, col, filter, update_doc, false, out_matched, out_modified)
+}
+
+/// Update the documents matching `filter`, the counts are set if their pointers are not null.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_update_many(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    filter: *const c_char,
+    update_doc: *const c_char,
+    out_matched: *mut u64,
+    out_modified: *mut u64,
+) -> c_int {
+    update(db, col, filter, update_doc, true, out_matched, out_modified)
+}
+
+unsafe fn delete(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    filter: *const c_char,
+    many: bool,
+    out_deleted: *mut u64,
+) -> c_int {
+    call(|| {
+        let collection = db_arg(db)?.collection::<Document>(str_arg(col, "col")?);
+        let filter = opt_doc_arg(filter, "filter")?;
+        let result = if many {
+            collection.delete_many(filter)?
+        } else {
+            collection.delete_one(filter)?
+        };
+        if let Some(out_deleted) = out_deleted.as_mut() {
+            *out_deleted = result.deleted_count;
+        }
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn polodb_delete_one(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    filter: *const c_char,
+    out_deleted: *mut u64,
+) -> c_int {
+    delete(db, col, filter, false, out_deleted)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn polodb_delete_many(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    filter: *const c_char,
+    out_deleted: *mut u64,
+) -> c_int {
+    delete(db, col, filter, true, out_deleted)
+}
+
+/// Create the index of `keys`, such as `{"code": 1}`. The `options` are the
+/// options of the index in camel case, such as `{"name": "code_1", "unique": true}`,
+/// they may be null.
+#[no_mangle]
+pub unsafe extern "C" fn polodb_create_index(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    keys: *const c_char,
+    options: *const c_char,
+) -> c_int {
+    call(|| {
+        let collection = db_arg(db)?.collection::<Document>(str_arg(col, "col")?);
+        let options = if options.is_null() {
+            None
+        } else {
+            let options = doc_arg(options, "options")?;
+            Some(polodb_core::bson::from_document::<IndexOptions>(options)
+                .map_err(|err| CallError::Argument(format!("invalid index options: {}", err)))?)
+        };
+        collection.create_index(IndexModel {
+            keys: doc_arg(keys, "keys")?,
+            options,
+        })?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn polodb_drop_index(
+    db: *const PldbDatabase,
+    col: *const c_char,
+    name: *const c_char,
+) -> c_int {
+    call(|| {
+        let collection = db_arg(db)?.collection::<Document>(str_arg(col, "col")?);
+        collection.drop_index(str_arg(name, "name")?)?;
+        Ok(())
+    })
+}
@@ -0,0 +1,151 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+use polodb_capi::*;
+
+fn c(value: &str) -> CString {
+    CString::new(value).unwrap()
+}
+
+/// Take a string returned by the ABI.
+unsafe fn take(value: *mut c_char) -> Option<String> {
+    if value.is_null() {
+        return None;
+    }
+    let text = CStr::from_ptr(value).to_str().unwrap().to_string();
+    polodb_free_string(value);
+    Some(text)
+}
+
+unsafe fn last_error() -> String {
+    CStr::from_ptr(polodb_last_error()).to_str().unwrap().to_string()
+}
+
+unsafe fn open(name: &str) -> *mut PldbDatabase {
+    let path = std::env::temp_dir().join(format!("{}-db", name));
+    let _ = std::fs::remove_dir_all(&path);
+    let path = c(path.to_str().unwrap());
+    let mut db = ptr::null_mut();
+    assert_eq!(polodb_open(path.as_ptr(), &mut db), 0);
+    db
+}
+
+unsafe fn read_all(cursor: *mut PldbCursor) -> Vec<String> {
+    let mut docs = vec![];
+    loop {
+        let mut doc = ptr::null_mut();
+        assert_eq!(polodb_cursor_next(cursor, &mut doc), 0);
+        match take(doc) {
+            Some(doc) => docs.push(doc),
+            None => break,
+        }
+    }
+    polodb_cursor_free(cursor);
+    docs
+}
+
+#[test]
+fn test_capi_crud() {
+    unsafe {
+        let db = open("test-capi-crud");
+        let col = c("countries");
+
+        let mut id = ptr::null_mut();
+        let doc = c(r#"{"_id": 1, "code": "FR", "population": {"$numberLong": "68000000"}}"#);
+        assert_eq!(polodb_insert_one(db, col.as_ptr(), doc.as_ptr(), &mut id), 0);
+        assert_eq!(take(id).unwrap(), "1");
+        let mut ids = ptr::null_mut();
+        let docs = c(r#"[{"_id": 2, "code": "DE"}, {"_id": 3, "code": "JP", "asia": true}]"#);
+        assert_eq!(polodb_insert_many(db, col.as_ptr(), docs.as_ptr(), &mut ids), 0);
+        assert_eq!(take(ids).unwrap(), "[2,3]");
+
+        let mut count = 0;
+        assert_eq!(polodb_count_documents(db, col.as_ptr(), &mut count), 0);
+        assert_eq!(count, 3);
+
+        let mut cursor = ptr::null_mut();
+        let options = c(r#"{"sort": {"code": -1}, "limit": 2}"#);
+        assert_eq!(polodb_find(db, col.as_ptr(), ptr::null(), options.as_ptr(), &mut cursor), 0);
+        assert_eq!(read_all(cursor), vec![
+            r#"{"_id":3,"code":"JP","asia":true}"#.to_string(),
+            r#"{"_id":1,"code":"FR","population":68000000}"#.to_string(),
+        ]);
+
+        let (mut matched, mut modified) = (0, 0);
+        let filter = c(r#"{"code": {"$in": ["FR", "DE"]}}"#);
+        let update = c(r#"{"$set": {"europe": true}}"#);
+        assert_eq!(polodb_update_many(db, col.as_ptr(), filter.as_ptr(), update.as_ptr(), &mut matched, &mut modified), 0);
+        assert_eq!((matched, modified), (2, 2));
+
+        let mut found = ptr::null_mut();
+        let filter = c(r#"{"code": "DE"}"#);
+        assert_eq!(polodb_find_one(db, col.as_ptr(), filter.as_ptr(), &mut found), 0);
+        assert_eq!(take(found).unwrap(), r#"{"_id":2,"code":"DE","europe":true}"#);
+
+        let pipeline = c(r#"[{"$match": {"europe": true}}, {"$count": "count"}]"#);
+        assert_eq!(polodb_aggregate(db, col.as_ptr(), pipeline.as_ptr(), &mut cursor), 0);
+        assert_eq!(read_all(cursor), vec![r#"{"count":2}"#.to_string()]);
+
+        let mut deleted = 0;
+        assert_eq!(polodb_delete_many(db, col.as_ptr(), filter.as_ptr(), &mut deleted), 0);
+        assert_eq!(deleted, 1);
+        assert_eq!(polodb_find_one(db, col.as_ptr(), filter.as_ptr(), &mut found), 0);
+        assert!(found.is_null());
+
+        let mut names = ptr::null_mut();
+        assert_eq!(polodb_list_collection_names(db, &mut names), 0);
+        assert_eq!(take(names).unwrap(), r#"["countries"]"#);
+
+        polodb_close(db);
+    }
+}
+
+#[test]
+fn test_capi_errors() {
+    unsafe {
+        let db = open("test-capi-errors");
+        let col = c("countries");
+        let keys = c(r#"{"code": 1}"#);
+        let options = c(r#"{"name": "code_unique", "unique": true}"#);
+        assert_eq!(polodb_create_index(db, col.as_ptr(), keys.as_ptr(), options.as_ptr()), 0);
+
+        let doc = c(r#"{"code": "FR"}"#);
+        assert_eq!(polodb_insert_one(db, col.as_ptr(), doc.as_ptr(), ptr::null_mut()), 0);
+        // DuplicateKey
+        assert_eq!(polodb_insert_one(db, col.as_ptr(), doc.as_ptr(), ptr::null_mut()), 6);
+        assert!(last_error().contains("code_unique"));
+
+        // InvalidArgument
+        let not_json = c("{code: FR}");
+        assert_eq!(polodb_insert_one(db, col.as_ptr(), not_json.as_ptr(), ptr::null_mut()), 2);
+        assert!(last_error().contains("is not JSON"));
+        let not_doc = c("[1, 2]");
+        assert_eq!(polodb_insert_one(db, col.as_ptr(), not_doc.as_ptr(), ptr::null_mut()), 2);
+        assert_eq!(polodb_insert_one(ptr::null(), col.as_ptr(), doc.as_ptr(), ptr::null_mut()), 2);
+        let mut cursor = ptr::null_mut();
+        let options = c(r#"{"limit": -1}"#);
+        assert_eq!(polodb_find(db, col.as_ptr(), ptr::null(), options.as_ptr(), &mut cursor), 2);
+        assert!(cursor.is_null());
+
+        let name = c("code_unique");
+        assert_eq!(polodb_drop_index(db, col.as_ptr(), name.as_ptr()), 0);
+        assert_eq!(polodb_insert_one(db, col.as_ptr(), doc.as_ptr(), ptr::null_mut()), 0);
+
+        assert!(CStr::from_ptr(polodb_version()).to_str().unwrap().starts_with("5."));
+        polodb_close(db);
+    }
+}
@@ -13,17 +13,19 @@
 // limitations under the License.
 
 use std::sync::Weak;
-use bson::Document;
+use bson::{Bson, Document};
 use serde::de::DeserializeOwned;
 use crate::{ClientCursor, Error, Result};
 use crate::db::db_inner::DatabaseInner;
 use crate::transaction::TransactionInner;
+use crate::vm::QueryPlan;
 
 pub struct Aggregate<'a, 'b, T: DeserializeOwned + Send + Sync = Document> {
     db: Weak<DatabaseInner>,
     name: &'a str,
     pipeline: Vec<Document>,
     txn: Option<&'b TransactionInner>,
+    comment: Option<Bson>,
     _phantom: std::marker::PhantomData<T>,
 }
 
@@ -34,10 +36,18 @@ impl <'a, 'b , T: DeserializeOwned + Send + Sync> Aggregate<'a, 'b, T> {
             name,
             pipeline,
             txn,
+            comment: None,
             _phantom: Default::default(),
         }
     }
 
+    /// Tag the aggregation with `comment`, shown in [`crate::Database::current_ops`]
+    /// and the entries of the profiler instead of the `$comment` of the first `$match`.
+    pub fn comment(mut self, comment: impl Into<Bson>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
     pub fn run(self) -> Result<ClientCursor<T>> {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
         let txn = match self.txn {
@@ -46,7 +56,7 @@ impl <'a, 'b , T: DeserializeOwned + Send + Sync> Aggregate<'a, 'b, T> {
                 db.start_transaction()?
             }
         };
-        db.aggregate_with_owned_session(self.name, self.pipeline, txn.clone())
+        db.aggregate_with_plan(self.name, self.pipeline, QueryPlan::default(), self.comment, txn.clone())
     }
 
     pub fn with_type<U>(self) -> Aggregate<'a, 'b, U>
@@ -56,6 +66,7 @@ impl <'a, 'b , T: DeserializeOwned + Send + Sync> Aggregate<'a, 'b, T> {
             name: self.name,
             pipeline: self.pipeline,
             txn: self.txn,
+            comment: self.comment,
             _phantom: Default::default(),
         }
     }
@@ -0,0 +1,62 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Weak;
+use crate::db::db_inner::DatabaseInner;
+use crate::{Error, Result};
+use crate::transaction::TransactionInner;
+
+/// Copy a collection to a new collection, without going through the documents one by one.
+///
+/// The options of the collection, such as its validator, are not copied,
+/// and the change streams and the audit log don't observe the copy.
+pub struct CloneTo<'a, 'b> {
+    db: Weak<DatabaseInner>,
+    name: &'a str,
+    target: &'a str,
+    txn: Option<&'b TransactionInner>,
+    indexes: bool,
+}
+
+impl<'a, 'b> CloneTo<'a, 'b> {
+    pub(crate) fn new(db: Weak<DatabaseInner>, name: &'a str, target: &'a str, txn: Option<&'b TransactionInner>) -> CloneTo<'a, 'b> {
+        CloneTo {
+            db,
+            name,
+            target,
+            txn,
+            indexes: true,
+        }
+    }
+
+    /// Copy the indexes of the collection too, the default.
+    pub fn indexes(mut self, indexes: bool) -> Self {
+        self.indexes = indexes;
+        self
+    }
+
+    /// Run the copy, return the number of copied documents.
+    pub fn run(self) -> Result<u64> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        match self.txn {
+            Some(txn) => db.clone_collection(self.name, self.target, self.indexes, txn),
+            None => {
+                let txn = db.start_transaction()?;
+                let count = db.clone_collection(self.name, self.target, self.indexes, &txn)?;
+                txn.commit()?;
+                Ok(count)
+            }
+        }
+    }
+}
@@ -12,12 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Weak;
-use bson::{Document, doc};
+use std::sync::{Arc, Weak};
+use bson::{Bson, Document, doc};
 use serde::de::DeserializeOwned;
 use crate::db::db_inner::DatabaseInner;
 use crate::{ClientCursor, Error, Result};
+use crate::db::client_cursor::Quarantine;
+use crate::index::IndexRange;
+use crate::options::{DecodeMode, Hint};
 use crate::transaction::TransactionInner;
+use crate::query_cache::{QueryCache, QueryKey, ResultRecorder};
+use crate::vm::{QueryPlan, ResidualFilter};
 
 pub struct Find<'a, 'b, T: DeserializeOwned + Send + Sync> {
     db: Weak<DatabaseInner>,
@@ -27,6 +32,12 @@ pub struct Find<'a, 'b, T: DeserializeOwned + Send + Sync> {
     skip: Option<u64>,
     limit: Option<u64>,
     sort: Option<Document>,
+    filter_fn: Option<ResidualFilter>,
+    collection_scan: bool,
+    decode_mode: DecodeMode,
+    hint: Option<Hint>,
+    min: Option<Document>,
+    max: Option<Document>,
     _phantom: std::marker::PhantomData<T>,
 }
 
@@ -40,6 +51,12 @@ impl <'a, 'b , T: DeserializeOwned + Send + Sync> Find<'a, 'b, T> {
             skip: None,
             limit: None,
             sort: None,
+            filter_fn: None,
+            collection_scan: false,
+            decode_mode: DecodeMode::Strict,
+            hint: None,
+            min: None,
+            max: None,
             _phantom: Default::default(),
         }
     }
@@ -59,16 +76,160 @@ impl <'a, 'b , T: DeserializeOwned + Send + Sync> Find<'a, 'b, T> {
         self
     }
 
+    /// Scan the collection in the order of the primary keys instead of finding
+    /// the documents by the primary key or an index, to compare with the plan
+    /// chosen by the planner. Sort by `{ "$natural": -1 }` for the reverse order.
+    pub fn collection_scan(mut self) -> Self {
+        self.collection_scan = true;
+        self
+    }
+
+    /// What the cursor does with the stored documents it fails to decode,
+    /// as documents or as `T`. By default, they fail the iteration.
+    /// Otherwise, the documents are read by scanning the collection
+    /// or by the indexes of the filter, and sorted in memory.
+    pub fn decode_mode(mut self, mode: DecodeMode) -> Self {
+        self.decode_mode = mode;
+        self
+    }
+
+    /// Find the documents with the index named `hint` or of the keys `hint`,
+    /// `{ "$natural": 1 }` to scan the collection.
+    pub fn hint(mut self, hint: impl Into<Hint>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    /// Only read the keys of the index of the hint from `min`, included,
+    /// such as `{ "created": DateTime }` for the index `{ "created": 1 }`.
+    /// The documents are given in the order of the index unless they are sorted,
+    /// the documents missing the field of the index are not found.
+    pub fn min(mut self, min: Document) -> Self {
+        self.min = Some(min);
+        self
+    }
+
+    /// Only read the keys of the index of the hint up to `max`, excluded.
+    /// See [`Find::min`].
+    pub fn max(mut self, max: Document) -> Self {
+        self.max = Some(max);
+        self
+    }
+
+    /// Tag the query with `comment`, shown in [`crate::Database::current_ops`] and
+    /// the entries of the profiler. It's the same as a `$comment` in the filter.
+    pub fn comment(mut self, comment: impl Into<Bson>) -> Self {
+        self.filter.insert("$comment", comment.into());
+        self
+    }
+
+    /// Only keep the documents for which `predicate` returns true, for the conditions
+    /// the filter can't express. The predicate is called on the documents matching
+    /// the filter, found by the indexes if the filter can use them, before they are
+    /// sorted, skipped and limited.
+    pub fn filter_fn<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&Document) -> bool + Send + Sync + 'static,
+    {
+        self.filter_fn = Some(ResidualFilter(Arc::new(predicate)));
+        self
+    }
+
+    /// Like [`Find::filter_fn`], with the documents deserialized as `T`.
+    /// The documents which can't be deserialized are left out.
+    pub fn filter_typed<F>(self, predicate: F) -> Self
+    where
+        T: 'static,
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        self.filter_fn(move |doc: &Document| {
+            bson::from_document::<T>(doc.clone()).is_ok_and(|value| predicate(&value))
+        })
+    }
+
+    pub(crate) fn has_filter(&self) -> bool {
+        self.filter.keys().any(|key| key != "$comment") || self.filter_fn.is_some()
+    }
+
+    pub(crate) fn has_sort(&self) -> bool {
+        self.sort.is_some()
+    }
+
+    /// The cache of the result, with its key and the generation to cache it at,
+    /// unless the find is in a transaction or can't be identified by its options.
+    fn query_cache(&self, db: &DatabaseInner) -> Option<(QueryCache, QueryKey, u64)> {
+        if self.txn.is_some() || self.filter_fn.is_some() || self.collection_scan || self.decode_mode != DecodeMode::Strict || self.hint.is_some() {
+            return None;
+        }
+        let cache = db.query_cache()?;
+        // before reading, a write committed after is not missed
+        let generation = cache.generation();
+        let key = QueryKey::new(self.name, &self.filter, self.sort.as_ref(), self.skip, self.limit)?;
+        Some((cache.clone(), key, generation))
+    }
+
     pub fn run(self) -> Result<ClientCursor<T>> {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        self.check_range()?;
+        let query_cache = self.query_cache(&db);
+        if let Some((cache, key, _)) = &query_cache {
+            if let Some(docs) = cache.get(key) {
+                db.metrics().add_query_cache_hit();
+                return Ok(ClientCursor::cached(docs));
+            }
+            db.metrics().add_query_cache_miss();
+        }
         let txn = match self.txn {
             Some(txn) => txn.clone(),
             None => {
                 db.start_transaction()?
             }
         };
-        match (self.skip.as_ref(), self.limit.as_ref(), self.sort.as_ref()) {
-            (None, None, None) => {
+        let recorder = match query_cache {
+            Some((cache, key, generation)) if db.is_query_cacheable(self.name, &txn)? => {
+                Some(ResultRecorder::new(&cache, key, generation))
+            }
+            _ => None,
+        };
+        let mut cursor = self.open(&db, txn)?;
+        if let Some(recorder) = recorder {
+            cursor.set_recorder(recorder);
+        }
+        Ok(cursor)
+    }
+
+    /// Run the find, and describe how it found the documents: by a scan of the
+    /// collection (`COLLSCAN`), an index (`IXSCAN`) or the `_id` (`IDHACK`), which
+    /// index, the estimated and actual numbers of documents examined, and the
+    /// program executed by the VM. The query cache is neither read nor written.
+    pub fn explain(self) -> Result<Document> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        self.check_range()?;
+        let txn = match self.txn {
+            Some(txn) => txn.clone(),
+            None => db.start_transaction()?,
+        };
+        let name = self.name;
+        let filter = self.filter.clone();
+        let cursor = self.open(&db, txn.clone())?;
+        db.explain(name, filter, cursor, &txn)
+    }
+
+    fn check_range(&self) -> Result<()> {
+        if self.hint.is_none() && (self.min.is_some() || self.max.is_some()) {
+            return Err(Error::InvalidIndexBounds("min and max need the hint of an index".to_string()));
+        }
+        Ok(())
+    }
+
+    fn open(self, db: &DatabaseInner, txn: TransactionInner) -> Result<ClientCursor<T>> {
+        let lenient = match self.decode_mode {
+            DecodeMode::Strict => None,
+            DecodeMode::Lenient => Some(None),
+            DecodeMode::Quarantine(name) => Some(Some(Quarantine::new(self.db.clone(), name))),
+        };
+        let mut cursor = match (self.skip.as_ref(), self.limit.as_ref(), self.sort.as_ref(), self.filter_fn.as_ref()) {
+            (None, None, None, None) if !self.collection_scan && lenient.is_none() && self.hint.is_none() => {
                 db.find_with_owned_session(self.name, self.filter, txn)
             }
             _ => {
@@ -96,8 +257,28 @@ impl <'a, 'b , T: DeserializeOwned + Send + Sync> Find<'a, 'b, T> {
                     });
                 }
 
-                db.aggregate_with_owned_session(self.name, pipeline, txn)
+                let plan = QueryPlan {
+                    collection_scan: self.collection_scan,
+                    residual_filter: self.filter_fn,
+                    lenient: lenient.is_some(),
+                    ..Default::default()
+                };
+                match self.hint {
+                    Some(hint) => {
+                        let range = IndexRange {
+                            hint,
+                            min: self.min,
+                            max: self.max,
+                        };
+                        db.find_with_hint(self.name, pipeline, &range, plan, txn)
+                    }
+                    None => db.aggregate_with_plan(self.name, pipeline, plan, None, txn),
+                }
             }
+        }?;
+        if let Some(quarantine) = lenient {
+            cursor.set_lenient(self.name.to_string(), quarantine);
         }
+        Ok(cursor)
     }
 }
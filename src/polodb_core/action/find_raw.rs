@@ -0,0 +1,62 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use bson::Document;
+use crate::action::Find;
+use crate::db::client_cursor::RawCursor;
+use crate::Result;
+
+/// Find the documents matching a filter and give them in their encoding.
+///
+/// The documents are not deserialized and serialized again when they are
+/// given as they are stored. If nothing reads the fields of the documents,
+/// they are not decoded at all.
+pub struct FindRaw<'a, 'b> {
+    find: Find<'a, 'b, Document>,
+}
+
+impl<'a, 'b> FindRaw<'a, 'b> {
+    pub(crate) fn new(find: Find<'a, 'b, Document>) -> FindRaw<'a, 'b> {
+        FindRaw {
+            find,
+        }
+    }
+
+    pub fn skip(mut self, skip: u64) -> Self {
+        self.find = self.find.skip(skip);
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.find = self.find.limit(limit);
+        self
+    }
+
+    pub fn sort(mut self, sort: Document) -> Self {
+        self.find = self.find.sort(sort);
+        self
+    }
+
+    pub fn run(self) -> Result<RawCursor> {
+        let lazy = !self.find.has_filter() && !self.find.has_sort();
+        let sorted = self.find.has_sort();
+        let mut cursor = self.find.run()?;
+        // the rows sorted in memory are not the documents last read
+        if !sorted || cursor.reads_index_order() {
+            cursor.set_raw_rows(lazy);
+        }
+        Ok(RawCursor::new(cursor))
+    }
+}
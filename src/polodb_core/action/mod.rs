@@ -14,6 +14,18 @@
 
 mod find;
 mod aggregate;
+mod watch;
+mod clone_to;
+mod paginate;
+mod find_raw;
+#[cfg(feature = "arrow")]
+mod to_arrow;
 
 pub use find::Find;
 pub use aggregate::Aggregate;
+pub use watch::Watch;
+pub use clone_to::CloneTo;
+pub use paginate::Paginate;
+pub use find_raw::FindRaw;
+#[cfg(feature = "arrow")]
+pub use to_arrow::ToArrow;
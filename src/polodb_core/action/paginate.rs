@@ -0,0 +1,291 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Write;
+use std::sync::Weak;
+use bson::{Bson, Document};
+use bson::spec::BinarySubtype;
+use serde::de::DeserializeOwned;
+use crate::db::db_inner::DatabaseInner;
+use crate::{Error, Result};
+use crate::index::IndexPosition;
+use crate::results::Page;
+use crate::transaction::TransactionInner;
+
+/// The first byte of the tokens of the sorted pages, no encoded primary key starts with it.
+const SORTED_TOKEN_TAG: u8 = 0;
+
+/// Read the documents matching a filter page by page, in the order of their `_id`,
+/// or in the order of a field with [`Paginate::sort`].
+///
+/// Each page ends with a continuation token encoding the position of its last
+/// document, the next page starts the scan right after this position instead of
+/// skipping the documents of the previous pages. The token is a string which can
+/// be kept to resume the reading later, in another process.
+///
+/// The position doesn't depend on the documents of the previous pages, so the
+/// reading continues as the collection changes between the pages: the documents
+/// inserted after the position are returned, the documents deleted or moved before
+/// the position are not, and a document moved from before to after the position
+/// is returned again.
+pub struct Paginate<'a, 'b, T: DeserializeOwned + Send + Sync> {
+    db: Weak<DatabaseInner>,
+    name: &'a str,
+    txn: Option<&'b TransactionInner>,
+    filter: Document,
+    page_size: u64,
+    sort: Option<Document>,
+    after: Option<String>,
+    _phantom: std::marker::PhantomData<T>,
+}
+
+impl<'a, 'b, T: DeserializeOwned + Send + Sync> Paginate<'a, 'b, T> {
+    pub(crate) fn new(
+        db: Weak<DatabaseInner>,
+        name: &'a str,
+        txn: Option<&'b TransactionInner>,
+        filter: Document,
+        page_size: u64,
+    ) -> Paginate<'a, 'b, T> {
+        Paginate {
+            db,
+            name,
+            txn,
+            filter,
+            page_size,
+            sort: None,
+            after: None,
+            _phantom: Default::default(),
+        }
+    }
+
+    /// Read the documents in the order of one field, such as `{ "created": -1 }`,
+    /// the documents of the same value in the order of their `_id`. The pages are
+    /// read from an index of the field if there is one, otherwise each page is
+    /// found by reading all the matching documents.
+    ///
+    /// The token of a page can only be used with the same sort.
+    pub fn sort(mut self, sort: Document) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    /// Get the page following the page which returned `token`.
+    pub fn after(mut self, token: impl Into<String>) -> Self {
+        self.after = Some(token.into());
+        self
+    }
+
+    pub fn run(self) -> Result<Page<T>> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let order = self.sort.as_ref().map(PageOrder::new).transpose()?;
+        let txn = match self.txn {
+            Some(txn) => txn.clone(),
+            None => db.start_transaction()?,
+        };
+        match order {
+            Some(order) => self.run_sorted(&db, order, txn),
+            None => self.run_by_id(&db, txn),
+        }
+    }
+
+    fn run_by_id(self, db: &DatabaseInner, txn: TransactionInner) -> Result<Page<T>> {
+        let after = match self.after.as_deref() {
+            Some(token) => {
+                let key = decode_token(token)?;
+                if key[0] == SORTED_TOKEN_TAG {
+                    return Err(Error::InvalidContinuationToken(token.to_string()));
+                }
+                Some(key)
+            }
+            None => None,
+        };
+        let mut cursor = db.find_page::<T>(self.name, self.filter, after, txn)?;
+
+        let mut items = Vec::new();
+        let mut last_id = None;
+        while (items.len() as u64) < self.page_size && cursor.advance()? {
+            last_id = cursor.get().as_document().and_then(|doc| doc.get("_id")).cloned();
+            items.push(cursor.deserialize_current()?);
+        }
+
+        let next_token = match last_id {
+            Some(id) if cursor.advance()? => Some(encode_token(crate::utils::bson::stacked_key([&id])?)),
+            _ => None,
+        };
+
+        Ok(Page {
+            items,
+            next_token,
+        })
+    }
+
+    fn run_sorted(self, db: &DatabaseInner, order: PageOrder, txn: TransactionInner) -> Result<Page<T>> {
+        let after = self.after.as_deref()
+            .map(|token| order.decode_token(token))
+            .transpose()?;
+        // one more document tells if there is a next page
+        let limit = self.page_size as usize + 1;
+        let mut rows = Vec::with_capacity(limit);
+
+        let indexed = db.find_index_page::<T>(
+            self.name,
+            self.filter.clone(),
+            &order.sort,
+            after.clone(),
+            txn.clone(),
+        )?;
+        match indexed {
+            Some(mut cursor) => {
+                while rows.len() < limit && cursor.advance()? {
+                    let doc = cursor.get().as_document().cloned().unwrap_or_default();
+                    let position = order.position(&doc)?;
+                    if after.as_ref().is_none_or(|after| order.cmp(&position, after).is_gt()) {
+                        rows.push((position, doc));
+                    }
+                }
+            }
+            None => {
+                // keep the first documents after the position, sorting them
+                // once they are twice as many as the documents of the page
+                let mut cursor = db.find_page::<T>(self.name, self.filter, None, txn)?;
+                while cursor.advance()? {
+                    let doc = cursor.get().as_document().cloned().unwrap_or_default();
+                    let position = order.position(&doc)?;
+                    if after.as_ref().is_some_and(|after| order.cmp(&position, after).is_le()) {
+                        continue;
+                    }
+                    rows.push((position, doc));
+                    if rows.len() >= limit * 2 {
+                        rows.sort_by(|a, b| order.cmp(&a.0, &b.0));
+                        rows.truncate(limit);
+                    }
+                }
+                rows.sort_by(|a, b| order.cmp(&a.0, &b.0));
+                rows.truncate(limit);
+            }
+        }
+
+        let next_token = if rows.len() == limit {
+            rows.pop();
+            match rows.last() {
+                Some((position, _)) => Some(order.encode_token(position)?),
+                None => None,
+            }
+        } else {
+            None
+        };
+        let items = rows.into_iter()
+            .map(|(_, doc)| Ok(bson::from_document(doc)?))
+            .collect::<Result<Vec<T>>>()?;
+
+        Ok(Page {
+            items,
+            next_token,
+        })
+    }
+}
+
+/// The order of the sorted pages.
+struct PageOrder {
+    sort: Document,
+    field: String,
+    reverse: bool,
+}
+
+impl PageOrder {
+
+    fn new(sort: &Document) -> Result<PageOrder> {
+        let mut iter = sort.iter();
+        let (field, direction) = match (iter.next(), iter.next()) {
+            (Some(first), None) => first,
+            _ => return Err(Error::ValidationError("the pages are sorted by one field".into())),
+        };
+        let reverse = match direction {
+            Bson::Int32(1) | Bson::Int64(1) => false,
+            Bson::Int32(-1) | Bson::Int64(-1) => true,
+            _ => return Err(Error::ValidationError("Invalid sort value".into())),
+        };
+        Ok(PageOrder {
+            sort: sort.clone(),
+            field: field.clone(),
+            reverse,
+        })
+    }
+
+    fn position(&self, doc: &Document) -> Result<IndexPosition> {
+        let id = doc.get("_id").ok_or(Error::DataHasNoPrimaryKey)?;
+        Ok(IndexPosition {
+            value: crate::utils::bson::try_get_document_value(doc, &self.field),
+            pkey: crate::utils::bson::stacked_key([id])?,
+        })
+    }
+
+    #[inline]
+    fn cmp(&self, a: &IndexPosition, b: &IndexPosition) -> std::cmp::Ordering {
+        a.cmp_in_order(b, self.reverse)
+    }
+
+    fn encode_token(&self, position: &IndexPosition) -> Result<String> {
+        let mut token_doc = Document::new();
+        token_doc.insert("sort", self.sort.clone());
+        if let Some(value) = &position.value {
+            token_doc.insert("value", value.clone());
+        }
+        token_doc.insert("pkey", Bson::Binary(bson::Binary {
+            subtype: BinarySubtype::Generic,
+            bytes: position.pkey.clone(),
+        }));
+        let mut bytes = vec![SORTED_TOKEN_TAG];
+        token_doc.to_writer(&mut bytes)?;
+        Ok(encode_token(bytes))
+    }
+
+    fn decode_token(&self, token: &str) -> Result<IndexPosition> {
+        let invalid = || Error::InvalidContinuationToken(token.to_string());
+        let bytes = decode_token(token)?;
+        if bytes[0] != SORTED_TOKEN_TAG {
+            return Err(invalid());
+        }
+        let token_doc = Document::from_reader(&bytes[1..]).map_err(|_| invalid())?;
+        if token_doc.get_document("sort").ok() != Some(&self.sort) {
+            return Err(invalid());
+        }
+        let pkey = token_doc.get_binary_generic("pkey").map_err(|_| invalid())?;
+        Ok(IndexPosition {
+            value: token_doc.get("value").cloned(),
+            pkey: pkey.clone(),
+        })
+    }
+
+}
+
+fn encode_token(bytes: Vec<u8>) -> String {
+    let mut token = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(token, "{:02x}", byte);
+    }
+    token
+}
+
+fn decode_token(token: &str) -> Result<Vec<u8>> {
+    let invalid = || Error::InvalidContinuationToken(token.to_string());
+    if token.is_empty() || !token.len().is_multiple_of(2) || !token.is_ascii() {
+        return Err(invalid());
+    }
+    (0..token.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&token[i..i + 2], 16).map_err(|_| invalid()))
+        .collect()
+}
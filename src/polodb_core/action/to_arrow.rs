@@ -0,0 +1,110 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::sync::Arc;
+#[cfg(feature = "parquet")]
+use std::path::Path;
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use bson::Document;
+use crate::action::Find;
+use crate::interop::arrow::{documents_to_record_batches, infer_schema, DEFAULT_BATCH_SIZE};
+#[cfg(feature = "parquet")]
+use crate::interop::parquet::write_parquet;
+use crate::Result;
+
+/// Export the documents matching a filter as Arrow record batches.
+///
+/// The schema is inferred from the matched documents unless
+/// it's provided by [`ToArrow::schema`].
+pub struct ToArrow<'a, 'b> {
+    find: Find<'a, 'b, Document>,
+    projection: Option<Vec<String>>,
+    schema: Option<SchemaRef>,
+    batch_size: usize,
+}
+
+impl<'a, 'b> ToArrow<'a, 'b> {
+    pub(crate) fn new(find: Find<'a, 'b, Document>) -> ToArrow<'a, 'b> {
+        ToArrow {
+            find,
+            projection: None,
+            schema: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Only export the given fields. Dotted paths like `author.name` are allowed.
+    /// Ignored if a schema is provided.
+    pub fn projection<I, S>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.projection = Some(fields.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Use the schema instead of inferring it. The field names of the schema
+    /// are the paths of the values in the documents.
+    pub fn schema(mut self, schema: SchemaRef) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// The max number of rows of a record batch, 8192 by default.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn skip(mut self, skip: u64) -> Self {
+        self.find = self.find.skip(skip);
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.find = self.find.limit(limit);
+        self
+    }
+
+    pub fn sort(mut self, sort: Document) -> Self {
+        self.find = self.find.sort(sort);
+        self
+    }
+
+    pub fn run(self) -> Result<Vec<RecordBatch>> {
+        let (_, batches) = self.run_with_schema()?;
+        Ok(batches)
+    }
+
+    /// Write the record batches into a Parquet file at `path`, the file is overwritten
+    /// if it exists. Return the number of rows written.
+    #[cfg(feature = "parquet")]
+    pub fn write_parquet(self, path: impl AsRef<Path>) -> Result<u64> {
+        let (schema, batches) = self.run_with_schema()?;
+        write_parquet(path.as_ref(), schema, &batches)
+    }
+
+    fn run_with_schema(self) -> Result<(SchemaRef, Vec<RecordBatch>)> {
+        let docs = self.find.run()?.collect::<Result<Vec<Document>>>()?;
+        let schema = match self.schema {
+            Some(schema) => schema,
+            None => Arc::new(infer_schema(&docs, self.projection.as_deref())),
+        };
+        let batches = documents_to_record_batches(&docs, schema.clone(), self.batch_size)?;
+        Ok((schema, batches))
+    }
+}
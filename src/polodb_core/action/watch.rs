@@ -0,0 +1,82 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::marker::PhantomData;
+use std::sync::Weak;
+use bson::Document;
+use serde::de::DeserializeOwned;
+use crate::{ChangeStream, Error, Result};
+use crate::db::db_inner::DatabaseInner;
+
+pub struct Watch<'a, T = Document> {
+    db: Weak<DatabaseInner>,
+    name: Option<&'a str>,
+    full_document: bool,
+    resume_after: Option<u64>,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<'a, T> Watch<'a, T> {
+    pub(crate) fn new(db: Weak<DatabaseInner>, name: Option<&'a str>) -> Watch<'a, T> {
+        Watch {
+            db,
+            name,
+            full_document: false,
+            resume_after: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Deserialize the documents of the events as `U` instead of the type of the collection,
+    /// `with_type::<Document>()` delivers the raw documents.
+    pub fn with_type<U>(self) -> Watch<'a, U> {
+        Watch {
+            db: self.db,
+            name: self.name,
+            full_document: self.full_document,
+            resume_after: self.resume_after,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Attach the document after the update to the update events,
+    /// by default they only carry the delta.
+    pub fn full_document(mut self, full_document: bool) -> Self {
+        self.full_document = full_document;
+        self
+    }
+
+    /// Start with the changes committed after the change with the resume `token`,
+    /// possibly before a restart, read from the oplog.
+    /// See [`crate::ConfigBuilder::set_oplog_size`].
+    pub fn resume_after(mut self, token: u64) -> Self {
+        self.resume_after = Some(token);
+        self
+    }
+
+    pub fn run(self) -> Result<ChangeStream<T>>
+    where T: DeserializeOwned {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let changes = db.change_streams();
+        let mut stream = changes.subscribe(self.name, self.full_document);
+        if let Some(token) = self.resume_after {
+            let oplog = changes.oplog().ok_or(Error::ChangeStreamHistoryLost(token))?;
+            let txn = db.start_transaction()?;
+            let events = oplog.read_after(&txn, token)?;
+            stream.resume(token, events);
+        }
+        Ok(stream)
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use bson::{doc, Bson, Document};
+use crate::{CollectionT, Database, Error, IndexInfo, IndexModel, Result, Transaction};
+
+/// The number of documents of an imported collection inserted at once.
+const IMPORT_BATCH_SIZE: usize = 1000;
+
+/// The database files attached to a database under an alias,
+/// see [`crate::Database::attach`].
+pub(crate) struct AttachRegistry {
+    databases: Mutex<HashMap<String, Database>>,
+}
+
+impl AttachRegistry {
+
+    pub fn new() -> AttachRegistry {
+        AttachRegistry {
+            databases: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn attach(&self, alias: &str, db: Database) -> Result<()> {
+        let mut databases = self.databases.lock()?;
+        if databases.contains_key(alias) {
+            return Err(Error::DatabaseAlreadyAttached(alias.to_string()));
+        }
+        databases.insert(alias.to_string(), db);
+        Ok(())
+    }
+
+    pub fn get(&self, alias: &str) -> Result<Database> {
+        self.databases.lock()?
+            .get(alias)
+            .cloned()
+            .ok_or_else(|| Error::DatabaseNotAttached(alias.to_string()))
+    }
+
+    /// Remove the database, its file is closed once the handles returned by `get` are dropped.
+    pub fn detach(&self, alias: &str) -> Result<Database> {
+        self.databases.lock()?
+            .remove(alias)
+            .ok_or_else(|| Error::DatabaseNotAttached(alias.to_string()))
+    }
+
+    pub fn aliases(&self) -> Vec<String> {
+        let mut aliases = self.databases.lock().unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        aliases.sort();
+        aliases
+    }
+
+}
+
+/// The definition of the index `name` to create it on another collection under the same name.
+pub(crate) fn index_model(name: &str, info: &IndexInfo) -> IndexModel {
+    let keys = info.keys.iter()
+        .map(|(key, order)| (key.clone(), Bson::Int32(*order as i32)))
+        .collect::<Document>();
+    let mut options = info.options.clone().unwrap_or_default();
+    options.name = Some(name.to_string());
+    IndexModel {
+        keys,
+        options: Some(options),
+    }
+}
+
+/// Copy the indexes and the documents of the collection `source` of `from` into
+/// the collection `target` written by `txn`, return the number of copied documents.
+/// The indexes are created first, so the documents violating a unique index fail the copy.
+pub(crate) fn import_collection(from: &Database, source: &str, txn: &Transaction, target: &str) -> Result<u64> {
+    let source = from.collection::<Document>(source);
+    let target = txn.collection::<Document>(target);
+    for name in source.list_index_names()? {
+        if let Some(info) = source.describe_index(&name)? {
+            target.create_index(index_model(&name, &info))?;
+        }
+    }
+
+    let mut count = 0_u64;
+    let mut batch = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    for doc in source.find(doc! {}).run()? {
+        batch.push(doc?);
+        if batch.len() == IMPORT_BATCH_SIZE {
+            count += batch.len() as u64;
+            target.insert_many(batch.drain(..))?;
+        }
+    }
+    if !batch.is_empty() {
+        count += batch.len() as u64;
+        target.insert_many(batch)?;
+    }
+    Ok(count)
+}
@@ -0,0 +1,239 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::{Arc, OnceLock, RwLock, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use bson::{doc, Bson, DateTime, Document};
+use crate::change_stream::OperationType;
+use crate::db::db_inner::DatabaseInner;
+use crate::transaction::TransactionInner;
+use crate::Result;
+
+/// The collection the writes are recorded into by default.
+pub const DEFAULT_AUDIT_COLLECTION: &str = "system_audit";
+
+/// A write recorded by the [`AuditLog`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEntry {
+    pub op: OperationType,
+    pub collection: String,
+    /// The `_id` of the document written.
+    pub document_key: Bson,
+    /// The user of the transaction, or the default user of the audit log.
+    pub user: Option<String>,
+    /// The document before the update or the delete, if the before images are recorded.
+    pub before: Option<Document>,
+    /// The document after the insert or the update, if the after images are recorded.
+    pub after: Option<Document>,
+    pub ts: DateTime,
+}
+
+impl AuditEntry {
+
+    pub fn to_document(&self) -> Document {
+        let op = match self.op {
+            OperationType::Insert => "insert",
+            OperationType::Update => "update",
+            OperationType::Delete => "delete",
+        };
+        let mut doc = doc! {
+            "op": op,
+            "ns": self.collection.as_str(),
+            "documentKey": self.document_key.clone(),
+            "ts": self.ts,
+        };
+        if let Some(user) = &self.user {
+            doc.insert("user", user.as_str());
+        }
+        if let Some(before) = &self.before {
+            doc.insert("before", before.clone());
+        }
+        if let Some(after) = &self.after {
+            doc.insert("after", after.clone());
+        }
+        doc
+    }
+
+}
+
+/// Where the [`AuditLog`] records the writes.
+#[derive(Clone)]
+pub enum AuditSink {
+    /// Insert the entries into a collection of the database, in the transaction
+    /// of the write. The collection is append-only.
+    Collection(String),
+    /// Call the function with every entry when the transaction of the write is committed.
+    Callback(Arc<dyn Fn(&AuditEntry) + Send + Sync>),
+}
+
+impl fmt::Debug for AuditSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditSink::Collection(name) => f.debug_tuple("Collection").field(name).finish(),
+            AuditSink::Callback(_) => f.write_str("Callback"),
+        }
+    }
+}
+
+impl Default for AuditSink {
+    fn default() -> Self {
+        AuditSink::Collection(DEFAULT_AUDIT_COLLECTION.to_string())
+    }
+}
+
+///
+/// The audit log records every committed write: the user, the operation,
+/// the time and optionally the document before and after the write.
+///
+/// The audit log is disabled by default, use [`AuditLog::enable`] to enable it.
+///
+/// ```rust
+/// # use polodb_core::Database;
+/// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-audit");
+/// let db = Database::open_path(db_path).unwrap();
+/// db.audit().set_before_images(false);
+/// db.audit().set_user(Some("admin".to_string()));
+/// db.audit().enable();
+/// ```
+#[derive(Clone)]
+pub struct AuditLog {
+    inner: Arc<AuditLogInner>,
+}
+
+struct AuditLogInner {
+    enable: AtomicBool,
+    before_images: AtomicBool,
+    after_images: AtomicBool,
+    user: RwLock<Option<String>>,
+    sink: RwLock<AuditSink>,
+    db: OnceLock<Weak<DatabaseInner>>,
+}
+
+impl AuditLog {
+
+    pub(crate) fn new() -> AuditLog {
+        let inner = Arc::new(AuditLogInner {
+            enable: AtomicBool::new(false),
+            before_images: AtomicBool::new(true),
+            after_images: AtomicBool::new(true),
+            user: RwLock::new(None),
+            sink: RwLock::new(AuditSink::default()),
+            db: OnceLock::new(),
+        });
+        AuditLog {
+            inner,
+        }
+    }
+
+    pub(crate) fn attach(&self, db: Weak<DatabaseInner>) {
+        let _ = self.inner.db.set(db);
+    }
+
+    pub fn enable(&self) {
+        self.inner.enable.store(true, Ordering::Relaxed);
+    }
+
+    pub fn disable(&self) {
+        self.inner.enable.store(false, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.inner.enable.load(Ordering::Relaxed)
+    }
+
+    /// Record the documents before the updates and the deletes, enabled by default.
+    pub fn set_before_images(&self, v: bool) {
+        self.inner.before_images.store(v, Ordering::Relaxed);
+    }
+
+    /// Record the documents after the inserts and the updates, enabled by default.
+    pub fn set_after_images(&self, v: bool) {
+        self.inner.after_images.store(v, Ordering::Relaxed);
+    }
+
+    /// The user of the writes made outside a transaction, or in a transaction
+    /// without a user, see [`crate::Transaction::set_user`].
+    pub fn set_user(&self, user: Option<String>) {
+        *self.inner.user.write().unwrap() = user;
+    }
+
+    pub fn set_sink(&self, sink: AuditSink) {
+        *self.inner.sink.write().unwrap() = sink;
+    }
+
+    /// Record the writes into the collection `name`.
+    pub fn set_collection(&self, name: &str) {
+        self.set_sink(AuditSink::Collection(name.to_string()));
+    }
+
+    /// Pass the writes to `callback` instead of a collection.
+    pub fn set_callback<F>(&self, callback: F)
+    where
+        F: Fn(&AuditEntry) + Send + Sync + 'static,
+    {
+        self.set_sink(AuditSink::Callback(Arc::new(callback)));
+    }
+
+    /// Whether `col_name` is the collection the entries are recorded into.
+    pub(crate) fn is_sink(&self, col_name: &str) -> bool {
+        match &*self.inner.sink.read().unwrap() {
+            AuditSink::Collection(name) => name == col_name,
+            AuditSink::Callback(_) => false,
+        }
+    }
+
+    /// Return the audit log if the writes on the collection are recorded.
+    pub(crate) fn recorder(&self, col_name: &str) -> Option<AuditLog> {
+        if !self.is_enabled() || self.is_sink(col_name) {
+            return None;
+        }
+        Some(self.clone())
+    }
+
+    pub(crate) fn record(
+        &self,
+        txn: &TransactionInner,
+        op: OperationType,
+        col_name: &str,
+        document_key: &Bson,
+        before: Option<&Document>,
+        after: Option<&Document>,
+    ) -> Result<()> {
+        let user = txn.user().or_else(|| self.inner.user.read().unwrap().clone());
+        let entry = AuditEntry {
+            op,
+            collection: col_name.to_string(),
+            document_key: document_key.clone(),
+            user,
+            before: before.filter(|_| self.inner.before_images.load(Ordering::Relaxed)).cloned(),
+            after: after.filter(|_| self.inner.after_images.load(Ordering::Relaxed)).cloned(),
+            ts: DateTime::now(),
+        };
+        let sink = self.inner.sink.read().unwrap().clone();
+        match sink {
+            AuditSink::Callback(callback) => {
+                txn.on_commit(Box::new(move || callback(&entry)));
+            }
+            AuditSink::Collection(name) => {
+                if let Some(db) = self.inner.db.get().and_then(Weak::upgrade) {
+                    db.insert_one(&name, entry.to_document(), txn)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+}
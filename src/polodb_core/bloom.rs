@@ -0,0 +1,230 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use bson::{Bson, Document};
+use crate::coll::collection_info::CollectionSpecification;
+use crate::cursor::Cursor;
+use crate::transaction::TransactionInner;
+use crate::Result;
+
+/// The bits given to each value, with 7 hashes about 1% of the absent values are let through.
+const BITS_PER_VALUE: u64 = 10;
+const HASH_COUNT: u64 = 7;
+const MIN_CAPACITY: u64 = 1024;
+
+/// A bloom filter sized for `capacity` values.
+struct BloomFilter {
+    bits: Vec<AtomicU64>,
+    capacity: u64,
+    len: AtomicU64,
+}
+
+impl BloomFilter {
+
+    fn new(capacity: u64) -> BloomFilter {
+        let words = (capacity * BITS_PER_VALUE).div_ceil(64);
+        BloomFilter {
+            bits: (0..words).map(|_| AtomicU64::new(0)).collect(),
+            capacity,
+            len: AtomicU64::new(0),
+        }
+    }
+
+    fn bit_count(&self) -> u64 {
+        self.bits.len() as u64 * 64
+    }
+
+    /// The positions of the bits of a value, by double hashing.
+    fn positions(&self, hashes: (u64, u64)) -> impl Iterator<Item = u64> + '_ {
+        let (h1, h2) = hashes;
+        (0..HASH_COUNT).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % self.bit_count())
+    }
+
+    fn insert(&self, hashes: (u64, u64)) {
+        for position in self.positions(hashes) {
+            self.bits[(position / 64) as usize].fetch_or(1 << (position % 64), Ordering::Relaxed);
+        }
+        self.len.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn contains(&self, hashes: (u64, u64)) -> bool {
+        self.positions(hashes).all(|position| {
+            self.bits[(position / 64) as usize].load(Ordering::Relaxed) & (1 << (position % 64)) != 0
+        })
+    }
+
+    fn is_full(&self) -> bool {
+        self.len.load(Ordering::Relaxed) >= self.capacity
+    }
+
+}
+
+/// The bloom filter of the values of a field of a collection, telling a lookup
+/// the value is definitely not in the collection without reading the storage.
+///
+/// The values are only added: a deleted value is still reported as present,
+/// as is a value written by a transaction which is rolled back. Once a filter
+/// is full, a filter twice as large is added for the next values.
+pub(crate) struct CollectionBloom {
+    field: String,
+    filters: RwLock<Vec<BloomFilter>>,
+}
+
+impl CollectionBloom {
+
+    fn new(field: &str, capacity: u64) -> CollectionBloom {
+        CollectionBloom {
+            field: field.to_string(),
+            filters: RwLock::new(vec![BloomFilter::new(capacity.max(MIN_CAPACITY))]),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn field(&self) -> &str {
+        &self.field
+    }
+
+    fn hashes(value: &Bson) -> Option<(u64, u64)> {
+        // the values are hashed by their key, so the numbers of different types are different values,
+        // like in the primary keys and the indexes
+        let key = crate::utils::bson::stacked_key([value]).ok()?;
+        let mut h1 = DefaultHasher::new();
+        key.hash(&mut h1);
+        let mut h2 = DefaultHasher::new();
+        0xb10fu16.hash(&mut h2);
+        key.hash(&mut h2);
+        Some((h1.finish(), h2.finish() | 1))
+    }
+
+    pub(crate) fn insert(&self, value: &Bson) {
+        let hashes = match CollectionBloom::hashes(value) {
+            Some(hashes) => hashes,
+            None => return,
+        };
+        {
+            let filters = self.filters.read().unwrap();
+            let last = filters.last().unwrap();
+            if !last.is_full() {
+                last.insert(hashes);
+                return;
+            }
+        }
+        let mut filters = self.filters.write().unwrap();
+        if filters.last().unwrap().is_full() {
+            let capacity = filters.last().unwrap().capacity * 2;
+            filters.push(BloomFilter::new(capacity));
+        }
+        filters.last().unwrap().insert(hashes);
+    }
+
+    /// Add the value of the field of the document.
+    pub(crate) fn insert_doc(&self, doc: &Document) {
+        if let Some(value) = crate::utils::bson::try_get_document_value(doc, &self.field) {
+            self.insert(&value);
+        }
+    }
+
+    /// Whether the value may be in the collection, `false` if it is definitely not.
+    pub(crate) fn may_contain(&self, value: &Bson) -> bool {
+        let hashes = match CollectionBloom::hashes(value) {
+            Some(hashes) => hashes,
+            None => return true,
+        };
+        let filters = self.filters.read().unwrap();
+        filters.iter().any(|filter| filter.contains(hashes))
+    }
+
+}
+
+/// The bloom filters of the collections, kept in memory and built when the database is opened.
+#[derive(Clone, Default)]
+pub(crate) struct BloomFilterRegistry {
+    inner: Arc<RwLock<HashMap<String, Arc<CollectionBloom>>>>,
+}
+
+impl BloomFilterRegistry {
+
+    pub(crate) fn new() -> BloomFilterRegistry {
+        BloomFilterRegistry::default()
+    }
+
+    pub(crate) fn get(&self, col_name: &str) -> Option<Arc<CollectionBloom>> {
+        self.inner.read().unwrap().get(col_name).cloned()
+    }
+
+    /// Register an empty filter for a new collection.
+    pub(crate) fn create(&self, col_name: &str, field: &str) {
+        let bloom = CollectionBloom::new(field, MIN_CAPACITY);
+        self.inner.write().unwrap().insert(col_name.to_string(), Arc::new(bloom));
+    }
+
+    /// Read the documents of the collection to fill its filter.
+    pub(crate) fn build(&self, txn: &TransactionInner, col_spec: &CollectionSpecification, field: &str) -> Result<()> {
+        let mut docs = Vec::new();
+        let mut cursor = Cursor::new_with_str_prefix(col_spec.name(), txn.rocksdb_txn.new_iterator())?;
+        cursor.reset()?;
+        while cursor.has_next() {
+            let doc = bson::from_slice::<Document>(cursor.copy_data()?.as_ref())?;
+            if let Some(value) = crate::utils::bson::try_get_document_value(&doc, field) {
+                docs.push(value);
+            }
+            cursor.next()?;
+        }
+
+        let bloom = CollectionBloom::new(field, docs.len() as u64 * 2);
+        for value in &docs {
+            bloom.insert(value);
+        }
+        self.inner.write().unwrap().insert(col_spec.name().to_string(), Arc::new(bloom));
+        Ok(())
+    }
+
+    /// Share the filter of a collection renamed by an uncommitted transaction with its new name,
+    /// so the writes of the transaction to the new name are added to it.
+    pub(crate) fn alias(&self, old_name: &str, new_name: &str) {
+        let mut inner = self.inner.write().unwrap();
+        if let Some(bloom) = inner.get(old_name).cloned() {
+            inner.insert(new_name.to_string(), bloom);
+        }
+    }
+
+    pub(crate) fn remove(&self, col_name: &str) {
+        self.inner.write().unwrap().remove(col_name);
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::Bson;
+    use super::CollectionBloom;
+
+    #[test]
+    fn test_bloom_filter() {
+        let bloom = CollectionBloom::new("_id", 16);
+        for i in 0..5000 {
+            bloom.insert(&Bson::Int64(i * 2));
+        }
+        assert!((0..5000).all(|i| bloom.may_contain(&Bson::Int64(i * 2))));
+
+        let false_positives = (0..5000).filter(|i| bloom.may_contain(&Bson::Int64(i * 2 + 1))).count();
+        assert!(false_positives < 150, "{} false positives", false_positives);
+    }
+
+}
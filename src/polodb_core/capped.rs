@@ -0,0 +1,163 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bson::{doc, Bson, Document};
+use crate::coll::collection_info::{CappedInfo, CollectionSpecification};
+use crate::cursor::Cursor;
+use crate::index::{IndexHelper, IndexHelperOperation};
+use crate::transaction::TransactionInner;
+use crate::Result;
+
+/// The insertion order of the documents of a capped collection,
+/// `[$CAPPED, <collection>, <seq>] -> { k: <_id>, s: <size> }`.
+const CAPPED_PREFIX: &str = "$CAPPED";
+
+/// The number and the size of the documents of a capped collection,
+/// `[$CAPPED_STATE, <collection>] -> CappedState`.
+const CAPPED_STATE_PREFIX: &str = "$CAPPED_STATE";
+
+#[derive(Default)]
+struct CappedState {
+    /// The sequence of the oldest document.
+    first: i64,
+    /// The sequence of the next document.
+    next: i64,
+    count: u64,
+    size: u64,
+}
+
+impl CappedState {
+
+    fn from_document(doc: &Document) -> CappedState {
+        CappedState {
+            first: doc.get_i64("first").unwrap_or(0),
+            next: doc.get_i64("next").unwrap_or(0),
+            count: doc.get_i64("count").unwrap_or(0) as u64,
+            size: doc.get_i64("size").unwrap_or(0) as u64,
+        }
+    }
+
+    fn to_document(&self) -> Document {
+        doc! {
+            "first": self.first,
+            "next": self.next,
+            "count": self.count as i64,
+            "size": self.size as i64,
+        }
+    }
+
+    fn is_full(&self, capped: &CappedInfo) -> bool {
+        capped.max.is_some_and(|max| self.count > max) ||
+            capped.size.is_some_and(|size| self.size > size)
+    }
+
+}
+
+fn state_key(col_name: &str) -> Result<Vec<u8>> {
+    crate::utils::bson::stacked_key([
+        &Bson::String(CAPPED_STATE_PREFIX.to_string()),
+        &Bson::String(col_name.to_string()),
+    ])
+}
+
+fn entries_prefix(col_name: &str) -> Result<Vec<u8>> {
+    crate::utils::bson::stacked_key([
+        &Bson::String(CAPPED_PREFIX.to_string()),
+        &Bson::String(col_name.to_string()),
+    ])
+}
+
+fn entry_key(col_name: &str, seq: i64) -> Result<Vec<u8>> {
+    crate::utils::bson::stacked_key([
+        &Bson::String(CAPPED_PREFIX.to_string()),
+        &Bson::String(col_name.to_string()),
+        &Bson::Int64(seq),
+    ])
+}
+
+fn read_document(txn: &TransactionInner, key: &[u8]) -> Result<Option<Document>> {
+    match txn.rocksdb_txn.get(key)? {
+        Some(data) => Ok(Some(bson::from_slice(&data)?)),
+        None => Ok(None),
+    }
+}
+
+/// Record the insertion of a document of `size` bytes in a capped collection,
+/// and remove the oldest documents until the collection fits in its limits.
+///
+/// The removed documents are not reported to the hooks and the change streams.
+pub(crate) fn record_insert(txn: &TransactionInner, col_spec: &CollectionSpecification, capped: &CappedInfo, pkey: &Bson, size: usize) -> Result<()> {
+    let col_name = col_spec.name();
+    let state_key = state_key(col_name)?;
+    let mut state = read_document(txn, &state_key)?
+        .map(|doc| CappedState::from_document(&doc))
+        .unwrap_or_default();
+
+    let seq = state.next;
+    let entry = doc! {
+        "k": pkey.clone(),
+        "s": size as i64,
+    };
+    txn.put(&entry_key(col_name, seq)?, &bson::to_vec(&entry)?)?;
+    state.next += 1;
+    state.count += 1;
+    state.size += size as u64;
+
+    // the document just inserted is kept even if it exceeds the size alone
+    while state.is_full(capped) && state.first < seq {
+        let key = entry_key(col_name, state.first)?;
+        if let Some(entry) = read_document(txn, &key)? {
+            let pkey = entry.get("k").cloned().unwrap_or(Bson::Null);
+            evict(txn, col_spec, &pkey)?;
+            txn.delete(&key)?;
+            state.count -= 1;
+            state.size -= entry.get_i64("s").unwrap_or(0) as u64;
+        }
+        state.first += 1;
+    }
+
+    txn.put(&state_key, &bson::to_vec(&state.to_document())?)
+}
+
+fn evict(txn: &TransactionInner, col_spec: &CollectionSpecification, pkey: &Bson) -> Result<()> {
+    let key = crate::utils::bson::stacked_key([
+        &Bson::String(col_spec._id.clone()),
+        pkey,
+    ])?;
+    if let Some(doc) = read_document(txn, &key)? {
+        let mut index_helper = IndexHelper::new(txn, col_spec, &doc, pkey);
+        index_helper.execute(IndexHelperOperation::Delete)?;
+        txn.delete(&key)?;
+    }
+    Ok(())
+}
+
+/// Move the insertion order of a renamed capped collection.
+pub(crate) fn rename(txn: &TransactionInner, old_name: &str, new_name: &str) -> Result<()> {
+    txn.move_prefix(entries_prefix(old_name)?, &entries_prefix(new_name)?)?;
+    txn.move_prefix(state_key(old_name)?, &state_key(new_name)?)
+}
+
+/// Remove the insertion order of a dropped capped collection.
+pub(crate) fn drop(txn: &TransactionInner, col_name: &str) -> Result<()> {
+    let mut cursor = Cursor::new(entries_prefix(col_name)?, txn.rocksdb_txn.new_iterator());
+    cursor.reset()?;
+    while cursor.has_next() {
+        if let Some(key) = cursor.peek_key() {
+            txn.delete(key.as_ref())?;
+        }
+        cursor.next()?;
+    }
+    txn.delete(&state_key(col_name)?)
+}
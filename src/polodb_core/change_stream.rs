@@ -0,0 +1,389 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::sync::{Arc, RwLock, Weak};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+use bson::{Bson, DateTime, Document};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use crate::audit::AuditLog;
+use crate::oplog::Oplog;
+use crate::transaction::TransactionInner;
+use crate::Result;
+
+/// The kind of write a [`ChangeEvent`] reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OperationType {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// The fields changed by an update.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateDescription {
+    /// The top-level fields added or modified by the update, with their new values.
+    pub updated_fields: Document,
+    /// The top-level fields removed by the update.
+    pub removed_fields: Vec<String>,
+}
+
+impl UpdateDescription {
+
+    fn diff(old: &Document, new: &Document) -> UpdateDescription {
+        let mut updated_fields = Document::new();
+        for (key, value) in new.iter() {
+            if old.get(key) != Some(value) {
+                updated_fields.insert(key.clone(), value.clone());
+            }
+        }
+        let removed_fields = old.keys()
+            .filter(|key| !new.contains_key(key.as_str()))
+            .cloned()
+            .collect();
+        UpdateDescription {
+            updated_fields,
+            removed_fields,
+        }
+    }
+
+}
+
+/// A committed write on a collection, delivered by a [`ChangeStream`].
+///
+/// The written document is deserialized as `T`, the type of the watched collection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeEvent<T = Document> {
+    /// The position of the change in the oplog, `None` if the oplog is disabled.
+    /// A stream can be resumed after it with [`crate::action::Watch::resume_after`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume_token: Option<u64>,
+    pub operation_type: OperationType,
+    pub collection: String,
+    /// The `_id` of the document written.
+    pub document_key: Bson,
+    /// The inserted document, or the document after the update when the stream
+    /// is opened with `full_document(true)`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_document: Option<T>,
+    /// The delta of an update.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_description: Option<UpdateDescription>,
+    /// The time of the write, `None` for the changes logged by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wall_time: Option<DateTime>,
+}
+
+impl ChangeEvent {
+
+    fn with_type<T: DeserializeOwned>(self) -> Result<ChangeEvent<T>> {
+        let full_document = match self.full_document {
+            Some(doc) => Some(bson::from_document(doc)?),
+            None => None,
+        };
+        Ok(ChangeEvent {
+            resume_token: self.resume_token,
+            operation_type: self.operation_type,
+            collection: self.collection,
+            document_key: self.document_key,
+            full_document,
+            update_description: self.update_description,
+            wall_time: self.wall_time,
+        })
+    }
+
+}
+
+/// The changes a stream is interested in.
+#[derive(Clone)]
+struct Subscription {
+    collection: Option<String>,
+    full_document: bool,
+}
+
+impl Subscription {
+
+    fn watches(&self, col_name: &str) -> bool {
+        self.collection.as_deref().is_none_or(|name| name == col_name)
+    }
+
+    fn accept(&self, event: &ChangeEvent) -> Option<ChangeEvent> {
+        if !self.watches(&event.collection) {
+            return None;
+        }
+        let mut event = event.clone();
+        if event.operation_type == OperationType::Update && !self.full_document {
+            event.full_document = None;
+        }
+        Some(event)
+    }
+
+}
+
+struct Subscriber {
+    id: u64,
+    subscription: Subscription,
+    sender: Sender<ChangeEvent>,
+}
+
+#[derive(Default)]
+struct Subscribers {
+    next_id: u64,
+    list: Vec<Subscriber>,
+}
+
+/// The change streams opened on a database, and the oplog recording
+/// the changes if it is enabled.
+#[derive(Clone, Default)]
+pub(crate) struct ChangeStreamRegistry {
+    inner: Arc<RwLock<Subscribers>>,
+    oplog: Option<Arc<Oplog>>,
+}
+
+impl ChangeStreamRegistry {
+
+    pub(crate) fn new(oplog: Option<Oplog>) -> ChangeStreamRegistry {
+        ChangeStreamRegistry {
+            inner: Arc::default(),
+            oplog: oplog.map(Arc::new),
+        }
+    }
+
+    pub(crate) fn oplog(&self) -> Option<&Oplog> {
+        self.oplog.as_deref()
+    }
+
+    pub(crate) fn subscribe<T>(&self, collection: Option<&str>, full_document: bool) -> ChangeStream<T> {
+        let subscription = Subscription {
+            collection: collection.map(|name| name.to_string()),
+            full_document,
+        };
+        let (sender, receiver) = channel();
+        let mut inner = self.inner.write().unwrap();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.list.push(Subscriber {
+            id,
+            subscription: subscription.clone(),
+            sender,
+        });
+        ChangeStream {
+            receiver,
+            registry: Arc::downgrade(&self.inner),
+            id,
+            subscription,
+            backlog: VecDeque::new(),
+            last_token: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Return a publisher if the changes of the collection are recorded
+    /// in the oplog or the audit log, or watched by a change stream.
+    pub(crate) fn publisher(&self, col_name: &str, audit: Option<AuditLog>) -> Option<ChangePublisher> {
+        let watched = self.inner.read().unwrap()
+            .list
+            .iter()
+            .any(|sub| sub.subscription.watches(col_name));
+        if !watched && self.oplog.is_none() && audit.is_none() {
+            return None;
+        }
+        Some(ChangePublisher {
+            registry: self.clone(),
+            audit,
+            collection: col_name.to_string(),
+        })
+    }
+
+    fn publish(&self, event: ChangeEvent) {
+        if let (Some(oplog), Some(token)) = (&self.oplog, event.resume_token) {
+            oplog.mark_committed(token);
+        }
+        let inner = self.inner.read().unwrap();
+        for sub in &inner.list {
+            if let Some(event) = sub.subscription.accept(&event) {
+                // the stream is being dropped, it is unregistered by its own drop
+                let _ = sub.sender.send(event);
+            }
+        }
+    }
+
+}
+
+/// Record the writes of a collection in the oplog and the audit log, and publish
+/// them to the change streams watching the collection when the transaction is committed.
+#[derive(Clone)]
+pub(crate) struct ChangePublisher {
+    registry: ChangeStreamRegistry,
+    audit: Option<AuditLog>,
+    collection: String,
+}
+
+impl ChangePublisher {
+
+    fn defer(&self, txn: &TransactionInner, operation_type: OperationType, before: Option<&Document>, after: Option<&Document>) -> Result<()> {
+        let document_key = after.or(before)
+            .and_then(|doc| doc.get("_id"))
+            .cloned()
+            .unwrap_or(Bson::Null);
+        if let Some(audit) = &self.audit {
+            audit.record(txn, operation_type, &self.collection, &document_key, before, after)?;
+        }
+        let mut event = ChangeEvent {
+            resume_token: None,
+            operation_type,
+            collection: self.collection.clone(),
+            document_key,
+            full_document: after.cloned(),
+            update_description: before.zip(after).map(|(old, new)| UpdateDescription::diff(old, new)),
+            wall_time: Some(DateTime::now()),
+        };
+        if let Some(oplog) = self.registry.oplog() {
+            oplog.append(txn, &mut event)?;
+        }
+        let registry = self.registry.clone();
+        txn.on_commit(Box::new(move || registry.publish(event)));
+        Ok(())
+    }
+
+    pub(crate) fn defer_insert(&self, txn: &TransactionInner, doc: &Document) -> Result<()> {
+        self.defer(txn, OperationType::Insert, None, Some(doc))
+    }
+
+    pub(crate) fn defer_update(&self, txn: &TransactionInner, old: &Document, new: &Document) -> Result<()> {
+        self.defer(txn, OperationType::Update, Some(old), Some(new))
+    }
+
+    pub(crate) fn defer_delete(&self, txn: &TransactionInner, doc: &Document) -> Result<()> {
+        self.defer(txn, OperationType::Delete, Some(doc), None)
+    }
+
+}
+
+/// The changes read from the oplog by [`crate::Database::changes_since`].
+#[derive(Debug, Clone)]
+pub struct ChangeBatch {
+    /// The changes in the order of their tokens, with the documents after the writes.
+    pub changes: Vec<ChangeEvent>,
+    /// The token to read the next batch after, the token of the last change,
+    /// or the token read after if there is no change.
+    pub resume_token: u64,
+}
+
+/// The committed changes of a collection, or of the whole database,
+/// in the order of the commits.
+///
+/// The written documents are deserialized as `T`, an event fails
+/// if its document doesn't match `T`.
+/// Iterating the stream blocks until the next change, the iterator ends
+/// when the database is closed. The stream is closed when dropped.
+///
+/// The clones of a [`crate::Database`] share their streams: a stream receives the
+/// writes made through any clone, from any thread. The database files can only be
+/// opened by one process at a time, a process opening them later catches up with
+/// [`crate::action::Watch::resume_after`] or [`crate::Database::changes_since`].
+pub struct ChangeStream<T = Document> {
+    receiver: Receiver<ChangeEvent>,
+    registry: Weak<RwLock<Subscribers>>,
+    id: u64,
+    subscription: Subscription,
+    backlog: VecDeque<ChangeEvent>,
+    last_token: Option<u64>,
+    _phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> ChangeStream<T> {
+
+    /// Deliver the changes read from the oplog after `token` before the live changes,
+    /// the live changes already in the backlog are skipped.
+    pub(crate) fn resume(&mut self, token: u64, events: Vec<ChangeEvent>) {
+        self.backlog = events.iter()
+            .filter_map(|event| self.subscription.accept(event))
+            .collect();
+        self.last_token = Some(events.last().and_then(|event| event.resume_token).unwrap_or(token));
+    }
+
+    fn is_new(&self, event: &ChangeEvent) -> bool {
+        match (self.last_token, event.resume_token) {
+            (Some(last_token), Some(token)) => token > last_token,
+            _ => true,
+        }
+    }
+
+}
+
+impl<T: DeserializeOwned> ChangeStream<T> {
+
+    /// Return the next change if one is pending, without blocking.
+    pub fn try_next(&mut self) -> Option<Result<ChangeEvent<T>>> {
+        if let Some(event) = self.backlog.pop_front() {
+            return Some(event.with_type());
+        }
+        loop {
+            let event = self.receiver.try_recv().ok()?;
+            if self.is_new(&event) {
+                return Some(event.with_type());
+            }
+        }
+    }
+
+    /// Wait for the next change at most `timeout`.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<Result<ChangeEvent<T>>> {
+        if let Some(event) = self.backlog.pop_front() {
+            return Some(event.with_type());
+        }
+        let deadline = Instant::now() + timeout;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let event = self.receiver.recv_timeout(timeout).ok()?;
+            if self.is_new(&event) {
+                return Some(event.with_type());
+            }
+        }
+    }
+
+}
+
+impl<T: DeserializeOwned> Iterator for ChangeStream<T> {
+    type Item = Result<ChangeEvent<T>>;
+
+    fn next(&mut self) -> Option<Result<ChangeEvent<T>>> {
+        if let Some(event) = self.backlog.pop_front() {
+            return Some(event.with_type());
+        }
+        loop {
+            let event = self.receiver.recv().ok()?;
+            if self.is_new(&event) {
+                return Some(event.with_type());
+            }
+        }
+    }
+}
+
+impl<T> Drop for ChangeStream<T> {
+
+    fn drop(&mut self) {
+        if let Some(registry) = self.registry.upgrade() {
+            let mut inner = registry.write().unwrap();
+            inner.list.retain(|sub| sub.id != self.id);
+        }
+    }
+
+}
@@ -13,16 +13,20 @@
 // limitations under the License.
 
 use serde::Serialize;
-use bson::Document;
+use bson::{doc, Bson, Document};
 use std::borrow::Borrow;
+#[cfg(feature = "parquet")]
+use std::path::Path;
 use std::sync::Weak;
 use serde::de::DeserializeOwned;
-use crate::options::UpdateOptions;
+use crate::options::{DeleteOptions, InsertManyOptions, UpdateOptions};
 use crate::{Error, IndexModel, Result};
 use crate::db::db_inner::DatabaseInner;
-use crate::action::{Aggregate, Find};
+use crate::action::{Aggregate, CloneTo, Find, FindRaw, Paginate, Watch};
+#[cfg(feature = "arrow")]
+use crate::action::ToArrow;
 use crate::results::{DeleteResult, InsertManyResult, InsertOneResult, UpdateResult};
-use super::collection_info::IndexInfo;
+use super::collection_info::{CollectionStatistics, IndexInfo};
 
 macro_rules! try_multiple {
     ($err: expr, $action: expr) => {
@@ -56,6 +60,13 @@ pub trait CollectionT<T> {
     /// Return the size of all data in the collection.
     fn count_documents(&self) -> Result<u64>;
 
+    /// Return the number of documents in the collection, the same as [`CollectionT::count_documents`].
+    fn count(&self) -> Result<u64>;
+
+    /// Return whether a document matches `filter`, the query stops at the first match
+    /// and the document is not deserialized.
+    fn exists(&self, filter: Document) -> Result<bool>;
+
     /// Updates up to one document matching `query` in the collection.
     /// [documentation](https://www.polodb.org/docs/curd/update) for more information on specifying updates.
     fn update_one(&self, query: Document, update: Document) -> Result<UpdateResult>;
@@ -71,18 +82,43 @@ pub trait CollectionT<T> {
     /// Deletes up to one document found matching `query`.
     fn delete_one(&self, query: Document) -> Result<DeleteResult>;
 
+    /// Deletes the document with the `_id`, found by its primary key.
+    fn delete_by_id(&self, id: impl Into<Bson>) -> Result<DeleteResult> {
+        self.delete_one(doc! { "_id": id.into() })
+    }
+
     /// When query is `None`, all the data in the collection will be deleted.
     ///
     /// The size of data deleted returns.
     fn delete_many(&self, query: Document) -> Result<DeleteResult>;
-    fn create_index(&self, index: IndexModel) -> Result<()>;
+
+    fn delete_many_with_options(&self, query: Document, options: DeleteOptions) -> Result<DeleteResult>;
+
+    /// Creates an index on the collection, returns its name. Creating an index
+    /// with the same keys and options as an existing one returns the name
+    /// of the existing index, creating an index with the name of an existing
+    /// one but different keys or options fails with [`Error::IndexAlreadyExists`].
+    fn create_index(&self, index: IndexModel) -> Result<String>;
+
+    /// Creates the indexes on the collection, returns their names. The new indexes
+    /// are built together by one scan of the collection, on up to the number of threads
+    /// set by [`ConfigBuilder::set_index_build_parallelism`](crate::ConfigBuilder::set_index_build_parallelism).
+    fn create_indexes(&self, indexes: impl IntoIterator<Item = IndexModel>) -> Result<Vec<String>>;
 
     /// Drops the index specified by `name` from this collection.
     fn drop_index(&self, name: impl AsRef<str>) -> Result<()>;
     fn list_index_names(&self) -> Result<Vec<String>>;
     fn describe_index(&self, name: impl AsRef<str>) -> Result<Option<IndexInfo>>;
+
+    /// Gathers the statistics of the indexed fields, the query planner uses them
+    /// to choose between the indexes. The statistics are not refreshed by the writes,
+    /// analyze the collection again after changing much of its data.
+    fn analyze(&self) -> Result<CollectionStatistics>;
     fn drop(&self) -> Result<()>;
 
+    /// Renames the collection, this handle keeps referring to the old name.
+    fn rename(&self, new_name: &str) -> Result<()>;
+
     /// Inserts `doc` into the collection.
     fn insert_one(&self, doc: impl Borrow<T>) -> Result<InsertOneResult>
     where T: Serialize;
@@ -91,17 +127,61 @@ pub trait CollectionT<T> {
     fn insert_many(&self, docs: impl IntoIterator<Item = impl Borrow<T>>) -> Result<InsertManyResult>
     where T: Serialize;
 
+    /// Inserts the data in `docs` into the collection, an unordered insert
+    /// attempts every document and reports the failures in the result.
+    fn insert_many_with_options(&self, docs: impl IntoIterator<Item = impl Borrow<T>>, options: InsertManyOptions) -> Result<InsertManyResult>
+    where T: Serialize;
+
+    /// Inserts a large number of documents at once: the indexes are not
+    /// updated document by document but rebuilt in key order at the end,
+    /// a duplicate key in a unique index fails the whole load.
+    fn bulk_load(&self, docs: impl IntoIterator<Item = impl Borrow<T>>) -> Result<InsertManyResult>
+    where T: Serialize;
+
     /// When query document is passed to the function. The result satisfies
     /// the query document.
     fn find(&self, filter: Document) -> Find<'_, '_, T>
     where T: DeserializeOwned + Send + Sync;
 
+    /// Reads the documents matching `filter` by pages of `page_size`
+    /// documents, in the order of their `_id` or of [`Paginate::sort`].
+    fn paginate(&self, filter: Document, page_size: u64) -> Paginate<'_, '_, T>
+    where T: DeserializeOwned + Send + Sync;
+
     /// Finds a single document in the collection matching `filter`.
     fn find_one(&self, filter: Document) -> Result<Option<T>>
     where T: DeserializeOwned + Send + Sync;
 
+    /// Finds the document with the `_id`, such as an `ObjectId`, a string or an integer,
+    /// the document is read by its primary key.
+    fn find_by_id(&self, id: impl Into<Bson>) -> Result<Option<T>>
+    where T: DeserializeOwned + Send + Sync {
+        self.find_one(doc! { "_id": id.into() })
+    }
+
+    /// Finds the documents matching `filter` as raw BSON, the documents read
+    /// are not deserialized when nothing needs their fields.
+    fn find_raw(&self, filter: Document) -> FindRaw<'_, '_>;
+
+    /// Exports the documents matching `filter` as Arrow record batches.
+    #[cfg(feature = "arrow")]
+    fn to_arrow(&self, filter: Document) -> ToArrow<'_, '_>;
+
+    /// Inserts all the rows of the Parquet file at `path` into the collection.
+    #[cfg(feature = "parquet")]
+    fn import_parquet(&self, path: impl AsRef<Path>) -> Result<InsertManyResult>;
+
     /// Runs an aggregation operation.
     fn aggregate(&self, pipeline: impl IntoIterator<Item = Document>) -> Aggregate<'_, '_>;
+
+    /// Opens a change stream delivering the inserts, updates and deletes
+    /// of the collection once they are committed, the written documents
+    /// are deserialized as `T`, see [`Watch::with_type`] to receive them raw.
+    fn watch(&self) -> Watch<'_, T>;
+
+    /// Copies the documents, and by default the indexes, of the collection
+    /// to the new collection `target`.
+    fn clone_to<'a>(&'a self, target: &'a str) -> CloneTo<'a, 'a>;
 }
 
 
@@ -125,6 +205,16 @@ impl<T>  Collection<T>
             _phantom: std::default::Default::default(),
         }
     }
+
+    /// Rebuild the indexes of the collection and compact the storage of its documents
+    /// and index entries, reclaiming the space of the deleted and overwritten ones.
+    /// The other collections are not rewritten and stay available meanwhile.
+    pub fn compact(&self) -> Result<()> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let txn = db.start_transaction()?;
+        try_db_op!(txn, db.rebuild_indexes(&self.name, &txn));
+        db.compact_collection(&self.name)
+    }
 }
 
 impl<T> CollectionT<T> for Collection<T> {
@@ -140,6 +230,17 @@ impl<T> CollectionT<T> for Collection<T> {
         Ok(count)
     }
 
+    fn count(&self) -> Result<u64> {
+        self.count_documents()
+    }
+
+    fn exists(&self, filter: Document) -> Result<bool> {
+        let mut cursor = Find::<Document>::new(self.db.clone(), &self.name, None, filter)
+            .limit(1)
+            .run()?;
+        cursor.advance()
+    }
+
     fn update_one(&self, query: Document, update: Document) -> Result<UpdateResult> {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
         let txn = db.start_transaction()?;
@@ -155,7 +256,7 @@ impl<T> CollectionT<T> for Collection<T> {
 
     fn update_one_with_options(&self, query: Document, update: Document, options: UpdateOptions) -> Result<UpdateResult> {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
-        let txn = db.start_transaction()?;
+        let txn = db.start_transaction_with_durability(options.durability)?;
         let result = try_db_op!(txn, db.update_one(
             &self.name,
             query,
@@ -181,7 +282,7 @@ impl<T> CollectionT<T> for Collection<T> {
 
     fn update_many_with_options(&self, query: Document, update: Document, options: UpdateOptions) -> Result<UpdateResult> {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
-        let txn = db.start_transaction()?;
+        let txn = db.start_transaction_with_durability(options.durability)?;
         let result = try_db_op!(txn, db.update_many(
             &self.name,
             query,
@@ -200,17 +301,28 @@ impl<T> CollectionT<T> for Collection<T> {
     }
 
     fn delete_many(&self, query: Document) -> Result<DeleteResult> {
+        self.delete_many_with_options(query, DeleteOptions::default())
+    }
+
+    fn delete_many_with_options(&self, query: Document, options: DeleteOptions) -> Result<DeleteResult> {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
-        let txn = db.start_transaction()?;
-        let result = try_db_op!(txn, db.delete_many(&self.name, query, &txn));
+        let txn = db.start_transaction_with_durability(options.durability)?;
+        let result = try_db_op!(txn, db.delete_many(&self.name, query, &options, &txn));
         Ok(result)
     }
 
-    fn create_index(&self, index: IndexModel) -> Result<()> {
+    fn create_index(&self, index: IndexModel) -> Result<String> {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
         let txn = db.start_transaction()?;
-        try_db_op!(txn, db.create_index(&self.name, index, &txn));
-        Ok(())
+        let name = try_db_op!(txn, db.create_index(&self.name, index, &txn));
+        Ok(name)
+    }
+
+    fn create_indexes(&self, indexes: impl IntoIterator<Item = IndexModel>) -> Result<Vec<String>> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let txn = db.start_transaction()?;
+        let names = try_db_op!(txn, db.create_indexes(&self.name, indexes.into_iter().collect(), &txn));
+        Ok(names)
     }
 
     fn drop_index(&self, name: impl AsRef<str>) -> Result<()> {
@@ -234,6 +346,13 @@ impl<T> CollectionT<T> for Collection<T> {
         Ok(info)
     }
 
+    fn analyze(&self) -> Result<CollectionStatistics> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let txn = db.start_transaction()?;
+        let statistics = try_db_op!(txn, db.analyze(&self.name, &txn));
+        Ok(statistics)
+    }
+
     fn drop(&self) -> Result<()> {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
         let txn = db.start_transaction()?;
@@ -241,6 +360,13 @@ impl<T> CollectionT<T> for Collection<T> {
         Ok(())
     }
 
+    fn rename(&self, new_name: &str) -> Result<()> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let txn = db.start_transaction()?;
+        try_db_op!(txn, db.rename_collection(&self.name, new_name, &txn));
+        Ok(())
+    }
+
     fn insert_one(&self, doc: impl Borrow<T>) -> Result<InsertOneResult>
     where T: Serialize {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
@@ -254,10 +380,23 @@ impl<T> CollectionT<T> for Collection<T> {
     }
 
     fn insert_many(&self, docs: impl IntoIterator<Item = impl Borrow<T>>) -> Result<InsertManyResult>
+    where T: Serialize {
+        self.insert_many_with_options(docs, InsertManyOptions::default())
+    }
+
+    fn insert_many_with_options(&self, docs: impl IntoIterator<Item = impl Borrow<T>>, options: InsertManyOptions) -> Result<InsertManyResult>
+    where T: Serialize {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let txn = db.start_transaction_with_durability(options.durability)?;
+        let result = try_db_op!(txn, db.insert_many(&self.name, docs, &options, &txn));
+        Ok(result)
+    }
+
+    fn bulk_load(&self, docs: impl IntoIterator<Item = impl Borrow<T>>) -> Result<InsertManyResult>
     where T: Serialize {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
         let txn = db.start_transaction()?;
-        let result = try_db_op!(txn, db.insert_many(&self.name, docs, &txn));
+        let result = try_db_op!(txn, db.bulk_load(&self.name, docs, &txn));
         Ok(result)
     }
 
@@ -266,6 +405,15 @@ impl<T> CollectionT<T> for Collection<T> {
         Find::new(self.db.clone(), &self.name, None, filter)
     }
 
+    fn find_raw(&self, filter: Document) -> FindRaw<'_, '_> {
+        FindRaw::new(Find::new(self.db.clone(), &self.name, None, filter))
+    }
+
+    fn paginate(&self, filter: Document, page_size: u64) -> Paginate<'_, '_, T>
+    where T: DeserializeOwned + Send + Sync {
+        Paginate::new(self.db.clone(), &self.name, None, filter, page_size)
+    }
+
     fn find_one(&self, filter: Document) -> Result<Option<T>>
     where T: DeserializeOwned + Send + Sync {
         let mut cursor = self.find(filter).run()?;
@@ -276,6 +424,19 @@ impl<T> CollectionT<T> for Collection<T> {
         Ok(Some(cursor.deserialize_current()?))
     }
 
+    #[cfg(feature = "arrow")]
+    fn to_arrow(&self, filter: Document) -> ToArrow<'_, '_> {
+        ToArrow::new(Find::new(self.db.clone(), &self.name, None, filter))
+    }
+
+    #[cfg(feature = "parquet")]
+    fn import_parquet(&self, path: impl AsRef<Path>) -> Result<InsertManyResult> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let txn = db.start_transaction()?;
+        let result = try_db_op!(txn, db.import_parquet(&self.name, path.as_ref(), &txn));
+        Ok(result)
+    }
+
     fn aggregate(&self, pipeline: impl IntoIterator<Item = Document>) -> Aggregate<'_, '_> {
         Aggregate::new(
             self.db.clone(),
@@ -284,4 +445,12 @@ impl<T> CollectionT<T> for Collection<T> {
             None,
         )
     }
+
+    fn watch(&self) -> Watch<'_, T> {
+        Watch::new(self.db.clone(), Some(&self.name))
+    }
+
+    fn clone_to<'a>(&'a self, target: &'a str) -> CloneTo<'a, 'a> {
+        CloneTo::new(self.db.clone(), &self.name, target, None)
+    }
 }
@@ -12,13 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use bson::{Binary, DateTime};
+use std::sync::OnceLock;
+use bson::{Binary, Bson, DateTime, Document};
 use bson::spec::BinarySubtype;
 use serde::{Deserialize, Serialize};
 use indexmap::IndexMap;
 use uuid::Uuid;
-use crate::IndexOptions;
+use crate::{Error, IndexOptions, Result};
+use crate::errors::{DocumentLimit, DocumentLimitError};
+use crate::index::PartialFilter;
+use crate::options::{Collation, FieldDefault, ValidationAction, ValidationLevel};
 use crate::utils::bson::bson_datetime_now;
+use crate::vector::VectorSimilarity;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -28,19 +33,34 @@ pub struct IndexInfo {
     pub keys: IndexMap<String, i8>,
 
     pub options: Option<IndexOptions>,
+
+    /// Whether a document has several entries in the index, one for each element of an array.
+    /// `None` for the indexes of the files which didn't record it, they may have some.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    multikey: Option<bool>,
+
+    /// The `partialFilterExpression` of the options, parsed once for the loaded index.
+    #[serde(skip)]
+    partial_filter: OnceLock<Option<Option<PartialFilter>>>,
 }
 
 impl IndexInfo {
 
-    pub fn single_index(name: String, order: i8, options: Option<IndexOptions>) -> IndexInfo {
-        let mut keys = IndexMap::new();
-        keys.insert(name, order);
+    pub fn new(keys: IndexMap<String, i8>, options: Option<IndexOptions>) -> IndexInfo {
         IndexInfo {
             keys,
             options,
+            multikey: Some(false),
+            partial_filter: OnceLock::new(),
         }
     }
 
+    pub fn single_index(name: String, order: i8, options: Option<IndexOptions>) -> IndexInfo {
+        let mut keys = IndexMap::new();
+        keys.insert(name, order);
+        IndexInfo::new(keys, options)
+    }
+
     #[inline]
     pub fn is_unique(&self) -> bool {
         self.options
@@ -49,6 +69,73 @@ impl IndexInfo {
             .unwrap_or(false)
     }
 
+    #[inline]
+    pub fn collation(&self) -> Collation {
+        self.options
+            .as_ref()
+            .and_then(|options| options.collation)
+            .unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn expire_after_secs(&self) -> Option<u64> {
+        self.options
+            .as_ref()
+            .and_then(|options| options.expire_after_secs)
+    }
+
+    #[inline]
+    pub fn partial_filter_expression(&self) -> Option<&Document> {
+        self.options
+            .as_ref()
+            .and_then(|options| options.partial_filter_expression.as_ref())
+    }
+
+    /// The parsed filter of a partial index, `Some(None)` if it fails to parse:
+    /// only a filter written by hand does, it has no document and covers no query.
+    fn partial_filter(&self) -> Option<Option<&PartialFilter>> {
+        self.partial_filter
+            .get_or_init(|| self.partial_filter_expression().map(|filter| PartialFilter::parse(filter).ok()))
+            .as_ref()
+            .map(Option::as_ref)
+    }
+
+    /// Whether the document has entries in the index, a partial index leaves out
+    /// the documents not matching its filter.
+    pub(crate) fn indexes(&self, doc: &Document) -> bool {
+        match self.partial_filter() {
+            Some(filter) => filter.is_some_and(|filter| filter.matches(doc, self.collation())),
+            None => true,
+        }
+    }
+
+    /// Whether the index has the entries of all the documents matching the query,
+    /// a partial index only has them if the query implies its filter.
+    pub(crate) fn covers(&self, query: &Document) -> bool {
+        match self.partial_filter() {
+            Some(filter) => filter.is_some_and(|filter| filter.is_implied_by(query, self.collation())),
+            None => true,
+        }
+    }
+
+    /// Whether a document may have several entries in the index, the bounds of
+    /// a range on its field may then be satisfied by different elements.
+    #[inline]
+    pub(crate) fn is_multikey(&self) -> bool {
+        self.multikey != Some(false)
+    }
+
+    #[inline]
+    pub(crate) fn set_multikey(&mut self) {
+        self.multikey = Some(true);
+    }
+
+    /// Whether the two indexes have the same keys and options, whatever their names.
+    pub(crate) fn same_definition(&self, other: &IndexInfo) -> bool {
+        self.keys == other.keys
+            && IndexOptions::normalized(self.options.as_ref()) == IndexOptions::normalized(other.options.as_ref())
+    }
+
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -81,6 +168,63 @@ pub struct CollectionSpecification {
     /// The name is converted to the underline format.
     /// For examples, `author.age` is converted to `author_age`
     pub indexes: IndexMap<String, IndexInfo>,
+
+    /// The `2dsphere` indexes, name -> field, the values of their fields
+    /// are checked to be GeoJSON geometries.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub geo_indexes: IndexMap<String, String>,
+
+    /// The `"vector"` indexes, name -> info, the values of their fields
+    /// are kept in a HNSW graph for the `$vectorSearch` stages.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub vector_indexes: IndexMap<String, VectorIndexInfo>,
+
+    /// The validation of the documents written to the collection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validation: Option<ValidationInfo>,
+
+    /// The limits of a capped collection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capped: Option<CappedInfo>,
+
+    /// The field holding the date after which a document is expired.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expire_at_field: Option<String>,
+
+    /// The definition of a materialized view, whose documents are kept up to date
+    /// with the documents of its source collection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub materialized_view: Option<MaterializedViewInfo>,
+
+    /// The definition of a read-only view, whose documents are computed
+    /// from its source collection when it's queried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view: Option<ViewInfo>,
+
+    /// The limits of the documents written to the collection,
+    /// overriding the limits of the database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<DocumentLimits>,
+
+    /// The values of the fields omitted by the inserted documents.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub defaults: Option<IndexMap<String, FieldDefault>>,
+
+    /// The field whose values are kept in a bloom filter for the point lookups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bloom_filter_field: Option<String>,
+
+    /// The field whose words are kept in a trigram index for the `$fuzzy` searches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fuzzy_index_field: Option<String>,
+
+    /// Merge the concurrent versions of the synced documents field by field.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub crdt: bool,
+
+    /// The statistics gathered by the last `analyze`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statistics: Option<CollectionStatistics>,
 }
 
 impl CollectionSpecification {
@@ -105,6 +249,315 @@ impl CollectionSpecification {
             },
 
             indexes: IndexMap::new(),
+
+            geo_indexes: IndexMap::new(),
+
+            vector_indexes: IndexMap::new(),
+
+            validation: None,
+
+            capped: None,
+
+            expire_at_field: None,
+
+            materialized_view: None,
+
+            view: None,
+
+            limits: None,
+
+            defaults: None,
+
+            bloom_filter_field: None,
+
+            fuzzy_index_field: None,
+
+            crdt: false,
+
+            statistics: None,
+        }
+    }
+
+    /// The options the collection was created or modified with,
+    /// in the format of `createCollection`.
+    pub(crate) fn options(&self) -> Result<Document> {
+        let mut options = Document::new();
+        if let Some(validation) = &self.validation {
+            options.insert("validator", validation.validator.clone());
+            options.insert("validationLevel", bson::to_bson(&validation.validation_level)?);
+            options.insert("validationAction", bson::to_bson(&validation.validation_action)?);
+        }
+        if let Some(capped) = &self.capped {
+            options.insert("capped", true);
+            if let Some(size) = capped.size {
+                options.insert("size", size as i64);
+            }
+            if let Some(max) = capped.max {
+                options.insert("max", max as i64);
+            }
+        }
+        if let Some(field) = &self.expire_at_field {
+            options.insert("expireAtField", field.clone());
+        }
+        if let Some(limits) = &self.limits {
+            if let Some(max_size) = limits.max_size {
+                options.insert("maxDocumentSize", max_size as i64);
+            }
+            if let Some(max_depth) = limits.max_depth {
+                options.insert("maxDocumentDepth", max_depth as i64);
+            }
+        }
+        if let Some(defaults) = &self.defaults {
+            options.insert("defaults", bson::to_bson(defaults)?);
+        }
+        if let Some(field) = &self.bloom_filter_field {
+            options.insert("bloomFilter", field.clone());
+        }
+        if let Some(field) = &self.fuzzy_index_field {
+            options.insert("fuzzyIndex", field.clone());
+        }
+        if self.crdt {
+            options.insert("crdt", true);
+        }
+        if let Some(view) = &self.view {
+            options.insert("viewOn", view.source.clone());
+            options.insert("pipeline", view.pipeline.clone());
+        }
+        Ok(options)
+    }
+
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CappedInfo {
+    /// The maximum size of the documents, in bytes.
+    pub size: Option<u64>,
+    /// The maximum number of documents.
+    pub max: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaterializedViewInfo {
+    /// The collection the documents of the view are computed from.
+    pub source: String,
+    /// The aggregation pipeline run on the documents of the source collection.
+    pub pipeline: Vec<Document>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewInfo {
+    /// The collection, or the view, the documents of the view are computed from.
+    pub source: String,
+    /// The aggregation pipeline run on the documents of the source when the view is queried.
+    pub pipeline: Vec<Document>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VectorIndexInfo {
+    /// The field holding the vectors.
+    pub field: String,
+    /// The number of values of the vectors.
+    pub dimensions: u32,
+    pub similarity: VectorSimilarity,
+}
+
+/// The statistics of a collection gathered by `analyze`.
+/// They are not maintained by the writes, they are refreshed by analyzing the collection again.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionStatistics {
+    /// The number of documents of the collection.
+    pub documents: u64,
+    /// The statistics of the indexed fields, by the name of their index.
+    pub indexes: IndexMap<String, FieldStatistics>,
+    pub analyzed_at: DateTime,
+}
+
+/// The statistics of the values of an indexed field.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldStatistics {
+    /// The indexed field, such as `author.age`.
+    pub field: String,
+    /// The number of documents having the field.
+    pub count: u64,
+    /// The number of distinct values of the field.
+    pub distinct: u64,
+    /// The most common values, the most common first.
+    pub most_common: Vec<ValueFrequency>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueFrequency {
+    pub value: Bson,
+    /// The number of documents having the value.
+    pub count: u64,
+}
+
+impl FieldStatistics {
+
+    /// The estimated number of documents whose field is equal to `value`.
+    /// The values which are not among the most common ones share the rest of the documents evenly.
+    pub(crate) fn estimate(&self, value: &Bson) -> u64 {
+        if let Some(frequency) = self.most_common.iter().find(|frequency| &frequency.value == value) {
+            return frequency.count;
+        }
+        let common_count: u64 = self.most_common.iter().map(|frequency| frequency.count).sum();
+        let rest_count = self.count.saturating_sub(common_count);
+        let rest_distinct = self.distinct.saturating_sub(self.most_common.len() as u64);
+        if rest_distinct == 0 {
+            return 0;
+        }
+        rest_count.div_ceil(rest_distinct)
+    }
+
+}
+
+/// The limits of the documents written to a collection, `None` means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentLimits {
+    /// The maximum size of an encoded document, in bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    /// The maximum nesting depth of the documents and arrays,
+    /// a document without any embedded document or array has a depth of 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<u64>,
+}
+
+impl DocumentLimits {
+
+    /// Take the limits which are not set from `fallback`.
+    pub(crate) fn or(self, fallback: DocumentLimits) -> DocumentLimits {
+        DocumentLimits {
+            max_size: self.max_size.or(fallback.max_size),
+            max_depth: self.max_depth.or(fallback.max_depth),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_unlimited(&self) -> bool {
+        self.max_size.is_none() && self.max_depth.is_none()
+    }
+
+    /// Check the document and its encoded size against the limits.
+    pub(crate) fn check(&self, col_name: &str, doc: &Document, size: usize) -> Result<()> {
+        if let Some(max) = self.max_size {
+            if size as u64 > max {
+                return Err(DocumentLimits::exceeded(col_name, DocumentLimit::Size, max, size as u64));
+            }
+        }
+        if let Some(max) = self.max_depth {
+            let depth = document_depth(doc, max.saturating_add(1));
+            if depth > max {
+                return Err(DocumentLimits::exceeded(col_name, DocumentLimit::Depth, max, depth));
+            }
+        }
+        Ok(())
+    }
+
+    fn exceeded(col_name: &str, limit: DocumentLimit, max: u64, actual: u64) -> Error {
+        Error::DocumentLimitExceeded(Box::new(DocumentLimitError {
+            ns: col_name.to_string(),
+            limit,
+            max,
+            actual,
+        }))
+    }
+
+}
+
+/// The nesting depth of a document, the walk stops at `stop`
+/// so a pathological document doesn't exhaust the stack.
+fn document_depth(doc: &Document, stop: u64) -> u64 {
+    let mut max = 1;
+    for value in doc.values() {
+        max = max.max(1 + value_depth(value, stop - 1));
+        if max >= stop {
+            break;
+        }
+    }
+    max
+}
+
+fn value_depth(value: &Bson, stop: u64) -> u64 {
+    if stop == 0 {
+        return 0;
+    }
+    match value {
+        Bson::Document(doc) => document_depth(doc, stop),
+        Bson::Array(arr) => {
+            let mut max = 1;
+            for item in arr {
+                max = max.max(1 + value_depth(item, stop - 1));
+                if max >= stop {
+                    break;
+                }
+            }
+            max
+        }
+        _ => 0,
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationInfo {
+    /// `{ "$jsonSchema": <schema> }`
+    pub validator: Document,
+    pub validation_level: ValidationLevel,
+    pub validation_action: ValidationAction,
+}
+
+impl ValidationInfo {
+
+    /// Check that the validator is a supported `$jsonSchema`.
+    pub(crate) fn check_validator(validator: &Document) -> Result<()> {
+        for key in validator.keys() {
+            if key != "$jsonSchema" {
+                return Err(Error::InvalidJsonSchema(format!("validator '{}' is not supported, use '$jsonSchema'", key)));
+            }
+        }
+        let schema = validator.get_document("$jsonSchema")
+            .map_err(|_| Error::InvalidJsonSchema("'$jsonSchema' must be an object".to_string()))?;
+        crate::schema::check_schema(schema)
+    }
+
+    /// Validate the document about to be written, `old` is the document before
+    /// the update.
+    pub(crate) fn validate(&self, col_name: &str, old: Option<&Document>, doc: &Document) -> Result<()> {
+        let schema = match self.validator.get_document("$jsonSchema") {
+            Ok(schema) => schema,
+            Err(_) => return Ok(()),
+        };
+        match self.validation_level {
+            ValidationLevel::Off => return Ok(()),
+            ValidationLevel::Moderate => {
+                let old_is_valid = old.is_none_or(|old| {
+                    crate::schema::validate(schema, &old.clone().into()).is_ok()
+                });
+                if !old_is_valid {
+                    return Ok(());
+                }
+            }
+            ValidationLevel::Strict => (),
+        }
+        let reason = match crate::schema::validate(schema, &doc.clone().into()) {
+            Ok(()) => return Ok(()),
+            Err(reason) => reason,
+        };
+        match self.validation_action {
+            ValidationAction::Error => Err(Error::DocumentValidationFailed(reason)),
+            ValidationAction::Warn => {
+                crate::polo_log!("document of '{}' failed validation: {}", col_name, reason);
+                Ok(())
+            }
         }
     }
 
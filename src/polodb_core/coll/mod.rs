@@ -15,6 +15,8 @@
 mod collection;
 pub mod collection_info;
 mod txn_collection;
+mod model;
 
 pub use collection::{Collection, CollectionT};
 pub use txn_collection::TransactionalCollection;
+pub use model::Model;
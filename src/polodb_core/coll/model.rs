@@ -0,0 +1,65 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use bson::Bson;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::IndexModel;
+
+/// A type stored in its own collection.
+///
+/// Usually derived with `#[derive(PoloModel)]` (requires the `derive` feature),
+/// and used through [`Database::collection_for`](crate::Database::collection_for).
+///
+/// ```rust
+/// # #[cfg(feature = "derive")]
+/// # {
+/// use polodb_core::{Database, CollectionT, PoloModel};
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Debug, Serialize, Deserialize, PoloModel)]
+/// #[polo(collection = "users")]
+/// struct User {
+///     #[serde(rename = "_id")]
+///     #[polo(id)]
+///     id: i64,
+///     #[polo(unique)]
+///     email: String,
+///     name: String,
+/// }
+///
+/// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-model");
+/// let db = Database::open_path(db_path).unwrap();
+/// let users = db.collection_for::<User>().unwrap();
+/// users.insert_one(User { id: 1, email: "a@b.c".into(), name: "Alice".into() }).unwrap();
+/// let user = users.find_one(polodb_core::bson::doc! { User::EMAIL: "a@b.c" }).unwrap();
+/// assert_eq!(user.unwrap().name, "Alice");
+/// # }
+/// ```
+pub trait Model: Serialize + DeserializeOwned + Send + Sync {
+    /// The name of the collection storing the type.
+    const COLLECTION_NAME: &'static str;
+
+    /// The indexes created when the collection is obtained
+    /// by [`Database::collection_for`](crate::Database::collection_for).
+    fn indexes() -> Vec<IndexModel> {
+        Vec::new()
+    }
+
+    /// The value of the `_id` field, `None` if it's generated by the database.
+    fn id(&self) -> Option<Bson> {
+        None
+    }
+}
@@ -13,17 +13,21 @@
 // limitations under the License.
 
 use std::borrow::Borrow;
+#[cfg(feature = "parquet")]
+use std::path::Path;
 use std::sync::Weak;
 use bson::Document;
 use serde::Serialize;
 use crate::db::db_inner::DatabaseInner;
-use crate::options::UpdateOptions;
+use crate::options::{DeleteOptions, InsertManyOptions, UpdateOptions};
 use serde::de::DeserializeOwned;
 use crate::{CollectionT, Error, IndexModel, Result};
-use crate::action::{Aggregate, Find};
+use crate::action::{Aggregate, CloneTo, Find, FindRaw, Paginate, Watch};
+#[cfg(feature = "arrow")]
+use crate::action::ToArrow;
 use crate::results::{DeleteResult, InsertManyResult, InsertOneResult, UpdateResult};
 use crate::transaction::TransactionInner;
-use super::collection_info::IndexInfo;
+use super::collection_info::{CollectionStatistics, IndexInfo};
 
 pub struct TransactionalCollection<T> {
     db: Weak<DatabaseInner>,
@@ -56,6 +60,17 @@ impl<T> CollectionT<T> for TransactionalCollection<T> {
         db.count_documents(&self.name, &self.txn)
     }
 
+    fn count(&self) -> Result<u64> {
+        self.count_documents()
+    }
+
+    fn exists(&self, filter: Document) -> Result<bool> {
+        let mut cursor = Find::<Document>::new(self.db.clone(), &self.name, Some(&self.txn), filter)
+            .limit(1)
+            .run()?;
+        cursor.advance()
+    }
+
     fn update_one(&self, query: Document, update: Document) -> crate::Result<UpdateResult> {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
         let result = db.update_one(
@@ -111,15 +126,23 @@ impl<T> CollectionT<T> for TransactionalCollection<T> {
     }
 
     fn delete_many(&self, query: Document) -> crate::Result<DeleteResult> {
+        self.delete_many_with_options(query, DeleteOptions::default())
+    }
+
+    fn delete_many_with_options(&self, query: Document, options: DeleteOptions) -> Result<DeleteResult> {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
-        let result = db.delete_many(&self.name, query, &self.txn)?;
+        let result = db.delete_many(&self.name, query, &options, &self.txn)?;
         Ok(result)
     }
 
-    fn create_index(&self, index: IndexModel) -> crate::Result<()> {
+    fn create_index(&self, index: IndexModel) -> crate::Result<String> {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
-        db.create_index(&self.name, index, &self.txn)?;
-        Ok(())
+        db.create_index(&self.name, index, &self.txn)
+    }
+
+    fn create_indexes(&self, indexes: impl IntoIterator<Item = IndexModel>) -> Result<Vec<String>> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        db.create_indexes(&self.name, indexes.into_iter().collect(), &self.txn)
     }
 
     fn drop_index(&self, name: impl AsRef<str>) -> Result<()> {
@@ -138,12 +161,22 @@ impl<T> CollectionT<T> for TransactionalCollection<T> {
         db.describe_collection_index(&self.name, name.as_ref(), &self.txn)
     }
 
+    fn analyze(&self) -> Result<CollectionStatistics> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        db.analyze(&self.name, &self.txn)
+    }
+
     fn drop(&self) -> crate::Result<()> {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
         db.drop_collection(&self.name, &self.txn)?;
         Ok(())
     }
 
+    fn rename(&self, new_name: &str) -> crate::Result<()> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        db.rename_collection(&self.name, new_name, &self.txn)
+    }
+
     fn insert_one(&self, doc: impl Borrow<T>) -> crate::Result<InsertOneResult>
     where T: Serialize {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
@@ -156,17 +189,37 @@ impl<T> CollectionT<T> for TransactionalCollection<T> {
     }
 
     fn insert_many(&self, docs: impl IntoIterator<Item = impl Borrow<T>>) -> crate::Result<InsertManyResult>
+    where T: Serialize {
+        self.insert_many_with_options(docs, InsertManyOptions::default())
+    }
+
+    fn insert_many_with_options(&self, docs: impl IntoIterator<Item = impl Borrow<T>>, options: InsertManyOptions) -> Result<InsertManyResult>
     where T: Serialize {
         let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
-        let result = db.insert_many(&self.name, docs, &self.txn)?;
+        let result = db.insert_many(&self.name, docs, &options, &self.txn)?;
         Ok(result)
     }
 
+    fn bulk_load(&self, docs: impl IntoIterator<Item = impl Borrow<T>>) -> Result<InsertManyResult>
+    where T: Serialize {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        db.bulk_load(&self.name, docs, &self.txn)
+    }
+
     fn find(&self, filter: Document) -> Find<'_, '_, T>
     where T: DeserializeOwned + Send + Sync {
         Find::new(self.db.clone(), &self.name, Some(&self.txn), filter)
     }
 
+    fn find_raw(&self, filter: Document) -> FindRaw<'_, '_> {
+        FindRaw::new(Find::new(self.db.clone(), &self.name, Some(&self.txn), filter))
+    }
+
+    fn paginate(&self, filter: Document, page_size: u64) -> Paginate<'_, '_, T>
+    where T: DeserializeOwned + Send + Sync {
+        Paginate::new(self.db.clone(), &self.name, Some(&self.txn), filter, page_size)
+    }
+
     fn find_one(&self, filter: Document) -> Result<Option<T>>
     where T: DeserializeOwned + Send + Sync {
         let mut cursor = self.find(filter).run()?;
@@ -177,6 +230,17 @@ impl<T> CollectionT<T> for TransactionalCollection<T> {
         Ok(Some(cursor.deserialize_current()?))
     }
 
+    #[cfg(feature = "arrow")]
+    fn to_arrow(&self, filter: Document) -> ToArrow<'_, '_> {
+        ToArrow::new(Find::new(self.db.clone(), &self.name, Some(&self.txn), filter))
+    }
+
+    #[cfg(feature = "parquet")]
+    fn import_parquet(&self, path: impl AsRef<Path>) -> Result<InsertManyResult> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        db.import_parquet(&self.name, path.as_ref(), &self.txn)
+    }
+
     fn aggregate(&self, pipeline: impl IntoIterator<Item = Document>) -> Aggregate<'_, '_> {
         Aggregate::new(
             self.db.clone(),
@@ -185,4 +249,12 @@ impl<T> CollectionT<T> for TransactionalCollection<T> {
             Some(&self.txn),
         )
     }
+
+    fn watch(&self) -> Watch<'_, T> {
+        Watch::new(self.db.clone(), Some(&self.name))
+    }
+
+    fn clone_to<'a>(&'a self, target: &'a str) -> CloneTo<'a, 'a> {
+        CloneTo::new(self.db.clone(), &self.name, target, Some(&self.txn))
+    }
 }
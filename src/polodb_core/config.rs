@@ -12,6 +12,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::object_id::ObjectIdCounterMode;
+use crate::options::Durability;
+use crate::strictness::BsonStrictness;
+
+/// How the files of a database are laid out in its directory,
+/// chosen when the database is created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StorageLayout {
+    /// The data files and the write-ahead log side by side, the default.
+    #[default]
+    Single,
+    /// The write-ahead log in the `wal` subdirectory, and the documents larger
+    /// than the min blob size in their own blob files, so an incremental backup
+    /// only copies the files that changed and the large documents are not rewritten
+    /// when the data files are compacted.
+    Directory,
+}
+
 ///
 /// Config builder for the database
 ///
@@ -80,6 +100,190 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn get_enable_statistics(&self) -> bool {
+        self.inner.enable_statistics
+    }
+
+    /// Collect the statistics of the storage engine, such as the block cache hits
+    /// and the bytes written to the WAL, at a small cost of performance.
+    /// See [`crate::Metrics::snapshot`].
+    pub fn set_enable_statistics(&mut self, v: bool) -> &mut Self {
+        self.inner.enable_statistics = v;
+        self
+    }
+
+    pub fn get_oplog_size(&self) -> u64 {
+        self.inner.oplog_size
+    }
+
+    /// Record the last `v` committed changes in the oplog, so a change stream can be
+    /// resumed after a restart with [`crate::action::Watch::resume_after`].
+    /// The oplog is disabled when `v` is 0, the default.
+    pub fn set_oplog_size(&mut self, v: u64) -> &mut Self {
+        self.inner.oplog_size = v;
+        self
+    }
+
+    pub fn get_max_document_size(&self) -> u64 {
+        self.inner.max_document_size
+    }
+
+    /// Reject the documents larger than `v` bytes once encoded,
+    /// unless the collection sets its own limit. There is no limit when `v` is 0, the default.
+    pub fn set_max_document_size(&mut self, v: u64) -> &mut Self {
+        self.inner.max_document_size = v;
+        self
+    }
+
+    pub fn get_max_document_depth(&self) -> u64 {
+        self.inner.max_document_depth
+    }
+
+    /// Reject the documents nesting more than `v` levels of documents and arrays,
+    /// unless the collection sets its own limit. There is no limit when `v` is 0, the default.
+    pub fn set_max_document_depth(&mut self, v: u64) -> &mut Self {
+        self.inner.max_document_depth = v;
+        self
+    }
+
+    pub fn get_object_id_machine_id(&self) -> Option<u32> {
+        self.inner.object_id_machine_id
+    }
+
+    /// Use the 3 lower bytes of `v` as the machine id of the generated ObjectIds,
+    /// instead of random bytes.
+    pub fn set_object_id_machine_id(&mut self, v: u32) -> &mut Self {
+        self.inner.object_id_machine_id = Some(v);
+        self
+    }
+
+    pub fn get_object_id_process_id(&self) -> Option<u16> {
+        self.inner.object_id_process_id
+    }
+
+    /// Use `v` as the process id of the generated ObjectIds, instead of random bytes.
+    pub fn set_object_id_process_id(&mut self, v: u16) -> &mut Self {
+        self.inner.object_id_process_id = Some(v);
+        self
+    }
+
+    pub fn get_object_id_counter_mode(&self) -> ObjectIdCounterMode {
+        self.inner.object_id_counter_mode
+    }
+
+    /// Choose how the counter of the generated ObjectIds advances,
+    /// see [`ObjectIdCounterMode`].
+    pub fn set_object_id_counter_mode(&mut self, v: ObjectIdCounterMode) -> &mut Self {
+        self.inner.object_id_counter_mode = v;
+        self
+    }
+
+    pub fn get_key_restart_interval(&self) -> u32 {
+        self.inner.key_restart_interval
+    }
+
+    /// The restart interval of the blocks of the storage: one key every `v` keys of
+    /// a block is stored in full, the keys in between only store the bytes they don't
+    /// share with the previous key. It is passed to the storage as it is, 0 is taken
+    /// as 1 and the values above `i32::MAX` as `i32::MAX`. The default is 16.
+    pub fn set_key_restart_interval(&mut self, v: u32) -> &mut Self {
+        self.inner.key_restart_interval = v.clamp(1, i32::MAX as u32);
+        self
+    }
+
+    pub fn get_index_build_parallelism(&self) -> usize {
+        self.inner.index_build_parallelism
+    }
+
+    /// Build the indexes created together by `create_indexes`, or rebuilt after
+    /// a `bulk_load`, on up to `v` threads sharing one scan of the collection.
+    /// The default is the number of available CPUs.
+    pub fn set_index_build_parallelism(&mut self, v: usize) -> &mut Self {
+        self.inner.index_build_parallelism = v.max(1);
+        self
+    }
+
+    pub fn get_record_cache_size(&self) -> u64 {
+        self.inner.record_cache_size
+    }
+
+    /// Keep up to `v` bytes of the documents recently found by their `_id` or an index
+    /// in memory, compressed, so reading them again doesn't read the storage.
+    /// The cache is disabled when `v` is 0, the default.
+    pub fn set_record_cache_size(&mut self, v: u64) -> &mut Self {
+        self.inner.record_cache_size = v;
+        self
+    }
+
+    pub fn get_query_cache_size(&self) -> u64 {
+        self.inner.query_cache_size
+    }
+
+    /// Keep up to `v` bytes of the results of the recent finds in memory, so running
+    /// the same find again on a collection which wasn't written since doesn't run the query.
+    /// A find is cached with its filter, sort, skip and limit; the finds in a transaction,
+    /// with a `filter_fn` and on views are not. The cache is disabled when `v` is 0, the default.
+    pub fn set_query_cache_size(&mut self, v: u64) -> &mut Self {
+        self.inner.query_cache_size = v;
+        self
+    }
+
+    pub fn get_query_cache_ttl(&self) -> Option<Duration> {
+        self.inner.query_cache_ttl
+    }
+
+    /// Run the query again once its cached result is older than `v`, even if its
+    /// collection wasn't written, see [`ConfigBuilder::set_query_cache_size`].
+    /// The results are kept until their collection is written by default.
+    pub fn set_query_cache_ttl(&mut self, v: Option<Duration>) -> &mut Self {
+        self.inner.query_cache_ttl = v;
+        self
+    }
+
+    pub fn get_storage_layout(&self) -> StorageLayout {
+        self.inner.storage_layout
+    }
+
+    /// Choose the layout of the files of a new database, see [`StorageLayout`].
+    /// An existing database keeps the layout it was created with.
+    pub fn set_storage_layout(&mut self, v: StorageLayout) -> &mut Self {
+        self.inner.storage_layout = v;
+        self
+    }
+
+    pub fn get_min_blob_size(&self) -> u64 {
+        self.inner.min_blob_size
+    }
+
+    /// Store the documents of at least `v` bytes in the blob files
+    /// of the [`StorageLayout::Directory`] layout. The default is 64 KiB.
+    pub fn set_min_blob_size(&mut self, v: u64) -> &mut Self {
+        self.inner.min_blob_size = v;
+        self
+    }
+
+    pub fn get_bson_strictness(&self) -> BsonStrictness {
+        self.inner.bson_strictness
+    }
+
+    /// Check the inserted and updated documents for the values other BSON
+    /// implementations fail to read, see [`BsonStrictness`].
+    pub fn set_bson_strictness(&mut self, v: BsonStrictness) -> &mut Self {
+        self.inner.bson_strictness = v;
+        self
+    }
+
+    pub fn get_durability(&self) -> Durability {
+        self.inner.durability
+    }
+
+    /// Choose when the writes return, see [`Durability`]. A write can override it
+    /// with the `durability` of its options. The default is [`Durability::Sync`].
+    pub fn set_durability(&mut self, v: Durability) -> &mut Self {
+        self.inner.durability = v;
+        self
+    }
+
     pub fn take(self) -> Config {
         self.inner
     }
@@ -98,9 +302,27 @@ pub struct Config {
     pub lsm_page_size:     u32,
     pub lsm_block_size:    u32,
     pub sync_log_count:    u64,
+    pub enable_statistics: bool,
+    pub oplog_size:        u64,
+    pub max_document_size:  u64,
+    pub max_document_depth: u64,
+    pub object_id_machine_id: Option<u32>,
+    pub object_id_process_id: Option<u16>,
+    pub object_id_counter_mode: ObjectIdCounterMode,
+    pub key_restart_interval: u32,
+    pub index_build_parallelism: usize,
+    pub record_cache_size: u64,
+    pub query_cache_size: u64,
+    pub query_cache_ttl: Option<Duration>,
+    pub storage_layout: StorageLayout,
+    pub min_blob_size: u64,
+    pub bson_strictness: BsonStrictness,
+    pub durability: Durability,
 }
 
 const SYNC_LOG_COUNT: u64 = 1000;
+const KEY_RESTART_INTERVAL: u32 = 16;
+const MIN_BLOB_SIZE: u64 = 64 * 1024;
 
 impl Default for Config {
 
@@ -111,6 +333,23 @@ impl Default for Config {
             lsm_page_size: 4096,
             lsm_block_size: 4 * 1024 * 1024,
             sync_log_count: SYNC_LOG_COUNT,
+            enable_statistics: false,
+            oplog_size: 0,
+            max_document_size: 0,
+            max_document_depth: 0,
+            object_id_machine_id: None,
+            object_id_process_id: None,
+            object_id_counter_mode: ObjectIdCounterMode::Random,
+            key_restart_interval: KEY_RESTART_INTERVAL,
+            index_build_parallelism: std::thread::available_parallelism()
+                .map_or(1, |parallelism| parallelism.get()),
+            record_cache_size: 0,
+            query_cache_size: 0,
+            query_cache_ttl: None,
+            storage_layout: StorageLayout::Single,
+            min_blob_size: MIN_BLOB_SIZE,
+            bson_strictness: BsonStrictness::Lenient,
+            durability: Durability::Sync,
         }
     }
 
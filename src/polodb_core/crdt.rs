@@ -0,0 +1,283 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bson::{Bson, DateTime, Document};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use crate::Result;
+
+/// When and where a write was made, the writes are ordered by time, then by node.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+struct Stamp {
+    time: i64,
+    node: String,
+}
+
+/// The last write of a top-level field: a value, an array whose elements
+/// are in the set of the field, or neither if the field was removed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Register {
+    stamp: Stamp,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<Bson>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    array: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Element {
+    tag: String,
+    value: Bson,
+}
+
+/// An observed-remove set: removing an element only removes the additions
+/// seen by the remover, so an element added concurrently elsewhere is kept.
+/// The elements are kept in the order they were added.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct OrSet {
+    elements: Vec<Element>,
+    removed: Vec<String>,
+}
+
+impl OrSet {
+
+    fn live(&self) -> impl Iterator<Item = &Element> {
+        self.elements.iter().filter(move |element| !self.removed.contains(&element.tag))
+    }
+
+    fn clear(&mut self) {
+        let tags = self.live().map(|element| element.tag.clone()).collect::<Vec<_>>();
+        self.removed.extend(tags);
+        self.removed.sort();
+    }
+
+    /// Remove the elements missing from `values`, and add the new ones.
+    fn update(&mut self, values: &[Bson], stamp: &Stamp) {
+        let mut remaining = self.live().collect::<Vec<_>>();
+        let mut added = Vec::new();
+        for value in values {
+            match remaining.iter().position(|element| element.value == *value) {
+                Some(pos) => {
+                    remaining.remove(pos);
+                }
+                None => added.push(value.clone()),
+            }
+        }
+        let removed = remaining.into_iter().map(|element| element.tag.clone()).collect::<Vec<_>>();
+        self.removed.extend(removed);
+        self.removed.sort();
+        for value in added {
+            // the time first, so the tags sort in the order of the additions
+            let tag = format!("{:016x}:{}:{}", stamp.time, stamp.node, self.elements.len());
+            self.elements.push(Element { tag, value });
+        }
+        self.elements.sort_by(|a, b| a.tag.cmp(&b.tag));
+    }
+
+    fn merge(&mut self, other: &OrSet) {
+        for element in &other.elements {
+            if !self.elements.iter().any(|mine| mine.tag == element.tag) {
+                self.elements.push(element.clone());
+            }
+        }
+        for tag in &other.removed {
+            if !self.removed.contains(tag) {
+                self.removed.push(tag.clone());
+            }
+        }
+        self.elements.sort_by(|a, b| a.tag.cmp(&b.tag));
+        self.removed.sort();
+    }
+
+}
+
+/// The mergeable state of a document of a CRDT collection: a last-write-wins register
+/// for every top-level field, and an observed-remove set for every array field.
+/// Merging the states of two devices in any order gives the same document.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CrdtState {
+    fields: IndexMap<String, Register>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    sets: IndexMap<String, OrSet>,
+    /// The last delete of the document, the fields written after it bring the document back.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deleted: Option<Stamp>,
+}
+
+impl CrdtState {
+
+    /// The state stored with the document, or the state of a document written
+    /// at `time` without it, by a device not merging the fields.
+    pub(crate) fn of(state: Option<&Document>, document: Option<&Document>, time: DateTime) -> Result<CrdtState> {
+        if let Some(state) = state {
+            return Ok(bson::from_document(state.clone())?);
+        }
+        let mut result = CrdtState::default();
+        result.record(document, time, "");
+        Ok(result)
+    }
+
+    pub(crate) fn to_document(&self) -> Result<Document> {
+        Ok(bson::to_document(self)?)
+    }
+
+    fn is_deleted(&self) -> bool {
+        match &self.deleted {
+            Some(deleted) => self.fields.values().all(|register| register.stamp < *deleted),
+            None => false,
+        }
+    }
+
+    /// Record the writes turning the document of the state into `document`,
+    /// made at `time` by `node`.
+    pub(crate) fn record(&mut self, document: Option<&Document>, time: DateTime, node: &str) {
+        // after the writes the state has seen, even if the clock of the device is behind
+        let latest = self.fields.values()
+            .map(|register| register.stamp.time)
+            .chain(self.deleted.iter().map(|deleted| deleted.time))
+            .max();
+        let stamp = Stamp {
+            time: latest.map_or(time.timestamp_millis(), |latest| time.timestamp_millis().max(latest + 1)),
+            node: node.to_string(),
+        };
+        let document = match document {
+            Some(document) => document,
+            None => {
+                self.deleted = Some(stamp);
+                return;
+            }
+        };
+        // every field of a document created again is written
+        let recreated = self.is_deleted();
+
+        for (key, value) in document {
+            let register = self.fields.get(key);
+            match value {
+                Bson::Array(values) => {
+                    let set = self.sets.entry(key.clone()).or_default();
+                    if recreated || !register.is_some_and(|register| register.array) {
+                        set.clear();
+                        self.fields.insert(key.clone(), Register {
+                            stamp: stamp.clone(),
+                            value: None,
+                            array: true,
+                        });
+                    }
+                    set.update(values, &stamp);
+                }
+                _ => {
+                    let changed = recreated || register.is_none_or(|register| {
+                        register.array || register.value.as_ref() != Some(value)
+                    });
+                    if changed {
+                        self.fields.insert(key.clone(), Register {
+                            stamp: stamp.clone(),
+                            value: Some(value.clone()),
+                            array: false,
+                        });
+                    }
+                }
+            }
+        }
+
+        let removed = self.fields.iter()
+            .filter(|(key, register)| (register.array || register.value.is_some()) && !document.contains_key(key.as_str()))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in removed {
+            self.fields.insert(key, Register {
+                stamp: stamp.clone(),
+                value: None,
+                array: false,
+            });
+        }
+    }
+
+    pub(crate) fn merge(&mut self, other: &CrdtState) {
+        for (key, register) in &other.fields {
+            match self.fields.get(key) {
+                Some(mine) if mine.stamp >= register.stamp => (),
+                _ => {
+                    self.fields.insert(key.clone(), register.clone());
+                }
+            }
+        }
+        for (key, set) in &other.sets {
+            self.sets.entry(key.clone()).or_default().merge(set);
+        }
+        if other.deleted > self.deleted {
+            self.deleted = other.deleted.clone();
+        }
+    }
+
+    /// The document of the state, `None` if it's deleted.
+    pub(crate) fn materialize(&self) -> Option<Document> {
+        if self.is_deleted() {
+            return None;
+        }
+        let mut document = Document::new();
+        for (key, register) in &self.fields {
+            if register.array {
+                let values = self.sets.get(key)
+                    .map(|set| set.live().map(|element| element.value.clone()).collect())
+                    .unwrap_or_default();
+                document.insert(key.clone(), Bson::Array(values));
+            } else if let Some(value) = &register.value {
+                document.insert(key.clone(), value.clone());
+            }
+        }
+        Some(document)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{doc, DateTime};
+    use super::CrdtState;
+
+    #[test]
+    fn test_crdt_merge() {
+        let base = doc! { "_id": 1, "title": "t", "tags": ["a", "b"] };
+        let mut state = CrdtState::default();
+        state.record(Some(&base), DateTime::from_millis(1), "base");
+
+        let mut left = state.clone();
+        left.record(Some(&doc! { "_id": 1, "title": "left", "tags": ["a", "b", "l"] }), DateTime::from_millis(3), "left");
+        let mut right = state.clone();
+        right.record(Some(&doc! { "_id": 1, "title": "right", "tags": ["b", "r"], "x": 1 }), DateTime::from_millis(2), "right");
+
+        let mut merged = left.clone();
+        merged.merge(&right);
+        let mut other = right.clone();
+        other.merge(&left);
+        assert_eq!(merged, other);
+        assert_eq!(merged.materialize().unwrap(), doc! {
+            "_id": 1,
+            "title": "left",
+            "tags": ["b", "r", "l"],
+            "x": 1,
+        });
+
+        // the document is deleted, then a field is written after the delete
+        let mut deleted = merged.clone();
+        deleted.record(None, DateTime::from_millis(4), "left");
+        assert!(deleted.materialize().is_none());
+        let mut edited = merged.clone();
+        edited.record(Some(&doc! { "_id": 1, "title": "again", "tags": ["b", "r", "l"], "x": 1 }), DateTime::from_millis(5), "right");
+        deleted.merge(&edited);
+        assert_eq!(deleted.materialize().unwrap().get_str("title").unwrap(), "again");
+    }
+
+}
@@ -0,0 +1,162 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use bson::{Bson, Document};
+
+/// An operation in progress, returned by [`crate::Database::current_ops`].
+#[derive(Debug, Clone)]
+pub struct CurrentOp {
+    pub id: u64,
+    /// The kind of the operation: `find`, `count`, `update`, `delete`,
+    /// `aggregate` or `transaction`.
+    pub op: &'static str,
+    pub collection: Option<String>,
+    pub filter: Option<Document>,
+    /// The `$comment` of the filter or the comment given to the operation,
+    /// to attribute it to a feature of the application.
+    pub comment: Option<Bson>,
+    /// How long the operation has been running when it was listed.
+    pub running_for: Duration,
+    killed: Arc<AtomicBool>,
+}
+
+impl CurrentOp {
+
+    /// Ask the operation to stop.
+    ///
+    /// A killed query fails with [`crate::Error::OperationKilled`] the next time it reads
+    /// a document. A killed transaction fails every following read, write and commit,
+    /// the owner of the transaction has to roll it back to release its locks.
+    pub fn kill(&self) {
+        self.killed.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::SeqCst)
+    }
+
+}
+
+struct OpEntry {
+    op: &'static str,
+    collection: Option<String>,
+    filter: Option<Document>,
+    comment: Option<Bson>,
+    started_at: Instant,
+    killed: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct OperationRegistryInner {
+    next_id: u64,
+    ops: BTreeMap<u64, OpEntry>,
+}
+
+/// The registry of the operations in progress.
+#[derive(Clone, Default)]
+pub(crate) struct OperationRegistry {
+    inner: Arc<Mutex<OperationRegistryInner>>,
+}
+
+impl OperationRegistry {
+
+    pub(crate) fn new() -> OperationRegistry {
+        OperationRegistry::default()
+    }
+
+    /// Register an operation, it's removed from the registry when the guard is dropped.
+    pub(crate) fn register(
+        &self,
+        op: &'static str,
+        collection: Option<&str>,
+        filter: Option<&Document>,
+        comment: Option<&Bson>,
+        killed: Arc<AtomicBool>,
+    ) -> OpGuard {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.ops.insert(id, OpEntry {
+            op,
+            collection: collection.map(str::to_string),
+            filter: filter.cloned(),
+            comment: comment.cloned(),
+            started_at: Instant::now(),
+            killed: killed.clone(),
+        });
+        OpGuard {
+            registry: Arc::downgrade(&self.inner),
+            id,
+            killed,
+        }
+    }
+
+    pub(crate) fn list(&self) -> Vec<CurrentOp> {
+        let inner = self.inner.lock().unwrap();
+        inner.ops
+            .iter()
+            .map(|(id, entry)| CurrentOp {
+                id: *id,
+                op: entry.op,
+                collection: entry.collection.clone(),
+                filter: entry.filter.clone(),
+                comment: entry.comment.clone(),
+                running_for: entry.started_at.elapsed(),
+                killed: entry.killed.clone(),
+            })
+            .collect()
+    }
+
+    pub(crate) fn kill(&self, id: u64) -> bool {
+        let inner = self.inner.lock().unwrap();
+        match inner.ops.get(&id) {
+            Some(entry) => {
+                entry.killed.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+}
+
+pub(crate) struct OpGuard {
+    registry: Weak<Mutex<OperationRegistryInner>>,
+    id: u64,
+    killed: Arc<AtomicBool>,
+}
+
+impl OpGuard {
+
+    #[inline]
+    pub(crate) fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::Relaxed)
+    }
+
+}
+
+impl Drop for OpGuard {
+    fn drop(&mut self) {
+        if let Some(registry) = self.registry.upgrade() {
+            if let Ok(mut inner) = registry.lock() {
+                inner.ops.remove(&self.id);
+            }
+        }
+    }
+}
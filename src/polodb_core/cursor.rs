@@ -13,7 +13,6 @@
 // limitations under the License.
 
 use std::cmp::Ordering;
-use std::sync::Arc;
 use bson::Bson;
 use crate::db::RocksDBIterator;
 use crate::Result;
@@ -25,7 +24,9 @@ use crate::index::make_index_key_with_query_key;
 pub(crate) struct Cursor {
     pub(crate)  prefix_bytes: Vec<u8>,
     kv_cursor:    RocksDBIterator,
-    current_key:  Option<Arc<[u8]>>,
+    current_key:  Option<Vec<u8>>,
+    /// Move from the last key of the prefix to the first one.
+    backward:     bool,
 }
 
 impl Cursor {
@@ -42,17 +43,46 @@ impl Cursor {
             prefix_bytes,
             kv_cursor,
             current_key: None,
+            backward: false,
         }
     }
 
+    #[inline]
+    pub fn set_backward(&mut self, backward: bool) {
+        self.backward = backward;
+    }
+
     #[inline]
     pub fn copy_data(&self) -> Result<Vec<u8>> {
         self.kv_cursor.copy_data()
     }
 
+    /// Copy the current value into `buf`, reusing its allocation.
+    #[inline]
+    pub fn copy_data_into(&self, buf: &mut Vec<u8>) -> Result<()> {
+        self.kv_cursor.copy_data_into(buf)
+    }
+
+    /// Copy the key at the iterator, reusing the buffer of the previous key.
+    fn read_current_key(&mut self) -> Result<()> {
+        let mut key = self.current_key.take().unwrap_or_default();
+        self.kv_cursor.copy_key_into(&mut key)?;
+        self.current_key = Some(key);
+        Ok(())
+    }
+
     pub fn update_current(&mut self, txn: &TransactionInner, value: &[u8]) -> Result<bool> {
         if let Some(key) = &self.current_key {
-            txn.rocksdb_txn.set(key.as_ref(), value)?;
+            txn.put(key.as_ref(), value)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Update the current value by a delta instead of rewriting it.
+    pub fn merge_current(&mut self, txn: &TransactionInner, delta: &[u8]) -> Result<bool> {
+        if let Some(key) = &self.current_key {
+            txn.merge(key.as_ref(), delta)?;
             return Ok(true);
         }
         Ok(false)
@@ -60,10 +90,33 @@ impl Cursor {
 
 
     pub fn reset(&mut self) -> Result<()> {
+        if self.backward {
+            return self.reset_to_last();
+        }
         self.kv_cursor.seek(self.prefix_bytes.as_slice());
 
         if self.kv_cursor.valid() {
-            self.current_key = Some(self.kv_cursor.copy_key_arc()?);
+            self.read_current_key()?;
+        }
+
+        Ok(())
+    }
+
+    /// Move to the last key starting with the prefix.
+    fn reset_to_last(&mut self) -> Result<()> {
+        let upper_bound = crate::utils::bson::prefix_upper_bound(&self.prefix_bytes);
+        if upper_bound.is_empty() {
+            self.kv_cursor.seek_to_last();
+        } else {
+            self.kv_cursor.seek_for_prev(upper_bound.as_slice());
+        }
+
+        self.current_key = None;
+        if self.kv_cursor.valid() {
+            self.read_current_key()?;
+            if self.current_key.as_deref() == Some(upper_bound.as_slice()) {
+                return self.next();
+            }
         }
 
         Ok(())
@@ -80,25 +133,25 @@ impl Cursor {
             key_buffer.extend_from_slice(&primary_key_buffer);
         }
 
-        self.reset_by_custom_key(key_buffer.as_slice())
+        self.reset_by_key(key_buffer.as_slice())
     }
 
-    #[allow(dead_code)]
     pub fn reset_by_pkey_buf(&mut self, pkey_buffer: &[u8]) -> Result<bool> {
         let mut key_buffer = self.prefix_bytes.clone();
 
         key_buffer.extend_from_slice(pkey_buffer);
 
-        self.reset_by_custom_key(key_buffer.as_slice())
+        self.reset_by_key(key_buffer.as_slice())
     }
 
-    fn reset_by_custom_key(&mut self, key_buffer: &[u8]) -> Result<bool> {
+    /// Move to the value at `key_buffer`, return false if there is none.
+    pub fn reset_by_key(&mut self, key_buffer: &[u8]) -> Result<bool> {
         self.kv_cursor.seek(key_buffer);
 
         if self.kv_cursor.valid() {
-            self.current_key = Some(self.kv_cursor.copy_key_arc()?);
+            self.read_current_key()?;
             if let Some(found) = &self.current_key {
-                return Ok(found.as_ref().cmp(key_buffer) == Ordering::Equal);
+                return Ok(found.as_slice().cmp(key_buffer) == Ordering::Equal);
             }
         }
 
@@ -114,9 +167,9 @@ impl Cursor {
         self.kv_cursor.seek(key_buffer.as_slice());
 
         if self.kv_cursor.valid() {
-            self.current_key = Some(self.kv_cursor.copy_key_arc()?);
+            self.read_current_key()?;
             if let Some(found) = &self.current_key {
-                let starts_with = found.as_ref().starts_with(key_buffer.as_slice());
+                let starts_with = found.starts_with(key_buffer.as_slice());
                 return Ok(starts_with);
             }
         }
@@ -124,8 +177,8 @@ impl Cursor {
         Ok(false)
     }
 
-    pub fn peek_key(&self) -> Option<Arc<[u8]>> {
-        self.current_key.clone()
+    pub fn peek_key(&self) -> Option<&[u8]> {
+        self.current_key.as_deref()
     }
 
     pub fn has_next(&self) -> bool {
@@ -144,12 +197,16 @@ impl Cursor {
     }
 
     pub fn next(&mut self) -> Result<()> {
-        self.kv_cursor.next();
+        if self.backward {
+            self.kv_cursor.prev();
+        } else {
+            self.kv_cursor.next();
+        }
         if !self.kv_cursor.valid() {
             self.current_key = None;
             return Ok(());
         }
-        self.current_key = Some(self.kv_cursor.copy_key_arc()?);
+        self.read_current_key()?;
         Ok(())
     }
 
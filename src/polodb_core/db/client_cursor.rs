@@ -12,12 +12,96 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::VecDeque;
 use std::fmt;
 use std::marker::PhantomData;
-use bson::Bson;
+use std::sync::{Arc, Weak};
+use std::time::Instant;
+use bson::{doc, Binary, Bson, DateTime, Document, RawDocumentBuf};
+use bson::spec::BinarySubtype;
 use serde::de::DeserializeOwned;
-use crate::{Result};
-use crate::vm::{VM, VmState};
+use crate::{DecodeError, Error, Result};
+use crate::db::db_inner::DatabaseInner;
+use crate::options::DeleteOptions;
+use crate::query_cache::ResultRecorder;
+use crate::vm::{AccessPath, VM, VmState};
+
+enum Rows {
+    Vm(Box<VM>),
+    /// The documents of a result of the query cache.
+    Cached {
+        docs: Arc<Vec<Bson>>,
+        next: usize,
+        current: Bson,
+    },
+}
+
+/// The collection the documents failing to decode are copied to,
+/// see [`crate::options::DecodeMode::Quarantine`].
+pub(crate) struct Quarantine {
+    db: Weak<DatabaseInner>,
+    name: String,
+}
+
+impl Quarantine {
+
+    pub(crate) fn new(db: Weak<DatabaseInner>, name: String) -> Quarantine {
+        Quarantine {
+            db,
+            name,
+        }
+    }
+
+    /// Copy the documents in their own transaction, the cursor may
+    /// read in a transaction which is never committed.
+    fn keep(&self, failures: Vec<DecodeError>) -> Result<()> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let txn = db.start_transaction()?;
+        let result = failures.into_iter().try_for_each(|failure| {
+            let id = match &failure.id {
+                // the copy of the same document has the same key
+                Some(id) => Bson::Binary(Binary {
+                    subtype: BinarySubtype::Generic,
+                    bytes: crate::utils::bson::stacked_key([&Bson::String(failure.ns.clone()), id])?,
+                }),
+                None => Bson::ObjectId(db.new_object_id()),
+            };
+            db.delete(&self.name, doc! { "_id": id.clone() }, false, &DeleteOptions::default(), &txn)?;
+            db.insert_one(&self.name, doc! {
+                "_id": id,
+                "ns": failure.ns,
+                "id": failure.id.unwrap_or(Bson::Null),
+                "data": Binary {
+                    subtype: BinarySubtype::Generic,
+                    bytes: failure.data,
+                },
+                "reason": failure.reason,
+                "quarantinedAt": DateTime::now(),
+            }, &txn)?;
+            Ok(())
+        });
+        match result {
+            Ok(()) => txn.commit(),
+            Err(err) => {
+                let _ = txn.rollback();
+                Err(err)
+            }
+        }
+    }
+
+}
+
+/// The state of a cursor going on with the next documents when
+/// it fails to decode one, see [`crate::options::DecodeMode`].
+struct Lenient {
+    /// The collection of the documents.
+    ns: String,
+    quarantine: Option<Quarantine>,
+    /// The documents set aside by the VM, given before its row.
+    failures: VecDeque<DecodeError>,
+    /// Whether the VM has a row, once the failures are given.
+    has_row: Option<bool>,
+}
 
 /// A `ClientCursor` is used get the result of a query.
 /// You can move the cursor forward using the `advance()`.
@@ -25,7 +109,10 @@ use crate::vm::{VM, VmState};
 /// Additionally, you can use deserialize_current() method to
 /// deserialize the documents returned by advance()
 pub struct ClientCursor<T: DeserializeOwned + Send + Sync> {
-    vm: VM,
+    rows: Rows,
+    /// Keeps the rows given so far, to cache the result once they are all given.
+    recorder: Option<ResultRecorder>,
+    lenient: Option<Box<Lenient>>,
     _phantom: PhantomData<T>,
 }
 
@@ -33,27 +120,198 @@ impl<T: DeserializeOwned + Send + Sync> ClientCursor<T> {
 
     pub(crate) fn new(vm: VM) -> ClientCursor<T> {
         ClientCursor{
-            vm,
+            rows: Rows::Vm(Box::new(vm)),
+            recorder: None,
+            lenient: None,
             _phantom: Default::default(),
         }
     }
 
-    #[inline]
-    fn has_row(&self) -> bool {
-        self.vm.state == VmState::HasRow
+    /// A cursor giving the documents of a cached result.
+    pub(crate) fn cached(docs: Arc<Vec<Bson>>) -> ClientCursor<T> {
+        ClientCursor {
+            rows: Rows::Cached {
+                docs,
+                next: 0,
+                current: Bson::Null,
+            },
+            recorder: None,
+            lenient: None,
+            _phantom: Default::default(),
+        }
+    }
+
+    /// Go on with the next documents when a document of `ns` fails to decode,
+    /// giving an error for it or copying it to the quarantine collection.
+    /// The VM sets aside the documents failing to decode.
+    pub(crate) fn set_lenient(&mut self, ns: String, quarantine: Option<Quarantine>) {
+        // the result misses the documents failing to decode
+        self.recorder = None;
+        self.lenient = Some(Box::new(Lenient {
+            ns,
+            quarantine,
+            failures: VecDeque::new(),
+            has_row: None,
+        }));
+    }
+
+    /// Cache the result once all its rows are given.
+    pub(crate) fn set_recorder(&mut self, recorder: ResultRecorder) {
+        self.recorder = Some(recorder);
     }
 
     #[inline]
     pub(crate) fn get(&self) -> &Bson {
-        self.vm.stack_top()
+        match &self.rows {
+            Rows::Vm(vm) => vm.stack_top(),
+            Rows::Cached { current, .. } => current,
+        }
+    }
+
+    fn take_current(&mut self) -> Bson {
+        match &mut self.rows {
+            Rows::Vm(vm) => vm.take_stack_top(),
+            Rows::Cached { current, .. } => std::mem::replace(current, Bson::Null),
+        }
     }
 
     pub fn advance(&mut self) -> Result<bool> {
-        if self.vm.state == VmState::Halt {
-            return Ok(false);
+        if let Some(lenient) = &mut self.lenient {
+            if let Some(failure) = lenient.failures.pop_front() {
+                return Err(Error::DecodeFailed(Box::new(failure)));
+            }
+            if let Some(has_row) = lenient.has_row.take() {
+                return Ok(has_row);
+            }
+        }
+        let has_row = match &mut self.rows {
+            Rows::Vm(vm) => {
+                if vm.state == VmState::Halt {
+                    return Ok(false);
+                }
+                if let Err(err) = vm.execute() {
+                    self.recorder = None;
+                    return Err(err);
+                }
+                let has_row = vm.state == VmState::HasRow;
+                if let Some(lenient) = &mut self.lenient {
+                    let failures = vm.take_decode_failures();
+                    if !failures.is_empty() {
+                        match &lenient.quarantine {
+                            Some(quarantine) => quarantine.keep(failures)?,
+                            None => {
+                                lenient.failures.extend(failures);
+                                lenient.has_row = Some(has_row);
+                                let failure = lenient.failures.pop_front().unwrap();
+                                return Err(Error::DecodeFailed(Box::new(failure)));
+                            }
+                        }
+                    }
+                }
+                has_row
+            }
+            Rows::Cached { docs, next, current } => match docs.get(*next) {
+                Some(doc) => {
+                    *current = doc.clone();
+                    *next += 1;
+                    true
+                }
+                None => false,
+            },
+        };
+        if self.recorder.is_some() {
+            self.record(has_row);
         }
-        self.vm.execute()?;
-        Ok(self.has_row())
+        Ok(has_row)
+    }
+
+    fn record(&mut self, has_row: bool) {
+        if !has_row {
+            if let Some(recorder) = self.recorder.take() {
+                recorder.finish();
+            }
+            return;
+        }
+        let row = self.get().clone();
+        let recorded = self.recorder.as_mut().is_some_and(|recorder| recorder.record(row));
+        if !recorded {
+            self.recorder = None;
+        }
+    }
+
+    pub(crate) fn set_raw_rows(&mut self, lazy: bool) {
+        // the rows may not be decoded
+        self.recorder = None;
+        if let Rows::Vm(vm) = &mut self.rows {
+            vm.set_raw_rows(lazy);
+        }
+    }
+
+    /// How the VM finds the documents, `None` for a cached result.
+    pub(crate) fn access_path(&self) -> Option<&AccessPath> {
+        match &self.rows {
+            Rows::Vm(vm) => Some(&vm.program.access),
+            Rows::Cached { .. } => None,
+        }
+    }
+
+    /// Read all the rows, then describe how they were found: the plan,
+    /// the statistics of the execution and the program of the VM.
+    pub(crate) fn explain(mut self) -> Result<Document> {
+        let plan = match &self.rows {
+            Rows::Vm(vm) => vm.explain_plan(),
+            Rows::Cached { .. } => doc! { "stage": "CACHED" },
+        };
+        let start = Instant::now();
+        let mut returned: u64 = 0;
+        loop {
+            match self.advance() {
+                Ok(true) => returned += 1,
+                Ok(false) => break,
+                // the documents failing to decode are examined, not returned
+                Err(Error::DecodeFailed(_)) if self.lenient.is_some() => (),
+                Err(err) => return Err(err),
+            }
+        }
+        let elapsed = start.elapsed();
+        let (examined, program) = match &self.rows {
+            Rows::Vm(vm) => (vm.docs_examined(), vm.program.to_string()),
+            Rows::Cached { docs, .. } => (docs.len() as u64, String::new()),
+        };
+        Ok(doc! {
+            "winningPlan": plan,
+            "executionStats": {
+                "nReturned": returned as i64,
+                "docsExamined": examined as i64,
+                "executionTimeMicros": elapsed.as_micros() as i64,
+            },
+            "program": program,
+        })
+    }
+
+    /// Whether the documents are read in the order of an index instead of being sorted.
+    pub(crate) fn reads_index_order(&self) -> bool {
+        match &self.rows {
+            Rows::Vm(vm) => vm.program.index_order.is_some(),
+            Rows::Cached { .. } => false,
+        }
+    }
+
+    /// The current row in its encoding, without decoding it if the row
+    /// is a document as it is stored.
+    pub(crate) fn take_raw(&mut self) -> Result<RawDocumentBuf> {
+        let data = match &mut self.rows {
+            Rows::Vm(vm) => vm.take_raw_document(),
+            Rows::Cached { .. } => None,
+        };
+        let raw = match data {
+            Some(data) => RawDocumentBuf::from_bytes(data),
+            None => {
+                let doc = self.get().as_document().expect("internal: the row must be a document");
+                RawDocumentBuf::from_document(doc)
+            }
+        };
+        Ok(raw.map_err(bson::de::Error::from)?)
     }
 
     pub fn deserialize_current(&self) -> Result<T> {
@@ -66,7 +324,10 @@ impl<T: DeserializeOwned + Send + Sync> ClientCursor<T> {
 impl<T: DeserializeOwned + Send + Sync> fmt::Display for ClientCursor<T> {
 
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Program: \n\n{}", self.vm.program)
+        match &self.rows {
+            Rows::Vm(vm) => write!(f, "Program: \n\n{}", vm.program),
+            Rows::Cached { docs, .. } => write!(f, "Cached result: {} documents", docs.len()),
+        }
     }
 
 }
@@ -78,15 +339,67 @@ impl<T> Iterator for ClientCursor<T>
     type Item = Result<T>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let test = self.advance();
-        match test {
-            Ok(false) => None,
-            Ok(true) => {
-                Some(Ok(bson::from_bson(self.get().clone()).unwrap()))
+        loop {
+            match self.advance() {
+                Ok(false) => return None,
+                Ok(true) => (),
+                Err(err) => return Some(Err(err)),
             }
-            Err(err) =>{
-                Some(Err(err))
+            let row = self.take_current();
+            let lenient = match &self.lenient {
+                Some(lenient) => lenient,
+                None => return Some(bson::from_bson(row).map_err(Error::from)),
+            };
+            let err = match bson::from_bson(row.clone()) {
+                Ok(value) => return Some(Ok(value)),
+                Err(err) => err,
+            };
+            // the document is stored, but it doesn't have the fields of `T`
+            let data = match row.as_document().map(bson::to_vec) {
+                Some(Ok(data)) => data,
+                _ => return Some(Err(err.into())),
+            };
+            let failure = DecodeError {
+                ns: lenient.ns.clone(),
+                id: row.as_document().and_then(|doc| doc.get("_id")).cloned(),
+                data,
+                reason: err.to_string(),
+            };
+            match &lenient.quarantine {
+                Some(quarantine) => {
+                    if let Err(err) = quarantine.keep(vec![failure]) {
+                        return Some(Err(err));
+                    }
+                }
+                None => return Some(Err(Error::DecodeFailed(Box::new(failure)))),
             }
         }
     }
 }
+
+/// A cursor giving the documents in their encoding, see [`crate::action::FindRaw`].
+pub struct RawCursor {
+    inner: ClientCursor<Document>,
+}
+
+impl RawCursor {
+
+    pub(crate) fn new(inner: ClientCursor<Document>) -> RawCursor {
+        RawCursor {
+            inner,
+        }
+    }
+
+}
+
+impl Iterator for RawCursor {
+    type Item = Result<RawDocumentBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inner.advance() {
+            Ok(false) => None,
+            Ok(true) => Some(self.inner.take_raw()),
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
@@ -12,15 +12,36 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::net::{TcpListener, ToSocketAddrs};
 use std::path::Path;
 use serde::Serialize;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use crate::errors::Error;
-use crate::{Config, Transaction};
+use crate::{ChangeBatch, Config, Transaction};
 use super::db_inner::DatabaseInner;
-use crate::coll::Collection;
+use crate::coll::{Collection, CollectionT, Model};
 use crate::metrics::Metrics;
+use crate::profiler::Profiler;
+use crate::audit::AuditLog;
+use crate::options::{CreateCollectionOptions, ModifyCollectionOptions};
+use crate::current_op::CurrentOp;
+use crate::verify::VerifyReport;
+use crate::replication::{Replica, ReplicationServer, ReplicationTransport};
+use crate::sync::SyncEngine;
+use crate::temp::TempCollection;
+use crate::namespace::LogicalDatabase;
+use crate::lock::AdvisoryLock;
+use crate::scheduler::Scheduler;
+use crate::version_info::VersionInfo;
+use crate::hooks::HookEvent;
+use crate::action::Watch;
+use bson::Document;
+use bson::oid::ObjectId;
+#[cfg(feature = "gridfs")]
+use crate::gridfs::GridFsBucket;
+#[cfg(feature = "gridfs")]
+use crate::options::GridFsBucketOptions;
 
 pub(crate) static SHOULD_LOG: AtomicBool = AtomicBool::new(false);
 
@@ -73,24 +94,187 @@ impl Database {
     }
 
     pub fn open_path_with_config<P: AsRef<Path>>(path: P, config: Config) -> Result<Database>  {
-        let inner = DatabaseInner::open_file(path.as_ref(), config)?;
+        let inner = Arc::new(DatabaseInner::open_file(path.as_ref(), config)?);
+        inner.profiler().attach(Arc::downgrade(&inner));
+        inner.audit().attach(Arc::downgrade(&inner));
 
         Ok(Database {
-            inner: Arc::new(inner),
+            inner,
         })
     }
 
+    /// Open the snapshot file written by [`Database::export_snapshot`]. The database
+    /// is read-only, the writes fail with [`Error::ReadOnlySnapshot`].
+    ///
+    /// The checksums and the length of the snapshot are verified as it's opened, a damaged
+    /// file fails with [`Error::InvalidSnapshot`]. The content of the snapshot is loaded into
+    /// the temporary directory, not rebuilt: the indexes are read as they were exported.
+    /// The loaded copy is removed once the database is closed, the file is left untouched.
+    pub fn open_snapshot<P: AsRef<Path>>(path: P) -> Result<Database> {
+        let inner = Arc::new(DatabaseInner::open_snapshot(path.as_ref())?);
+        inner.profiler().attach(Arc::downgrade(&inner));
+        inner.audit().attach(Arc::downgrade(&inner));
+
+        Ok(Database {
+            inner,
+        })
+    }
+
+    #[inline]
+    pub(crate) fn inner(&self) -> &DatabaseInner {
+        &self.inner
+    }
+
     /// Return the metrics object of the database
     pub fn metrics(&self) -> Metrics {
         self.inner.metrics()
     }
 
+    /// Return the profiler of the database, it records the slow operations
+    /// into a collection or a callback.
+    pub fn profiler(&self) -> Profiler {
+        self.inner.profiler()
+    }
+
+    /// Return the audit log of the database, it records the writes
+    /// into an append-only collection or a callback.
+    pub fn audit(&self) -> AuditLog {
+        self.inner.audit()
+    }
+
+    /// Generate an ObjectId as the ones given to the inserted documents without `_id`,
+    /// following the ObjectId settings of the [`Config`].
+    pub fn new_object_id(&self) -> ObjectId {
+        self.inner.new_object_id()
+    }
+
     /// Creates a new collection in the database with the given `name`.
     pub fn create_collection(&self, name: &str) -> Result<()> {
         let _ = self.inner.create_collection(name)?;
         Ok(())
     }
 
+    /// Renames the collection `old_name` to `new_name` in one transaction, keeping its documents,
+    /// its indexes, its options and the validators and hooks registered on it.
+    /// Fails with [`Error::CollectionAlreadyExits`] if `new_name` is taken.
+    pub fn rename_collection(&self, old_name: &str, new_name: &str) -> Result<()> {
+        let txn = self.inner.start_transaction()?;
+        self.inner.rename_collection(old_name, new_name, &txn)?;
+        txn.commit()
+    }
+
+    /// Creates a new collection with options, such as a `$jsonSchema` validator
+    /// enforced on every insert and update.
+    ///
+    /// ```rust
+    /// use polodb_core::{Database, CollectionT, Error};
+    /// use polodb_core::bson::doc;
+    /// use polodb_core::options::CreateCollectionOptions;
+    /// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-validator");
+    /// let db = Database::open_path(db_path).unwrap();
+    /// db.create_collection_with_options("patients", CreateCollectionOptions::builder()
+    ///     .validator(doc! {
+    ///         "$jsonSchema": {
+    ///             "bsonType": "object",
+    ///             "required": ["name"],
+    ///             "properties": { "name": { "bsonType": "string" } },
+    ///         },
+    ///     })
+    ///     .build()
+    /// ).unwrap();
+    /// let patients = db.collection("patients");
+    /// assert!(patients.insert_one(doc! { "name": "Ann" }).is_ok());
+    /// assert!(matches!(patients.insert_one(doc! { "name": 1 }), Err(Error::DocumentValidationFailed(_))));
+    /// ```
+    pub fn create_collection_with_options(&self, name: &str, options: CreateCollectionOptions) -> Result<()> {
+        let _ = self.inner.create_collection_with_options(name, options)?;
+        Ok(())
+    }
+
+    /// Changes the options of an existing collection, the existing documents
+    /// are not validated against a new validator.
+    pub fn modify_collection(&self, name: &str, options: ModifyCollectionOptions) -> Result<()> {
+        let txn = self.inner.start_transaction()?;
+        self.inner.modify_collection(name, options, &txn)?;
+        txn.commit()
+    }
+
+    /// Creates the collection `name` holding the result of the aggregation `pipeline` run
+    /// on the documents of `source`, kept up to date as the documents of `source` change.
+    ///
+    /// A pipeline made of an optional `$match` followed by `$addFields` and `$unset` stages
+    /// is maintained document by document: a write to a document of `source` only computes
+    /// the document of the view with the same `_id`. The other pipelines, such as the ones
+    /// counting or grouping the documents, are run again on the whole `source` by each write to it.
+    /// The documents written directly to the view are replaced by the next refresh.
+    ///
+    /// ```rust
+    /// use polodb_core::{Database, CollectionT};
+    /// use polodb_core::bson::{doc, Document};
+    /// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-materialized-view");
+    /// let db = Database::open_path(db_path).unwrap();
+    /// let orders = db.collection::<Document>("orders");
+    /// orders.insert_one(doc! { "_id": 1, "status": "paid", "discount": -2 }).unwrap();
+    ///
+    /// db.create_materialized_view("paid_orders", "orders", vec![
+    ///     doc! { "$match": { "status": "paid" } },
+    ///     doc! { "$addFields": { "rebate": { "$abs": "$discount" } } },
+    /// ]).unwrap();
+    /// orders.insert_one(doc! { "_id": 2, "status": "paid", "discount": -5 }).unwrap();
+    ///
+    /// let paid = db.collection::<Document>("paid_orders");
+    /// assert_eq!(paid.find_one(doc! { "_id": 2 }).unwrap().unwrap().get_i32("rebate").unwrap(), 5);
+    /// ```
+    pub fn create_materialized_view(
+        &self,
+        name: &str,
+        source: &str,
+        pipeline: impl IntoIterator<Item = Document>,
+    ) -> Result<()> {
+        let txn = self.inner.start_transaction()?;
+        self.inner.create_materialized_view(name, source, pipeline.into_iter().collect(), &txn)?;
+        txn.commit()
+    }
+
+    /// Creates the read-only view `name` of the documents of `source`, a collection or another
+    /// view, transformed by the aggregation `pipeline`. Nothing is stored: the filter, the sort
+    /// and the pipeline of a query on the view are run after `pipeline` on the current documents
+    /// of `source`, so a view can hide the fields the callers must not see.
+    /// Writing to the view fails with [`Error::ReadOnlyView`].
+    ///
+    /// ```rust
+    /// use polodb_core::{Database, CollectionT};
+    /// use polodb_core::bson::{doc, Document};
+    /// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-view");
+    /// let db = Database::open_path(db_path).unwrap();
+    /// let users = db.collection::<Document>("users");
+    /// users.insert_one(doc! { "_id": 1, "name": "Ada", "password": "secret" }).unwrap();
+    ///
+    /// db.create_view("public_users", "users", vec![doc! { "$unset": "password" }]).unwrap();
+    ///
+    /// let public_users = db.collection::<Document>("public_users");
+    /// let ada = public_users.find_one(doc! { "name": "Ada" }).unwrap().unwrap();
+    /// assert_eq!(ada, doc! { "_id": 1, "name": "Ada" });
+    /// ```
+    pub fn create_view(
+        &self,
+        name: &str,
+        source: &str,
+        pipeline: impl IntoIterator<Item = Document>,
+    ) -> Result<()> {
+        let txn = self.inner.start_transaction()?;
+        self.inner.create_view(name, source, pipeline.into_iter().collect(), &txn)?;
+        txn.commit()
+    }
+
+    /// Computes the documents of the materialized view `name` again from all the documents
+    /// of its source, replacing the documents written directly to the view.
+    pub fn refresh_materialized_view(&self, name: &str) -> Result<()> {
+        let txn = self.inner.start_transaction()?;
+        self.inner.refresh_materialized_view(name, &txn)?;
+        txn.commit()
+    }
+
     ///
     /// [error]: ../enum.DbErr.html
     ///
@@ -101,16 +285,442 @@ impl Database {
         Collection::new(Arc::downgrade(&self.inner), col_name)
     }
 
+    /// Creates a temporary collection living as long as the returned handle,
+    /// as scratch space for the steps of a data processing job, see [`TempCollection`].
+    /// The name is only seen by the handle, it doesn't clash with the collections of the database.
+    pub fn create_temp_collection<T: Serialize>(&self, name: &str) -> Result<TempCollection<T>> {
+        TempCollection::create(&self.inner, name)
+    }
+
+    /// Return the collection of a [`Model`], the indexes defined
+    /// by the model are created if they don't exist.
+    pub fn collection_for<T: Model>(&self) -> Result<Collection<T>> {
+        let collection = self.collection::<T>(T::COLLECTION_NAME);
+        for index in T::indexes() {
+            collection.create_index(index)?;
+        }
+        Ok(collection)
+    }
+
+    /// Return the default bucket of files, `fs`, to store files larger
+    /// than a document.
+    #[cfg(feature = "gridfs")]
+    pub fn gridfs_bucket(&self) -> GridFsBucket {
+        self.gridfs_bucket_with_options(GridFsBucketOptions::default())
+    }
+
+    /// Return a bucket of files with a custom name or chunk size.
+    #[cfg(feature = "gridfs")]
+    pub fn gridfs_bucket_with_options(&self, options: GridFsBucketOptions) -> GridFsBucket {
+        GridFsBucket::new(self.clone(), options)
+    }
+
     pub fn start_transaction(&self) -> Result<Transaction> {
         let mut inner = self.inner.start_transaction()?;
         inner.set_auto_commit(false);
-        Ok(Transaction::new(Arc::downgrade(&self.inner), inner))
+        let guard = self.inner.operations().register("transaction", None, None, None, inner.kill_flag());
+        Ok(Transaction::new(Arc::downgrade(&self.inner), inner, guard))
+    }
+
+    /// Register a hook running before a document of the collection is written.
+    ///
+    /// The hook receives the document about to be inserted, the document after
+    /// the update is applied, or the document about to be deleted. The changes
+    /// made by the hook to an inserted or updated document are written, returning
+    /// an error, such as [`Error::HookRejected`], vetoes the write and fails the operation.
+    ///
+    /// ```rust
+    /// use polodb_core::{Database, HookEvent};
+    /// use polodb_core::bson::DateTime;
+    /// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-pre-hook");
+    /// let db = Database::open_path(db_path).unwrap();
+    /// db.add_pre_hook("posts", HookEvent::Update, |doc| {
+    ///     doc.insert("updated_at", DateTime::now());
+    ///     Ok(())
+    /// });
+    /// ```
+    pub fn add_pre_hook<F>(&self, col_name: &str, event: HookEvent, hook: F)
+    where
+        F: Fn(&mut Document) -> Result<()> + Send + Sync + 'static,
+    {
+        self.inner.hooks().add_pre_hook(col_name, event, Arc::new(hook));
+    }
+
+    /// Register a hook observing the documents of the collection once the write
+    /// is committed, it receives the inserted document, the updated document or
+    /// the deleted document.
+    pub fn add_post_hook<F>(&self, col_name: &str, event: HookEvent, hook: F)
+    where
+        F: Fn(&Document) + Send + Sync + 'static,
+    {
+        self.inner.hooks().add_post_hook(col_name, event, Arc::new(hook));
     }
 
-    /// Gets the names of the collections in the database.
+    /// Remove all the hooks of the collection.
+    pub fn clear_hooks(&self, col_name: &str) {
+        self.inner.hooks().clear(col_name);
+    }
+
+    /// Register a validator run on every document inserted into or updated in the collection,
+    /// after the pre hooks and the `$jsonSchema` validator. Returning an error rejects the
+    /// write with [`Error::DocumentValidationFailed`].
+    ///
+    /// Unlike the `$jsonSchema` validator, the validators are not persisted and must be
+    /// registered each time the database is opened.
+    ///
+    /// ```rust
+    /// use polodb_core::Database;
+    /// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-validator");
+    /// let db = Database::open_path(db_path).unwrap();
+    /// db.add_validator("bookings", |doc| {
+    ///     match (doc.get_datetime("start"), doc.get_datetime("end")) {
+    ///         (Ok(start), Ok(end)) if start > end => Err("start is after end".to_string()),
+    ///         _ => Ok(()),
+    ///     }
+    /// });
+    /// ```
+    pub fn add_validator<F>(&self, col_name: &str, validator: F)
+    where
+        F: Fn(&Document) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        self.inner.hooks().add_validator(col_name, Arc::new(validator));
+    }
+
+    /// Remove all the validators of the collection.
+    pub fn clear_validators(&self, col_name: &str) {
+        self.inner.hooks().clear_validators(col_name);
+    }
+
+    /// Open a change stream delivering the committed writes of all the collections.
+    ///
+    /// ```rust
+    /// use polodb_core::{Database, CollectionT, OperationType};
+    /// use polodb_core::bson::doc;
+    /// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-watch");
+    /// let db = Database::open_path(db_path).unwrap();
+    /// let mut stream = db.watch().run().unwrap();
+    /// db.collection("books").insert_one(doc! { "title": "Dune" }).unwrap();
+    /// let event = stream.try_next().unwrap();
+    /// let event = event.unwrap();
+    /// assert_eq!(event.operation_type, OperationType::Insert);
+    /// assert_eq!(event.collection, "books");
+    /// ```
+    pub fn watch(&self) -> Watch<'_> {
+        Watch::new(Arc::downgrade(&self.inner), None)
+    }
+
+    /// Read the changes committed after the change with the resume `token`, all the changes
+    /// of the oplog if it's 0, so a pipeline can mirror the data elsewhere incrementally.
+    /// Fails with [`Error::ChangeStreamHistoryLost`] if the oplog no longer has the changes
+    /// after `token`, or if it's disabled, see [`crate::ConfigBuilder::set_oplog_size`].
+    pub fn changes_since(&self, token: u64) -> Result<ChangeBatch> {
+        self.inner.changes_since(token)
+    }
+
+    /// Stream the changes of this database to the replicas connecting to `listener`,
+    /// see [`Database::replicate_from`]. The database needs the oplog,
+    /// see [`crate::ConfigBuilder::set_oplog_size`].
+    ///
+    /// A replica first copies the collections, then applies the changes as they are committed.
+    /// A replica reconnecting resumes after the last change it applied, if it's still in the oplog.
+    pub fn serve_replication(&self, listener: TcpListener) -> Result<ReplicationServer> {
+        ReplicationServer::start(Arc::downgrade(&self.inner), listener)
+    }
+
+    /// Serve one replica connected with a custom transport,
+    /// blocking until it disconnects or the database is closed.
+    pub fn serve_replica(&self, transport: &mut dyn ReplicationTransport) -> Result<()> {
+        crate::replication::serve_replica(&Arc::downgrade(&self.inner), transport, &AtomicBool::new(false))
+    }
+
+    /// Make this database a read-only replica of the primary listening at `addr`,
+    /// see [`Database::serve_replication`]. The writes fail with [`Error::ReadOnlyReplica`]
+    /// until the returned [`Replica`] is stopped.
+    pub fn replicate_from(&self, addr: impl ToSocketAddrs) -> Result<Replica> {
+        let connect = crate::replication::tcp_connect(addr)?;
+        Replica::start(&self.inner, connect)
+    }
+
+    /// Make this database a read-only replica of the primary reached by the transports
+    /// returned by `connect`, called again to reconnect.
+    pub fn replicate_with<F>(&self, connect: F) -> Result<Replica>
+    where
+        F: FnMut() -> Result<Box<dyn ReplicationTransport>> + Send + 'static,
+    {
+        Replica::start(&self.inner, connect)
+    }
+
+    /// Create an engine syncing `collections` with a remote backend, see [`crate::sync`].
+    pub fn sync_engine<I, S>(&self, collections: I) -> SyncEngine
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        SyncEngine::new(&self.inner, collections.into_iter().map(Into::into).collect())
+    }
+
+    /// Return the advisory lock `name`, shared by the handles of the database,
+    /// see [`AdvisoryLock`].
+    pub fn lock(&self, name: &str) -> AdvisoryLock {
+        AdvisoryLock::new(&self.inner, name)
+    }
+
+    /// Start a scheduler running recurring jobs on this database in the background,
+    /// each run in its own transaction, see [`Scheduler`].
+    pub fn start_scheduler(&self) -> Scheduler {
+        Scheduler::start(&self.inner)
+    }
+
+    /// List the operations in progress: the running queries, updates and deletes,
+    /// and the open transactions.
+    pub fn current_ops(&self) -> Vec<CurrentOp> {
+        self.inner.current_ops()
+    }
+
+    /// Kill the operation with the `id` returned by [`Database::current_ops`],
+    /// return `false` if the operation is already finished.
+    pub fn kill_op(&self, id: u64) -> bool {
+        self.inner.operations().kill(id)
+    }
+
+    /// Execute a SQL statement in a new transaction, the transaction is
+    /// committed if the statement succeeds.
+    ///
+    /// See [`crate::sql`] for the supported subset of SQL.
+    #[cfg(feature = "sql")]
+    pub fn sql(&self, sql: &str) -> Result<crate::sql::SqlResult> {
+        let txn = self.start_transaction()?;
+        match crate::sql::execute(&txn, sql) {
+            Ok(result) => {
+                txn.commit()?;
+                Ok(result)
+            }
+            Err(err) => {
+                txn.rollback()?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Gets the names of the collections in the database,
+    /// without the collections of the logical databases.
     pub fn list_collection_names(&self) -> Result<Vec<String>> {
         let txn = self.inner.start_transaction()?;
-        self.inner.list_collection_names_with_session(&txn)
+        let names = self.inner.list_collection_names_with_session(&txn)?;
+        Ok(crate::namespace::collections_of(names, None))
+    }
+
+    /// Return the logical database `name`, a group of collections stored in this file,
+    /// see [`LogicalDatabase`].
+    pub fn database(&self, name: &str) -> LogicalDatabase {
+        LogicalDatabase::new(&self.inner, name)
+    }
+
+    /// Gets the names of the logical databases having collections, see [`Database::database`].
+    pub fn list_database_names(&self) -> Result<Vec<String>> {
+        let txn = self.inner.start_transaction()?;
+        let names = self.inner.list_collection_names_with_session(&txn)?;
+        Ok(crate::namespace::database_names(&names))
+    }
+
+    /// Open the database file at `path` and attach it to this database under `alias`,
+    /// to read its collections next to the ones of this database and import them with
+    /// [`Database::import_attached`], such as to merge an archive into the live database.
+    ///
+    /// The attached database is returned, and can be obtained again with [`Database::attached`].
+    /// Its file stays open until it's detached with [`Database::detach`] and the returned
+    /// handles are dropped, or until this database is closed.
+    ///
+    /// ```rust
+    /// use polodb_core::{Database, CollectionT};
+    /// use polodb_core::bson::{doc, Document};
+    /// # let archive_path = polodb_core::test_utils::mk_db_path("doc-test-attach-archive");
+    /// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-attach");
+    /// # {
+    /// #     let archive = Database::open_path(&archive_path).unwrap();
+    /// #     archive.collection::<Document>("orders").insert_one(doc! { "total": 12 }).unwrap();
+    /// # }
+    /// let db = Database::open_path(db_path).unwrap();
+    /// let archive = db.attach("archive", &archive_path).unwrap();
+    /// assert_eq!(archive.collection::<Document>("orders").count_documents().unwrap(), 1);
+    ///
+    /// assert_eq!(db.import_attached("archive", "orders", "orders").unwrap(), 1);
+    /// db.detach("archive").unwrap();
+    /// assert_eq!(db.collection::<Document>("orders").count_documents().unwrap(), 1);
+    /// ```
+    pub fn attach<P: AsRef<Path>>(&self, alias: &str, path: P) -> Result<Database> {
+        self.attach_with_config(alias, path, Config::default())
+    }
+
+    /// Attach the database file at `path` under `alias`, opened with `config`.
+    pub fn attach_with_config<P: AsRef<Path>>(&self, alias: &str, path: P, config: Config) -> Result<Database> {
+        if self.inner.attached().get(alias).is_ok() {
+            return Err(Error::DatabaseAlreadyAttached(alias.to_string()));
+        }
+        let db = Database::open_path_with_config(path, config)?;
+        self.inner.attached().attach(alias, db.clone())?;
+        Ok(db)
+    }
+
+    /// Return the database attached under `alias`.
+    pub fn attached(&self, alias: &str) -> Result<Database> {
+        self.inner.attached().get(alias)
+    }
+
+    /// Gets the aliases of the attached databases, sorted.
+    pub fn list_attached(&self) -> Vec<String> {
+        self.inner.attached().aliases()
+    }
+
+    /// Detach the database attached under `alias`. Its file is closed once the handles
+    /// returned by [`Database::attach`] and [`Database::attached`] are dropped.
+    pub fn detach(&self, alias: &str) -> Result<()> {
+        self.inner.attached().detach(alias)?;
+        Ok(())
+    }
+
+    /// Copy the collection `source` of the database attached under `alias` into the
+    /// collection `target` of this database, which is created if it doesn't exist,
+    /// and return the number of copied documents.
+    ///
+    /// The indexes of `source` are created on `target` first, the documents are then
+    /// inserted. The copy is one transaction: if a document fails, such as a document
+    /// violating a unique index, nothing is copied.
+    pub fn import_attached(&self, alias: &str, source: &str, target: &str) -> Result<u64> {
+        let attached = self.attached(alias)?;
+        let exists = {
+            let txn = attached.inner.start_transaction()?;
+            attached.inner.get_collection_meta_by_name_advanced_auto(source, false, &txn)?.is_some()
+        };
+        if !exists {
+            return Err(Error::CollectionNotFound(source.to_string()));
+        }
+
+        let txn = self.start_transaction()?;
+        match crate::attach::import_collection(&attached, source, &txn, target) {
+            Ok(count) => {
+                txn.commit()?;
+                Ok(count)
+            }
+            Err(err) => {
+                txn.rollback()?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Describe the collections of the database, for the administration tools.
+    ///
+    /// Each document has the `name` of the collection, the `options` it was created or
+    /// modified with, the `info` with its `uuid` and `createdAt` time, the number of indexes
+    /// `nindexes`, the number of validators registered with [`Database::add_validator`]
+    /// `nativeValidators`, and the approximate number of documents `count`.
+    /// The collections of the logical databases are left out.
+    pub fn list_collections(&self) -> Result<Vec<Document>> {
+        let txn = self.inner.start_transaction()?;
+        let mut collections = self.inner.list_collections(&txn)?;
+        collections.retain(|info| {
+            crate::namespace::split_name(info.get_str("name").unwrap_or_default()).0.is_none()
+        });
+        Ok(collections)
+    }
+
+    /// Removes all the collections of the database with their documents and indexes,
+    /// the oplog and the audit log, then reclaims the space of the files.
+    ///
+    /// The removal is atomic, the database stays open and can be used afterward.
+    /// The hooks and the validators registered on the collections are kept.
+    pub fn drop(&self) -> Result<()> {
+        self.inner.drop_database()
+    }
+
+    /// Compact the underlying storage, reclaiming the space of
+    /// deleted and overwritten records.
+    pub fn compact(&self) -> Result<()> {
+        self.inner.compact()
+    }
+
+    /// Check the integrity of the database: read every key of the storage, verifying
+    /// the checksums of the blocks and the order of the keys, then check that each
+    /// document is stored under the key of its `_id` and the entries of the indexes
+    /// match the documents. Run it after an unclean shutdown, before the writes.
+    ///
+    /// The problems found are listed in the report, an error is only returned
+    /// if the verification itself can't run.
+    pub fn verify(&self) -> Result<VerifyReport> {
+        self.inner.verify()
+    }
+
+    /// Write the database to a single snapshot file at `path`, to ship a dataset with an
+    /// application and open it with [`Database::open_snapshot`]. The snapshot holds the
+    /// collections as they are when the export starts, without the space of the deleted
+    /// documents, compressed and checksummed.
+    ///
+    /// The file is written next to `path` and renamed once complete, an existing file
+    /// at `path` is replaced.
+    ///
+    /// ```rust
+    /// use polodb_core::{CollectionT, Database};
+    /// use polodb_core::bson::{doc, Document};
+    /// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-export-snapshot");
+    /// # let snapshot_path = db_path.with_extension("snapshot");
+    /// let db = Database::open_path(db_path).unwrap();
+    /// db.collection::<Document>("countries").insert_one(doc! { "code": "FR" }).unwrap();
+    /// db.export_snapshot(&snapshot_path).unwrap();
+    ///
+    /// let reference = Database::open_snapshot(&snapshot_path).unwrap();
+    /// assert_eq!(reference.collection::<Document>("countries").count_documents().unwrap(), 1);
+    /// assert!(reference.collection::<Document>("countries").insert_one(doc! { "code": "DE" }).is_err());
+    /// ```
+    pub fn export_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.inner.export_snapshot(path.as_ref())
+    }
+
+    /// Return the information recorded when the file was created: the version of the crate
+    /// that created it, the version of the on-disk format, the enabled storage features
+    /// and the page size. Useful to diagnose a database file sent by a user.
+    pub fn version_info(&self) -> VersionInfo {
+        self.inner.version_info()
+    }
+
+    /// Read a collection, its documents and the entries of its indexes,
+    /// into the block cache, so the first queries after opening the database
+    /// do not wait for the disk. `target` is the name of the collection,
+    /// or `"collection.index"` to read only the entries of one index.
+    /// Return the number of entries read.
+    pub fn preload(&self, target: &str) -> Result<u64> {
+        self.inner.preload(target)
+    }
+
+    /// Keep the blocks of a collection and its indexes in memory
+    /// until [`Database::unpin_collection`] is called, instead of letting the
+    /// reads of the other collections evict them from the block cache.
+    /// This is meant for small collections read by every request, such as settings.
+    ///
+    /// The blocks are pinned in the files of the storage they are read from: these files,
+    /// and the memtables of the writes not flushed yet, are kept on the disk and in memory
+    /// while they are pinned, even once compacted. The blocks are pinned again from the
+    /// new files by the first transaction after a flush or a compaction, and by
+    /// [`Database::compact`], so the blocks written after the collection is pinned
+    /// are pinned too. Dropping the collection unpins it.
+    /// Return the number of entries pinned.
+    pub fn pin_collection(&self, name: &str) -> Result<u64> {
+        self.inner.pin_collection(name)
+    }
+
+    /// Release the blocks of a collection pinned by [`Database::pin_collection`],
+    /// return whether the collection was pinned.
+    pub fn unpin_collection(&self, name: &str) -> Result<bool> {
+        self.inner.unpin_collection(name)
+    }
+
+    /// Delete the expired documents of the collections created with an
+    /// `expire_at_field` or with a TTL index, return the number of deleted documents.
+    ///
+    /// The expired documents are already hidden from the reads,
+    /// this only reclaims their space.
+    pub fn remove_expired_documents(&self) -> Result<u64> {
+        self.inner.remove_expired()
     }
 
 }
@@ -14,33 +14,83 @@
 
 use std::borrow::Borrow;
 use std::collections::HashMap;
-use bson::{Bson, Document};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use bson::{doc, Bson, Document};
 use serde::Serialize;
 use super::db::Result;
 use crate::errors::Error;
-use crate::options::UpdateOptions;
+use crate::options::{
+    Collation,
+    CreateCollectionOptions,
+    DeleteOptions,
+    Durability,
+    Hint,
+    InsertManyOptions,
+    ModifyCollectionOptions,
+    UpdateOptions,
+    ValidationAction,
+    ValidationLevel,
+};
 use crate::Config;
-use crate::vm::SubProgram;
+use crate::vm::{index_candidates, natural_direction, AccessPath, QueryPlan, SubProgram};
 use crate::meta_doc_helper::meta_doc_key;
 use crate::index::{IndexBuilder, IndexModel, IndexOptions};
 use crate::db::client_cursor::ClientCursor;
 use crate::results::{DeleteResult, InsertManyResult, InsertOneResult, UpdateResult};
 use std::path::Path;
+use std::time::Instant;
 use bson::oid::ObjectId;
+use indexmap::IndexMap;
+use crate::object_id::ObjectIdGenerator;
 use serde::de::DeserializeOwned;
 use crate::coll::collection_info::{
+    CappedInfo,
     CollectionSpecification,
+    CollectionStatistics,
+    CollectionType,
+    DocumentLimits,
     IndexInfo,
+    MaterializedViewInfo,
+    ValidationInfo,
+    VectorIndexInfo,
+    ViewInfo,
 };
 use crate::cursor::Cursor;
-use crate::index::{IndexHelper, IndexHelperOperation};
+use crate::utils::bson::bson_datetime_now;
+use crate::index::{IndexBounds, IndexHelper, IndexHelperOperation, IndexOrder, IndexPosition, IndexRange, PartialFilter};
 use crate::metrics::Metrics;
+use crate::profiler::Profiler;
+use crate::current_op::{CurrentOp, OperationRegistry};
+use crate::hooks::{HookEvent, HookRegistry};
+use crate::change_stream::{ChangeBatch, ChangePublisher, ChangeStreamRegistry};
+use crate::audit::AuditLog;
+use crate::oplog::Oplog;
+use crate::expiry::Expiry;
+use crate::bloom::BloomFilterRegistry;
+use crate::attach::AttachRegistry;
+use crate::snapshot::SnapshotDir;
+use crate::fuzzy::{FuzzyIndexRegistry, FuzzySearch};
+use crate::vector::{Vector, VectorIndexRegistry, VECTOR_SCORE_FIELD};
+use crate::view::ViewRegistry;
+use crate::record_cache::RecordCache;
+use crate::query_cache::QueryCache;
 use crate::db::rocksdb_wrapper::RocksDBWrapper;
-use crate::transaction::TransactionInner;
+use crate::transaction::{ReadOnly, TransactionInner};
 use crate::vm::VM;
+use crate::verify::VerifyReport;
+use crate::strictness::BsonStrictness;
+use crate::version_info::{self, VersionInfo};
+#[cfg(feature = "parquet")]
+use crate::interop::arrow::DEFAULT_BATCH_SIZE;
+#[cfg(feature = "parquet")]
+use crate::interop::parquet::ParquetDocumentReader;
 
 const TABLE_META_PREFIX: &str = "$TABLE_META";
 
+/// The planner stops counting the entries of an index beyond this number.
+const INDEX_PROBE_LIMIT: u64 = 1000;
+
 /**
  * API for all platforms
  */
@@ -48,8 +98,28 @@ pub(crate) struct DatabaseInner {
     rocksdb:      RocksDBWrapper,
     node_id:      [u8; 6],
     metrics:      Metrics,
-    #[allow(dead_code)]
+    profiler:     Profiler,
+    ops:          OperationRegistry,
+    hooks:        HookRegistry,
+    blooms:       BloomFilterRegistry,
+    fuzzy_indexes: FuzzyIndexRegistry,
+    vector_indexes: VectorIndexRegistry,
+    views:        ViewRegistry,
+    changes:      ChangeStreamRegistry,
+    audit:        AuditLog,
+    object_ids:   ObjectIdGenerator,
+    record_cache: Option<RecordCache>,
+    query_cache: Option<QueryCache>,
+    version_info: VersionInfo,
+    /// Refuse the writes but the changes applied from the primary.
+    replica:      AtomicBool,
+    /// Serializes the reads and the writes of the advisory locks.
+    advisory_locks: Mutex<()>,
+    attached:     AttachRegistry,
     config:       Config,
+    /// The store holding the keys of the opened snapshot file, the database is read-only.
+    /// Declared after the storage, the store is removed once it's closed.
+    snapshot:     Option<SnapshotDir>,
 }
 
 impl DatabaseInner {
@@ -64,6 +134,24 @@ impl DatabaseInner {
         )
     }
 
+    /// Open the snapshot file at `path`, written by [`DatabaseInner::export_snapshot`].
+    /// Its keys are loaded into a store of the temporary directory.
+    pub fn open_snapshot(path: &Path) -> Result<DatabaseInner> {
+        let dir = SnapshotDir::new();
+        let config = Config::default();
+        crate::snapshot::load(path, dir.path(), &config)?;
+        let mut inner = DatabaseInner::open_with_backend(dir.path(), config, Metrics::new())?;
+        inner.snapshot = Some(dir);
+        Ok(inner)
+    }
+
+    /// Write the documents, the indexes and the collections to the snapshot file at `path`,
+    /// as they are when the export starts.
+    pub(crate) fn export_snapshot(&self, path: &Path) -> Result<()> {
+        let txn = self.start_transaction()?;
+        crate::snapshot::export(&txn, path)
+    }
+
     fn open_with_backend(
         path: &Path,
         config: Config,
@@ -72,28 +160,354 @@ impl DatabaseInner {
         let mut node_id: [u8; 6] = [0; 6];
         getrandom::getrandom(&mut node_id).unwrap();
 
-        let rocksdb = RocksDBWrapper::open(path)?;
+        let rocksdb = RocksDBWrapper::open_with_config(path, &config)?;
+        metrics.attach_storage(rocksdb.downgrade());
+
+        let version_info = {
+            let txn = TransactionInner::new(rocksdb.begin_transaction()?);
+            version_info::load_or_init(&txn, &config, &rocksdb)?
+        };
+
+        let oplog = if config.oplog_size > 0 {
+            let txn = TransactionInner::new(rocksdb.begin_transaction()?);
+            Some(Oplog::open(&txn, config.oplog_size)?)
+        } else {
+            None
+        };
+        let changes = ChangeStreamRegistry::new(oplog);
 
         let ctx = DatabaseInner {
             rocksdb,
             // first_page,
             node_id,
             metrics,
+            profiler: Profiler::new(),
+            ops: OperationRegistry::new(),
+            hooks: HookRegistry::new(),
+            blooms: BloomFilterRegistry::new(),
+            fuzzy_indexes: FuzzyIndexRegistry::new(),
+            vector_indexes: VectorIndexRegistry::new(),
+            views: ViewRegistry::new(),
+            changes,
+            audit: AuditLog::new(),
+            object_ids: ObjectIdGenerator::new(
+                config.object_id_machine_id,
+                config.object_id_process_id,
+                config.object_id_counter_mode,
+            ),
+            record_cache: if config.record_cache_size > 0 {
+                Some(RecordCache::new(config.record_cache_size))
+            } else {
+                None
+            },
+            query_cache: if config.query_cache_size > 0 {
+                Some(QueryCache::new(config.query_cache_size, config.query_cache_ttl))
+            } else {
+                None
+            },
+            version_info,
+            replica: AtomicBool::new(false),
+            advisory_locks: Mutex::new(()),
+            attached: AttachRegistry::new(),
             config,
+            snapshot: None,
         };
+        ctx.build_bloom_filters()?;
 
         Ok(ctx)
     }
 
+    /// Fill the bloom filters, the fuzzy indexes and the vector indexes
+    /// of the collections, before any transaction may write to them.
+    fn build_bloom_filters(&self) -> Result<()> {
+        let txn = self.start_transaction()?;
+        for meta in self.query_all_meta(&txn)? {
+            let col_spec: CollectionSpecification = bson::from_document(meta)?;
+            if let Some(field) = &col_spec.bloom_filter_field {
+                self.blooms.build(&txn, &col_spec, field)?;
+            }
+            if let Some(field) = &col_spec.fuzzy_index_field {
+                self.fuzzy_indexes.build(&txn, &col_spec, field)?;
+            }
+            for (index_name, info) in &col_spec.vector_indexes {
+                self.vector_indexes.build(&txn, col_spec.name(), index_name, info)?;
+            }
+            if let Some(view) = &col_spec.materialized_view {
+                self.views.register(col_spec.name(), view.clone());
+            }
+        }
+        Ok(())
+    }
+
     pub fn metrics(&self) -> Metrics {
         self.metrics.clone()
     }
 
+    pub(crate) fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub(crate) fn advisory_locks(&self) -> &Mutex<()> {
+        &self.advisory_locks
+    }
+
+    pub(crate) fn attached(&self) -> &AttachRegistry {
+        &self.attached
+    }
+
+    pub(crate) fn query_cache(&self) -> Option<&QueryCache> {
+        self.query_cache.as_ref()
+    }
+
+    /// Whether the results of the finds on the collection can be cached until it's written:
+    /// the documents of a view change with its source, and the expired documents
+    /// of a collection are hidden as time passes.
+    pub(crate) fn is_query_cacheable(&self, col_name: &str, txn: &TransactionInner) -> Result<bool> {
+        let col_spec = self.get_collection_meta_by_name_advanced_auto(col_name, false, txn)?;
+        Ok(col_spec.is_none_or(|col_spec| {
+            col_spec.view.is_none() && Expiry::of_collection(&col_spec).is_none()
+        }))
+    }
+
+    pub fn profiler(&self) -> Profiler {
+        self.profiler.clone()
+    }
+
+    pub(crate) fn operations(&self) -> &OperationRegistry {
+        &self.ops
+    }
+
+    pub fn current_ops(&self) -> Vec<CurrentOp> {
+        self.ops.list()
+    }
+
+    pub(crate) fn hooks(&self) -> &HookRegistry {
+        &self.hooks
+    }
+
+    pub(crate) fn change_streams(&self) -> &ChangeStreamRegistry {
+        &self.changes
+    }
+
+    pub fn audit(&self) -> AuditLog {
+        self.audit.clone()
+    }
+
+    fn change_publisher(&self, col_name: &str) -> Option<ChangePublisher> {
+        self.changes.publisher(col_name, self.audit.recorder(col_name))
+    }
+
+    /// Attach the hooks, the change streams and the audit log of the collection
+    /// to a VM updating or deleting its documents.
+    fn observe_writes(&self, vm: &mut VM, col_name: &str) -> Result<()> {
+        if self.audit.is_enabled() && self.audit.is_sink(col_name) {
+            return Err(Error::AuditLogAppendOnly(col_name.to_string()));
+        }
+        if let Some(hooks) = self.hooks.get(col_name) {
+            vm.set_hooks(hooks);
+        }
+        if let Some(publisher) = self.change_publisher(col_name) {
+            vm.set_change_publisher(publisher);
+        }
+        Ok(())
+    }
+
+    /// Name the operation of the VM for the metrics, register it in the current operations
+    /// and attach the recorder of the profiler to it.
+    fn track_vm(&self, vm: &mut VM, op: &'static str, col_name: &str, filter: Option<&Document>) {
+        let comment = filter.and_then(|filter| filter.get("$comment"));
+        self.track_vm_with_comment(vm, op, col_name, filter, comment);
+    }
+
+    /// Like [`DatabaseInner::track_vm`], tagging the operation with `comment`
+    /// instead of the `$comment` of the filter.
+    fn track_vm_with_comment(
+        &self,
+        vm: &mut VM,
+        op: &'static str,
+        col_name: &str,
+        filter: Option<&Document>,
+        comment: Option<&Bson>,
+    ) {
+        vm.set_op(op);
+        vm.set_op_guard(self.ops.register(op, Some(col_name), filter, comment, Default::default()));
+        if let Some(recorder) = self.profiler.recorder(op, col_name, filter, comment) {
+            vm.set_profile(recorder);
+        }
+    }
+
     pub fn start_transaction(&self) -> Result<TransactionInner> {
-        Ok(TransactionInner::new(self.rocksdb.begin_transaction()?))
+        self.start_transaction_with_durability(None)
+    }
+
+    /// Start a transaction committed with `durability`, or the durability of the database.
+    pub fn start_transaction_with_durability(&self, durability: Option<Durability>) -> Result<TransactionInner> {
+        let sync = durability.unwrap_or(self.config.durability) == Durability::Sync;
+        let txn = TransactionInner::new(self.rocksdb.begin_transaction_with_sync(sync)?);
+        Ok(txn
+            .with_record_cache(self.record_cache.clone())
+            .with_query_cache(self.query_cache.clone())
+            .with_read_only(self.read_only()))
+    }
+
+    fn read_only(&self) -> Option<ReadOnly> {
+        if self.snapshot.is_some() {
+            Some(ReadOnly::Snapshot)
+        } else if self.replica.load(Ordering::SeqCst) {
+            Some(ReadOnly::Replica)
+        } else {
+            None
+        }
+    }
+
+    /// Start a transaction applying the changes of the primary, writable on a replica.
+    pub(crate) fn start_replication_transaction(&self) -> Result<TransactionInner> {
+        let txn = TransactionInner::new(self.rocksdb.begin_transaction()?);
+        // a snapshot is never written, not even by the changes of a primary
+        let read_only = self.snapshot.as_ref().map(|_| ReadOnly::Snapshot);
+        Ok(txn
+            .with_record_cache(self.record_cache.clone())
+            .with_query_cache(self.query_cache.clone())
+            .with_read_only(read_only))
+    }
+
+    pub(crate) fn set_replica(&self, replica: bool) {
+        self.replica.store(replica, Ordering::SeqCst);
+    }
+
+    /// Replace the collection by an empty one with the specification
+    /// of the collection of the primary, with its indexes and options.
+    pub(crate) fn restore_collection(&self, spec: &CollectionSpecification, txn: &TransactionInner) -> Result<()> {
+        let name = spec.name();
+        DatabaseInner::validate_col_name(name)?;
+        self.drop_collection_internal(name, txn)?;
+        DatabaseInner::update_collection_spec(name, spec, txn)?;
+        if let Some(field) = &spec.bloom_filter_field {
+            self.blooms.create(name, field);
+        }
+        if let Some(field) = &spec.fuzzy_index_field {
+            self.fuzzy_indexes.create(name, field);
+        }
+        for (index_name, info) in &spec.vector_indexes {
+            self.vector_indexes.build(txn, name, index_name, info)?;
+        }
+        Ok(())
+    }
+
+    pub fn compact(&self) -> Result<()> {
+        self.rocksdb.compact()
+    }
+
+    pub fn verify(&self) -> Result<VerifyReport> {
+        let txn = self.start_transaction()?;
+        crate::verify::verify_database(self, &txn)
+    }
+
+    pub fn changes_since(&self, token: u64) -> Result<ChangeBatch> {
+        let oplog = self.change_streams().oplog().ok_or(Error::ChangeStreamHistoryLost(token))?;
+        let txn = self.start_transaction()?;
+        let changes = oplog.read_after(&txn, token)?;
+        let resume_token = changes.last()
+            .and_then(|event| event.resume_token)
+            .unwrap_or(token);
+        Ok(ChangeBatch {
+            changes,
+            resume_token,
+        })
+    }
+
+    pub fn version_info(&self) -> VersionInfo {
+        self.version_info.clone()
+    }
+
+    /// Build the entries of the indexes of a collection again from its documents.
+    pub fn rebuild_indexes(&self, col_name: &str, txn: &TransactionInner) -> Result<()> {
+        DatabaseInner::validate_col_name(col_name)?;
+        let col_spec = match self.internal_get_collection_id_by_name(txn, col_name) {
+            Ok(col_spec) => col_spec,
+            Err(Error::CollectionNotFound(_)) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let indexes = col_spec.indexes.iter()
+            .map(|(index_name, index_info)| (index_name.as_str(), index_info))
+            .collect::<Vec<(&str, &IndexInfo)>>();
+        IndexBuilder::rebuild_many(txn, col_name, &indexes, self.config.index_build_parallelism)
+    }
+
+    /// Compact the documents and the index entries of a collection,
+    /// the keys of the other collections are not rewritten.
+    pub fn compact_collection(&self, col_name: &str) -> Result<()> {
+        let prefixes = match self.preload_prefixes(col_name) {
+            Ok(prefixes) => prefixes,
+            Err(Error::CollectionNotFound(_)) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        self.rocksdb.compact_prefixes(&prefixes)
+    }
+
+    /// Read the documents and the index entries of a collection into the block cache,
+    /// or only the entries of one of its indexes when `target` is `collection.index`.
+    pub fn preload(&self, target: &str) -> Result<u64> {
+        let prefixes = self.preload_prefixes(target)?;
+        self.rocksdb.preload(prefixes)
+    }
+
+    pub fn pin_collection(&self, col_name: &str) -> Result<u64> {
+        DatabaseInner::validate_col_name(col_name)?;
+        let prefixes = self.preload_prefixes(col_name)?;
+        self.rocksdb.pin(col_name, prefixes)
+    }
+
+    pub fn unpin_collection(&self, col_name: &str) -> Result<bool> {
+        self.rocksdb.unpin(col_name)
+    }
+
+    fn preload_prefixes(&self, target: &str) -> Result<Vec<Vec<u8>>> {
+        let (col_name, index_name) = match target.split_once('.') {
+            Some((col_name, index_name)) => (col_name, Some(index_name)),
+            None => (target, None),
+        };
+        DatabaseInner::validate_col_name(col_name)?;
+
+        let txn = self.start_transaction()?;
+        let col_spec = self.internal_get_collection_id_by_name(&txn, col_name)?;
+        if let Some(index_name) = index_name {
+            if !col_spec.indexes.contains_key(index_name) {
+                return Err(Error::IndexNotFound(index_name.to_string()));
+            }
+            return Ok(vec![DatabaseInner::index_prefix(col_name, index_name)?]);
+        }
+
+        let b_col_name = Bson::String(col_name.to_string());
+        let b_index_prefix = Bson::String(crate::index::INDEX_PREFIX.to_string());
+        Ok(vec![
+            crate::utils::bson::stacked_key([&b_col_name])?,
+            crate::utils::bson::stacked_key([&b_index_prefix, &b_col_name])?,
+        ])
+    }
+
+    /// Delete every key of the database in one transaction, then reclaim the space.
+    pub fn drop_database(&self) -> Result<()> {
+        let txn = self.start_transaction()?;
+        let mut cursor = Cursor::new(Vec::new(), txn.rocksdb_txn.new_iterator());
+        cursor.reset()?;
+        while cursor.has_next() {
+            if let Some(key) = cursor.peek_key() {
+                txn.delete(key.as_ref())?;
+            }
+            cursor.next()?;
+        }
+        txn.commit()?;
+
+        self.rocksdb.compact()
     }
 
     fn internal_get_collection_id_by_name(&self, txn: &TransactionInner, name: &str) -> Result<CollectionSpecification> {
+        DatabaseInner::read_collection_spec(txn, name)
+    }
+
+    /// Read the specification of the collection in the transaction.
+    pub(crate) fn read_collection_spec(txn: &TransactionInner, name: &str) -> Result<CollectionSpecification> {
         let mut cursor =  {
             let kv_cursor = txn.rocksdb_txn.new_iterator();
             Cursor::new_with_str_prefix(TABLE_META_PREFIX.to_string(), kv_cursor)?
@@ -113,6 +527,17 @@ impl DatabaseInner {
         Ok(entry)
     }
 
+    /// Record in the specification of the collection that a document has several
+    /// entries in the index, if it's not recorded yet.
+    pub(crate) fn mark_index_multikey(txn: &TransactionInner, col_name: &str, index_name: &str) -> Result<()> {
+        let mut spec = DatabaseInner::read_collection_spec(txn, col_name)?;
+        match spec.indexes.get_mut(index_name) {
+            Some(index_info) if !index_info.is_multikey() => index_info.set_multikey(),
+            _ => return Ok(()),
+        }
+        DatabaseInner::update_collection_spec(col_name, &spec, txn)
+    }
+
     pub fn get_collection_meta_by_name_advanced_auto(
         &self,
         name: &str,
@@ -149,6 +574,519 @@ impl DatabaseInner {
         Ok(result)
     }
 
+    pub fn create_collection_with_options(&self, name: &str, options: CreateCollectionOptions) -> Result<CollectionSpecification> {
+        DatabaseInner::validate_col_name(name)?;
+
+        let validation = DatabaseInner::merge_validation(
+            None,
+            options.validator,
+            options.validation_level,
+            options.validation_action,
+        )?;
+        if let Some(defaults) = &options.defaults {
+            crate::defaults::check(defaults)?;
+        }
+        let capped = if options.capped.unwrap_or(false) {
+            if options.size.is_none() && options.max.is_none() {
+                return Err(Error::CappedCollection(format!("'{}' needs a size or a max", name)));
+            }
+            Some(CappedInfo {
+                size: options.size,
+                max: options.max,
+            })
+        } else {
+            None
+        };
+
+        let txn = self.start_transaction()?;
+        let mut spec = self.create_collection_internal(name, &txn)?;
+        spec.validation = validation;
+        spec.capped = capped;
+        spec.expire_at_field = options.expire_at_field.filter(|field| !field.is_empty());
+        spec.limits = DatabaseInner::merge_limits(
+            None,
+            options.max_document_size,
+            options.max_document_depth,
+        );
+        spec.defaults = options.defaults.filter(|defaults| !defaults.is_empty());
+        spec.bloom_filter_field = options.bloom_filter.filter(|field| !field.is_empty());
+        spec.fuzzy_index_field = options.fuzzy_index.filter(|field| !field.is_empty());
+        spec.crdt = options.crdt.unwrap_or(false);
+        DatabaseInner::update_collection_spec(name, &spec, &txn)?;
+        if let Some(field) = &spec.bloom_filter_field {
+            self.blooms.create(name, field);
+        }
+        if let Some(field) = &spec.fuzzy_index_field {
+            self.fuzzy_indexes.create(name, field);
+        }
+        txn.commit()?;
+
+        Ok(spec)
+    }
+
+    /// Create the collection `name` holding the result of the pipeline run on the documents
+    /// of `source`, kept up to date by the writes to `source` once the transaction is committed.
+    pub fn create_materialized_view(&self, name: &str, source: &str, pipeline: Vec<Document>, txn: &TransactionInner) -> Result<()> {
+        DatabaseInner::validate_col_name(name)?;
+        DatabaseInner::validate_col_name(source)?;
+        if name == source {
+            return Err(Error::IllegalCollectionName(name.to_string()));
+        }
+        let info = MaterializedViewInfo {
+            source: source.to_string(),
+            pipeline,
+        };
+        let mut spec = self.create_collection_internal(name, txn)?;
+        spec.materialized_view = Some(info.clone());
+        DatabaseInner::update_collection_spec(name, &spec, txn)?;
+        self.recompute_view(name, &info, txn)?;
+
+        let views = self.views.clone();
+        let name = name.to_string();
+        txn.on_commit(Box::new(move || views.register(&name, info)));
+        Ok(())
+    }
+
+    /// Create the read-only view `name`, whose documents are the result of the pipeline
+    /// run on the documents of `source` each time the view is queried.
+    pub fn create_view(&self, name: &str, source: &str, pipeline: Vec<Document>, txn: &TransactionInner) -> Result<()> {
+        DatabaseInner::validate_col_name(name)?;
+        DatabaseInner::validate_col_name(source)?;
+        let mut spec = self.create_collection_internal(name, txn)?;
+
+        // a view can't be computed from itself, through the views it's computed from
+        let mut current = source.to_string();
+        loop {
+            if current == name {
+                return Err(Error::IllegalCollectionName(name.to_string()));
+            }
+            match self.get_collection_meta_by_name_advanced_auto(&current, false, txn)? {
+                Some(CollectionSpecification { view: Some(view), .. }) => current = view.source,
+                _ => break,
+            }
+        }
+
+        spec.collection_type = CollectionType::View;
+        spec.info.uuid = None;
+        spec.view = Some(ViewInfo {
+            source: source.to_string(),
+            pipeline,
+        });
+        DatabaseInner::update_collection_spec(name, &spec, txn)
+    }
+
+    /// Compute the documents of the materialized view again from all the documents of its source.
+    pub fn refresh_materialized_view(&self, name: &str, txn: &TransactionInner) -> Result<()> {
+        DatabaseInner::validate_col_name(name)?;
+        let spec = self.internal_get_collection_id_by_name(txn, name)?;
+        match &spec.materialized_view {
+            Some(info) => self.recompute_view(name, info, txn),
+            None => Err(Error::NotMaterializedView(name.to_string())),
+        }
+    }
+
+    /// Replace the documents of the view by the result of its pipeline.
+    fn recompute_view(&self, name: &str, info: &MaterializedViewInfo, txn: &TransactionInner) -> Result<()> {
+        let mut txn = txn.clone();
+        txn.set_auto_commit(false);
+        self.internal_delete_all(&txn, name)?;
+        let docs = self.aggregate_with_plan::<Document>(
+            &info.source,
+            info.pipeline.clone(),
+            QueryPlan::default(),
+            None,
+            txn.clone(),
+        )?.collect::<Result<Vec<Document>>>()?;
+        let view_spec = self.internal_get_collection_id_by_name(&txn, name)?;
+        for doc in docs {
+            self.insert_one_with_meta(&txn, &view_spec, doc, true)?;
+        }
+        Ok(())
+    }
+
+    /// Bring the materialized views of the collection up to date with the writes to the documents `ids`,
+    /// or to any of its documents if `ids` is `None`.
+    fn maintain_views(&self, source: &str, ids: Option<&[Bson]>, txn: &TransactionInner) -> Result<()> {
+        let views = self.views.views_of(source);
+        if views.is_empty() {
+            return Ok(());
+        }
+        let mut txn = txn.clone();
+        txn.set_auto_commit(false);
+        for (name, info) in views {
+            let ids = match ids {
+                Some(ids) if info.is_incremental() => ids,
+                _ => {
+                    self.recompute_view(&name, &info, &txn)?;
+                    continue;
+                }
+            };
+            let view_spec = match self.internal_get_collection_id_by_name(&txn, &name) {
+                Ok(spec) => spec,
+                Err(Error::CollectionNotFound(_)) => continue,
+                Err(err) => return Err(err),
+            };
+            for id in ids {
+                let docs = self.aggregate_with_plan::<Document>(
+                    source,
+                    info.document_pipeline(id),
+                    QueryPlan::default(),
+                    None,
+                    txn.clone(),
+                )?.collect::<Result<Vec<Document>>>()?;
+                self.internal_delete_by_query(&txn, &name, doc! { "_id": id.clone() }, false, &DeleteOptions::default())?;
+                for mut doc in docs {
+                    doc.insert("_id", id.clone());
+                    self.insert_one_with_meta(&txn, &view_spec, doc, true)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn modify_collection(&self, name: &str, options: ModifyCollectionOptions, txn: &TransactionInner) -> Result<()> {
+        DatabaseInner::validate_col_name(name)?;
+
+        let mut spec = self.internal_get_collection_id_by_name(txn, name)?;
+        spec.validation = DatabaseInner::merge_validation(
+            spec.validation.take(),
+            options.validator,
+            options.validation_level,
+            options.validation_action,
+        )?;
+        if let Some(field) = options.expire_at_field {
+            spec.expire_at_field = if field.is_empty() { None } else { Some(field) };
+        }
+        spec.limits = DatabaseInner::merge_limits(
+            spec.limits,
+            options.max_document_size,
+            options.max_document_depth,
+        );
+        if let Some(defaults) = options.defaults {
+            crate::defaults::check(&defaults)?;
+            spec.defaults = if defaults.is_empty() { None } else { Some(defaults) };
+        }
+        if let Some(crdt) = options.crdt {
+            spec.crdt = crdt;
+        }
+        DatabaseInner::update_collection_spec(name, &spec, txn)
+    }
+
+    /// Apply the changes to the limits of a collection, 0 removes a limit.
+    fn merge_limits(current: Option<DocumentLimits>, max_size: Option<u64>, max_depth: Option<u64>) -> Option<DocumentLimits> {
+        let current = current.unwrap_or_default();
+        let limits = DocumentLimits {
+            max_size: max_size.map_or(current.max_size, |size| Some(size).filter(|size| *size > 0)),
+            max_depth: max_depth.map_or(current.max_depth, |depth| Some(depth).filter(|depth| *depth > 0)),
+        };
+        if limits.is_unlimited() {
+            None
+        } else {
+            Some(limits)
+        }
+    }
+
+    /// The limits of the documents of the collection, falling back to the limits of the database.
+    fn document_limits(&self, col_spec: &CollectionSpecification) -> DocumentLimits {
+        let db_limits = DocumentLimits {
+            max_size: Some(self.config.max_document_size).filter(|size| *size > 0),
+            max_depth: Some(self.config.max_document_depth).filter(|depth| *depth > 0),
+        };
+        col_spec.limits.unwrap_or_default().or(db_limits)
+    }
+
+    /// Hide the expired documents of the collection from the VM.
+    fn apply_expiry(vm: &mut VM, col_spec: &CollectionSpecification) {
+        if let Some(expiry) = Expiry::of_collection(col_spec) {
+            vm.set_expiry(expiry);
+        }
+    }
+
+    /// Let the VM answer the lookups of the values missing from the bloom filter of the collection.
+    fn apply_bloom_filter(&self, vm: &mut VM, col_spec: &CollectionSpecification) -> Result<()> {
+        let field = match &col_spec.bloom_filter_field {
+            Some(field) => field,
+            None => return Ok(()),
+        };
+        let bloom = match self.blooms.get(col_spec.name()) {
+            Some(bloom) if bloom.field() == field => bloom,
+            _ => return Ok(()),
+        };
+        let mut index_prefixes = Vec::new();
+        for (index_name, index_info) in &col_spec.indexes {
+            let (key, _order) = index_info.keys.iter().next().unwrap();
+            // the filter holds the values which are not collated
+            if index_info.keys.len() == 1 && key == field && index_info.collation() == Collation::Simple {
+                index_prefixes.push(IndexHelper::index_prefix(col_spec.name(), index_name)?);
+            }
+        }
+        vm.set_bloom_filter(bloom, index_prefixes);
+        Ok(())
+    }
+
+    /// Let the VM add the updated documents to the fuzzy index of the collection,
+    /// and only read the documents found by the index for a `$fuzzy` search of the query.
+    fn apply_fuzzy_index(&self, vm: &mut VM, col_spec: &CollectionSpecification, query: Option<&Document>) {
+        let field = match &col_spec.fuzzy_index_field {
+            Some(field) => field,
+            None => return,
+        };
+        let index = match self.fuzzy_indexes.get(col_spec.name()) {
+            Some(index) if index.field() == field => index,
+            _ => return,
+        };
+        let search = match query.and_then(|query| query.get(field)) {
+            Some(Bson::Document(condition)) => condition.get("$fuzzy").and_then(FuzzySearch::parse),
+            _ => None,
+        };
+        if let Some(search) = search {
+            vm.set_candidates(index.candidates(&search));
+        }
+        vm.set_fuzzy_index(index);
+    }
+
+    /// Resolve the hint and the collation given to a write into the plan of its query.
+    fn query_plan(col_spec: &CollectionSpecification, hint: Option<&Hint>, collation: Option<Collation>) -> Result<QueryPlan> {
+        // { "$natural": 1 | -1 } scans the collection without the indexes
+        let natural = match hint {
+            Some(Hint::Keys(keys)) if keys.len() == 1 => keys.get("$natural").and_then(natural_direction),
+            _ => None,
+        };
+        if let Some(backward) = natural {
+            return Ok(QueryPlan {
+                collation: collation.unwrap_or_default(),
+                collection_scan: true,
+                backward,
+                ..Default::default()
+            });
+        }
+        let hint = match hint {
+            Some(Hint::Name(name)) => {
+                if !col_spec.indexes.contains_key(name) {
+                    return Err(Error::IndexNotFound(name.clone()));
+                }
+                Some(name.clone())
+            }
+            Some(Hint::Keys(keys)) => {
+                let found = col_spec.indexes.iter().find(|(_, info)| {
+                    info.keys.len() == keys.len() && info.keys.iter().zip(keys.iter()).all(|((key, order), (hint_key, hint_order))| {
+                        key == hint_key && matches!(hint_order.as_i64().or_else(|| hint_order.as_i32().map(i64::from)), Some(o) if o == *order as i64)
+                    })
+                });
+                match found {
+                    Some((name, _)) => Some(name.clone()),
+                    None => return Err(Error::IndexNotFound(keys.to_string())),
+                }
+            }
+            None => None,
+        };
+        Ok(QueryPlan {
+            hint,
+            collation: collation.unwrap_or_default(),
+            ..Default::default()
+        })
+    }
+
+    /// Choose the index finding the fewest documents when several indexes can run
+    /// the query. The number of documents is estimated with the statistics of the
+    /// collection if it was analyzed, otherwise by counting the entries of the
+    /// queried values up to a bound.
+    fn plan_query(&self, col_spec: &CollectionSpecification, query: &Document, mut plan: QueryPlan, txn: &TransactionInner) -> Result<QueryPlan> {
+        if plan.hint.is_some() || plan.collection_scan || query.contains_key("_id") {
+            return Ok(plan);
+        }
+        let candidates = index_candidates(col_spec, query, None, plan.collation);
+        if candidates.len() < 2 {
+            return Ok(plan);
+        }
+
+        let mut best: Option<(&str, u64)> = None;
+        for candidate in &candidates {
+            let count = DatabaseInner::estimate_index_count(col_spec, candidate.index_name, &candidate.values, txn)?;
+            if best.is_none_or(|(_, best_count)| count < best_count) {
+                best = Some((candidate.index_name, count));
+            }
+            if count == 0 {
+                break;
+            }
+        }
+        plan.index = best.map(|(index_name, _)| index_name.to_string());
+
+        Ok(plan)
+    }
+
+    /// The number of documents of the index having `values` for its first keys,
+    /// estimated with the statistics of the collection if it was analyzed,
+    /// otherwise counted up to a bound.
+    fn estimate_index_count(col_spec: &CollectionSpecification, index_name: &str, values: &[Bson], txn: &TransactionInner) -> Result<u64> {
+        // the statistics are on the first field of the index
+        let estimate = col_spec.statistics.as_ref()
+            .filter(|_| values.len() == 1)
+            .and_then(|statistics| statistics.indexes.get(index_name))
+            .map(|field_statistics| field_statistics.estimate(&values[0]));
+        match estimate {
+            Some(estimate) => Ok(estimate),
+            None => IndexHelper::count_value(txn, col_spec.name(), index_name, values, INDEX_PROBE_LIMIT),
+        }
+    }
+
+    /// Read all the documents of `cursor`, and describe how the find of `filter` on
+    /// the collection found them, with the number of documents it was estimated to examine.
+    pub(crate) fn explain<T: DeserializeOwned + Send + Sync>(
+        &self,
+        col_name: &str,
+        filter: Document,
+        cursor: ClientCursor<T>,
+        txn: &TransactionInner,
+    ) -> Result<Document> {
+        let estimate = match cursor.access_path() {
+            Some(AccessPath::PrimaryKey(_)) => 1,
+            Some(AccessPath::Index { col_name, index_name, values }) => {
+                match self.get_collection_meta_by_name_advanced_auto(col_name, false, txn)? {
+                    Some(col_spec) => DatabaseInner::estimate_index_count(&col_spec, index_name, values, txn)?,
+                    None => 0,
+                }
+            }
+            // at most all the documents, the bounds of a scan in the order of an index are not estimated
+            Some(AccessPath::CollectionScan(col_name)) => {
+                match self.get_collection_meta_by_name_advanced_auto(col_name, false, txn)? {
+                    Some(col_spec) => match &col_spec.statistics {
+                        Some(statistics) => statistics.documents,
+                        None => self.count(col_name, txn)?,
+                    },
+                    None => 0,
+                }
+            }
+            Some(AccessPath::Empty) | Some(AccessPath::Values) | None => 0,
+        };
+        let mut result = doc! {
+            "namespace": col_name,
+            "filter": filter,
+            "estimatedDocsExamined": estimate as i64,
+        };
+        result.extend(cursor.explain()?);
+        Ok(result)
+    }
+
+    /// The filters a write runs with. The writes scan the collection,
+    /// so a hinted write first reads the `_id` of its documents through
+    /// the index, then writes them one by one. Likewise, a sorted write
+    /// with a limit first reads the `_id` of the first documents in order.
+    fn write_filters(
+        &self,
+        col_spec: &CollectionSpecification,
+        query: Document,
+        plan: QueryPlan,
+        sort: Option<&Document>,
+        max: Option<u64>,
+        txn: &TransactionInner,
+    ) -> Result<(Vec<Document>, QueryPlan)> {
+        // all the documents are written, whatever their order
+        let sort = sort.filter(|_| max.is_some());
+        if plan.hint.is_none() && sort.is_none() {
+            return Ok((vec![query], plan));
+        }
+
+        let max = max.unwrap_or(u64::MAX);
+        let subprogram = match sort {
+            Some(sort) => {
+                let pipeline = vec![
+                    doc! { "$match": query },
+                    doc! { "$sort": sort.clone() },
+                    doc! { "$limit": max.min(i64::MAX as u64) as i64 },
+                ];
+                SubProgram::compile_aggregate_with_plan(col_spec, pipeline, true, &plan)?
+            }
+            None => SubProgram::compile_query_with_plan(col_spec, &query, true, &plan)?,
+        };
+        let mut vm = VM::new(txn.clone(), subprogram, self.metrics.clone());
+        DatabaseInner::apply_expiry(&mut vm, col_spec);
+        self.apply_bloom_filter(&mut vm, col_spec)?;
+        let mut cursor = ClientCursor::<Document>::new(vm);
+
+        let mut filters = Vec::new();
+        while (filters.len() as u64) < max && cursor.advance()? {
+            let id = cursor.get().as_document()
+                .and_then(|doc| doc.get("_id"))
+                .cloned()
+                .expect("internal: document must have an _id");
+            filters.push(doc! { "_id": id });
+        }
+
+        Ok((filters, QueryPlan::default()))
+    }
+
+    /// Delete the expired documents of all the collections declaring
+    /// an expiry field or a TTL index, return the number of deleted documents.
+    pub fn remove_expired(&self) -> Result<u64> {
+        let mut txn = self.start_transaction()?;
+        txn.set_auto_commit(false);
+        let deleted_count = self.remove_expired_with_session(&txn)?;
+        txn.commit()?;
+        Ok(deleted_count)
+    }
+
+    pub(crate) fn remove_expired_with_session(&self, txn: &TransactionInner) -> Result<u64> {
+        let names = self.list_collection_names_with_session(txn)?;
+        let mut deleted_count = 0;
+
+        for name in names {
+            let col_spec = self.internal_get_collection_id_by_name(txn, &name)?;
+            let expiry = match Expiry::reaper(&col_spec) {
+                Some(expiry) => expiry,
+                None => continue,
+            };
+            let col_name = col_spec.name();
+            let subprogram = SubProgram::compile_delete_all(
+                &col_spec,
+                col_name,
+                true,
+            )?;
+
+            let mut vm = VM::new(
+                txn.clone(),
+                subprogram,
+                self.metrics.clone(),
+            );
+            vm.set_expiry(expiry);
+            self.track_vm(&mut vm, "delete", col_name, None);
+            self.observe_writes(&mut vm, col_name)?;
+            vm.execute()?;
+
+            deleted_count += vm.r2 as u64;
+        }
+
+        Ok(deleted_count)
+    }
+
+    /// Apply the changes to the validation of a collection,
+    /// an empty validator removes the validation.
+    fn merge_validation(
+        current: Option<ValidationInfo>,
+        validator: Option<Document>,
+        level: Option<ValidationLevel>,
+        action: Option<ValidationAction>,
+    ) -> Result<Option<ValidationInfo>> {
+        let validator = match (validator, &current) {
+            (Some(validator), _) => validator,
+            (None, Some(current)) => current.validator.clone(),
+            (None, None) => Document::new(),
+        };
+        if validator.is_empty() {
+            return Ok(None);
+        }
+        ValidationInfo::check_validator(&validator)?;
+        Ok(Some(ValidationInfo {
+            validator,
+            validation_level: level
+                .or(current.as_ref().map(|current| current.validation_level))
+                .unwrap_or_default(),
+            validation_action: action
+                .or(current.as_ref().map(|current| current.validation_action))
+                .unwrap_or_default(),
+        }))
+    }
+
     #[inline]
     pub fn create_collection_internal(&self, name: &str, txn: &TransactionInner) -> Result<CollectionSpecification> {
         let meta = self.internal_create_collection(txn, name, &self.node_id)?;
@@ -197,16 +1135,56 @@ impl DatabaseInner {
         Ok(ClientCursor::new(vm))
     }
 
-    pub fn create_index(&self, col_name: &str, index: IndexModel, txn: &TransactionInner) -> Result<()> {
+    /// Create the index, return its name. Creating an index identical to an existing one
+    /// returns the name of the existing index.
+    pub fn create_index(&self, col_name: &str, index: IndexModel, txn: &TransactionInner) -> Result<String> {
         DatabaseInner::validate_col_name(col_name)?;
 
-        self.internal_create_index(txn, col_name, index)?;
+        let (index_name, created) = self.define_index(txn, col_name, index)?;
+        if let Some(index_info) = created {
+            self.build_index(
+                txn,
+                col_name,
+                index_name.as_str(),
+                &index_info,
+            )?;
+        }
 
-        Ok(())
+        Ok(index_name)
     }
 
-    fn internal_create_index(&self, txn: &TransactionInner, col_name: &str, index: IndexModel) -> Result<()> {
-        if index.keys.len() != 1 {
+    /// Create the indexes, return their names. The new indexes are built together
+    /// by one scan of the collection, on up to `index_build_parallelism` threads.
+    pub fn create_indexes(&self, col_name: &str, indexes: Vec<IndexModel>, txn: &TransactionInner) -> Result<Vec<String>> {
+        DatabaseInner::validate_col_name(col_name)?;
+
+        let mut names = Vec::with_capacity(indexes.len());
+        let mut created = Vec::<(String, IndexInfo)>::new();
+        for index in indexes {
+            let (index_name, index_info) = self.define_index(txn, col_name, index)?;
+            if let Some(index_info) = index_info {
+                created.push((index_name.clone(), index_info));
+            }
+            names.push(index_name);
+        }
+
+        let created = created.iter()
+            .map(|(index_name, index_info)| (index_name.as_str(), index_info))
+            .collect::<Vec<(&str, &IndexInfo)>>();
+        IndexBuilder::rebuild_many(txn, col_name, &created, self.config.index_build_parallelism)?;
+
+        Ok(names)
+    }
+
+    /// Add the index to the specification of the collection, return its name
+    /// and its definition if it does not exist yet and must be built.
+    fn define_index(&self, txn: &TransactionInner, col_name: &str, index: IndexModel) -> Result<(String, Option<IndexInfo>)> {
+        if let Some(col_spec) = self.get_collection_meta_by_name_advanced_auto(col_name, false, txn)? {
+            DatabaseInner::check_not_view(&col_spec)?;
+        }
+        // the geo and vector indexes are on a single field
+        let is_special = |value: &Bson| matches!(value.as_str(), Some("2dsphere" | "vector"));
+        if index.keys.is_empty() || (index.keys.len() > 1 && index.keys.values().any(is_special)) {
             return Err(Error::OnlySupportSingleFieldIndexes(Box::new(index.keys)));
         }
 
@@ -217,22 +1195,162 @@ impl DatabaseInner {
 
         let (key, value) = first_tuple;
 
-        self.create_single_index(txn, col_name, key.as_str(), value, options)
+        if value.as_str() == Some("2dsphere") {
+            return self.define_geo_index(txn, col_name, key.as_str(), options);
+        }
+        if value.as_str() == Some("vector") {
+            return self.define_vector_index(txn, col_name, key.as_str(), options);
+        }
+
+        self.define_single_index(txn, col_name, &tuples, options)
     }
 
-    fn create_single_index(
+    /// Add a `2dsphere` index to the collection, after checking the values
+    /// of the field in the existing documents. There is nothing to build.
+    fn define_geo_index(
         &self,
         txn: &TransactionInner,
         col_name: &str,
         key: &str,
-        order: &Bson,
         options: Option<&IndexOptions>,
-    ) -> Result<()> {
-        if !DatabaseInner::is_num_1(order) {
-            return Err(Error::OnlySupportsAscendingOrder(key.to_string()));
+    ) -> Result<(String, Option<IndexInfo>)> {
+        if options.is_some_and(|options| options.unique == Some(true)) {
+            return Err(Error::UnsupportedIndexOption("unique".to_string()));
+        }
+        if options.is_some_and(|options| options.partial_filter_expression.is_some()) {
+            return Err(Error::UnsupportedIndexOption("partialFilterExpression".to_string()));
+        }
+        let index_name = match options.and_then(|options| options.name.as_ref()) {
+            Some(name) => {
+                DatabaseInner::validate_index_name(name)?;
+                name.clone()
+            }
+            None => key.replace('.', "_") + "_2dsphere",
+        };
+
+        let mut collection_spec = match self.internal_get_collection_id_by_name(txn, col_name) {
+            Ok(spec) => spec,
+            Err(Error::CollectionNotFound(_)) => {
+                let uuid = uuid::Uuid::now_v1(&self.node_id);
+                CollectionSpecification::new(col_name.to_string(), uuid)
+            }
+            Err(err) => return Err(err),
+        };
+        if let Some(field) = collection_spec.geo_indexes.get(&index_name) {
+            if field != key {
+                return Err(Error::IndexAlreadyExists(index_name));
+            }
+            return Ok((index_name, None));
+        }
+        if collection_spec.indexes.contains_key(&index_name) {
+            return Err(Error::IndexAlreadyExists(index_name));
+        }
+
+        let fields = [key.to_string()];
+        let mut cursor = Cursor::new_with_str_prefix(col_name.to_string(), txn.rocksdb_txn.new_iterator())?;
+        cursor.reset()?;
+        while cursor.has_next() {
+            let doc = bson::from_slice::<Document>(cursor.copy_data()?.as_ref())?;
+            crate::geo::check_fields(&fields, &doc)?;
+            cursor.next()?;
+        }
+
+        collection_spec.geo_indexes.insert(index_name.clone(), key.to_string());
+        DatabaseInner::update_collection_spec(col_name, &collection_spec, txn)?;
+
+        Ok((index_name, None))
+    }
+
+    /// Add a `"vector"` index to the collection, after checking the values
+    /// of the field in the existing documents, and build its graph in memory.
+    fn define_vector_index(
+        &self,
+        txn: &TransactionInner,
+        col_name: &str,
+        key: &str,
+        options: Option<&IndexOptions>,
+    ) -> Result<(String, Option<IndexInfo>)> {
+        if options.is_some_and(|options| options.unique == Some(true)) {
+            return Err(Error::UnsupportedIndexOption("unique".to_string()));
+        }
+        if options.is_some_and(|options| options.partial_filter_expression.is_some()) {
+            return Err(Error::UnsupportedIndexOption("partialFilterExpression".to_string()));
+        }
+        let dimensions = match options.and_then(|options| options.dimensions) {
+            Some(dimensions) if dimensions > 0 => dimensions,
+            _ => return Err(Error::InvalidVector("a vector index requires the number of dimensions".to_string())),
+        };
+        let info = VectorIndexInfo {
+            field: key.to_string(),
+            dimensions,
+            similarity: options.and_then(|options| options.similarity).unwrap_or_default(),
+        };
+        let index_name = match options.and_then(|options| options.name.as_ref()) {
+            Some(name) => {
+                DatabaseInner::validate_index_name(name)?;
+                name.clone()
+            }
+            None => key.replace('.', "_") + "_vector",
+        };
+
+        let mut collection_spec = match self.internal_get_collection_id_by_name(txn, col_name) {
+            Ok(spec) => spec,
+            Err(Error::CollectionNotFound(_)) => {
+                let uuid = uuid::Uuid::now_v1(&self.node_id);
+                CollectionSpecification::new(col_name.to_string(), uuid)
+            }
+            Err(err) => return Err(err),
+        };
+        if let Some(existing) = collection_spec.vector_indexes.get(&index_name) {
+            if *existing != info {
+                return Err(Error::IndexAlreadyExists(index_name));
+            }
+            return Ok((index_name, None));
         }
+        if collection_spec.indexes.contains_key(&index_name) || collection_spec.geo_indexes.contains_key(&index_name) {
+            return Err(Error::IndexAlreadyExists(index_name));
+        }
+
+        let mut cursor = Cursor::new_with_str_prefix(col_name.to_string(), txn.rocksdb_txn.new_iterator())?;
+        cursor.reset()?;
+        while cursor.has_next() {
+            let doc = bson::from_slice::<Document>(cursor.copy_data()?.as_ref())?;
+            crate::vector::check_fields([&info], &doc)?;
+            cursor.next()?;
+        }
+
+        self.vector_indexes.build(txn, col_name, &index_name, &info)?;
+        collection_spec.vector_indexes.insert(index_name.clone(), info);
+        DatabaseInner::update_collection_spec(col_name, &collection_spec, txn)?;
 
-        let index_name = DatabaseInner::make_index_name(key, 1, options)?;
+        Ok((index_name, None))
+    }
+
+    /// Add an index on one field, or a compound index on several fields,
+    /// such as `{ "category": 1, "tags": 1 }`.
+    fn define_single_index(
+        &self,
+        txn: &TransactionInner,
+        col_name: &str,
+        keys: &[(&String, &Bson)],
+        options: Option<&IndexOptions>,
+    ) -> Result<(String, Option<IndexInfo>)> {
+        for (key, order) in keys {
+            if !DatabaseInner::is_num_1(order) {
+                return Err(Error::OnlySupportsAscendingOrder(key.to_string()));
+            }
+        }
+        if let Some(filter) = options.and_then(|options| options.partial_filter_expression.as_ref()) {
+            PartialFilter::parse(filter)?;
+        }
+        // the documents expire by the date of a single field, whatever the filter of the index
+        let partial = options.is_some_and(|options| options.partial_filter_expression.is_some());
+        if (keys.len() > 1 || partial) && options.is_some_and(|options| options.expire_after_secs.is_some()) {
+            return Err(Error::UnsupportedIndexOption("expireAfterSeconds".to_string()));
+        }
+
+        let key_names = keys.iter().map(|(key, _)| key.as_str()).collect::<Vec<&str>>();
+        let index_name = DatabaseInner::make_index_name(&key_names, 1, options)?;
 
         let test_collection_spec = self.internal_get_collection_id_by_name(txn, col_name);
         let mut collection_spec = match test_collection_spec {
@@ -246,15 +1364,22 @@ impl DatabaseInner {
             }
         };
 
-        if collection_spec.indexes.get(&index_name).is_some() {
-            return Ok(())
-        }
-
-        let index_info = IndexInfo::single_index(
-            key.to_string(),
-            1,
+        let index_info = IndexInfo::new(
+            key_names.iter().map(|key| (key.to_string(), 1)).collect(),
             options.cloned(),
         );
+        if let Some(existing) = collection_spec.indexes.get(&index_name) {
+            if !existing.same_definition(&index_info) {
+                return Err(Error::IndexAlreadyExists(index_name));
+            }
+            return Ok((index_name, None));
+        }
+        let identical = collection_spec.indexes.iter()
+            .find(|(_, existing)| existing.same_definition(&index_info));
+        if let Some((name, _)) = identical {
+            return Ok((name.clone(), None));
+        }
+
         collection_spec.indexes.insert(index_name.clone(), index_info.clone());
 
         DatabaseInner::update_collection_spec(
@@ -263,12 +1388,7 @@ impl DatabaseInner {
             txn,
         )?;
 
-        self.build_index(
-            txn,
-            col_name,
-            index_name.as_str(),
-            &index_info,
-        )
+        Ok((index_name, Some(index_info)))
     }
 
     fn build_index(
@@ -308,6 +1428,17 @@ impl DatabaseInner {
             }
         };
 
+        if collection_spec.geo_indexes.shift_remove(index_name).is_some() {
+            return DatabaseInner::update_collection_spec(col_name, &collection_spec, txn);
+        }
+        if collection_spec.vector_indexes.shift_remove(index_name).is_some() {
+            let vector_indexes = self.vector_indexes.clone();
+            let (col_name, index_name) = (col_name.to_string(), index_name.to_string());
+            DatabaseInner::update_collection_spec(&col_name, &collection_spec, txn)?;
+            txn.on_commit(Box::new(move || vector_indexes.remove_index(&col_name, &index_name)));
+            return Ok(());
+        }
+
         let index_info = collection_spec.indexes.get(index_name);
         if index_info.is_none() {
             return Ok(());
@@ -325,6 +1456,9 @@ impl DatabaseInner {
         builder.execute(IndexHelperOperation::Delete)?;
 
         collection_spec.indexes.shift_remove(index_name);
+        if let Some(statistics) = &mut collection_spec.statistics {
+            statistics.indexes.shift_remove(index_name);
+        }
 
         DatabaseInner::update_collection_spec(
             col_name,
@@ -344,7 +1478,11 @@ impl DatabaseInner {
             Err(err) => return Err(err),
         };
 
-        Ok(collection_spec.indexes.keys().cloned().collect())
+        Ok(collection_spec.indexes.keys()
+            .chain(collection_spec.geo_indexes.keys())
+            .chain(collection_spec.vector_indexes.keys())
+            .cloned()
+            .collect())
     }
 
     pub fn describe_collection_index(&self, col_name: &str, index_name: &str, txn: &TransactionInner) -> Result<Option<IndexInfo>> {
@@ -360,6 +1498,30 @@ impl DatabaseInner {
         Ok(collection_spec.indexes.get(index_name).cloned())
     }
 
+    /// Gather the statistics of the indexed fields of the collection,
+    /// and keep them in the collection for the query planner.
+    pub(crate) fn analyze(&self, col_name: &str, txn: &TransactionInner) -> Result<CollectionStatistics> {
+        DatabaseInner::validate_col_name(col_name)?;
+        let mut collection_spec = self.internal_get_collection_id_by_name(txn, col_name)?;
+
+        let mut indexes = IndexMap::new();
+        for (index_name, index_info) in &collection_spec.indexes {
+            let (field, _order) = index_info.keys.iter().next().unwrap();
+            let field_statistics = IndexHelper::field_statistics(txn, col_name, index_name, field)?;
+            indexes.insert(index_name.clone(), field_statistics);
+        }
+        let statistics = CollectionStatistics {
+            documents: self.count(col_name, txn)?,
+            indexes,
+            analyzed_at: bson_datetime_now(),
+        };
+
+        collection_spec.statistics = Some(statistics.clone());
+        DatabaseInner::update_collection_spec(col_name, &collection_spec, txn)?;
+
+        Ok(statistics)
+    }
+
     fn update_collection_spec(col_name: &str, collection_spec: &CollectionSpecification, txn: &TransactionInner) -> Result<()> {
         let stacked_key = crate::utils::bson::stacked_key(&[
             Bson::String(TABLE_META_PREFIX.to_string()),
@@ -373,7 +1535,21 @@ impl DatabaseInner {
         Ok(())
     }
 
-    fn make_index_name(key: &str, order: i32, index_options: Option<&IndexOptions>) -> Result<String> {
+    fn check_not_view(col_spec: &CollectionSpecification) -> Result<()> {
+        if col_spec.view.is_some() {
+            return Err(Error::ReadOnlyView(col_spec.name().to_string()));
+        }
+        Ok(())
+    }
+
+    fn check_not_capped(col_spec: &CollectionSpecification) -> Result<()> {
+        if col_spec.capped.is_some() {
+            return Err(Error::CappedCollection(format!("cannot delete documents from '{}'", col_spec.name())));
+        }
+        Ok(())
+    }
+
+    fn make_index_name(keys: &[&str], order: i32, index_options: Option<&IndexOptions>) -> Result<String> {
         if let Some(options) = index_options {
             if let Some(name) = &options.name {
                 DatabaseInner::validate_index_name(name)?;
@@ -381,13 +1557,11 @@ impl DatabaseInner {
             }
         }
 
-        let mut index_name = key.to_string().replace(".", "_");
+        let names = keys.iter()
+            .map(|key| format!("{}_{}", key.replace('.', "_"), order))
+            .collect::<Vec<String>>();
 
-        index_name += "_";
-        let num_str = order.to_string();
-        index_name += &num_str;
-
-        Ok(index_name)
+        Ok(names.join("_"))
     }
 
     #[inline]
@@ -395,8 +1569,13 @@ impl DatabaseInner {
         matches!(val, Bson::Int32(1) | Bson::Int64(1))
     }
 
+    /// Generate an ObjectId as the ones given to the inserted documents without `_id`.
+    pub(crate) fn new_object_id(&self) -> ObjectId {
+        self.object_ids.generate()
+    }
+
     #[inline]
-    fn fix_doc(mut doc: Document) -> Document {
+    fn fix_doc(&self, mut doc: Document) -> Document {
         if let Some(id) = doc.get(meta_doc_key::ID) {
             // If the id type is not null, the document is ok
             if id.as_null().is_none() {
@@ -404,17 +1583,27 @@ impl DatabaseInner {
             }
         }
 
-        let new_oid = ObjectId::new();
+        let new_oid = self.new_object_id();
         doc.insert::<String, Bson>(meta_doc_key::ID.into(), new_oid.into());
         doc
     }
 
+    /// The name of a collection, or the name `<database>.<collection>` of a collection of a logical database.
     fn validate_col_name(col_name: &str) -> Result<()> {
-        for ch in col_name.chars() {
+        let (database, name) = crate::namespace::split_name(col_name);
+        if database.is_some_and(str::is_empty) || name.is_empty() {
+            return Err(Error::IllegalCollectionName(col_name.to_string()))
+        }
+        for ch in name.chars() {
             if ch == '$' || ch == '\n' || ch == '\t' || ch == '\r' || ch == '.' {
                 return Err(Error::IllegalCollectionName(col_name.to_string()))
             }
         }
+        for ch in database.unwrap_or_default().chars() {
+            if ch == '$' || ch == '\n' || ch == '\t' || ch == '\r' {
+                return Err(Error::IllegalCollectionName(col_name.to_string()))
+            }
+        }
 
         Ok(())
     }
@@ -432,7 +1621,9 @@ impl DatabaseInner {
     pub fn insert_one(&self, col_name: &str, doc: Document, txn: &TransactionInner) -> Result<InsertOneResult> {
         DatabaseInner::validate_col_name(col_name)?;
 
+        let start = Instant::now();
         let changed = self.insert_one_internal(txn, col_name, doc, &self.node_id)?;
+        self.metrics.record_operation("insert", start.elapsed(), 0, 0);
 
         Ok(changed)
     }
@@ -440,14 +1631,31 @@ impl DatabaseInner {
     fn insert_one_internal(&self, txn: &TransactionInner, col_name: &str, doc: Document, node_id: &[u8; 6]) -> Result<InsertOneResult> {
         let col_meta = self.get_collection_meta_by_name_advanced(txn, col_name, true, node_id)?
             .expect("internal: meta must exist");
-        let (result, _) = self.insert_one_with_meta(txn, col_meta, doc)?;
-        Ok(result)
+        self.insert_one_with_meta(txn, &col_meta, doc, true)
     }
 
-    /// Insert one item with the collection spec
-    /// return the new spec for the outside to do the following operation
-    fn insert_one_with_meta(&self, txn: &TransactionInner, col_spec: CollectionSpecification, doc: Document) -> Result<(InsertOneResult, CollectionSpecification)> {
-        let doc  = DatabaseInner::fix_doc(doc);
+    /// Insert one item with the collection spec,
+    /// the indexes are left to the caller if `maintain_indexes` is false.
+    fn insert_one_with_meta(&self, txn: &TransactionInner, col_spec: &CollectionSpecification, doc: Document, maintain_indexes: bool) -> Result<InsertOneResult> {
+        DatabaseInner::check_not_view(col_spec)?;
+        let mut doc  = self.fix_doc(doc);
+        if let Some(defaults) = &col_spec.defaults {
+            crate::defaults::apply(txn, col_spec.name(), defaults, &mut doc)?;
+        }
+
+        let hooks = self.hooks.get(col_spec.name());
+        if let Some(hooks) = &hooks {
+            hooks.run_pre(HookEvent::Insert, &mut doc)?;
+        }
+        crate::strictness::check(self.config.bson_strictness, col_spec.name(), &mut doc)?;
+        if let Some(validation) = &col_spec.validation {
+            validation.validate(col_spec.name(), None, &doc)?;
+        }
+        crate::geo::check_fields(col_spec.geo_indexes.values(), &doc)?;
+        crate::vector::check_fields(col_spec.vector_indexes.values(), &doc)?;
+        if let Some(hooks) = &hooks {
+            hooks.validate(&doc)?;
+        }
 
         let pkey = doc.get("_id").unwrap();
 
@@ -457,18 +1665,45 @@ impl DatabaseInner {
         ])?;
 
         let doc_buf = bson::to_vec(&doc)?;
+        self.document_limits(col_spec).check(col_spec.name(), &doc, doc_buf.len())?;
 
         txn.put(
             stacked_key.as_ref(),
             &doc_buf,
         )?;
 
-        self.try_insert_index(txn, &col_spec, &doc, pkey)?;
+        if maintain_indexes {
+            self.try_insert_index(txn, col_spec, &doc, pkey)?;
+        }
+
+        if col_spec.bloom_filter_field.is_some() {
+            if let Some(bloom) = self.blooms.get(col_spec.name()) {
+                bloom.insert_doc(&doc);
+            }
+        }
+        if col_spec.fuzzy_index_field.is_some() {
+            if let Some(index) = self.fuzzy_indexes.get(col_spec.name()) {
+                index.insert_doc(&doc);
+            }
+        }
+        for index in self.vector_indexes.indexes(col_spec) {
+            index.insert_doc(&doc);
+        }
+
+        if let Some(capped) = &col_spec.capped {
+            crate::capped::record_insert(txn, col_spec, capped, pkey, doc_buf.len())?;
+        }
+
+        if let Some(hooks) = &hooks {
+            hooks.defer_post(txn, HookEvent::Insert, &doc);
+        }
+        if let Some(publisher) = self.change_publisher(col_spec.name()) {
+            publisher.defer_insert(txn, &doc)?;
+        }
 
-        Ok((
-            InsertOneResult { inserted_id: pkey.clone() },
-            col_spec
-        ))
+        self.maintain_views(col_spec.name(), Some(std::slice::from_ref(pkey)), txn)?;
+
+        Ok(InsertOneResult { inserted_id: pkey.clone() })
     }
 
     fn try_insert_index(&self, txn: &TransactionInner, col_spec: &CollectionSpecification, doc: &Document, pkey: &Bson) -> Result<()> {
@@ -485,36 +1720,113 @@ impl DatabaseInner {
         &self,
         col_name: &str,
         docs: impl IntoIterator<Item = impl Borrow<T>>,
+        options: &InsertManyOptions,
         txn: &TransactionInner,
     ) -> Result<InsertManyResult> {
         DatabaseInner::validate_col_name(col_name)?;
 
-        let result = self.insert_many_internal(txn, col_name, docs, &self.node_id)?;
+        let start = Instant::now();
+        let result = self.insert_many_internal(txn, col_name, docs, options.is_ordered(), &self.node_id)?;
+        self.metrics.record_operation("insert", start.elapsed(), 0, 0);
 
         Ok(result)
     }
 
-    fn insert_many_internal<T: Serialize>(
+    fn insert_many_internal<T: Serialize>(
+        &self,
+        txn: &TransactionInner,
+        col_name: &str,
+        docs: impl IntoIterator<Item = impl Borrow<T>>,
+        ordered: bool,
+        node_id: &[u8; 6],
+    ) -> Result<InsertManyResult> {
+        let col_spec = self.get_collection_meta_by_name_advanced(txn, col_name, true, node_id)?
+            .expect("internal: meta must exist");
+        let mut inserted_ids: HashMap<usize, Bson> = HashMap::new();
+        let mut write_errors: HashMap<usize, Error> = HashMap::new();
+
+        for (counter, item) in docs.into_iter().enumerate() {
+            if ordered {
+                let doc = bson::to_document(item.borrow())?;
+                let insert_one_result = self.insert_one_with_meta(txn, &col_spec, doc, true)?;
+                inserted_ids.insert(counter, insert_one_result.inserted_id);
+                continue;
+            }
+
+            // a failing document must not leave its data or index entries behind
+            txn.set_savepoint();
+            let attempt = bson::to_document(item.borrow())
+                .map_err(Error::from)
+                .and_then(|doc| self.insert_one_with_meta(txn, &col_spec, doc, true));
+            match attempt {
+                Ok(insert_one_result) => {
+                    inserted_ids.insert(counter, insert_one_result.inserted_id);
+                }
+                Err(Error::OperationKilled) => return Err(Error::OperationKilled),
+                Err(err) => {
+                    txn.rollback_to_savepoint()?;
+                    write_errors.insert(counter, err);
+                }
+            }
+        }
+
+        Ok(InsertManyResult {
+            inserted_ids,
+            write_errors,
+        })
+    }
+
+    /// Insert the documents without maintaining the indexes,
+    /// which are rebuilt from the sorted entries once all the documents are written.
+    pub fn bulk_load<T: Serialize>(
         &self,
-        txn: &TransactionInner,
         col_name: &str,
         docs: impl IntoIterator<Item = impl Borrow<T>>,
-        node_id: &[u8; 6],
+        txn: &TransactionInner,
     ) -> Result<InsertManyResult> {
-        let mut col_spec = self.get_collection_meta_by_name_advanced(txn, col_name, true, node_id)?
+        DatabaseInner::validate_col_name(col_name)?;
+
+        let start = Instant::now();
+        let col_spec = self.get_collection_meta_by_name_advanced(txn, col_name, true, &self.node_id)?
             .expect("internal: meta must exist");
         let mut inserted_ids: HashMap<usize, Bson> = HashMap::new();
 
         for (counter, item) in docs.into_iter().enumerate() {
             let doc = bson::to_document(item.borrow())?;
-            let (insert_one_result, new_col_spec) = self.insert_one_with_meta(txn, col_spec, doc)?;
+            let insert_one_result = self.insert_one_with_meta(txn, &col_spec, doc, false)?;
             inserted_ids.insert(counter, insert_one_result.inserted_id);
+        }
+
+        let indexes = col_spec.indexes.iter()
+            .map(|(index_name, index_info)| (index_name.as_str(), index_info))
+            .collect::<Vec<(&str, &IndexInfo)>>();
+        IndexBuilder::rebuild_many(txn, col_name, &indexes, self.config.index_build_parallelism)?;
+        self.metrics.record_operation("insert", start.elapsed(), 0, 0);
+
+        Ok(InsertManyResult {
+            inserted_ids,
+            write_errors: HashMap::new(),
+        })
+    }
+
+    #[cfg(feature = "parquet")]
+    pub fn import_parquet(&self, col_name: &str, path: &Path, txn: &TransactionInner) -> Result<InsertManyResult> {
+        DatabaseInner::validate_col_name(col_name)?;
 
-            col_spec = new_col_spec;
+        let reader = ParquetDocumentReader::open(path, DEFAULT_BATCH_SIZE)?;
+        let mut inserted_ids: HashMap<usize, Bson> = HashMap::new();
+
+        for docs in reader {
+            let result = self.insert_many_internal::<Document>(txn, col_name, docs?, true, &self.node_id)?;
+            let offset = inserted_ids.len();
+            for (index, id) in result.inserted_ids {
+                inserted_ids.insert(offset + index, id);
+            }
         }
 
         Ok(InsertManyResult {
             inserted_ids,
+            write_errors: HashMap::new(),
         })
     }
 
@@ -524,17 +1836,25 @@ impl DatabaseInner {
         query: Option<Document>,
         txn: TransactionInner,
     ) -> Result<ClientCursor<T>> {
+        let comment = query.as_ref().and_then(|query| query.get("$comment")).cloned();
         let subprogram = match query {
-            Some(query) => SubProgram::compile_query(
-                col_spec,
-                &query,
-                true
-            ),
+            Some(query) => {
+                let plan = self.plan_query(col_spec, &query, QueryPlan::default(), &txn)?;
+                SubProgram::compile_query_with_plan(col_spec, &query, true, &plan)
+            }
             None => SubProgram::compile_query_all(col_spec, true),
         }?;
 
-        let handle = self.make_handle(subprogram, txn)?;
-        Ok(handle)
+        let mut vm = VM::new(
+            txn,
+            subprogram,
+            self.metrics.clone(),
+        );
+        self.track_vm_with_comment(&mut vm, "count", col_spec.name(), None, comment.as_ref());
+        DatabaseInner::apply_expiry(&mut vm, col_spec);
+        self.apply_bloom_filter(&mut vm, col_spec)?;
+
+        Ok(ClientCursor::new(vm))
     }
 
     pub fn update_one(
@@ -591,26 +1911,74 @@ impl DatabaseInner {
 
         let result = match &meta_opt {
             Some(col_spec) => {
-                let subprogram = SubProgram::compile_update(
-                    col_spec,
-                    &query,
-                    &update,
-                    true,
-                    is_many,
-                )?;
-
-                let mut vm = VM::new(
-                    txn.clone(),
-                    subprogram,
-                    self.metrics.clone(),
-                );
-                vm.execute()?;
-
-                // vm.r2 as u64
-                UpdateResult {
-                    matched_count: vm.r2 as u64,
-                    modified_count: vm.r4 as u64,
+                DatabaseInner::check_not_view(col_spec)?;
+                let plan = DatabaseInner::query_plan(col_spec, options.hint.as_ref(), options.collation)?;
+                let limit = options.limit.filter(|limit| is_many && *limit > 0);
+                let max = if is_many { limit } else { Some(1) };
+                let (filters, plan) = self.write_filters(col_spec, query.clone(), plan, options.sort.as_ref(), max, txn)?;
+                let mut result = UpdateResult::default();
+                let has_views = !self.views.views_of(col_name).is_empty();
+                let mut written_ids = Vec::new();
+
+                for filter in &filters {
+                    let subprogram = SubProgram::compile_update(
+                        col_spec,
+                        filter,
+                        &update,
+                        true,
+                        is_many,
+                        &plan,
+                    )?;
+
+                    let mut vm = VM::new(
+                        txn.clone(),
+                        subprogram,
+                        self.metrics.clone(),
+                    );
+                    self.track_vm(&mut vm, "update", col_name, Some(&query));
+                    self.observe_writes(&mut vm, col_name)?;
+                    if let Some(validation) = &col_spec.validation {
+                        vm.set_validation(col_name, validation.clone());
+                    }
+                    if col_spec.capped.is_some() {
+                        vm.set_capped();
+                    }
+                    DatabaseInner::apply_expiry(&mut vm, col_spec);
+                    self.apply_bloom_filter(&mut vm, col_spec)?;
+                    self.apply_fuzzy_index(&mut vm, col_spec, Some(&query));
+                    let limits = self.document_limits(col_spec);
+                    if !limits.is_unlimited() {
+                        vm.set_limits(col_name, limits);
+                    }
+                    if self.config.bson_strictness != BsonStrictness::Lenient {
+                        vm.set_strictness(col_name, self.config.bson_strictness);
+                    }
+                    if !col_spec.geo_indexes.is_empty() {
+                        vm.set_geo_fields(col_spec.geo_indexes.values().cloned().collect());
+                    }
+                    if !col_spec.vector_indexes.is_empty() {
+                        vm.set_vector_indexes(
+                            col_spec.vector_indexes.values().cloned().collect(),
+                            self.vector_indexes.indexes(col_spec),
+                        );
+                    }
+                    if let Some(limit) = limit {
+                        vm.set_write_limit(limit - result.matched_count);
+                    }
+                    if has_views {
+                        vm.track_written_ids();
+                    }
+                    vm.execute()?;
+
+                    result.matched_count += vm.r2 as u64;
+                    result.modified_count += vm.r4 as u64;
+                    written_ids.extend(vm.take_written_ids());
+                }
+                if has_views {
+                    self.maintain_views(col_name, Some(&written_ids), txn)?;
                 }
+
+                result
             },
             None => UpdateResult::default(),
         };
@@ -651,6 +2019,141 @@ impl DatabaseInner {
 
         Ok(())
     }
+    /// Rename a collection with its documents, its indexes, its options and its validators.
+    pub fn rename_collection(&self, old_name: &str, new_name: &str, txn: &TransactionInner) -> Result<()> {
+        DatabaseInner::validate_col_name(old_name)?;
+        DatabaseInner::validate_col_name(new_name)?;
+
+        let mut col_spec = self.internal_get_collection_id_by_name(txn, old_name)?;
+        match self.internal_get_collection_id_by_name(txn, new_name) {
+            Ok(_) => return Err(Error::CollectionAlreadyExits(new_name.to_string())),
+            Err(Error::CollectionNotFound(_)) => (),
+            Err(err) => return Err(err),
+        }
+
+        let old_col = Bson::String(old_name.to_string());
+        let new_col = Bson::String(new_name.to_string());
+        txn.move_prefix(
+            crate::utils::bson::stacked_key([&old_col])?,
+            &crate::utils::bson::stacked_key([&new_col])?,
+        )?;
+        let b_index_prefix = Bson::String(crate::index::INDEX_PREFIX.to_string());
+        txn.move_prefix(
+            crate::utils::bson::stacked_key([&b_index_prefix, &old_col])?,
+            &crate::utils::bson::stacked_key([&b_index_prefix, &new_col])?,
+        )?;
+        if col_spec.capped.is_some() {
+            crate::capped::rename(txn, old_name, new_name)?;
+        }
+        crate::defaults::rename(txn, old_name, new_name)?;
+
+        self.delete_collection_meta(old_name, txn)?;
+        col_spec._id = new_name.to_string();
+        DatabaseInner::update_collection_spec(new_name, &col_spec, txn)?;
+
+        if col_spec.bloom_filter_field.is_some() {
+            self.blooms.alias(old_name, new_name);
+            let blooms = self.blooms.clone();
+            let old_name = old_name.to_string();
+            txn.on_commit(Box::new(move || blooms.remove(&old_name)));
+        }
+        if col_spec.fuzzy_index_field.is_some() {
+            self.fuzzy_indexes.alias(old_name, new_name);
+            let fuzzy_indexes = self.fuzzy_indexes.clone();
+            let old_name = old_name.to_string();
+            txn.on_commit(Box::new(move || fuzzy_indexes.remove(&old_name)));
+        }
+        if !col_spec.vector_indexes.is_empty() {
+            self.vector_indexes.alias(old_name, new_name);
+            let vector_indexes = self.vector_indexes.clone();
+            let old_name = old_name.to_string();
+            txn.on_commit(Box::new(move || vector_indexes.remove(&old_name)));
+        }
+        self.rename_views(&col_spec, old_name, new_name, txn)?;
+
+        let hooks = self.hooks.clone();
+        let (old_name, new_name) = (old_name.to_string(), new_name.to_string());
+        txn.on_commit(Box::new(move || hooks.rename(&old_name, &new_name)));
+
+        Ok(())
+    }
+
+    /// Follow the renamed collection in the definitions of the views,
+    /// the renamed collection being a view or the source of views.
+    fn rename_views(&self, col_spec: &CollectionSpecification, old_name: &str, new_name: &str, txn: &TransactionInner) -> Result<()> {
+        let mut renamed = Vec::<(String, MaterializedViewInfo)>::new();
+        if let Some(info) = &col_spec.materialized_view {
+            renamed.push((new_name.to_string(), info.clone()));
+        }
+        let mut read_txn = txn.clone();
+        read_txn.set_auto_commit(false);
+        for meta in self.query_all_meta(&read_txn)? {
+            let mut view_spec = bson::from_document::<CollectionSpecification>(meta)?;
+            match &mut view_spec.view {
+                Some(info) if info.source == old_name => info.source = new_name.to_string(),
+                _ => continue,
+            }
+            DatabaseInner::update_collection_spec(&view_spec._id, &view_spec, txn)?;
+        }
+        for (view, _) in self.views.views_of(old_name) {
+            let mut view_spec = self.internal_get_collection_id_by_name(txn, &view)?;
+            let info = match &mut view_spec.materialized_view {
+                Some(info) => info,
+                None => continue,
+            };
+            info.source = new_name.to_string();
+            renamed.push((view.clone(), info.clone()));
+            DatabaseInner::update_collection_spec(&view, &view_spec, txn)?;
+        }
+        if renamed.is_empty() {
+            return Ok(());
+        }
+
+        let views = self.views.clone();
+        let old_name = old_name.to_string();
+        txn.on_commit(Box::new(move || {
+            views.unregister(&old_name);
+            for (name, info) in renamed {
+                views.register(&name, info);
+            }
+        }));
+        Ok(())
+    }
+
+    /// Copy the documents of a collection, and optionally its indexes, to a new collection,
+    /// return the number of copied documents.
+    pub fn clone_collection(&self, source: &str, target: &str, with_indexes: bool, txn: &TransactionInner) -> Result<u64> {
+        DatabaseInner::validate_col_name(source)?;
+        DatabaseInner::validate_col_name(target)?;
+
+        let source_spec = self.internal_get_collection_id_by_name(txn, source)?;
+        match self.internal_get_collection_id_by_name(txn, target) {
+            Ok(_) => return Err(Error::CollectionAlreadyExits(target.to_string())),
+            Err(Error::CollectionNotFound(_)) => (),
+            Err(err) => return Err(err),
+        }
+        let mut target_spec = self.create_collection_internal(target, txn)?;
+
+        let source_col = Bson::String(source.to_string());
+        let target_col = Bson::String(target.to_string());
+        let count = txn.copy_prefix(
+            crate::utils::bson::stacked_key([&source_col])?,
+            &crate::utils::bson::stacked_key([&target_col])?,
+        )?;
+
+        if with_indexes && !source_spec.indexes.is_empty() {
+            let b_index_prefix = Bson::String(crate::index::INDEX_PREFIX.to_string());
+            txn.copy_prefix(
+                crate::utils::bson::stacked_key([&b_index_prefix, &source_col])?,
+                &crate::utils::bson::stacked_key([&b_index_prefix, &target_col])?,
+            )?;
+            target_spec.indexes = source_spec.indexes;
+            DatabaseInner::update_collection_spec(target, &target_spec, txn)?;
+        }
+
+        Ok(count)
+    }
+
     pub fn drop_collection(&self, col_name: &str, txn: &TransactionInner) -> Result<()> {
         DatabaseInner::validate_col_name(col_name)?;
 
@@ -685,7 +2188,30 @@ impl DatabaseInner {
             vm.execute()?;
         } // Delete content end
 
+        if collection_spec.capped.is_some() {
+            crate::capped::drop(txn, col_name)?;
+        }
+        crate::defaults::drop(txn, col_name)?;
         self.delete_collection_meta(col_name, txn)?;
+        self.maintain_views(col_name, None, txn)?;
+        if collection_spec.materialized_view.is_some() {
+            let views = self.views.clone();
+            let col_name = col_name.to_string();
+            txn.on_commit(Box::new(move || views.unregister(&col_name)));
+        }
+        if !collection_spec.vector_indexes.is_empty() {
+            let vector_indexes = self.vector_indexes.clone();
+            let col_name = col_name.to_string();
+            txn.on_commit(Box::new(move || vector_indexes.remove(&col_name)));
+        }
+        // the pinned blocks keep the files of the documents
+        let rocksdb = self.rocksdb.downgrade();
+        let col_name = col_name.to_string();
+        txn.on_commit(Box::new(move || {
+            if let Some(rocksdb) = rocksdb.upgrade() {
+                let _ = rocksdb.unpin(&col_name);
+            }
+        }));
 
         Ok(())
     }
@@ -708,37 +2234,67 @@ impl DatabaseInner {
         Ok(())
     }
 
-    pub fn delete(&self, col_name: &str, query: Document, is_many: bool, txn: &TransactionInner) -> Result<usize> {
+    pub fn delete(&self, col_name: &str, query: Document, is_many: bool, options: &DeleteOptions, txn: &TransactionInner) -> Result<usize> {
         DatabaseInner::validate_col_name(col_name)?;
         let mut txn = txn.clone();
         txn.set_auto_commit(false);
-        let result = self.internal_delete_by_query(&txn, col_name, query, is_many)?;
+        let result = self.internal_delete_by_query(&txn, col_name, query, is_many, options)?;
         Ok(result)
     }
 
-    fn internal_delete_by_query(&self, txn: &TransactionInner, col_name: &str, query: Document, is_many: bool) -> Result<usize> {
+    fn internal_delete_by_query(&self, txn: &TransactionInner, col_name: &str, query: Document, is_many: bool, options: &DeleteOptions) -> Result<usize> {
         let col_spec = self.get_collection_meta_by_name_advanced(txn, col_name, true, &self.node_id)?;
         if col_spec.is_none() {
             return Ok(0);
         }
         let col_spec = col_spec.unwrap();
+        DatabaseInner::check_not_view(&col_spec)?;
+        DatabaseInner::check_not_capped(&col_spec)?;
+
+        let plan = DatabaseInner::query_plan(&col_spec, options.hint.as_ref(), options.collation)?;
+        let limit = options.limit.filter(|limit| is_many && *limit > 0);
+        let max = if is_many { limit } else { Some(1) };
+        let (filters, plan) = self.write_filters(&col_spec, query.clone(), plan, options.sort.as_ref(), max, txn)?;
+        let mut deleted_count = 0;
+        let has_views = !self.views.views_of(col_name).is_empty();
+        let mut written_ids = Vec::new();
+
+        for filter in &filters {
+            let subprogram = SubProgram::compile_delete(
+                &col_spec,
+                col_name,
+                Some(filter),
+                true,
+                is_many,
+                &plan,
+            )?;
 
-        let subprogram = SubProgram::compile_delete(
-            &col_spec,
-            col_name,
-            Some(&query),
-            true,
-            is_many,
-        )?;
+            let mut vm = VM::new(
+                txn.clone(),
+                subprogram,
+                self.metrics.clone(),
+            );
+            self.track_vm(&mut vm, "delete", col_name, Some(&query));
+            self.observe_writes(&mut vm, col_name)?;
+            DatabaseInner::apply_expiry(&mut vm, &col_spec);
+            self.apply_bloom_filter(&mut vm, &col_spec)?;
+            self.apply_fuzzy_index(&mut vm, &col_spec, Some(&query));
+            if let Some(limit) = limit {
+                vm.set_write_limit(limit - deleted_count as u64);
+            }
+            if has_views {
+                vm.track_written_ids();
+            }
+            vm.execute()?;
 
-        let mut vm = VM::new(
-            txn.clone(),
-            subprogram,
-            self.metrics.clone(),
-        );
-        vm.execute()?;
+            deleted_count += vm.r2 as usize;
+            written_ids.extend(vm.take_written_ids());
+        }
+        if has_views {
+            self.maintain_views(col_name, Some(&written_ids), txn)?;
+        }
 
-        Ok(vm.r2 as usize)
+        Ok(deleted_count)
     }
 
     fn internal_delete_all(&self, txn: &TransactionInner, col_name: &str) -> Result<usize> {
@@ -748,6 +2304,8 @@ impl DatabaseInner {
             Err(Error::CollectionNotFound(_)) => return Ok(0),
             Err(err) => return Err(err),
         };
+        DatabaseInner::check_not_view(&collection_spec)?;
+        DatabaseInner::check_not_capped(&collection_spec)?;
 
         // Delete content begin
         let subprogram = SubProgram::compile_delete_all(
@@ -762,10 +2320,14 @@ impl DatabaseInner {
                 subprogram,
                 self.metrics.clone(),
             );
+            self.track_vm(&mut vm, "delete", col_name, None);
+            self.observe_writes(&mut vm, col_name)?;
+            DatabaseInner::apply_expiry(&mut vm, &collection_spec);
             vm.execute()?;
 
             vm.r2 as usize
         }; // Delete content end
+        self.maintain_views(col_name, None, txn)?;
 
         Ok(delete_count)
     }
@@ -815,8 +2377,24 @@ impl DatabaseInner {
         }
 
         let col = col.unwrap();
-        let mut count = 0;
 
+        if col.view.is_some() {
+            let mut count = 0;
+            let mut handle = self.aggregate_with_plan::<Document>(name, vec![], QueryPlan::default(), None, txn.clone())?;
+            while handle.advance()? {
+                count += 1;
+            }
+            return Ok(count);
+        }
+
+        // without expiry, every record is counted, so the documents aren't decoded
+        if Expiry::of_collection(&col).is_none() {
+            let mut data_prefix = Vec::<u8>::new();
+            crate::utils::bson::stacked_key_bytes(&mut data_prefix, &Bson::String(col._id.clone()))?;
+            return DatabaseInner::count_prefix(txn, data_prefix);
+        }
+
+        let mut count = 0;
         let mut handle = self.find_internal::<Document>(&col, None, txn.clone())?;
 
         while handle.advance()? {
@@ -831,6 +2409,42 @@ impl DatabaseInner {
         Ok(collection_metas_to_names(docs))
     }
 
+    /// Describe the collections of the database, one document per collection.
+    ///
+    /// The count is the number of stored documents, including the expired
+    /// documents not yet removed.
+    pub(crate) fn list_collections(&self, txn: &TransactionInner) -> Result<Vec<Document>> {
+        let names = self.list_collection_names_with_session(txn)?;
+        let mut result = Vec::with_capacity(names.len());
+        for name in names {
+            let col_spec = self.internal_get_collection_id_by_name(txn, &name)?;
+
+            let mut data_prefix = Vec::<u8>::new();
+            crate::utils::bson::stacked_key_bytes(&mut data_prefix, &Bson::String(col_spec._id.clone()))?;
+            let (count, _, _) = DatabaseInner::scan_prefix(txn, data_prefix)?;
+
+            let native_validators = self.hooks.get(&name)
+                .map_or(0, |hooks| hooks.validator_count());
+            let mut info = doc! {
+                "createdAt": col_spec.info.create_at,
+            };
+            if let Some(uuid) = &col_spec.info.uuid {
+                info.insert("uuid", uuid.clone());
+            }
+
+            result.push(doc! {
+                "name": name,
+                "type": bson::to_bson(&col_spec.collection_type)?,
+                "options": col_spec.options()?,
+                "info": info,
+                "nindexes": col_spec.indexes.len() as i64,
+                "nativeValidators": native_validators as i64,
+                "count": count as i64,
+            });
+        }
+        Ok(result)
+    }
+
     pub(crate) fn query_all_meta(&self, txn: &TransactionInner) -> Result<Vec<Document>> {
         let mut handle: ClientCursor<Document> = {
             let subprogram = SubProgram::compile_query_all_by_name(
@@ -865,31 +2479,212 @@ impl DatabaseInner {
             false,
             &txn,
         )?;
-        let subprogram = match meta_opt {
+        if meta_opt.as_ref().is_some_and(|col_spec| col_spec.view.is_some()) {
+            let pipeline = vec![doc! { "$match": filter_query.unwrap_or_default() }];
+            return self.aggregate_with_plan(col_name, pipeline, QueryPlan::default(), None, txn);
+        }
+        let subprogram = match &meta_opt {
             Some(col_spec) => {
-                match filter_query {
-                    Some(query) => SubProgram::compile_query(
-                        &col_spec,
-                        &query,
-                        true
-                    ),
-                    None => SubProgram::compile_query_all(&col_spec, true),
+                match &filter_query {
+                    Some(query) => {
+                        let plan = self.plan_query(col_spec, query, QueryPlan::default(), &txn)?;
+                        SubProgram::compile_query_with_plan(col_spec, query, true, &plan)
+                    }
+                    None => SubProgram::compile_query_all(col_spec, true),
                 }?
             }
             None => SubProgram::compile_empty_query(),
         };
 
-        let vm = VM::new(
+        let mut vm = VM::new(
             txn,
             subprogram,
             self.metrics.clone(),
         );
+        self.track_vm(&mut vm, "find", col_name, filter_query.as_ref());
+        if let Some(col_spec) = &meta_opt {
+            DatabaseInner::apply_expiry(&mut vm, col_spec);
+            self.apply_bloom_filter(&mut vm, col_spec)?;
+            self.apply_fuzzy_index(&mut vm, col_spec, filter_query.as_ref());
+        }
 
         let handle = ClientCursor::new(vm);
 
         Ok(handle)
     }
 
+    /// Find the documents in the order of the primary keys,
+    /// starting after the encoded primary key `after`.
+    pub(crate) fn find_page<T: DeserializeOwned + Send + Sync>(
+        &self,
+        col_name: &str,
+        filter: Document,
+        after: Option<Vec<u8>>,
+        txn: TransactionInner,
+    ) -> Result<ClientCursor<T>> {
+        DatabaseInner::validate_col_name(col_name)?;
+        let meta_opt = self.get_collection_meta_by_name_advanced_auto(
+            col_name,
+            false,
+            &txn,
+        )?;
+        let subprogram = match &meta_opt {
+            Some(col_spec) => {
+                let plan = QueryPlan {
+                    collection_scan: true,
+                    ..Default::default()
+                };
+                SubProgram::compile_query_with_plan(col_spec, &filter, true, &plan)?
+            }
+            None => SubProgram::compile_empty_query(),
+        };
+
+        let mut vm = VM::new(
+            txn,
+            subprogram,
+            self.metrics.clone(),
+        );
+        self.track_vm(&mut vm, "find", col_name, Some(&filter));
+        if let Some(col_spec) = &meta_opt {
+            DatabaseInner::apply_expiry(&mut vm, col_spec);
+            self.apply_bloom_filter(&mut vm, col_spec)?;
+        }
+        if let Some(pkey) = after {
+            vm.set_resume_after(pkey);
+        }
+
+        Ok(ClientCursor::new(vm))
+    }
+
+    /// Find the documents in the order of the index serving `sort`, starting after
+    /// the document at `after`. Return `None` if no index of the collection serves the sort.
+    pub(crate) fn find_index_page<T: DeserializeOwned + Send + Sync>(
+        &self,
+        col_name: &str,
+        filter: Document,
+        sort: &Document,
+        after: Option<IndexPosition>,
+        txn: TransactionInner,
+    ) -> Result<Option<ClientCursor<T>>> {
+        DatabaseInner::validate_col_name(col_name)?;
+        let meta_opt = self.get_collection_meta_by_name_advanced_auto(
+            col_name,
+            false,
+            &txn,
+        )?;
+        let subprogram = match &meta_opt {
+            Some(col_spec) => {
+                let index_order = match IndexOrder::find(&col_spec.indexes, sort) {
+                    Some(index_order) => index_order,
+                    None => return Ok(None),
+                };
+                let plan = QueryPlan {
+                    collection_scan: true,
+                    index_order: Some(index_order),
+                    ..Default::default()
+                };
+                let pipeline = vec![
+                    doc! { "$match": filter.clone() },
+                    doc! { "$sort": sort.clone() },
+                ];
+                SubProgram::compile_aggregate_with_plan(col_spec, pipeline, true, &plan)?
+            }
+            None => SubProgram::compile_empty_query(),
+        };
+
+        let mut vm = VM::new(
+            txn,
+            subprogram,
+            self.metrics.clone(),
+        );
+        self.track_vm(&mut vm, "find", col_name, Some(&filter));
+        if let Some(col_spec) = &meta_opt {
+            DatabaseInner::apply_expiry(&mut vm, col_spec);
+            self.apply_bloom_filter(&mut vm, col_spec)?;
+        }
+        if let Some(position) = after {
+            vm.set_index_resume(position);
+        }
+
+        Ok(Some(ClientCursor::new(vm)))
+    }
+
+    /// Find the documents of the pipeline with the index of the hint of `range`.
+    /// With bounds, its keys are read from the lower bound, included, to the upper
+    /// bound, excluded, and the documents are given in the order of the index
+    /// unless the pipeline sorts them.
+    pub(crate) fn find_with_hint<T: DeserializeOwned + Send + Sync>(
+        &self,
+        col_name: &str,
+        pipeline: Vec<Document>,
+        range: &IndexRange,
+        mut plan: QueryPlan,
+        txn: TransactionInner,
+    ) -> Result<ClientCursor<T>> {
+        DatabaseInner::validate_col_name(col_name)?;
+        let meta_opt = self.get_collection_meta_by_name_advanced_auto(
+            col_name,
+            false,
+            &txn,
+        )?;
+        let filter = match pipeline.first().and_then(|stage| stage.get("$match")) {
+            Some(Bson::Document(filter)) => Some(filter.clone()),
+            _ => None,
+        };
+        let mut bounds = None;
+        let subprogram = match &meta_opt {
+            Some(col_spec) => {
+                let hinted = DatabaseInner::query_plan(col_spec, Some(&range.hint), None)?;
+                let bounded = range.min.is_some() || range.max.is_some();
+                // the scan in the order of an index decodes the documents strictly
+                if bounded && plan.lenient {
+                    return Err(Error::InvalidIndexBounds("the bounds need the strict decode mode".to_string()));
+                }
+                match hinted.hint {
+                    Some(index_name) if bounded => {
+                        let index_info = col_spec.indexes[&index_name].clone();
+                        bounds = Some(IndexBounds::new(&index_info, range.min.as_ref(), range.max.as_ref())?);
+                        plan.collection_scan = true;
+                        plan.index_order = Some(IndexOrder {
+                            index_name,
+                            index_info,
+                            reverse: false,
+                        });
+                    }
+                    Some(index_name) => plan.hint = Some(index_name),
+                    None if bounded => {
+                        return Err(Error::InvalidIndexBounds("the hint must name an index, not $natural".to_string()));
+                    }
+                    None => {
+                        plan.collection_scan = true;
+                        plan.backward = hinted.backward;
+                    }
+                }
+                SubProgram::compile_aggregate_with_plan(col_spec, pipeline, true, &plan)?
+            }
+            None => SubProgram::compile_empty_query(),
+        };
+
+        let mut vm = VM::new(
+            txn,
+            subprogram,
+            self.metrics.clone(),
+        );
+        self.track_vm(&mut vm, "find", col_name, filter.as_ref());
+        if let Some(col_spec) = &meta_opt {
+            DatabaseInner::apply_expiry(&mut vm, col_spec);
+            self.apply_bloom_filter(&mut vm, col_spec)?;
+        }
+        if let Some(bounds) = bounds {
+            vm.set_index_bounds(bounds);
+        }
+        if plan.lenient {
+            vm.set_lenient(col_name.to_string());
+        }
+
+        Ok(ClientCursor::new(vm))
+    }
+
     pub(crate) fn count_documents(&self, col_name: &str, txn: &TransactionInner) -> Result<u64> {
         DatabaseInner::validate_col_name(col_name)?;
         let test_result = self.count(col_name, txn);
@@ -912,6 +2707,7 @@ impl DatabaseInner {
             col_name,
             query,
             false,
+            &DeleteOptions::default(),
             txn,
         );
 
@@ -926,13 +2722,13 @@ impl DatabaseInner {
         }
     }
 
-    pub(crate) fn delete_many(&self, col_name: &str, query: Document, txn: &TransactionInner) -> Result<DeleteResult> {
+    pub(crate) fn delete_many(&self, col_name: &str, query: Document, options: &DeleteOptions, txn: &TransactionInner) -> Result<DeleteResult> {
         DatabaseInner::validate_col_name(col_name)?;
 
-        let test_deleted_count = if query.is_empty() {
+        let test_deleted_count = if query.is_empty() && options.limit.unwrap_or(0) == 0 && options.hint.is_none() {
             self.delete_all(col_name, txn)
         } else {
-            self.delete(col_name, query, true, txn)
+            self.delete(col_name, query, true, options, txn)
         };
         match test_deleted_count {
             Ok(deleted_count) => Ok(DeleteResult {
@@ -945,36 +2741,285 @@ impl DatabaseInner {
         }
     }
 
-    pub(crate) fn aggregate_with_owned_session<T: DeserializeOwned + Send + Sync>(
+    /// Run the pipeline with the choices of `base_plan`, such as the residual filter of a find.
+    /// The operation is tagged with `comment`, or the `$comment` of the first `$match`.
+    pub(crate) fn aggregate_with_plan<T: DeserializeOwned + Send + Sync>(
         &self,
         col_name: &str,
         pipeline: impl IntoIterator<Item = Document>,
+        base_plan: QueryPlan,
+        comment: Option<Bson>,
         txn: TransactionInner,
     ) -> Result<ClientCursor<T>> {
         DatabaseInner::validate_col_name(col_name)?;
         let meta_opt = self.get_collection_meta_by_name_advanced_auto(col_name, false, &txn)?;
-        let subprogram = match meta_opt {
+        let pipeline: Vec<Document> = pipeline.into_iter().collect();
+        let match_query = match pipeline.first().and_then(|stage| stage.get("$match")) {
+            Some(Bson::Document(query)) => Some(query.clone()),
+            _ => None,
+        };
+        // the query of a view is run on its source
+        if let Some(view) = meta_opt.as_ref().and_then(|col_spec| col_spec.view.as_ref()) {
+            let comment = comment.or_else(|| match_query.as_ref().and_then(|query| query.get("$comment")).cloned());
+            let pipeline = view.compose(pipeline, base_plan.residual_filter.is_some());
+            return self.aggregate_with_plan(&view.source, pipeline, base_plan, comment, txn);
+        }
+        let lenient = base_plan.lenient;
+        let subprogram = match &meta_opt {
             Some(col_spec) => {
-                SubProgram::compile_aggregate(
-                    &col_spec,
-                    pipeline,
-                    true
-                )?
+                match self.aggregation_source_values(col_spec, &pipeline, &txn)? {
+                    Some(values) => {
+                        let mut stages = pipeline[1..].to_vec();
+                        // the score is removed once the pipeline doesn't need it anymore
+                        if pipeline[0].contains_key("$vectorSearch") {
+                            stages.push(doc! { "$unset": VECTOR_SCORE_FIELD });
+                        }
+                        SubProgram::compile_aggregate_with_values(values, &stages, true)?
+                    }
+                    None => {
+                        let plan = match &match_query {
+                            Some(query) => self.plan_query(col_spec, query, base_plan, &txn)?,
+                            None => base_plan,
+                        };
+                        SubProgram::compile_aggregate_with_plan(
+                            col_spec,
+                            pipeline,
+                            true,
+                            &plan,
+                        )?
+                    }
+                }
             }
             None => SubProgram::compile_empty_query(),
         };
 
-        let vm = VM::new(
+        let mut vm = VM::new(
             txn,
             subprogram,
             self.metrics.clone(),
         );
+        let comment = comment.or_else(|| match_query.as_ref().and_then(|query| query.get("$comment")).cloned());
+        self.track_vm_with_comment(&mut vm, "aggregate", col_name, None, comment.as_ref());
+        if let Some(col_spec) = &meta_opt {
+            DatabaseInner::apply_expiry(&mut vm, col_spec);
+            self.apply_bloom_filter(&mut vm, col_spec)?;
+            self.apply_fuzzy_index(&mut vm, col_spec, match_query.as_ref());
+        }
+        if lenient {
+            vm.set_lenient(col_name.to_string());
+        }
 
         let handle = ClientCursor::new(vm);
 
         Ok(handle)
     }
 
+
+    /// Return the documents of the source stage if the pipeline starts with
+    /// `$collStats`, `$indexStats` or `$vectorSearch`, `None` if it reads the collection.
+    fn aggregation_source_values(
+        &self,
+        col_spec: &CollectionSpecification,
+        pipeline: &[Document],
+        txn: &TransactionInner,
+    ) -> Result<Option<Vec<Document>>> {
+        let first = match pipeline.first() {
+            Some(first) => first,
+            None => return Ok(None),
+        };
+        let (key, value) = match first.iter().next() {
+            Some(tuple) if first.len() == 1 => tuple,
+            _ => return Ok(None),
+        };
+        if !matches!(key.as_str(), "$collStats" | "$indexStats" | "$vectorSearch") {
+            return Ok(None);
+        }
+        let options = match value {
+            Bson::Document(options) => options,
+            _ => return Err(Error::InvalidAggregationStage(Box::new(first.clone()))),
+        };
+        let values = match key.as_str() {
+            "$collStats" => vec![self.collection_stats(col_spec, txn)?],
+            "$indexStats" => self.index_stats(col_spec, txn)?,
+            _ => self.vector_search(col_spec, options, txn)?,
+        };
+        Ok(Some(values))
+    }
+
+    /// The `limit` documents closest to the `queryVector` among the `numCandidates`
+    /// found by the graph of the `index`, and matching the `filter`, the closest first.
+    fn vector_search(&self, col_spec: &CollectionSpecification, options: &Document, txn: &TransactionInner) -> Result<Vec<Document>> {
+        let index_name = options.get_str("index")
+            .map_err(|_| Error::ValidationError("$vectorSearch requires the name of the 'index'".into()))?;
+        let info = col_spec.vector_indexes.get(index_name)
+            .ok_or_else(|| Error::IndexNotFound(index_name.to_string()))?;
+        if options.get_str("path").is_ok_and(|path| path != info.field) {
+            return Err(Error::ValidationError(format!("the index '{}' is on the path '{}'", index_name, info.field)));
+        }
+        let query = match options.get("queryVector").and_then(Vector::from_bson) {
+            Some(query) if query.dimensions() == info.dimensions as usize => query,
+            _ => {
+                return Err(Error::InvalidVector(format!(
+                    "$vectorSearch requires a 'queryVector' of {} numbers", info.dimensions,
+                )));
+            }
+        };
+        let count = |name: &str| match options.get(name) {
+            None => Ok(None),
+            Some(Bson::Int32(count)) if *count > 0 => Ok(Some(*count as usize)),
+            Some(Bson::Int64(count)) if *count > 0 => Ok(Some(*count as usize)),
+            Some(_) => Err(Error::ValidationError(format!("$vectorSearch '{}' must be a positive integer", name))),
+        };
+        let limit = count("limit")?
+            .ok_or_else(|| Error::ValidationError("$vectorSearch requires a 'limit'".into()))?;
+        let num_candidates = count("numCandidates")?.unwrap_or(limit * 10).max(limit);
+
+        let graph = match self.vector_indexes.get(col_spec.name(), index_name) {
+            Some(graph) if graph.info() == info => graph,
+            _ => {
+                self.vector_indexes.build(txn, col_spec.name(), index_name, info)?;
+                self.vector_indexes.get(col_spec.name(), index_name).unwrap()
+            }
+        };
+        let ids = graph.candidates(query.as_slice(), num_candidates);
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut filter = doc! { "_id": { "$in": ids } };
+        match options.get("filter") {
+            None => (),
+            Some(Bson::Document(condition)) => {
+                filter = doc! { "$and": [condition.clone(), filter] };
+            }
+            Some(_) => return Err(Error::ValidationError("$vectorSearch 'filter' must be a document".into())),
+        }
+
+        // the documents are scored by their current vectors
+        let mut found = Vec::<(f64, Document)>::new();
+        for doc in self.find_with_owned_session::<Document>(col_spec.name(), filter, txn.clone())? {
+            let doc = doc?;
+            let vector = crate::utils::bson::try_get_document_value(&doc, &info.field)
+                .as_ref()
+                .and_then(Vector::from_bson);
+            if let Some(vector) = vector.filter(|vector| vector.dimensions() == query.dimensions()) {
+                found.push((info.similarity.score(query.as_slice(), vector.as_slice()), doc));
+            }
+        }
+        found.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        found.truncate(limit);
+
+        Ok(found.into_iter()
+            .map(|(score, mut doc)| {
+                doc.insert(VECTOR_SCORE_FIELD, score);
+                doc
+            })
+            .collect())
+    }
+
+    /// Count the entries under the key prefix, return the count and the bytes
+    /// of the keys and the values.
+    fn scan_prefix(txn: &TransactionInner, prefix: Vec<u8>) -> Result<(u64, u64, u64)> {
+        let db_iter = txn.rocksdb_txn.new_iterator();
+        let mut cursor = Cursor::new(prefix, db_iter);
+        cursor.reset()?;
+
+        let mut count = 0;
+        let mut key_bytes = 0;
+        let mut value_bytes = 0;
+        while cursor.has_next() {
+            count += 1;
+            key_bytes += cursor.peek_key().map(|key| key.len()).unwrap_or(0) as u64;
+            value_bytes += cursor.copy_data()?.len() as u64;
+            cursor.next()?;
+        }
+
+        Ok((count, key_bytes, value_bytes))
+    }
+
+    /// Count the entries under the key prefix, without reading their values.
+    fn count_prefix(txn: &TransactionInner, prefix: Vec<u8>) -> Result<u64> {
+        let db_iter = txn.rocksdb_txn.new_iterator();
+        let mut cursor = Cursor::new(prefix, db_iter);
+        cursor.reset()?;
+
+        let mut count = 0;
+        while cursor.has_next() {
+            count += 1;
+            cursor.next()?;
+        }
+
+        Ok(count)
+    }
+
+    fn index_prefix(col_name: &str, index_name: &str) -> Result<Vec<u8>> {
+        let b_prefix = Bson::String(crate::index::INDEX_PREFIX.to_string());
+        let b_col_name = Bson::String(col_name.to_string());
+        let b_index_name = Bson::String(index_name.to_string());
+        crate::utils::bson::stacked_key([&b_prefix, &b_col_name, &b_index_name])
+    }
+
+    /// The statistics of a collection, in the format of the `collStats` command.
+    pub(crate) fn collection_stats(&self, col_spec: &CollectionSpecification, txn: &TransactionInner) -> Result<Document> {
+        let mut data_prefix = Vec::<u8>::new();
+        crate::utils::bson::stacked_key_bytes(&mut data_prefix, &Bson::String(col_spec._id.clone()))?;
+        let (count, key_bytes, value_bytes) = DatabaseInner::scan_prefix(txn, data_prefix)?;
+
+        let mut index_sizes = Document::new();
+        let mut total_index_size = 0;
+        for index_name in col_spec.indexes.keys() {
+            let prefix = DatabaseInner::index_prefix(col_spec.name(), index_name)?;
+            let (_, index_key_bytes, index_value_bytes) = DatabaseInner::scan_prefix(txn, prefix)?;
+            let size = index_key_bytes + index_value_bytes;
+            total_index_size += size;
+            index_sizes.insert(index_name.clone(), size as i64);
+        }
+
+        let mut stats = doc! {
+            "ns": col_spec.name(),
+            "count": count as i64,
+            "size": value_bytes as i64,
+            "avgObjSize": value_bytes.checked_div(count).unwrap_or(0) as i64,
+            "storageSize": (key_bytes + value_bytes) as i64,
+            "nindexes": col_spec.indexes.len() as i64,
+            "totalIndexSize": total_index_size as i64,
+            "indexSizes": index_sizes,
+            "createdAt": col_spec.info.create_at,
+            "capped": col_spec.capped.is_some(),
+        };
+        if let Some(capped) = &col_spec.capped {
+            if let Some(max) = capped.max {
+                stats.insert("max", max as i64);
+            }
+            if let Some(size) = capped.size {
+                stats.insert("maxSize", size as i64);
+            }
+        }
+        Ok(stats)
+    }
+
+    /// The statistics of the indexes of a collection, one document per index.
+    pub(crate) fn index_stats(&self, col_spec: &CollectionSpecification, txn: &TransactionInner) -> Result<Vec<Document>> {
+        let mut result = Vec::with_capacity(col_spec.indexes.len());
+        for (index_name, index_info) in &col_spec.indexes {
+            let prefix = DatabaseInner::index_prefix(col_spec.name(), index_name)?;
+            let (entries, key_bytes, value_bytes) = DatabaseInner::scan_prefix(txn, prefix)?;
+
+            let mut key = Document::new();
+            for (field, order) in &index_info.keys {
+                key.insert(field.clone(), *order as i32);
+            }
+
+            result.push(doc! {
+                "name": index_name.as_str(),
+                "key": key,
+                "unique": index_info.is_unique(),
+                "entries": entries as i64,
+                "size": (key_bytes + value_bytes) as i64,
+            });
+        }
+        Ok(result)
+    }
+
 }
 
 fn collection_metas_to_names(doc_meta: Vec<Document>) -> Vec<String> {
@@ -997,7 +3042,11 @@ mod tests {
         assert!(DatabaseInner::validate_col_name("test").is_ok());
         assert!(DatabaseInner::validate_col_name("$test$").is_err());
         assert!(DatabaseInner::validate_col_name("test\n").is_err());
-        assert!(DatabaseInner::validate_col_name("test.ok").is_err());
+        assert!(DatabaseInner::validate_col_name("tenant.test").is_ok());
+        assert!(DatabaseInner::validate_col_name("tenant.test.ok").is_err());
+        assert!(DatabaseInner::validate_col_name(".test").is_err());
+        assert!(DatabaseInner::validate_col_name("tenant.").is_err());
+        assert!(DatabaseInner::validate_col_name("$tenant.test").is_err());
     }
 
     #[test]
@@ -1010,8 +3059,9 @@ mod tests {
 
     #[test]
     fn test_make_index_name() {
-        assert_eq!(DatabaseInner::make_index_name("test", 1, None).unwrap(), "test_1");
-        assert_eq!(DatabaseInner::make_index_name("test.ok", 1, None).unwrap(), "test_ok_1");
+        assert_eq!(DatabaseInner::make_index_name(&["test"], 1, None).unwrap(), "test_1");
+        assert_eq!(DatabaseInner::make_index_name(&["test.ok"], 1, None).unwrap(), "test_ok_1");
+        assert_eq!(DatabaseInner::make_index_name(&["category", "tags"], 1, None).unwrap(), "category_1_tags_1");
     }
 
     #[test]
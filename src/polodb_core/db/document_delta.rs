@@ -0,0 +1,223 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! The updates changing a few fields of a large document are written as
+//! a delta of the fields, the merge operator of the storage applies
+//! the deltas to the document when it's read or compacted.
+
+use std::{ptr, slice};
+use bson::{Bson, Document};
+use libc::{c_char, c_int, c_uchar, c_void, size_t};
+use polodb_librocksdb_sys as ffi;
+use crate::Result;
+
+/// The documents smaller than this are always written as a whole.
+pub(crate) const DELTA_MIN_DOCUMENT_SIZE: usize = 1024;
+
+/// A delta is written when it's at most a quarter of the size of the document.
+const DELTA_MAX_RATIO: usize = 4;
+
+const MERGE_OPERATOR_NAME: &[u8] = b"polodb.DocumentDelta\0";
+
+/// The delta turning `old` into `new`: the top-level fields set and unset, `None` if
+/// the fields of `new` are not in the order the delta would put them.
+pub(crate) fn document_delta(old: &Document, new: &Document) -> Option<Document> {
+    let mut retained = old.keys().filter(|key| new.contains_key(key.as_str()));
+    let mut set = Document::new();
+    let mut appending = false;
+    for (key, value) in new {
+        match old.get(key) {
+            Some(old_value) => {
+                // the fields kept by the delta stay in place, the added ones are appended
+                if appending || retained.next() != Some(key) {
+                    return None;
+                }
+                if old_value != value {
+                    set.insert(key.clone(), value.clone());
+                }
+            }
+            None => {
+                appending = true;
+                set.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    let unset = old.keys()
+        .filter(|key| !new.contains_key(key.as_str()))
+        .map(|key| Bson::String(key.clone()))
+        .collect::<Vec<_>>();
+
+    let mut delta = Document::new();
+    if !set.is_empty() {
+        delta.insert("$set", set);
+    }
+    if !unset.is_empty() {
+        delta.insert("$unset", unset);
+    }
+    Some(delta)
+}
+
+/// The encoded delta of the update if it's worth writing instead of the document.
+pub(crate) fn encoded_delta(old: &Document, new: &Document, new_size: usize) -> Result<Option<Vec<u8>>> {
+    let delta = match document_delta(old, new) {
+        Some(delta) => delta,
+        None => return Ok(None),
+    };
+    let buf = bson::to_vec(&delta)?;
+    if buf.len() * DELTA_MAX_RATIO > new_size {
+        return Ok(None);
+    }
+    Ok(Some(buf))
+}
+
+fn apply_delta(doc: &mut Document, delta: &Document) {
+    if let Ok(unset) = delta.get_array("$unset") {
+        for key in unset.iter().filter_map(Bson::as_str) {
+            doc.remove(key);
+        }
+    }
+    if let Ok(set) = delta.get_document("$set") {
+        for (key, value) in set {
+            doc.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+fn merge_deltas<'a>(existing: Option<&[u8]>, operands: impl Iterator<Item = &'a [u8]>) -> Result<Vec<u8>> {
+    let mut doc: Document = match existing {
+        Some(data) => bson::from_slice(data)?,
+        None => Document::new(),
+    };
+    for operand in operands {
+        let delta: Document = bson::from_slice(operand)?;
+        apply_delta(&mut doc, &delta);
+    }
+    Ok(bson::to_vec(&doc)?)
+}
+
+/// Create the merge operator applying the deltas, the options opening
+/// the storage take its ownership.
+pub(crate) fn create_merge_operator() -> *mut ffi::rocksdb_mergeoperator_t {
+    unsafe {
+        ffi::rocksdb_mergeoperator_create(
+            ptr::null_mut(),
+            Some(destructor),
+            Some(full_merge),
+            Some(partial_merge),
+            Some(delete_value),
+            Some(name),
+        )
+    }
+}
+
+unsafe extern "C" fn destructor(_state: *mut c_void) {}
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn full_merge(
+    _state: *mut c_void,
+    _key: *const c_char,
+    _key_length: size_t,
+    existing_value: *const c_char,
+    existing_value_length: size_t,
+    operands_list: *const *const c_char,
+    operands_list_length: *const size_t,
+    num_operands: c_int,
+    success: *mut c_uchar,
+    new_value_length: *mut size_t,
+) -> *mut c_char {
+    let existing = if existing_value.is_null() {
+        None
+    } else {
+        Some(slice::from_raw_parts(existing_value as *const u8, existing_value_length))
+    };
+    let operands = (0..num_operands as usize).map(|i| {
+        slice::from_raw_parts(*operands_list.add(i) as *const u8, *operands_list_length.add(i))
+    });
+    match merge_deltas(existing, operands) {
+        Ok(value) => {
+            let value = value.into_boxed_slice();
+            *new_value_length = value.len();
+            *success = 1;
+            Box::into_raw(value) as *mut c_char
+        }
+        Err(_) => {
+            *success = 0;
+            ptr::null_mut()
+        }
+    }
+}
+
+unsafe extern "C" fn partial_merge(
+    _state: *mut c_void,
+    _key: *const c_char,
+    _key_length: size_t,
+    _operands_list: *const *const c_char,
+    _operands_list_length: *const size_t,
+    _num_operands: c_int,
+    success: *mut c_uchar,
+    _new_value_length: *mut size_t,
+) -> *mut c_char {
+    // the deltas are kept until the document is merged
+    *success = 0;
+    ptr::null_mut()
+}
+
+unsafe extern "C" fn delete_value(_state: *mut c_void, value: *const c_char, value_length: size_t) {
+    if !value.is_null() {
+        let value = slice::from_raw_parts_mut(value as *mut u8, value_length);
+        drop(Box::from_raw(value as *mut [u8]));
+    }
+}
+
+unsafe extern "C" fn name(_state: *mut c_void) -> *const c_char {
+    MERGE_OPERATOR_NAME.as_ptr() as *const c_char
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+    use super::{apply_delta, document_delta};
+
+    #[test]
+    fn test_document_delta() {
+        let old = doc! {
+            "_id": 1,
+            "text": "hello",
+            "read": false,
+            "draft": true,
+        };
+        let new = doc! {
+            "_id": 1,
+            "text": "hello",
+            "read": true,
+            "readers": ["alice"],
+        };
+        let delta = document_delta(&old, &new).unwrap();
+        assert_eq!(delta, doc! {
+            "$set": { "read": true, "readers": ["alice"] },
+            "$unset": ["draft"],
+        });
+
+        let mut merged = old.clone();
+        apply_delta(&mut merged, &delta);
+        assert_eq!(merged, new);
+        assert_eq!(merged.keys().collect::<Vec<_>>(), new.keys().collect::<Vec<_>>());
+
+        // the fields moved can't be described by a delta
+        let renamed = doc! { "_id": 1, "read": false, "text": "hello", "draft": true };
+        assert!(document_delta(&old, &renamed).is_none());
+    }
+
+}
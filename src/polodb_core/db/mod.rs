@@ -19,7 +19,10 @@ mod rocksdb_wrapper;
 mod rocksdb_transaction;
 mod rocksdb_iterator;
 mod rocksdb_options;
+pub(crate) mod document_delta;
 
 pub use db::{Database, Result};
+pub(crate) use db::SHOULD_LOG;
 pub(crate) use rocksdb_transaction::RocksDBTransaction;
 pub(crate) use rocksdb_iterator::RocksDBIterator;
+pub(crate) use rocksdb_wrapper::{RocksDBWrapper, WeakRocksDBWrapper};
@@ -41,6 +41,15 @@ impl RocksDBIterator {
         self.inner.seek(key)
     }
 
+    pub fn seek_to_last(&self) {
+        self.inner.seek_to_last()
+    }
+
+    /// Move to the last key less than or equal to `key`.
+    pub fn seek_for_prev(&self, key: &[u8]) {
+        self.inner.seek_for_prev(key)
+    }
+
     pub fn valid(&self) -> bool {
         self.inner.valid()
     }
@@ -49,7 +58,6 @@ impl RocksDBIterator {
         self.inner.next()
     }
 
-    #[allow(dead_code)]
     pub fn prev(&self) {
         self.inner.prev()
     }
@@ -70,6 +78,16 @@ impl RocksDBIterator {
     pub fn copy_data(&self) -> Result<Vec<u8>> {
         self.inner.copy_data()
     }
+
+    /// Copy the key into `buf`, reusing its allocation.
+    pub fn copy_key_into(&self, buf: &mut Vec<u8>) -> Result<()> {
+        self.inner.copy_key_into(buf)
+    }
+
+    /// Copy the value into `buf`, reusing its allocation.
+    pub fn copy_data_into(&self, buf: &mut Vec<u8>) -> Result<()> {
+        self.inner.copy_data_into(buf)
+    }
 }
 
 pub(crate) struct RocksDBIteratorInner {
@@ -101,12 +119,24 @@ impl RocksDBIteratorInner {
         }
     }
 
+    pub fn seek_to_last(&self) {
+        unsafe {
+            ffi::rocksdb_iter_seek_to_last(self.inner);
+        }
+    }
+
     pub fn seek(&self, key: &[u8]) {
         unsafe {
             ffi::rocksdb_iter_seek(self.inner, key.as_ptr() as *const i8, key.len());
         }
     }
 
+    pub fn seek_for_prev(&self, key: &[u8]) {
+        unsafe {
+            ffi::rocksdb_iter_seek_for_prev(self.inner, key.as_ptr() as *const i8, key.len());
+        }
+    }
+
     pub fn valid(&self) -> bool {
         unsafe {
             ffi::rocksdb_iter_valid(self.inner) != 0
@@ -172,6 +202,28 @@ impl RocksDBIteratorInner {
         }
     }
 
+    pub fn copy_key_into(&self, buf: &mut Vec<u8>) -> Result<()> {
+        self.error()?;
+        unsafe {
+            let mut len: usize = 0;
+            let key = ffi::rocksdb_iter_key(self.inner, &mut len);
+            buf.clear();
+            buf.extend_from_slice(std::slice::from_raw_parts(key as *const u8, len));
+        }
+        Ok(())
+    }
+
+    pub fn copy_data_into(&self, buf: &mut Vec<u8>) -> Result<()> {
+        self.error()?;
+        unsafe {
+            let mut len: usize = 0;
+            let data = ffi::rocksdb_iter_value(self.inner, &mut len);
+            buf.clear();
+            buf.extend_from_slice(std::slice::from_raw_parts(data as *const u8, len));
+        }
+        Ok(())
+    }
+
 }
 
 impl Drop for RocksDBIteratorInner {
@@ -45,8 +45,8 @@ pub(crate) struct RocksDBTransaction {
 
 impl RocksDBTransaction {
 
-    pub(crate) fn new(db_inner: *mut RocksDBWrapperInner) -> Result<RocksDBTransaction>  {
-        let inner = RocksDBTransactionInner::new(db_inner)?;
+    pub(crate) fn new(db_inner: *mut RocksDBWrapperInner, sync: bool) -> Result<RocksDBTransaction>  {
+        let inner = RocksDBTransactionInner::new(db_inner, sync)?;
         Ok(RocksDBTransaction {
             inner: Arc::new(Mutex::new(inner)),
         })
@@ -68,6 +68,11 @@ impl RocksDBTransaction {
         inner.get(key)
     }
 
+    pub fn merge(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        inner.merge(key, value)
+    }
+
     pub fn delete(&self, key: &[u8]) -> Result<()> {
         let inner = self.inner.lock().unwrap();
         inner.delete(key)
@@ -78,6 +83,16 @@ impl RocksDBTransaction {
         inner.rollback()
     }
 
+    pub fn set_savepoint(&self) {
+        let inner = self.inner.lock().unwrap();
+        inner.set_savepoint()
+    }
+
+    pub fn rollback_to_savepoint(&self) -> Result<()> {
+        let inner = self.inner.lock().unwrap();
+        inner.rollback_to_savepoint()
+    }
+
     pub fn commit(&self) -> Result<()> {
         let inner = self.inner.lock().unwrap();
         inner.commit()
@@ -99,11 +114,13 @@ unsafe impl Sync for RocksDBTransactionInner {}
 
 impl RocksDBTransactionInner {
 
-    pub(crate) fn new(db_inner: *mut RocksDBWrapperInner) -> Result<RocksDBTransactionInner>  {
+    /// Begin a transaction, waiting for the write-ahead log to be flushed to the disk
+    /// when it's committed if `sync` is true.
+    pub(crate) fn new(db_inner: *mut RocksDBWrapperInner, sync: bool) -> Result<RocksDBTransactionInner>  {
         unsafe {
             let read_options = RocksDBReadOptions::new();
             let write_options = RocksDBWriteOptions::new();
-            write_options.set_sync(true);
+            write_options.set_sync(sync);
             let txn_options = RocksDBTransactionOptions::new();
             _ = (*db_inner).txn_count.fetch_add(1, Ordering::SeqCst);
             let inner = ffi::rocksdb_transaction_begin(
@@ -142,6 +159,24 @@ impl RocksDBTransactionInner {
         }
     }
 
+    pub fn merge(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        unsafe {
+            let mut err: *mut c_char = ptr::null_mut();
+
+            ffi::rocksdb_transaction_merge(
+                self.inner,
+                key.as_ptr() as *const i8,
+                key.len(),
+                value.as_ptr() as *const i8,
+                value.len(),
+                &mut err,
+            );
+
+            check_err!(err);
+            Ok(())
+        }
+    }
+
     pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
         unsafe {
             let mut err: *mut c_char = ptr::null_mut();
@@ -192,6 +227,22 @@ impl RocksDBTransactionInner {
         }
     }
 
+    pub fn set_savepoint(&self) {
+        unsafe {
+            ffi::rocksdb_transaction_set_savepoint(self.inner);
+        }
+    }
+
+    pub fn rollback_to_savepoint(&self) -> Result<()> {
+        unsafe {
+            let mut err: *mut c_char = ptr::null_mut();
+            ffi::rocksdb_transaction_rollback_to_savepoint(self.inner, &mut err);
+
+            check_err!(err);
+            Ok(())
+        }
+    }
+
     pub(crate) fn commit(&self) -> Result<()> {
         unsafe {
             let mut err: *mut c_char = ptr::null_mut();
@@ -12,17 +12,25 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::ops::DerefMut;
 use std::path::{Path, PathBuf};
 use std::{env, ptr};
-use std::ffi::CString;
-use libc::c_char;
+use std::ffi::{CStr, CString};
+use libc::{c_char, c_int};
 use polodb_librocksdb_sys as ffi;
 use super::db::Result;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Arc, Mutex};
-use crate::db::rocksdb_options::RocksDBWaitForCompactOptions;
+use std::sync::{Arc, Mutex, Weak};
+use crate::db::document_delta;
+use crate::db::rocksdb_options::{RocksDBReadOptions, RocksDBWaitForCompactOptions};
 use crate::db::rocksdb_transaction::RocksDBTransaction;
+use crate::metrics::StorageStatistics;
+use crate::{Config, StorageLayout};
+
+/// The subdirectory of the write-ahead log in the [`StorageLayout::Directory`] layout.
+const WAL_DIR: &str = "wal";
 
 macro_rules! check_err {
     ($err:expr) => {
@@ -45,16 +53,242 @@ pub(crate) struct RocksDBWrapper {
 
 impl RocksDBWrapper {
 
+    #[cfg(test)]
     pub fn open(path: &Path) -> Result<RocksDBWrapper> {
-        let inner = RocksDBWrapperInner::open(path)?;
+        RocksDBWrapper::open_with_config(path, &Config::default())
+    }
+
+    pub fn open_with_config(path: &Path, config: &Config) -> Result<RocksDBWrapper> {
+        let inner = RocksDBWrapperInner::open(path, config)?;
         Ok(RocksDBWrapper {
             inner: Arc::new(Mutex::new(inner)),
         })
     }
 
     pub fn begin_transaction(&self) -> Result<RocksDBTransaction> {
+        self.begin_transaction_with_sync(true)
+    }
+
+    pub fn begin_transaction_with_sync(&self, sync: bool) -> Result<RocksDBTransaction> {
+        let mut db_inner = self.inner.lock()?;
+        RocksDBWrapper::refresh_pins(&mut db_inner)?;
+        RocksDBTransaction::new(db_inner.deref_mut() as *mut _, sync)
+    }
+
+    /// Compact the whole key range of the underlying database,
+    /// the pinned blocks are read again from the compacted files.
+    pub fn compact(&self) -> Result<()> {
+        let mut db_inner = self.inner.lock()?;
+        unsafe {
+            let base_db = ffi::rocksdb_transactiondb_get_base_db(db_inner.inner);
+            ffi::rocksdb_compact_range(base_db, ptr::null(), 0, ptr::null(), 0);
+            ffi::rocksdb_transactiondb_close_base_db(base_db);
+        }
+        RocksDBWrapper::reload_pins(&mut db_inner)
+    }
+
+    /// Compact only the keys starting with one of the prefixes.
+    pub fn compact_prefixes(&self, prefixes: &[Vec<u8>]) -> Result<()> {
         let mut db_inner = self.inner.lock()?;
-        RocksDBTransaction::new(db_inner.deref_mut() as *mut _)
+        unsafe {
+            let base_db = ffi::rocksdb_transactiondb_get_base_db(db_inner.inner);
+            for prefix in prefixes {
+                let upper_bound = crate::utils::bson::prefix_upper_bound(prefix);
+                let (limit, limit_len) = if upper_bound.is_empty() {
+                    (ptr::null(), 0)
+                } else {
+                    (upper_bound.as_ptr() as *const c_char, upper_bound.len())
+                };
+                ffi::rocksdb_compact_range(
+                    base_db,
+                    prefix.as_ptr() as *const c_char,
+                    prefix.len(),
+                    limit,
+                    limit_len,
+                );
+            }
+            ffi::rocksdb_transactiondb_close_base_db(base_db);
+        }
+        RocksDBWrapper::reload_pins(&mut db_inner)
+    }
+
+    /// Pin the blocks of the pinned keys again if a flush or a compaction changed
+    /// the files of the storage since they were pinned.
+    fn refresh_pins(db_inner: &mut RocksDBWrapperInner) -> Result<()> {
+        if db_inner.pins.is_empty() || db_inner.super_version() == db_inner.pins_version {
+            return Ok(());
+        }
+        RocksDBWrapper::reload_pins(db_inner)
+    }
+
+    /// Pin the blocks of the pinned keys again after a compaction rewrote them,
+    /// which releases the files and the memtables the previous pins were read from.
+    fn reload_pins(db_inner: &mut RocksDBWrapperInner) -> Result<()> {
+        db_inner.pins_version = db_inner.super_version();
+        let pins = std::mem::take(&mut db_inner.pins);
+        for (name, pinned) in pins {
+            let (_, pinned) = unsafe { PinnedBlocks::load(db_inner.inner, pinned.prefixes.clone(), true)? };
+            db_inner.pins.insert(name, pinned);
+        }
+        Ok(())
+    }
+
+    /// Read every key starting with one of the prefixes, which brings their blocks
+    /// into the block cache, return the number of keys read.
+    pub fn preload(&self, prefixes: Vec<Vec<u8>>) -> Result<u64> {
+        let db_inner = self.inner.lock()?;
+        let (count, _) = unsafe { PinnedBlocks::load(db_inner.inner, prefixes, false)? };
+        Ok(count)
+    }
+
+    /// Keep the blocks of the keys starting with one of the prefixes in memory
+    /// until they are unpinned by `name`. The blocks written after are pinned
+    /// by the first transaction after they are flushed.
+    pub fn pin(&self, name: &str, prefixes: Vec<Vec<u8>>) -> Result<u64> {
+        let mut db_inner = self.inner.lock()?;
+        RocksDBWrapper::refresh_pins(&mut db_inner)?;
+        let (count, pinned) = unsafe { PinnedBlocks::load(db_inner.inner, prefixes, true)? };
+        db_inner.pins.insert(name.to_string(), pinned);
+        Ok(count)
+    }
+
+    /// Release the blocks pinned by `name`, return whether they were pinned.
+    pub fn unpin(&self, name: &str) -> Result<bool> {
+        let mut db_inner = self.inner.lock()?;
+        Ok(db_inner.pins.remove(name).is_some())
+    }
+
+    /// The layout of the files of the database, chosen when it was created.
+    pub fn layout(&self) -> StorageLayout {
+        self.inner.lock().map_or(StorageLayout::Single, |db_inner| db_inner.layout)
+    }
+
+    /// Whether the storage compresses the blocks it writes, RocksDB compresses
+    /// them with Snappy only when the library is built with it.
+    pub fn compression(&self) -> bool {
+        self.inner.lock().is_ok_and(|db_inner| db_inner.compression)
+    }
+
+    pub fn downgrade(&self) -> WeakRocksDBWrapper {
+        WeakRocksDBWrapper {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// Return the statistics of the storage,
+    /// `None` if the database is opened without statistics.
+    pub fn statistics(&self) -> Option<StorageStatistics> {
+        let db_inner = self.inner.lock().ok()?;
+        if !db_inner.enable_statistics {
+            return None;
+        }
+        let text = unsafe {
+            let raw = ffi::rocksdb_options_statistics_get_string(db_inner.options);
+            if raw.is_null() {
+                return None;
+            }
+            let text = CStr::from_ptr(raw).to_string_lossy().into_owned();
+            ffi::rocksdb_free(raw.cast());
+            text
+        };
+        Some(parse_statistics(&text))
+    }
+
+}
+
+/// An iterator over the keys starting with the prefixes. Reading with `pin_data`
+/// keeps the blocks the iterator has read in memory until it is dropped. The iterator
+/// holds the version of the storage it was created at: the files and the memtables
+/// of this version are not freed, even once compacted, until it is dropped.
+struct PinnedBlocks {
+    prefixes: Vec<Vec<u8>>,
+    iter: *mut ffi::rocksdb_iterator_t,
+    _read_options: RocksDBReadOptions,
+}
+
+impl PinnedBlocks {
+
+    unsafe fn load(
+        db: *mut ffi::rocksdb_transactiondb_t,
+        prefixes: Vec<Vec<u8>>,
+        pin: bool,
+    ) -> Result<(u64, PinnedBlocks)> {
+        let read_options = RocksDBReadOptions::new();
+        if pin {
+            ffi::rocksdb_readoptions_set_pin_data(read_options.get(), 1);
+        }
+        let iter = ffi::rocksdb_transactiondb_create_iterator(db, read_options.get());
+        let pinned = PinnedBlocks {
+            prefixes,
+            iter,
+            _read_options: read_options,
+        };
+
+        let mut count = 0;
+        for prefix in &pinned.prefixes {
+            ffi::rocksdb_iter_seek(iter, prefix.as_ptr().cast(), prefix.len());
+            while ffi::rocksdb_iter_valid(iter) != 0 {
+                let mut len: usize = 0;
+                let key = ffi::rocksdb_iter_key(iter, &mut len);
+                if !std::slice::from_raw_parts(key.cast::<u8>(), len).starts_with(prefix) {
+                    break;
+                }
+                count += 1;
+                ffi::rocksdb_iter_next(iter);
+            }
+            let mut err: *mut c_char = ptr::null_mut();
+            ffi::rocksdb_iter_get_error(iter, &mut err);
+            check_err!(err);
+        }
+
+        Ok((count, pinned))
+    }
+
+}
+
+impl Drop for PinnedBlocks {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::rocksdb_iter_destroy(self.iter);
+        }
+    }
+}
+
+/// Parse the tickers of the statistics dump,
+/// the lines of the tickers look like `rocksdb.block.cache.hit COUNT : 42`.
+fn parse_statistics(text: &str) -> StorageStatistics {
+    let mut result = StorageStatistics::default();
+    for line in text.lines() {
+        let mut parts = line.split_whitespace();
+        let (name, kind, _, value) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(name), Some(kind), Some(sep), Some(value)) => (name, kind, sep, value),
+            _ => continue,
+        };
+        if kind != "COUNT" {
+            continue;
+        }
+        let value = value.parse::<u64>().unwrap_or(0);
+        match name {
+            "rocksdb.block.cache.hit" => result.block_cache_hit = value,
+            "rocksdb.block.cache.miss" => result.block_cache_miss = value,
+            "rocksdb.bytes.written" => result.bytes_written = value,
+            "rocksdb.bytes.read" => result.bytes_read = value,
+            "rocksdb.wal.bytes" => result.wal_bytes_written = value,
+            _ => (),
+        }
+    }
+    result
+}
+
+#[derive(Clone)]
+pub(crate) struct WeakRocksDBWrapper {
+    inner: Weak<Mutex<RocksDBWrapperInner>>,
+}
+
+impl WeakRocksDBWrapper {
+
+    pub fn upgrade(&self) -> Option<RocksDBWrapper> {
+        self.inner.upgrade().map(|inner| RocksDBWrapper { inner })
     }
 
 }
@@ -66,6 +300,12 @@ pub(crate) struct RocksDBWrapperInner {
     pub(crate) txn_db_options: *mut ffi::rocksdb_transactiondb_options_t,
     pub(crate) inner: *mut ffi::rocksdb_transactiondb_t,
     pub(crate) txn_count: AtomicU64,
+    enable_statistics: bool,
+    layout: StorageLayout,
+    compression: bool,
+    pins: HashMap<String, PinnedBlocks>,
+    /// The version of the storage the pins were read at.
+    pins_version: u64,
 }
 
 unsafe impl Send for RocksDBWrapperInner {}
@@ -73,12 +313,53 @@ unsafe impl Sync for RocksDBWrapperInner {}
 
 impl RocksDBWrapperInner {
 
-    pub fn open(path: &Path) -> Result<RocksDBWrapperInner> {
+    /// An existing database keeps the layout it was created with,
+    /// the write-ahead log must be read from where it was written.
+    fn detect_layout(path: &Path, config: &Config) -> StorageLayout {
+        if !path.join("CURRENT").exists() {
+            config.storage_layout
+        } else if path.join(WAL_DIR).is_dir() {
+            StorageLayout::Directory
+        } else {
+            StorageLayout::Single
+        }
+    }
+
+    pub fn open(path: &Path, config: &Config) -> Result<RocksDBWrapperInner> {
+        let layout = RocksDBWrapperInner::detect_layout(path, config);
+        let wal_dir = path.join(WAL_DIR);
         let path: String = path.to_str().unwrap().into();
+        let enable_statistics = config.enable_statistics;
         unsafe {
             let txn_db_opts = ffi::rocksdb_transactiondb_options_create();
             let options = ffi::rocksdb_options_create();
             ffi::rocksdb_options_set_create_if_missing(options, 1);
+            let compression = ffi::rocksdb_options_get_compression(options) != ffi::rocksdb_no_compression as c_int;
+
+            // the table factory keeps a copy of the table options
+            let table_options = ffi::rocksdb_block_based_options_create();
+            ffi::rocksdb_block_based_options_set_block_restart_interval(
+                table_options,
+                i32::try_from(config.key_restart_interval.max(1)).unwrap_or(i32::MAX),
+            );
+            ffi::rocksdb_options_set_block_based_table_factory(options, table_options);
+            ffi::rocksdb_block_based_options_destroy(table_options);
+
+            // the options own the merge operator
+            ffi::rocksdb_options_set_merge_operator(options, document_delta::create_merge_operator());
+
+            if enable_statistics {
+                ffi::rocksdb_options_enable_statistics(options);
+            }
+
+            if layout == StorageLayout::Directory {
+                let wal_dir_c = CString::new(wal_dir.to_str().unwrap()).unwrap();
+                ffi::rocksdb_options_set_wal_dir(options, wal_dir_c.as_ptr());
+                ffi::rocksdb_options_set_enable_blob_files(options, 1);
+                ffi::rocksdb_options_set_min_blob_size(options, config.min_blob_size);
+                ffi::rocksdb_options_set_enable_blob_gc(options, 1);
+            }
+
             let mut err: *mut c_char = ptr::null_mut();
             let path_c = CString::new(path.clone()).unwrap();
             let db = ffi::rocksdb_transactiondb_open(options, txn_db_opts, path_c.as_ptr(), &mut err);
@@ -89,10 +370,31 @@ impl RocksDBWrapperInner {
                 txn_db_options: txn_db_opts,
                 inner: db,
                 txn_count: AtomicU64::new(0),
+                enable_statistics,
+                layout,
+                compression,
+                pins: HashMap::new(),
+                pins_version: 0,
             })
         }
     }
 
+    /// The number of the version of the files and the memtables of the storage,
+    /// it changes with every flush and compaction.
+    fn super_version(&self) -> u64 {
+        let mut version = 0;
+        unsafe {
+            let base_db = ffi::rocksdb_transactiondb_get_base_db(self.inner);
+            ffi::rocksdb_property_int(
+                base_db,
+                b"rocksdb.current-super-version-number\0".as_ptr() as *const c_char,
+                &mut version,
+            );
+            ffi::rocksdb_transactiondb_close_base_db(base_db);
+        }
+        version
+    }
+
 }
 
 impl Drop for RocksDBWrapperInner {
@@ -101,6 +403,8 @@ impl Drop for RocksDBWrapperInner {
             if self.txn_count.load(Ordering::SeqCst) != 0 {
                 panic!("there are still transactions opened")
             }
+            // the iterators must be released before the database is closed
+            self.pins.clear();
             let mut err: *mut c_char = ptr::null_mut();
 
             {
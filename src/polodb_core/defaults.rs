@@ -0,0 +1,102 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bson::{doc, Bson, DateTime, Document};
+use indexmap::IndexMap;
+use crate::cursor::Cursor;
+use crate::errors::Error;
+use crate::options::FieldDefault;
+use crate::transaction::TransactionInner;
+use crate::Result;
+
+/// The last number of a sequence of a collection,
+/// `[$SEQUENCE, <collection>, <field>] -> { last: <i64> }`.
+const SEQUENCE_PREFIX: &str = "$SEQUENCE";
+
+fn sequences_prefix(col_name: &str) -> Result<Vec<u8>> {
+    crate::utils::bson::stacked_key([
+        &Bson::String(SEQUENCE_PREFIX.to_string()),
+        &Bson::String(col_name.to_string()),
+    ])
+}
+
+fn sequence_key(col_name: &str, field: &str) -> Result<Vec<u8>> {
+    crate::utils::bson::stacked_key([
+        &Bson::String(SEQUENCE_PREFIX.to_string()),
+        &Bson::String(col_name.to_string()),
+        &Bson::String(field.to_string()),
+    ])
+}
+
+/// Only the top-level fields, except `_id`, can have a default value.
+pub(crate) fn check(defaults: &IndexMap<String, FieldDefault>) -> Result<()> {
+    for field in defaults.keys() {
+        if field.is_empty() || field == "_id" || field.starts_with('$') || field.contains('.') {
+            return Err(Error::IllegalDefaultField(field.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Set the fields omitted by the document to their default value,
+/// the sequences are advanced in the transaction of the insert.
+pub(crate) fn apply(
+    txn: &TransactionInner,
+    col_name: &str,
+    defaults: &IndexMap<String, FieldDefault>,
+    doc: &mut Document,
+) -> Result<()> {
+    let mut now = None;
+    for (field, default) in defaults {
+        if doc.contains_key(field) {
+            continue;
+        }
+        let value = match default {
+            FieldDefault::Value(value) => value.clone(),
+            FieldDefault::Now => Bson::DateTime(*now.get_or_insert_with(DateTime::now)),
+            FieldDefault::Sequence => Bson::Int64(next_sequence(txn, col_name, field)?),
+        };
+        doc.insert(field.clone(), value);
+    }
+    Ok(())
+}
+
+fn next_sequence(txn: &TransactionInner, col_name: &str, field: &str) -> Result<i64> {
+    let key = sequence_key(col_name, field)?;
+    let last = match txn.rocksdb_txn.get(&key)? {
+        Some(data) => bson::from_slice::<Document>(&data)?.get_i64("last").unwrap_or(0),
+        None => 0,
+    };
+    let next = last + 1;
+    txn.put(&key, &bson::to_vec(&doc! { "last": next })?)?;
+    Ok(next)
+}
+
+/// Move the sequences of a renamed collection.
+pub(crate) fn rename(txn: &TransactionInner, old_name: &str, new_name: &str) -> Result<()> {
+    txn.move_prefix(sequences_prefix(old_name)?, &sequences_prefix(new_name)?)
+}
+
+/// Remove the sequences of a dropped collection.
+pub(crate) fn drop(txn: &TransactionInner, col_name: &str) -> Result<()> {
+    let mut cursor = Cursor::new(sequences_prefix(col_name)?, txn.rocksdb_txn.new_iterator());
+    cursor.reset()?;
+    while cursor.has_next() {
+        if let Some(key) = cursor.peek_key() {
+            txn.delete(key.as_ref())?;
+        }
+        cursor.next()?;
+    }
+    Ok(())
+}
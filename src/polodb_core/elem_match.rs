@@ -0,0 +1,281 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bson::{Bson, Document, Regex};
+use regex::RegexBuilder;
+use crate::errors::RegexError;
+use crate::options::Collation;
+use crate::utils::bson::try_get_document_value;
+use crate::vm::{field_cmp, regex_matches, value_in, DbOp};
+use crate::Result;
+
+/// The conditions of an `$elemMatch`, all satisfied by the same element of an array.
+///
+/// The conditions are on the element itself, such as `{ "$gte": 80, "$lt": 85 }`,
+/// or on the fields of the documents of the array, such as
+/// `{ "score": { "$gt": 4 }, "user": "a" }`. The values are compared
+/// by the comparisons of the filter.
+pub(crate) enum ElemMatch {
+    Value(Vec<Condition>),
+    Fields(Vec<Clause>),
+}
+
+pub(crate) enum Clause {
+    Field(String, Vec<Condition>),
+    And(Vec<Vec<Clause>>),
+    Or(Vec<Vec<Clause>>),
+    Nor(Vec<Vec<Clause>>),
+}
+
+pub(crate) enum Condition {
+    Eq(Bson),
+    Ne(Bson),
+    Gt(Bson),
+    Gte(Bson),
+    Lt(Bson),
+    Lte(Bson),
+    In(Vec<Bson>),
+    Nin(Vec<Bson>),
+    Size(i64),
+    Regex(regex::Regex),
+    Not(Vec<Condition>),
+    ElemMatch(Box<ElemMatch>),
+}
+
+impl ElemMatch {
+
+    /// Parse the query of an `$elemMatch`, the error is the name of the invalid field.
+    pub(crate) fn parse(query: &Document) -> std::result::Result<ElemMatch, String> {
+        let is_logical = |key: &str| matches!(key, "$and" | "$or" | "$nor");
+        let on_value = !query.is_empty() && query.keys().all(|key| key.starts_with('$') && !is_logical(key));
+        if on_value {
+            Ok(ElemMatch::Value(parse_conditions(query)?))
+        } else {
+            Ok(ElemMatch::Fields(parse_clauses(query)?))
+        }
+    }
+
+    /// Parse the elements removed by a `$pull`: the conditions of an `$elemMatch`,
+    /// or the value they are equal to.
+    pub(crate) fn parse_element(condition: &Bson) -> std::result::Result<ElemMatch, String> {
+        match condition {
+            Bson::Document(query) if !query.is_empty() => ElemMatch::parse(query),
+            Bson::RegularExpression(re) => {
+                let re = build_regex(re).map_err(|_| "$regex".to_string())?;
+                Ok(ElemMatch::Value(vec![Condition::Regex(re)]))
+            }
+            _ => Ok(ElemMatch::Value(vec![Condition::Eq(condition.clone())])),
+        }
+    }
+
+    /// Whether `value` is an array with an element satisfying all the conditions.
+    pub(crate) fn matches(&self, value: &Bson, collation: Collation) -> bool {
+        match value {
+            Bson::Array(arr) => arr.iter().any(|item| self.matches_element(item, collation)),
+            _ => false,
+        }
+    }
+
+    /// Whether the element of an array satisfies all the conditions.
+    pub(crate) fn matches_element(&self, item: &Bson, collation: Collation) -> bool {
+        match self {
+            ElemMatch::Value(conditions) => conditions_match(conditions, item, collation),
+            ElemMatch::Fields(clauses) => match item {
+                Bson::Document(doc) => clauses_match(clauses, doc, collation),
+                _ => false,
+            },
+        }
+    }
+
+}
+
+fn parse_clauses(query: &Document) -> std::result::Result<Vec<Clause>, String> {
+    let mut clauses = Vec::with_capacity(query.len());
+    for (key, value) in query {
+        let clause = match key.as_str() {
+            "$and" | "$or" | "$nor" => {
+                let items = match value {
+                    Bson::Array(items) if !items.is_empty() => items,
+                    _ => return Err(key.clone()),
+                };
+                let queries = items.iter()
+                    .map(|item| match item {
+                        Bson::Document(doc) => parse_clauses(doc),
+                        _ => Err(key.clone()),
+                    })
+                    .collect::<std::result::Result<Vec<Vec<Clause>>, String>>()?;
+                match key.as_str() {
+                    "$and" => Clause::And(queries),
+                    "$or" => Clause::Or(queries),
+                    _ => Clause::Nor(queries),
+                }
+            }
+            _ if key.starts_with('$') => return Err(key.clone()),
+            _ => {
+                let conditions = match value {
+                    Bson::Document(doc) if doc.keys().next().is_some_and(|key| key.starts_with('$')) => {
+                        parse_conditions(doc)?
+                    }
+                    Bson::RegularExpression(re) => vec![Condition::Regex(build_regex(re).map_err(|_| key.clone())?)],
+                    Bson::Array(_) => return Err(key.clone()),
+                    _ => vec![Condition::Eq(value.clone())],
+                };
+                Clause::Field(key.clone(), conditions)
+            }
+        };
+        clauses.push(clause);
+    }
+    Ok(clauses)
+}
+
+fn parse_conditions(query: &Document) -> std::result::Result<Vec<Condition>, String> {
+    let mut conditions = Vec::with_capacity(query.len());
+    for (key, value) in query {
+        let condition = match (key.as_str(), value) {
+            ("$eq", _) => Condition::Eq(value.clone()),
+            ("$ne", _) => Condition::Ne(value.clone()),
+            ("$gt", _) => Condition::Gt(value.clone()),
+            ("$gte", _) => Condition::Gte(value.clone()),
+            ("$lt", _) => Condition::Lt(value.clone()),
+            ("$lte", _) => Condition::Lte(value.clone()),
+            ("$in", Bson::Array(values)) => Condition::In(values.clone()),
+            ("$nin", Bson::Array(values)) => Condition::Nin(values.clone()),
+            ("$size", Bson::Int32(size)) => Condition::Size(i64::from(*size)),
+            ("$size", Bson::Int64(size)) => Condition::Size(*size),
+            ("$regex", Bson::RegularExpression(re)) => {
+                Condition::Regex(build_regex(re).map_err(|_| key.clone())?)
+            }
+            ("$not", Bson::Document(doc)) => Condition::Not(parse_conditions(doc)?),
+            ("$elemMatch", Bson::Document(doc)) => Condition::ElemMatch(Box::new(ElemMatch::parse(doc)?)),
+            _ => return Err(key.clone()),
+        };
+        conditions.push(condition);
+    }
+    Ok(conditions)
+}
+
+fn clauses_match(clauses: &[Clause], doc: &Document, collation: Collation) -> bool {
+    clauses.iter().all(|clause| match clause {
+        // like the filter, a missing field matches no condition
+        Clause::Field(key, conditions) => match try_get_document_value(doc, key) {
+            Some(value) => conditions_match(conditions, &value, collation),
+            None => false,
+        },
+        Clause::And(queries) => queries.iter().all(|query| clauses_match(query, doc, collation)),
+        Clause::Or(queries) => queries.iter().any(|query| clauses_match(query, doc, collation)),
+        Clause::Nor(queries) => !queries.iter().any(|query| clauses_match(query, doc, collation)),
+    })
+}
+
+fn conditions_match(conditions: &[Condition], value: &Bson, collation: Collation) -> bool {
+    conditions.iter().all(|condition| condition_matches(condition, value, collation))
+}
+
+fn condition_matches(condition: &Condition, value: &Bson, collation: Collation) -> bool {
+    // the values the filter fails to compare satisfy no comparison
+    let cmp = |op: DbOp, other: &Bson| field_cmp(op, value, other, collation).unwrap_or(false);
+    match condition {
+        Condition::Eq(other) => cmp(DbOp::Equal, other),
+        Condition::Ne(other) => !cmp(DbOp::Equal, other),
+        Condition::Gt(other) => cmp(DbOp::Greater, other),
+        Condition::Gte(other) => cmp(DbOp::GreaterEqual, other),
+        Condition::Lt(other) => cmp(DbOp::Less, other),
+        Condition::Lte(other) => cmp(DbOp::LessEqual, other),
+        Condition::In(values) => value_in(value, values, collation),
+        Condition::Nin(values) => !value_in(value, values, collation),
+        Condition::Size(size) => matches!(value, Bson::Array(arr) if arr.len() as i64 == *size),
+        Condition::Regex(re) => regex_matches(re, value),
+        Condition::Not(conditions) => !conditions_match(conditions, value, collation),
+        Condition::ElemMatch(elem_match) => elem_match.matches(value, collation),
+    }
+}
+
+/// Build the regular expression of a BSON regex, with its options.
+pub(crate) fn build_regex(re: &Regex) -> Result<regex::Regex> {
+    let mut re_build = RegexBuilder::new(re.pattern.as_str());
+    for char in re.options.chars() {
+        match char {
+            'i' => {
+                re_build.case_insensitive(true);
+            }
+            'm' => {
+                re_build.multi_line(true);
+            }
+            's' => {
+                re_build.dot_matches_new_line(true);
+            }
+            'u' => {
+                re_build.unicode(true);
+            }
+            'U' => {
+                re_build.swap_greed(true);
+            }
+            'x' => {
+                re_build.ignore_whitespace(true);
+            }
+            _ => {
+                return Err(RegexError {
+                    error: format!("unknown regex option: {}", char),
+                    expression: re.pattern.clone(),
+                    options: re.options.clone(),
+                }.into());
+            }
+        }
+    }
+
+    re_build.build().map_err(|err| {
+        RegexError {
+            error: format!("regex build error: {err}"),
+            expression: re.pattern.clone(),
+            options: re.options.clone(),
+        }.into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{doc, Bson};
+    use crate::options::Collation;
+    use super::ElemMatch;
+
+    fn matches(query: bson::Document, value: Bson) -> bool {
+        ElemMatch::parse(&query).unwrap().matches(&value, Collation::Simple)
+    }
+
+    #[test]
+    fn test_elem_match() {
+        let reviews = Bson::Array(vec![
+            Bson::Document(doc! { "user": "a", "score": 3 }),
+            Bson::Document(doc! { "user": "b", "score": 5 }),
+        ]);
+        assert!(matches(doc! { "user": "b", "score": { "$gt": 4 } }, reviews.clone()));
+        assert!(!matches(doc! { "user": "a", "score": { "$gt": 4 } }, reviews.clone()));
+        assert!(matches(doc! { "$or": [{ "user": "c" }, { "score": 3 }] }, reviews.clone()));
+
+        let scores = Bson::Array(vec![Bson::Int32(70), Bson::Int32(90)]);
+        assert!(matches(doc! { "$gte": 80, "$lt": 95 }, scores.clone()));
+        assert!(!matches(doc! { "$gt": 70, "$lt": 90 }, scores));
+        assert!(!matches(doc! { "$gt": 70 }, Bson::Int32(90)));
+
+        // the arrays of the elements are compared like the filter compares them
+        let items = Bson::Array(vec![Bson::Document(doc! { "sizes": [1, 20], "tags": ["a", "b"] })]);
+        assert!(matches(doc! { "sizes": { "$gt": 5 } }, items.clone()));
+        assert!(matches(doc! { "sizes": 20, "tags": "b" }, items.clone()));
+        assert!(!matches(doc! { "sizes": { "$gt": 30 } }, items));
+
+        assert!(ElemMatch::parse(&doc! { "$gt": 1, "user": "a" }).is_err());
+        assert!(ElemMatch::parse(&doc! { "score": { "$unknown": 1 } }).is_err());
+    }
+
+}
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use bson::ser::Error as BsonErr;
-use bson::Document;
+use bson::{Bson, Document};
 use std::fmt;
 use std::io;
 use std::string::FromUtf8Error;
@@ -142,6 +142,50 @@ pub struct DuplicateKeyError {
     pub ns: String,   // collection name
 }
 
+/// Which limit a document exceeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentLimit {
+    /// The size of the encoded document, in bytes.
+    Size,
+    /// The nesting depth of the documents and arrays.
+    Depth,
+}
+
+impl fmt::Display for DocumentLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DocumentLimit::Size => write!(f, "size"),
+            DocumentLimit::Depth => write!(f, "nesting depth"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DocumentLimitError {
+    pub ns: String, // collection name
+    pub limit: DocumentLimit,
+    pub max: u64,
+    pub actual: u64,
+}
+
+#[derive(Debug)]
+pub struct InvalidBsonError {
+    pub ns: String,   // collection name
+    pub path: String, // path of the offending value
+    pub reason: &'static str,
+}
+
+/// A stored document a lenient cursor fails to decode, see [`crate::options::DecodeMode`].
+#[derive(Debug, Clone)]
+pub struct DecodeError {
+    pub ns: String, // collection name
+    /// The `_id` of the document, read from its key.
+    pub id: Option<Bson>,
+    /// The document as it is stored.
+    pub data: Vec<u8>,
+    pub reason: String,
+}
+
 #[derive(Debug)]
 pub struct RegexError {
     pub error: String,
@@ -149,6 +193,69 @@ pub struct RegexError {
     pub options: String,
 }
 
+/// The stable, machine-readable category of an [`Error`], to branch on
+/// without matching the messages. The numbers never change between versions.
+#[repr(u32)]
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    Internal = 1,
+    InvalidArgument = 2,
+    TypeMismatch = 3,
+    NotFound = 4,
+    AlreadyExists = 5,
+    DuplicateKey = 6,
+    WriteConflict = 7,
+    Corruption = 8,
+    QuotaExceeded = 9,
+    ValidationFailed = 10,
+    Killed = 11,
+    Closed = 12,
+    Busy = 13,
+    Transaction = 14,
+    Io = 15,
+    Encoding = 16,
+    Unsupported = 17,
+    HistoryLost = 18,
+}
+
+impl ErrorCode {
+    /// The number of the code.
+    pub fn as_u32(self) -> u32 {
+        self as u32
+    }
+
+    /// The name of the code, such as `"DuplicateKey"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorCode::Internal => "Internal",
+            ErrorCode::InvalidArgument => "InvalidArgument",
+            ErrorCode::TypeMismatch => "TypeMismatch",
+            ErrorCode::NotFound => "NotFound",
+            ErrorCode::AlreadyExists => "AlreadyExists",
+            ErrorCode::DuplicateKey => "DuplicateKey",
+            ErrorCode::WriteConflict => "WriteConflict",
+            ErrorCode::Corruption => "Corruption",
+            ErrorCode::QuotaExceeded => "QuotaExceeded",
+            ErrorCode::ValidationFailed => "ValidationFailed",
+            ErrorCode::Killed => "Killed",
+            ErrorCode::Closed => "Closed",
+            ErrorCode::Busy => "Busy",
+            ErrorCode::Transaction => "Transaction",
+            ErrorCode::Io => "Io",
+            ErrorCode::Encoding => "Encoding",
+            ErrorCode::Unsupported => "Unsupported",
+            ErrorCode::HistoryLost => "HistoryLost",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("unexpected id type, expected: {0}, actual: {1}")]
@@ -163,6 +270,12 @@ pub enum Error {
     InvalidOrderOfIndex(String),
     #[error("index for '{0}' already exists")]
     IndexAlreadyExists(String),
+    #[error("index '{0}' does not exist")]
+    IndexNotFound(String),
+    #[error("invalid continuation token: '{0}'")]
+    InvalidContinuationToken(String),
+    #[error("invalid index bounds: {0}")]
+    InvalidIndexBounds(String),
     #[error("{0}")]
     FieldTypeUnexpected(Box<FieldTypeUnexpectedStruct>),
     #[error("unexpected type: {} for op: {}, expected: {}", .0.actual_ty, .0.operation, .0.expected_ty)]
@@ -180,6 +293,8 @@ pub enum Error {
     BsonErr(Box<BtWrapper<BsonErr>>),
     #[error("bson de error: {0}")]
     BsonDeErr(Box<bson::de::Error>),
+    #[error("failed to decode a document of '{}': {}", .0.ns, .0.reason)]
+    DecodeFailed(Box<DecodeError>),
     #[error("data size too large, expected: {0}, actual: {1}")]
     DataSizeTooLarge(u32, u32),
     #[error("decode EOF")]
@@ -216,6 +331,8 @@ pub enum Error {
     IllegalCollectionName(String),
     #[error("index name '{0}' is illegal")]
     IllegalIndexName(String),
+    #[error("the field '{0}' can not have a default value")]
+    IllegalDefaultField(String),
     #[error("unexpected page header")]
     UnexpectedPageHeader,
     #[error("unexpected page type")]
@@ -226,12 +343,32 @@ pub enum Error {
     BufferNotEnough(usize),
     #[error("unknown update operation: '{0}'")]
     UnknownUpdateOperation(String),
+    #[error("invalid update path: {0}")]
+    InvalidUpdatePath(String),
     #[error("can not increment a field which is null")]
     IncrementNullField,
     #[error("VM can not execute because it's halt")]
     VmIsHalt,
+    #[error("the operation was killed")]
+    OperationKilled,
+    #[error("the write is rejected by a hook: {0}")]
+    HookRejected(String),
+    #[error("operation not allowed on a capped collection: {0}")]
+    CappedCollection(String),
+    #[error("document failed validation: {0}")]
+    DocumentValidationFailed(String),
+    #[error("invalid $jsonSchema: {0}")]
+    InvalidJsonSchema(String),
+    #[error("the audit collection '{0}' is append-only")]
+    AuditLogAppendOnly(String),
+    #[error("the changes after the resume token {0} are no longer in the oplog")]
+    ChangeStreamHistoryLost(u64),
     #[error("collection name '{0}' already exists")]
     CollectionAlreadyExits(String),
+    #[error("a database is already attached as '{0}'")]
+    DatabaseAlreadyAttached(String),
+    #[error("no database is attached as '{0}'")]
+    DatabaseNotAttached(String),
     #[error("it's illegal to update '_id' field")]
     UnableToUpdatePrimaryKey,
     #[error("the file is not a valid database")]
@@ -242,6 +379,16 @@ pub enum Error {
     DatabaseOccupied,
     #[error("multiple errors")]
     Multiple(Vec<Error>),
+    #[error("the database is a read-only replica")]
+    ReadOnlyReplica,
+    #[error("the database is a read-only snapshot")]
+    ReadOnlySnapshot,
+    #[error("invalid snapshot {0}")]
+    InvalidSnapshot(String),
+    #[error("replication error: {0}")]
+    Replication(String),
+    #[error("sync error: {0}")]
+    Sync(String),
     #[error("db version mismatched, please upgrade")]
     VersionMismatch(Box<VersionMismatchError>),
     #[error("the mutex is poisoned")]
@@ -262,6 +409,8 @@ pub enum Error {
     OnlySupportSingleFieldIndexes(Box<Document>),
     #[error("only support ascending order index currently: {0}")]
     OnlySupportsAscendingOrder(String),
+    #[error("the index option '{0}' is not supported yet")]
+    UnsupportedIndexOption(String),
     #[error("duplicate key error collection: {}, index: {}, key: {}", .0.ns, .0.name, .0.key)]
     DuplicateKey(Box<DuplicateKeyError>),
     #[error("the element type {0} is unknown")]
@@ -274,13 +423,197 @@ pub enum Error {
     InvalidAggregationStage(Box<Document>),
     #[error("rocks db error: {0}")]
     RocksDbErr(String),
+    #[error("document of '{}' exceeds the maximum {}: {} > {}", .0.ns, .0.limit, .0.actual, .0.max)]
+    DocumentLimitExceeded(Box<DocumentLimitError>),
+    #[error("invalid value at '{}' in a document of '{}': {}", .0.path, .0.ns, .0.reason)]
+    InvalidBson(Box<InvalidBsonError>),
     #[error("$set value is not a document")]
     SetIsNotADocument,
     #[error("the field '{0}' is not a valid field name")]
     UpsertError(String),
+    #[error("file '{0}' not found")]
+    FileNotFound(String),
+    #[error("invalid GeoJSON geometry: {0}")]
+    InvalidGeometry(String),
+    #[error("invalid vector: {0}")]
+    InvalidVector(String),
+    #[error("the collection '{0}' is not a materialized view")]
+    NotMaterializedView(String),
+    #[error("the view '{0}' is read-only")]
+    ReadOnlyView(String),
+    #[error("the content of file '{0}' doesn't match its length or checksum")]
+    FileCorrupted(String),
+    #[cfg(feature = "arrow")]
+    #[error("arrow error: {0}")]
+    ArrowError(Box<arrow_schema::ArrowError>),
+    #[cfg(feature = "parquet")]
+    #[error("parquet error: {0}")]
+    ParquetError(Box<parquet::errors::ParquetError>),
+    #[cfg(feature = "sql")]
+    #[error("sql error: {0}")]
+    SqlError(String),
 }
 
 impl Error {
+    /// The category of the error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::Multiple(errors) => errors.first().map_or(ErrorCode::Internal, Error::code),
+            Error::DuplicateKey(_) => ErrorCode::DuplicateKey,
+            Error::RocksDbErr(msg) => rocksdb_error_code(msg),
+
+            Error::CollectionNotFound(_)
+            | Error::IndexNotFound(_)
+            | Error::FileNotFound(_)
+            | Error::DatabaseNotAttached(_) => ErrorCode::NotFound,
+
+            Error::CollectionAlreadyExits(_)
+            | Error::IndexAlreadyExists(_)
+            | Error::DatabaseAlreadyAttached(_)
+            | Error::DataExist(_) => ErrorCode::AlreadyExists,
+
+            Error::UnexpectedIdType(_, _)
+            | Error::NotAValidKeyType(_)
+            | Error::InvalidField(_)
+            | Error::InvalidOrderOfIndex(_)
+            | Error::ParseError(_)
+            | Error::InvalidContinuationToken(_)
+            | Error::InvalidIndexBounds(_)
+            | Error::DataHasNoPrimaryKey
+            | Error::IllegalCollectionName(_)
+            | Error::NotMaterializedView(_)
+            | Error::IllegalIndexName(_)
+            | Error::IllegalDefaultField(_)
+            | Error::UnknownUpdateOperation(_)
+            | Error::InvalidUpdatePath(_)
+            | Error::InvalidJsonSchema(_)
+            | Error::UnableToUpdatePrimaryKey
+            | Error::RegexError(_)
+            | Error::UnknownAggregationOperation(_)
+            | Error::InvalidAggregationStage(_)
+            | Error::SetIsNotADocument
+            | Error::UpsertError(_) => ErrorCode::InvalidArgument,
+
+            Error::FieldTypeUnexpected(_)
+            | Error::UnexpectedTypeForOp(_)
+            | Error::IncrementNullField
+            | Error::CannotApplyOperation(_) => ErrorCode::TypeMismatch,
+
+            Error::ChecksumMismatch
+            | Error::JournalPageSizeMismatch(_, _)
+            | Error::SaltMismatch
+            | Error::PageMagicMismatch(_)
+            | Error::MetaPageIdError
+            | Error::UnexpectedPageHeader
+            | Error::UnexpectedPageType
+            | Error::UnknownTransactionType
+            | Error::NotAValidDatabase
+            | Error::FileCorrupted(_)
+            | Error::InvalidSnapshot(_)
+            | Error::DecodeEOF => ErrorCode::Corruption,
+
+            Error::DataSizeTooLarge(_, _)
+            | Error::DataOverflow
+            | Error::PageSpaceNotEnough
+            | Error::ItemSizeGreaterThanExpected
+            | Error::BufferNotEnough(_)
+            | Error::DocumentLimitExceeded(_) => ErrorCode::QuotaExceeded,
+
+            Error::ValidationError(_)
+            | Error::HookRejected(_)
+            | Error::DocumentValidationFailed(_)
+            | Error::InvalidGeometry(_)
+            | Error::InvalidVector(_)
+            | Error::InvalidBson(_) => ErrorCode::ValidationFailed,
+
+            Error::OperationKilled => ErrorCode::Killed,
+
+            Error::DbIsClosed
+            | Error::DbNotReady => ErrorCode::Closed,
+
+            Error::Busy
+            | Error::DatabaseOccupied => ErrorCode::Busy,
+
+            Error::CannotWriteDbWithoutTransaction
+            | Error::StartTransactionInAnotherTransaction
+            | Error::RollbackNotInTransaction
+            | Error::NoTransactionStarted
+            | Error::SessionOutdated => ErrorCode::Transaction,
+
+            Error::IOErr(_)
+            | Error::Replication(_)
+            | Error::Sync(_) => ErrorCode::Io,
+
+            Error::UTF8Err { .. }
+            | Error::BsonErr(_)
+            | Error::BsonDeErr(_)
+            | Error::DecodeFailed(_)
+            | Error::FromUtf8Error(_)
+            | Error::UnknownBsonElementType(_) => ErrorCode::Encoding,
+
+            Error::CappedCollection(_)
+            | Error::AuditLogAppendOnly(_)
+            | Error::ReadOnlyView(_)
+            | Error::VersionMismatch(_)
+            | Error::ReadOnlyReplica
+            | Error::ReadOnlySnapshot
+            | Error::OnlySupportSingleFieldIndexes(_)
+            | Error::OnlySupportsAscendingOrder(_)
+            | Error::UnsupportedIndexOption(_) => ErrorCode::Unsupported,
+
+            Error::ChangeStreamHistoryLost(_) => ErrorCode::HistoryLost,
+
+            Error::VmIsHalt
+            | Error::LockError => ErrorCode::Internal,
+
+            #[cfg(feature = "arrow")]
+            Error::ArrowError(_) => ErrorCode::Encoding,
+            #[cfg(feature = "parquet")]
+            Error::ParquetError(_) => ErrorCode::Encoding,
+            #[cfg(feature = "sql")]
+            Error::SqlError(_) => ErrorCode::InvalidArgument,
+        }
+    }
+
+    /// The collection involved in the error, if any.
+    pub fn collection(&self) -> Option<&str> {
+        match self {
+            Error::DuplicateKey(err) => Some(err.ns.as_str()),
+            Error::DocumentLimitExceeded(err) => Some(err.ns.as_str()),
+            Error::InvalidBson(err) => Some(err.ns.as_str()),
+            Error::DecodeFailed(err) => Some(err.ns.as_str()),
+            Error::CollectionNotFound(name)
+            | Error::CollectionAlreadyExits(name)
+            | Error::IllegalCollectionName(name)
+            | Error::AuditLogAppendOnly(name)
+            | Error::ReadOnlyView(name) => Some(name.as_str()),
+            Error::Multiple(errors) => errors.first().and_then(Error::collection),
+            _ => None,
+        }
+    }
+
+    /// The index involved in the error, if any.
+    pub fn index(&self) -> Option<&str> {
+        match self {
+            Error::DuplicateKey(err) => Some(err.name.as_str()),
+            Error::IndexNotFound(name)
+            | Error::IndexAlreadyExists(name)
+            | Error::IllegalIndexName(name) => Some(name.as_str()),
+            Error::Multiple(errors) => errors.first().and_then(Error::index),
+            _ => None,
+        }
+    }
+
+    /// The offending key of the error, if any.
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            Error::DuplicateKey(err) => Some(err.key.as_str()),
+            Error::DataExist(key) => Some(key.as_str()),
+            Error::Multiple(errors) => errors.first().and_then(Error::key),
+            _ => None,
+        }
+    }
+
     pub(crate) fn add(self, next: Error) -> Error {
         match self {
             Error::Multiple(mut result) => {
@@ -295,6 +628,27 @@ impl Error {
     }
 }
 
+/// RocksDB reports its errors as the message of its status,
+/// the prefix of the message tells the kind of the status.
+fn rocksdb_error_code(msg: &str) -> ErrorCode {
+    const PREFIXES: [(&str, ErrorCode); 10] = [
+        // a transaction waiting for the lock of a key written by another one
+        ("Resource busy", ErrorCode::WriteConflict),
+        ("Operation timed out", ErrorCode::WriteConflict),
+        ("Operation aborted", ErrorCode::WriteConflict),
+        ("Operation expired", ErrorCode::WriteConflict),
+        ("Operation failed. Try again.", ErrorCode::WriteConflict),
+        ("Corruption", ErrorCode::Corruption),
+        ("IO error", ErrorCode::Io),
+        ("Invalid argument", ErrorCode::InvalidArgument),
+        ("Not implemented", ErrorCode::Unsupported),
+        ("Shutdown in progress", ErrorCode::Closed),
+    ];
+    PREFIXES.iter()
+        .find(|(prefix, _)| msg.starts_with(prefix))
+        .map_or(ErrorCode::Internal, |(_, code)| *code)
+}
+
 impl From<bson::de::Error> for Error {
     fn from(error: bson::de::Error) -> Self {
         Error::BsonDeErr(Box::new(error))
@@ -343,13 +697,37 @@ impl From<RegexError> for Error {
     }
 }
 
+#[cfg(feature = "arrow")]
+impl From<arrow_schema::ArrowError> for Error {
+    fn from(value: arrow_schema::ArrowError) -> Self {
+        Error::ArrowError(Box::new(value))
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for Error {
+    fn from(value: parquet::errors::ParquetError) -> Self {
+        Error::ParquetError(Box::new(value))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::Error;
+    use crate::{Error, ErrorCode};
 
     #[test]
     fn print_value_size() {
         let size = std::mem::size_of::<Error>();
         assert_eq!(size, 32);
     }
+
+    #[test]
+    fn test_rocksdb_error_code() {
+        let err = Error::RocksDbErr("Resource busy: ".to_string());
+        assert_eq!(err.code(), ErrorCode::WriteConflict);
+        let err = Error::RocksDbErr("Corruption: block checksum mismatch".to_string());
+        assert_eq!(err.code(), ErrorCode::Corruption);
+        let err = Error::Multiple(vec![Error::OperationKilled, Error::DbIsClosed]);
+        assert_eq!(err.code(), ErrorCode::Killed);
+    }
 }
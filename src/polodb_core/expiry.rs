@@ -0,0 +1,74 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bson::{Bson, DateTime, Document};
+use crate::coll::collection_info::CollectionSpecification;
+
+/// Hide the expired documents from a VM, or, for the reaper, the documents
+/// which are not expired.
+///
+/// A document is expired once the date of the expiry field of the collection
+/// is past, or the date of the field of a TTL index is older than the TTL.
+/// The documents without the fields, or with values which are not dates, never expire.
+pub(crate) struct Expiry {
+    /// The fields holding a date and the delay after which the document expires, in milliseconds.
+    rules: Vec<(String, i64)>,
+    now: DateTime,
+    reap: bool,
+}
+
+impl Expiry {
+
+    /// The expiry of the collection, `None` if its documents never expire.
+    pub(crate) fn of_collection(col_spec: &CollectionSpecification) -> Option<Expiry> {
+        let mut rules = Vec::new();
+        if let Some(field) = &col_spec.expire_at_field {
+            rules.push((field.clone(), 0));
+        }
+        for index_info in col_spec.indexes.values() {
+            if let Some(secs) = index_info.expire_after_secs() {
+                let (field, _order) = index_info.keys.iter().next().unwrap();
+                let millis = (secs.min(i64::MAX as u64) as i64).saturating_mul(1000);
+                rules.push((field.clone(), millis));
+            }
+        }
+        if rules.is_empty() {
+            return None;
+        }
+        Some(Expiry {
+            rules,
+            now: DateTime::now(),
+            reap: false,
+        })
+    }
+
+    pub(crate) fn reaper(col_spec: &CollectionSpecification) -> Option<Expiry> {
+        Expiry::of_collection(col_spec).map(|expiry| Expiry {
+            reap: true,
+            ..expiry
+        })
+    }
+
+    fn is_expired(&self, doc: &Document) -> bool {
+        self.rules.iter().any(|(field, after)| match crate::utils::bson::try_get_document_value(doc, field) {
+            Some(Bson::DateTime(date)) => date.timestamp_millis().saturating_add(*after) <= self.now.timestamp_millis(),
+            _ => false,
+        })
+    }
+
+    pub(crate) fn skips(&self, doc: &Document) -> bool {
+        self.is_expired(doc) != self.reap
+    }
+
+}
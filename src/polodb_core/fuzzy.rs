@@ -0,0 +1,365 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use bson::{Bson, Document};
+use crate::coll::collection_info::CollectionSpecification;
+use crate::cursor::Cursor;
+use crate::transaction::TransactionInner;
+use crate::Result;
+
+/// The character padding the words, so their first and last characters are in as many trigrams as the others.
+const PAD: char = '\0';
+
+/// A trigram is in the windows of at most 4 characters around an edit,
+/// a word within `k` edits of a term shares all but `4 * k` of its distinct trigrams.
+const TRIGRAMS_PER_EDIT: usize = 4;
+
+type Trigram = [char; 3];
+
+/// The words of a `$fuzzy` search, each matching the words within its number of edits.
+///
+/// The search is a string, the number of edits depending on the length of each word:
+/// none up to 2 characters, 1 up to 5 characters, 2 above. Or a document with the
+/// string in `$search`, and the number of edits of all the words in `$maxEdits`.
+pub(crate) struct FuzzySearch {
+    terms: Vec<(Vec<char>, usize)>,
+}
+
+impl FuzzySearch {
+
+    pub(crate) fn parse(value: &Bson) -> Option<FuzzySearch> {
+        let (search, max_edits) = match value {
+            Bson::String(search) => (search.as_str(), None),
+            Bson::Document(doc) => {
+                let search = doc.get_str("$search").ok()?;
+                let max_edits = match doc.get("$maxEdits") {
+                    None => None,
+                    Some(Bson::Int32(edits)) if *edits >= 0 => Some(*edits as usize),
+                    Some(Bson::Int64(edits)) if *edits >= 0 => Some(*edits as usize),
+                    Some(_) => return None,
+                };
+                if doc.keys().any(|key| key != "$search" && key != "$maxEdits") {
+                    return None;
+                }
+                (search, max_edits)
+            }
+            _ => return None,
+        };
+        let terms: Vec<(Vec<char>, usize)> = words(search)
+            .map(|word| {
+                let chars: Vec<char> = word.chars().collect();
+                let edits = max_edits.unwrap_or(match chars.len() {
+                    0..=2 => 0,
+                    3..=5 => 1,
+                    _ => 2,
+                });
+                (chars, edits)
+            })
+            .collect();
+        if terms.is_empty() {
+            return None;
+        }
+        Some(FuzzySearch { terms })
+    }
+
+    /// Whether every word of the search is close to a word of the string, or of a string of the array.
+    pub(crate) fn matches(&self, value: &Bson) -> bool {
+        let mut found = vec![false; self.terms.len()];
+        FuzzySearch::visit_words(value, &mut |word: &[char]| {
+            for (found, (term, max_edits)) in found.iter_mut().zip(&self.terms) {
+                if !*found && within_edits(term, word, *max_edits) {
+                    *found = true;
+                }
+            }
+        });
+        found.iter().all(|found| *found)
+    }
+
+    fn visit_words(value: &Bson, f: &mut dyn FnMut(&[char])) {
+        match value {
+            Bson::String(s) => {
+                for word in words(s) {
+                    let chars: Vec<char> = word.chars().collect();
+                    f(&chars);
+                }
+            }
+            Bson::Array(arr) => {
+                for item in arr {
+                    FuzzySearch::visit_words(item, f);
+                }
+            }
+            _ => (),
+        }
+    }
+
+}
+
+fn words(s: &str) -> impl Iterator<Item = String> + '_ {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+}
+
+fn trigrams(word: &[char]) -> HashSet<Trigram> {
+    let mut padded = vec![PAD, PAD];
+    padded.extend_from_slice(word);
+    padded.extend_from_slice(&[PAD, PAD]);
+    padded.windows(3).map(|w| [w[0], w[1], w[2]]).collect()
+}
+
+/// Whether `a` becomes `b` with at most `max_edits` insertions, deletions,
+/// substitutions or transpositions of adjacent characters.
+pub(crate) fn within_edits(a: &[char], b: &[char], max_edits: usize) -> bool {
+    if a.len().abs_diff(b.len()) > max_edits {
+        return false;
+    }
+    // the optimal string alignment distance, row by row
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        let mut row_min = i;
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(before[j - 2] + 1);
+            }
+            current[j] = distance;
+            row_min = row_min.min(distance);
+        }
+        if row_min > max_edits {
+            return false;
+        }
+        before = std::mem::replace(&mut previous, std::mem::take(&mut current));
+        current = vec![0; b.len() + 1];
+    }
+    previous[b.len()] <= max_edits
+}
+
+#[derive(Default)]
+struct FuzzyIndexInner {
+    /// The words of the field, with the primary keys of the documents holding them.
+    words: Vec<(Vec<char>, HashSet<Vec<u8>>)>,
+    word_ids: HashMap<String, usize>,
+    /// The words holding each trigram.
+    trigrams: HashMap<Trigram, Vec<usize>>,
+}
+
+impl FuzzyIndexInner {
+
+    fn insert(&mut self, word: String, pkey: &[u8]) {
+        let id = match self.word_ids.get(&word) {
+            Some(id) => *id,
+            None => {
+                let id = self.words.len();
+                let chars: Vec<char> = word.chars().collect();
+                for trigram in trigrams(&chars) {
+                    self.trigrams.entry(trigram).or_default().push(id);
+                }
+                self.words.push((chars, HashSet::new()));
+                self.word_ids.insert(word, id);
+                id
+            }
+        };
+        let pkeys = &mut self.words[id].1;
+        if !pkeys.contains(pkey) {
+            pkeys.insert(pkey.to_vec());
+        }
+    }
+
+    /// The primary keys of the documents holding a word close to the term.
+    fn find(&self, term: &[char], max_edits: usize) -> HashSet<&[u8]> {
+        let term_trigrams = trigrams(term);
+        let threshold = term_trigrams.len().saturating_sub(TRIGRAMS_PER_EDIT * max_edits);
+        let candidates: Vec<usize> = if threshold == 0 {
+            (0..self.words.len()).collect()
+        } else {
+            let mut counts: HashMap<usize, usize> = HashMap::new();
+            for trigram in &term_trigrams {
+                for id in self.trigrams.get(trigram).into_iter().flatten() {
+                    *counts.entry(*id).or_default() += 1;
+                }
+            }
+            counts.into_iter()
+                .filter(|(_, count)| *count >= threshold)
+                .map(|(id, _)| id)
+                .collect()
+        };
+        let mut result = HashSet::new();
+        for id in candidates {
+            let (word, pkeys) = &self.words[id];
+            if within_edits(term, word, max_edits) {
+                result.extend(pkeys.iter().map(|pkey| pkey.as_slice()));
+            }
+        }
+        result
+    }
+
+}
+
+/// The trigram index of the words of a field of a collection, giving the documents
+/// a `$fuzzy` search on the field may match without reading the others.
+///
+/// Like the bloom filters, the words are only added: the documents found are
+/// still checked by the filter, a document deleted or updated since is left out.
+pub(crate) struct CollectionFuzzyIndex {
+    field: String,
+    inner: RwLock<FuzzyIndexInner>,
+}
+
+impl CollectionFuzzyIndex {
+
+    fn new(field: &str) -> CollectionFuzzyIndex {
+        CollectionFuzzyIndex {
+            field: field.to_string(),
+            inner: RwLock::new(FuzzyIndexInner::default()),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// Add the words of the field of the document.
+    pub(crate) fn insert_doc(&self, doc: &Document) {
+        let value = match crate::utils::bson::try_get_document_value(doc, &self.field) {
+            Some(value) => value,
+            None => return,
+        };
+        let pkey = match doc.get("_id").map(|id| crate::utils::bson::stacked_key([id])) {
+            Some(Ok(pkey)) => pkey,
+            _ => return,
+        };
+        let mut inner = self.inner.write().unwrap();
+        FuzzySearch::visit_words(&value, &mut |word: &[char]| {
+            inner.insert(word.iter().collect(), &pkey);
+        });
+    }
+
+    /// The encoded primary keys of the documents which may match the search,
+    /// in the reverse order of the keys.
+    pub(crate) fn candidates(&self, search: &FuzzySearch) -> Vec<Vec<u8>> {
+        let inner = self.inner.read().unwrap();
+        let mut result: Option<HashSet<&[u8]>> = None;
+        for (term, max_edits) in &search.terms {
+            let found = inner.find(term, *max_edits);
+            result = Some(match result {
+                Some(result) => result.intersection(&found).copied().collect(),
+                None => found,
+            });
+        }
+        let mut pkeys: Vec<Vec<u8>> = result.unwrap_or_default()
+            .into_iter()
+            .map(|pkey| pkey.to_vec())
+            .collect();
+        pkeys.sort_unstable_by(|a, b| b.cmp(a));
+        pkeys
+    }
+
+}
+
+/// The fuzzy indexes of the collections, kept in memory and built when the database is opened.
+#[derive(Clone, Default)]
+pub(crate) struct FuzzyIndexRegistry {
+    inner: Arc<RwLock<HashMap<String, Arc<CollectionFuzzyIndex>>>>,
+}
+
+impl FuzzyIndexRegistry {
+
+    pub(crate) fn new() -> FuzzyIndexRegistry {
+        FuzzyIndexRegistry::default()
+    }
+
+    pub(crate) fn get(&self, col_name: &str) -> Option<Arc<CollectionFuzzyIndex>> {
+        self.inner.read().unwrap().get(col_name).cloned()
+    }
+
+    /// Register an empty index for a new collection.
+    pub(crate) fn create(&self, col_name: &str, field: &str) {
+        let index = CollectionFuzzyIndex::new(field);
+        self.inner.write().unwrap().insert(col_name.to_string(), Arc::new(index));
+    }
+
+    /// Read the documents of the collection to fill its index.
+    pub(crate) fn build(&self, txn: &TransactionInner, col_spec: &CollectionSpecification, field: &str) -> Result<()> {
+        let index = CollectionFuzzyIndex::new(field);
+        let mut cursor = Cursor::new_with_str_prefix(col_spec.name(), txn.rocksdb_txn.new_iterator())?;
+        cursor.reset()?;
+        while cursor.has_next() {
+            let doc = bson::from_slice::<Document>(cursor.copy_data()?.as_ref())?;
+            index.insert_doc(&doc);
+            cursor.next()?;
+        }
+        self.inner.write().unwrap().insert(col_spec.name().to_string(), Arc::new(index));
+        Ok(())
+    }
+
+    /// Share the index of a collection renamed by an uncommitted transaction with its new name,
+    /// so the writes of the transaction to the new name are added to it.
+    pub(crate) fn alias(&self, old_name: &str, new_name: &str) {
+        let mut inner = self.inner.write().unwrap();
+        if let Some(index) = inner.get(old_name).cloned() {
+            inner.insert(new_name.to_string(), index);
+        }
+    }
+
+    pub(crate) fn remove(&self, col_name: &str) {
+        self.inner.write().unwrap().remove(col_name);
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{doc, Bson};
+    use super::{within_edits, CollectionFuzzyIndex, FuzzySearch};
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn test_within_edits() {
+        assert!(within_edits(&chars("iphone"), &chars("iphone"), 0));
+        assert!(within_edits(&chars("iphone"), &chars("iphnoe"), 1));
+        assert!(within_edits(&chars("iphone"), &chars("iphon"), 1));
+        assert!(within_edits(&chars("iphone"), &chars("ipxhone"), 1));
+        assert!(!within_edits(&chars("iphone"), &chars("ipad"), 2));
+        assert!(within_edits(&chars("kitten"), &chars("sitting"), 3));
+        assert!(!within_edits(&chars("kitten"), &chars("sitting"), 2));
+    }
+
+    #[test]
+    fn test_fuzzy_index_candidates() {
+        let index = CollectionFuzzyIndex::new("name");
+        index.insert_doc(&doc! { "_id": 1, "name": "Apple iPhone 15" });
+        index.insert_doc(&doc! { "_id": 2, "name": "Apple iPad" });
+        index.insert_doc(&doc! { "_id": 3, "name": "Samsung Galaxy" });
+
+        let search = FuzzySearch::parse(&Bson::String("aple iphnoe".into())).unwrap();
+        let candidates = index.candidates(&search);
+        assert_eq!(candidates, vec![crate::utils::bson::stacked_key([&Bson::Int32(1)]).unwrap()]);
+
+        let search = FuzzySearch::parse(&Bson::String("aple".into())).unwrap();
+        assert_eq!(index.candidates(&search).len(), 2);
+    }
+
+}
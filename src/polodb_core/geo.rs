@@ -0,0 +1,534 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GeoJSON geometries stored in the documents, and the distances between them.
+//!
+//! A [`Point`], a [`LineString`] or a [`Polygon`] is stored as a GeoJSON document,
+//! the longitude first: `{ "type": "Point", "coordinates": [2.35, 48.85] }`.
+//! The field of a `2dsphere` index only accepts the valid geometries:
+//!
+//! ```rust
+//! use polodb_core::{Database, CollectionT, Error, IndexModel};
+//! use polodb_core::bson::doc;
+//! use polodb_core::geo::Point;
+//! # let db_path = polodb_core::test_utils::mk_db_path("doc-test-geo");
+//! let db = Database::open_path(db_path).unwrap();
+//! let places = db.collection("places");
+//! places.create_index(IndexModel {
+//!     keys: doc! { "location": "2dsphere" },
+//!     options: None,
+//! }).unwrap();
+//! places.insert_one(doc! { "name": "Louvre", "location": Point::new(2.3376, 48.8606) }).unwrap();
+//! let err = places.insert_one(doc! { "location": { "type": "Point", "coordinates": [200, 0] } }).unwrap_err();
+//! assert!(matches!(err, Error::InvalidGeometry(_)));
+//! ```
+//!
+//! The distances, in meters on a sphere of the radius of the Earth, are computed by
+//! the `$geoNear` aggregation stage, which sorts the documents from the nearest:
+//!
+//! ```javascript
+//! { "$geoNear": {
+//!     "near": { "type": "Point", "coordinates": [2.35, 48.85] },
+//!     "key": "location",
+//!     "distanceField": "distance",
+//!     "maxDistance": 2000,
+//! } }
+//! ```
+//!
+//! and by the `$geoDistance` expression, such as `{ "$geoDistance": ["$location", [2.35, 48.85]] }`
+//! in an `$addFields` stage.
+
+use std::convert::TryFrom;
+use bson::{Bson, Document};
+use serde::{Deserialize, Serialize};
+use crate::{Error, Result};
+
+/// The radius of the Earth used for the distances, in meters.
+pub const EARTH_RADIUS: f64 = 6_378_100.0;
+
+/// A longitude and a latitude, in degrees.
+pub type Position = [f64; 2];
+
+/// The GeoJSON format of the geometries.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum GeoJson {
+    Point { coordinates: Vec<f64> },
+    LineString { coordinates: Vec<Vec<f64>> },
+    Polygon { coordinates: Vec<Vec<Vec<f64>>> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(into = "GeoJson", try_from = "GeoJson")]
+pub struct Point {
+    position: Position,
+}
+
+impl Point {
+
+    pub fn new(longitude: f64, latitude: f64) -> Point {
+        Point {
+            position: [longitude, latitude],
+        }
+    }
+
+    pub fn longitude(&self) -> f64 {
+        self.position[0]
+    }
+
+    pub fn latitude(&self) -> f64 {
+        self.position[1]
+    }
+
+    /// The great-circle distance to `other`, in meters.
+    pub fn distance_to(&self, other: &Point) -> f64 {
+        angle(&self.position, &other.position) * EARTH_RADIUS
+    }
+
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "GeoJson", try_from = "GeoJson")]
+pub struct LineString {
+    positions: Vec<Position>,
+}
+
+impl LineString {
+
+    pub fn new(points: impl IntoIterator<Item = Point>) -> LineString {
+        LineString {
+            positions: points.into_iter().map(|point| point.position).collect(),
+        }
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        self.positions.iter().map(|position| Point { position: *position })
+    }
+
+}
+
+/// An area bounded by an exterior ring, without the areas of its holes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "GeoJson", try_from = "GeoJson")]
+pub struct Polygon {
+    rings: Vec<Vec<Position>>,
+}
+
+impl Polygon {
+
+    /// The polygon bounded by the points, the ring is closed
+    /// if the last point is not the first one.
+    pub fn new(exterior: impl IntoIterator<Item = Point>) -> Polygon {
+        Polygon {
+            rings: vec![closed_ring(exterior)],
+        }
+    }
+
+    /// Cut a hole bounded by the points out of the polygon.
+    pub fn hole(mut self, ring: impl IntoIterator<Item = Point>) -> Polygon {
+        self.rings.push(closed_ring(ring));
+        self
+    }
+
+    /// Whether the point is inside the polygon and not in one of its holes.
+    pub fn contains(&self, point: &Point) -> bool {
+        let [x, y] = point.position;
+        let mut inside = false;
+        for ring in &self.rings {
+            for edge in ring.windows(2) {
+                let ([x1, y1], [x2, y2]) = (edge[0], edge[1]);
+                if (y1 > y) != (y2 > y) && x < (x2 - x1) * (y - y1) / (y2 - y1) + x1 {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+
+}
+
+fn closed_ring(points: impl IntoIterator<Item = Point>) -> Vec<Position> {
+    let mut ring = points.into_iter().map(|point| point.position).collect::<Vec<_>>();
+    if let (Some(first), Some(last)) = (ring.first().copied(), ring.last()) {
+        if first != *last {
+            ring.push(first);
+        }
+    }
+    ring
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "GeoJson", try_from = "GeoJson")]
+pub enum Geometry {
+    Point(Point),
+    LineString(LineString),
+    Polygon(Polygon),
+}
+
+impl Geometry {
+
+    /// Read a GeoJSON geometry, or a legacy `[longitude, latitude]` pair,
+    /// failing with [`Error::InvalidGeometry`] if it's not valid.
+    pub fn from_bson(value: &Bson) -> Result<Geometry> {
+        match value {
+            Bson::Array(_) => {
+                let position = position_of_bson(value)?;
+                Geometry::try_from(GeoJson::Point { coordinates: position.to_vec() })
+            }
+            Bson::Document(_) => {
+                let geo_json = bson::from_bson::<GeoJson>(value.clone())
+                    .map_err(|err| Error::InvalidGeometry(err.to_string()))?;
+                Geometry::try_from(geo_json)
+            }
+            _ => Err(Error::InvalidGeometry(format!("expected a GeoJSON document, got {}", value))),
+        }
+    }
+
+    /// The distance from the nearest point of the geometry to `point`, in meters,
+    /// 0 if the point is inside a polygon.
+    pub fn distance_to(&self, point: &Point) -> f64 {
+        let target = &point.position;
+        let radians = match self {
+            Geometry::Point(geometry) => angle(&geometry.position, target),
+            Geometry::LineString(line) => path_angle(&line.positions, target),
+            Geometry::Polygon(polygon) => {
+                if polygon.contains(point) {
+                    0.0
+                } else {
+                    polygon.rings.iter()
+                        .map(|ring| path_angle(ring, target))
+                        .fold(f64::INFINITY, f64::min)
+                }
+            }
+        };
+        radians * EARTH_RADIUS
+    }
+
+    pub fn to_document(&self) -> Document {
+        match bson::to_bson(self) {
+            Ok(Bson::Document(doc)) => doc,
+            _ => unreachable!("a geometry is serialized to a document"),
+        }
+    }
+
+}
+
+impl From<Point> for Geometry {
+    fn from(point: Point) -> Geometry {
+        Geometry::Point(point)
+    }
+}
+
+impl From<LineString> for Geometry {
+    fn from(line: LineString) -> Geometry {
+        Geometry::LineString(line)
+    }
+}
+
+impl From<Polygon> for Geometry {
+    fn from(polygon: Polygon) -> Geometry {
+        Geometry::Polygon(polygon)
+    }
+}
+
+impl From<Geometry> for Bson {
+    fn from(geometry: Geometry) -> Bson {
+        Bson::Document(geometry.to_document())
+    }
+}
+
+impl From<Point> for Bson {
+    fn from(point: Point) -> Bson {
+        Geometry::Point(point).into()
+    }
+}
+
+impl From<LineString> for Bson {
+    fn from(line: LineString) -> Bson {
+        Geometry::LineString(line).into()
+    }
+}
+
+impl From<Polygon> for Bson {
+    fn from(polygon: Polygon) -> Bson {
+        Geometry::Polygon(polygon).into()
+    }
+}
+
+impl From<Geometry> for GeoJson {
+    fn from(geometry: Geometry) -> GeoJson {
+        match geometry {
+            Geometry::Point(point) => point.into(),
+            Geometry::LineString(line) => line.into(),
+            Geometry::Polygon(polygon) => polygon.into(),
+        }
+    }
+}
+
+impl From<Point> for GeoJson {
+    fn from(point: Point) -> GeoJson {
+        GeoJson::Point { coordinates: point.position.to_vec() }
+    }
+}
+
+impl From<LineString> for GeoJson {
+    fn from(line: LineString) -> GeoJson {
+        GeoJson::LineString {
+            coordinates: line.positions.iter().map(|position| position.to_vec()).collect(),
+        }
+    }
+}
+
+impl From<Polygon> for GeoJson {
+    fn from(polygon: Polygon) -> GeoJson {
+        GeoJson::Polygon {
+            coordinates: polygon.rings.iter()
+                .map(|ring| ring.iter().map(|position| position.to_vec()).collect())
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<GeoJson> for Geometry {
+    type Error = Error;
+
+    fn try_from(value: GeoJson) -> Result<Geometry> {
+        match value {
+            GeoJson::Point { coordinates } => Ok(Geometry::Point(Point {
+                position: position(&coordinates)?,
+            })),
+            GeoJson::LineString { coordinates } => {
+                let positions = coordinates.iter()
+                    .map(|coordinates| position(coordinates))
+                    .collect::<Result<Vec<_>>>()?;
+                if positions.len() < 2 {
+                    return Err(Error::InvalidGeometry("a LineString needs at least 2 positions".to_string()));
+                }
+                Ok(Geometry::LineString(LineString { positions }))
+            }
+            GeoJson::Polygon { coordinates } => {
+                if coordinates.is_empty() {
+                    return Err(Error::InvalidGeometry("a Polygon needs an exterior ring".to_string()));
+                }
+                let mut rings = Vec::with_capacity(coordinates.len());
+                for ring in &coordinates {
+                    let ring = ring.iter()
+                        .map(|coordinates| position(coordinates))
+                        .collect::<Result<Vec<_>>>()?;
+                    if ring.len() < 4 {
+                        return Err(Error::InvalidGeometry("a ring of a Polygon needs at least 4 positions".to_string()));
+                    }
+                    if ring.first() != ring.last() {
+                        return Err(Error::InvalidGeometry("a ring of a Polygon must end at its first position".to_string()));
+                    }
+                    rings.push(ring);
+                }
+                Ok(Geometry::Polygon(Polygon { rings }))
+            }
+        }
+    }
+}
+
+impl TryFrom<GeoJson> for Point {
+    type Error = Error;
+
+    fn try_from(value: GeoJson) -> Result<Point> {
+        match Geometry::try_from(value)? {
+            Geometry::Point(point) => Ok(point),
+            _ => Err(Error::InvalidGeometry("expected a Point".to_string())),
+        }
+    }
+}
+
+impl TryFrom<GeoJson> for LineString {
+    type Error = Error;
+
+    fn try_from(value: GeoJson) -> Result<LineString> {
+        match Geometry::try_from(value)? {
+            Geometry::LineString(line) => Ok(line),
+            _ => Err(Error::InvalidGeometry("expected a LineString".to_string())),
+        }
+    }
+}
+
+impl TryFrom<GeoJson> for Polygon {
+    type Error = Error;
+
+    fn try_from(value: GeoJson) -> Result<Polygon> {
+        match Geometry::try_from(value)? {
+            Geometry::Polygon(polygon) => Ok(polygon),
+            _ => Err(Error::InvalidGeometry("expected a Polygon".to_string())),
+        }
+    }
+}
+
+/// The position of the coordinates, an altitude after the latitude is ignored.
+fn position(coordinates: &[f64]) -> Result<Position> {
+    let (longitude, latitude) = match coordinates {
+        [longitude, latitude, ..] => (*longitude, *latitude),
+        _ => return Err(Error::InvalidGeometry("a position needs a longitude and a latitude".to_string())),
+    };
+    if !(-180.0..=180.0).contains(&longitude) {
+        return Err(Error::InvalidGeometry(format!("longitude {} is out of bounds", longitude)));
+    }
+    if !(-90.0..=90.0).contains(&latitude) {
+        return Err(Error::InvalidGeometry(format!("latitude {} is out of bounds", latitude)));
+    }
+    Ok([longitude, latitude])
+}
+
+fn position_of_bson(value: &Bson) -> Result<Position> {
+    let array = match value {
+        Bson::Array(array) => array,
+        _ => return Err(Error::InvalidGeometry(format!("expected a position, got {}", value))),
+    };
+    let coordinates = array.iter()
+        .map(|value| match value {
+            Bson::Double(value) => Ok(*value),
+            Bson::Int32(value) => Ok(*value as f64),
+            Bson::Int64(value) => Ok(*value as f64),
+            _ => Err(Error::InvalidGeometry(format!("expected a coordinate, got {}", value))),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    position(&coordinates)
+}
+
+/// Check the values of the fields of the `2dsphere` indexes, a document
+/// without the field or with a null value is not indexed.
+pub(crate) fn check_fields<'a>(fields: impl IntoIterator<Item = &'a String>, doc: &Document) -> Result<()> {
+    for field in fields {
+        match field_value(doc, field) {
+            None | Some(Bson::Null) => (),
+            Some(value) => {
+                Geometry::from_bson(value).map_err(|err| match err {
+                    Error::InvalidGeometry(reason) => Error::InvalidGeometry(format!("field '{}': {}", field, reason)),
+                    err => err,
+                })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The value at the dotted path of the document, a geometry is a document
+/// unlike the values of the other indexes.
+pub(crate) fn field_value<'a>(doc: &'a Document, path: &str) -> Option<&'a Bson> {
+    let mut keys = path.split('.');
+    let mut value = doc.get(keys.next()?)?;
+    for key in keys {
+        value = value.as_document()?.get(key)?;
+    }
+    Some(value)
+}
+
+/// The angle between the positions seen from the center of the Earth, in radians.
+fn angle(a: &Position, b: &Position) -> f64 {
+    let (lat1, lat2) = (a[1].to_radians(), b[1].to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lng = (b[0] - a[0]).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+    2.0 * h.sqrt().min(1.0).asin()
+}
+
+/// The initial bearing from `a` to `b`, in radians.
+fn bearing(a: &Position, b: &Position) -> f64 {
+    let (lat1, lat2) = (a[1].to_radians(), b[1].to_radians());
+    let d_lng = (b[0] - a[0]).to_radians();
+    let y = d_lng.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lng.cos();
+    y.atan2(x)
+}
+
+/// The angle from the nearest point of the great-circle segment `a`-`b` to `p`.
+fn segment_angle(a: &Position, b: &Position, p: &Position) -> f64 {
+    let d_ap = angle(a, p);
+    let d_ab = angle(a, b);
+    if d_ab == 0.0 {
+        return d_ap;
+    }
+    let theta = bearing(a, p) - bearing(a, b);
+    // behind the start of the segment
+    if theta.cos() <= 0.0 {
+        return d_ap;
+    }
+    let cross_track = (d_ap.sin() * theta.sin()).asin();
+    let along_track = (d_ap.cos() / cross_track.cos()).clamp(-1.0, 1.0).acos();
+    if along_track > d_ab {
+        return angle(b, p);
+    }
+    cross_track.abs()
+}
+
+fn path_angle(positions: &[Position], p: &Position) -> f64 {
+    match positions {
+        [single] => angle(single, p),
+        _ => positions.windows(2)
+            .map(|segment| segment_angle(&segment[0], &segment[1], p))
+            .fold(f64::INFINITY, f64::min),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{doc, Bson};
+    use super::{Geometry, LineString, Point, Polygon};
+
+    #[test]
+    fn test_geometry_distance() {
+        let paris = Point::new(2.3522, 48.8566);
+        let london = Point::new(-0.1276, 51.5072);
+        let distance = paris.distance_to(&london);
+        assert!((distance - 344_000.0).abs() < 2_000.0, "{}", distance);
+
+        // the nearest point of the line is between its ends
+        let line = Geometry::from(LineString::new([Point::new(0.0, 0.0), Point::new(0.0, 10.0)]));
+        let distance = line.distance_to(&Point::new(1.0, 5.0));
+        assert!((distance - Point::new(0.0, 5.0).distance_to(&Point::new(1.0, 5.0))).abs() < 100.0);
+
+        let square = Polygon::new([
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 10.0),
+            Point::new(10.0, 10.0),
+            Point::new(10.0, 0.0),
+        ]).hole([
+            Point::new(4.0, 4.0),
+            Point::new(4.0, 6.0),
+            Point::new(6.0, 6.0),
+            Point::new(6.0, 4.0),
+        ]);
+        assert!(square.contains(&Point::new(1.0, 1.0)));
+        assert!(!square.contains(&Point::new(5.0, 5.0)));
+        assert_eq!(Geometry::from(square.clone()).distance_to(&Point::new(1.0, 1.0)), 0.0);
+        assert!(Geometry::from(square).distance_to(&Point::new(5.0, 5.0)) > 100_000.0);
+    }
+
+    #[test]
+    fn test_geometry_from_bson() {
+        let point = Geometry::from_bson(&Bson::from(doc! { "type": "Point", "coordinates": [2, 48.5, 35] })).unwrap();
+        assert_eq!(point, Geometry::Point(Point::new(2.0, 48.5)));
+        assert_eq!(Geometry::from_bson(&Bson::from(vec![Bson::Int32(2), Bson::Double(48.5)])).unwrap(), point);
+        assert_eq!(Bson::from(Point::new(2.0, 48.5)), Bson::from(doc! { "type": "Point", "coordinates": [2.0, 48.5] }));
+
+        for invalid in [
+            doc! { "type": "Point", "coordinates": [2] },
+            doc! { "type": "Point", "coordinates": [2, 91] },
+            doc! { "type": "Circle", "coordinates": [2, 48] },
+            doc! { "type": "LineString", "coordinates": [[2, 48]] },
+            doc! { "type": "Polygon", "coordinates": [[[0, 0], [0, 1], [1, 1], [1, 0]]] },
+        ] {
+            assert!(Geometry::from_bson(&Bson::from(invalid)).is_err());
+        }
+    }
+
+}
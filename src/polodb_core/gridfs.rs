@@ -0,0 +1,477 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storage of the files larger than a document, split in chunks.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::{Bound, RangeBounds};
+use bson::{doc, Binary, Bson, DateTime, Document};
+use bson::oid::ObjectId;
+use bson::spec::BinarySubtype;
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use crate::{CollectionT, Database, Error, IndexModel, Result};
+use crate::options::{GridFsBucketOptions, GridFsFindOptions};
+
+const DEFAULT_BUCKET_NAME: &str = "fs";
+const DEFAULT_CHUNK_SIZE: u32 = 255 * 1024;
+
+/// The description of a file stored in a [`GridFsBucket`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GridFsFile {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub filename: String,
+    /// The size of the file in bytes.
+    pub length: u64,
+    pub chunk_size: u32,
+    pub upload_date: DateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Document>,
+    /// The hex MD5 digest of the content.
+    pub md5: String,
+    /// The hex SHA-256 digest of the content.
+    pub sha256: String,
+}
+
+impl GridFsFile {
+    fn chunk_count(&self) -> u32 {
+        self.length.div_ceil(self.chunk_size as u64) as u32
+    }
+}
+
+/// A bucket of files, the content of each file is split in chunks
+/// stored as documents of their own, so a file can exceed the maximum
+/// size of a document and is uploaded and downloaded as a stream.
+///
+/// The files are described in the collection `<bucket>_files`,
+/// and their chunks are in `<bucket>_chunks`.
+#[derive(Clone)]
+pub struct GridFsBucket {
+    db: Database,
+    files: String,
+    chunks: String,
+    chunk_size: u32,
+}
+
+impl GridFsBucket {
+    pub(crate) fn new(db: Database, options: GridFsBucketOptions) -> GridFsBucket {
+        let bucket_name = options.bucket_name.unwrap_or_else(|| DEFAULT_BUCKET_NAME.to_string());
+        GridFsBucket {
+            db,
+            files: format!("{}_files", bucket_name),
+            chunks: format!("{}_chunks", bucket_name),
+            chunk_size: options.chunk_size_bytes.filter(|size| *size > 0).unwrap_or(DEFAULT_CHUNK_SIZE),
+        }
+    }
+
+    /// The `_id` of a chunk, the chunks of a file are contiguous and ordered in the collection.
+    fn chunk_id(files_id: &ObjectId, n: u32) -> Bson {
+        let mut bytes = files_id.bytes().to_vec();
+        bytes.extend_from_slice(&n.to_be_bytes());
+        Bson::Binary(Binary {
+            subtype: BinarySubtype::Generic,
+            bytes,
+        })
+    }
+
+    /// Open a stream writing a new file, the file is visible once
+    /// [`GridFsUploadStream::finish`] is called.
+    pub fn open_upload_stream(&self, filename: &str, metadata: Option<Document>) -> GridFsUploadStream {
+        GridFsUploadStream {
+            bucket: self.clone(),
+            id: self.db.new_object_id(),
+            filename: filename.to_string(),
+            metadata,
+            buffer: Vec::with_capacity(self.chunk_size as usize),
+            n: 0,
+            length: 0,
+            md5: Md5::new(),
+            sha256: Sha256::new(),
+            closed: false,
+        }
+    }
+
+    /// Upload the content of `source` as a new file, return its id.
+    pub fn upload_from_reader(&self, filename: &str, mut source: impl Read, metadata: Option<Document>) -> Result<ObjectId> {
+        let mut stream = self.open_upload_stream(filename, metadata);
+        io::copy(&mut source, &mut stream)?;
+        stream.finish()
+    }
+
+    /// Index the fields of the files queried the most, once the collection exists.
+    fn create_indexes(&self) -> Result<()> {
+        let files = self.db.collection::<Document>(&self.files);
+        for field in ["filename", "uploadDate"] {
+            files.create_index(IndexModel {
+                keys: doc! { field: 1 },
+                options: None,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Open a stream reading the content of the file `id`, the stream fails
+    /// if the content doesn't match the length or the checksums of the file.
+    pub fn open_download_stream(&self, id: ObjectId) -> Result<GridFsDownloadStream> {
+        let file = self.find_file(id)?;
+        let end = file.length;
+        Ok(GridFsDownloadStream {
+            bucket: self.clone(),
+            file,
+            n: 0,
+            chunk: Vec::new(),
+            pos: 0,
+            read: 0,
+            end,
+            verify: true,
+            md5: Md5::new(),
+            sha256: Sha256::new(),
+        })
+    }
+
+    /// Open a stream reading the bytes of the file `id` in `range`, such as a segment
+    /// of a video, only the chunks holding them are read. The range is clamped
+    /// to the length of the file, the checksums are verified if it's the whole file.
+    pub fn open_download_stream_range(&self, id: ObjectId, range: impl RangeBounds<u64>) -> Result<GridFsDownloadStream> {
+        let mut stream = self.open_download_stream(id)?;
+        let length = stream.file.length;
+        let start = match range.start_bound() {
+            Bound::Included(start) => *start,
+            Bound::Excluded(start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        }.min(length);
+        let end = match range.end_bound() {
+            Bound::Included(end) => end.saturating_add(1),
+            Bound::Excluded(end) => *end,
+            Bound::Unbounded => length,
+        }.clamp(start, length);
+        stream.end = end;
+        if start > 0 || end < length {
+            stream.verify = false;
+            stream.seek_to(start)?;
+        }
+        Ok(stream)
+    }
+
+    /// Find the files matching `filter`, such as `{ "filename": "logo.png" }`
+    /// or `{ "uploadDate": { "$gte": date } }`, see [`GridFsFindOptions::tags`]
+    /// for the files holding some tags.
+    pub fn find(&self, filter: Document) -> Result<Vec<GridFsFile>> {
+        self.find_with_options(filter, GridFsFindOptions::default())
+    }
+
+    pub fn find_with_options(&self, filter: Document, options: GridFsFindOptions) -> Result<Vec<GridFsFile>> {
+        let files = self.db.collection::<GridFsFile>(&self.files);
+        let mut find = files.find(filter);
+        if let Some(sort) = options.sort {
+            find = find.sort(sort);
+        }
+        if let Some(skip) = options.skip {
+            find = find.skip(skip);
+        }
+        if let Some(limit) = options.limit {
+            find = find.limit(limit);
+        }
+        if let Some(tags) = options.tags.filter(|tags| !tags.is_empty()) {
+            find = find.filter_fn(move |file| {
+                let file_tags = file.get_document("metadata")
+                    .and_then(|metadata| metadata.get_array("tags"))
+                    .map(|file_tags| file_tags.as_slice())
+                    .unwrap_or_default();
+                tags.iter().all(|tag| file_tags.iter().any(|file_tag| file_tag.as_str() == Some(tag.as_str())))
+            });
+        }
+        find.run()?.collect()
+    }
+
+    fn find_file(&self, id: ObjectId) -> Result<GridFsFile> {
+        self.db.collection::<GridFsFile>(&self.files)
+            .find_one(doc! { "_id": id })?
+            .ok_or_else(|| Error::FileNotFound(id.to_hex()))
+    }
+
+    pub fn rename(&self, id: ObjectId, new_filename: &str) -> Result<()> {
+        let result = self.db.collection::<Document>(&self.files).update_one(
+            doc! { "_id": id },
+            doc! { "$set": { "filename": new_filename } },
+        )?;
+        if result.matched_count == 0 {
+            return Err(Error::FileNotFound(id.to_hex()));
+        }
+        Ok(())
+    }
+
+    /// Delete the file `id` and its chunks.
+    pub fn delete(&self, id: ObjectId) -> Result<()> {
+        let file = self.find_file(id)?;
+        let txn = self.db.start_transaction()?;
+        let chunks = txn.collection::<Document>(&self.chunks);
+        for n in 0..file.chunk_count() {
+            chunks.delete_one(doc! { "_id": GridFsBucket::chunk_id(&id, n) })?;
+        }
+        txn.collection::<Document>(&self.files).delete_one(doc! { "_id": id })?;
+        txn.commit()
+    }
+
+    /// Delete every file of the bucket.
+    pub fn drop(&self) -> Result<()> {
+        let txn = self.db.start_transaction()?;
+        txn.collection::<Document>(&self.chunks).drop()?;
+        txn.collection::<Document>(&self.files).drop()?;
+        txn.commit()
+    }
+}
+
+/// A stream writing a file of a [`GridFsBucket`], a chunk is written
+/// each time enough content is received.
+///
+/// The chunks are removed if the stream is dropped before [`GridFsUploadStream::finish`].
+pub struct GridFsUploadStream {
+    bucket: GridFsBucket,
+    id: ObjectId,
+    filename: String,
+    metadata: Option<Document>,
+    buffer: Vec<u8>,
+    n: u32,
+    length: u64,
+    md5: Md5,
+    sha256: Sha256,
+    closed: bool,
+}
+
+impl GridFsUploadStream {
+    /// The id of the file being written.
+    pub fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    fn write_chunk(&mut self) -> Result<()> {
+        let data = std::mem::replace(&mut self.buffer, Vec::with_capacity(self.bucket.chunk_size as usize));
+        self.md5.update(&data);
+        self.sha256.update(&data);
+        self.length += data.len() as u64;
+        self.bucket.db.collection::<Document>(&self.bucket.chunks).insert_one(doc! {
+            "_id": GridFsBucket::chunk_id(&self.id, self.n),
+            "files_id": self.id,
+            "n": self.n as i64,
+            "data": Binary {
+                subtype: BinarySubtype::Generic,
+                bytes: data,
+            },
+        })?;
+        self.n += 1;
+        Ok(())
+    }
+
+    /// Write the last chunk and the description of the file, return the id of the file.
+    pub fn finish(mut self) -> Result<ObjectId> {
+        if !self.buffer.is_empty() {
+            self.write_chunk()?;
+        }
+        let mut file = doc! {
+            "_id": self.id,
+            "filename": self.filename.as_str(),
+            "length": self.length as i64,
+            "chunkSize": self.bucket.chunk_size as i64,
+            "uploadDate": DateTime::now(),
+            "md5": format!("{:x}", self.md5.finalize_reset()),
+            "sha256": format!("{:x}", self.sha256.finalize_reset()),
+        };
+        if let Some(metadata) = self.metadata.take() {
+            file.insert("metadata", metadata);
+        }
+        self.bucket.db.collection::<Document>(&self.bucket.files).insert_one(file)?;
+        self.bucket.create_indexes()?;
+        self.closed = true;
+        Ok(self.id)
+    }
+
+    /// Remove the chunks already written.
+    pub fn abort(mut self) -> Result<()> {
+        self.closed = true;
+        self.delete_chunks()
+    }
+
+    fn delete_chunks(&self) -> Result<()> {
+        let chunks = self.bucket.db.collection::<Document>(&self.bucket.chunks);
+        for n in 0..self.n {
+            chunks.delete_one(doc! { "_id": GridFsBucket::chunk_id(&self.id, n) })?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for GridFsUploadStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let chunk_size = self.bucket.chunk_size as usize;
+        let mut written = 0;
+        while written < buf.len() {
+            let size = (chunk_size - self.buffer.len()).min(buf.len() - written);
+            self.buffer.extend_from_slice(&buf[written..written + size]);
+            written += size;
+            if self.buffer.len() == chunk_size {
+                self.write_chunk().map_err(io::Error::other)?;
+            }
+        }
+        Ok(written)
+    }
+
+    /// The chunks are written once full, only [`GridFsUploadStream::finish`] writes the last one.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for GridFsUploadStream {
+    fn drop(&mut self) {
+        if !self.closed {
+            let _ = self.delete_chunks();
+        }
+    }
+}
+
+/// A stream reading a file of a [`GridFsBucket`] chunk by chunk.
+///
+/// Seeking only reads the chunk of the new position, the checksums of the file
+/// are not verified once the stream has seeked.
+pub struct GridFsDownloadStream {
+    bucket: GridFsBucket,
+    file: GridFsFile,
+    n: u32,
+    chunk: Vec<u8>,
+    pos: usize,
+    /// The offset of the end of the current chunk.
+    read: u64,
+    /// The offset the stream stops reading at.
+    end: u64,
+    /// Whether the content is read in order from the start, so it can be checked.
+    verify: bool,
+    md5: Md5,
+    sha256: Sha256,
+}
+
+impl GridFsDownloadStream {
+    /// The description of the file being read.
+    pub fn file(&self) -> &GridFsFile {
+        &self.file
+    }
+
+    fn corrupted(&self) -> Error {
+        Error::FileCorrupted(self.file.id.to_hex())
+    }
+
+    /// The offset of the next byte read.
+    fn position(&self) -> u64 {
+        self.read - (self.chunk.len() - self.pos) as u64
+    }
+
+    /// Load the chunk holding the byte at `offset`.
+    fn seek_to(&mut self, offset: u64) -> Result<()> {
+        self.chunk.clear();
+        self.pos = 0;
+        if offset >= self.file.length {
+            self.n = self.file.chunk_count();
+            self.read = self.file.length;
+            return Ok(());
+        }
+        let chunk_size = self.file.chunk_size as u64;
+        self.n = (offset / chunk_size) as u32;
+        self.read = self.n as u64 * chunk_size;
+        self.next_chunk()?;
+        self.pos = (offset % chunk_size) as usize;
+        Ok(())
+    }
+
+    /// Load the next chunk, return false at the end of the file.
+    fn next_chunk(&mut self) -> Result<bool> {
+        if self.read == self.file.length {
+            if !self.verify {
+                return Ok(false);
+            }
+            let md5 = format!("{:x}", self.md5.finalize_reset());
+            let sha256 = format!("{:x}", self.sha256.finalize_reset());
+            if md5 != self.file.md5 || sha256 != self.file.sha256 {
+                return Err(self.corrupted());
+            }
+            return Ok(false);
+        }
+
+        let chunk = self.bucket.db.collection::<Document>(&self.bucket.chunks)
+            .find_one(doc! { "_id": GridFsBucket::chunk_id(&self.file.id, self.n) })?;
+        let data = match chunk {
+            Some(mut chunk) => match chunk.remove("data") {
+                Some(Bson::Binary(bin)) => bin.bytes,
+                _ => return Err(self.corrupted()),
+            },
+            None => return Err(self.corrupted()),
+        };
+        let expected = (self.file.length - self.read).min(self.file.chunk_size as u64);
+        if data.len() as u64 != expected {
+            return Err(self.corrupted());
+        }
+
+        if self.verify {
+            self.md5.update(&data);
+            self.sha256.update(&data);
+        }
+        self.read += data.len() as u64;
+        self.n += 1;
+        self.chunk = data;
+        self.pos = 0;
+        Ok(true)
+    }
+}
+
+impl Read for GridFsDownloadStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.end.saturating_sub(self.position());
+        if buf.is_empty() || (remaining == 0 && !self.verify) {
+            return Ok(0);
+        }
+        if self.pos == self.chunk.len() {
+            let has_chunk = self.next_chunk()
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            if !has_chunk {
+                return Ok(0);
+            }
+        }
+        let size = (self.chunk.len() - self.pos).min(buf.len()).min(remaining as usize);
+        buf[..size].copy_from_slice(&self.chunk[self.pos..self.pos + size]);
+        self.pos += size;
+        Ok(size)
+    }
+}
+
+impl Seek for GridFsDownloadStream {
+    /// Move to an offset of the file, the end is the end of the file even for a range.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let current = self.position();
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(delta) => self.file.length.checked_add_signed(delta),
+            SeekFrom::Current(delta) => current.checked_add_signed(delta),
+        };
+        let target = target.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")
+        })?;
+        if target != current {
+            self.verify = false;
+            self.seek_to(target).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        }
+        Ok(target)
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use bson::Document;
+use crate::transaction::TransactionInner;
+use crate::{Error, Result};
+
+/// The write operations a hook can be registered on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookEvent {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl HookEvent {
+
+    fn index(self) -> usize {
+        match self {
+            HookEvent::Insert => 0,
+            HookEvent::Update => 1,
+            HookEvent::Delete => 2,
+        }
+    }
+
+}
+
+type PreHook = Arc<dyn Fn(&mut Document) -> Result<()> + Send + Sync>;
+type PostHook = Arc<dyn Fn(&Document) + Send + Sync>;
+type Validator = Arc<dyn Fn(&Document) -> std::result::Result<(), String> + Send + Sync>;
+
+/// The hooks registered on a collection.
+#[derive(Default, Clone)]
+pub(crate) struct CollectionHooks {
+    pre: [Vec<PreHook>; 3],
+    post: [Vec<PostHook>; 3],
+    validators: Vec<Validator>,
+}
+
+impl CollectionHooks {
+
+    #[inline]
+    pub(crate) fn validator_count(&self) -> usize {
+        self.validators.len()
+    }
+
+    /// Run the validators on the document about to be inserted or updated,
+    /// the first failure rejects the write.
+    pub(crate) fn validate(&self, doc: &Document) -> Result<()> {
+        for validator in &self.validators {
+            validator(doc).map_err(Error::DocumentValidationFailed)?;
+        }
+        Ok(())
+    }
+
+    /// Run the pre hooks of the event on the document about to be written,
+    /// an error vetoes the write.
+    pub(crate) fn run_pre(&self, event: HookEvent, doc: &mut Document) -> Result<()> {
+        let hooks = &self.pre[event.index()];
+        if hooks.is_empty() {
+            return Ok(());
+        }
+        let pkey = doc.get("_id").cloned();
+        for hook in hooks {
+            hook(doc)?;
+        }
+        if doc.get("_id") != pkey.as_ref() {
+            return Err(Error::UnableToUpdatePrimaryKey);
+        }
+        Ok(())
+    }
+
+    /// Schedule the post hooks of the event to run when the transaction is committed.
+    pub(crate) fn defer_post(&self, txn: &TransactionInner, event: HookEvent, doc: &Document) {
+        let hooks = &self.post[event.index()];
+        if hooks.is_empty() {
+            return;
+        }
+        let hooks = hooks.clone();
+        let doc = doc.clone();
+        txn.on_commit(Box::new(move || {
+            for hook in &hooks {
+                hook(&doc);
+            }
+        }));
+    }
+
+}
+
+/// The hooks of all the collections of a database.
+#[derive(Clone, Default)]
+pub(crate) struct HookRegistry {
+    inner: Arc<RwLock<HashMap<String, Arc<CollectionHooks>>>>,
+}
+
+impl HookRegistry {
+
+    pub(crate) fn new() -> HookRegistry {
+        HookRegistry::default()
+    }
+
+    pub(crate) fn get(&self, col_name: &str) -> Option<Arc<CollectionHooks>> {
+        self.inner.read().unwrap().get(col_name).cloned()
+    }
+
+    fn modify<F: FnOnce(&mut CollectionHooks)>(&self, col_name: &str, f: F) {
+        let mut inner = self.inner.write().unwrap();
+        let mut hooks = inner.get(col_name).map(|hooks| hooks.as_ref().clone()).unwrap_or_default();
+        f(&mut hooks);
+        inner.insert(col_name.to_string(), Arc::new(hooks));
+    }
+
+    pub(crate) fn add_pre_hook(&self, col_name: &str, event: HookEvent, hook: PreHook) {
+        self.modify(col_name, |hooks| hooks.pre[event.index()].push(hook));
+    }
+
+    pub(crate) fn add_post_hook(&self, col_name: &str, event: HookEvent, hook: PostHook) {
+        self.modify(col_name, |hooks| hooks.post[event.index()].push(hook));
+    }
+
+    /// Move the hooks and the validators of a renamed collection.
+    pub(crate) fn rename(&self, old_name: &str, new_name: &str) {
+        let mut inner = self.inner.write().unwrap();
+        if let Some(hooks) = inner.remove(old_name) {
+            inner.insert(new_name.to_string(), hooks);
+        }
+    }
+
+    pub(crate) fn add_validator(&self, col_name: &str, validator: Validator) {
+        self.modify(col_name, |hooks| hooks.validators.push(validator));
+    }
+
+    pub(crate) fn clear_validators(&self, col_name: &str) {
+        self.modify(col_name, |hooks| hooks.validators.clear());
+    }
+
+    /// Remove the hooks of the collection, keeping its validators.
+    pub(crate) fn clear(&self, col_name: &str) {
+        self.modify(col_name, |hooks| {
+            *hooks = CollectionHooks {
+                validators: std::mem::take(&mut hooks.validators),
+                ..CollectionHooks::default()
+            };
+        });
+    }
+
+}
@@ -12,13 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use bson::Document;
+use bson::{Bson, Document};
+use bson::spec::ElementType;
 use crate::Result;
 use crate::coll::collection_info::IndexInfo;
 use crate::cursor::Cursor;
+use crate::db::db_inner::DatabaseInner;
+use crate::errors::DuplicateKeyError;
 use crate::index::{IndexHelper, IndexHelperOperation};
 use crate::transaction::TransactionInner;
 
+/// The number of documents read by the scan before the threads make their index entries.
+const REBUILD_BATCH_SIZE: usize = 1024;
+
 pub(crate) struct IndexBuilder<'b, 'c, 'd, 'e> {
     txn: &'b TransactionInner,
     col_name: &'c str,
@@ -64,6 +70,65 @@ impl<'b, 'c, 'd, 'e> IndexBuilder<'b, 'c, 'd, 'e> {
         Ok(())
     }
 
+    /// Build the indexes from scratch with one scan of the collection: the entries
+    /// of each index are made, sorted and checked for duplicates on up to `parallelism` threads,
+    /// then written in the order of their keys.
+    pub fn rebuild_many(
+        txn: &TransactionInner,
+        col_name: &str,
+        indexes: &[(&str, &IndexInfo)],
+        parallelism: usize,
+    ) -> Result<()> {
+        let mut rebuilt = Vec::with_capacity(indexes.len());
+        for (index_name, index_info) in indexes {
+            let prefix = IndexHelper::index_prefix(col_name, index_name)?;
+            txn.delete_prefix(prefix)?;
+            rebuilt.push(RebuiltIndex {
+                col_name,
+                index_name,
+                index_info,
+                entries: Vec::new(),
+                multikey: false,
+            });
+        }
+        if rebuilt.is_empty() {
+            return Ok(());
+        }
+        // each thread builds the same number of indexes
+        let chunk_size = rebuilt.len().div_ceil(parallelism.max(1));
+
+        let multi_cursor = txn.rocksdb_txn.new_iterator();
+        let mut cursor = Cursor::new_with_str_prefix(
+            col_name.to_string(),
+            multi_cursor,
+        )?;
+        cursor.reset()?;
+
+        let mut batch = Vec::<Document>::with_capacity(REBUILD_BATCH_SIZE);
+        while cursor.has_next() {
+            batch.push(bson::from_slice::<Document>(cursor.copy_data()?.as_ref())?);
+            cursor.next()?;
+
+            if batch.len() == REBUILD_BATCH_SIZE || !cursor.has_next() {
+                run_in_parallel(&mut rebuilt, chunk_size, |index| index.add_entries(&batch))?;
+                batch.clear();
+            }
+        }
+        run_in_parallel(&mut rebuilt, chunk_size, RebuiltIndex::sort_entries)?;
+
+        let value_buf = [ElementType::Null as u8];
+        for index in rebuilt {
+            if index.multikey && !index.index_info.is_multikey() {
+                DatabaseInner::mark_index_multikey(txn, col_name, index.index_name)?;
+            }
+            for (key, _, _) in index.entries {
+                txn.put(&key, &value_buf)?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn execute_index_item(&mut self, op: IndexHelperOperation, current_data: &[u8]) -> Result<()> {
         let data_doc = bson::from_slice::<Document>(current_data)?;
         let pkey = data_doc.get("_id").unwrap();
@@ -80,3 +145,69 @@ impl<'b, 'c, 'd, 'e> IndexBuilder<'b, 'c, 'd, 'e> {
     }
 
 }
+
+struct RebuiltIndex<'a> {
+    col_name: &'a str,
+    index_name: &'a str,
+    index_info: &'a IndexInfo,
+    // the keys, and the length of the part without the primary key
+    entries: Vec<(Vec<u8>, usize, Bson)>,
+    /// A document has several entries.
+    multikey: bool,
+}
+
+impl RebuiltIndex<'_> {
+
+    fn add_entries(&mut self, docs: &[Document]) -> Result<()> {
+        for data_doc in docs {
+            let entries = IndexHelper::index_values(data_doc, self.index_info);
+            self.multikey |= entries.len() > 1;
+            for values in entries {
+                let pkey = data_doc.get("_id").unwrap();
+                let value_len = IndexHelper::make_index_key(self.col_name, self.index_name, &values, None)?.len();
+                let key = IndexHelper::make_index_key(self.col_name, self.index_name, &values, Some(pkey))?;
+                self.entries.push((key, value_len, IndexHelper::entry_value(&values)));
+            }
+        }
+        Ok(())
+    }
+
+    fn sort_entries(&mut self) -> Result<()> {
+        self.entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        if self.index_info.is_unique() {
+            for pair in self.entries.windows(2) {
+                let ((left, left_len, value), (right, right_len, _)) = (&pair[0], &pair[1]);
+                if left[..*left_len] == right[..*right_len] {
+                    return Err(DuplicateKeyError {
+                        name: self.index_name.to_string(),
+                        key: value.to_string(),
+                        ns: self.col_name.to_string(),
+                    }.into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+}
+
+/// Run `f` on each index, the indexes are split into chunks of `chunk_size`
+/// handled by their own thread.
+fn run_in_parallel<'a, F>(indexes: &mut [RebuiltIndex<'a>], chunk_size: usize, f: F) -> Result<()>
+where
+    F: Fn(&mut RebuiltIndex<'a>) -> Result<()> + Sync,
+{
+    if indexes.len() <= chunk_size {
+        return indexes.iter_mut().try_for_each(f);
+    }
+    let f = &f;
+    std::thread::scope(|scope| {
+        let handles = indexes.chunks_mut(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter_mut().try_for_each(f)))
+            .collect::<Vec<_>>();
+        handles.into_iter()
+            .try_for_each(|handle| handle.join().expect("internal: index build thread panicked"))
+    })
+}
@@ -17,13 +17,20 @@ use bson::spec::ElementType;
 use crate::Result;
 use crate::coll::collection_info::{
     CollectionSpecification,
+    FieldStatistics,
     IndexInfo,
+    ValueFrequency,
 };
+use crate::cursor::Cursor;
+use crate::db::db_inner::DatabaseInner;
 use crate::errors::DuplicateKeyError;
 use crate::transaction::TransactionInner;
 
 pub(crate) const INDEX_PREFIX: &str = "$I";
 
+/// The number of the most common values kept by the statistics of a field.
+const MOST_COMMON_VALUES: usize = 10;
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 #[repr(u8)]
 pub(crate) enum IndexHelperOperation {
@@ -38,11 +45,14 @@ pub(crate) struct IndexHelper<'b, 'c, 'd, 'e> {
     pkey: &'e Bson,
 }
 
+/// The prefix of the keys of an index holding the value looked up. The values
+/// of the first fields of a compound index are looked up as an array.
 pub(crate) fn make_index_key_with_query_key(prefix_bytes: &[u8], query_value: &Bson) -> Result<Vec<u8>> {
     let mut key_buffer = prefix_bytes.to_vec();
-    let primary_key_buffer = crate::utils::bson::stacked_key([
-        query_value,
-    ])?;
+    let primary_key_buffer = match query_value {
+        Bson::Array(values) => crate::utils::bson::stacked_key(values)?,
+        _ => crate::utils::bson::stacked_key([query_value])?,
+    };
 
     key_buffer.extend_from_slice(&primary_key_buffer);
 
@@ -95,51 +105,190 @@ impl<'b, 'c, 'd, 'e> IndexHelper<'b, 'c, 'd, 'e> {
         index_info: &IndexInfo,
         txn: &TransactionInner,
     ) -> Result<()> {
-        let tuples = index_info.keys.iter().collect::<Vec<(&String, &i8)>>();
-        let first_tuple = tuples.first().unwrap();
-        let (keys, _order) = first_tuple;
+        let target = IndexTarget { col_name, pkey, index_name, index_info };
+        let entries = IndexHelper::index_values(data_doc, index_info);
+        if op == IndexHelperOperation::Insert && entries.len() > 1 && !index_info.is_multikey() {
+            DatabaseInner::mark_index_multikey(txn, col_name, index_name)?;
+        }
+        for values in entries {
+            target.execute_value(op, &values, txn)?;
+        }
+
+        Ok(())
+    }
 
-        let value = crate::utils::bson::try_get_document_value(data_doc, keys);
-        if value.is_none() {
-            return Ok(())
+    /// Update the entries of a document changed from `old_doc` to `new_doc`: only
+    /// the values it doesn't have anymore are deleted, and the new ones inserted,
+    /// such as the element pushed to an array.
+    pub(crate) fn update_with_index_info(
+        old_doc: &Document,
+        new_doc: &Document,
+        col_name: &str,
+        pkey: &Bson,
+        index_name: &str,
+        index_info: &IndexInfo,
+        txn: &TransactionInner,
+    ) -> Result<()> {
+        let target = IndexTarget { col_name, pkey, index_name, index_info };
+        let old_entries = IndexHelper::index_values(old_doc, index_info);
+        let new_entries = IndexHelper::index_values(new_doc, index_info);
+        if new_entries.len() > 1 && !index_info.is_multikey() {
+            DatabaseInner::mark_index_multikey(txn, col_name, index_name)?;
+        }
+        for values in old_entries.iter().filter(|values| !new_entries.contains(values)) {
+            target.execute_value(IndexHelperOperation::Delete, values, txn)?;
         }
+        for values in new_entries.iter().filter(|values| !old_entries.contains(values)) {
+            target.execute_value(IndexHelperOperation::Insert, values, txn)?;
+        }
+        Ok(())
+    }
 
-        if index_info.is_unique() {
-            IndexHelper::check_unique_key(
-                col_name,
-                index_name,
-                value.as_ref().unwrap(),
-                txn,
-            )?;
+    /// The entries of the document in the index, with the values of the fields of the index,
+    /// none if the document is not indexed. The index is multikey: the distinct elements
+    /// of an array are stored one by one, an empty array isn't stored. The documents
+    /// missing a field of a compound index have an entry with null in its place,
+    /// unless they miss all of them. A partial index has no entry of the documents
+    /// not matching its filter.
+    pub(crate) fn index_values(data_doc: &Document, index_info: &IndexInfo) -> Vec<Vec<Bson>> {
+        if !index_info.indexes(data_doc) {
+            return Vec::new();
         }
+        let is_compound = index_info.keys.len() > 1;
+        let mut entries: Vec<Vec<Bson>> = vec![Vec::with_capacity(index_info.keys.len())];
+        let mut missing = 0;
+        for key in index_info.keys.keys() {
+            let values = match crate::utils::bson::try_get_document_value(data_doc, key) {
+                Some(value) => IndexHelper::field_values(value, index_info),
+                None if is_compound => {
+                    missing += 1;
+                    vec![Bson::Null]
+                }
+                None => Vec::new(),
+            };
+            // an entry for each combination of the values of the fields
+            entries = entries.into_iter()
+                .flat_map(|entry| values.iter().map(move |value| {
+                    let mut entry = entry.clone();
+                    entry.push(value.clone());
+                    entry
+                }))
+                .collect();
+        }
+        if missing == index_info.keys.len() {
+            return Vec::new();
+        }
+        entries
+    }
 
-        let index_key = IndexHelper::make_index_key(
-            col_name,
-            index_name,
-            value.as_ref().unwrap(),
-            Some(pkey),
-        )?;
+    fn field_values(value: Bson, index_info: &IndexInfo) -> Vec<Bson> {
+        // the strings are stored as they are compared by the collation of the index
+        let collation = index_info.collation();
+        match value {
+            Bson::Array(arr) => {
+                let mut values: Vec<Bson> = Vec::with_capacity(arr.len());
+                for item in &arr {
+                    let item = collation.collate(item).into_owned();
+                    if !values.contains(&item) {
+                        values.push(item);
+                    }
+                }
+                values
+            }
+            value => vec![collation.collate(&value).into_owned()],
+        }
+    }
 
-        if op == IndexHelperOperation::Insert {
-            let value_buf = [ElementType::Null as u8];
-            txn.put(index_key.as_slice(), &value_buf)?;
-        } else {
-            txn.delete(index_key.as_slice())?;
+    /// Count the entries of the values of the first fields of the index, stop counting at `limit`.
+    pub(crate) fn count_value(
+        txn: &TransactionInner,
+        col_name: &str,
+        index_name: &str,
+        values: &[Bson],
+        limit: u64,
+    ) -> Result<u64> {
+        let prefix = IndexHelper::make_index_key(col_name, index_name, values, None)?;
+        let mut cursor = Cursor::new(prefix, txn.rocksdb_txn.new_iterator());
+        cursor.reset()?;
+
+        let mut count = 0;
+        while count < limit && cursor.has_next() {
+            count += 1;
+            cursor.next()?;
         }
+        Ok(count)
+    }
 
-        Ok(())
+    /// Read the entries of the index to gather the statistics of its first field.
+    /// The entries of a value are adjacent, so the values are counted in one pass.
+    pub(crate) fn field_statistics(
+        txn: &TransactionInner,
+        col_name: &str,
+        index_name: &str,
+        field: &str,
+    ) -> Result<FieldStatistics> {
+        let prefix = IndexHelper::index_prefix(col_name, index_name)?;
+        let prefix_len = prefix.len();
+        let mut cursor = Cursor::new(prefix, txn.rocksdb_txn.new_iterator());
+        cursor.reset()?;
+
+        let mut statistics = FieldStatistics {
+            field: field.to_string(),
+            count: 0,
+            distinct: 0,
+            most_common: Vec::new(),
+        };
+        let mut current: Option<ValueFrequency> = None;
+        while cursor.has_next() {
+            let key = cursor.peek_key().expect("key must exist");
+            let slices = crate::utils::bson::split_stacked_keys(&key[prefix_len..])?;
+            let value = slices.into_iter().next().expect("value must exist");
+
+            statistics.count += 1;
+            match &mut current {
+                Some(frequency) if frequency.value == value => frequency.count += 1,
+                _ => {
+                    if let Some(frequency) = current.take() {
+                        IndexHelper::keep_most_common(&mut statistics.most_common, frequency);
+                    }
+                    statistics.distinct += 1;
+                    current = Some(ValueFrequency { value, count: 1 });
+                }
+            }
+            cursor.next()?;
+        }
+        if let Some(frequency) = current {
+            IndexHelper::keep_most_common(&mut statistics.most_common, frequency);
+        }
+        statistics.most_common.sort_by_key(|frequency| std::cmp::Reverse(frequency.count));
+
+        Ok(statistics)
+    }
+
+    fn keep_most_common(most_common: &mut Vec<ValueFrequency>, frequency: ValueFrequency) {
+        if most_common.len() < MOST_COMMON_VALUES {
+            most_common.push(frequency);
+            return;
+        }
+        let (least_index, least) = most_common.iter()
+            .enumerate()
+            .min_by_key(|(_, item)| item.count)
+            .expect("most common values must exist");
+        if frequency.count > least.count {
+            most_common[least_index] = frequency;
+        }
     }
 
     fn check_unique_key(
         col_name: &str,
         index_name: &str,
-        value: &Bson,
+        values: &[Bson],
         txn: &TransactionInner,
     ) -> Result<()> {
         let index_key_tester = IndexHelper::make_index_key(
             col_name,
             index_name,
-            value,
+            values,
             None,
         )?;
 
@@ -154,7 +303,7 @@ impl<'b, 'c, 'd, 'e> IndexHelper<'b, 'c, 'd, 'e> {
         if current_key.starts_with(&index_key_tester) {
             return Err(DuplicateKeyError {
                 name: index_name.to_string(),
-                key: value.to_string(),
+                key: IndexHelper::entry_value(values).to_string(),
                 ns: col_name.to_string(),
             }.into());
         }
@@ -162,7 +311,25 @@ impl<'b, 'c, 'd, 'e> IndexHelper<'b, 'c, 'd, 'e> {
         Ok(())
     }
 
-    pub fn make_index_key(col_name: &str, index_name: &str, value: &Bson, pkey: Option<&Bson>) -> Result<Vec<u8>> {
+    /// The value of an entry as it is reported: the value of the field,
+    /// or the array of the values of the fields of a compound index.
+    pub(crate) fn entry_value(values: &[Bson]) -> Bson {
+        match values {
+            [value] => value.clone(),
+            values => Bson::Array(values.to_vec()),
+        }
+    }
+
+    /// The prefix shared by all the keys of an index.
+    pub(crate) fn index_prefix(col_name: &str, index_name: &str) -> Result<Vec<u8>> {
+        crate::utils::bson::stacked_key([
+            &Bson::String(INDEX_PREFIX.to_string()),
+            &Bson::String(col_name.to_string()),
+            &Bson::String(index_name.to_string()),
+        ])
+    }
+
+    pub fn make_index_key(col_name: &str, index_name: &str, values: &[Bson], pkey: Option<&Bson>) -> Result<Vec<u8>> {
         let b_prefix = Bson::String(INDEX_PREFIX.to_string());
         let b_col_name = Bson::String(col_name.to_string());
         let b_index_name = &Bson::String(index_name.to_string());
@@ -171,8 +338,8 @@ impl<'b, 'c, 'd, 'e> IndexHelper<'b, 'c, 'd, 'e> {
             &b_prefix,
             &b_col_name,
             &b_index_name,
-            value,
         ];
+        buf.extend(values);
 
         if let Some(pkey) = pkey {
             buf.push(pkey);
@@ -183,6 +350,46 @@ impl<'b, 'c, 'd, 'e> IndexHelper<'b, 'c, 'd, 'e> {
 
 }
 
+/// The entries of a document in an index.
+struct IndexTarget<'a> {
+    col_name: &'a str,
+    pkey: &'a Bson,
+    index_name: &'a str,
+    index_info: &'a IndexInfo,
+}
+
+impl IndexTarget<'_> {
+
+    fn execute_value(&self, op: IndexHelperOperation, values: &[Bson], txn: &TransactionInner) -> Result<()> {
+        // the entry deleted is the one of the document itself
+        if op == IndexHelperOperation::Insert && self.index_info.is_unique() {
+            IndexHelper::check_unique_key(
+                self.col_name,
+                self.index_name,
+                values,
+                txn,
+            )?;
+        }
+
+        let index_key = IndexHelper::make_index_key(
+            self.col_name,
+            self.index_name,
+            values,
+            Some(self.pkey),
+        )?;
+
+        if op == IndexHelperOperation::Insert {
+            let value_buf = [ElementType::Null as u8];
+            txn.put(index_key.as_slice(), &value_buf)?;
+        } else {
+            txn.delete(index_key.as_slice())?;
+        }
+
+        Ok(())
+    }
+
+}
+
 #[cfg(test)]
 mod tests {
     use bson::Bson;
@@ -194,7 +401,7 @@ mod tests {
         let index_key = IndexHelper::make_index_key(
             "users",
             "name",
-            &Bson::String("value".to_string()),
+            &[Bson::String("value".to_string())],
             Some(&Bson::String("Vincent".to_string())),
         ).unwrap() ;
 
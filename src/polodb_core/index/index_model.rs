@@ -14,6 +14,8 @@
 
 use bson::Document;
 use serde::{Deserialize, Serialize};
+use crate::options::Collation;
+use crate::vector::VectorSimilarity;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -26,7 +28,7 @@ pub struct IndexModel {
     pub options: Option<IndexOptions>,
 }
 
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IndexOptions {
 
@@ -37,4 +39,112 @@ pub struct IndexOptions {
     /// key value matches an existing value in the index. The default value is false.
     pub unique: Option<bool>,
 
+    /// Expires the documents this number of seconds after the date of their indexed field.
+    /// The expired documents are hidden from the reads and removed by
+    /// [`crate::Database::remove_expired_documents`], or in the background by a
+    /// [`crate::Scheduler`] running [`crate::Job::remove_expired`].
+    /// The documents whose field is not a date never expire.
+    #[serde(rename = "expireAfterSeconds", default, skip_serializing_if = "Option::is_none")]
+    pub expire_after_secs: Option<u64>,
+
+    /// Leaves the documents without the indexed field out of the index. The indexes
+    /// never have entries of the documents missing all their fields, whether they
+    /// are sparse or not.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sparse: Option<bool>,
+
+    /// Only indexes the documents matching the filter, such as `{ "age": { "$gte": 18 } }`.
+    /// The filter is made of equalities, `$eq`, `$gt`, `$gte`, `$lt`, `$lte`, `$in`
+    /// and `$and`. A query only uses the index if the documents it matches all match
+    /// the filter, the sorts are never made by reading a partial index.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partial_filter_expression: Option<Document>,
+
+    /// How the string keys of the index are compared, a query uses
+    /// the index only if it runs with the same collation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collation: Option<Collation>,
+
+    /// The number of dimensions of the vectors of a `"vector"` index.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<u32>,
+
+    /// How the vectors of a `"vector"` index are compared, cosine by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<VectorSimilarity>,
+
+}
+
+impl IndexOptions {
+
+    pub fn builder() -> IndexOptionsBuilder {
+        IndexOptionsBuilder::default()
+    }
+
+    /// The options with the defaults filled in and without the name,
+    /// two indexes with the same keys and normalized options are identical.
+    pub(crate) fn normalized(options: Option<&IndexOptions>) -> IndexOptions {
+        let options = options.cloned().unwrap_or_default();
+        IndexOptions {
+            name: None,
+            unique: Some(options.unique.unwrap_or(false)),
+            sparse: Some(options.sparse.unwrap_or(false)),
+            collation: Some(options.collation.unwrap_or_default()),
+            ..options
+        }
+    }
+
+}
+
+#[derive(Default)]
+pub struct IndexOptionsBuilder {
+    inner: IndexOptions,
+}
+
+impl IndexOptionsBuilder {
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.inner.name = Some(name.into());
+        self
+    }
+
+    pub fn unique(mut self, unique: bool) -> Self {
+        self.inner.unique = Some(unique);
+        self
+    }
+
+    pub fn expire_after_secs(mut self, secs: u64) -> Self {
+        self.inner.expire_after_secs = Some(secs);
+        self
+    }
+
+    pub fn sparse(mut self, sparse: bool) -> Self {
+        self.inner.sparse = Some(sparse);
+        self
+    }
+
+    pub fn partial_filter_expression(mut self, filter: Document) -> Self {
+        self.inner.partial_filter_expression = Some(filter);
+        self
+    }
+
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.inner.collation = Some(collation);
+        self
+    }
+
+    pub fn dimensions(mut self, dimensions: u32) -> Self {
+        self.inner.dimensions = Some(dimensions);
+        self
+    }
+
+    pub fn similarity(mut self, similarity: VectorSimilarity) -> Self {
+        self.inner.similarity = Some(similarity);
+        self
+    }
+
+    pub fn build(self) -> IndexOptions {
+        self.inner
+    }
+
 }
@@ -0,0 +1,772 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use bson::{doc, Bson, Document};
+use bson::spec::ElementType;
+use crate::coll::collection_info::IndexInfo;
+use crate::cursor::Cursor;
+use crate::db::RocksDBIterator;
+use crate::index::IndexHelper;
+use crate::options::{Collation, Hint};
+use crate::transaction::TransactionInner;
+use crate::utils::bson::type_rank;
+use crate::{Error, Result};
+
+/// The types of the numbers, their keys are not ordered like their values,
+/// and the numbers of the different types are compared with each other.
+const NUMBER_TYPES: [u8; 4] = [
+    ElementType::Double as u8,
+    ElementType::Int32 as u8,
+    ElementType::Int64 as u8,
+    ElementType::Decimal128 as u8,
+];
+
+/// How an index scan is used to return the documents in a sorted order.
+#[derive(Debug, Clone)]
+pub(crate) struct IndexOrder {
+    pub index_name: String,
+    pub index_info: IndexInfo,
+    /// The documents are returned from the greatest value.
+    pub reverse: bool,
+}
+
+impl IndexOrder {
+
+    /// The index which can return the documents in the order of `sort`,
+    /// such as `{ "created": -1 }` for the index `{ "created": 1 }`.
+    pub(crate) fn find<'a, I>(indexes: I, sort: &Document) -> Option<IndexOrder>
+    where
+        I: IntoIterator<Item = (&'a String, &'a IndexInfo)>,
+    {
+        let directions = sort.iter()
+            .map(|(key, value)| Some((key, sort_direction(value)?)))
+            .collect::<Option<Vec<(&String, i8)>>>()?;
+        if directions.is_empty() {
+            return None;
+        }
+        for (index_name, index_info) in indexes {
+            // the in-memory sort compares the strings byte by byte,
+            // and the scan reads the entries of the indexes on a single field,
+            // it would read the documents left out of a partial index out of order
+            if index_info.collation() != Collation::Simple
+                || index_info.keys.len() != 1
+                || directions.len() > index_info.keys.len()
+                || index_info.partial_filter_expression().is_some()
+            {
+                continue;
+            }
+            // the sort is a prefix of the keys of the index, all in the same
+            // direction as the index or all in the opposite direction
+            let (first_key, first_direction) = directions[0];
+            let reverse = index_info.keys.get(first_key).map(|order| *order != first_direction);
+            let matches = directions.iter()
+                .zip(index_info.keys.iter())
+                .all(|((key, direction), (index_key, order))| {
+                    *key == index_key && Some(*direction != *order) == reverse
+                });
+            if matches {
+                return Some(IndexOrder {
+                    index_name: index_name.clone(),
+                    index_info: index_info.clone(),
+                    reverse: reverse.unwrap(),
+                });
+            }
+        }
+        None
+    }
+
+}
+
+fn sort_direction(value: &Bson) -> Option<i8> {
+    match value {
+        Bson::Int32(1) | Bson::Int64(1) => Some(1),
+        Bson::Int32(-1) | Bson::Int64(-1) => Some(-1),
+        _ => None,
+    }
+}
+
+/// The numbers are compared by their values, the other values by their types,
+/// a run never holds two types which are not numbers.
+fn cmp_index_values(a: &Bson, b: &Bson) -> Ordering {
+    type_rank(a).cmp(&type_rank(b)).then_with(|| {
+        crate::utils::bson::value_cmp(a, b).unwrap_or(Ordering::Equal)
+    })
+}
+
+/// The position of a document in the order of an index: the value of the field
+/// of the index, `None` if the document misses it, and the encoded primary key.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct IndexPosition {
+    pub value: Option<Bson>,
+    pub pkey: Vec<u8>,
+}
+
+impl IndexPosition {
+
+    /// Compare the positions in the order the documents are read by an [`IndexOrderScan`].
+    pub(crate) fn cmp_in_order(&self, other: &IndexPosition, reverse: bool) -> Ordering {
+        let ordering = match (&self.value, &other.value) {
+            (Some(a), Some(b)) => cmp_index_values(a, b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        let ordering = if reverse { ordering.reverse() } else { ordering };
+        ordering.then_with(|| self.pkey.cmp(&other.pkey))
+    }
+
+}
+
+/// The index a find reads, and the range of its keys: from `min` included to `max` excluded.
+#[derive(Debug, Clone)]
+pub(crate) struct IndexRange {
+    pub hint: Hint,
+    pub min: Option<Document>,
+    pub max: Option<Document>,
+}
+
+/// The values of the field of an index bounding its scan,
+/// stored as the keys of the index are.
+#[derive(Debug, Clone)]
+pub(crate) struct IndexBounds {
+    pub min: Option<Bson>,
+    pub max: Option<Bson>,
+    /// The value of `min` is out of the bounds, as for `$gt`.
+    pub min_excluded: bool,
+    /// The value of `max` is within the bounds, as for `$lte`.
+    pub max_included: bool,
+    /// Only the values of the type of the bounds are within them, the numbers
+    /// of all the types being of the same type.
+    pub same_type: bool,
+}
+
+impl IndexBounds {
+
+    /// The bounds must name the fields of the index, in the order of its keys,
+    /// such as `{ "created": DateTime }` for the index `{ "created": 1 }`.
+    pub(crate) fn new(index_info: &IndexInfo, min: Option<&Document>, max: Option<&Document>) -> Result<IndexBounds> {
+        if index_info.keys.len() != 1 && (min.is_some() || max.is_some()) {
+            return Err(Error::InvalidIndexBounds("the bounds need an index on a single field".to_string()));
+        }
+        let bound_value = |bound: Option<&Document>| -> Result<Option<Bson>> {
+            let bound = match bound {
+                Some(bound) => bound,
+                None => return Ok(None),
+            };
+            let matches = bound.len() == index_info.keys.len()
+                && bound.keys().zip(index_info.keys.keys()).all(|(key, index_key)| key == index_key);
+            if !matches {
+                return Err(Error::InvalidIndexBounds(format!(
+                    "the fields of {} are not the keys of the index", bound,
+                )));
+            }
+            let value = bound.values().next().unwrap();
+            Ok(Some(index_info.collation().collate(value).into_owned()))
+        };
+        Ok(IndexBounds {
+            min: bound_value(min)?,
+            max: bound_value(max)?,
+            min_excluded: false,
+            max_included: false,
+            same_type: false,
+        })
+    }
+
+    /// The bounds as they are explained, the missing bounds are `MinKey` and `MaxKey`.
+    pub(crate) fn to_document(&self) -> Document {
+        doc! {
+            "min": self.min.clone().unwrap_or(Bson::MinKey),
+            "max": self.max.clone().unwrap_or(Bson::MaxKey),
+            "minExcluded": self.min_excluded,
+            "maxIncluded": self.max_included,
+            "sameType": self.same_type,
+        }
+    }
+
+    /// The bounds of the range of `condition` on the field of an index on a single field,
+    /// such as `{ "$gte": DateTime }`. The bounds hold all the values the filter matches,
+    /// it checks the documents read. A range only holds the values of the type of its
+    /// bound, the scan reads the keys of this type. Both sides bound the scan unless
+    /// the index is multikey: each side may then be satisfied by another element
+    /// of an array, only one side does. `None` if the condition has no such bound.
+    pub(crate) fn from_condition(index_info: &IndexInfo, condition: &Document) -> Option<IndexBounds> {
+        if index_info.keys.len() != 1 {
+            return None;
+        }
+        let unbounded = || IndexBounds {
+            min: None,
+            max: None,
+            min_excluded: false,
+            max_included: false,
+            same_type: true,
+        };
+        let mut lower = unbounded();
+        let mut upper = unbounded();
+        for (op, value) in condition {
+            // the types whose keys are in the order of their values
+            let keyed = matches!(
+                value,
+                Bson::Double(_) | Bson::Int32(_) | Bson::Int64(_) | Bson::Decimal128(_)
+                    | Bson::String(_) | Bson::DateTime(_) | Bson::ObjectId(_) | Bson::Boolean(_) | Bson::Binary(_)
+            );
+            let (bounds, is_min) = match op.as_str() {
+                "$gt" | "$gte" if keyed => (&mut lower, true),
+                "$lt" | "$lte" if keyed => (&mut upper, false),
+                _ => continue,
+            };
+            let value = index_info.collation().collate(value).into_owned();
+            let excluded = matches!(op.as_str(), "$gt" | "$lt");
+            let bound = if is_min { &mut bounds.min } else { &mut bounds.max };
+            // the narrowest bound of the side, the filter checks a bound of another type
+            let narrower = match bound.as_ref() {
+                None => true,
+                Some(current) if type_rank(current) != type_rank(&value) => false,
+                Some(current) => match cmp_index_values(&value, current) {
+                    Ordering::Equal => excluded,
+                    Ordering::Greater => is_min,
+                    Ordering::Less => !is_min,
+                },
+            };
+            if !narrower {
+                continue;
+            }
+            *bound = Some(value);
+            if is_min {
+                bounds.min_excluded = excluded;
+            } else {
+                bounds.max_included = !excluded;
+            }
+        }
+        match (lower.type_rank(), upper.type_rank()) {
+            (Some(min_rank), Some(max_rank)) if min_rank == max_rank && !index_info.is_multikey() => {
+                lower.max = upper.max;
+                lower.max_included = upper.max_included;
+                Some(lower)
+            }
+            (Some(_), _) => Some(lower),
+            (None, Some(_)) => Some(upper),
+            (None, None) => None,
+        }
+    }
+
+    /// The rank of the type of the values within the bounds, `None` if they hold all the types.
+    fn type_rank(&self) -> Option<u8> {
+        if !self.same_type {
+            return None;
+        }
+        self.min.as_ref().or(self.max.as_ref()).map(type_rank)
+    }
+
+    fn contains(&self, value: &Bson) -> bool {
+        if self.type_rank().is_some_and(|rank| rank != type_rank(value)) {
+            return false;
+        }
+        let above_min = self.min.as_ref().is_none_or(|min| match cmp_index_values(value, min) {
+            Ordering::Greater => true,
+            Ordering::Equal => !self.min_excluded,
+            Ordering::Less => false,
+        });
+        let below_max = self.max.as_ref().is_none_or(|max| match cmp_index_values(value, max) {
+            Ordering::Less => true,
+            Ordering::Equal => self.max_included,
+            Ordering::Greater => false,
+        });
+        above_min && below_max
+    }
+
+}
+
+struct IndexEntry {
+    value: Bson,
+    pkey: Bson,
+    pkey_bytes: Vec<u8>,
+}
+
+impl IndexEntry {
+
+    fn cmp_value(&self, other: &IndexEntry) -> Ordering {
+        cmp_index_values(&self.value, &other.value)
+    }
+
+    /// The entry is read before the document at `value` and `pkey`, or is this document.
+    fn is_at_or_before(&self, value: &Bson, pkey: &[u8], reverse: bool) -> bool {
+        let ordering = cmp_index_values(&self.value, value);
+        let ordering = if reverse { ordering.reverse() } else { ordering };
+        ordering.then_with(|| self.pkey_bytes.as_slice().cmp(pkey)) != Ordering::Greater
+    }
+
+}
+
+/// The keys of an index in a range whose byte order is the order of the values,
+/// or their reverse order.
+struct IndexRun {
+    iter: RocksDBIterator,
+    lower: Vec<u8>,
+    upper: Vec<u8>,
+    /// Move from the upper bound to the lower bound.
+    backward: bool,
+    /// The types whose keys are read by the other runs.
+    skipped_types: &'static [u8],
+    prefix_len: usize,
+    started: bool,
+    exhausted: bool,
+    pending: VecDeque<IndexEntry>,
+}
+
+impl IndexRun {
+
+    fn new(txn: &TransactionInner, prefix: &[u8], lower: &[u8], upper: &[u8], backward: bool) -> IndexRun {
+        let bound = |bytes: &[u8]| {
+            let mut key = prefix.to_vec();
+            key.extend_from_slice(bytes);
+            key
+        };
+        IndexRun {
+            iter: txn.rocksdb_txn.new_iterator(),
+            lower: bound(lower),
+            upper: bound(upper),
+            backward,
+            skipped_types: &[],
+            prefix_len: prefix.len(),
+            started: false,
+            exhausted: false,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Only read the keys of the values of the type of `rank`, the runs
+    /// of the numbers hold the keys of the numbers, the other run the others.
+    fn restrict_to_type(&mut self, rank: u8) {
+        let numbers_run = self.lower.len() > self.prefix_len;
+        let numbers = rank == ElementType::Double as u8;
+        if numbers_run != numbers {
+            self.exhausted = true;
+        } else if !numbers {
+            self.lower.push(rank);
+            self.upper.truncate(self.prefix_len);
+            self.upper.push(rank + 1);
+        }
+    }
+
+    /// Skip the entries read before the document at `value` and `pkey`, and this document.
+    fn resume_after(&mut self, value: &Bson, pkey: &[u8], reverse: bool) -> Result<()> {
+        let mut key = self.lower[..self.prefix_len].to_vec();
+        key.extend_from_slice(&crate::utils::bson::stacked_key([value])?);
+        let ty = key[self.prefix_len];
+        if key >= self.lower && key < self.upper && !self.skipped_types.contains(&ty) {
+            // the keys of the run are in the order of the values, seek the key of the value
+            self.started = true;
+            if self.backward {
+                key.push(0xFF);
+                self.iter.seek_for_prev(&key);
+            } else {
+                key.extend_from_slice(pkey);
+                key.push(0);
+                self.iter.seek(&key);
+            }
+        }
+        while let Some(entry) = self.peek()? {
+            if !entry.is_at_or_before(value, pkey, reverse) {
+                break;
+            }
+            if type_rank(&entry.value) != type_rank(value) {
+                // the run holds no value of the type of the position, they are all read before it
+                self.exhausted = true;
+                self.pending.clear();
+                break;
+            }
+            self.pending.pop_front();
+        }
+        Ok(())
+    }
+
+    fn current_key(&self) -> Result<Option<Vec<u8>>> {
+        loop {
+            if !self.iter.valid() {
+                return Ok(None);
+            }
+            let key = self.iter.copy_key()?;
+            if key < self.lower || key >= self.upper {
+                return Ok(None);
+            }
+            let ty = key[self.prefix_len];
+            if !self.skipped_types.contains(&ty) {
+                return Ok(Some(key));
+            }
+            // jump over the keys of the type
+            let mut bound = key[..=self.prefix_len].to_vec();
+            if self.backward {
+                self.iter.seek_for_prev(&bound);
+            } else {
+                bound[self.prefix_len] = ty + 1;
+                self.iter.seek(&bound);
+            }
+        }
+    }
+
+    fn advance(&self) {
+        if self.backward {
+            self.iter.prev();
+        } else {
+            self.iter.next();
+        }
+    }
+
+    fn read_entry(&self, key: &[u8]) -> Result<(IndexEntry, usize)> {
+        let slices = crate::utils::bson::split_stacked_keys(&key[self.prefix_len..])?;
+        let mut slices = slices.into_iter();
+        let value = slices.next().expect("value must exist");
+        let pkey = slices.next().expect("pkey must exist");
+        let value_len = crate::utils::bson::stacked_key([&value])?.len();
+        let pkey_bytes = key[self.prefix_len + value_len..].to_vec();
+        Ok((IndexEntry { value, pkey, pkey_bytes }, self.prefix_len + value_len))
+    }
+
+    /// The next entry of the run, the entries of the same value
+    /// are always given in the order of their primary keys.
+    fn peek(&mut self) -> Result<Option<&IndexEntry>> {
+        if self.exhausted {
+            return Ok(None);
+        }
+        if !self.started {
+            self.started = true;
+            if self.backward {
+                self.iter.seek_for_prev(&self.upper);
+            } else {
+                self.iter.seek(&self.lower);
+            }
+        }
+        if self.pending.is_empty() {
+            if let Some(key) = self.current_key()? {
+                let (entry, value_end) = self.read_entry(&key)?;
+                self.pending.push_back(entry);
+                self.advance();
+                if self.backward {
+                    while let Some(next_key) = self.current_key()? {
+                        if next_key.len() < value_end || next_key[..value_end] != key[..value_end] {
+                            break;
+                        }
+                        self.pending.push_front(self.read_entry(&next_key)?.0);
+                        self.advance();
+                    }
+                }
+            }
+        }
+        Ok(self.pending.front())
+    }
+
+}
+
+enum Phase {
+    Index,
+    Missing,
+    Done,
+}
+
+/// Read the documents of a collection in the order of the values of an index,
+/// without buffering them. The documents missing the field of the index
+/// are given after the others, or first in the reverse order, like the in-memory sort.
+pub(crate) struct IndexOrderScan {
+    txn: TransactionInner,
+    col_name: String,
+    order: IndexOrder,
+    runs: Vec<IndexRun>,
+    missing: Option<Cursor>,
+    phase: Phase,
+    bounds: Option<IndexBounds>,
+}
+
+impl IndexOrderScan {
+
+    pub(crate) fn new(txn: &TransactionInner, col_name: &str, order: IndexOrder) -> Result<IndexOrderScan> {
+        let prefix = IndexHelper::index_prefix(col_name, &order.index_name)?;
+        let backward = order.reverse;
+        let double = ElementType::Double as u8;
+        let int32 = ElementType::Int32 as u8;
+        let int64 = ElementType::Int64 as u8;
+        let decimal = ElementType::Decimal128 as u8;
+        // the sign bit of the numbers is set for the negative numbers,
+        // the keys of the negative doubles are in the reverse order of their values
+        let mut runs = vec![
+            IndexRun::new(txn, &prefix, &[double, 0x80], &[double + 1], !backward),
+            IndexRun::new(txn, &prefix, &[double], &[double, 0x80], backward),
+            IndexRun::new(txn, &prefix, &[int32, 0x80], &[int32 + 1], backward),
+            IndexRun::new(txn, &prefix, &[int32], &[int32, 0x80], backward),
+            IndexRun::new(txn, &prefix, &[int64, 0x80], &[int64 + 1], backward),
+            IndexRun::new(txn, &prefix, &[int64], &[int64, 0x80], backward),
+            IndexRun::new(txn, &prefix, &[decimal], &[decimal + 1], backward),
+        ];
+        let mut others = IndexRun::new(txn, &prefix, &[], &[0xFF], backward);
+        others.skipped_types = &NUMBER_TYPES;
+        runs.push(others);
+
+        let phase = if backward { Phase::Missing } else { Phase::Index };
+        Ok(IndexOrderScan {
+            txn: txn.clone(),
+            col_name: col_name.to_string(),
+            order,
+            runs,
+            missing: None,
+            phase,
+            bounds: None,
+        })
+    }
+
+    /// Only read the documents whose values are within `bounds`, the documents
+    /// missing the field of the index are not read. The scan starts at the bound
+    /// it's read from by seeking its key.
+    pub(crate) fn set_bounds(&mut self, bounds: IndexBounds) -> Result<()> {
+        // the position is after the documents of the bound if it's excluded, before them otherwise
+        let position = |value: &Bson, excluded: bool| IndexPosition {
+            value: Some(value.clone()),
+            pkey: if excluded { vec![0xFF] } else { Vec::new() },
+        };
+        let start = if self.order.reverse {
+            bounds.max.as_ref().map(|value| position(value, !bounds.max_included))
+        } else {
+            bounds.min.as_ref().map(|value| position(value, bounds.min_excluded))
+        };
+        if let Some(rank) = bounds.type_rank() {
+            for run in &mut self.runs {
+                run.restrict_to_type(rank);
+            }
+        }
+        self.phase = Phase::Index;
+        if let Some(position) = start {
+            self.resume_after(&position)?;
+        }
+        self.bounds = Some(bounds);
+        Ok(())
+    }
+
+    /// Start the scan after the document at `position`, the documents read before
+    /// this position are skipped by seeking the keys, not by reading them.
+    pub(crate) fn resume_after(&mut self, position: &IndexPosition) -> Result<()> {
+        let value = match &position.value {
+            Some(value) => value,
+            None => {
+                // among the documents missing the field, read in the order of their primary keys
+                let mut cursor = Cursor::new_with_str_prefix(
+                    self.col_name.as_str(),
+                    self.txn.rocksdb_txn.new_iterator(),
+                )?;
+                if cursor.reset_by_pkey_buf(&position.pkey)? {
+                    cursor.next()?;
+                }
+                self.missing = Some(cursor);
+                self.phase = Phase::Missing;
+                if !self.order.reverse {
+                    for run in &mut self.runs {
+                        run.exhausted = true;
+                    }
+                }
+                return Ok(());
+            }
+        };
+        // the documents missing the field are read first in the reverse order
+        self.phase = Phase::Index;
+        let reverse = self.order.reverse;
+        for run in &mut self.runs {
+            run.resume_after(value, &position.pkey, reverse)?;
+        }
+        Ok(())
+    }
+
+    /// The next document, with its encoding.
+    pub(crate) fn next_document(&mut self) -> Result<Option<(Document, Vec<u8>)>> {
+        loop {
+            let found = match self.phase {
+                Phase::Index => self.next_indexed()?,
+                Phase::Missing => self.next_missing()?,
+                Phase::Done => return Ok(None),
+            };
+            if found.is_some() {
+                return Ok(found);
+            }
+            self.phase = match (&self.phase, self.order.reverse) {
+                (Phase::Index, false) if self.bounds.is_none() => Phase::Missing,
+                (Phase::Missing, true) => Phase::Index,
+                _ => Phase::Done,
+            };
+        }
+    }
+
+    fn next_indexed(&mut self) -> Result<Option<(Document, Vec<u8>)>> {
+        loop {
+            let reverse = self.order.reverse;
+            let mut best: Option<usize> = None;
+            for i in 0..self.runs.len() {
+                if self.runs[i].peek()?.is_none() {
+                    continue;
+                }
+                let better = match best {
+                    None => true,
+                    Some(best) => {
+                        let candidate = self.runs[i].pending.front().unwrap();
+                        let current = self.runs[best].pending.front().unwrap();
+                        let ordering = candidate.cmp_value(current);
+                        let ordering = if reverse { ordering.reverse() } else { ordering };
+                        ordering.then_with(|| candidate.pkey_bytes.cmp(&current.pkey_bytes)) == Ordering::Less
+                    }
+                };
+                if better {
+                    best = Some(i);
+                }
+            }
+            let best = match best {
+                Some(best) => best,
+                None => return Ok(None),
+            };
+            // the values are read in order, the next ones are out of the bounds too
+            if let Some(bounds) = &self.bounds {
+                if !bounds.contains(&self.runs[best].pending.front().unwrap().value) {
+                    return Ok(None);
+                }
+            }
+            let entry = self.runs[best].pending.pop_front().unwrap();
+            let key = crate::utils::bson::stacked_key([
+                &Bson::String(self.col_name.clone()),
+                &entry.pkey,
+            ])?;
+            // the index may refer to a document deleted by this transaction
+            if let Some(buf) = self.txn.rocksdb_txn.get(&key)? {
+                let doc = bson::from_slice(&buf)?;
+                if self.is_first_entry(&doc, &entry.value) {
+                    return Ok(Some((doc, buf)));
+                }
+            }
+        }
+    }
+
+    /// A document with an array has an entry for each element, it's only given
+    /// at the entry of the first element read, the least one or the greatest one
+    /// in the reverse order, among the ones within the bounds.
+    fn is_first_entry(&self, doc: &Document, value: &Bson) -> bool {
+        // the order is the one of an index on a single field
+        let values: Vec<Bson> = IndexHelper::index_values(doc, &self.order.index_info)
+            .into_iter()
+            .flatten()
+            .collect();
+        if values.len() <= 1 {
+            return true;
+        }
+        let in_bounds = values.iter()
+            .filter(|value| self.bounds.as_ref().is_none_or(|bounds| bounds.contains(value)));
+        let first = if self.order.reverse {
+            in_bounds.max_by(|a, b| cmp_index_values(a, b))
+        } else {
+            in_bounds.min_by(|a, b| cmp_index_values(a, b))
+        };
+        first == Some(value)
+    }
+
+    fn next_missing(&mut self) -> Result<Option<(Document, Vec<u8>)>> {
+        if self.missing.is_none() {
+            let mut cursor = Cursor::new_with_str_prefix(
+                self.col_name.as_str(),
+                self.txn.rocksdb_txn.new_iterator(),
+            )?;
+            cursor.reset()?;
+            self.missing = Some(cursor);
+        }
+        let cursor = self.missing.as_mut().unwrap();
+        while cursor.has_next() {
+            let buf = cursor.copy_data()?;
+            let doc = bson::from_slice::<Document>(buf.as_ref())?;
+            cursor.next()?;
+            if IndexHelper::index_values(&doc, &self.order.index_info).is_empty() {
+                return Ok(Some((doc, buf)));
+            }
+        }
+        Ok(None)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{doc, Bson};
+    use indexmap::IndexMap;
+    use crate::coll::collection_info::IndexInfo;
+    use super::{IndexBounds, IndexOrder};
+
+    #[test]
+    fn test_find_index_order() {
+        let mut indexes = IndexMap::new();
+        indexes.insert("created_1".to_string(), IndexInfo::single_index("created".to_string(), 1, None));
+
+        let order = IndexOrder::find(&indexes, &doc! { "created": -1 }).unwrap();
+        assert_eq!(order.index_name, "created_1");
+        assert!(order.reverse);
+        let order = IndexOrder::find(&indexes, &doc! { "created": 1 }).unwrap();
+        assert!(!order.reverse);
+
+        assert!(IndexOrder::find(&indexes, &doc! { "name": 1 }).is_none());
+        assert!(IndexOrder::find(&indexes, &doc! { "created": 1, "name": 1 }).is_none());
+    }
+
+    #[test]
+    fn test_index_bounds() {
+        let info = IndexInfo::single_index("created".to_string(), 1, None);
+
+        let bounds = IndexBounds::new(&info, Some(&doc! { "created": 2 }), Some(&doc! { "created": 5 })).unwrap();
+        assert!(!bounds.contains(&Bson::Int32(1)));
+        assert!(bounds.contains(&Bson::Double(2.0)));
+        assert!(bounds.contains(&Bson::Int64(4)));
+        assert!(!bounds.contains(&Bson::Int32(5)));
+        assert!(!bounds.contains(&Bson::String("a".to_string())));
+
+        let bounds = IndexBounds::new(&info, None, Some(&doc! { "created": 5 })).unwrap();
+        assert!(bounds.contains(&Bson::Int32(-10)));
+
+        assert!(IndexBounds::new(&info, Some(&doc! { "name": 2 }), None).is_err());
+        assert!(IndexBounds::new(&info, None, Some(&doc! { "created": 2, "name": 1 })).is_err());
+    }
+
+    #[test]
+    fn test_index_bounds_from_condition() {
+        let info = IndexInfo::single_index("created".to_string(), 1, None);
+
+        let bounds = IndexBounds::from_condition(&info, &doc! { "$gt": 2, "$gte": 1, "$lte": 5_i64 }).unwrap();
+        assert!(!bounds.contains(&Bson::Int32(2)));
+        assert!(bounds.contains(&Bson::Double(2.5)));
+        assert!(bounds.contains(&Bson::Int32(5)));
+        assert!(!bounds.contains(&Bson::Int32(6)));
+        assert!(!bounds.contains(&Bson::String("a".to_string())));
+
+        let bounds = IndexBounds::from_condition(&info, &doc! { "$lt": 5.5, "$ne": 3 }).unwrap();
+        assert!(bounds.contains(&Bson::Int32(-10)));
+        assert!(!bounds.contains(&Bson::Int32(6)));
+        assert!(!bounds.contains(&Bson::String("a".to_string())));
+
+        // the range only holds the values of the type of its bound
+        let bounds = IndexBounds::from_condition(&info, &doc! { "$gt": "a" }).unwrap();
+        assert!(bounds.contains(&Bson::String("b".to_string())));
+        assert!(!bounds.contains(&Bson::Int32(1)));
+        let bounds = IndexBounds::from_condition(&info, &doc! { "$gt": "a", "$lt": 5 }).unwrap();
+        assert!(bounds.max.is_none());
+
+        // each side may be satisfied by another element of an array
+        let mut multikey = info.clone();
+        multikey.set_multikey();
+        let bounds = IndexBounds::from_condition(&multikey, &doc! { "$gt": 2, "$lt": 5 }).unwrap();
+        assert!(bounds.contains(&Bson::Int32(6)));
+        assert!(!bounds.contains(&Bson::Int32(2)));
+
+        assert!(IndexBounds::from_condition(&info, &doc! { "$ne": 3 }).is_none());
+        assert!(IndexBounds::from_condition(&info, &doc! { "$gt": { "a": 1 } }).is_none());
+    }
+}
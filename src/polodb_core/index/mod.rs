@@ -15,7 +15,11 @@
 mod index_helper;
 mod index_model;
 mod index_builder;
+mod index_order;
+mod partial_filter;
 
 pub(crate) use index_helper::{IndexHelper, IndexHelperOperation, INDEX_PREFIX, make_index_key_with_query_key};
 pub(crate) use index_builder::IndexBuilder;
-pub use index_model::{IndexModel, IndexOptions};
+pub(crate) use index_order::{IndexBounds, IndexOrder, IndexOrderScan, IndexPosition, IndexRange};
+pub(crate) use partial_filter::PartialFilter;
+pub use index_model::{IndexModel, IndexOptions, IndexOptionsBuilder};
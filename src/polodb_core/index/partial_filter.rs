@@ -0,0 +1,217 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+use bson::{Bson, Document};
+use crate::options::Collation;
+use crate::utils::bson::{try_get_document_value, value_cmp};
+use crate::vm::{field_cmp, value_in, DbOp};
+use crate::{Error, Result};
+
+/// The filter of a partial index: only the documents matching it have entries
+/// in the index, so the index only serves the queries whose documents all match it.
+///
+/// The filter is a conjunction of conditions on fields: the equalities, `$eq`,
+/// `$gt`, `$gte`, `$lt`, `$lte` and `$in`, possibly grouped by `$and`.
+#[derive(Debug, Clone)]
+pub(crate) struct PartialFilter {
+    clauses: Vec<(String, Predicate)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Predicate {
+    Eq(Bson),
+    Gt(Bson),
+    Gte(Bson),
+    Lt(Bson),
+    Lte(Bson),
+    In(Vec<Bson>),
+}
+
+impl PartialFilter {
+
+    pub(crate) fn parse(filter: &Document) -> Result<PartialFilter> {
+        let mut clauses = Vec::new();
+        parse_clauses(filter, &mut clauses, true)?;
+        Ok(PartialFilter {
+            clauses,
+        })
+    }
+
+    /// Whether the document matches the filter, a missing field matches no condition.
+    pub(crate) fn matches(&self, doc: &Document, collation: Collation) -> bool {
+        self.clauses.iter().all(|(key, predicate)| match try_get_document_value(doc, key) {
+            Some(value) => predicate.matches(&value, collation),
+            None => false,
+        })
+    }
+
+    /// Whether the documents matching the query all match the filter: each condition
+    /// of the filter is implied by a condition of the query on the same field, such as
+    /// `{ "age": { "$gt": 30 } }` for `{ "age": { "$gt": 18 } }`. The other conditions
+    /// of the query only narrow its documents down, they are left aside.
+    pub(crate) fn is_implied_by(&self, query: &Document, collation: Collation) -> bool {
+        let mut query_clauses = Vec::new();
+        if parse_clauses(query, &mut query_clauses, false).is_err() {
+            return false;
+        }
+        self.clauses.iter().all(|(key, predicate)| {
+            query_clauses.iter().any(|(query_key, query_predicate)| {
+                query_key == key && query_predicate.implies(predicate, collation)
+            })
+        })
+    }
+
+}
+
+fn unsupported(operator: &str) -> Error {
+    Error::UnsupportedIndexOption(format!("partialFilterExpression with {}", operator))
+}
+
+/// Add the conditions of `filter` to `clauses`. In the `strict` mode, the filter of an index,
+/// the other operators are rejected, otherwise, for a query, they are skipped.
+fn parse_clauses(filter: &Document, clauses: &mut Vec<(String, Predicate)>, strict: bool) -> Result<()> {
+    for (key, value) in filter {
+        match key.as_str() {
+            "$and" => {
+                let items = match value {
+                    Bson::Array(items) => items,
+                    _ => return Err(unsupported("a $and which is not an array")),
+                };
+                for item in items {
+                    match item {
+                        Bson::Document(doc) => parse_clauses(doc, clauses, strict)?,
+                        _ => return Err(unsupported("a $and which is not an array of documents")),
+                    }
+                }
+            }
+            _ if key.starts_with('$') => {
+                if strict {
+                    return Err(unsupported(key));
+                }
+            }
+            _ => match value {
+                Bson::Document(condition) if condition.keys().next().is_some_and(|op| op.starts_with('$')) => {
+                    for (op, operand) in condition {
+                        let predicate = match (op.as_str(), operand) {
+                            ("$eq", _) => Predicate::Eq(operand.clone()),
+                            ("$gt", _) => Predicate::Gt(operand.clone()),
+                            ("$gte", _) => Predicate::Gte(operand.clone()),
+                            ("$lt", _) => Predicate::Lt(operand.clone()),
+                            ("$lte", _) => Predicate::Lte(operand.clone()),
+                            ("$in", Bson::Array(values)) => Predicate::In(values.clone()),
+                            _ if strict => return Err(unsupported(op)),
+                            _ => continue,
+                        };
+                        clauses.push((key.clone(), predicate));
+                    }
+                }
+                Bson::RegularExpression(_) if strict => return Err(unsupported("$regex")),
+                Bson::RegularExpression(_) => (),
+                _ => clauses.push((key.clone(), Predicate::Eq(value.clone()))),
+            },
+        }
+    }
+    Ok(())
+}
+
+impl Predicate {
+
+    /// Whether the value satisfies the predicate, compared like the filter of a query.
+    fn matches(&self, value: &Bson, collation: Collation) -> bool {
+        let (op, other) = match self {
+            Predicate::Eq(other) => (DbOp::Equal, other),
+            Predicate::Gt(other) => (DbOp::Greater, other),
+            Predicate::Gte(other) => (DbOp::GreaterEqual, other),
+            Predicate::Lt(other) => (DbOp::Less, other),
+            Predicate::Lte(other) => (DbOp::LessEqual, other),
+            Predicate::In(values) => return value_in(value, values, collation),
+        };
+        field_cmp(op, value, other, collation).unwrap_or(false)
+    }
+
+    /// Whether the values satisfying this predicate of a query all satisfy `other`.
+    fn implies(&self, other: &Predicate, collation: Collation) -> bool {
+        if self == other {
+            return true;
+        }
+        // an array is equal to the documents holding it as an element too
+        let is_value = |value: &Bson| !matches!(value, Bson::Array(_));
+        // the bounds of different types aren't ordered like the values between them
+        let cmp = |a: &Bson, b: &Bson| {
+            if a.element_type() != b.element_type() {
+                return None;
+            }
+            value_cmp(&collation.collate(a), &collation.collate(b)).ok()
+        };
+        match (self, other) {
+            (Predicate::Eq(value), _) => is_value(value) && other.matches(value, collation),
+            (Predicate::In(values), _) => values.iter().all(|value| is_value(value) && other.matches(value, collation)),
+            (Predicate::Gt(b), Predicate::Gt(a) | Predicate::Gte(a))
+            | (Predicate::Gte(b), Predicate::Gte(a)) => matches!(cmp(b, a), Some(Ordering::Greater | Ordering::Equal)),
+            (Predicate::Gte(b), Predicate::Gt(a)) => cmp(b, a) == Some(Ordering::Greater),
+            (Predicate::Lt(b), Predicate::Lt(a) | Predicate::Lte(a))
+            | (Predicate::Lte(b), Predicate::Lte(a)) => matches!(cmp(b, a), Some(Ordering::Less | Ordering::Equal)),
+            (Predicate::Lte(b), Predicate::Lt(a)) => cmp(b, a) == Some(Ordering::Less),
+            _ => false,
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+    use crate::options::Collation;
+    use super::PartialFilter;
+
+    fn implied(filter: bson::Document, query: bson::Document) -> bool {
+        PartialFilter::parse(&filter).unwrap().is_implied_by(&query, Collation::Simple)
+    }
+
+    #[test]
+    fn test_partial_filter_matches() {
+        let filter = PartialFilter::parse(&doc! { "age": { "$gte": 18 }, "kind": "user" }).unwrap();
+        assert!(filter.matches(&doc! { "age": 18, "kind": "user" }, Collation::Simple));
+        assert!(filter.matches(&doc! { "age": [3, 40], "kind": "user" }, Collation::Simple));
+        assert!(!filter.matches(&doc! { "age": 17, "kind": "user" }, Collation::Simple));
+        assert!(!filter.matches(&doc! { "kind": "user" }, Collation::Simple));
+
+        let filter = PartialFilter::parse(&doc! { "closed": null }).unwrap();
+        assert!(filter.matches(&doc! { "closed": null }, Collation::Simple));
+        assert!(!filter.matches(&doc! {}, Collation::Simple));
+
+        assert!(PartialFilter::parse(&doc! { "$or": [{ "a": 1 }] }).is_err());
+        assert!(PartialFilter::parse(&doc! { "a": { "$ne": 1 } }).is_err());
+    }
+
+    #[test]
+    fn test_partial_filter_implied_by() {
+        let filter = doc! { "age": { "$gt": 18 } };
+        assert!(implied(filter.clone(), doc! { "age": { "$gt": 18 } }));
+        assert!(implied(filter.clone(), doc! { "age": { "$gte": 30 }, "name": "a" }));
+        assert!(implied(filter.clone(), doc! { "age": 20 }));
+        assert!(implied(filter.clone(), doc! { "age": { "$in": [20, 30] } }));
+        assert!(implied(filter.clone(), doc! { "$and": [{ "age": { "$gt": 20 } }] }));
+        assert!(!implied(filter.clone(), doc! { "age": { "$gte": 18 } }));
+        assert!(!implied(filter.clone(), doc! { "age": { "$in": [10, 30] } }));
+        assert!(!implied(filter.clone(), doc! { "age": { "$lt": 30 } }));
+        assert!(!implied(filter.clone(), doc! { "age": { "$gt": 20.5 } }));
+        assert!(!implied(filter, doc! { "name": "a" }));
+
+        assert!(implied(doc! { "status": "active", "age": { "$lte": 65 } }, doc! { "status": "active", "age": 40 }));
+        assert!(!implied(doc! { "status": "active" }, doc! { "status": { "$in": ["active", "idle"] } }));
+    }
+
+}
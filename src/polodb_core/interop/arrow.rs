@@ -0,0 +1,399 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! Conversion between BSON documents and Arrow record batches.
+//!
+//! Every field of the schema is nullable, a missing field is converted to null.
+//! When the schema is inferred, the type of a field is decided by all the values
+//! of the field:
+//!
+//! | BSON                     | Arrow                         |
+//! |--------------------------|-------------------------------|
+//! | Boolean                  | Boolean                       |
+//! | Int32                    | Int32                         |
+//! | Int32 and Int64          | Int64                         |
+//! | Double mixed with ints   | Float64                       |
+//! | DateTime                 | Timestamp(Millisecond, None)  |
+//! | Binary                   | Binary                        |
+//! | others, or mixed types   | Utf8 (relaxed extended JSON)  |
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+use arrow_array::{
+    Array, ArrayRef, BinaryArray, BooleanArray, Float64Array, Int32Array, Int64Array,
+    RecordBatch, RecordBatchOptions, StringArray, TimestampMicrosecondArray,
+    TimestampMillisecondArray, TimestampNanosecondArray, TimestampSecondArray,
+};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Date32Type, Date64Type, Float32Type, Float64Type, Int16Type, Int32Type, Int64Type, Int8Type,
+    TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+    TimestampSecondType, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use bson::{Binary, Bson, DateTime, Document};
+use bson::spec::BinarySubtype;
+use indexmap::IndexMap;
+use crate::utils::bson::try_get_document_value;
+use crate::Result;
+
+pub(crate) const DEFAULT_BATCH_SIZE: usize = 8192;
+
+fn get_field_value(doc: &Document, field: &str) -> Option<Bson> {
+    if field.contains('.') {
+        try_get_document_value(doc, field)
+    } else {
+        doc.get(field).cloned()
+    }
+}
+
+fn bson_data_type(value: &Bson) -> Option<DataType> {
+    let ty = match value {
+        Bson::Null | Bson::Undefined => return None,
+        Bson::Boolean(_) => DataType::Boolean,
+        Bson::Int32(_) => DataType::Int32,
+        Bson::Int64(_) => DataType::Int64,
+        Bson::Double(_) => DataType::Float64,
+        Bson::DateTime(_) => DataType::Timestamp(TimeUnit::Millisecond, None),
+        Bson::Binary(_) => DataType::Binary,
+        _ => DataType::Utf8,
+    };
+    Some(ty)
+}
+
+fn merge_data_type(prev: &DataType, next: DataType) -> DataType {
+    match (prev, &next) {
+        (a, b) if a == b => next,
+        (DataType::Int32, DataType::Int64) | (DataType::Int64, DataType::Int32) => DataType::Int64,
+        (DataType::Int32 | DataType::Int64, DataType::Float64) |
+        (DataType::Float64, DataType::Int32 | DataType::Int64) => DataType::Float64,
+        _ => DataType::Utf8,
+    }
+}
+
+/// Infer the schema from the documents.
+///
+/// If `projection` is given, the fields are in the order of the projection,
+/// otherwise in the order they first appear in the documents.
+pub(crate) fn infer_schema(docs: &[Document], projection: Option<&[String]>) -> Schema {
+    let mut types: IndexMap<String, Option<DataType>> = IndexMap::new();
+
+    let mut merge = |name: &str, value: &Bson| {
+        let next = match bson_data_type(value) {
+            Some(ty) => ty,
+            None => {
+                types.entry(name.to_string()).or_insert(None);
+                return;
+            }
+        };
+        let entry = types.entry(name.to_string()).or_insert(None);
+        *entry = Some(match entry {
+            Some(prev) => merge_data_type(prev, next),
+            None => next,
+        });
+    };
+
+    if let Some(projection) = projection {
+        for field in projection {
+            merge(field, &Bson::Null);
+        }
+    }
+
+    for doc in docs {
+        match projection {
+            Some(projection) => {
+                for field in projection {
+                    if let Some(value) = get_field_value(doc, field) {
+                        merge(field, &value);
+                    }
+                }
+            }
+            None => {
+                for (key, value) in doc.iter() {
+                    merge(key, value);
+                }
+            }
+        }
+    }
+
+    let fields = types
+        .into_iter()
+        .map(|(name, ty)| Field::new(name, ty.unwrap_or(DataType::Utf8), true))
+        .collect::<Vec<Field>>();
+
+    Schema::new(fields)
+}
+
+fn unexpected_value(field: &Field, value: &Bson) -> ArrowError {
+    ArrowError::CastError(format!(
+        "can not convert {:?} to {} for field '{}'",
+        value.element_type(),
+        field.data_type(),
+        field.name(),
+    ))
+}
+
+fn to_i64(value: &Bson) -> Option<i64> {
+    match value {
+        Bson::Int32(v) => Some(*v as i64),
+        Bson::Int64(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn to_string(value: Bson) -> String {
+    match value {
+        Bson::String(s) => s,
+        Bson::ObjectId(oid) => oid.to_hex(),
+        Bson::Symbol(s) => s,
+        other => other.into_relaxed_extjson().to_string(),
+    }
+}
+
+/// Collect the values of a column, `convert` returns `None` if
+/// the value can not be represented by the type of the field.
+fn collect_column<T, F>(values: Vec<Option<Bson>>, field: &Field, convert: F) -> Result<Vec<Option<T>>>
+where
+    F: Fn(&Bson) -> Option<T>,
+{
+    let mut result = Vec::with_capacity(values.len());
+    for value in values {
+        match value {
+            None | Some(Bson::Null) | Some(Bson::Undefined) => result.push(None),
+            Some(value) => {
+                let v = convert(&value).ok_or_else(|| unexpected_value(field, &value))?;
+                result.push(Some(v));
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn build_timestamp_column(values: Vec<Option<Bson>>, field: &Field, unit: &TimeUnit, tz: &Option<Arc<str>>) -> Result<ArrayRef> {
+    let millis = collect_column(values, field, |value| match value {
+        Bson::DateTime(dt) => Some(dt.timestamp_millis()),
+        _ => None,
+    })?;
+    let array: ArrayRef = match unit {
+        TimeUnit::Second => Arc::new(
+            TimestampSecondArray::from(millis.into_iter().map(|v| v.map(|v| v.div_euclid(1000))).collect::<Vec<_>>())
+                .with_timezone_opt(tz.clone())
+        ),
+        TimeUnit::Millisecond => Arc::new(
+            TimestampMillisecondArray::from(millis).with_timezone_opt(tz.clone())
+        ),
+        TimeUnit::Microsecond => Arc::new(
+            TimestampMicrosecondArray::from(millis.into_iter().map(|v| v.map(|v| v * 1000)).collect::<Vec<_>>())
+                .with_timezone_opt(tz.clone())
+        ),
+        TimeUnit::Nanosecond => Arc::new(
+            TimestampNanosecondArray::from(millis.into_iter().map(|v| v.map(|v| v * 1_000_000)).collect::<Vec<_>>())
+                .with_timezone_opt(tz.clone())
+        ),
+    };
+    Ok(array)
+}
+
+fn build_column(values: Vec<Option<Bson>>, field: &Field) -> Result<ArrayRef> {
+    let array: ArrayRef = match field.data_type() {
+        DataType::Boolean => Arc::new(BooleanArray::from(collect_column(values, field, Bson::as_bool)?)),
+        DataType::Int32 => Arc::new(Int32Array::from(collect_column(values, field, |value| {
+            to_i64(value).and_then(|v| i32::try_from(v).ok())
+        })?)),
+        DataType::Int64 => Arc::new(Int64Array::from(collect_column(values, field, to_i64)?)),
+        DataType::Float64 => Arc::new(Float64Array::from(collect_column(values, field, |value| match value {
+            Bson::Double(v) => Some(*v),
+            _ => to_i64(value).map(|v| v as f64),
+        })?)),
+        DataType::Utf8 => Arc::new(StringArray::from(collect_column(values, field, |value| {
+            Some(to_string(value.clone()))
+        })?)),
+        DataType::Binary => {
+            let bytes = collect_column(values, field, |value| match value {
+                Bson::Binary(bin) => Some(bin.bytes.clone()),
+                _ => None,
+            })?;
+            Arc::new(bytes.into_iter().collect::<BinaryArray>())
+        }
+        DataType::Timestamp(unit, tz) => build_timestamp_column(values, field, unit, tz)?,
+        other => {
+            return Err(ArrowError::NotYetImplemented(format!(
+                "data type {} of field '{}' is not supported", other, field.name()
+            )).into());
+        }
+    };
+    Ok(array)
+}
+
+fn documents_to_record_batch(docs: &[Document], schema: &SchemaRef) -> Result<RecordBatch> {
+    let mut columns = Vec::<ArrayRef>::with_capacity(schema.fields().len());
+    for field in schema.fields() {
+        let values = docs
+            .iter()
+            .map(|doc| get_field_value(doc, field.name()))
+            .collect::<Vec<Option<Bson>>>();
+        columns.push(build_column(values, field)?);
+    }
+    let options = RecordBatchOptions::new().with_row_count(Some(docs.len()));
+    let batch = RecordBatch::try_new_with_options(schema.clone(), columns, &options)?;
+    Ok(batch)
+}
+
+/// Convert the documents into record batches of at most `batch_size` rows.
+pub(crate) fn documents_to_record_batches(
+    docs: &[Document],
+    schema: SchemaRef,
+    batch_size: usize,
+) -> Result<Vec<RecordBatch>> {
+    let mut batches = Vec::with_capacity(docs.len() / batch_size.max(1) + 1);
+    for chunk in docs.chunks(batch_size.max(1)) {
+        batches.push(documents_to_record_batch(chunk, &schema)?);
+    }
+    Ok(batches)
+}
+
+/// Read the value at `row` of the column, `None` if it's null.
+fn column_value(column: &ArrayRef, row: usize, field: &Field) -> Result<Option<Bson>> {
+    if column.is_null(row) {
+        return Ok(None);
+    }
+    let value = match column.data_type() {
+        DataType::Null => return Ok(None),
+        DataType::Boolean => Bson::Boolean(column.as_boolean().value(row)),
+        DataType::Int8 => Bson::Int32(column.as_primitive::<Int8Type>().value(row) as i32),
+        DataType::Int16 => Bson::Int32(column.as_primitive::<Int16Type>().value(row) as i32),
+        DataType::Int32 => Bson::Int32(column.as_primitive::<Int32Type>().value(row)),
+        DataType::Int64 => Bson::Int64(column.as_primitive::<Int64Type>().value(row)),
+        DataType::UInt8 => Bson::Int32(column.as_primitive::<UInt8Type>().value(row) as i32),
+        DataType::UInt16 => Bson::Int32(column.as_primitive::<UInt16Type>().value(row) as i32),
+        DataType::UInt32 => Bson::Int64(column.as_primitive::<UInt32Type>().value(row) as i64),
+        DataType::UInt64 => {
+            let v = column.as_primitive::<UInt64Type>().value(row);
+            let v = i64::try_from(v).map_err(|_| ArrowError::CastError(format!(
+                "value {} of field '{}' overflows int64", v, field.name()
+            )))?;
+            Bson::Int64(v)
+        }
+        DataType::Float32 => Bson::Double(column.as_primitive::<Float32Type>().value(row) as f64),
+        DataType::Float64 => Bson::Double(column.as_primitive::<Float64Type>().value(row)),
+        DataType::Utf8 => Bson::String(column.as_string::<i32>().value(row).to_string()),
+        DataType::LargeUtf8 => Bson::String(column.as_string::<i64>().value(row).to_string()),
+        DataType::Binary | DataType::LargeBinary => {
+            let bytes = match column.data_type() {
+                DataType::Binary => column.as_binary::<i32>().value(row),
+                _ => column.as_binary::<i64>().value(row),
+            };
+            Bson::Binary(Binary {
+                subtype: BinarySubtype::Generic,
+                bytes: bytes.to_vec(),
+            })
+        }
+        DataType::Date32 => {
+            let days = column.as_primitive::<Date32Type>().value(row) as i64;
+            Bson::DateTime(DateTime::from_millis(days * 86_400_000))
+        }
+        DataType::Date64 => Bson::DateTime(DateTime::from_millis(column.as_primitive::<Date64Type>().value(row))),
+        DataType::Timestamp(unit, _) => {
+            let millis = match unit {
+                TimeUnit::Second => column.as_primitive::<TimestampSecondType>().value(row) * 1000,
+                TimeUnit::Millisecond => column.as_primitive::<TimestampMillisecondType>().value(row),
+                TimeUnit::Microsecond => column.as_primitive::<TimestampMicrosecondType>().value(row).div_euclid(1000),
+                TimeUnit::Nanosecond => column.as_primitive::<TimestampNanosecondType>().value(row).div_euclid(1_000_000),
+            };
+            Bson::DateTime(DateTime::from_millis(millis))
+        }
+        other => {
+            return Err(ArrowError::NotYetImplemented(format!(
+                "data type {} of field '{}' is not supported", other, field.name()
+            )).into());
+        }
+    };
+    Ok(Some(value))
+}
+
+/// Insert the value into the document, a dotted name like `author.name`
+/// is inserted as a nested document.
+fn insert_field_value(doc: &mut Document, name: &str, value: Bson) {
+    match name.split_once('.') {
+        Some((first, remains)) => {
+            if !matches!(doc.get(first), Some(Bson::Document(_))) {
+                doc.insert(first, Document::new());
+            }
+            if let Some(Bson::Document(sub_doc)) = doc.get_mut(first) {
+                insert_field_value(sub_doc, remains, value);
+            }
+        }
+        None => {
+            doc.insert(name, value);
+        }
+    }
+}
+
+/// Convert every row of the record batch into a document, null values are omitted.
+#[cfg_attr(not(feature = "parquet"), allow(dead_code))]
+pub(crate) fn record_batch_to_documents(batch: &RecordBatch) -> Result<Vec<Document>> {
+    let schema = batch.schema();
+    let mut docs = vec![Document::new(); batch.num_rows()];
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        for (row, doc) in docs.iter_mut().enumerate() {
+            if let Some(value) = column_value(column, row, field)? {
+                insert_field_value(doc, field.name(), value);
+            }
+        }
+    }
+    Ok(docs)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_schema::{DataType, TimeUnit};
+    use bson::{doc, DateTime};
+    use super::{documents_to_record_batches, infer_schema, record_batch_to_documents};
+
+    #[test]
+    fn test_infer_schema() {
+        let docs = vec![
+            doc! { "a": 1, "b": "x", "c": 1, "d": DateTime::from_millis(0) },
+            doc! { "a": 2_i64, "b": 1, "c": 1.5, "e": null },
+        ];
+        let schema = infer_schema(&docs, None);
+        let types = schema.fields().iter().map(|f| f.data_type().clone()).collect::<Vec<DataType>>();
+        assert_eq!(types, vec![
+            DataType::Int64,
+            DataType::Utf8,
+            DataType::Float64,
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            DataType::Utf8,
+        ]);
+
+        let projection = vec!["c".to_string(), "a".to_string()];
+        let schema = infer_schema(&docs, Some(&projection));
+        assert_eq!(schema.field(0).name(), "c");
+        assert_eq!(schema.field(1).data_type(), &DataType::Int64);
+    }
+
+    #[test]
+    fn test_record_batch_to_documents() {
+        let docs = vec![
+            doc! { "a": 1_i64, "b": { "c": "x" }, "d": DateTime::from_millis(1000) },
+            doc! { "a": 2_i64 },
+        ];
+        let projection = vec!["a".to_string(), "b.c".to_string(), "d".to_string()];
+        let schema = std::sync::Arc::new(infer_schema(&docs, Some(&projection)));
+        let batches = documents_to_record_batches(&docs, schema, 10).unwrap();
+        let result = record_batch_to_documents(&batches[0]).unwrap();
+        assert_eq!(result, docs);
+    }
+}
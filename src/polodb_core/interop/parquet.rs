@@ -0,0 +1,68 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::fs::File;
+use std::path::Path;
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use bson::Document;
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder};
+use crate::interop::arrow::record_batch_to_documents;
+use crate::Result;
+
+/// Write the record batches into a new Parquet file at `path`,
+/// return the number of rows written.
+pub(crate) fn write_parquet(path: &Path, schema: SchemaRef, batches: &[RecordBatch]) -> Result<u64> {
+    let file = File::create(path)?;
+    let mut writer = ArrowWriter::try_new(file, schema, None)?;
+    let mut rows: u64 = 0;
+    for batch in batches {
+        writer.write(batch)?;
+        rows += batch.num_rows() as u64;
+    }
+    writer.close()?;
+    Ok(rows)
+}
+
+/// Iterate the rows of a Parquet file as documents, batch by batch.
+pub(crate) struct ParquetDocumentReader {
+    reader: ParquetRecordBatchReader,
+}
+
+impl ParquetDocumentReader {
+
+    pub(crate) fn open(path: &Path, batch_size: usize) -> Result<ParquetDocumentReader> {
+        let file = File::open(path)?;
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)?
+            .with_batch_size(batch_size)
+            .build()?;
+        Ok(ParquetDocumentReader {
+            reader,
+        })
+    }
+
+}
+
+impl Iterator for ParquetDocumentReader {
+    type Item = Result<Vec<Document>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let batch = self.reader.next()?;
+        Some(batch
+            .map_err(Into::into)
+            .and_then(|batch| record_batch_to_documents(&batch)))
+    }
+}
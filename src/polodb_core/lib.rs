@@ -152,19 +152,91 @@ pub mod results;
 
 pub mod test_utils;
 mod metrics;
+mod profiler;
+mod current_op;
+mod hooks;
+mod change_stream;
+mod oplog;
+mod audit;
+mod schema;
+mod capped;
+mod expiry;
+mod bloom;
+mod fuzzy;
+mod elem_match;
+mod record_cache;
+mod query_cache;
+mod verify;
+mod replication;
+pub mod sync;
+pub mod geo;
+mod crdt;
+mod temp;
+mod namespace;
+mod attach;
+mod snapshot;
+mod lock;
+mod scheduler;
+mod vector;
+mod view;
+mod strictness;
+mod version_info;
+mod object_id;
+mod defaults;
 mod utils;
 mod index;
 mod coll;
+mod interop;
+#[cfg(feature = "gridfs")]
+mod gridfs;
+#[cfg(feature = "sql")]
+pub mod sql;
 pub mod action;
+pub mod query;
 
 pub use db::{Database, Result};
-pub use coll::{Collection, CollectionT, TransactionalCollection};
-pub use coll::collection_info::IndexInfo;
-pub use config::{Config, ConfigBuilder};
+pub use coll::{Collection, CollectionT, Model, TransactionalCollection};
+pub use coll::collection_info::{CollectionStatistics, FieldStatistics, IndexInfo, MaterializedViewInfo, ValueFrequency, VectorIndexInfo, ViewInfo};
+pub use config::{Config, ConfigBuilder, StorageLayout};
+/// Serialize a `uuid::Uuid` as a BSON binary of the UUID subtype,
+/// the compact form to use for a UUID `_id`: `#[serde(with = "polodb_core::uuid_as_binary")]`.
+pub use bson::serde_helpers::uuid_1_as_binary as uuid_as_binary;
+/// Serialize a `chrono::DateTime<Utc>` as a BSON datetime, instead of a string:
+/// `#[serde(with = "polodb_core::chrono_as_datetime")]`.
+#[cfg(feature = "chrono")]
+pub use bson::serde_helpers::chrono_datetime_as_bson_datetime as chrono_as_datetime;
+/// Serialize a `time::OffsetDateTime` as a BSON datetime, instead of a string:
+/// `#[serde(with = "polodb_core::time_as_datetime")]`.
+#[cfg(feature = "time")]
+pub use bson::serde_helpers::time_0_3_offsetdatetime_as_bson_datetime as time_as_datetime;
+pub use object_id::{ObjectIdCounterMode, ObjectIdExt};
+pub use strictness::BsonStrictness;
 pub use transaction::Transaction;
-pub use db::client_cursor::ClientCursor;
-pub use errors::Error;
-pub use metrics::Metrics;
-pub use index::{IndexModel, IndexOptions};
+pub use db::client_cursor::{ClientCursor, RawCursor};
+pub use errors::{Error, ErrorCode, DecodeError, DocumentLimit, DocumentLimitError, InvalidBsonError};
+pub use current_op::CurrentOp;
+pub use hooks::HookEvent;
+pub use change_stream::{ChangeBatch, ChangeEvent, ChangeStream, OperationType, UpdateDescription};
+pub use metrics::{Metrics, MetricsSnapshot, OperationMetrics, Histogram, StorageStatistics};
+pub use audit::{AuditLog, AuditEntry, AuditSink, DEFAULT_AUDIT_COLLECTION};
+pub use profiler::{Profiler, ProfileEntry, ProfilerSink, DEFAULT_PROFILE_COLLECTION};
+pub use verify::{CollectionVerifyReport, VerifyIssue, VerifyReport};
+pub use replication::{Replica, ReplicaState, ReplicaStatus, ReplicationServer, ReplicationTransport};
+pub use sync::SyncEngine;
+pub use temp::TempCollection;
+pub use namespace::LogicalDatabase;
+pub use lock::{AdvisoryLock, LockHolder};
+pub use scheduler::{Job, JobStatus, Scheduler};
+pub use vector::{Vector, VectorSimilarity};
+pub use version_info::VersionInfo;
+pub use index::{IndexModel, IndexOptions, IndexOptionsBuilder};
+#[cfg(feature = "gridfs")]
+pub use gridfs::{GridFsBucket, GridFsDownloadStream, GridFsFile, GridFsUploadStream};
+#[cfg(feature = "derive")]
+pub use polodb_derive::PoloModel;
 
 pub extern crate bson;
+#[cfg(feature = "arrow")]
+pub extern crate arrow_array;
+#[cfg(feature = "arrow")]
+pub extern crate arrow_schema;
@@ -0,0 +1,146 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use bson::{Bson, DateTime};
+use bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use crate::db::db_inner::DatabaseInner;
+use crate::transaction::TransactionInner;
+use crate::{Error, Result};
+
+/// The prefix of the keys of the advisory locks.
+const LOCK_PREFIX: &str = "$LOCK";
+
+/// The owner of an advisory lock, until the lock expires.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockHolder {
+    pub owner: String,
+    pub acquired_at: DateTime,
+    pub expires_at: DateTime,
+}
+
+/// A named lock stored in the database, to coordinate the instances of an application,
+/// such as running a job on one of them at a time. Created by [`crate::Database::lock`].
+///
+/// The lock is advisory: it doesn't prevent any read or write, the instances agree
+/// to run the job only while they hold it. It's acquired for a time to live,
+/// so the lock of a crashed instance is taken over once it expires.
+/// The lock can't be acquired on a replica, the writes are refused there.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use polodb_core::Database;
+/// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-advisory-lock");
+/// let db = Database::open_path(db_path).unwrap();
+/// let lock = db.lock("report-job").owner("worker-1");
+/// if lock.try_acquire(Duration::from_secs(60)).unwrap() {
+///     // run the job, calling try_acquire again to extend the lock
+///     lock.release().unwrap();
+/// }
+/// ```
+pub struct AdvisoryLock {
+    db: Weak<DatabaseInner>,
+    name: String,
+    owner: String,
+}
+
+impl AdvisoryLock {
+
+    pub(crate) fn new(db: &Arc<DatabaseInner>, name: &str) -> AdvisoryLock {
+        AdvisoryLock {
+            db: Arc::downgrade(db),
+            name: name.to_string(),
+            owner: ObjectId::new().to_hex(),
+        }
+    }
+
+    /// Identify the owner of the lock, a random id by default.
+    /// The handles with the same owner share the lock, such as the ones
+    /// of an instance acquiring it again after a restart.
+    pub fn owner(mut self, owner: impl Into<String>) -> AdvisoryLock {
+        self.owner = owner.into();
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Acquire the lock for `ttl` if it's free, expired, or already held by the owner,
+    /// extending it then. Return `false` if another owner holds it.
+    pub fn try_acquire(&self, ttl: Duration) -> Result<bool> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let _guard = db.advisory_locks().lock().unwrap();
+        let txn = db.start_transaction()?;
+        let key = lock_key(&self.name)?;
+        let now = DateTime::now();
+        let acquired_at = match load_holder(&txn, &key, now)? {
+            Some(holder) if holder.owner != self.owner => return Ok(false),
+            Some(holder) => holder.acquired_at,
+            None => now,
+        };
+        let holder = LockHolder {
+            owner: self.owner.clone(),
+            acquired_at,
+            expires_at: DateTime::from_millis(now.timestamp_millis().saturating_add(ttl.as_millis() as i64)),
+        };
+        txn.put(&key, &bson::to_vec(&holder)?)?;
+        txn.commit()?;
+        Ok(true)
+    }
+
+    /// Release the lock, return `false` if the owner didn't hold it.
+    pub fn release(&self) -> Result<bool> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let _guard = db.advisory_locks().lock().unwrap();
+        let txn = db.start_transaction()?;
+        let key = lock_key(&self.name)?;
+        match load_holder(&txn, &key, DateTime::now())? {
+            Some(holder) if holder.owner == self.owner => {
+                txn.delete(&key)?;
+                txn.commit()?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// The current holder of the lock, `None` if it's free or expired.
+    pub fn holder(&self) -> Result<Option<LockHolder>> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let txn = db.start_transaction()?;
+        load_holder(&txn, &lock_key(&self.name)?, DateTime::now())
+    }
+
+}
+
+fn lock_key(name: &str) -> Result<Vec<u8>> {
+    crate::utils::bson::stacked_key([
+        &Bson::String(LOCK_PREFIX.to_string()),
+        &Bson::String(name.to_string()),
+    ])
+}
+
+fn load_holder(txn: &TransactionInner, key: &[u8], now: DateTime) -> Result<Option<LockHolder>> {
+    let holder: LockHolder = match txn.rocksdb_txn.get(key)? {
+        Some(buf) => bson::from_slice(&buf)?,
+        None => return Ok(None),
+    };
+    if holder.expires_at <= now {
+        return Ok(None);
+    }
+    Ok(Some(holder))
+}
@@ -12,8 +12,23 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use std::fmt::Write;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use crate::db::WeakRocksDBWrapper;
+
+/// The operations counted by the metrics.
+const OPERATIONS: [&str; 6] = ["insert", "find", "count", "update", "delete", "aggregate"];
+
+/// The upper bounds of the latency buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 14] = [
+    0.00005, 0.0001, 0.00025, 0.0005,
+    0.001, 0.0025, 0.005, 0.01,
+    0.025, 0.05, 0.1, 0.25,
+    0.5, 1.0,
+];
 
 #[derive(Clone)]
 pub struct Metrics {
@@ -30,10 +45,18 @@ impl Metrics {
         }
     }
 
+    pub(crate) fn attach_storage(&self, storage: WeakRocksDBWrapper) {
+        let _ = self.inner.storage.set(storage);
+    }
+
     pub fn enable(&self) {
         self.inner.enable()
     }
 
+    pub fn is_enabled(&self) -> bool {
+        self.inner.enable.load(Ordering::Relaxed)
+    }
+
     #[inline]
     pub(crate) fn add_find_by_index_count(&self) {
         self.inner.add_find_by_index_count();
@@ -43,11 +66,177 @@ impl Metrics {
         self.inner.find_by_index_count.load(Ordering::SeqCst)
     }
 
+    #[inline]
+    pub(crate) fn add_bloom_filter_skip(&self) {
+        self.inner.add_bloom_filter_skip();
+    }
+
+    /// The number of lookups answered by the bloom filter of a collection
+    /// without reading the storage.
+    pub fn bloom_filter_skips(&self) -> u64 {
+        self.inner.bloom_filter_skips.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub(crate) fn add_delta_update(&self) {
+        self.inner.add_delta_update();
+    }
+
+    /// The number of updated documents written as a delta of their fields.
+    pub fn delta_updates(&self) -> u64 {
+        self.inner.delta_updates.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub(crate) fn add_record_cache_hit(&self) {
+        self.inner.add_record_cache_hit();
+    }
+
+    #[inline]
+    pub(crate) fn add_record_cache_miss(&self) {
+        self.inner.add_record_cache_miss();
+    }
+
+    /// The number of documents read from the record cache instead of the storage,
+    /// see [`crate::ConfigBuilder::set_record_cache_size`].
+    pub fn record_cache_hits(&self) -> u64 {
+        self.inner.record_cache_hits.load(Ordering::SeqCst)
+    }
+
+    /// The number of documents looked up in the record cache and read from the storage.
+    pub fn record_cache_misses(&self) -> u64 {
+        self.inner.record_cache_misses.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub(crate) fn add_query_cache_hit(&self) {
+        self.inner.add_query_cache_hit();
+    }
+
+    #[inline]
+    pub(crate) fn add_query_cache_miss(&self) {
+        self.inner.add_query_cache_miss();
+    }
+
+    /// The number of finds answered by the query cache without running the query,
+    /// see [`crate::ConfigBuilder::set_query_cache_size`].
+    pub fn query_cache_hits(&self) -> u64 {
+        self.inner.query_cache_hits.load(Ordering::SeqCst)
+    }
+
+    /// The number of finds looked up in the query cache and run.
+    pub fn query_cache_misses(&self) -> u64 {
+        self.inner.query_cache_misses.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub(crate) fn record_operation(&self, op: &'static str, elapsed: Duration, docs_scanned: u64, docs_returned: u64) {
+        self.inner.record_operation(op, elapsed, docs_scanned, docs_returned);
+    }
+
+    /// The number of times the operation `op` was executed, `op` is one of
+    /// `insert`, `find`, `count`, `update`, `delete` and `aggregate`.
+    pub fn operation_count(&self, op: &str) -> u64 {
+        match operation_index(op) {
+            Some(index) => self.inner.latencies[index].count.load(Ordering::SeqCst),
+            None => 0,
+        }
+    }
+
+    pub fn docs_scanned(&self) -> u64 {
+        self.inner.docs_scanned.load(Ordering::SeqCst)
+    }
+
+    pub fn docs_returned(&self) -> u64 {
+        self.inner.docs_returned.load(Ordering::SeqCst)
+    }
+
+    /// Take a snapshot of all the metrics.
+    ///
+    /// The storage statistics are only available if the database is opened
+    /// with [`crate::ConfigBuilder::set_enable_statistics`].
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let operations = OPERATIONS.iter()
+            .zip(self.inner.latencies.iter())
+            .map(|(name, histogram)| OperationMetrics {
+                name,
+                latency: histogram.snapshot(),
+            })
+            .collect();
+        let storage = self.inner.storage
+            .get()
+            .and_then(WeakRocksDBWrapper::upgrade)
+            .and_then(|storage| storage.statistics());
+        MetricsSnapshot {
+            find_by_index_count: self.find_by_index_count() as u64,
+            bloom_filter_skips: self.bloom_filter_skips(),
+            delta_updates: self.delta_updates(),
+            record_cache_hits: self.record_cache_hits(),
+            record_cache_misses: self.record_cache_misses(),
+            query_cache_hits: self.query_cache_hits(),
+            query_cache_misses: self.query_cache_misses(),
+            docs_scanned: self.docs_scanned(),
+            docs_returned: self.docs_returned(),
+            operations,
+            storage,
+        }
+    }
+
+}
+
+fn operation_index(op: &str) -> Option<usize> {
+    OPERATIONS.iter().position(|name| *name == op)
+}
+
+struct AtomicHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl AtomicHistogram {
+
+    fn new() -> AtomicHistogram {
+        AtomicHistogram {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        let index = LATENCY_BUCKETS.iter()
+            .position(|bound| secs <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[index].fetch_add(1, Ordering::SeqCst);
+        self.count.fetch_add(1, Ordering::SeqCst);
+        self.sum_micros.fetch_add(elapsed.as_micros() as u64, Ordering::SeqCst);
+    }
+
+    fn snapshot(&self) -> Histogram {
+        Histogram {
+            buckets: self.buckets.iter().map(|bucket| bucket.load(Ordering::SeqCst)).collect(),
+            count: self.count.load(Ordering::SeqCst),
+            sum: Duration::from_micros(self.sum_micros.load(Ordering::SeqCst)),
+        }
+    }
+
 }
 
 struct MetricsInner {
     enable: AtomicBool,
     find_by_index_count: AtomicUsize,
+    bloom_filter_skips: AtomicU64,
+    delta_updates: AtomicU64,
+    record_cache_hits: AtomicU64,
+    record_cache_misses: AtomicU64,
+    query_cache_hits: AtomicU64,
+    query_cache_misses: AtomicU64,
+    docs_scanned: AtomicU64,
+    docs_returned: AtomicU64,
+    latencies: [AtomicHistogram; OPERATIONS.len()],
+    storage: OnceLock<WeakRocksDBWrapper>,
 }
 
 macro_rules! test_enable {
@@ -65,6 +254,16 @@ impl MetricsInner {
         MetricsInner {
             enable: AtomicBool::new(false),
             find_by_index_count: AtomicUsize::new(0),
+            bloom_filter_skips: AtomicU64::new(0),
+            delta_updates: AtomicU64::new(0),
+            record_cache_hits: AtomicU64::new(0),
+            record_cache_misses: AtomicU64::new(0),
+            query_cache_hits: AtomicU64::new(0),
+            query_cache_misses: AtomicU64::new(0),
+            docs_scanned: AtomicU64::new(0),
+            docs_returned: AtomicU64::new(0),
+            latencies: std::array::from_fn(|_| AtomicHistogram::new()),
+            storage: OnceLock::new(),
         }
     }
 
@@ -79,5 +278,201 @@ impl MetricsInner {
         self.find_by_index_count.fetch_add(1, Ordering::SeqCst);
     }
 
+    fn add_bloom_filter_skip(&self) {
+        test_enable!(self);
+
+        self.bloom_filter_skips.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn add_delta_update(&self) {
+        test_enable!(self);
+
+        self.delta_updates.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn add_record_cache_hit(&self) {
+        test_enable!(self);
+
+        self.record_cache_hits.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn add_record_cache_miss(&self) {
+        test_enable!(self);
+
+        self.record_cache_misses.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn add_query_cache_hit(&self) {
+        test_enable!(self);
+
+        self.query_cache_hits.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn add_query_cache_miss(&self) {
+        test_enable!(self);
+
+        self.query_cache_misses.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn record_operation(&self, op: &'static str, elapsed: Duration, docs_scanned: u64, docs_returned: u64) {
+        test_enable!(self);
+
+        if let Some(index) = operation_index(op) {
+            self.latencies[index].observe(elapsed);
+        }
+        self.docs_scanned.fetch_add(docs_scanned, Ordering::SeqCst);
+        self.docs_returned.fetch_add(docs_returned, Ordering::SeqCst);
+    }
+
 }
 
+/// The latency distribution of an operation.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    /// The number of observations in each bucket of [`Histogram::bounds`],
+    /// the last one counts the observations above the largest bound.
+    /// The counts are not cumulative.
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum: Duration,
+}
+
+impl Histogram {
+
+    /// The upper bounds of the buckets, in seconds.
+    pub fn bounds() -> &'static [f64] {
+        &LATENCY_BUCKETS
+    }
+
+}
+
+#[derive(Debug, Clone)]
+pub struct OperationMetrics {
+    pub name: &'static str,
+    pub latency: Histogram,
+}
+
+/// The statistics reported by the storage engine.
+#[derive(Debug, Clone, Default)]
+pub struct StorageStatistics {
+    pub block_cache_hit: u64,
+    pub block_cache_miss: u64,
+    pub bytes_written: u64,
+    pub bytes_read: u64,
+    pub wal_bytes_written: u64,
+}
+
+impl StorageStatistics {
+
+    /// The ratio of the block reads served by the block cache,
+    /// `None` if no block was read.
+    pub fn cache_hit_ratio(&self) -> Option<f64> {
+        let total = self.block_cache_hit + self.block_cache_miss;
+        if total == 0 {
+            return None;
+        }
+        Some(self.block_cache_hit as f64 / total as f64)
+    }
+
+}
+
+/// A point-in-time copy of the [`Metrics`].
+#[derive(Debug, Clone)]
+pub struct MetricsSnapshot {
+    pub find_by_index_count: u64,
+    pub bloom_filter_skips: u64,
+    pub delta_updates: u64,
+    pub record_cache_hits: u64,
+    pub record_cache_misses: u64,
+    pub query_cache_hits: u64,
+    pub query_cache_misses: u64,
+    pub docs_scanned: u64,
+    pub docs_returned: u64,
+    pub operations: Vec<OperationMetrics>,
+    pub storage: Option<StorageStatistics>,
+}
+
+impl MetricsSnapshot {
+
+    pub fn operation(&self, name: &str) -> Option<&OperationMetrics> {
+        self.operations.iter().find(|op| op.name == name)
+    }
+
+    /// Render the snapshot in the Prometheus text exposition format,
+    /// the names of the metrics are prefixed with `polodb_`.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        write_counter(&mut out, "polodb_find_by_index_total", "Documents found through an index.", self.find_by_index_count);
+        write_counter(&mut out, "polodb_bloom_filter_skips_total", "Lookups answered by a bloom filter without reading the storage.", self.bloom_filter_skips);
+        write_counter(&mut out, "polodb_delta_updates_total", "Updated documents written as a delta of their fields.", self.delta_updates);
+        write_counter(&mut out, "polodb_record_cache_hits_total", "Documents read from the record cache.", self.record_cache_hits);
+        write_counter(&mut out, "polodb_record_cache_misses_total", "Documents looked up in the record cache and read from the storage.", self.record_cache_misses);
+        write_counter(&mut out, "polodb_query_cache_hits_total", "Finds answered by the query cache.", self.query_cache_hits);
+        write_counter(&mut out, "polodb_query_cache_misses_total", "Finds looked up in the query cache and run.", self.query_cache_misses);
+        write_counter(&mut out, "polodb_docs_scanned_total", "Documents read from the storage by queries.", self.docs_scanned);
+        write_counter(&mut out, "polodb_docs_returned_total", "Documents returned by queries.", self.docs_returned);
+
+        let _ = writeln!(out, "# HELP polodb_operation_duration_seconds The latency of the operations.");
+        let _ = writeln!(out, "# TYPE polodb_operation_duration_seconds histogram");
+        for op in &self.operations {
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(op.latency.buckets.iter()) {
+                cumulative += count;
+                let _ = writeln!(out, "polodb_operation_duration_seconds_bucket{{op=\"{}\",le=\"{}\"}} {}", op.name, bound, cumulative);
+            }
+            let _ = writeln!(out, "polodb_operation_duration_seconds_bucket{{op=\"{}\",le=\"+Inf\"}} {}", op.name, op.latency.count);
+            let _ = writeln!(out, "polodb_operation_duration_seconds_sum{{op=\"{}\"}} {}", op.name, op.latency.sum.as_secs_f64());
+            let _ = writeln!(out, "polodb_operation_duration_seconds_count{{op=\"{}\"}} {}", op.name, op.latency.count);
+        }
+
+        if let Some(storage) = &self.storage {
+            write_counter(&mut out, "polodb_block_cache_hit_total", "Block cache hits.", storage.block_cache_hit);
+            write_counter(&mut out, "polodb_block_cache_miss_total", "Block cache misses.", storage.block_cache_miss);
+            write_counter(&mut out, "polodb_bytes_written_total", "Bytes written to the storage.", storage.bytes_written);
+            write_counter(&mut out, "polodb_bytes_read_total", "Bytes read from the storage.", storage.bytes_read);
+            write_counter(&mut out, "polodb_wal_bytes_written_total", "Bytes written to the write-ahead log.", storage.wal_bytes_written);
+        }
+
+        out
+    }
+
+}
+
+fn write_counter(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::Metrics;
+
+    #[test]
+    fn test_latency_histogram() {
+        let metrics = Metrics::new();
+        metrics.record_operation("find", Duration::from_micros(10), 5, 1);
+        assert_eq!(metrics.operation_count("find"), 0);
+
+        metrics.enable();
+        metrics.record_operation("find", Duration::from_micros(10), 5, 1);
+        metrics.record_operation("find", Duration::from_millis(3), 5, 1);
+        metrics.record_operation("find", Duration::from_secs(2), 5, 1);
+
+        let snapshot = metrics.snapshot();
+        let find = snapshot.operation("find").unwrap();
+        assert_eq!(find.latency.count, 3);
+        assert_eq!(find.latency.buckets[0], 1);
+        assert_eq!(find.latency.buckets[6], 1);
+        assert_eq!(*find.latency.buckets.last().unwrap(), 1);
+        assert_eq!(snapshot.docs_scanned, 15);
+        assert!(snapshot.storage.is_none());
+
+        let text = snapshot.to_prometheus();
+        assert!(text.contains("polodb_operation_duration_seconds_bucket{op=\"find\",le=\"0.005\"} 2"));
+        assert!(text.contains("polodb_operation_duration_seconds_count{op=\"find\"} 3"));
+    }
+
+}
@@ -15,4 +15,10 @@
 
 mod metrics;
 
-pub use metrics::{Metrics};
+pub use metrics::{
+    Metrics,
+    MetricsSnapshot,
+    OperationMetrics,
+    Histogram,
+    StorageStatistics,
+};
@@ -0,0 +1,230 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Weak};
+use bson::{doc, Document};
+use serde::Serialize;
+use crate::db::db_inner::DatabaseInner;
+use crate::options::CreateCollectionOptions;
+use crate::{Collection, Error, Result};
+
+/// The separator of the name of a logical database and the name of its collection.
+pub(crate) const NAMESPACE_SEPARATOR: char = '.';
+
+/// The name of the collection `name` of the logical database `database`.
+pub(crate) fn qualified_name(database: &str, name: &str) -> String {
+    format!("{}{}{}", database, NAMESPACE_SEPARATOR, name)
+}
+
+/// The logical database of the collection, if it's in one, and its name in this database.
+pub(crate) fn split_name(name: &str) -> (Option<&str>, &str) {
+    match name.split_once(NAMESPACE_SEPARATOR) {
+        Some((database, name)) => (Some(database), name),
+        None => (None, name),
+    }
+}
+
+/// The collections of the logical database `database`, or the ones outside of the logical
+/// databases if `None`, with their names in this database.
+pub(crate) fn collections_of(names: Vec<String>, database: Option<&str>) -> Vec<String> {
+    names.iter()
+        .filter_map(|name| match split_name(name) {
+            (db, name) if db == database => Some(name.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The names of the logical databases having collections.
+pub(crate) fn database_names(names: &[String]) -> Vec<String> {
+    let mut result = names.iter()
+        .filter_map(|name| split_name(name).0.map(str::to_string))
+        .collect::<Vec<_>>();
+    result.sort();
+    result.dedup();
+    result
+}
+
+/// A named group of collections stored in the file of a [`crate::Database`],
+/// returned by [`crate::Database::database`], such as the collections of a tenant.
+///
+/// The collection `orders` of the logical database `tenant_a` is the collection
+/// `tenant_a.orders` of the file. The collections of the logical databases aren't listed
+/// by [`crate::Database::list_collection_names`], and the logical databases are
+/// listed by [`crate::Database::list_database_names`]. A transaction of the file
+/// covers the collections of all its logical databases, by their qualified names.
+///
+/// ```rust
+/// use polodb_core::{Database, CollectionT};
+/// use polodb_core::bson::{doc, Document};
+/// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-logical-database");
+/// let db = Database::open_path(db_path).unwrap();
+/// let tenant = db.database("tenant_a");
+/// tenant.collection::<Document>("orders").insert_one(doc! { "total": 12 }).unwrap();
+///
+/// assert_eq!(tenant.list_collection_names().unwrap(), vec!["orders".to_string()]);
+/// assert_eq!(db.list_database_names().unwrap(), vec!["tenant_a".to_string()]);
+/// assert_eq!(tenant.stats().unwrap().get_i64("objects").unwrap(), 1);
+///
+/// tenant.drop().unwrap();
+/// assert!(db.list_database_names().unwrap().is_empty());
+/// ```
+pub struct LogicalDatabase {
+    db: Weak<DatabaseInner>,
+    name: String,
+}
+
+impl LogicalDatabase {
+
+    pub(crate) fn new(db: &Arc<DatabaseInner>, name: &str) -> LogicalDatabase {
+        LogicalDatabase {
+            db: Arc::downgrade(db),
+            name: name.to_string(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn db(&self) -> Result<Arc<DatabaseInner>> {
+        self.db.upgrade().ok_or(Error::DbIsClosed)
+    }
+
+    /// The collection `name` of this logical database, created by the first write.
+    pub fn collection<T: Serialize>(&self, name: &str) -> Collection<T> {
+        Collection::new(self.db.clone(), &qualified_name(&self.name, name))
+    }
+
+    pub fn create_collection(&self, name: &str) -> Result<()> {
+        self.db()?.create_collection(&qualified_name(&self.name, name))?;
+        Ok(())
+    }
+
+    pub fn create_collection_with_options(&self, name: &str, options: CreateCollectionOptions) -> Result<()> {
+        self.db()?.create_collection_with_options(&qualified_name(&self.name, name), options)?;
+        Ok(())
+    }
+
+    /// Renames the collection `old_name` to `new_name` in this logical database.
+    pub fn rename_collection(&self, old_name: &str, new_name: &str) -> Result<()> {
+        let db = self.db()?;
+        let txn = db.start_transaction()?;
+        db.rename_collection(&qualified_name(&self.name, old_name), &qualified_name(&self.name, new_name), &txn)?;
+        txn.commit()
+    }
+
+    /// Gets the names of the collections of this logical database.
+    pub fn list_collection_names(&self) -> Result<Vec<String>> {
+        let db = self.db()?;
+        let txn = db.start_transaction()?;
+        let names = db.list_collection_names_with_session(&txn)?;
+        Ok(collections_of(names, Some(&self.name)))
+    }
+
+    /// Describe the collections of this logical database, like [`crate::Database::list_collections`].
+    pub fn list_collections(&self) -> Result<Vec<Document>> {
+        let db = self.db()?;
+        let txn = db.start_transaction()?;
+        let mut result = Vec::new();
+        for mut info in db.list_collections(&txn)? {
+            let name = match split_name(info.get_str("name").unwrap_or_default()) {
+                (Some(database), name) if database == self.name => name.to_string(),
+                _ => continue,
+            };
+            info.insert("name", name);
+            result.push(info);
+        }
+        Ok(result)
+    }
+
+    /// The statistics of the collections of this logical database, in the format
+    /// of the `dbStats` command: the numbers of `collections`, `views`, `objects`
+    /// and `indexes`, and the bytes of the documents and of the indexes.
+    pub fn stats(&self) -> Result<Document> {
+        let db = self.db()?;
+        let mut txn = db.start_transaction()?;
+        txn.set_auto_commit(false);
+        let names = collections_of(db.list_collection_names_with_session(&txn)?, Some(&self.name));
+
+        let (mut collections, mut views) = (0_i64, 0_i64);
+        let (mut objects, mut data_size, mut storage_size) = (0_i64, 0_i64, 0_i64);
+        let (mut indexes, mut index_size) = (0_i64, 0_i64);
+        for name in names {
+            let col_spec = match db.get_collection_meta_by_name_advanced_auto(&qualified_name(&self.name, &name), false, &txn)? {
+                Some(col_spec) => col_spec,
+                None => continue,
+            };
+            if col_spec.view.is_some() {
+                views += 1;
+                continue;
+            }
+            collections += 1;
+            let stats = db.collection_stats(&col_spec, &txn)?;
+            let get = |key: &str| stats.get_i64(key).unwrap_or(0);
+            objects += get("count");
+            data_size += get("size");
+            storage_size += get("storageSize");
+            indexes += get("nindexes");
+            index_size += get("totalIndexSize");
+        }
+
+        Ok(doc! {
+            "db": self.name.clone(),
+            "collections": collections,
+            "views": views,
+            "objects": objects,
+            "avgObjSize": if objects > 0 { data_size / objects } else { 0 },
+            "dataSize": data_size,
+            "storageSize": storage_size,
+            "indexes": indexes,
+            "indexSize": index_size,
+            "totalSize": storage_size + index_size,
+        })
+    }
+
+    /// Drops the collections of this logical database in one transaction,
+    /// the other logical databases are left untouched.
+    pub fn drop(&self) -> Result<()> {
+        let db = self.db()?;
+        let mut txn = db.start_transaction()?;
+        txn.set_auto_commit(false);
+        let names = collections_of(db.list_collection_names_with_session(&txn)?, Some(&self.name));
+        for name in names {
+            if let Err(err) = db.drop_collection(&qualified_name(&self.name, &name), &txn) {
+                let _ = txn.rollback();
+                return Err(err);
+            }
+        }
+        txn.commit()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collections_of, database_names, split_name};
+
+    #[test]
+    fn test_split_names() {
+        assert_eq!(split_name("orders"), (None, "orders"));
+        assert_eq!(split_name("tenant_a.orders"), (Some("tenant_a"), "orders"));
+
+        let names = vec!["orders".to_string(), "a.orders".to_string(), "a.users".to_string(), "b.orders".to_string()];
+        assert_eq!(collections_of(names.clone(), None), vec!["orders".to_string()]);
+        assert_eq!(collections_of(names.clone(), Some("a")), vec!["orders".to_string(), "users".to_string()]);
+        assert_eq!(database_names(&names), vec!["a".to_string(), "b".to_string()]);
+    }
+
+}
@@ -0,0 +1,197 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use bson::DateTime;
+use bson::oid::ObjectId;
+
+const MAX_U24: u32 = 0xFF_FFFF;
+
+/// How the counter of the generated ObjectIds advances.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ObjectIdCounterMode {
+    /// Start from a random value and wrap around, as the `bson` crate does.
+    #[default]
+    Random,
+    /// Restart from 0 every second, borrowing the next second when the counter
+    /// overflows or the clock goes back, so the ObjectIds generated by the process
+    /// are strictly increasing.
+    Monotonic,
+}
+
+/// Generates the `_id` of the inserted documents which have none.
+pub(crate) struct ObjectIdGenerator {
+    /// The 5 bytes following the timestamp, `None` to let the `bson` crate generate the ids.
+    process: Option<[u8; 5]>,
+    mode: ObjectIdCounterMode,
+    counter: AtomicU32,
+    /// The timestamp and the counter of the last id in the monotonic mode.
+    last: Mutex<(u32, u32)>,
+}
+
+impl ObjectIdGenerator {
+    pub(crate) fn new(machine_id: Option<u32>, process_id: Option<u16>, mode: ObjectIdCounterMode) -> ObjectIdGenerator {
+        let mut random = [0u8; 8];
+        getrandom::getrandom(&mut random).unwrap();
+
+        let process = if machine_id.is_none() && process_id.is_none() && mode == ObjectIdCounterMode::Random {
+            None
+        } else {
+            let mut process = [0u8; 5];
+            process.copy_from_slice(&random[..5]);
+            if let Some(machine_id) = machine_id {
+                process[..3].copy_from_slice(&(machine_id & MAX_U24).to_be_bytes()[1..]);
+            }
+            if let Some(process_id) = process_id {
+                process[3..].copy_from_slice(&process_id.to_be_bytes());
+            }
+            Some(process)
+        };
+
+        let counter = u32::from_be_bytes([0, random[5], random[6], random[7]]);
+
+        ObjectIdGenerator {
+            process,
+            mode,
+            counter: AtomicU32::new(counter),
+            last: Mutex::new((0, 0)),
+        }
+    }
+
+    pub(crate) fn generate(&self) -> ObjectId {
+        let process = match self.process {
+            Some(process) => process,
+            None => return ObjectId::new(),
+        };
+
+        let (timestamp, counter) = match self.mode {
+            ObjectIdCounterMode::Random => {
+                let counter = self.counter.fetch_add(1, Ordering::SeqCst) & MAX_U24;
+                (now_secs(), counter)
+            }
+            ObjectIdCounterMode::Monotonic => {
+                let mut last = self.last.lock().unwrap();
+                let (last_timestamp, last_counter) = *last;
+                let now = now_secs();
+                *last = if now > last_timestamp {
+                    (now, 0)
+                } else if last_counter < MAX_U24 {
+                    (last_timestamp, last_counter + 1)
+                } else {
+                    (last_timestamp + 1, 0)
+                };
+                *last
+            }
+        };
+
+        let counter = counter.to_be_bytes();
+        ObjectId::from_parts(timestamp, process, [counter[1], counter[2], counter[3]])
+    }
+}
+
+fn now_secs() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as u32)
+}
+
+/// Helpers reading the parts of an [`ObjectId`].
+pub trait ObjectIdExt {
+    /// The smallest ObjectId generated at `time`, to find the documents
+    /// inserted before or after `time` with a range on `_id`.
+    fn min_for_time(time: DateTime) -> ObjectId;
+
+    /// The seconds since the Unix epoch at which the ObjectId was generated.
+    fn timestamp_secs(&self) -> u32;
+
+    /// The machine id, the first 3 bytes following the timestamp.
+    fn machine_id(&self) -> u32;
+
+    /// The process id, the 2 bytes following the machine id.
+    fn process_id(&self) -> u16;
+
+    /// The counter, the last 3 bytes.
+    fn counter(&self) -> u32;
+}
+
+impl ObjectIdExt for ObjectId {
+    fn min_for_time(time: DateTime) -> ObjectId {
+        let secs = time.timestamp_millis().div_euclid(1000).clamp(0, u32::MAX as i64) as u32;
+        ObjectId::from_parts(secs, [0; 5], [0; 3])
+    }
+
+    fn timestamp_secs(&self) -> u32 {
+        let bytes = self.bytes();
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+    }
+
+    fn machine_id(&self) -> u32 {
+        let bytes = self.bytes();
+        u32::from_be_bytes([0, bytes[4], bytes[5], bytes[6]])
+    }
+
+    fn process_id(&self) -> u16 {
+        let bytes = self.bytes();
+        u16::from_be_bytes([bytes[7], bytes[8]])
+    }
+
+    fn counter(&self) -> u32 {
+        let bytes = self.bytes();
+        u32::from_be_bytes([0, bytes[9], bytes[10], bytes[11]])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+    use super::{ObjectIdCounterMode, ObjectIdExt, ObjectIdGenerator};
+
+    #[test]
+    fn test_generate_with_process() {
+        let generator = ObjectIdGenerator::new(Some(0x123456), Some(0x789a), ObjectIdCounterMode::Random);
+        let first = generator.generate();
+        let second = generator.generate();
+        assert_eq!(first.machine_id(), 0x123456);
+        assert_eq!(first.process_id(), 0x789a);
+        assert_eq!((first.counter() + 1) & 0xFF_FFFF, second.counter());
+    }
+
+    #[test]
+    fn test_generate_monotonic() {
+        let generator = ObjectIdGenerator::new(None, None, ObjectIdCounterMode::Monotonic);
+        let mut last = generator.generate();
+        {
+            // the counter is exhausted, the next id borrows the next second
+            let mut state = generator.last.lock().unwrap();
+            state.1 = 0xFF_FFFF;
+            state.0 = last.timestamp_secs();
+        }
+        for _ in 0..1000 {
+            let id = generator.generate();
+            assert!(id > last);
+            last = id;
+        }
+    }
+
+    #[test]
+    fn test_min_for_time() {
+        let id = ObjectId::new();
+        let min = ObjectId::min_for_time(id.timestamp());
+        assert!(min <= id);
+        assert_eq!(min.timestamp_secs(), id.timestamp_secs());
+        assert_eq!(min.counter(), 0);
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use bson::Bson;
+use crate::change_stream::ChangeEvent;
+use crate::cursor::Cursor;
+use crate::transaction::TransactionInner;
+use crate::{Error, Result};
+
+const OPLOG_PREFIX: &str = "$OPLOG";
+
+/// The log of the committed changes, capped to the last `capacity` entries.
+///
+/// Every change is stored in the transaction writing it under a monotonically
+/// increasing token, so the log only contains the committed changes.
+/// The tokens of the rolled back transactions are skipped.
+pub(crate) struct Oplog {
+    capacity: u64,
+    last_token: AtomicU64,
+    /// The greatest token of the committed changes.
+    committed_token: AtomicU64,
+}
+
+impl Oplog {
+
+    fn make_key(token: u64) -> Result<Vec<u8>> {
+        crate::utils::bson::stacked_key([
+            &Bson::String(OPLOG_PREFIX.to_string()),
+            &Bson::Int64(token as i64),
+        ])
+    }
+
+    pub(crate) fn open(txn: &TransactionInner, capacity: u64) -> Result<Oplog> {
+        let mut cursor = Oplog::cursor(txn)?;
+        cursor.reset()?;
+        let mut last_token = 0;
+        while cursor.has_next() {
+            last_token = Oplog::read_event(&cursor)?.resume_token.unwrap_or(last_token);
+            cursor.next()?;
+        }
+        Ok(Oplog {
+            capacity,
+            last_token: AtomicU64::new(last_token),
+            committed_token: AtomicU64::new(last_token),
+        })
+    }
+
+    fn cursor(txn: &TransactionInner) -> Result<Cursor> {
+        let kv_cursor = txn.rocksdb_txn.new_iterator();
+        Cursor::new_with_str_prefix(OPLOG_PREFIX, kv_cursor)
+    }
+
+    fn read_event(cursor: &Cursor) -> Result<ChangeEvent> {
+        let data = cursor.copy_data()?;
+        Ok(bson::from_slice(&data)?)
+    }
+
+    pub(crate) fn last_token(&self) -> u64 {
+        self.last_token.load(Ordering::SeqCst)
+    }
+
+    /// The token of the last committed change, the tokens after it
+    /// are assigned to the changes of transactions not committed yet.
+    pub(crate) fn committed_token(&self) -> u64 {
+        self.committed_token.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn mark_committed(&self, token: u64) {
+        self.committed_token.fetch_max(token, Ordering::SeqCst);
+    }
+
+    /// Assign a token to the change and write it in the transaction,
+    /// evicting the oldest entry.
+    pub(crate) fn append(&self, txn: &TransactionInner, event: &mut ChangeEvent) -> Result<()> {
+        let token = self.last_token.fetch_add(1, Ordering::SeqCst) + 1;
+        event.resume_token = Some(token);
+        txn.put(&Oplog::make_key(token)?, &bson::to_vec(event)?)?;
+        if token > self.capacity {
+            txn.delete(&Oplog::make_key(token - self.capacity)?)?;
+        }
+        Ok(())
+    }
+
+    /// Read the changes committed after `token`.
+    pub(crate) fn read_after(&self, txn: &TransactionInner, token: u64) -> Result<Vec<ChangeEvent>> {
+        let oldest = self.last_token().saturating_sub(self.capacity) + 1;
+        if token + 1 < oldest {
+            return Err(Error::ChangeStreamHistoryLost(token));
+        }
+        let mut cursor = Oplog::cursor(txn)?;
+        cursor.reset_by_pkey(&Bson::Int64((token + 1) as i64))?;
+        let mut result = Vec::new();
+        while cursor.has_next() {
+            result.push(Oplog::read_event(&cursor)?);
+            cursor.next()?;
+        }
+        Ok(result)
+    }
+
+}
@@ -12,9 +12,108 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::borrow::Cow;
+use bson::{Bson, Document};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+/// The index the query planner should use, given by its name
+/// or by its keys, such as `{ "age": 1 }`.
+///
+/// `{ "$natural": 1 }` scans the collection without the indexes,
+/// `{ "$natural": -1 }` in the reverse order of the keys.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Hint {
+    Name(String),
+    Keys(Document),
+}
+
+impl From<&str> for Hint {
+    fn from(name: &str) -> Self {
+        Hint::Name(name.to_string())
+    }
+}
+
+impl From<String> for Hint {
+    fn from(name: String) -> Self {
+        Hint::Name(name)
+    }
+}
+
+impl From<Document> for Hint {
+    fn from(keys: Document) -> Self {
+        Hint::Keys(keys)
+    }
+}
+
+/// How the strings are compared by the filter of an operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Collation {
+    /// Compare the strings byte by byte.
+    #[default]
+    Simple,
+    /// Compare the strings ignoring the case of the letters.
+    CaseInsensitive,
+}
+
+impl Collation {
+
+    /// The value compared under the collation.
+    pub(crate) fn collate(self, value: &Bson) -> Cow<'_, Bson> {
+        match (self, value) {
+            (Collation::CaseInsensitive, Bson::String(s)) => Cow::Owned(Bson::String(s.to_lowercase())),
+            _ => Cow::Borrowed(value),
+        }
+    }
+
+}
+
+/// When a write returns, relative to the write-ahead log reaching the disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Durability {
+    /// Flush the write-ahead log to the disk before returning, the default.
+    #[default]
+    Sync,
+    /// Return once the changes are in the buffer of the write-ahead log.
+    /// They survive a crash of the process, but the last ones may be lost
+    /// if the operating system crashes or the machine loses power.
+    Buffered,
+}
+
+/// What a cursor does with a stored document it fails to decode, such as
+/// a document written by a newer version or a corrupt one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DecodeMode {
+    /// Fail the iteration, the default.
+    #[default]
+    Strict,
+    /// Give an [`crate::Error::DecodeFailed`] item for the document,
+    /// and go on with the next documents.
+    Lenient,
+    /// Copy the document as it is stored to the collection of this name,
+    /// and go on with the next documents. The copy has the `ns` and the `id`
+    /// of the document, its encoding as `data`, the `reason` of the failure
+    /// and the `quarantinedAt` date, and it replaces the previous copy
+    /// of the same document.
+    Quarantine(String),
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct UpdateOptions {
     pub upsert: Option<bool>,
+    /// The index to use to find the documents, the operation fails
+    /// if the collection has no such index.
+    pub hint: Option<Hint>,
+    pub collation: Option<Collation>,
+    /// The maximum number of documents to update.
+    pub limit: Option<u64>,
+    /// The order of the documents, the documents updated within
+    /// the limit are the first ones in this order.
+    pub sort: Option<Document>,
+    /// Override the durability of the database for this write,
+    /// ignored in a transaction.
+    pub durability: Option<Durability>,
 }
 
 impl UpdateOptions {
@@ -30,6 +129,11 @@ impl UpdateOptions {
 #[derive(Default)]
 pub struct UpdateOptionsBuilder {
     upsert: Option<bool>,
+    hint: Option<Hint>,
+    collation: Option<Collation>,
+    limit: Option<u64>,
+    sort: Option<Document>,
+    durability: Option<Durability>,
 }
 
 impl UpdateOptionsBuilder {
@@ -38,9 +142,514 @@ impl UpdateOptionsBuilder {
         self
     }
 
+    pub fn hint(mut self, hint: impl Into<Hint>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.collation = Some(collation);
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn sort(mut self, sort: Document) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = Some(durability);
+        self
+    }
+
     pub fn build(self) -> UpdateOptions {
         UpdateOptions {
             upsert: self.upsert,
+            hint: self.hint,
+            collation: self.collation,
+            limit: self.limit,
+            sort: self.sort,
+            durability: self.durability,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DeleteOptions {
+    /// The index to use to find the documents, the operation fails
+    /// if the collection has no such index.
+    pub hint: Option<Hint>,
+    pub collation: Option<Collation>,
+    /// The maximum number of documents to delete.
+    pub limit: Option<u64>,
+    /// The order of the documents, the documents deleted within
+    /// the limit are the first ones in this order.
+    pub sort: Option<Document>,
+    /// Override the durability of the database for this write,
+    /// ignored in a transaction.
+    pub durability: Option<Durability>,
+}
+
+impl DeleteOptions {
+    pub fn builder() -> DeleteOptionsBuilder {
+        DeleteOptionsBuilder::default()
+    }
+}
+
+#[derive(Default)]
+pub struct DeleteOptionsBuilder {
+    hint: Option<Hint>,
+    collation: Option<Collation>,
+    limit: Option<u64>,
+    sort: Option<Document>,
+    durability: Option<Durability>,
+}
+
+impl DeleteOptionsBuilder {
+    pub fn hint(mut self, hint: impl Into<Hint>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.collation = Some(collation);
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn sort(mut self, sort: Document) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = Some(durability);
+        self
+    }
+
+    pub fn build(self) -> DeleteOptions {
+        DeleteOptions {
+            hint: self.hint,
+            collation: self.collation,
+            limit: self.limit,
+            sort: self.sort,
+            durability: self.durability,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InsertManyOptions {
+    /// If true (the default), the insert stops at the first failing document
+    /// and returns its error. Otherwise every document is attempted and the
+    /// failures are reported in [`InsertManyResult::write_errors`](crate::results::InsertManyResult).
+    pub ordered: Option<bool>,
+    /// Override the durability of the database for this write,
+    /// ignored in a transaction.
+    pub durability: Option<Durability>,
+}
+
+impl InsertManyOptions {
+    pub fn builder() -> InsertManyOptionsBuilder {
+        InsertManyOptionsBuilder::default()
+    }
+
+    pub(crate) fn is_ordered(&self) -> bool {
+        self.ordered.unwrap_or(true)
+    }
+}
+
+#[derive(Default)]
+pub struct InsertManyOptionsBuilder {
+    ordered: Option<bool>,
+    durability: Option<Durability>,
+}
+
+impl InsertManyOptionsBuilder {
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = Some(ordered);
+        self
+    }
+
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = Some(durability);
+        self
+    }
+
+    pub fn build(self) -> InsertManyOptions {
+        InsertManyOptions {
+            ordered: self.ordered,
+            durability: self.durability,
+        }
+    }
+}
+
+/// Which documents the validator of a collection applies to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ValidationLevel {
+    /// No validation.
+    Off,
+    /// Validate all the inserts and updates.
+    #[default]
+    Strict,
+    /// Validate the inserts and the updates of the documents which are already valid.
+    Moderate,
+}
+
+/// What to do with a document failing the validation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ValidationAction {
+    /// Reject the write with [`crate::Error::DocumentValidationFailed`].
+    #[default]
+    Error,
+    /// Log the failure and accept the write.
+    Warn,
+}
+
+/// The value given by the engine to a field omitted by an inserted document.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FieldDefault {
+    /// A constant value.
+    Value(Bson),
+    /// The time of the insert.
+    Now,
+    /// The next number of a sequence of the collection, starting from 1.
+    Sequence,
+}
+
+/// The options of a new collection.
+#[derive(Debug, Clone, Default)]
+pub struct CreateCollectionOptions {
+    /// The validator of the documents, `{ "$jsonSchema": <schema> }`.
+    pub validator: Option<Document>,
+    pub validation_level: Option<ValidationLevel>,
+    pub validation_action: Option<ValidationAction>,
+    /// Create a capped collection: once `size` or `max` is reached,
+    /// inserting a document removes the oldest one.
+    pub capped: Option<bool>,
+    /// The maximum size of the documents of a capped collection, in bytes.
+    pub size: Option<u64>,
+    /// The maximum number of documents of a capped collection.
+    pub max: Option<u64>,
+    /// The field holding the expiry date of the documents: once the date is
+    /// past, the document is hidden from the reads and can be removed.
+    pub expire_at_field: Option<String>,
+    /// The maximum size of an encoded document, in bytes,
+    /// overriding the limit of the database.
+    pub max_document_size: Option<u64>,
+    /// The maximum nesting depth of the documents and arrays,
+    /// overriding the limit of the database.
+    pub max_document_depth: Option<u64>,
+    /// The values of the top-level fields omitted by the inserted documents.
+    pub defaults: Option<IndexMap<String, FieldDefault>>,
+    /// Keep the values of this field in a bloom filter, so the lookups of absent values
+    /// by `_id`, or by an index on the field, return without reading the storage.
+    pub bloom_filter: Option<String>,
+    /// Keep the words of the strings of this field in a trigram index, so the `$fuzzy`
+    /// searches on the field only read the documents holding words close to theirs.
+    pub fuzzy_index: Option<String>,
+    /// Merge the versions of the documents changed on several devices field by field
+    /// when they are synced, see [`crate::sync`]: the last write of a field wins,
+    /// and the elements added to and removed from an array are all kept.
+    pub crdt: Option<bool>,
+}
+
+impl CreateCollectionOptions {
+    pub fn builder() -> CreateCollectionOptionsBuilder {
+        CreateCollectionOptionsBuilder::default()
+    }
+}
+
+#[derive(Default)]
+pub struct CreateCollectionOptionsBuilder {
+    inner: CreateCollectionOptions,
+}
+
+impl CreateCollectionOptionsBuilder {
+    pub fn validator(mut self, validator: Document) -> Self {
+        self.inner.validator = Some(validator);
+        self
+    }
+
+    pub fn validation_level(mut self, level: ValidationLevel) -> Self {
+        self.inner.validation_level = Some(level);
+        self
+    }
+
+    pub fn validation_action(mut self, action: ValidationAction) -> Self {
+        self.inner.validation_action = Some(action);
+        self
+    }
+
+    pub fn capped(mut self, capped: bool) -> Self {
+        self.inner.capped = Some(capped);
+        self
+    }
+
+    pub fn size(mut self, size: u64) -> Self {
+        self.inner.size = Some(size);
+        self
+    }
+
+    pub fn max(mut self, max: u64) -> Self {
+        self.inner.max = Some(max);
+        self
+    }
+
+    pub fn expire_at_field(mut self, field: impl Into<String>) -> Self {
+        self.inner.expire_at_field = Some(field.into());
+        self
+    }
+
+    pub fn max_document_size(mut self, size: u64) -> Self {
+        self.inner.max_document_size = Some(size);
+        self
+    }
+
+    pub fn max_document_depth(mut self, depth: u64) -> Self {
+        self.inner.max_document_depth = Some(depth);
+        self
+    }
+
+    pub fn default_value(mut self, field: impl Into<String>, value: impl Into<Bson>) -> Self {
+        self.inner.defaults.get_or_insert_with(IndexMap::new)
+            .insert(field.into(), FieldDefault::Value(value.into()));
+        self
+    }
+
+    pub fn default_now(mut self, field: impl Into<String>) -> Self {
+        self.inner.defaults.get_or_insert_with(IndexMap::new)
+            .insert(field.into(), FieldDefault::Now);
+        self
+    }
+
+    pub fn default_sequence(mut self, field: impl Into<String>) -> Self {
+        self.inner.defaults.get_or_insert_with(IndexMap::new)
+            .insert(field.into(), FieldDefault::Sequence);
+        self
+    }
+
+    pub fn bloom_filter(mut self, field: impl Into<String>) -> Self {
+        self.inner.bloom_filter = Some(field.into());
+        self
+    }
+
+    pub fn fuzzy_index(mut self, field: impl Into<String>) -> Self {
+        self.inner.fuzzy_index = Some(field.into());
+        self
+    }
+
+    pub fn crdt(mut self, crdt: bool) -> Self {
+        self.inner.crdt = Some(crdt);
+        self
+    }
+
+    pub fn build(self) -> CreateCollectionOptions {
+        self.inner
+    }
+}
+
+/// The changes to the options of an existing collection, the options which
+/// are `None` are left unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct ModifyCollectionOptions {
+    /// The new validator of the documents, an empty document removes the validator.
+    pub validator: Option<Document>,
+    pub validation_level: Option<ValidationLevel>,
+    pub validation_action: Option<ValidationAction>,
+    /// The new expiry field of the documents, an empty name removes the expiry.
+    pub expire_at_field: Option<String>,
+    /// The new maximum size of an encoded document, 0 falls back to the limit of the database.
+    pub max_document_size: Option<u64>,
+    /// The new maximum nesting depth, 0 falls back to the limit of the database.
+    pub max_document_depth: Option<u64>,
+    /// The new default values, replacing all the existing ones. An empty map removes them.
+    pub defaults: Option<IndexMap<String, FieldDefault>>,
+    /// Whether the synced documents are merged field by field.
+    pub crdt: Option<bool>,
+}
+
+impl ModifyCollectionOptions {
+    pub fn builder() -> ModifyCollectionOptionsBuilder {
+        ModifyCollectionOptionsBuilder::default()
+    }
+}
+
+#[derive(Default)]
+pub struct ModifyCollectionOptionsBuilder {
+    inner: ModifyCollectionOptions,
+}
+
+impl ModifyCollectionOptionsBuilder {
+    pub fn validator(mut self, validator: Document) -> Self {
+        self.inner.validator = Some(validator);
+        self
+    }
+
+    pub fn validation_level(mut self, level: ValidationLevel) -> Self {
+        self.inner.validation_level = Some(level);
+        self
+    }
+
+    pub fn validation_action(mut self, action: ValidationAction) -> Self {
+        self.inner.validation_action = Some(action);
+        self
+    }
+
+    pub fn expire_at_field(mut self, field: impl Into<String>) -> Self {
+        self.inner.expire_at_field = Some(field.into());
+        self
+    }
+
+    pub fn max_document_size(mut self, size: u64) -> Self {
+        self.inner.max_document_size = Some(size);
+        self
+    }
+
+    pub fn max_document_depth(mut self, depth: u64) -> Self {
+        self.inner.max_document_depth = Some(depth);
+        self
+    }
+
+    pub fn default_value(mut self, field: impl Into<String>, value: impl Into<Bson>) -> Self {
+        self.inner.defaults.get_or_insert_with(IndexMap::new)
+            .insert(field.into(), FieldDefault::Value(value.into()));
+        self
+    }
+
+    pub fn default_now(mut self, field: impl Into<String>) -> Self {
+        self.inner.defaults.get_or_insert_with(IndexMap::new)
+            .insert(field.into(), FieldDefault::Now);
+        self
+    }
+
+    pub fn default_sequence(mut self, field: impl Into<String>) -> Self {
+        self.inner.defaults.get_or_insert_with(IndexMap::new)
+            .insert(field.into(), FieldDefault::Sequence);
+        self
+    }
+
+    pub fn crdt(mut self, crdt: bool) -> Self {
+        self.inner.crdt = Some(crdt);
+        self
+    }
+
+    pub fn build(self) -> ModifyCollectionOptions {
+        self.inner
+    }
+}
+
+#[cfg(feature = "gridfs")]
+#[derive(Debug, Clone, Default)]
+pub struct GridFsBucketOptions {
+    /// The prefix of the collections of the bucket, `fs` by default,
+    /// the files are in `<name>_files` and their chunks in `<name>_chunks`.
+    pub bucket_name: Option<String>,
+    /// The size of the chunks of the uploaded files, 255 KiB by default.
+    pub chunk_size_bytes: Option<u32>,
+}
+
+#[cfg(feature = "gridfs")]
+impl GridFsBucketOptions {
+    pub fn builder() -> GridFsBucketOptionsBuilder {
+        GridFsBucketOptionsBuilder::default()
+    }
+}
+
+#[cfg(feature = "gridfs")]
+#[derive(Default)]
+pub struct GridFsBucketOptionsBuilder {
+    bucket_name: Option<String>,
+    chunk_size_bytes: Option<u32>,
+}
+
+#[cfg(feature = "gridfs")]
+impl GridFsBucketOptionsBuilder {
+    pub fn bucket_name(mut self, bucket_name: impl Into<String>) -> Self {
+        self.bucket_name = Some(bucket_name.into());
+        self
+    }
+
+    pub fn chunk_size_bytes(mut self, chunk_size_bytes: u32) -> Self {
+        self.chunk_size_bytes = Some(chunk_size_bytes);
+        self
+    }
+
+    pub fn build(self) -> GridFsBucketOptions {
+        GridFsBucketOptions {
+            bucket_name: self.bucket_name,
+            chunk_size_bytes: self.chunk_size_bytes,
+        }
+    }
+}
+
+#[cfg(feature = "gridfs")]
+#[derive(Debug, Clone, Default)]
+pub struct GridFsFindOptions {
+    /// The order of the files, such as `{ "uploadDate": -1 }` for the newest first.
+    pub sort: Option<Document>,
+    pub skip: Option<u64>,
+    pub limit: Option<u64>,
+    /// Only the files whose `metadata.tags` array holds all these tags.
+    pub tags: Option<Vec<String>>,
+}
+
+#[cfg(feature = "gridfs")]
+impl GridFsFindOptions {
+    pub fn builder() -> GridFsFindOptionsBuilder {
+        GridFsFindOptionsBuilder::default()
+    }
+}
+
+#[cfg(feature = "gridfs")]
+#[derive(Default)]
+pub struct GridFsFindOptionsBuilder {
+    sort: Option<Document>,
+    skip: Option<u64>,
+    limit: Option<u64>,
+    tags: Option<Vec<String>>,
+}
+
+#[cfg(feature = "gridfs")]
+impl GridFsFindOptionsBuilder {
+    pub fn sort(mut self, sort: Document) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    pub fn skip(mut self, skip: u64) -> Self {
+        self.skip = Some(skip);
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.get_or_insert_with(Vec::new).push(tag.into());
+        self
+    }
+
+    pub fn build(self) -> GridFsFindOptions {
+        GridFsFindOptions {
+            sort: self.sort,
+            skip: self.skip,
+            limit: self.limit,
+            tags: self.tags,
         }
     }
 }
@@ -0,0 +1,261 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::fmt;
+use std::sync::{Arc, OnceLock, RwLock, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use bson::{doc, Bson, DateTime, Document};
+use crate::db::db_inner::DatabaseInner;
+use crate::vm::SubProgram;
+
+/// The collection the slow operations are recorded into by default.
+pub const DEFAULT_PROFILE_COLLECTION: &str = "system_profile";
+
+/// An operation recorded by the [`Profiler`].
+#[derive(Debug, Clone)]
+pub struct ProfileEntry {
+    /// The kind of the operation: `find`, `count`, `update`, `delete` or `aggregate`.
+    pub op: &'static str,
+    pub collection: String,
+    pub filter: Option<Document>,
+    /// The `$comment` of the filter or the comment given to the operation.
+    pub comment: Option<Bson>,
+    /// The disassembly of the program executed by the operation.
+    pub plan: String,
+    pub duration: Duration,
+    /// The number of documents read from the storage.
+    pub docs_examined: u64,
+    /// The number of documents returned to the caller.
+    pub docs_returned: u64,
+    pub ts: DateTime,
+}
+
+impl ProfileEntry {
+
+    pub fn to_document(&self) -> Document {
+        let mut doc = doc! {
+            "op": self.op,
+            "ns": self.collection.as_str(),
+            "plan": self.plan.as_str(),
+            "millis": self.duration.as_millis() as i64,
+            "micros": self.duration.as_micros() as i64,
+            "docsExamined": self.docs_examined as i64,
+            "nreturned": self.docs_returned as i64,
+            "ts": self.ts,
+        };
+        if let Some(filter) = &self.filter {
+            doc.insert("filter", filter.clone());
+        }
+        if let Some(comment) = &self.comment {
+            doc.insert("comment", comment.clone());
+        }
+        doc
+    }
+
+}
+
+/// Where the [`Profiler`] records the slow operations.
+#[derive(Clone)]
+pub enum ProfilerSink {
+    /// Insert the entries into a collection of the database.
+    Collection(String),
+    /// Call the function with every entry.
+    Callback(Arc<dyn Fn(&ProfileEntry) + Send + Sync>),
+}
+
+impl fmt::Debug for ProfilerSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfilerSink::Collection(name) => f.debug_tuple("Collection").field(name).finish(),
+            ProfilerSink::Callback(_) => f.write_str("Callback"),
+        }
+    }
+}
+
+impl Default for ProfilerSink {
+    fn default() -> Self {
+        ProfilerSink::Collection(DEFAULT_PROFILE_COLLECTION.to_string())
+    }
+}
+
+///
+/// The profiler records the operations slower than a threshold,
+/// with their filter, plan, duration and the number of documents examined.
+///
+/// The profiler is disabled by default, use [`Profiler::set_slow_threshold`] to enable it.
+///
+/// ```rust
+/// use std::time::Duration;
+/// # use polodb_core::Database;
+/// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-profiler");
+/// let db = Database::open_path(db_path).unwrap();
+/// db.profiler().set_callback(|entry| {
+///     println!("slow {} on {}: {:?}", entry.op, entry.collection, entry.duration);
+/// });
+/// db.profiler().set_slow_threshold(Duration::from_millis(100));
+/// ```
+#[derive(Clone)]
+pub struct Profiler {
+    inner: Arc<ProfilerInner>,
+}
+
+struct ProfilerInner {
+    enable: AtomicBool,
+    threshold: RwLock<Duration>,
+    sink: RwLock<ProfilerSink>,
+    db: OnceLock<Weak<DatabaseInner>>,
+}
+
+impl Profiler {
+
+    pub(crate) fn new() -> Profiler {
+        let inner = Arc::new(ProfilerInner {
+            enable: AtomicBool::new(false),
+            threshold: RwLock::new(Duration::ZERO),
+            sink: RwLock::new(ProfilerSink::default()),
+            db: OnceLock::new(),
+        });
+        Profiler {
+            inner,
+        }
+    }
+
+    pub(crate) fn attach(&self, db: Weak<DatabaseInner>) {
+        let _ = self.inner.db.set(db);
+    }
+
+    /// Enable the profiler, record the operations taking at least `threshold`.
+    pub fn set_slow_threshold(&self, threshold: Duration) {
+        *self.inner.threshold.write().unwrap() = threshold;
+        self.inner.enable.store(true, Ordering::Relaxed);
+    }
+
+    pub fn slow_threshold(&self) -> Option<Duration> {
+        if self.is_enabled() {
+            Some(*self.inner.threshold.read().unwrap())
+        } else {
+            None
+        }
+    }
+
+    pub fn disable(&self) {
+        self.inner.enable.store(false, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.inner.enable.load(Ordering::Relaxed)
+    }
+
+    pub fn set_sink(&self, sink: ProfilerSink) {
+        *self.inner.sink.write().unwrap() = sink;
+    }
+
+    /// Record the slow operations into the collection `name`.
+    pub fn set_collection(&self, name: &str) {
+        self.set_sink(ProfilerSink::Collection(name.to_string()));
+    }
+
+    /// Pass the slow operations to `callback` instead of a collection.
+    pub fn set_callback<F>(&self, callback: F)
+    where
+        F: Fn(&ProfileEntry) + Send + Sync + 'static,
+    {
+        self.set_sink(ProfilerSink::Callback(Arc::new(callback)));
+    }
+
+    /// Create a recorder for an operation, return `None` if the profiler is disabled.
+    pub(crate) fn recorder(
+        &self,
+        op: &'static str,
+        col_name: &str,
+        filter: Option<&Document>,
+        comment: Option<&Bson>,
+    ) -> Option<ProfileRecorder> {
+        if !self.is_enabled() {
+            return None;
+        }
+        // the operations on the profile collection are not recorded,
+        // otherwise the insertion of the entry may wait for the lock
+        // held by the operation itself
+        if let ProfilerSink::Collection(name) = &*self.inner.sink.read().unwrap() {
+            if name == col_name {
+                return None;
+            }
+        }
+        Some(ProfileRecorder {
+            profiler: self.clone(),
+            op,
+            collection: col_name.to_string(),
+            filter: filter.cloned(),
+            comment: comment.cloned(),
+        })
+    }
+
+    fn record(&self, entry: ProfileEntry) {
+        let sink = self.inner.sink.read().unwrap().clone();
+        match sink {
+            ProfilerSink::Callback(callback) => callback(&entry),
+            ProfilerSink::Collection(name) => {
+                let db = match self.inner.db.get().and_then(Weak::upgrade) {
+                    Some(db) => db,
+                    None => return,
+                };
+                let result = db.start_transaction().and_then(|txn| {
+                    db.insert_one(&name, entry.to_document(), &txn)?;
+                    txn.commit()
+                });
+                if let Err(err) = result {
+                    crate::polo_log!("failed to record the profile entry: {}", err);
+                }
+            }
+        }
+    }
+
+}
+
+/// Holds the description of a profiled operation,
+/// the entry is recorded when the VM of the operation is dropped.
+pub(crate) struct ProfileRecorder {
+    profiler: Profiler,
+    op: &'static str,
+    collection: String,
+    filter: Option<Document>,
+    comment: Option<Bson>,
+}
+
+impl ProfileRecorder {
+
+    pub(crate) fn finish(self, program: &SubProgram, elapsed: Duration, docs_examined: u64, docs_returned: u64) {
+        let threshold = *self.profiler.inner.threshold.read().unwrap();
+        if elapsed < threshold {
+            return;
+        }
+        let entry = ProfileEntry {
+            op: self.op,
+            collection: self.collection,
+            filter: self.filter,
+            comment: self.comment,
+            plan: program.to_string(),
+            duration: elapsed,
+            docs_examined,
+            docs_returned,
+            ts: DateTime::now(),
+        };
+        self.profiler.record(entry);
+    }
+
+}
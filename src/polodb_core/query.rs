@@ -0,0 +1,254 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A typed alternative to `doc!` for the filters of the queries.
+//!
+//! The derive macro generates a `fields()` function on the models,
+//! returning the typed [`Field`]s of the model:
+//!
+//! ```rust
+//! # #[cfg(feature = "derive")]
+//! # {
+//! use polodb_core::{Database, CollectionT, PoloModel};
+//! use polodb_core::query::filter;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, Serialize, Deserialize, PoloModel)]
+//! struct User {
+//!     name: String,
+//!     age: i32,
+//! }
+//!
+//! # let db_path = polodb_core::test_utils::mk_db_path("doc-test-query");
+//! let db = Database::open_path(db_path).unwrap();
+//! let users = db.collection_for::<User>().unwrap();
+//! users.insert_one(User { name: "Ann".into(), age: 31 }).unwrap();
+//!
+//! let fields = User::fields();
+//! let adults = users.find(filter(fields.age().gte(18).and(fields.name().ne("Bob")))).run().unwrap();
+//! assert_eq!(adults.count(), 1);
+//! # }
+//! ```
+
+use std::marker::PhantomData;
+use bson::{Bson, Document, Regex};
+use serde::Serialize;
+
+fn to_bson<V: Serialize + ?Sized>(value: &V) -> Bson {
+    bson::to_bson(value).expect("the value of a filter must be serializable as BSON")
+}
+
+/// A field of a model holding values of type `V`, identified by its path in the documents.
+///
+/// The values compared to the field are serialized as BSON,
+/// the methods panic if a value can't be serialized.
+pub struct Field<V> {
+    path: &'static str,
+    _phantom: PhantomData<fn() -> V>,
+}
+
+impl<V> Clone for Field<V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<V> Copy for Field<V> {}
+
+impl<V> Field<V> {
+
+    pub const fn new(path: &'static str) -> Field<V> {
+        Field {
+            path,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The path of the field in the documents.
+    pub fn path(&self) -> &'static str {
+        self.path
+    }
+
+    fn op(&self, op: &str, value: Bson) -> Filter {
+        let mut condition = Document::new();
+        condition.insert(op, value);
+        let mut doc = Document::new();
+        doc.insert(self.path, condition);
+        Filter(doc)
+    }
+
+}
+
+impl<V: Serialize> Field<V> {
+
+    pub fn eq(&self, value: impl Into<V>) -> Filter {
+        self.op("$eq", to_bson(&value.into()))
+    }
+
+    pub fn ne(&self, value: impl Into<V>) -> Filter {
+        self.op("$ne", to_bson(&value.into()))
+    }
+
+    pub fn gt(&self, value: impl Into<V>) -> Filter {
+        self.op("$gt", to_bson(&value.into()))
+    }
+
+    pub fn gte(&self, value: impl Into<V>) -> Filter {
+        self.op("$gte", to_bson(&value.into()))
+    }
+
+    pub fn lt(&self, value: impl Into<V>) -> Filter {
+        self.op("$lt", to_bson(&value.into()))
+    }
+
+    pub fn lte(&self, value: impl Into<V>) -> Filter {
+        self.op("$lte", to_bson(&value.into()))
+    }
+
+    /// The field is equal to one of the `values`.
+    pub fn is_in<I: Into<V>>(&self, values: impl IntoIterator<Item = I>) -> Filter {
+        let values = values.into_iter().map(|value| to_bson(&value.into())).collect();
+        self.op("$in", Bson::Array(values))
+    }
+
+    /// The field is equal to none of the `values`.
+    pub fn not_in<I: Into<V>>(&self, values: impl IntoIterator<Item = I>) -> Filter {
+        let values = values.into_iter().map(|value| to_bson(&value.into())).collect();
+        self.op("$nin", Bson::Array(values))
+    }
+
+}
+
+impl Field<String> {
+
+    /// The field is a string matching the regular expression `pattern`.
+    pub fn matches(&self, pattern: &str, options: &str) -> Filter {
+        self.op("$regex", Bson::RegularExpression(Regex {
+            pattern: pattern.to_string(),
+            options: options.to_string(),
+        }))
+    }
+
+    /// Each word of `search` is within `max_edits` typos of a word of the field.
+    pub fn fuzzy(&self, search: &str, max_edits: u32) -> Filter {
+        self.op("$fuzzy", Bson::Document(bson::doc! {
+            "$search": search,
+            "$maxEdits": max_edits as i64,
+        }))
+    }
+
+}
+
+impl<T> Field<Vec<T>> {
+
+    /// The field is an array of `size` elements.
+    pub fn size(&self, size: usize) -> Filter {
+        self.op("$size", Bson::Int64(size as i64))
+    }
+
+    /// An element of the field matches all the conditions of `query`, such as
+    /// `{ "score": { "$gt": 4 }, "user": "a" }` for the elements which are documents.
+    pub fn elem_match(&self, query: Document) -> Filter {
+        self.op("$elemMatch", Bson::Document(query))
+    }
+
+}
+
+/// A filter of a query, built from the [`Field`]s of a model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter(Document);
+
+impl Filter {
+
+    /// The documents matching both filters.
+    pub fn and(self, other: Filter) -> Filter {
+        Filter::combine("$and", self, other)
+    }
+
+    /// The documents matching either filter.
+    pub fn or(self, other: Filter) -> Filter {
+        Filter::combine("$or", self, other)
+    }
+
+    fn combine(op: &str, left: Filter, right: Filter) -> Filter {
+        let mut filters = Vec::new();
+        for filter in [left, right] {
+            match filter.into_operands(op) {
+                Ok(operands) => filters.extend(operands),
+                Err(filter) => filters.push(Bson::Document(filter.0)),
+            }
+        }
+        let mut doc = Document::new();
+        doc.insert(op, filters);
+        Filter(doc)
+    }
+
+    /// The operands of a filter made of the operator `op` only, such as
+    /// `{ "$and": [...] }`, so the chains of `and` stay flat.
+    fn into_operands(self, op: &str) -> std::result::Result<Vec<Bson>, Filter> {
+        if self.0.len() == 1 {
+            if let Some(Bson::Array(operands)) = self.0.get(op) {
+                return Ok(operands.clone());
+            }
+        }
+        Err(self)
+    }
+
+    pub fn into_document(self) -> Document {
+        self.0
+    }
+
+}
+
+impl From<Filter> for Document {
+    fn from(filter: Filter) -> Document {
+        filter.0
+    }
+}
+
+/// Convert a typed filter into the document given to the queries.
+pub fn filter(filter: Filter) -> Document {
+    filter.0
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+    use super::Field;
+
+    #[test]
+    fn test_filter() {
+        let age = Field::<i32>::new("age");
+        let name = Field::<String>::new("name");
+        let tags = Field::<Vec<String>>::new("tags");
+
+        let filter = age.gt(18).and(name.eq("Ann")).and(tags.size(2));
+        assert_eq!(filter.into_document(), doc! {
+            "$and": [
+                { "age": { "$gt": 18 } },
+                { "name": { "$eq": "Ann" } },
+                { "tags": { "$size": 2_i64 } },
+            ],
+        });
+
+        let filter = age.lt(10).or(age.is_in([20, 30])).and(name.ne("Bob"));
+        assert_eq!(filter.into_document(), doc! {
+            "$and": [
+                { "$or": [{ "age": { "$lt": 10 } }, { "age": { "$in": [20, 30] } }] },
+                { "name": { "$ne": "Bob" } },
+            ],
+        });
+    }
+
+}
@@ -0,0 +1,328 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use bson::{doc, Bson, Document};
+use bson::spec::ElementType;
+
+/// The bytes counted for an entry besides its key and its documents.
+const ENTRY_OVERHEAD: usize = 128;
+
+/// The query of a cached result: the collection, the filter and the options of a find.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) struct QueryKey {
+    collection: String,
+    query: Vec<u8>,
+}
+
+impl QueryKey {
+
+    pub(crate) fn new(
+        collection: &str,
+        filter: &Document,
+        sort: Option<&Document>,
+        skip: Option<u64>,
+        limit: Option<u64>,
+    ) -> Option<QueryKey> {
+        let query = doc! {
+            "filter": filter.clone(),
+            "sort": sort.cloned(),
+            "skip": skip.map(|skip| skip as i64),
+            "limit": limit.map(|limit| limit as i64),
+        };
+        Some(QueryKey {
+            collection: collection.to_string(),
+            query: bson::to_vec(&query).ok()?,
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.collection.len() + self.query.len()
+    }
+
+}
+
+struct CacheEntry {
+    docs: Arc<Vec<Bson>>,
+    size: usize,
+    tick: u64,
+    cached_at: Instant,
+}
+
+struct QueryCacheInner {
+    capacity: usize,
+    size: usize,
+    tick: u64,
+    entries: HashMap<QueryKey, CacheEntry>,
+    /// The keys of the entries, least recently used first.
+    order: BTreeMap<u64, QueryKey>,
+    /// The generation of the last write to each collection.
+    written_at: HashMap<String, u64>,
+}
+
+impl QueryCacheInner {
+
+    fn remove(&mut self, key: &QueryKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.tick);
+            self.size -= entry.size;
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.capacity {
+            let key = match self.order.pop_first() {
+                Some((_, key)) => key,
+                None => break,
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.size -= entry.size;
+            }
+        }
+    }
+
+}
+
+/// A LRU cache of the results of the recent finds, see [`crate::ConfigBuilder::set_query_cache_size`].
+///
+/// The results of a collection are removed when a transaction writing to the collection
+/// is committed. A result read before such a commit is not cached after it, so a reader
+/// racing with a writer doesn't put back the result the writer changed.
+#[derive(Clone)]
+pub(crate) struct QueryCache {
+    inner: Arc<Mutex<QueryCacheInner>>,
+    generation: Arc<AtomicU64>,
+    ttl: Option<Duration>,
+}
+
+impl QueryCache {
+
+    pub(crate) fn new(capacity: u64, ttl: Option<Duration>) -> QueryCache {
+        QueryCache {
+            inner: Arc::new(Mutex::new(QueryCacheInner {
+                capacity: capacity as usize,
+                size: 0,
+                tick: 0,
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                written_at: HashMap::new(),
+            })),
+            generation: Arc::new(AtomicU64::new(0)),
+            ttl,
+        }
+    }
+
+    /// The bytes the cache can take, the results larger than it are not cached.
+    pub(crate) fn capacity(&self) -> usize {
+        self.inner.lock().unwrap().capacity
+    }
+
+    /// The generation to give to `insert` for a result read after this call.
+    #[inline]
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// The cached result of the query, unless it's older than the time to live.
+    pub(crate) fn get(&self, key: &QueryKey) -> Option<Arc<Vec<Bson>>> {
+        let mut inner = self.inner.lock().unwrap();
+        let expired = inner.entries.get(key)
+            .map(|entry| self.ttl.is_some_and(|ttl| entry.cached_at.elapsed() >= ttl))?;
+        if expired {
+            inner.remove(key);
+            return None;
+        }
+        inner.tick += 1;
+        let tick = inner.tick;
+        let entry = inner.entries.get_mut(key)?;
+        let previous = std::mem::replace(&mut entry.tick, tick);
+        let docs = entry.docs.clone();
+        if let Some(key) = inner.order.remove(&previous) {
+            inner.order.insert(tick, key);
+        }
+        Some(docs)
+    }
+
+    /// Cache the result of the query read at `generation`, unless
+    /// a transaction has written to its collection since.
+    pub(crate) fn insert(&self, key: QueryKey, docs: Vec<Bson>, docs_size: usize, generation: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.written_at.get(&key.collection).is_some_and(|written_at| *written_at > generation) {
+            return;
+        }
+        let size = key.size() + docs_size + ENTRY_OVERHEAD;
+        if size > inner.capacity {
+            return;
+        }
+        inner.remove(&key);
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.order.insert(tick, key.clone());
+        inner.entries.insert(key, CacheEntry {
+            docs: Arc::new(docs),
+            size,
+            tick,
+            cached_at: Instant::now(),
+        });
+        inner.size += size;
+        inner.evict();
+    }
+
+    /// Remove the results of the collections written at `keys`.
+    pub(crate) fn invalidate(&self, keys: &[Vec<u8>]) {
+        let mut collections = keys.iter()
+            .filter_map(|key| written_collection(key))
+            .collect::<Vec<_>>();
+        collections.sort_unstable();
+        collections.dedup();
+
+        let mut inner = self.inner.lock().unwrap();
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        for collection in collections {
+            inner.written_at.insert(collection.to_string(), generation);
+            let stale = inner.entries.keys()
+                .filter(|key| key.collection == collection)
+                .cloned()
+                .collect::<Vec<_>>();
+            for key in stale {
+                inner.remove(&key);
+            }
+        }
+    }
+
+    /// The bytes taken by the cached results.
+    #[allow(dead_code)]
+    pub(crate) fn size(&self) -> usize {
+        self.inner.lock().unwrap().size
+    }
+
+}
+
+/// Keeps the rows of a result as a cursor gives them, to cache the result once they are all given.
+pub(crate) struct ResultRecorder {
+    cache: QueryCache,
+    key: QueryKey,
+    generation: u64,
+    docs: Vec<Bson>,
+    size: usize,
+    capacity: usize,
+}
+
+impl ResultRecorder {
+
+    pub(crate) fn new(cache: &QueryCache, key: QueryKey, generation: u64) -> ResultRecorder {
+        ResultRecorder {
+            capacity: cache.capacity(),
+            cache: cache.clone(),
+            key,
+            generation,
+            docs: Vec::new(),
+            size: 0,
+        }
+    }
+
+    /// Keep the row, return false once the result is too large to be cached.
+    pub(crate) fn record(&mut self, row: Bson) -> bool {
+        self.size += match &row {
+            Bson::Document(doc) => bson::to_vec(doc).map_or(0, |data| data.len()),
+            _ => 0,
+        };
+        self.docs.push(row);
+        self.size <= self.capacity
+    }
+
+    pub(crate) fn finish(self) {
+        self.cache.insert(self.key, self.docs, self.size, self.generation);
+    }
+
+}
+
+/// The string at the start of a stacked key, and the rest of the key.
+fn leading_string(key: &[u8]) -> Option<(&str, &[u8])> {
+    if key.first() != Some(&(ElementType::String as u8)) {
+        return None;
+    }
+    let len = key[1..].iter().position(|byte| *byte == 0)?;
+    let value = std::str::from_utf8(&key[1..1 + len]).ok()?;
+    Some((value, &key[2 + len..]))
+}
+
+/// The collection whose documents, indexes or specification are stored at `key`:
+/// the keys of the documents start with the name of the collection, and the other
+/// keys of a collection with an internal prefix followed by its name.
+fn written_collection(key: &[u8]) -> Option<&str> {
+    let (first, rest) = leading_string(key)?;
+    if !first.starts_with('$') {
+        return Some(first);
+    }
+    leading_string(rest).map(|(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use bson::{doc, Bson};
+    use super::{QueryCache, QueryKey};
+
+    fn data_key(collection: &str, id: i32) -> Vec<u8> {
+        crate::utils::bson::stacked_key([&Bson::String(collection.to_string()), &Bson::Int32(id)]).unwrap()
+    }
+
+    #[test]
+    fn test_query_cache_invalidation() {
+        let cache = QueryCache::new(64 * 1024, None);
+        let countries = QueryKey::new("countries", &doc! {}, None, None, None).unwrap();
+        let orders = QueryKey::new("orders", &doc! {}, None, None, Some(10)).unwrap();
+        let generation = cache.generation();
+        cache.insert(countries.clone(), vec![Bson::Int32(1)], 16, generation);
+        cache.insert(orders.clone(), vec![Bson::Int32(2)], 16, generation);
+        assert!(cache.get(&countries).is_some());
+
+        // a write to orders leaves the results of countries
+        cache.invalidate(&[data_key("orders", 1)]);
+        assert!(cache.get(&orders).is_none());
+        assert!(cache.get(&countries).is_some());
+
+        // read before the write was committed
+        cache.insert(orders.clone(), vec![Bson::Int32(3)], 16, generation);
+        assert!(cache.get(&orders).is_none());
+
+        // the index entries and the specification of a collection
+        let index_key = crate::utils::bson::stacked_key([
+            &Bson::String("$I".to_string()),
+            &Bson::String("countries".to_string()),
+        ]).unwrap();
+        cache.invalidate(&[index_key]);
+        assert!(cache.get(&countries).is_none());
+    }
+
+    #[test]
+    fn test_query_cache_ttl_and_size() {
+        let cache = QueryCache::new(1024, Some(Duration::from_millis(20)));
+        let key = QueryKey::new("countries", &doc! { "code": "FR" }, None, None, None).unwrap();
+        cache.insert(key.clone(), vec![Bson::Int32(1)], 16, cache.generation());
+        assert!(cache.get(&key).is_some());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(cache.get(&key).is_none());
+        assert_eq!(cache.size(), 0);
+
+        // larger than the cache
+        cache.insert(key.clone(), vec![Bson::Int32(1)], 4096, cache.generation());
+        assert!(cache.get(&key).is_none());
+    }
+
+}
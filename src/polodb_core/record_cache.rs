@@ -0,0 +1,209 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The bytes counted for an entry besides its key and its compressed document.
+const ENTRY_OVERHEAD: usize = 64;
+
+struct CacheEntry {
+    compressed: Vec<u8>,
+    tick: u64,
+}
+
+struct RecordCacheInner {
+    capacity: usize,
+    size: usize,
+    tick: u64,
+    entries: HashMap<Vec<u8>, CacheEntry>,
+    /// The keys of the entries, least recently used first.
+    order: BTreeMap<u64, Vec<u8>>,
+}
+
+impl RecordCacheInner {
+
+    fn entry_size(key: &[u8], compressed: &[u8]) -> usize {
+        key.len() + compressed.len() + ENTRY_OVERHEAD
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.tick);
+            self.size -= RecordCacheInner::entry_size(key, &entry.compressed);
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.capacity {
+            let key = match self.order.pop_first() {
+                Some((_, key)) => key,
+                None => break,
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.size -= RecordCacheInner::entry_size(&key, &entry.compressed);
+            }
+        }
+    }
+
+}
+
+/// A LRU cache of the documents found by their key, kept compressed so more
+/// of them fit in the configured size, and decompressed when they are read again.
+///
+/// The documents written by a transaction are removed when it is committed.
+/// A document read before a commit is not cached after it, so a reader
+/// racing with a writer doesn't put back the document the writer replaced.
+#[derive(Clone)]
+pub(crate) struct RecordCache {
+    inner: Arc<Mutex<RecordCacheInner>>,
+    generation: Arc<AtomicU64>,
+}
+
+impl RecordCache {
+
+    pub(crate) fn new(capacity: u64) -> RecordCache {
+        RecordCache {
+            inner: Arc::new(Mutex::new(RecordCacheInner {
+                capacity: capacity as usize,
+                size: 0,
+                tick: 0,
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+            })),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// The generation to give to `insert` for a document read after this call.
+    #[inline]
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Decompress the document at `key` into `buf`, return false if it's not cached.
+    pub(crate) fn get(&self, key: &[u8], buf: &mut Vec<u8>) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        inner.tick += 1;
+        let tick = inner.tick;
+        let previous = match inner.entries.get_mut(key) {
+            Some(entry) => std::mem::replace(&mut entry.tick, tick),
+            None => return false,
+        };
+        if let Some(key) = inner.order.remove(&previous) {
+            inner.order.insert(tick, key);
+        }
+        let compressed = &inner.entries[key].compressed;
+        let len = match snap::raw::decompress_len(compressed) {
+            Ok(len) => len,
+            Err(_) => return false,
+        };
+        buf.clear();
+        buf.resize(len, 0);
+        snap::raw::Decoder::new().decompress(compressed, buf).is_ok()
+    }
+
+    /// Cache the document at `key` read at `generation`, unless
+    /// a transaction has written documents since.
+    pub(crate) fn insert(&self, key: &[u8], data: &[u8], generation: u64) {
+        let compressed = match snap::raw::Encoder::new().compress_vec(data) {
+            Ok(compressed) => compressed,
+            Err(_) => return,
+        };
+        let mut inner = self.inner.lock().unwrap();
+        if self.generation() != generation {
+            return;
+        }
+        let size = RecordCacheInner::entry_size(key, &compressed);
+        if size > inner.capacity {
+            return;
+        }
+        inner.remove(key);
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.order.insert(tick, key.to_vec());
+        inner.entries.insert(key.to_vec(), CacheEntry { compressed, tick });
+        inner.size += size;
+        inner.evict();
+    }
+
+    /// Remove the documents written at `keys`.
+    pub(crate) fn invalidate(&self, keys: &[Vec<u8>]) {
+        let mut inner = self.inner.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        for key in keys {
+            inner.remove(key);
+        }
+    }
+
+    /// The bytes taken by the cached documents.
+    #[allow(dead_code)]
+    pub(crate) fn size(&self) -> usize {
+        self.inner.lock().unwrap().size
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecordCache;
+
+    #[test]
+    fn test_record_cache_eviction() {
+        let cache = RecordCache::new(1024);
+        let data = vec![7u8; 4096];
+        let generation = cache.generation();
+        cache.insert(b"a", &data, generation);
+        cache.insert(b"b", &data, generation);
+
+        // the documents are compressed, both fit
+        let mut buf = Vec::new();
+        assert!(cache.get(b"a", &mut buf));
+        assert_eq!(buf, data);
+        assert!(cache.size() <= 1024);
+
+        // "b" is the least recently used
+        let mut seed: u32 = 1;
+        let other: Vec<u8> = (0..600).map(|_| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) as u8
+        }).collect();
+        cache.insert(b"c", &other, generation);
+        assert!(!cache.get(b"b", &mut buf));
+        assert!(cache.get(b"a", &mut buf));
+        assert!(cache.get(b"c", &mut buf));
+        assert_eq!(buf, other);
+    }
+
+    #[test]
+    fn test_record_cache_invalidation() {
+        let cache = RecordCache::new(1024);
+        let stale = cache.generation();
+        cache.insert(b"a", b"old", stale);
+        cache.invalidate(&[b"a".to_vec()]);
+
+        let mut buf = Vec::new();
+        assert!(!cache.get(b"a", &mut buf));
+
+        // read before the write was committed
+        cache.insert(b"a", b"old", stale);
+        assert!(!cache.get(b"a", &mut buf));
+
+        cache.insert(b"a", b"new", cache.generation());
+        assert!(cache.get(b"a", &mut buf));
+        assert_eq!(buf, b"new");
+    }
+
+}
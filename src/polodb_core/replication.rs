@@ -0,0 +1,541 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{ErrorKind, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+use bson::{doc, Bson, Document};
+use serde::{Deserialize, Serialize};
+use crate::change_stream::{ChangeEvent, OperationType};
+use crate::coll::collection_info::CollectionSpecification;
+use crate::cursor::Cursor;
+use crate::db::db_inner::DatabaseInner;
+use crate::transaction::TransactionInner;
+use crate::{Error, Result};
+
+/// The interval of the heartbeats sent by the primary when nothing changes.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+/// A replica reconnects when it hears nothing from the primary for this long.
+const PRIMARY_TIMEOUT: Duration = Duration::from_secs(5);
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+/// The number of documents or changes sent in one message.
+const BATCH_SIZE: usize = 1000;
+/// The key of the token of the last change of the primary applied by a replica.
+const REPLICATION_KEY: &str = "$REPLICATION";
+
+/// A bidirectional channel carrying the messages between a primary and a replica,
+/// a [`TcpStream`] by default.
+pub trait ReplicationTransport: Send {
+    fn send(&mut self, message: &Document) -> Result<()>;
+
+    /// Wait for the next message, failing if the channel is closed.
+    fn receive(&mut self) -> Result<Document>;
+}
+
+impl ReplicationTransport for TcpStream {
+
+    fn send(&mut self, message: &Document) -> Result<()> {
+        let buf = bson::to_vec(message)?;
+        self.write_all(&buf)?;
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Document> {
+        Ok(Document::from_reader(self)?)
+    }
+
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Message {
+    /// Sent by a replica when it connects, with the token of the last change it applied.
+    Hello { after: Option<u64> },
+    /// The start of the initial sync, the collections of the replica are dropped.
+    SyncStart,
+    /// A collection of the primary, with its indexes and options.
+    Collection { spec: Document },
+    Documents { collection: String, documents: Vec<Document> },
+    /// The end of the initial sync, the snapshot contains the changes up to `token`.
+    SyncEnd { token: u64 },
+    Changes { events: Vec<ChangeEvent> },
+    /// Sent by the primary when nothing changes, with its last committed token.
+    Heartbeat { token: u64 },
+}
+
+fn send(transport: &mut dyn ReplicationTransport, message: &Message) -> Result<()> {
+    transport.send(&bson::to_document(message)?)
+}
+
+fn receive(transport: &mut dyn ReplicationTransport) -> Result<Message> {
+    Ok(bson::from_document(transport.receive()?)?)
+}
+
+fn upgrade(db: &Weak<DatabaseInner>) -> Result<Arc<DatabaseInner>> {
+    db.upgrade().ok_or(Error::DbIsClosed)
+}
+
+/// Serve one replica until it disconnects, `stop` is set, or the database is closed.
+pub(crate) fn serve_replica(
+    db: &Weak<DatabaseInner>,
+    transport: &mut dyn ReplicationTransport,
+    stop: &AtomicBool,
+) -> Result<()> {
+    let after = match receive(transport)? {
+        Message::Hello { after } => after,
+        message => return Err(Error::Replication(format!("unexpected message: {:?}", message))),
+    };
+
+    let mut stream = {
+        let db = upgrade(db)?;
+        let changes = db.change_streams();
+        let oplog = changes.oplog().ok_or_else(|| {
+            Error::Replication("the primary needs the oplog, see ConfigBuilder::set_oplog_size".to_string())
+        })?;
+        // subscribed first, so the changes committed during the sync are not missed
+        let mut stream = changes.subscribe::<Document>(None, true);
+        let txn = db.start_transaction()?;
+        let resumed = match after {
+            // the replica was synced from another primary
+            Some(token) if token > oplog.committed_token() => None,
+            Some(token) => match oplog.read_after(&txn, token) {
+                Ok(events) => Some((token, events)),
+                Err(Error::ChangeStreamHistoryLost(_)) => None,
+                Err(err) => return Err(err),
+            },
+            None => None,
+        };
+        let (token, events) = match resumed {
+            Some(resumed) => resumed,
+            None => {
+                // read before the snapshot, the changes after it are applied again
+                let token = oplog.committed_token();
+                send_snapshot(&db, &txn, transport, token)?;
+                (token, oplog.read_after(&txn, token)?)
+            }
+        };
+        stream.resume(token, events);
+        stream
+    };
+
+    while !stop.load(Ordering::SeqCst) {
+        match stream.next_timeout(HEARTBEAT_INTERVAL) {
+            Some(event) => {
+                let mut events = vec![event?];
+                while events.len() < BATCH_SIZE {
+                    match stream.try_next() {
+                        Some(event) => events.push(event?),
+                        None => break,
+                    }
+                }
+                send(transport, &Message::Changes { events })?;
+            }
+            None => {
+                let db = upgrade(db)?;
+                let token = db.change_streams().oplog().map_or(0, |oplog| oplog.committed_token());
+                send(transport, &Message::Heartbeat { token })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn send_snapshot(
+    db: &DatabaseInner,
+    txn: &TransactionInner,
+    transport: &mut dyn ReplicationTransport,
+    token: u64,
+) -> Result<()> {
+    send(transport, &Message::SyncStart)?;
+    for spec in db.query_all_meta(txn)? {
+        let name = spec.get_str("_id").map_err(|_| Error::Replication("invalid collection".to_string()))?.to_string();
+        send(transport, &Message::Collection { spec })?;
+
+        let mut cursor = Cursor::new_with_str_prefix(name.as_str(), txn.rocksdb_txn.new_iterator())?;
+        cursor.reset()?;
+        let mut documents = Vec::new();
+        while cursor.has_next() {
+            documents.push(bson::from_slice::<Document>(&cursor.copy_data()?)?);
+            if documents.len() == BATCH_SIZE {
+                let collection = name.clone();
+                send(transport, &Message::Documents { collection, documents: std::mem::take(&mut documents) })?;
+            }
+            cursor.next()?;
+        }
+        if !documents.is_empty() {
+            send(transport, &Message::Documents { collection: name, documents })?;
+        }
+    }
+    send(transport, &Message::SyncEnd { token })
+}
+
+/// Accepts the replicas connecting to a primary, see [`crate::Database::serve_replication`].
+/// The replicas are disconnected when it's stopped or dropped.
+pub struct ReplicationServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ReplicationServer {
+
+    pub(crate) fn start(db: Weak<DatabaseInner>, listener: TcpListener) -> Result<ReplicationServer> {
+        let addr = listener.local_addr()?;
+        listener.set_nonblocking(true)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let server_stop = stop.clone();
+        let handle = std::thread::spawn(move || {
+            while !server_stop.load(Ordering::SeqCst) && db.strong_count() > 0 {
+                match listener.accept() {
+                    Ok((mut stream, _)) => {
+                        let db = db.clone();
+                        let stop = server_stop.clone();
+                        std::thread::spawn(move || {
+                            if stream.set_nonblocking(false).is_ok() {
+                                let _ = serve_replica(&db, &mut stream, &stop);
+                            }
+                        });
+                    }
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                        std::thread::sleep(Duration::from_millis(20));
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+        Ok(ReplicationServer {
+            addr,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    /// The address the replicas connect to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+
+}
+
+impl Drop for ReplicationServer {
+
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplicaState {
+    Connecting,
+    /// Copying the collections of the primary.
+    InitialSync,
+    /// Applying the changes of the primary as they are committed.
+    Streaming,
+    Stopped,
+}
+
+/// The progress of a replica, see [`Replica::status`].
+#[derive(Debug, Clone)]
+pub struct ReplicaStatus {
+    pub state: ReplicaState,
+    /// The token of the last change of the primary applied.
+    pub applied_token: Option<u64>,
+    /// The token of the last change committed by the primary, as of the last message.
+    pub primary_token: Option<u64>,
+    /// The last time a message was received from the primary.
+    pub last_contact: Option<SystemTime>,
+    /// The error which interrupted the last connection.
+    pub last_error: Option<String>,
+}
+
+impl ReplicaStatus {
+
+    /// The number of changes committed by the primary and not applied yet.
+    pub fn lag(&self) -> u64 {
+        self.primary_token.unwrap_or(0).saturating_sub(self.applied_token.unwrap_or(0))
+    }
+
+}
+
+type Connect = Box<dyn FnMut() -> Result<Box<dyn ReplicationTransport>> + Send>;
+
+/// Applies the changes of a primary to a read-only database, see [`crate::Database::replicate_from`].
+///
+/// The replica reconnects when the connection is lost, resuming after the last change it applied,
+/// or syncing the whole database again if the oplog of the primary no longer has it.
+/// The database is writable again once the replica is stopped or dropped.
+///
+/// The documents are replicated with the indexes and the options of the collections
+/// at the time of the initial sync. The collections and the indexes created or dropped
+/// later on the primary are not replicated, until the next initial sync.
+pub struct Replica {
+    db: Weak<DatabaseInner>,
+    status: Arc<Mutex<ReplicaStatus>>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Replica {
+
+    pub(crate) fn start<F>(db: &Arc<DatabaseInner>, connect: F) -> Result<Replica>
+    where
+        F: FnMut() -> Result<Box<dyn ReplicationTransport>> + Send + 'static,
+    {
+        let applied_token = load_token(db)?;
+        db.set_replica(true);
+        let status = Arc::new(Mutex::new(ReplicaStatus {
+            state: ReplicaState::Connecting,
+            applied_token,
+            primary_token: None,
+            last_contact: None,
+            last_error: None,
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let mut worker = ReplicaWorker {
+            db: Arc::downgrade(db),
+            status: status.clone(),
+            stop: stop.clone(),
+            connect: Box::new(connect),
+        };
+        let handle = std::thread::spawn(move || worker.run());
+
+        Ok(Replica {
+            db: Arc::downgrade(db),
+            status,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn status(&self) -> ReplicaStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Wait until the replica is streaming and has applied the changes up to `token`, at most `timeout`.
+    pub fn wait_for(&self, token: u64, timeout: Duration) -> bool {
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            let status = self.status();
+            if status.state == ReplicaState::Streaming && status.applied_token.unwrap_or(0) >= token {
+                return true;
+            }
+            if std::time::Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Stop applying the changes, the database is writable again.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        if let Some(db) = self.db.upgrade() {
+            db.set_replica(false);
+        }
+        self.status.lock().unwrap().state = ReplicaState::Stopped;
+    }
+
+}
+
+impl Drop for Replica {
+
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+
+}
+
+struct ReplicaWorker {
+    db: Weak<DatabaseInner>,
+    status: Arc<Mutex<ReplicaStatus>>,
+    stop: Arc<AtomicBool>,
+    connect: Connect,
+}
+
+impl ReplicaWorker {
+
+    fn run(&mut self) {
+        while !self.stop.load(Ordering::SeqCst) && self.db.strong_count() > 0 {
+            self.set_state(ReplicaState::Connecting);
+            let result = (self.connect)().and_then(|mut transport| self.session(transport.as_mut()));
+            if let Err(err) = result {
+                self.status.lock().unwrap().last_error = Some(err.to_string());
+                self.sleep(RECONNECT_DELAY);
+            }
+        }
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let deadline = std::time::Instant::now() + duration;
+        while !self.stop.load(Ordering::SeqCst) && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn set_state(&self, state: ReplicaState) {
+        self.status.lock().unwrap().state = state;
+    }
+
+    fn session(&self, transport: &mut dyn ReplicationTransport) -> Result<()> {
+        let after = load_token(upgrade(&self.db)?.as_ref())?;
+        send(transport, &Message::Hello { after })?;
+        while !self.stop.load(Ordering::SeqCst) {
+            let message = receive(transport)?;
+            let db = upgrade(&self.db)?;
+            self.status.lock().unwrap().last_contact = Some(SystemTime::now());
+            match message {
+                Message::SyncStart => {
+                    self.set_state(ReplicaState::InitialSync);
+                    apply(&db, |txn| {
+                        txn.delete(&replication_key()?)?;
+                        for name in db.list_collection_names_with_session(txn)? {
+                            db.drop_collection(&name, txn)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+                Message::Collection { spec } => {
+                    let spec: CollectionSpecification = bson::from_document(spec)?;
+                    apply(&db, |txn| db.restore_collection(&spec, txn))?;
+                }
+                Message::Documents { collection, documents } => {
+                    apply(&db, |txn| {
+                        for doc in documents {
+                            db.insert_one(&collection, doc, txn)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+                Message::SyncEnd { token } => {
+                    apply(&db, |txn| save_token(txn, token))?;
+                    let mut status = self.status.lock().unwrap();
+                    status.state = ReplicaState::Streaming;
+                    status.applied_token = Some(token);
+                    status.primary_token = Some(status.primary_token.unwrap_or(0).max(token));
+                }
+                Message::Changes { events } => {
+                    let token = events.iter().filter_map(|event| event.resume_token).max();
+                    apply(&db, |txn| {
+                        for event in events {
+                            apply_change(&db, event, txn)?;
+                        }
+                        if let Some(token) = token {
+                            save_token(txn, token)?;
+                        }
+                        Ok(())
+                    })?;
+                    let mut status = self.status.lock().unwrap();
+                    status.state = ReplicaState::Streaming;
+                    if let Some(token) = token {
+                        status.applied_token = Some(token);
+                        status.primary_token = Some(status.primary_token.unwrap_or(0).max(token));
+                    }
+                }
+                Message::Heartbeat { token } => {
+                    let mut status = self.status.lock().unwrap();
+                    if status.state == ReplicaState::Connecting {
+                        status.state = ReplicaState::Streaming;
+                    }
+                    status.primary_token = Some(token);
+                }
+                message => return Err(Error::Replication(format!("unexpected message: {:?}", message))),
+            }
+        }
+        Ok(())
+    }
+
+}
+
+/// Apply the writes in one transaction of the replica.
+fn apply<F>(db: &DatabaseInner, f: F) -> Result<()>
+where
+    F: FnOnce(&TransactionInner) -> Result<()>,
+{
+    let mut txn = db.start_replication_transaction()?;
+    txn.set_auto_commit(false);
+    match f(&txn) {
+        Ok(()) => txn.commit(),
+        Err(err) => {
+            txn.rollback()?;
+            Err(err)
+        }
+    }
+}
+
+/// Write the document as it is on the primary, so a change applied twice,
+/// such as a change committed during the initial sync, leaves the same document.
+fn apply_change(db: &DatabaseInner, event: ChangeEvent, txn: &TransactionInner) -> Result<()> {
+    db.delete_one(&event.collection, doc! { "_id": event.document_key }, txn)?;
+    match (event.operation_type, event.full_document) {
+        (OperationType::Delete, _) => Ok(()),
+        (_, Some(doc)) => {
+            db.insert_one(&event.collection, doc, txn)?;
+            Ok(())
+        }
+        (_, None) => Err(Error::Replication(format!("the change of '{}' has no document", event.collection))),
+    }
+}
+
+fn replication_key() -> Result<Vec<u8>> {
+    crate::utils::bson::stacked_key([&Bson::String(REPLICATION_KEY.to_string())])
+}
+
+fn load_token(db: &DatabaseInner) -> Result<Option<u64>> {
+    let txn = db.start_replication_transaction()?;
+    let buf = match txn.rocksdb_txn.get(&replication_key()?)? {
+        Some(buf) => buf,
+        None => return Ok(None),
+    };
+    let doc = bson::from_slice::<Document>(&buf)?;
+    Ok(doc.get_i64("after").ok().map(|token| token as u64))
+}
+
+fn save_token(txn: &TransactionInner, token: u64) -> Result<()> {
+    let doc = doc! { "after": token as i64 };
+    txn.put(&replication_key()?, &bson::to_vec(&doc)?)
+}
+
+/// Connect to the primary listening at `addr`, see [`crate::Database::serve_replication`].
+pub(crate) fn tcp_connect(addr: impl ToSocketAddrs) -> Result<Connect> {
+    let addrs = addr.to_socket_addrs()?.collect::<Vec<_>>();
+    Ok(Box::new(move || {
+        let stream = TcpStream::connect(addrs.as_slice())?;
+        stream.set_read_timeout(Some(PRIMARY_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        Ok(Box::new(stream) as Box<dyn ReplicationTransport>)
+    }))
+}
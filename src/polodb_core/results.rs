@@ -16,6 +16,7 @@ use std::collections::{HashMap};
 use crate::bson::Bson;
 use serde::{Serialize, Serializer};
 use serde::ser::SerializeMap;
+use crate::Error;
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -30,6 +31,17 @@ pub struct InsertManyResult {
     /// The `_id` field of the documents inserted.
     #[serde(serialize_with = "map_serialize")]
     pub inserted_ids: HashMap<usize, Bson>,
+    /// The errors of the documents that failed in an unordered insert,
+    /// keyed by their index in the input.
+    #[serde(skip_serializing_if = "HashMap::is_empty", serialize_with = "errors_serialize")]
+    pub write_errors: HashMap<usize, Error>,
+}
+
+impl InsertManyResult {
+    /// Returns true if some documents of an unordered insert failed.
+    pub fn has_write_errors(&self) -> bool {
+        !self.write_errors.is_empty()
+    }
 }
 
 fn map_serialize<S>(data: &HashMap<usize, Bson>, serializer: S) -> Result<S::Ok, S::Error>
@@ -46,6 +58,19 @@ fn map_serialize<S>(data: &HashMap<usize, Bson>, serializer: S) -> Result<S::Ok,
     map.end()
 }
 
+fn errors_serialize<S>(data: &HashMap<usize, Error>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+{
+    let mut map = serializer.serialize_map(Some(data.len()))?;
+
+    for (index, err) in data {
+        map.serialize_entry(&index.to_string(), &err.to_string())?;
+    }
+
+    map.end()
+}
+
 #[derive(Debug, Serialize, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct UpdateResult {
@@ -70,6 +95,17 @@ pub struct CountDocumentsResult {
     pub count: u64,
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Page<T> {
+    /// The documents of the page.
+    pub items: Vec<T>,
+    /// The token to pass to `after` to get the next page,
+    /// `None` on the last page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_token: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use bson::doc;
@@ -82,7 +118,7 @@ mod tests {
         let mut inserted_ids: HashMap<usize, Bson> = HashMap::new() ;
         inserted_ids.insert(0, doc! {}.into());
 
-        let result = InsertManyResult { inserted_ids };
+        let result = InsertManyResult { inserted_ids, write_errors: HashMap::new() };
         let _bson_doc = bson::to_document(&result).unwrap();
         let bson_str = format!("{:?}", _bson_doc);
         assert_eq!(r#"Document({"insertedIds": Document({"0": Document({})})})"#, bson_str);
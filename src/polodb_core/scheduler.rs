@@ -0,0 +1,326 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+use bson::{doc, Bson, DateTime, Document};
+use crate::db::db_inner::DatabaseInner;
+use crate::{CollectionT, Error, Result, Transaction};
+
+/// How often the scheduler checks the jobs due, and whether it's stopped.
+const TICK: Duration = Duration::from_millis(10);
+
+type JobFn = Box<dyn Fn(&Transaction) -> Result<()> + Send + Sync>;
+
+/// The work of a scheduled job, run in a transaction: the writes of a run are
+/// committed together when it succeeds, and rolled back when it fails.
+pub struct Job {
+    run: JobFn,
+}
+
+impl Job {
+
+    /// A job running `f`, the transaction is committed if it returns `Ok`.
+    pub fn new<F>(f: F) -> Job
+    where
+        F: Fn(&Transaction) -> Result<()> + Send + Sync + 'static,
+    {
+        Job {
+            run: Box::new(f),
+        }
+    }
+
+    /// A job replacing the documents of `target` by the result of the
+    /// aggregation `pipeline` run on `source`, such as a rollup of the source.
+    pub fn aggregate_into(
+        source: impl Into<String>,
+        pipeline: impl IntoIterator<Item = Document>,
+        target: impl Into<String>,
+    ) -> Job {
+        let source = source.into();
+        let pipeline = pipeline.into_iter().collect::<Vec<Document>>();
+        let target = target.into();
+        Job::new(move |txn| {
+            let docs = txn.collection::<Document>(&source)
+                .aggregate(pipeline.clone())
+                .run()?
+                .collect::<Result<Vec<Document>>>()?;
+            let target = txn.collection::<Document>(&target);
+            target.delete_many(doc! {})?;
+            if !docs.is_empty() {
+                target.insert_many(docs)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// A job deleting the documents of `collection` whose datetime `field`
+    /// is older than `age` at the time of the run.
+    pub fn purge_older_than(collection: impl Into<String>, field: impl Into<String>, age: Duration) -> Job {
+        let collection = collection.into();
+        let field = field.into();
+        Job::new(move |txn| {
+            let before = SystemTime::now().checked_sub(age).unwrap_or(SystemTime::UNIX_EPOCH);
+            txn.collection::<Document>(&collection).delete_many(doc! {
+                field.as_str(): { "$lt": DateTime::from_system_time(before) },
+            })?;
+            Ok(())
+        })
+    }
+
+    /// A job deleting the expired documents of the collections created with an
+    /// `expire_at_field` or with a TTL index, see [`crate::IndexOptions::expire_after_secs`].
+    pub fn remove_expired() -> Job {
+        Job::new(|txn| {
+            txn.remove_expired_documents()?;
+            Ok(())
+        })
+    }
+
+}
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Job")
+    }
+}
+
+/// The state of a scheduled job, see [`Scheduler::jobs`].
+#[derive(Debug, Clone)]
+pub struct JobStatus {
+    pub name: String,
+    pub interval: Duration,
+    /// The number of runs, successful or not.
+    pub runs: u64,
+    /// The number of runs which failed and were rolled back.
+    pub failures: u64,
+    /// When the last run started.
+    pub last_run: Option<SystemTime>,
+    /// How long the last run took.
+    pub last_duration: Option<Duration>,
+    /// The error of the last run, if it failed.
+    pub last_error: Option<String>,
+    pub next_run: SystemTime,
+}
+
+struct ScheduledJob {
+    job: Job,
+    status: Mutex<JobStatus>,
+    /// Held while the job runs, a job never runs twice at the same time.
+    running: Mutex<()>,
+}
+
+impl ScheduledJob {
+
+    /// Run the job in its own transaction, record the outcome in its status.
+    fn run(&self, db: &Arc<DatabaseInner>) -> Result<()> {
+        let _running = self.running.lock()?;
+        let started_at = SystemTime::now();
+        let name = self.status.lock()?.name.clone();
+
+        let result = start_transaction(db, &name).and_then(|txn| {
+            match (self.job.run)(&txn) {
+                Ok(()) => txn.commit(),
+                Err(err) => {
+                    let _ = txn.rollback();
+                    Err(err)
+                }
+            }
+        });
+
+        let mut status = self.status.lock()?;
+        status.runs += 1;
+        status.last_run = Some(started_at);
+        status.last_duration = Some(started_at.elapsed().unwrap_or_default());
+        status.next_run = (started_at + status.interval).max(SystemTime::now());
+        status.last_error = match &result {
+            Ok(()) => None,
+            Err(err) => {
+                status.failures += 1;
+                crate::polo_log!("scheduled job '{}' failed: {}", name, err);
+                Some(err.to_string())
+            }
+        };
+        result
+    }
+
+}
+
+/// The transaction of a run of a job, listed in the current operations with the name of the job.
+fn start_transaction(db: &Arc<DatabaseInner>, name: &str) -> Result<Transaction> {
+    let mut inner = db.start_transaction()?;
+    inner.set_auto_commit(false);
+    let comment = Bson::String(name.to_string());
+    let guard = db.operations().register("transaction", None, None, Some(&comment), inner.kill_flag());
+    Ok(Transaction::new(Arc::downgrade(db), inner, guard))
+}
+
+type Jobs = Arc<Mutex<Vec<Arc<ScheduledJob>>>>;
+
+/// Runs recurring jobs on a background thread, see [`crate::Database::start_scheduler`].
+///
+/// Each run of a job is a transaction, committed when the job succeeds and rolled back
+/// when it fails; a failing job runs again at its next time. The jobs are kept in memory,
+/// they are scheduled again after the database is opened again. The scheduler stops
+/// when it's stopped or dropped, or when the database is closed.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use polodb_core::{Database, Job};
+/// use polodb_core::bson::doc;
+/// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-scheduler");
+/// let db = Database::open_path(db_path).unwrap();
+/// let scheduler = db.start_scheduler();
+/// scheduler.schedule("rollup", Duration::from_secs(300), Job::aggregate_into(
+///     "events",
+///     vec![doc! { "$count": "total" }],
+///     "event_counts",
+/// )).unwrap();
+/// scheduler.schedule("purge", Duration::from_secs(86400), Job::purge_older_than(
+///     "events",
+///     "created_at",
+///     Duration::from_secs(30 * 86400),
+/// )).unwrap();
+/// ```
+pub struct Scheduler {
+    db: Weak<DatabaseInner>,
+    jobs: Jobs,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Scheduler {
+
+    pub(crate) fn start(db: &Arc<DatabaseInner>) -> Scheduler {
+        let jobs: Jobs = Arc::new(Mutex::new(Vec::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let weak_db = Arc::downgrade(db);
+        let worker_jobs = jobs.clone();
+        let worker_stop = stop.clone();
+        let handle = std::thread::spawn(move || {
+            while !worker_stop.load(Ordering::SeqCst) {
+                let db = match weak_db.upgrade() {
+                    Some(db) => db,
+                    None => break,
+                };
+                let now = SystemTime::now();
+                let due = worker_jobs.lock().unwrap()
+                    .iter()
+                    .filter(|job| job.status.lock().unwrap().next_run <= now)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for job in due {
+                    if worker_stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    // the failure is kept in the status of the job
+                    let _ = job.run(&db);
+                }
+                drop(db);
+                std::thread::sleep(TICK);
+            }
+        });
+
+        Scheduler {
+            db: Arc::downgrade(db),
+            jobs,
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// Run `job` every `interval`, the first time one `interval` from now.
+    /// The job replaces the scheduled job with the same name.
+    pub fn schedule(&self, name: impl Into<String>, interval: Duration, job: Job) -> Result<()> {
+        if interval.is_zero() {
+            return Err(Error::ValidationError("the interval of a job must be greater than zero".into()));
+        }
+        let name = name.into();
+        let scheduled = Arc::new(ScheduledJob {
+            job,
+            status: Mutex::new(JobStatus {
+                name: name.clone(),
+                interval,
+                runs: 0,
+                failures: 0,
+                last_run: None,
+                last_duration: None,
+                last_error: None,
+                next_run: SystemTime::now() + interval,
+            }),
+            running: Mutex::new(()),
+        });
+        let mut jobs = self.jobs.lock()?;
+        jobs.retain(|job| job.status.lock().unwrap().name != name);
+        jobs.push(scheduled);
+        Ok(())
+    }
+
+    /// Remove the job `name`, return false if there is no such job.
+    /// A run in progress is finished.
+    pub fn unschedule(&self, name: &str) -> bool {
+        let mut jobs = self.jobs.lock().unwrap();
+        let count = jobs.len();
+        jobs.retain(|job| job.status.lock().unwrap().name != name);
+        jobs.len() != count
+    }
+
+    /// Run the job `name` now on the current thread, after its run in progress if any,
+    /// and return its result. The next run is one interval from now.
+    /// Return false if there is no such job.
+    pub fn run_now(&self, name: &str) -> Result<bool> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let job = self.jobs.lock()?
+            .iter()
+            .find(|job| job.status.lock().unwrap().name == name)
+            .cloned();
+        match job {
+            Some(job) => job.run(&db).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// The status of the scheduled jobs, in the order they were scheduled.
+    pub fn jobs(&self) -> Vec<JobStatus> {
+        self.jobs.lock().unwrap()
+            .iter()
+            .map(|job| job.status.lock().unwrap().clone())
+            .collect()
+    }
+
+    /// Stop running the jobs, after the run in progress.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+
+}
+
+impl Drop for Scheduler {
+
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+
+}
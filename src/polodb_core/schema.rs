@@ -0,0 +1,502 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! The subset of JSON Schema supported by `$jsonSchema`, following the draft 4
+//! keywords accepted by MongoDB, with `bsonType` to match the BSON types.
+
+use std::collections::HashSet;
+use bson::{Bson, Document};
+use bson::spec::ElementType;
+use regex::Regex;
+use crate::{Error, Result};
+
+const KEYWORDS: &[&str] = &[
+    "bsonType", "type", "enum", "title", "description",
+    "properties", "required", "additionalProperties", "patternProperties",
+    "minProperties", "maxProperties",
+    "minimum", "maximum", "exclusiveMinimum", "exclusiveMaximum", "multipleOf",
+    "minLength", "maxLength", "pattern",
+    "items", "additionalItems", "minItems", "maxItems", "uniqueItems",
+    "allOf", "anyOf", "oneOf", "not",
+];
+
+fn invalid(message: String) -> Error {
+    Error::InvalidJsonSchema(message)
+}
+
+/// Check that `schema` only uses the supported keywords, with values of the right type.
+pub(crate) fn check_schema(schema: &Document) -> Result<()> {
+    for (key, value) in schema {
+        if !KEYWORDS.contains(&key.as_str()) {
+            return Err(invalid(format!("keyword '{}' is not supported", key)));
+        }
+        match key.as_str() {
+            "bsonType" | "type" => {
+                let names: Vec<&Bson> = match value {
+                    Bson::Array(arr) => arr.iter().collect(),
+                    _ => vec![value],
+                };
+                for name in names {
+                    let name = name.as_str()
+                        .ok_or_else(|| invalid(format!("'{}' must be a string or an array of strings", key)))?;
+                    let known = if key == "bsonType" {
+                        bson_type_matcher(name).is_some()
+                    } else {
+                        json_type_matcher(name).is_some()
+                    };
+                    if !known {
+                        return Err(invalid(format!("unknown {} '{}'", key, name)));
+                    }
+                }
+            }
+            "enum" | "required" => {
+                let arr = value.as_array()
+                    .ok_or_else(|| invalid(format!("'{}' must be an array", key)))?;
+                if key == "required" && arr.iter().any(|item| item.as_str().is_none()) {
+                    return Err(invalid("'required' must be an array of strings".to_string()));
+                }
+            }
+            "title" | "description" => {
+                if value.as_str().is_none() {
+                    return Err(invalid(format!("'{}' must be a string", key)));
+                }
+            }
+            "properties" | "patternProperties" => {
+                let props = value.as_document()
+                    .ok_or_else(|| invalid(format!("'{}' must be an object", key)))?;
+                for (name, sub_schema) in props {
+                    if key == "patternProperties" {
+                        Regex::new(name).map_err(|err| invalid(err.to_string()))?;
+                    }
+                    check_sub_schema(key, sub_schema)?;
+                }
+            }
+            "additionalProperties" | "additionalItems" => {
+                if !matches!(value, Bson::Boolean(_)) {
+                    check_sub_schema(key, value)?;
+                }
+            }
+            "minProperties" | "maxProperties" | "minLength" | "maxLength" | "minItems" | "maxItems" => {
+                if as_number(value).is_none_or(|n| n < 0.0 || n.fract() != 0.0) {
+                    return Err(invalid(format!("'{}' must be a non-negative integer", key)));
+                }
+            }
+            "minimum" | "maximum" => {
+                if as_number(value).is_none() {
+                    return Err(invalid(format!("'{}' must be a number", key)));
+                }
+            }
+            "multipleOf" => {
+                if as_number(value).is_none_or(|n| n <= 0.0) {
+                    return Err(invalid("'multipleOf' must be a positive number".to_string()));
+                }
+            }
+            "exclusiveMinimum" | "exclusiveMaximum" | "uniqueItems" => {
+                if !matches!(value, Bson::Boolean(_)) {
+                    return Err(invalid(format!("'{}' must be a boolean", key)));
+                }
+            }
+            "pattern" => {
+                let pattern = value.as_str()
+                    .ok_or_else(|| invalid("'pattern' must be a string".to_string()))?;
+                Regex::new(pattern).map_err(|err| invalid(err.to_string()))?;
+            }
+            "items" => {
+                match value {
+                    Bson::Array(arr) => {
+                        for item in arr {
+                            check_sub_schema(key, item)?;
+                        }
+                    }
+                    _ => check_sub_schema(key, value)?,
+                }
+            }
+            "allOf" | "anyOf" | "oneOf" => {
+                let arr = value.as_array()
+                    .ok_or_else(|| invalid(format!("'{}' must be an array", key)))?;
+                if arr.is_empty() {
+                    return Err(invalid(format!("'{}' must not be empty", key)));
+                }
+                for item in arr {
+                    check_sub_schema(key, item)?;
+                }
+            }
+            "not" => check_sub_schema(key, value)?,
+            _ => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+fn check_sub_schema(key: &str, value: &Bson) -> Result<()> {
+    let schema = value.as_document()
+        .ok_or_else(|| invalid(format!("'{}' must contain schemas", key)))?;
+    check_schema(schema)
+}
+
+fn as_number(value: &Bson) -> Option<f64> {
+    match value {
+        Bson::Int32(n) => Some(*n as f64),
+        Bson::Int64(n) => Some(*n as f64),
+        Bson::Double(n) => Some(*n),
+        _ => None,
+    }
+}
+
+fn bson_type_matcher(name: &str) -> Option<fn(&Bson) -> bool> {
+    let matcher: fn(&Bson) -> bool = match name {
+        "double" => |v| v.element_type() == ElementType::Double,
+        "string" => |v| v.element_type() == ElementType::String,
+        "object" => |v| v.element_type() == ElementType::EmbeddedDocument,
+        "array" => |v| v.element_type() == ElementType::Array,
+        "binData" => |v| v.element_type() == ElementType::Binary,
+        "objectId" => |v| v.element_type() == ElementType::ObjectId,
+        "bool" => |v| v.element_type() == ElementType::Boolean,
+        "date" => |v| v.element_type() == ElementType::DateTime,
+        "null" => |v| v.element_type() == ElementType::Null,
+        "regex" => |v| v.element_type() == ElementType::RegularExpression,
+        "int" => |v| v.element_type() == ElementType::Int32,
+        "timestamp" => |v| v.element_type() == ElementType::Timestamp,
+        "long" => |v| v.element_type() == ElementType::Int64,
+        "decimal" => |v| v.element_type() == ElementType::Decimal128,
+        "number" => |v| matches!(v, Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_) | Bson::Decimal128(_)),
+        _ => return None,
+    };
+    Some(matcher)
+}
+
+fn json_type_matcher(name: &str) -> Option<fn(&Bson) -> bool> {
+    let matcher: fn(&Bson) -> bool = match name {
+        "object" => |v| matches!(v, Bson::Document(_)),
+        "array" => |v| matches!(v, Bson::Array(_)),
+        "number" => |v| matches!(v, Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_) | Bson::Decimal128(_)),
+        "boolean" => |v| matches!(v, Bson::Boolean(_)),
+        "string" => |v| matches!(v, Bson::String(_)),
+        "null" => |v| matches!(v, Bson::Null),
+        _ => return None,
+    };
+    Some(matcher)
+}
+
+type TypeMatcher = fn(&str) -> Option<fn(&Bson) -> bool>;
+
+fn type_matches(value: &Bson, names: &Bson, matcher: TypeMatcher) -> bool {
+    let matches_name = |name: &Bson| {
+        name.as_str()
+            .and_then(matcher)
+            .is_some_and(|matcher| matcher(value))
+    };
+    match names {
+        Bson::Array(arr) => arr.iter().any(matches_name),
+        _ => matches_name(names),
+    }
+}
+
+fn child_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "the document"
+    } else {
+        path
+    }
+}
+
+fn fail<T>(path: &str, reason: String) -> std::result::Result<T, String> {
+    Err(format!("{}: {}", display_path(path), reason))
+}
+
+/// Validate `value` against a schema checked by [`check_schema`],
+/// return the reason of the first mismatch.
+pub(crate) fn validate(schema: &Document, value: &Bson) -> std::result::Result<(), String> {
+    validate_at(schema, value, "")
+}
+
+fn validate_at(schema: &Document, value: &Bson, path: &str) -> std::result::Result<(), String> {
+    if let Some(names) = schema.get("bsonType") {
+        if !type_matches(value, names, bson_type_matcher) {
+            return fail(path, format!("expected bsonType {}, found {:?}", names, value.element_type()));
+        }
+    }
+    if let Some(names) = schema.get("type") {
+        if !type_matches(value, names, json_type_matcher) {
+            return fail(path, format!("expected type {}, found {:?}", names, value.element_type()));
+        }
+    }
+    if let Ok(values) = schema.get_array("enum") {
+        if !values.contains(value) {
+            return fail(path, format!("{} is not one of the enum values", value));
+        }
+    }
+
+    if let Bson::Document(doc) = value {
+        validate_object(schema, doc, path)?;
+    }
+    if let Some(n) = as_number(value) {
+        validate_number(schema, n, path)?;
+    }
+    if let Bson::String(s) = value {
+        validate_string(schema, s, path)?;
+    }
+    if let Bson::Array(arr) = value {
+        validate_array(schema, arr, path)?;
+    }
+
+    if let Ok(schemas) = schema.get_array("allOf") {
+        for sub_schema in schemas.iter().filter_map(Bson::as_document) {
+            validate_at(sub_schema, value, path)?;
+        }
+    }
+    if let Ok(schemas) = schema.get_array("anyOf") {
+        let any = schemas.iter()
+            .filter_map(Bson::as_document)
+            .any(|sub_schema| validate_at(sub_schema, value, path).is_ok());
+        if !any {
+            return fail(path, "does not match any schema of anyOf".to_string());
+        }
+    }
+    if let Ok(schemas) = schema.get_array("oneOf") {
+        let count = schemas.iter()
+            .filter_map(Bson::as_document)
+            .filter(|sub_schema| validate_at(sub_schema, value, path).is_ok())
+            .count();
+        if count != 1 {
+            return fail(path, format!("matches {} schemas of oneOf instead of exactly one", count));
+        }
+    }
+    if let Ok(sub_schema) = schema.get_document("not") {
+        if validate_at(sub_schema, value, path).is_ok() {
+            return fail(path, "matches the schema of not".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_object(schema: &Document, doc: &Document, path: &str) -> std::result::Result<(), String> {
+    if let Ok(required) = schema.get_array("required") {
+        for name in required.iter().filter_map(Bson::as_str) {
+            if !doc.contains_key(name) {
+                return fail(path, format!("missing required field '{}'", name));
+            }
+        }
+    }
+    if let Some(n) = schema.get("minProperties").and_then(as_number) {
+        if (doc.len() as f64) < n {
+            return fail(path, format!("has fewer than {} fields", n));
+        }
+    }
+    if let Some(n) = schema.get("maxProperties").and_then(as_number) {
+        if (doc.len() as f64) > n {
+            return fail(path, format!("has more than {} fields", n));
+        }
+    }
+
+    let properties = schema.get_document("properties").ok();
+    let pattern_properties: Vec<(Regex, &Document)> = match schema.get_document("patternProperties") {
+        Ok(props) => props.iter()
+            .filter_map(|(pattern, sub_schema)| {
+                Some((Regex::new(pattern).ok()?, sub_schema.as_document()?))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    for (key, field) in doc {
+        let field_path = child_path(path, key);
+        let mut matched = false;
+        if let Some(sub_schema) = properties.and_then(|props| props.get_document(key).ok()) {
+            matched = true;
+            validate_at(sub_schema, field, &field_path)?;
+        }
+        for (regex, sub_schema) in &pattern_properties {
+            if regex.is_match(key) {
+                matched = true;
+                validate_at(sub_schema, field, &field_path)?;
+            }
+        }
+        if matched {
+            continue;
+        }
+        match schema.get("additionalProperties") {
+            Some(Bson::Boolean(false)) => {
+                return fail(path, format!("additional field '{}' is not allowed", key));
+            }
+            Some(Bson::Document(sub_schema)) => validate_at(sub_schema, field, &field_path)?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn validate_number(schema: &Document, n: f64, path: &str) -> std::result::Result<(), String> {
+    if let Some(min) = schema.get("minimum").and_then(as_number) {
+        let exclusive = schema.get_bool("exclusiveMinimum").unwrap_or(false);
+        if n < min || (exclusive && n == min) {
+            return fail(path, format!("{} is less than the minimum {}", n, min));
+        }
+    }
+    if let Some(max) = schema.get("maximum").and_then(as_number) {
+        let exclusive = schema.get_bool("exclusiveMaximum").unwrap_or(false);
+        if n > max || (exclusive && n == max) {
+            return fail(path, format!("{} is greater than the maximum {}", n, max));
+        }
+    }
+    if let Some(divisor) = schema.get("multipleOf").and_then(as_number) {
+        if (n / divisor).fract() != 0.0 {
+            return fail(path, format!("{} is not a multiple of {}", n, divisor));
+        }
+    }
+    Ok(())
+}
+
+fn validate_string(schema: &Document, s: &str, path: &str) -> std::result::Result<(), String> {
+    let len = s.chars().count() as f64;
+    if let Some(min) = schema.get("minLength").and_then(as_number) {
+        if len < min {
+            return fail(path, format!("is shorter than {} characters", min));
+        }
+    }
+    if let Some(max) = schema.get("maxLength").and_then(as_number) {
+        if len > max {
+            return fail(path, format!("is longer than {} characters", max));
+        }
+    }
+    if let Ok(pattern) = schema.get_str("pattern") {
+        if let Ok(regex) = Regex::new(pattern) {
+            if !regex.is_match(s) {
+                return fail(path, format!("does not match the pattern '{}'", pattern));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_array(schema: &Document, arr: &[Bson], path: &str) -> std::result::Result<(), String> {
+    if let Some(min) = schema.get("minItems").and_then(as_number) {
+        if (arr.len() as f64) < min {
+            return fail(path, format!("has fewer than {} items", min));
+        }
+    }
+    if let Some(max) = schema.get("maxItems").and_then(as_number) {
+        if (arr.len() as f64) > max {
+            return fail(path, format!("has more than {} items", max));
+        }
+    }
+    if schema.get_bool("uniqueItems").unwrap_or(false) {
+        let mut seen = HashSet::new();
+        for item in arr {
+            let key = bson::to_vec(&bson::doc! { "v": item.clone() }).unwrap_or_default();
+            if !seen.insert(key) {
+                return fail(path, format!("the item {} is duplicated", item));
+            }
+        }
+    }
+    match schema.get("items") {
+        Some(Bson::Document(sub_schema)) => {
+            for (index, item) in arr.iter().enumerate() {
+                validate_at(sub_schema, item, &child_path(path, &index.to_string()))?;
+            }
+        }
+        Some(Bson::Array(schemas)) => {
+            for (index, item) in arr.iter().enumerate() {
+                let item_path = child_path(path, &index.to_string());
+                match schemas.get(index) {
+                    Some(Bson::Document(sub_schema)) => validate_at(sub_schema, item, &item_path)?,
+                    Some(_) => (),
+                    None => match schema.get("additionalItems") {
+                        Some(Bson::Boolean(false)) => {
+                            return fail(path, format!("has more than {} items", schemas.len()));
+                        }
+                        Some(Bson::Document(sub_schema)) => validate_at(sub_schema, item, &item_path)?,
+                        _ => (),
+                    },
+                }
+            }
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{doc, Bson};
+    use super::{check_schema, validate};
+
+    #[test]
+    fn test_check_schema() {
+        assert!(check_schema(&doc! {
+            "bsonType": "object",
+            "required": ["name"],
+            "properties": {
+                "name": { "bsonType": "string", "minLength": 1 },
+                "tags": { "bsonType": "array", "items": { "bsonType": "string" } },
+            },
+        }).is_ok());
+        assert!(check_schema(&doc! { "bsonType": "integer" }).is_err());
+        assert!(check_schema(&doc! { "format": "email" }).is_err());
+        assert!(check_schema(&doc! { "properties": { "a": 1 } }).is_err());
+        assert!(check_schema(&doc! { "pattern": "(" }).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = doc! {
+            "bsonType": "object",
+            "required": ["name", "age"],
+            "additionalProperties": false,
+            "properties": {
+                "_id": {},
+                "name": { "bsonType": "string", "pattern": "^[A-Z]" },
+                "age": { "bsonType": ["int", "long"], "minimum": 0, "maximum": 150 },
+                "tags": { "bsonType": "array", "uniqueItems": true, "items": { "enum": ["a", "b"] } },
+            },
+        };
+        let check = |value: Bson| validate(&schema, &value);
+        assert!(check(Bson::Document(doc! { "_id": 1, "name": "Ann", "age": 3 })).is_ok());
+        assert!(check(Bson::Document(doc! { "name": "Ann", "age": 3, "tags": ["a", "b"] })).is_ok());
+
+        let err = check(Bson::Document(doc! { "name": "Ann" })).unwrap_err();
+        assert_eq!(err, "the document: missing required field 'age'");
+        let err = check(Bson::Document(doc! { "name": "ann", "age": 3 })).unwrap_err();
+        assert_eq!(err, "name: does not match the pattern '^[A-Z]'");
+        assert!(check(Bson::Document(doc! { "name": "Ann", "age": 3.5 })).is_err());
+        assert!(check(Bson::Document(doc! { "name": "Ann", "age": 200 })).is_err());
+        assert!(check(Bson::Document(doc! { "name": "Ann", "age": 3, "other": 1 })).is_err());
+        let err = check(Bson::Document(doc! { "name": "Ann", "age": 3, "tags": ["a", "c"] })).unwrap_err();
+        assert_eq!(err, "tags.1: \"c\" is not one of the enum values");
+        assert!(check(Bson::Document(doc! { "name": "Ann", "age": 3, "tags": ["a", "a"] })).is_err());
+    }
+
+    #[test]
+    fn test_combinators() {
+        let schema = doc! {
+            "oneOf": [
+                { "bsonType": "string" },
+                { "bsonType": "int", "not": { "enum": [0] } },
+            ],
+        };
+        assert!(validate(&schema, &Bson::String("x".into())).is_ok());
+        assert!(validate(&schema, &Bson::Int32(1)).is_ok());
+        assert!(validate(&schema, &Bson::Int32(0)).is_err());
+        assert!(validate(&schema, &Bson::Boolean(true)).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The snapshot files written by [`crate::Database::export_snapshot`].
+//!
+//! A snapshot starts with a header, the magic bytes and the version of the format,
+//! followed by the keys and the values of the database in the snappy framing format,
+//! whose chunks are checksummed. The keys end with a marker and their count, so a
+//! truncated snapshot is detected.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use bson::oid::ObjectId;
+use crate::db::RocksDBWrapper;
+use crate::transaction::TransactionInner;
+use crate::{Config, Error, Result};
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"POLOSNAP";
+const SNAPSHOT_VERSION: u32 = 1;
+/// The length written instead of the length of a key after the last key.
+const END_MARKER: u32 = u32::MAX;
+/// The number of keys loaded by a transaction when a snapshot is opened.
+const LOAD_BATCH_SIZE: u64 = 4096;
+
+/// Write the keys read by `txn` to the snapshot file `path`. The file is written
+/// next to `path` and renamed once complete, a snapshot is never seen half written.
+pub(crate) fn export(txn: &TransactionInner, path: &Path) -> Result<()> {
+    let partial = partial_path(path);
+    if let Err(err) = write_snapshot(txn, &partial) {
+        let _ = std::fs::remove_file(&partial);
+        return Err(err);
+    }
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    path.with_file_name(name)
+}
+
+fn write_snapshot(txn: &TransactionInner, path: &Path) -> Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(SNAPSHOT_MAGIC)?;
+    file.write_u32::<LittleEndian>(SNAPSHOT_VERSION)?;
+
+    let mut writer = snap::write::FrameEncoder::new(file);
+    // the iterator reads the keys as they are when it's created
+    let iter = txn.rocksdb_txn.new_iterator();
+    iter.seek_to_first();
+    let mut key = Vec::new();
+    let mut value = Vec::new();
+    let mut count = 0_u64;
+    while iter.valid() {
+        iter.copy_key_into(&mut key)?;
+        iter.copy_data_into(&mut value)?;
+        writer.write_u32::<LittleEndian>(key.len() as u32)?;
+        writer.write_all(&key)?;
+        writer.write_u32::<LittleEndian>(value.len() as u32)?;
+        writer.write_all(&value)?;
+        count += 1;
+        iter.next();
+    }
+    iter.error()?;
+    writer.write_u32::<LittleEndian>(END_MARKER)?;
+    writer.write_u64::<LittleEndian>(count)?;
+
+    let file = writer.into_inner().map_err(|err| Error::from(err.into_error()))?;
+    let file = file.into_inner().map_err(|err| Error::from(err.into_error()))?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Load the snapshot file `path` into a new store at `target`. The content
+/// of the snapshot is checked as it's read, the store is incomplete on error.
+pub(crate) fn load(path: &Path, target: &Path, config: &Config) -> Result<()> {
+    let invalid = |reason: &str| Error::InvalidSnapshot(format!("{}: {}", path.display(), reason));
+    let read_err = |err: std::io::Error| match err.kind() {
+        ErrorKind::UnexpectedEof => invalid("truncated"),
+        _ => invalid(&err.to_string()),
+    };
+    let mut file = BufReader::new(File::open(path)?);
+    let mut magic = [0_u8; 8];
+    if file.read_exact(&mut magic).is_err() || &magic != SNAPSHOT_MAGIC {
+        return Err(invalid("not a snapshot"));
+    }
+    let version = file.read_u32::<LittleEndian>().map_err(|_| invalid("not a snapshot"))?;
+    if version > SNAPSHOT_VERSION {
+        return Err(invalid(&format!("unsupported version {}", version)));
+    }
+
+    // a chunk whose checksum doesn't match fails the read
+    let mut reader = snap::read::FrameDecoder::new(file);
+    let store = RocksDBWrapper::open_with_config(target, config)?;
+    let mut txn = store.begin_transaction()?;
+    let mut count = 0_u64;
+    let mut batched = 0_u64;
+    loop {
+        let key_len = reader.read_u32::<LittleEndian>().map_err(read_err)?;
+        if key_len == END_MARKER {
+            break;
+        }
+        let key = read_bytes(&mut reader, key_len).map_err(read_err)?;
+        let value_len = reader.read_u32::<LittleEndian>().map_err(read_err)?;
+        let value = read_bytes(&mut reader, value_len).map_err(read_err)?;
+        txn.set(&key, &value)?;
+        count += 1;
+        batched += 1;
+        if batched == LOAD_BATCH_SIZE {
+            batched = 0;
+            txn.commit()?;
+            txn = store.begin_transaction()?;
+        }
+    }
+    let expected = reader.read_u64::<LittleEndian>().map_err(read_err)?;
+    let mut rest = [0_u8; 1];
+    if expected != count || !matches!(reader.read(&mut rest), Ok(0)) {
+        return Err(invalid("the count of the keys doesn't match"));
+    }
+    txn.commit()?;
+    Ok(())
+}
+
+fn read_bytes(reader: &mut impl Read, len: u32) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![0_u8; len as usize];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// The directory of the store holding the keys of an opened snapshot,
+/// removed once the database is closed.
+pub(crate) struct SnapshotDir {
+    path: PathBuf,
+}
+
+impl SnapshotDir {
+
+    pub(crate) fn new() -> SnapshotDir {
+        SnapshotDir {
+            path: std::env::temp_dir().join(format!("polodb-snapshot-{}", ObjectId::new().to_hex())),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+}
+
+impl Drop for SnapshotDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
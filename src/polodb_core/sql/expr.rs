@@ -0,0 +1,262 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! Translate SQL expressions into query documents.
+
+use bson::{doc, Bson, Document, Regex};
+use sqlparser::ast::{BinaryOperator, Expr, Ident, UnaryOperator, Value};
+use crate::{Error, Result};
+
+pub(super) fn sql_error(msg: impl Into<String>) -> Error {
+    Error::SqlError(msg.into())
+}
+
+/// The names a table is referred by in a statement.
+pub(super) struct TableScope {
+    /// The table name or the alias of the queried table.
+    pub(super) main: String,
+    /// The names of the joined tables, their documents are
+    /// embedded in the rows under these names.
+    pub(super) joined: Vec<String>,
+}
+
+pub(super) enum ColumnRef {
+    Main(String),
+    Joined(String),
+}
+
+impl TableScope {
+
+    pub(super) fn new(main: String) -> TableScope {
+        TableScope {
+            main,
+            joined: Vec::new(),
+        }
+    }
+
+    /// Resolve a column expression into a field path.
+    pub(super) fn column(&self, expr: &Expr) -> Option<ColumnRef> {
+        match expr {
+            Expr::Identifier(ident) => Some(ColumnRef::Main(ident.value.clone())),
+            Expr::CompoundIdentifier(idents) => {
+                let first = &idents[0].value;
+                if idents.len() > 1 && *first == self.main {
+                    Some(ColumnRef::Main(join_idents(&idents[1..])))
+                } else if self.joined.contains(first) {
+                    Some(ColumnRef::Joined(join_idents(idents)))
+                } else {
+                    Some(ColumnRef::Main(join_idents(idents)))
+                }
+            }
+            Expr::Nested(expr) => self.column(expr),
+            _ => None,
+        }
+    }
+
+    /// Resolve a column of the queried table, columns of the joined tables are rejected.
+    pub(super) fn main_column(&self, expr: &Expr) -> Result<String> {
+        match self.column(expr) {
+            Some(ColumnRef::Main(path)) => Ok(path),
+            Some(ColumnRef::Joined(path)) => Err(sql_error(format!(
+                "column of joined table is not allowed here: {}", path
+            ))),
+            None => Err(sql_error(format!("expected a column, got: {}", expr))),
+        }
+    }
+
+}
+
+pub(super) fn join_idents(idents: &[Ident]) -> String {
+    idents.iter().map(|ident| ident.value.as_str()).collect::<Vec<&str>>().join(".")
+}
+
+fn number_to_bson(s: &str, negative: bool) -> Result<Bson> {
+    let s = if negative { format!("-{}", s) } else { s.to_string() };
+    if let Ok(i) = s.parse::<i64>() {
+        return Ok(Bson::Int64(i));
+    }
+    s.parse::<f64>()
+        .map(Bson::Double)
+        .map_err(|_| sql_error(format!("invalid number: {}", s)))
+}
+
+/// Convert a literal expression into a BSON value.
+pub(super) fn literal(expr: &Expr) -> Result<Bson> {
+    match expr {
+        Expr::Value(value) => match value {
+            Value::Number(s, _) => number_to_bson(s, false),
+            Value::SingleQuotedString(s) | Value::DoubleQuotedString(s) => Ok(Bson::String(s.clone())),
+            Value::Boolean(b) => Ok(Bson::Boolean(*b)),
+            Value::Null => Ok(Bson::Null),
+            other => Err(sql_error(format!("unsupported value: {}", other))),
+        },
+        Expr::UnaryOp { op: UnaryOperator::Minus, expr } => match expr.as_ref() {
+            Expr::Value(Value::Number(s, _)) => number_to_bson(s, true),
+            other => Err(sql_error(format!("expected a number, got: {}", other))),
+        },
+        Expr::Nested(expr) => literal(expr),
+        other => Err(sql_error(format!("expected a literal value, got: {}", other))),
+    }
+}
+
+fn comparison_op(op: &BinaryOperator) -> Option<&'static str> {
+    let name = match op {
+        BinaryOperator::Eq => "$eq",
+        BinaryOperator::NotEq => "$ne",
+        BinaryOperator::Gt => "$gt",
+        BinaryOperator::GtEq => "$gte",
+        BinaryOperator::Lt => "$lt",
+        BinaryOperator::LtEq => "$lte",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// The operator when the operands are swapped, `1 < a` is `a > 1`.
+fn flip_op(op: &'static str) -> &'static str {
+    match op {
+        "$gt" => "$lt",
+        "$gte" => "$lte",
+        "$lt" => "$gt",
+        "$lte" => "$gte",
+        other => other,
+    }
+}
+
+fn negate_op(op: &'static str) -> &'static str {
+    match op {
+        "$eq" => "$ne",
+        "$ne" => "$eq",
+        "$gt" => "$lte",
+        "$gte" => "$lt",
+        "$lt" => "$gte",
+        "$lte" => "$gt",
+        other => other,
+    }
+}
+
+/// Convert a SQL `LIKE` pattern into a regular expression.
+fn like_to_regex(pattern: &str) -> String {
+    let mut result = String::with_capacity(pattern.len() + 2);
+    result.push('^');
+    for ch in pattern.chars() {
+        match ch {
+            '%' => result.push_str(".*"),
+            '_' => result.push('.'),
+            _ => result.push_str(&regex::escape(&ch.to_string())),
+        }
+    }
+    result.push('$');
+    result
+}
+
+fn flatten_logic(key: &str, left: Document, right: Document) -> Document {
+    let mut items = Vec::<Bson>::new();
+    for doc in [left, right] {
+        match doc.get_array(key) {
+            Ok(arr) if doc.len() == 1 => items.extend(arr.iter().cloned()),
+            _ => items.push(Bson::Document(doc)),
+        }
+    }
+    doc! { key: items }
+}
+
+fn comparison(scope: &TableScope, left: &Expr, op: &'static str, right: &Expr, negated: bool) -> Result<Document> {
+    let (path, op, value) = match (scope.column(left), scope.column(right)) {
+        (Some(_), None) => (scope.main_column(left)?, op, literal(right)?),
+        (None, Some(_)) => (scope.main_column(right)?, flip_op(op), literal(left)?),
+        _ => return Err(sql_error(format!(
+            "comparison must be between a column and a value: {} {}", left, right
+        ))),
+    };
+    let op = if negated { negate_op(op) } else { op };
+    Ok(doc! { path: { op: value } })
+}
+
+/// Translate the `WHERE` clause into a query document.
+pub(super) fn filter(scope: &TableScope, expr: &Expr) -> Result<Document> {
+    translate(scope, expr, false)
+}
+
+fn translate(scope: &TableScope, expr: &Expr, negated: bool) -> Result<Document> {
+    match expr {
+        Expr::Nested(expr) => translate(scope, expr, negated),
+        Expr::UnaryOp { op: UnaryOperator::Not, expr } => translate(scope, expr, !negated),
+        Expr::BinaryOp { left, op: BinaryOperator::And, right } => {
+            let left = translate(scope, left, negated)?;
+            let right = translate(scope, right, negated)?;
+            // De Morgan's laws: NOT (a AND b) is (NOT a) OR (NOT b)
+            Ok(flatten_logic(if negated { "$or" } else { "$and" }, left, right))
+        }
+        Expr::BinaryOp { left, op: BinaryOperator::Or, right } => {
+            let left = translate(scope, left, negated)?;
+            let right = translate(scope, right, negated)?;
+            Ok(flatten_logic(if negated { "$and" } else { "$or" }, left, right))
+        }
+        Expr::BinaryOp { left, op, right } => {
+            let op = comparison_op(op).ok_or_else(|| sql_error(format!("unsupported operator: {}", op)))?;
+            comparison(scope, left, op, right, negated)
+        }
+        Expr::InList { expr, list, negated: not_in } => {
+            let path = scope.main_column(expr)?;
+            let values = list.iter().map(literal).collect::<Result<Vec<Bson>>>()?;
+            let op = if *not_in != negated { "$nin" } else { "$in" };
+            Ok(doc! { path: { op: values } })
+        }
+        Expr::Between { expr, negated: not_between, low, high } => {
+            let path = scope.main_column(expr)?;
+            let low = literal(low)?;
+            let high = literal(high)?;
+            if *not_between != negated {
+                Ok(doc! { "$or": [
+                    { path.clone(): { "$lt": low } },
+                    { path: { "$gt": high } },
+                ] })
+            } else {
+                Ok(doc! { path: { "$gte": low, "$lte": high } })
+            }
+        }
+        Expr::IsNull(expr) => {
+            let path = scope.main_column(expr)?;
+            let op = if negated { "$ne" } else { "$eq" };
+            Ok(doc! { path: { op: Bson::Null } })
+        }
+        Expr::IsNotNull(expr) => {
+            let path = scope.main_column(expr)?;
+            let op = if negated { "$eq" } else { "$ne" };
+            Ok(doc! { path: { op: Bson::Null } })
+        }
+        Expr::Like { negated: not_like, expr, pattern, escape_char, .. } => {
+            if escape_char.is_some() {
+                return Err(sql_error("ESCAPE is not supported in LIKE"));
+            }
+            let path = scope.main_column(expr)?;
+            let pattern = match literal(pattern)? {
+                Bson::String(s) => s,
+                other => return Err(sql_error(format!("LIKE pattern must be a string, got: {}", other))),
+            };
+            let regex = Bson::RegularExpression(Regex {
+                pattern: like_to_regex(&pattern),
+                options: String::new(),
+            });
+            if *not_like != negated {
+                Ok(doc! { path: { "$not": { "$regex": regex } } })
+            } else {
+                Ok(doc! { path: { "$regex": regex } })
+            }
+        }
+        other => Err(sql_error(format!("unsupported expression: {}", other))),
+    }
+}
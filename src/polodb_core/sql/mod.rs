@@ -0,0 +1,388 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! A SQL layer translating a subset of SQL into the document operations.
+//!
+//! Supported statements:
+//!
+//! - `SELECT` with `WHERE`, `ORDER BY`, `LIMIT`, `OFFSET`, `COUNT(*)`,
+//!   and `[LEFT] JOIN` on the `_id` of the joined table
+//! - `INSERT INTO ... VALUES`
+//! - `UPDATE ... SET ... WHERE`, `SET a = a + 1` is translated into `$inc`
+//! - `DELETE FROM ... WHERE`
+//!
+//! A table is a collection, and a column is a field path, `address.city` refers to
+//! a nested field. The document of a joined table is embedded in the row under
+//! the name (or alias) of the table.
+
+mod expr;
+
+use bson::{doc, Bson, Document};
+use sqlparser::ast::{
+    Assignment, AssignmentTarget, BinaryOperator, Expr, FromTable, GroupByExpr, Join,
+    JoinConstraint, JoinOperator, ObjectName, Query, SelectItem, SetExpr, Statement,
+    TableFactor, TableWithJoins,
+};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::Parser;
+use crate::results::{DeleteResult, InsertManyResult, UpdateResult};
+use crate::utils::bson::try_get_document_value;
+use crate::{CollectionT, Result, Transaction};
+use expr::{filter, join_idents, literal, sql_error, ColumnRef, TableScope};
+
+/// The result of a SQL statement.
+#[derive(Debug)]
+pub enum SqlResult {
+    Rows(Vec<Document>),
+    Insert(InsertManyResult),
+    Update(UpdateResult),
+    Delete(DeleteResult),
+}
+
+pub(crate) fn execute(txn: &Transaction, sql: &str) -> Result<SqlResult> {
+    let mut statements = Parser::parse_sql(&GenericDialect {}, sql)
+        .map_err(|err| sql_error(err.to_string()))?;
+    if statements.len() != 1 {
+        return Err(sql_error(format!("expected one statement, got {}", statements.len())));
+    }
+    match statements.remove(0) {
+        Statement::Query(query) => execute_query(txn, &query).map(SqlResult::Rows),
+        Statement::Insert(insert) => {
+            if insert.returning.is_some() || insert.on.is_some() {
+                return Err(sql_error("RETURNING and ON CONFLICT are not supported"));
+            }
+            let source = insert.source.ok_or_else(|| sql_error("INSERT requires VALUES"))?;
+            execute_insert(txn, &insert.table_name, &insert.columns, &source).map(SqlResult::Insert)
+        }
+        Statement::Update { table, assignments, from, selection, returning, .. } => {
+            if from.is_some() || returning.is_some() {
+                return Err(sql_error("UPDATE ... FROM and RETURNING are not supported"));
+            }
+            execute_update(txn, &table, &assignments, selection.as_ref()).map(SqlResult::Update)
+        }
+        Statement::Delete(delete) => {
+            if delete.using.is_some() || delete.returning.is_some() || !delete.order_by.is_empty() {
+                return Err(sql_error("USING, RETURNING and ORDER BY are not supported in DELETE"));
+            }
+            let tables = match &delete.from {
+                FromTable::WithFromKeyword(tables) | FromTable::WithoutKeyword(tables) => tables,
+            };
+            let limit = delete.limit.as_ref().map(literal_u64).transpose()?;
+            execute_delete(txn, tables, delete.selection.as_ref(), limit).map(SqlResult::Delete)
+        }
+        other => Err(sql_error(format!("unsupported statement: {}", other))),
+    }
+}
+
+fn literal_u64(expr: &Expr) -> Result<u64> {
+    match literal(expr)? {
+        Bson::Int64(v) if v >= 0 => Ok(v as u64),
+        other => Err(sql_error(format!("expected a non-negative integer, got: {}", other))),
+    }
+}
+
+/// Return the collection name and the name the table is referred by.
+fn table_name(factor: &TableFactor) -> Result<(String, String)> {
+    match factor {
+        TableFactor::Table { name, alias, .. } => {
+            let col_name = object_name(name);
+            let scope_name = alias.as_ref().map(|alias| alias.name.value.clone()).unwrap_or_else(|| col_name.clone());
+            Ok((col_name, scope_name))
+        }
+        other => Err(sql_error(format!("unsupported table: {}", other))),
+    }
+}
+
+fn object_name(name: &ObjectName) -> String {
+    join_idents(&name.0)
+}
+
+fn single_table(tables: &[TableWithJoins]) -> Result<(String, String)> {
+    match tables {
+        [table] if table.joins.is_empty() => table_name(&table.relation),
+        _ => Err(sql_error("only one table is supported")),
+    }
+}
+
+fn where_filter(scope: &TableScope, selection: Option<&Expr>) -> Result<Document> {
+    match selection {
+        Some(expr) => filter(scope, expr),
+        None => Ok(Document::new()),
+    }
+}
+
+struct JoinPlan {
+    col_name: String,
+    scope_name: String,
+    local_path: String,
+    outer: bool,
+}
+
+fn join_plan(scope: &TableScope, join: &Join) -> Result<JoinPlan> {
+    let (col_name, scope_name) = table_name(&join.relation)?;
+    let (constraint, outer) = match &join.join_operator {
+        JoinOperator::Inner(constraint) => (constraint, false),
+        JoinOperator::LeftOuter(constraint) => (constraint, true),
+        other => return Err(sql_error(format!("unsupported join: {:?}", other))),
+    };
+    let (left, right) = match constraint {
+        JoinConstraint::On(Expr::BinaryOp { left, op: BinaryOperator::Eq, right }) => (left, right),
+        _ => return Err(sql_error("JOIN requires ON with an equality")),
+    };
+
+    let joined_id = format!("{}._id", scope_name);
+    let local = match (scope.column(left), scope.column(right)) {
+        (Some(ColumnRef::Joined(path)), Some(ColumnRef::Main(local))) |
+        (Some(ColumnRef::Main(local)), Some(ColumnRef::Joined(path))) if path == joined_id => local,
+        _ => return Err(sql_error(format!("JOIN must be on {}", joined_id))),
+    };
+
+    Ok(JoinPlan {
+        col_name,
+        scope_name,
+        local_path: local,
+        outer,
+    })
+}
+
+fn is_count_star(expr: &Expr) -> bool {
+    match expr {
+        Expr::Function(func) => func.to_string().eq_ignore_ascii_case("count(*)"),
+        _ => false,
+    }
+}
+
+fn execute_query(txn: &Transaction, query: &Query) -> Result<Vec<Document>> {
+    if query.with.is_some() || query.fetch.is_some() {
+        return Err(sql_error("WITH and FETCH are not supported"));
+    }
+    let select = match query.body.as_ref() {
+        SetExpr::Select(select) => select,
+        other => return Err(sql_error(format!("unsupported query: {}", other))),
+    };
+    if select.distinct.is_some() || select.having.is_some() {
+        return Err(sql_error("DISTINCT and HAVING are not supported"));
+    }
+    if !matches!(&select.group_by, GroupByExpr::Expressions(exprs, _) if exprs.is_empty()) {
+        return Err(sql_error("GROUP BY is not supported"));
+    }
+    let table = match select.from.as_slice() {
+        [table] => table,
+        _ => return Err(sql_error("only one table is supported in FROM, use JOIN instead")),
+    };
+
+    let (col_name, scope_name) = table_name(&table.relation)?;
+    let mut scope = TableScope::new(scope_name);
+    for join in &table.joins {
+        let (_, joined_name) = table_name(&join.relation)?;
+        scope.joined.push(joined_name);
+    }
+    let joins = table.joins
+        .iter()
+        .map(|join| join_plan(&scope, join))
+        .collect::<Result<Vec<JoinPlan>>>()?;
+
+    let query_filter = where_filter(&scope, select.selection.as_ref())?;
+    let collection = txn.collection::<Document>(&col_name);
+
+    if let [SelectItem::UnnamedExpr(expr)] = select.projection.as_slice() {
+        if is_count_star(expr) {
+            if !joins.is_empty() {
+                return Err(sql_error("COUNT(*) with JOIN is not supported"));
+            }
+            let count = collection.find(query_filter).run()?.count() as i64;
+            return Ok(vec![doc! { "count": count }]);
+        }
+    }
+
+    let mut find = collection.find(query_filter);
+    if let Some(order_by) = &query.order_by {
+        let mut sort = Document::new();
+        for item in &order_by.exprs {
+            let path = scope.main_column(&item.expr)?;
+            sort.insert(path, if item.asc.unwrap_or(true) { 1 } else { -1 });
+        }
+        find = find.sort(sort);
+    }
+    if let Some(offset) = &query.offset {
+        find = find.skip(literal_u64(&offset.value)?);
+    }
+    if let Some(limit) = &query.limit {
+        find = find.limit(literal_u64(limit)?);
+    }
+
+    let mut rows = Vec::<Document>::new();
+    'outer: for row in find.run()? {
+        let mut row = row?;
+        for join in &joins {
+            let local_value = try_get_document_value(&row, &join.local_path);
+            let joined = match local_value {
+                Some(value) => txn.collection::<Document>(&join.col_name).find_one(doc! { "_id": value })?,
+                None => None,
+            };
+            match joined {
+                Some(joined) => {
+                    row.insert(join.scope_name.clone(), joined);
+                }
+                None if join.outer => (),
+                None => continue 'outer,
+            }
+        }
+        rows.push(project(&scope, &select.projection, row)?);
+    }
+
+    Ok(rows)
+}
+
+fn project(scope: &TableScope, projection: &[SelectItem], row: Document) -> Result<Document> {
+    if let [SelectItem::Wildcard(_)] = projection {
+        return Ok(row);
+    }
+    let mut result = Document::new();
+    for item in projection {
+        match item {
+            SelectItem::Wildcard(_) => {
+                result.extend(row.clone());
+            }
+            SelectItem::QualifiedWildcard(name, _) => {
+                let name = object_name(name);
+                if name == scope.main {
+                    for (key, value) in row.iter() {
+                        if !scope.joined.contains(key) {
+                            result.insert(key.clone(), value.clone());
+                        }
+                    }
+                } else if let Some(value) = row.get(&name) {
+                    result.insert(name, value.clone());
+                }
+            }
+            SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+                let path = match scope.column(expr) {
+                    Some(ColumnRef::Main(path)) | Some(ColumnRef::Joined(path)) => path,
+                    None => return Err(sql_error(format!("unsupported column: {}", expr))),
+                };
+                let name = match item {
+                    SelectItem::ExprWithAlias { alias, .. } => alias.value.clone(),
+                    _ => path.clone(),
+                };
+                if let Some(value) = try_get_document_value(&row, &path) {
+                    result.insert(name, value);
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn execute_insert(
+    txn: &Transaction,
+    table_name: &ObjectName,
+    columns: &[sqlparser::ast::Ident],
+    source: &Query,
+) -> Result<InsertManyResult> {
+    let values = match source.body.as_ref() {
+        SetExpr::Values(values) => values,
+        _ => return Err(sql_error("INSERT requires VALUES")),
+    };
+    if columns.is_empty() {
+        return Err(sql_error("INSERT requires the column names"));
+    }
+
+    let mut docs = Vec::<Document>::with_capacity(values.rows.len());
+    for row in &values.rows {
+        if row.len() != columns.len() {
+            return Err(sql_error(format!(
+                "expected {} values, got {}", columns.len(), row.len()
+            )));
+        }
+        let mut doc = Document::new();
+        for (column, value) in columns.iter().zip(row) {
+            doc.insert(column.value.clone(), literal(value)?);
+        }
+        docs.push(doc);
+    }
+
+    txn.collection::<Document>(&object_name(table_name)).insert_many(docs)
+}
+
+/// Translate an assignment into an update operator,
+/// `a = a + 1` into `$inc`, `a = a * 2` into `$mul`, others into `$set`.
+fn assignment_update(scope: &TableScope, path: &str, value: &Expr) -> Result<(&'static str, Bson)> {
+    if let Expr::BinaryOp { left, op, right } = value {
+        let is_self = matches!(scope.column(left), Some(ColumnRef::Main(ref p)) if p == path);
+        if is_self {
+            let operand = literal(right)?;
+            return match op {
+                BinaryOperator::Plus => Ok(("$inc", operand)),
+                BinaryOperator::Minus => {
+                    let negated = match operand {
+                        Bson::Int64(v) => Bson::Int64(-v),
+                        Bson::Double(v) => Bson::Double(-v),
+                        other => return Err(sql_error(format!("expected a number, got: {}", other))),
+                    };
+                    Ok(("$inc", negated))
+                }
+                BinaryOperator::Multiply => Ok(("$mul", operand)),
+                other => Err(sql_error(format!("unsupported operator in SET: {}", other))),
+            };
+        }
+    }
+    Ok(("$set", literal(value)?))
+}
+
+fn execute_update(
+    txn: &Transaction,
+    table: &TableWithJoins,
+    assignments: &[Assignment],
+    selection: Option<&Expr>,
+) -> Result<UpdateResult> {
+    let (col_name, scope_name) = single_table(std::slice::from_ref(table))?;
+    let scope = TableScope::new(scope_name);
+
+    let mut update = Document::new();
+    for assignment in assignments {
+        let path = match &assignment.target {
+            AssignmentTarget::ColumnName(name) => {
+                scope.main_column(&Expr::CompoundIdentifier(name.0.clone()))?
+            }
+            AssignmentTarget::Tuple(_) => return Err(sql_error("tuple assignment is not supported")),
+        };
+        let (op, value) = assignment_update(&scope, &path, &assignment.value)?;
+        let entry = update.entry(op.to_string()).or_insert_with(|| Bson::Document(Document::new()));
+        if let Bson::Document(fields) = entry {
+            fields.insert(path, value);
+        }
+    }
+
+    let query_filter = where_filter(&scope, selection)?;
+    txn.collection::<Document>(&col_name).update_many(query_filter, update)
+}
+
+fn execute_delete(
+    txn: &Transaction,
+    tables: &[TableWithJoins],
+    selection: Option<&Expr>,
+    limit: Option<u64>,
+) -> Result<DeleteResult> {
+    let (col_name, scope_name) = single_table(tables)?;
+    let scope = TableScope::new(scope_name);
+    let query_filter = where_filter(&scope, selection)?;
+    let collection = txn.collection::<Document>(&col_name);
+    match limit {
+        None => collection.delete_many(query_filter),
+        Some(1) => collection.delete_one(query_filter),
+        Some(_) => Err(sql_error("only LIMIT 1 is supported in DELETE")),
+    }
+}
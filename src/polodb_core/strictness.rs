@@ -0,0 +1,139 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bson::{Bson, Document};
+use crate::errors::InvalidBsonError;
+use crate::{Error, Result};
+
+/// How the values of the written documents which don't survive a round trip
+/// through other BSON implementations are handled: the deprecated types
+/// (undefined, symbol, DBPointer and code with scope) and the NaN numbers.
+///
+/// The field names and the regular expressions containing a NUL character
+/// can't be encoded, they are rejected in every mode but [`BsonStrictness::Lenient`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BsonStrictness {
+    /// Store the documents as they are, the default.
+    #[default]
+    Lenient,
+    /// Reject the documents with the path of the first offending value.
+    Reject,
+    /// Replace the symbols by strings, and the other offending values by null.
+    Sanitize,
+}
+
+/// Check the document written to `col_name`, fixing it in place when sanitizing.
+pub(crate) fn check(strictness: BsonStrictness, col_name: &str, doc: &mut Document) -> Result<()> {
+    if strictness == BsonStrictness::Lenient {
+        return Ok(());
+    }
+    let mut path = Vec::new();
+    check_document(strictness, doc, &mut path).map_err(|(path, reason)| {
+        Error::InvalidBson(Box::new(InvalidBsonError {
+            ns: col_name.to_string(),
+            path,
+            reason,
+        }))
+    })
+}
+
+type Invalid = (String, &'static str);
+
+fn invalid(path: &[String], reason: &'static str) -> Invalid {
+    (path.join("."), reason)
+}
+
+fn check_document(strictness: BsonStrictness, doc: &mut Document, path: &mut Vec<String>) -> std::result::Result<(), Invalid> {
+    for (key, value) in doc.iter_mut() {
+        path.push(key.clone());
+        if key.contains('\0') {
+            return Err(invalid(path, "the field name contains a NUL character"));
+        }
+        check_value(strictness, value, path)?;
+        path.pop();
+    }
+    Ok(())
+}
+
+fn check_value(strictness: BsonStrictness, value: &mut Bson, path: &mut Vec<String>) -> std::result::Result<(), Invalid> {
+    let reason = match value {
+        Bson::Document(doc) => return check_document(strictness, doc, path),
+        Bson::Array(arr) => {
+            for (index, item) in arr.iter_mut().enumerate() {
+                path.push(index.to_string());
+                check_value(strictness, item, path)?;
+                path.pop();
+            }
+            return Ok(());
+        }
+        Bson::RegularExpression(regex) => {
+            if regex.pattern.contains('\0') || regex.options.contains('\0') {
+                return Err(invalid(path, "the regular expression contains a NUL character"));
+            }
+            return Ok(());
+        }
+        Bson::Double(number) if number.is_nan() => "NaN is not comparable",
+        Bson::Undefined => "undefined is deprecated",
+        Bson::Symbol(_) => "symbol is deprecated",
+        Bson::DbPointer(_) => "DBPointer is deprecated",
+        Bson::JavaScriptCodeWithScope(_) => "code with scope is deprecated",
+        _ => return Ok(()),
+    };
+    if strictness == BsonStrictness::Reject {
+        return Err(invalid(path, reason));
+    }
+    *value = match std::mem::take(value) {
+        Bson::Symbol(symbol) => Bson::String(symbol),
+        _ => Bson::Null,
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{doc, Bson};
+    use crate::Error;
+    use super::{check, BsonStrictness};
+
+    #[test]
+    fn test_bson_strictness() {
+        let mut doc = doc! {
+            "name": "a",
+            "scores": [1.0, { "value": f64::NAN }],
+            "tag": Bson::Symbol("red".into()),
+            "missing": Bson::Undefined,
+        };
+        let err = check(BsonStrictness::Reject, "items", &mut doc.clone()).unwrap_err();
+        match err {
+            Error::InvalidBson(err) => {
+                assert_eq!(err.ns, "items");
+                assert_eq!(err.path, "scores.1.value");
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+
+        check(BsonStrictness::Sanitize, "items", &mut doc).unwrap();
+        assert_eq!(doc, doc! {
+            "name": "a",
+            "scores": [1.0, { "value": Bson::Null }],
+            "tag": "red",
+            "missing": Bson::Null,
+        });
+
+        let mut doc = doc! { "a": { "b\0c": 1 } };
+        let err = check(BsonStrictness::Sanitize, "items", &mut doc).unwrap_err();
+        assert!(matches!(err, Error::InvalidBson(err) if err.path == "a.b\0c"));
+    }
+
+}
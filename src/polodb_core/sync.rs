@@ -0,0 +1,486 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Offline-first sync of local collections with a remote backend.
+//!
+//! The [`SyncEngine`] pushes the local writes recorded in the oplog to a [`SyncRemote`],
+//! pulls the remote changes, and resolves the documents changed on both sides
+//! with a [`ConflictPolicy`]. The documents of the collections created with
+//! [`crate::options::CreateCollectionOptions::crdt`] are merged field by field instead,
+//! so the devices editing the same document converge to the same version.
+//!
+//! A remote backed by a MongoDB (or Atlas) collection stamps the documents it stores
+//! with their update time, and keeps the deleted documents as tombstones:
+//!
+//! ```ignore
+//! impl SyncRemote for MongoRemote {
+//!     fn push(&mut self, changes: &[SyncChange]) -> Result<()> {
+//!         for change in changes {
+//!             let coll = self.db.collection::<Document>(&change.collection);
+//!             let doc = doc! {
+//!                 "_id": change.id.clone(),
+//!                 "doc": change.document.clone(),
+//!                 "updatedAt": change.updated_at,
+//!             };
+//!             coll.replace_one(doc! { "_id": change.id.clone() }, doc).upsert(true).run()
+//!                 .map_err(|err| Error::Sync(err.to_string()))?;
+//!         }
+//!         Ok(())
+//!     }
+//!
+//!     fn pull(&mut self, collections: &[String], checkpoint: Option<&Bson>) -> Result<SyncBatch> {
+//!         // find { updatedAt: { $gt: checkpoint } } in every collection,
+//!         // returning the greatest updatedAt as the next checkpoint
+//!     }
+//! }
+//! ```
+
+use std::sync::{Arc, Weak};
+use bson::{doc, Bson, DateTime, Document};
+use indexmap::IndexMap;
+use crate::change_stream::{ChangeEvent, OperationType};
+use crate::crdt::CrdtState;
+use crate::cursor::Cursor;
+use crate::db::db_inner::DatabaseInner;
+use crate::transaction::TransactionInner;
+use crate::{Error, Result};
+
+/// The key of the oplog token of the last local change pushed, and of the remote checkpoint.
+const SYNC_STATE_KEY: &str = "$SYNC_STATE";
+/// The prefix of the last version of the documents known to both sides.
+const SYNC_BASE_PREFIX: &str = "$SYNC_BASE";
+
+/// The state of a document written on one side: the document, or `None` if it was deleted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncChange {
+    pub collection: String,
+    /// The `_id` of the document.
+    pub id: Bson,
+    /// The document after the change, `None` if it was deleted.
+    pub document: Option<Document>,
+    pub updated_at: DateTime,
+    /// The merge state of a document of a CRDT collection, which the remote
+    /// stores with the document, see [`crate::options::CreateCollectionOptions::crdt`].
+    pub crdt: Option<Document>,
+}
+
+/// The changes pulled from a remote.
+#[derive(Debug, Clone, Default)]
+pub struct SyncBatch {
+    pub changes: Vec<SyncChange>,
+    /// The position of the remote after these changes, passed to the next pull.
+    pub checkpoint: Option<Bson>,
+}
+
+/// The remote end of a [`SyncEngine`], a MongoDB collection for example.
+///
+/// The remote must report the deletes as changes without a document,
+/// so it keeps a tombstone of the deleted documents.
+pub trait SyncRemote {
+    /// Store the local changes on the remote.
+    fn push(&mut self, changes: &[SyncChange]) -> Result<()>;
+
+    /// Return the changes of `collections` after `checkpoint`, all the documents if `None`.
+    /// The changes pushed by this engine may be returned too, they are skipped.
+    fn pull(&mut self, collections: &[String], checkpoint: Option<&Bson>) -> Result<SyncBatch>;
+}
+
+/// A document changed both locally and remotely since the last sync.
+#[derive(Debug, Clone)]
+pub struct SyncConflict {
+    /// The version of the document at the last sync, `None` if it was unknown to one side.
+    pub base: Option<Document>,
+    pub local: SyncChange,
+    pub remote: SyncChange,
+}
+
+type Merger = Box<dyn Fn(&SyncConflict) -> Option<Document> + Send + Sync>;
+
+/// How a [`SyncConflict`] is resolved, the result is kept locally and pushed.
+#[derive(Default)]
+pub enum ConflictPolicy {
+    /// Keep the change made last, the remote one if they were made at the same time.
+    #[default]
+    LastWriteWins,
+    /// Merge the versions with a function, returning `None` to delete the document.
+    Custom(Merger),
+}
+
+impl ConflictPolicy {
+
+    fn resolve(&self, conflict: &SyncConflict) -> Option<Document> {
+        match self {
+            ConflictPolicy::LastWriteWins => {
+                if conflict.local.updated_at > conflict.remote.updated_at {
+                    conflict.local.document.clone()
+                } else {
+                    conflict.remote.document.clone()
+                }
+            }
+            ConflictPolicy::Custom(merge) => merge(conflict),
+        }
+    }
+
+}
+
+/// What a call to [`SyncEngine::sync`] did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// The number of local changes pushed.
+    pub pushed: usize,
+    /// The number of remote changes applied locally.
+    pub pulled: usize,
+    /// The number of documents changed on both sides.
+    pub conflicts: usize,
+}
+
+/// Syncs collections of a database with a [`SyncRemote`], see [`crate::Database::sync_engine`].
+///
+/// The local changes are read from the oplog, the database needs it,
+/// see [`crate::ConfigBuilder::set_oplog_size`]. If the changes since the last sync
+/// are no longer in the oplog, the documents are compared with the versions of the last sync.
+pub struct SyncEngine {
+    db: Weak<DatabaseInner>,
+    collections: Vec<String>,
+    policy: ConflictPolicy,
+}
+
+impl SyncEngine {
+
+    pub(crate) fn new(db: &Arc<DatabaseInner>, collections: Vec<String>) -> SyncEngine {
+        SyncEngine {
+            db: Arc::downgrade(db),
+            collections,
+            policy: ConflictPolicy::default(),
+        }
+    }
+
+    pub fn conflict_policy(mut self, policy: ConflictPolicy) -> SyncEngine {
+        self.policy = policy;
+        self
+    }
+
+    /// Pull the remote changes, then push the local changes made since the last sync.
+    pub fn sync(&self, remote: &mut dyn SyncRemote) -> Result<SyncReport> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let oplog = db.change_streams().oplog().ok_or_else(|| {
+            Error::Sync("the sync needs the oplog, see ConfigBuilder::set_oplog_size".to_string())
+        })?;
+        let mut report = SyncReport::default();
+        let state = load_state(&db)?;
+        let node = state.node.clone().unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+
+        let (mut local, token, crdt) = {
+            let txn = db.start_transaction()?;
+            let crdt = self.crdt_collections(&db, &txn)?;
+            let token = oplog.committed_token();
+            let events = match state.pushed() {
+                Some(pushed) => match oplog.read_after(&txn, pushed) {
+                    Ok(events) => Some(events),
+                    Err(Error::ChangeStreamHistoryLost(_)) => None,
+                    Err(err) => return Err(err),
+                },
+                None => None,
+            };
+            let (changes, token) = match events {
+                Some(events) => {
+                    let token = events.iter().filter_map(|event| event.resume_token).fold(token, u64::max);
+                    (self.changes_from_events(events), token)
+                }
+                None => (self.changes_from_documents(&txn)?, token),
+            };
+            let mut local = IndexMap::new();
+            for change in changes {
+                local.insert(base_key(&change.collection, &change.id)?, change);
+            }
+            let mut unchanged = Vec::new();
+            for (key, change) in local.iter_mut() {
+                let base = load_base(&txn, key)?;
+                // the remote changes applied by the last sync are logged too
+                if base.as_ref().map(|base| &base.document) == Some(&change.document) {
+                    unchanged.push(key.clone());
+                } else if crdt.contains(&change.collection) {
+                    let mut state = match &base {
+                        Some(base) => CrdtState::of(base.crdt.as_ref(), base.document.as_ref(), DateTime::from_millis(0))?,
+                        None => CrdtState::default(),
+                    };
+                    state.record(change.document.as_ref(), change.updated_at, &node);
+                    change.crdt = Some(state.to_document()?);
+                }
+            }
+            for key in unchanged {
+                local.shift_remove(&key);
+            }
+            (local, token, crdt)
+        };
+
+        let SyncBatch { changes, checkpoint } = remote.pull(&self.collections, state.checkpoint.as_ref())?;
+        // only the last change of a document matters, the previous ones may be our own pushes
+        let mut remote_changes = IndexMap::new();
+        for change in changes {
+            if self.collections.contains(&change.collection) {
+                let key = base_key(&change.collection, &change.id)?;
+                remote_changes.shift_remove(&key);
+                remote_changes.insert(key, change);
+            }
+        }
+        apply(&db, |txn| {
+            for (key, mut change) in remote_changes {
+                let is_crdt = crdt.contains(&change.collection);
+                if is_crdt && change.crdt.is_none() {
+                    let state = CrdtState::of(None, change.document.as_ref(), change.updated_at)?;
+                    change.crdt = Some(state.to_document()?);
+                }
+                let base = load_base(txn, &key)?;
+                let mine = local.shift_remove(&key);
+                let base_document = base.map(|base| base.document);
+                match mine {
+                    None if base_document.as_ref() == Some(&change.document) => (),
+                    None => {
+                        write_document(&db, txn, &change)?;
+                        report.pulled += 1;
+                    }
+                    Some(mine) if is_crdt => {
+                        if mine.document != change.document {
+                            report.conflicts += 1;
+                        }
+                        let remote_state = CrdtState::of(change.crdt.as_ref(), None, change.updated_at)?;
+                        let mut merged = CrdtState::of(mine.crdt.as_ref(), mine.document.as_ref(), mine.updated_at)?;
+                        merged.merge(&remote_state);
+                        let document = merged.materialize();
+                        if document != change.document || merged != remote_state {
+                            local.insert(key.clone(), SyncChange {
+                                document,
+                                crdt: Some(merged.to_document()?),
+                                updated_at: DateTime::now(),
+                                ..mine
+                            });
+                        }
+                        write_document(&db, txn, local.get(&key).unwrap_or(&change))?;
+                    }
+                    Some(mine) if mine.document == change.document => (),
+                    Some(mine) => {
+                        report.conflicts += 1;
+                        let conflict = SyncConflict {
+                            base: base_document.flatten(),
+                            local: mine,
+                            remote: change.clone(),
+                        };
+                        let resolved = self.policy.resolve(&conflict);
+                        if resolved != change.document {
+                            let updated_at = if resolved == conflict.local.document {
+                                conflict.local.updated_at
+                            } else {
+                                DateTime::now()
+                            };
+                            local.insert(key.clone(), SyncChange {
+                                document: resolved,
+                                updated_at,
+                                crdt: None,
+                                ..conflict.local
+                            });
+                        }
+                        write_document(&db, txn, local.get(&key).unwrap_or(&change))?;
+                    }
+                }
+                save_base(txn, &key, &change)?;
+            }
+            let mut state = load_state_in(txn)?;
+            state.node = Some(node.clone());
+            state.checkpoint = checkpoint;
+            save_state(txn, &state)
+        })?;
+
+        let changes = local.into_values().collect::<Vec<_>>();
+        if !changes.is_empty() {
+            remote.push(&changes)?;
+        }
+        apply(&db, |txn| {
+            for change in &changes {
+                save_base(txn, &base_key(&change.collection, &change.id)?, change)?;
+            }
+            let mut state = load_state_in(txn)?;
+            state.pushed = Some(token as i64);
+            save_state(txn, &state)
+        })?;
+        report.pushed = changes.len();
+        Ok(report)
+    }
+
+    fn crdt_collections(&self, db: &DatabaseInner, txn: &TransactionInner) -> Result<Vec<String>> {
+        let mut result = Vec::new();
+        for name in &self.collections {
+            let spec = db.get_collection_meta_by_name_advanced_auto(name, false, txn)?;
+            if spec.is_some_and(|spec| spec.crdt) {
+                result.push(name.clone());
+            }
+        }
+        Ok(result)
+    }
+
+    fn changes_from_events(&self, events: Vec<ChangeEvent>) -> Vec<SyncChange> {
+        events
+            .into_iter()
+            .filter(|event| self.collections.contains(&event.collection))
+            .map(|event| SyncChange {
+                collection: event.collection,
+                id: event.document_key,
+                document: match event.operation_type {
+                    OperationType::Delete => None,
+                    _ => event.full_document,
+                },
+                updated_at: event.wall_time.unwrap_or_else(DateTime::now),
+                crdt: None,
+            })
+            .collect()
+    }
+
+    /// Every local document, and a delete for the documents of the last sync which are gone.
+    fn changes_from_documents(&self, txn: &TransactionInner) -> Result<Vec<SyncChange>> {
+        let now = DateTime::now();
+        let mut changes = IndexMap::new();
+        for collection in &self.collections {
+            let mut cursor = Cursor::new_with_str_prefix(collection.as_str(), txn.rocksdb_txn.new_iterator())?;
+            cursor.reset()?;
+            while cursor.has_next() {
+                let doc = bson::from_slice::<Document>(&cursor.copy_data()?)?;
+                let id = doc.get("_id").cloned().unwrap_or(Bson::Null);
+                changes.insert(base_key(collection, &id)?, SyncChange {
+                    collection: collection.clone(),
+                    id,
+                    document: Some(doc),
+                    updated_at: now,
+                    crdt: None,
+                });
+                cursor.next()?;
+            }
+        }
+
+        let mut cursor = Cursor::new_with_str_prefix(SYNC_BASE_PREFIX, txn.rocksdb_txn.new_iterator())?;
+        cursor.reset()?;
+        while cursor.has_next() {
+            let base = bson::from_slice::<SyncBase>(&cursor.copy_data()?)?;
+            let key = base_key(&base.collection, &base.id)?;
+            if self.collections.contains(&base.collection) && !changes.contains_key(&key) {
+                changes.insert(key, SyncChange {
+                    collection: base.collection,
+                    id: base.id,
+                    document: None,
+                    updated_at: now,
+                    crdt: None,
+                });
+            }
+            cursor.next()?;
+        }
+        Ok(changes.into_values().collect())
+    }
+
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct SyncState {
+    /// The token of the last local change pushed.
+    pushed: Option<i64>,
+    checkpoint: Option<Bson>,
+    /// The name of this device in the CRDT merge states.
+    node: Option<String>,
+}
+
+impl SyncState {
+
+    fn pushed(&self) -> Option<u64> {
+        self.pushed.map(|token| token as u64)
+    }
+
+}
+
+/// The version of a document at the last sync.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SyncBase {
+    collection: String,
+    id: Bson,
+    document: Option<Document>,
+    #[serde(default)]
+    crdt: Option<Document>,
+}
+
+fn apply<F>(db: &DatabaseInner, f: F) -> Result<()>
+where
+    F: FnOnce(&TransactionInner) -> Result<()>,
+{
+    let mut txn = db.start_transaction()?;
+    txn.set_auto_commit(false);
+    match f(&txn) {
+        Ok(()) => txn.commit(),
+        Err(err) => {
+            txn.rollback()?;
+            Err(err)
+        }
+    }
+}
+
+/// Replace the local document by the version of the change.
+fn write_document(db: &DatabaseInner, txn: &TransactionInner, change: &SyncChange) -> Result<()> {
+    db.delete_one(&change.collection, doc! { "_id": change.id.clone() }, txn)?;
+    if let Some(doc) = &change.document {
+        let mut doc = doc.clone();
+        doc.insert("_id", change.id.clone());
+        db.insert_one(&change.collection, doc, txn)?;
+    }
+    Ok(())
+}
+
+fn state_key() -> Result<Vec<u8>> {
+    crate::utils::bson::stacked_key([&Bson::String(SYNC_STATE_KEY.to_string())])
+}
+
+fn base_key(collection: &str, id: &Bson) -> Result<Vec<u8>> {
+    crate::utils::bson::stacked_key([
+        &Bson::String(SYNC_BASE_PREFIX.to_string()),
+        &Bson::String(collection.to_string()),
+        id,
+    ])
+}
+
+fn load_state(db: &DatabaseInner) -> Result<SyncState> {
+    load_state_in(&db.start_transaction()?)
+}
+
+fn load_state_in(txn: &TransactionInner) -> Result<SyncState> {
+    match txn.rocksdb_txn.get(&state_key()?)? {
+        Some(buf) => Ok(bson::from_slice(&buf)?),
+        None => Ok(SyncState::default()),
+    }
+}
+
+fn save_state(txn: &TransactionInner, state: &SyncState) -> Result<()> {
+    txn.put(&state_key()?, &bson::to_vec(state)?)
+}
+
+fn load_base(txn: &TransactionInner, key: &[u8]) -> Result<Option<SyncBase>> {
+    match txn.rocksdb_txn.get(key)? {
+        Some(buf) => Ok(Some(bson::from_slice(&buf)?)),
+        None => Ok(None),
+    }
+}
+
+fn save_base(txn: &TransactionInner, key: &[u8], change: &SyncChange) -> Result<()> {
+    let base = SyncBase {
+        collection: change.collection.clone(),
+        id: change.id.clone(),
+        document: change.document.clone(),
+        crdt: change.crdt.clone(),
+    };
+    txn.put(key, &bson::to_vec(&base)?)
+}
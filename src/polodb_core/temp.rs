@@ -0,0 +1,137 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Deref;
+use std::path::PathBuf;
+use bson::oid::ObjectId;
+use serde::Serialize;
+use crate::db::db_inner::DatabaseInner;
+use crate::options::Durability;
+use crate::{Collection, Config, Database, Result};
+
+/// A database of its own in the temporary directory, holding one temporary collection.
+/// The database is closed and its files are removed when it's dropped.
+pub(crate) struct ScratchSpace {
+    db: Option<Database>,
+    path: PathBuf,
+}
+
+impl ScratchSpace {
+
+    /// Open a scratch database accepting the same documents as `main`.
+    pub(crate) fn open(main: &DatabaseInner) -> Result<ScratchSpace> {
+        let path = std::env::temp_dir().join(format!("polodb-temp-{}", ObjectId::new().to_hex()));
+        let main_config = main.config();
+        // nothing to recover after a crash, the files are removed anyway
+        let config = Config {
+            max_document_size: main_config.max_document_size,
+            max_document_depth: main_config.max_document_depth,
+            object_id_machine_id: main_config.object_id_machine_id,
+            object_id_process_id: main_config.object_id_process_id,
+            object_id_counter_mode: main_config.object_id_counter_mode,
+            bson_strictness: main_config.bson_strictness,
+            durability: Durability::Buffered,
+            ..Config::default()
+        };
+        let db = match Database::open_path_with_config(&path, config) {
+            Ok(db) => db,
+            Err(err) => {
+                let _ = std::fs::remove_dir_all(&path);
+                return Err(err);
+            }
+        };
+        Ok(ScratchSpace {
+            db: Some(db),
+            path,
+        })
+    }
+
+    /// Create the collection `name` in the scratch database.
+    pub(crate) fn create_collection<T: Serialize>(&self, name: &str) -> Result<Collection<T>> {
+        let db = self.db.as_ref().unwrap();
+        db.create_collection(name)?;
+        Ok(db.collection(name))
+    }
+
+}
+
+impl Drop for ScratchSpace {
+    fn drop(&mut self) {
+        // close the database before removing its files
+        self.db.take();
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A collection living as long as the handle, created by [`Database::create_temp_collection`].
+///
+/// The documents are stored in a scratch database of the temporary directory,
+/// never in the files of the database, and are removed with the handle.
+/// It's used as a [`Collection`], the writes are not part of the transactions
+/// of the database, and are not recorded in its oplog or its audit log.
+///
+/// ```rust
+/// use polodb_core::{Database, CollectionT};
+/// use polodb_core::bson::{doc, Document};
+/// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-temp-collection");
+/// let db = Database::open_path(db_path).unwrap();
+/// let totals = db.create_temp_collection::<Document>("totals").unwrap();
+/// totals.insert_one(doc! { "_id": "books", "total": 12 }).unwrap();
+/// assert_eq!(totals.count_documents().unwrap(), 1);
+/// assert!(db.list_collection_names().unwrap().is_empty());
+/// ```
+pub struct TempCollection<T> {
+    collection: Collection<T>,
+    _scratch: ScratchSpace,
+}
+
+impl<T: Serialize> TempCollection<T> {
+
+    pub(crate) fn create(main: &DatabaseInner, name: &str) -> Result<TempCollection<T>> {
+        let scratch = ScratchSpace::open(main)?;
+        let collection = scratch.create_collection(name)?;
+        Ok(TempCollection {
+            collection,
+            _scratch: scratch,
+        })
+    }
+
+}
+
+impl<T> Deref for TempCollection<T> {
+    type Target = Collection<T>;
+
+    fn deref(&self) -> &Collection<T> {
+        &self.collection
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{doc, Document};
+    use crate::test_utils::prepare_db;
+    use crate::CollectionT;
+
+    #[test]
+    fn test_temp_collection_files_removed() {
+        let db = prepare_db("test-temp-collection-files").unwrap();
+        let temp = db.create_temp_collection::<Document>("scratch").unwrap();
+        temp.insert_one(doc! { "_id": 1 }).unwrap();
+        let path = temp._scratch.path.clone();
+        assert!(path.exists());
+        drop(temp);
+        assert!(!path.exists());
+    }
+
+}
@@ -14,6 +14,7 @@
 
 use std::path::PathBuf;
 use std::env;
+use bson::Bson;
 use crate::{Config, Database, Result};
 
 pub fn mk_db_path(db_name: &str) -> PathBuf {
@@ -35,4 +36,15 @@ pub fn prepare_db_with_config(db_name: &str, config: Config) -> Result<Database>
 
 pub fn prepare_db(db_name: &str) -> Result<Database> {
     prepare_db_with_config(db_name, Config::default())
-}
\ No newline at end of file
+}
+
+/// Store `data` as the document of the `_id` in the collection, as it is,
+/// to read a corrupt document or a document written by a newer version.
+pub fn put_raw_document(db: &Database, col_name: &str, id: impl Into<Bson>, data: &[u8]) -> Result<()> {
+    let db = db.inner();
+    let txn = db.start_transaction()?;
+    db.get_collection_meta_by_name_advanced_auto(col_name, true, &txn)?;
+    let key = crate::utils::bson::stacked_key([&Bson::String(col_name.to_string()), &id.into()])?;
+    txn.put(&key, data)?;
+    txn.commit()
+}
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use bson::{doc, Document};
+use bson::{doc, Bson, Document};
 use serde::{Deserialize, Serialize};
 use polodb_core::{Result, CollectionT, Database};
 use polodb_core::test_utils::prepare_db as project_prepare_db;
@@ -372,3 +372,463 @@ fn test_aggregate_abs() {
         assert_eq!(abs_weight, weight.abs());
     }
 }
+
+#[test]
+fn test_aggregate_match_after_stage() {
+    let db = prepare_db("test-aggregate-match-after-stage").unwrap();
+    let fruits = db.collection::<Document>("fruits");
+
+    let result = fruits
+        .aggregate(vec![
+            doc! {
+                "$sort": {
+                    "weight": -1,
+                },
+            },
+            doc! {
+                "$match": {
+                    "shape": "round",
+                    "weight": { "$gte": 120 },
+                },
+            },
+            doc! {
+                "$limit": 2,
+            },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].get("name").unwrap().as_str().unwrap(), "orange");
+    assert_eq!(result[1].get("name").unwrap().as_str().unwrap(), "peach");
+
+    // the fields added by the previous stages are matched
+    let result = fruits
+        .aggregate(vec![
+            doc! {
+                "$match": {
+                    "color": "yellow",
+                },
+            },
+            doc! {
+                "$addFields": {
+                    "grams": "$weight",
+                },
+            },
+            doc! {
+                "$match": {
+                    "grams": { "$gt": 150 },
+                },
+            },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].get("name").unwrap().as_str().unwrap(), "banana");
+}
+
+#[test]
+fn test_aggregate_coll_stats() {
+    use polodb_core::IndexModel;
+
+    let db = prepare_db("test-aggregate-coll-stats").unwrap();
+    let fruits = db.collection::<Document>("fruits");
+    fruits.create_index(IndexModel {
+        keys: doc! {
+            "name": 1,
+        },
+        options: None,
+    }).unwrap();
+
+    let result = fruits
+        .aggregate(vec![
+            doc! {
+                "$collStats": {},
+            },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(result.len(), 1);
+    let stats = &result[0];
+    assert_eq!(stats.get_str("ns").unwrap(), "fruits");
+    assert_eq!(stats.get_i64("count").unwrap(), 5);
+    assert_eq!(stats.get_i64("nindexes").unwrap(), 1);
+    assert!(stats.get_i64("size").unwrap() > 0);
+    assert!(stats.get_document("indexSizes").unwrap().get_i64("name_1").unwrap() > 0);
+
+    let result = fruits
+        .aggregate(vec![
+            doc! {
+                "$indexStats": {},
+            },
+            doc! {
+                "$unset": ["size", "entries"],
+            },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(result, vec![
+        doc! {
+            "name": "name_1",
+            "key": { "name": 1 },
+            "unique": false,
+        },
+    ]);
+}
+
+#[test]
+fn test_aggregate_lookup() {
+    use polodb_core::IndexModel;
+
+    let db = project_prepare_db("test-aggregate-lookup").unwrap();
+    let users = db.collection::<Document>("users");
+    users.insert_many(vec![
+        doc! { "_id": 1, "name": "Ann", "email": "ann@example.com" },
+        doc! { "_id": 2, "name": "Bob", "email": "bob@example.com" },
+        doc! { "_id": 3, "name": "Cid" },
+    ]).unwrap();
+    let orders = db.collection::<Document>("orders");
+    orders.insert_many(vec![
+        doc! { "_id": 10, "user_id": 1, "total": 30, "emails": ["ann@example.com"] },
+        doc! { "_id": 11, "user_id": 2, "total": 12 },
+        doc! { "_id": 12, "user_id": 1, "total": 8 },
+        doc! { "_id": 13, "total": 5, "emails": ["bob@example.com", "cid@example.com"] },
+    ]).unwrap();
+
+    let names = |docs: &Bson| docs.as_array().unwrap().iter()
+        .map(|doc| doc.as_document().unwrap().get_str("name").unwrap().to_string())
+        .collect::<Vec<String>>();
+    let totals = |docs: &Bson| docs.as_array().unwrap().iter()
+        .map(|doc| doc.as_document().unwrap().get_i32("total").unwrap())
+        .collect::<Vec<i32>>();
+
+    // by the primary key of the joined collection
+    let result = orders.aggregate(vec![
+        doc! { "$match": { "total": { "$gt": 10 } } },
+        doc! { "$lookup": { "from": "users", "localField": "user_id", "foreignField": "_id", "as": "user" } },
+    ]).run().unwrap().collect::<Result<Vec<Document>>>().unwrap();
+    assert_eq!(result.len(), 2);
+    assert_eq!(names(result[0].get("user").unwrap()), vec!["Ann"]);
+    assert_eq!(names(result[1].get("user").unwrap()), vec!["Bob"]);
+
+    // by scanning the joined collection, then by its index
+    let lookup_orders = || users.aggregate(vec![
+        doc! { "$lookup": { "from": "orders", "localField": "_id", "foreignField": "user_id", "as": "orders" } },
+    ]).run().unwrap().map(|doc| totals(doc.unwrap().get("orders").unwrap())).collect::<Vec<Vec<i32>>>();
+    let scanned = lookup_orders();
+    assert_eq!(scanned, vec![vec![30, 8], vec![12], vec![]]);
+    orders.create_index(IndexModel {
+        keys: doc! { "user_id": 1, "total": 1 },
+        options: None,
+    }).unwrap();
+    assert_eq!(lookup_orders(), vec![vec![8, 30], vec![12], vec![]]);
+
+    // an array matches any of its elements, on both sides
+    orders.create_index(IndexModel {
+        keys: doc! { "emails": 1 },
+        options: None,
+    }).unwrap();
+    let result = orders.aggregate(vec![
+        doc! { "$lookup": { "from": "users", "localField": "emails", "foreignField": "email", "as": "users" } },
+    ]).run().unwrap().collect::<Result<Vec<Document>>>().unwrap();
+    assert_eq!(names(result[0].get("users").unwrap()), vec!["Ann"]);
+    assert_eq!(names(result[3].get("users").unwrap()), vec!["Bob"]);
+    let result = users.aggregate(vec![
+        doc! { "$lookup": { "from": "orders", "localField": "email", "foreignField": "emails", "as": "orders" } },
+    ]).run().unwrap().collect::<Result<Vec<Document>>>().unwrap();
+    assert_eq!(totals(result[1].get("orders").unwrap()), vec![5]);
+    // a missing field equals null, and the documents missing the foreign field
+    assert_eq!(totals(result[2].get("orders").unwrap()), vec![12, 8]);
+
+    let result = users.aggregate(vec![
+        doc! { "$lookup": { "from": "missing", "localField": "_id", "foreignField": "user_id", "as": "found" } },
+    ]).run().unwrap().collect::<Result<Vec<Document>>>().unwrap();
+    assert!(result.iter().all(|doc| doc.get_array("found").unwrap().is_empty()));
+
+    let result = users.aggregate(vec![
+        doc! { "$lookup": { "from": "orders", "localField": "_id", "as": "orders" } },
+    ]).run();
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_aggregate_group() {
+    let db = prepare_db("test-aggregate-group").unwrap();
+    let fruits = db.collection::<Document>("fruits");
+
+    let result = fruits
+        .aggregate(vec![
+            doc! {
+                "$group": {
+                    "_id": "$color",
+                    "count": { "$sum": 1 },
+                    "total": { "$sum": "$weight" },
+                    "average": { "$avg": "$weight" },
+                    "lightest": { "$min": "$weight" },
+                    "heaviest": { "$max": "$weight" },
+                    "names": { "$push": "$name" },
+                    "shapes": { "$addToSet": "$shape" },
+                },
+            },
+            doc! {
+                "$sort": { "_id": 1 },
+            },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(result, vec![
+        doc! {
+            "_id": "orange",
+            "count": 2,
+            "total": 280,
+            "average": 140.0,
+            "lightest": 130,
+            "heaviest": 150,
+            "names": ["orange", "peach"],
+            "shapes": ["round"],
+        },
+        doc! {
+            "_id": "red",
+            "count": 1,
+            "total": 100,
+            "average": 100.0,
+            "lightest": 100,
+            "heaviest": 100,
+            "names": ["apple"],
+            "shapes": ["round"],
+        },
+        doc! {
+            "_id": "yellow",
+            "count": 2,
+            "total": 320,
+            "average": 160.0,
+            "lightest": 120,
+            "heaviest": 200,
+            "names": ["banana", "pear"],
+            "shapes": ["long", "round"],
+        },
+    ]);
+
+    // a single group of all the documents, by an expression of the fields
+    let result = fruits
+        .aggregate(vec![
+            doc! {
+                "$match": { "shape": "round" },
+            },
+            doc! {
+                "$group": {
+                    "_id": null,
+                    "total": { "$sum": { "$multiply": ["$weight", 2] } },
+                    "first": { "$first": "$name" },
+                    "last": { "$last": "$name" },
+                    "missing": { "$avg": "$price" },
+                },
+            },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(result, vec![
+        doc! { "_id": null, "total": 1000, "first": "apple", "last": "peach", "missing": null },
+    ]);
+
+    // grouped by a document of expressions, then grouped again
+    let result = fruits
+        .aggregate(vec![
+            doc! {
+                "$group": {
+                    "_id": { "color": "$color", "shape": "$shape" },
+                    "count": { "$sum": 1 },
+                },
+            },
+            doc! {
+                "$group": {
+                    "_id": "$_id.shape",
+                    "kinds": { "$sum": 1 },
+                },
+            },
+            doc! {
+                "$sort": { "_id": 1 },
+            },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(result, vec![
+        doc! { "_id": "long", "kinds": 1 },
+        doc! { "_id": "round", "kinds": 3 },
+    ]);
+
+    let result = fruits
+        .aggregate(vec![
+            doc! {
+                "$group": {
+                    "_id": "$color",
+                    "total": { "$median": "$weight" },
+                },
+            },
+        ])
+        .run();
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_aggregate_unwind() {
+    let db = project_prepare_db("test-aggregate-unwind").unwrap();
+    let orders = db.collection::<Document>("orders");
+    orders.insert_many(vec![
+        doc! { "_id": 1, "items": [{ "sku": "A", "qty": 2 }, { "sku": "B", "qty": 1 }] },
+        doc! { "_id": 2, "items": [] },
+        doc! { "_id": 3, "items": { "sku": "A", "qty": 5 } },
+        doc! { "_id": 4 },
+        doc! { "_id": 5, "items": [{ "sku": "C", "qty": 3 }] },
+    ]).unwrap();
+
+    let result = orders
+        .aggregate(vec![
+            doc! { "$unwind": "$items" },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(result, vec![
+        doc! { "_id": 1, "items": { "sku": "A", "qty": 2 } },
+        doc! { "_id": 1, "items": { "sku": "B", "qty": 1 } },
+        doc! { "_id": 3, "items": { "sku": "A", "qty": 5 } },
+        doc! { "_id": 5, "items": { "sku": "C", "qty": 3 } },
+    ]);
+
+    let result = orders
+        .aggregate(vec![
+            doc! {
+                "$unwind": {
+                    "path": "$items",
+                    "includeArrayIndex": "position",
+                    "preserveNullAndEmptyArrays": true,
+                },
+            },
+            doc! { "$project": { "sku": "$items.sku", "position": 1 } },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(result, vec![
+        doc! { "_id": 1, "sku": "A", "position": 0i64 },
+        doc! { "_id": 1, "sku": "B", "position": 1i64 },
+        doc! { "_id": 2, "sku": null, "position": null },
+        doc! { "_id": 3, "sku": "A", "position": null },
+        doc! { "_id": 4, "sku": null, "position": null },
+        doc! { "_id": 5, "sku": "C", "position": 0i64 },
+    ]);
+
+    // the quantities sold by product
+    let result = orders
+        .aggregate(vec![
+            doc! { "$unwind": "$items" },
+            doc! { "$group": { "_id": "$items.sku", "qty": { "$sum": "$items.qty" } } },
+            doc! { "$sort": { "qty": -1 } },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(result, vec![
+        doc! { "_id": "A", "qty": 7 },
+        doc! { "_id": "C", "qty": 3 },
+        doc! { "_id": "B", "qty": 1 },
+    ]);
+
+    assert!(orders.aggregate(vec![doc! { "$unwind": "items" }]).run().is_err());
+}
+
+#[test]
+fn test_aggregate_project() {
+    let db = project_prepare_db("test-aggregate-project").unwrap();
+    let people = db.collection::<Document>("people");
+    people.insert_many(vec![
+        doc! { "_id": 1, "first": "Ada", "last": "Lovelace", "price": 10, "qty": 3, "card": "1234" },
+        doc! { "_id": 2, "first": "Alan", "last": "Turing", "price": 2.5, "qty": 4, "card": "5678" },
+        doc! { "_id": 3, "first": "Grace", "price": 7, "qty": 0 },
+    ]).unwrap();
+
+    let result = people
+        .aggregate(vec![
+            doc! {
+                "$project": {
+                    "_id": 0,
+                    "first": 1,
+                    "name": { "$concat": ["$first", " ", "$last"] },
+                    "total": { "$multiply": ["$price", "$qty"] },
+                    "discounted": { "$subtract": [{ "$multiply": ["$price", "$qty"] }, 1] },
+                    "average": { "$divide": ["$price", "$qty"] },
+                    "rest": { "$mod": ["$price", 3] },
+                    "shipped": { "$add": ["$qty", 1] },
+                    "summary.kind": "person",
+                },
+            },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(result, vec![
+        doc! {
+            "first": "Ada",
+            "name": "Ada Lovelace",
+            "total": 30,
+            "discounted": 29,
+            "average": 10.0 / 3.0,
+            "rest": 1,
+            "shipped": 4,
+            "summary": { "kind": "person" },
+        },
+        doc! {
+            "first": "Alan",
+            "name": "Alan Turing",
+            "total": 10.0,
+            "discounted": 9.0,
+            "average": 0.625,
+            "rest": 2.5,
+            "shipped": 5,
+            "summary": { "kind": "person" },
+        },
+        // null for a missing field, and for a division by zero
+        doc! {
+            "first": "Grace",
+            "name": null,
+            "total": 0,
+            "discounted": -1,
+            "average": null,
+            "rest": 1,
+            "shipped": 1,
+            "summary": { "kind": "person" },
+        },
+    ]);
+
+    let result = people
+        .aggregate(vec![
+            doc! { "$project": { "card": 0, "price": false } },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(result[0], doc! { "_id": 1, "first": "Ada", "last": "Lovelace", "qty": 3 });
+    assert_eq!(result[2], doc! { "_id": 3, "first": "Grace", "qty": 0 });
+
+    assert!(people.aggregate(vec![doc! { "$project": { "first": 1, "card": 0 } }]).run().is_err());
+    assert!(people.aggregate(vec![doc! { "$project": { "total": { "$subtract": ["$price"] } } }]).run().is_err());
+}
@@ -0,0 +1,80 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use bson::{doc, Document};
+use polodb_core::{CollectionT, Result};
+use crate::common::prepare_db;
+
+mod common;
+
+/// Count the allocations of the current thread,
+/// the tests run on their own threads.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> u64 {
+    ALLOCATIONS.with(|count| count.get())
+}
+
+#[test]
+fn test_scan_reuses_buffers() {
+    let db = prepare_db("test-scan-reuses-buffers").unwrap();
+    let col = db.collection::<Document>("people");
+    col.insert_many((0..2000).map(|i| doc! {
+        "_id": i,
+        "name": format!("name-{}", i),
+        "age": i % 50,
+        "tags": ["a", "b"],
+    })).unwrap();
+
+    // the documents not matching are neither copied nor decoded
+    let before = allocations();
+    let count = col.find(doc! { "age": 100 }).run().unwrap().count();
+    assert_eq!(count, 0);
+    assert!(allocations() - before < 200);
+
+    // the documents found are moved out of the cursor
+    let before = allocations();
+    let found = col.find(doc! {})
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(found.len(), 2000);
+    assert!(allocations() - before < 2000 * 30);
+}
@@ -0,0 +1,106 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+#![cfg(feature = "arrow")]
+
+use std::sync::Arc;
+use polodb_core::CollectionT;
+use polodb_core::arrow_array::{Array, Float64Array, Int64Array, StringArray};
+use polodb_core::arrow_schema::{DataType, Field, Schema};
+use polodb_core::bson::{doc, Document};
+
+mod common;
+
+use common::prepare_db;
+
+#[test]
+fn test_to_arrow_infer_schema() {
+    let db = prepare_db("test-to-arrow-infer-schema").unwrap();
+    let collection = db.collection::<Document>("telemetry");
+
+    let docs = (0..100).map(|i| doc! {
+        "_id": i as i64,
+        "device": format!("device-{}", i % 3),
+        "value": if i % 2 == 0 { i as f64 } else { 0.5 },
+        "meta": {
+            "version": i as i64,
+        },
+    });
+    collection.insert_many(docs).unwrap();
+
+    let batches = collection
+        .to_arrow(doc! { "_id": { "$lt": 50_i64 } })
+        .batch_size(20)
+        .run()
+        .unwrap();
+    assert_eq!(batches.len(), 3);
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 50);
+
+    let schema = batches[0].schema();
+    assert_eq!(schema.field_with_name("_id").unwrap().data_type(), &DataType::Int64);
+    assert_eq!(schema.field_with_name("device").unwrap().data_type(), &DataType::Utf8);
+    assert_eq!(schema.field_with_name("value").unwrap().data_type(), &DataType::Float64);
+    assert_eq!(schema.field_with_name("meta").unwrap().data_type(), &DataType::Utf8);
+
+    let batches = collection
+        .to_arrow(doc! {})
+        .projection(["device", "meta.version"])
+        .run()
+        .unwrap();
+    assert_eq!(batches.len(), 1);
+    let batch = &batches[0];
+    assert_eq!(batch.num_columns(), 2);
+    assert_eq!(batch.num_rows(), 100);
+
+    let devices = batch.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!(devices.value(4), "device-1");
+    let versions = batch.column(1).as_any().downcast_ref::<Int64Array>().unwrap();
+    assert_eq!(versions.value(99), 99);
+}
+
+#[test]
+fn test_to_arrow_with_schema() {
+    let db = prepare_db("test-to-arrow-with-schema").unwrap();
+    let collection = db.collection::<Document>("telemetry");
+
+    collection.insert_many(vec![
+        doc! { "name": "a", "value": 1 },
+        doc! { "name": "b", "value": 2.5 },
+        doc! { "name": "c" },
+    ]).unwrap();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("value", DataType::Float64, true),
+    ]));
+    let batches = collection
+        .to_arrow(doc! {})
+        .schema(schema.clone())
+        .run()
+        .unwrap();
+    let values = batches[0].column(0).as_any().downcast_ref::<Float64Array>().unwrap();
+    assert_eq!(values.value(0), 1.0);
+    assert_eq!(values.value(1), 2.5);
+    assert!(values.is_null(2));
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("name", DataType::Int64, true),
+    ]));
+    let err = collection
+        .to_arrow(doc! {})
+        .schema(schema)
+        .run()
+        .unwrap_err();
+    assert!(err.to_string().contains("name"));
+}
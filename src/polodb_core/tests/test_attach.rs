@@ -0,0 +1,97 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Database, Error, ErrorCode, IndexModel, IndexOptions};
+
+mod common;
+
+use common::{mk_db_path, prepare_db};
+
+fn prepare_archive(name: &str) -> std::path::PathBuf {
+    let path = mk_db_path(name);
+    let _ = std::fs::remove_dir_all(&path);
+    let archive = Database::open_path(&path).unwrap();
+    let orders = archive.collection::<Document>("orders");
+    orders.create_index(IndexModel {
+        keys: doc! { "number": 1 },
+        options: Some(IndexOptions {
+            name: Some("number_unique".to_string()),
+            unique: Some(true),
+            ..Default::default()
+        }),
+    }).unwrap();
+    orders.insert_many(vec![
+        doc! { "_id": 1, "number": "A-1", "total": 12 },
+        doc! { "_id": 2, "number": "A-2", "total": 30 },
+        doc! { "_id": 3, "number": "A-3", "total": 7 },
+    ]).unwrap();
+    path
+}
+
+#[test]
+fn test_attach_and_import() {
+    let archive_path = prepare_archive("test-attach-and-import-archive");
+    let db = prepare_db("test-attach-and-import").unwrap();
+    db.collection::<Document>("orders").insert_one(doc! { "_id": 10, "number": "B-1", "total": 5 }).unwrap();
+
+    let archive = db.attach("archive", &archive_path).unwrap();
+    assert_eq!(db.list_attached(), vec!["archive".to_string()]);
+    assert_eq!(archive.collection::<Document>("orders").count_documents().unwrap(), 3);
+    let err = db.attach("archive", mk_db_path("test-attach-and-import-other")).err().unwrap();
+    assert!(matches!(err, Error::DatabaseAlreadyAttached(_)));
+    assert_eq!(err.code(), ErrorCode::AlreadyExists);
+
+    // merge the archive into the live collection, with its indexes
+    assert_eq!(db.import_attached("archive", "orders", "orders").unwrap(), 3);
+    let orders = db.collection::<Document>("orders");
+    assert_eq!(orders.count_documents().unwrap(), 4);
+    assert!(orders.describe_index("number_unique").unwrap().unwrap().is_unique());
+    assert_eq!(orders.find_one(doc! { "number": "A-2" }).unwrap().unwrap().get_i32("total").unwrap(), 30);
+
+    // into a new collection
+    assert_eq!(db.import_attached("archive", "orders", "archived_orders").unwrap(), 3);
+    assert_eq!(db.collection::<Document>("archived_orders").count_documents().unwrap(), 3);
+
+    assert!(matches!(
+        db.import_attached("archive", "customers", "customers").unwrap_err(),
+        Error::CollectionNotFound(_),
+    ));
+    assert!(matches!(
+        db.import_attached("backup", "orders", "orders").unwrap_err(),
+        Error::DatabaseNotAttached(_),
+    ));
+}
+
+#[test]
+fn test_import_is_atomic_and_detach() {
+    let archive_path = prepare_archive("test-import-is-atomic-archive");
+    let db = prepare_db("test-import-is-atomic").unwrap();
+    db.collection::<Document>("orders").insert_one(doc! { "_id": 20, "number": "A-3", "total": 1 }).unwrap();
+
+    db.attach("archive", &archive_path).unwrap();
+    let err = db.import_attached("archive", "orders", "orders").unwrap_err();
+    assert_eq!(err.code(), ErrorCode::DuplicateKey);
+    let orders = db.collection::<Document>("orders");
+    assert_eq!(orders.count_documents().unwrap(), 1);
+    assert!(orders.describe_index("number_unique").unwrap().is_none());
+
+    // the file is closed once detached, it can be opened again
+    db.detach("archive").unwrap();
+    assert!(db.list_attached().is_empty());
+    assert!(matches!(db.attached("archive").err().unwrap(), Error::DatabaseNotAttached(_)));
+    assert!(matches!(db.detach("archive").unwrap_err(), Error::DatabaseNotAttached(_)));
+    let archive = Database::open_path(&archive_path).unwrap();
+    assert_eq!(archive.collection::<Document>("orders").count_documents().unwrap(), 3);
+}
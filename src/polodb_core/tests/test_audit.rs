@@ -0,0 +1,98 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Mutex};
+use polodb_core::bson::{doc, Bson, Document};
+use polodb_core::{AuditEntry, CollectionT, Error, OperationType, DEFAULT_AUDIT_COLLECTION};
+
+mod common;
+
+use common::prepare_db;
+
+#[test]
+fn test_audit_collection() {
+    let db = prepare_db("test-audit-collection").unwrap();
+    db.audit().set_user(Some("system".to_string()));
+    db.audit().enable();
+
+    let records = db.collection::<Document>("records");
+    records.insert_one(doc! { "_id": 1, "name": "Alice" }).unwrap();
+
+    let txn = db.start_transaction().unwrap();
+    txn.set_user(Some("dr_bob".to_string()));
+    txn.collection::<Document>("records")
+        .update_one(doc! { "_id": 1 }, doc! { "$set": { "name": "Alicia" } })
+        .unwrap();
+    txn.commit().unwrap();
+
+    let txn = db.start_transaction().unwrap();
+    txn.collection::<Document>("records").delete_one(doc! { "_id": 1 }).unwrap();
+    txn.rollback().unwrap();
+
+    let audit = db.collection::<Document>(DEFAULT_AUDIT_COLLECTION);
+    let entries: Vec<Document> = audit.find(doc! {}).run().unwrap()
+        .map(|entry| entry.unwrap())
+        .collect();
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(entries[0].get_str("op").unwrap(), "insert");
+    assert_eq!(entries[0].get_str("ns").unwrap(), "records");
+    assert_eq!(entries[0].get_str("user").unwrap(), "system");
+    assert_eq!(entries[0].get("documentKey"), Some(&Bson::Int32(1)));
+    assert!(entries[0].get("before").is_none());
+    assert_eq!(entries[0].get_document("after").unwrap(), &doc! { "_id": 1, "name": "Alice" });
+
+    assert_eq!(entries[1].get_str("op").unwrap(), "update");
+    assert_eq!(entries[1].get_str("user").unwrap(), "dr_bob");
+    assert_eq!(entries[1].get_document("before").unwrap(), &doc! { "_id": 1, "name": "Alice" });
+    assert_eq!(entries[1].get_document("after").unwrap(), &doc! { "_id": 1, "name": "Alicia" });
+
+    assert!(matches!(
+        audit.delete_many(doc! {}),
+        Err(Error::AuditLogAppendOnly(_)),
+    ));
+    assert!(matches!(
+        audit.update_many(doc! {}, doc! { "$set": { "user": "nobody" } }),
+        Err(Error::AuditLogAppendOnly(_)),
+    ));
+}
+
+#[test]
+fn test_audit_callback() {
+    let db = prepare_db("test-audit-callback").unwrap();
+    let entries = Arc::new(Mutex::new(Vec::<AuditEntry>::new()));
+    {
+        let entries = entries.clone();
+        db.audit().set_callback(move |entry| {
+            entries.lock().unwrap().push(entry.clone());
+        });
+    }
+    db.audit().set_after_images(false);
+    db.audit().enable();
+
+    let records = db.collection::<Document>("records");
+    records.insert_one(doc! { "_id": 1, "n": 1 }).unwrap();
+    records.delete_one(doc! { "_id": 1 }).unwrap();
+
+    db.audit().disable();
+    records.insert_one(doc! { "_id": 2 }).unwrap();
+
+    let entries = entries.lock().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].op, OperationType::Insert);
+    assert_eq!(entries[0].after, None);
+    assert_eq!(entries[1].op, OperationType::Delete);
+    assert_eq!(entries[1].before, Some(doc! { "_id": 1, "n": 1 }));
+    assert_eq!(entries[1].user, None);
+}
@@ -0,0 +1,112 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Database, IndexModel, IndexOptions};
+use polodb_core::options::CreateCollectionOptions;
+
+mod common;
+
+use common::{mk_db_path, prepare_db};
+
+#[test]
+fn test_bloom_filter_on_id() {
+    let db_path = mk_db_path("test-bloom-filter-id");
+    let _ = std::fs::remove_dir_all(db_path.as_path());
+
+    {
+        let db = Database::open_path(db_path.as_path()).unwrap();
+        db.create_collection_with_options("events", CreateCollectionOptions::builder()
+            .bloom_filter("_id")
+            .build()
+        ).unwrap();
+        let events = db.collection::<Document>("events");
+        events.insert_many((0..200).map(|i| doc! { "_id": format!("event-{}", i) })).unwrap();
+
+        let metrics = db.metrics();
+        metrics.enable();
+
+        assert!(events.find_by_id("event-42").unwrap().is_some());
+        for i in 200..1200 {
+            assert!(!events.exists(doc! { "_id": format!("event-{}", i) }).unwrap());
+        }
+        assert!(metrics.bloom_filter_skips() > 950);
+
+        // the documents written by a transaction are found by the transaction
+        let txn = db.start_transaction().unwrap();
+        let txn_events = txn.collection::<Document>("events");
+        txn_events.insert_one(doc! { "_id": "event-5000" }).unwrap();
+        assert!(txn_events.find_by_id("event-5000").unwrap().is_some());
+        txn.commit().unwrap();
+        assert!(events.find_by_id("event-5000").unwrap().is_some());
+    }
+
+    // the filter is built again when the database is opened
+    let db = Database::open_path(db_path.as_path()).unwrap();
+    let metrics = db.metrics();
+    metrics.enable();
+
+    let events = db.collection::<Document>("events");
+    assert!(events.find_by_id("event-199").unwrap().is_some());
+    assert!(events.find_by_id("event-5000").unwrap().is_some());
+    assert!(events.find_by_id("event-6000").unwrap().is_none());
+
+    events.rename("archived-events").unwrap();
+    let archived = db.collection::<Document>("archived-events");
+    archived.insert_one(doc! { "_id": "event-7000" }).unwrap();
+    assert!(archived.find_by_id("event-7000").unwrap().is_some());
+    for i in 8000..8100 {
+        assert!(archived.find_by_id(format!("event-{}", i)).unwrap().is_none());
+    }
+    assert!(metrics.bloom_filter_skips() > 90);
+}
+
+#[test]
+fn test_bloom_filter_on_unique_key() {
+    let db = prepare_db("test-bloom-filter-key").unwrap();
+    db.create_collection_with_options("users", CreateCollectionOptions::builder()
+        .bloom_filter("email")
+        .build()
+    ).unwrap();
+    let users = db.collection::<Document>("users");
+    users.create_index(IndexModel {
+        keys: doc! { "email": 1 },
+        options: Some(IndexOptions {
+            unique: Some(true),
+            ..Default::default()
+        }),
+    }).unwrap();
+    users.insert_many((0..100).map(|i| doc! {
+        "_id": i,
+        "email": format!("user{}@example.com", i),
+    })).unwrap();
+
+    let metrics = db.metrics();
+    metrics.enable();
+
+    assert!(users.exists(doc! { "email": "user7@example.com" }).unwrap());
+    for i in 100..300 {
+        assert!(!users.exists(doc! { "email": format!("user{}@example.com", i) }).unwrap());
+    }
+    let skips = metrics.bloom_filter_skips();
+    assert!(skips > 190);
+
+    // the updated values are added to the filter
+    users.update_one(
+        doc! { "_id": 3 },
+        doc! { "$set": { "email": "new@example.com" } },
+    ).unwrap();
+    let user = users.find_one(doc! { "email": "new@example.com" }).unwrap().unwrap();
+    assert_eq!(user.get_i32("_id").unwrap(), 3);
+}
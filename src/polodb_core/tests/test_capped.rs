@@ -0,0 +1,103 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Error, IndexModel};
+use polodb_core::options::CreateCollectionOptions;
+
+mod common;
+
+use common::prepare_db;
+
+fn ids(docs: Vec<Document>) -> Vec<i32> {
+    docs.iter().map(|doc| doc.get_i32("_id").unwrap()).collect()
+}
+
+#[test]
+fn test_capped_max_documents() {
+    let db = prepare_db("test-capped-max").unwrap();
+    db.create_collection_with_options("logs", CreateCollectionOptions::builder()
+        .capped(true)
+        .max(3)
+        .build()
+    ).unwrap();
+    let logs = db.collection::<Document>("logs");
+    logs.create_index(IndexModel {
+        keys: doc! { "level": 1 },
+        options: None,
+    }).unwrap();
+
+    for i in 0..5 {
+        logs.insert_one(doc! { "_id": i, "level": "info" }).unwrap();
+    }
+    logs.insert_many(vec![
+        doc! { "_id": 5, "level": "warn" },
+        doc! { "_id": 6, "level": "info" },
+    ]).unwrap();
+
+    let docs: Vec<Document> = logs.find(doc! {}).run().unwrap().map(|doc| doc.unwrap()).collect();
+    assert_eq!(ids(docs), vec![4, 5, 6]);
+
+    // the index entries of the removed documents are removed too
+    let docs: Vec<Document> = logs.find(doc! { "level": "info" }).run().unwrap().map(|doc| doc.unwrap()).collect();
+    assert_eq!(ids(docs), vec![4, 6]);
+}
+
+#[test]
+fn test_capped_size() {
+    let db = prepare_db("test-capped-size").unwrap();
+    let one_doc_size = polodb_core::bson::to_vec(&doc! { "_id": 0, "msg": "0123456789" }).unwrap().len() as u64;
+    db.create_collection_with_options("logs", CreateCollectionOptions::builder()
+        .capped(true)
+        .size(one_doc_size * 2)
+        .build()
+    ).unwrap();
+    let logs = db.collection::<Document>("logs");
+    for i in 0..4 {
+        logs.insert_one(doc! { "_id": i, "msg": "0123456789" }).unwrap();
+    }
+    assert_eq!(logs.count_documents().unwrap(), 2);
+    let docs: Vec<Document> = logs.find(doc! {}).run().unwrap().map(|doc| doc.unwrap()).collect();
+    assert_eq!(ids(docs), vec![2, 3]);
+}
+
+#[test]
+fn test_capped_restrictions() {
+    let db = prepare_db("test-capped-restrictions").unwrap();
+    assert!(matches!(
+        db.create_collection_with_options("logs", CreateCollectionOptions::builder().capped(true).build()),
+        Err(Error::CappedCollection(_)),
+    ));
+    db.create_collection_with_options("logs", CreateCollectionOptions::builder()
+        .capped(true)
+        .max(10)
+        .build()
+    ).unwrap();
+    let logs = db.collection::<Document>("logs");
+    logs.insert_one(doc! { "_id": 1, "msg": "abc" }).unwrap();
+
+    assert!(matches!(logs.delete_one(doc! { "_id": 1 }), Err(Error::CappedCollection(_))));
+    assert!(matches!(logs.delete_many(doc! {}), Err(Error::CappedCollection(_))));
+    assert!(matches!(
+        logs.update_one(doc! { "_id": 1 }, doc! { "$set": { "msg": "abcdef" } }),
+        Err(Error::CappedCollection(_)),
+    ));
+    logs.update_one(doc! { "_id": 1 }, doc! { "$set": { "msg": "xyz" } }).unwrap();
+
+    logs.drop().unwrap();
+    db.create_collection("logs").unwrap();
+    let logs = db.collection::<Document>("logs");
+    logs.insert_one(doc! { "_id": 1 }).unwrap();
+    logs.delete_one(doc! { "_id": 1 }).unwrap();
+}
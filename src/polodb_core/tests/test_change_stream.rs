@@ -0,0 +1,250 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::thread;
+use std::time::Duration;
+use polodb_core::bson::{doc, Bson, Document};
+use polodb_core::{CollectionT, ConfigBuilder, Database, Error, OperationType, Result};
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+use common::{prepare_db, prepare_db_with_config};
+
+#[test]
+fn test_watch_collection() {
+    let db = prepare_db("test-watch-collection").unwrap();
+    let items = db.collection::<Document>("items");
+    let mut stream = items.watch().run().unwrap();
+    let mut full_stream = items.watch().full_document(true).run().unwrap();
+
+    items.insert_one(doc! { "_id": 1, "name": "apple", "color": "red" }).unwrap();
+    items.update_one(doc! { "_id": 1 }, doc! {
+        "$set": { "name": "pear" },
+        "$unset": { "color": "" },
+    }).unwrap();
+    items.delete_one(doc! { "_id": 1 }).unwrap();
+    db.collection::<Document>("others").insert_one(doc! { "_id": 2 }).unwrap();
+
+    let event = stream.try_next().unwrap().unwrap();
+    assert_eq!(event.operation_type, OperationType::Insert);
+    assert_eq!(event.collection, "items");
+    assert_eq!(event.document_key, Bson::Int32(1));
+    assert_eq!(event.full_document, Some(doc! { "_id": 1, "name": "apple", "color": "red" }));
+
+    let event = stream.try_next().unwrap().unwrap();
+    assert_eq!(event.operation_type, OperationType::Update);
+    assert_eq!(event.full_document, None);
+    let delta = event.update_description.unwrap();
+    assert_eq!(delta.updated_fields, doc! { "name": "pear" });
+    assert_eq!(delta.removed_fields, vec!["color".to_string()]);
+
+    let event = stream.try_next().unwrap().unwrap();
+    assert_eq!(event.operation_type, OperationType::Delete);
+    assert_eq!(event.document_key, Bson::Int32(1));
+    assert!(stream.try_next().is_none());
+
+    let _ = full_stream.try_next().unwrap().unwrap();
+    let event = full_stream.try_next().unwrap().unwrap();
+    assert_eq!(event.full_document, Some(doc! { "_id": 1, "name": "pear" }));
+}
+
+#[test]
+fn test_watch_only_committed() {
+    let db = prepare_db("test-watch-only-committed").unwrap();
+    let mut stream = db.watch().run().unwrap();
+
+    let txn = db.start_transaction().unwrap();
+    let items = txn.collection::<Document>("items");
+    items.insert_one(doc! { "_id": 1 }).unwrap();
+    assert!(stream.try_next().is_none());
+    txn.rollback().unwrap();
+    assert!(stream.try_next().is_none());
+
+    let txn = db.start_transaction().unwrap();
+    let items = txn.collection::<Document>("items");
+    items.insert_one(doc! { "_id": 2 }).unwrap();
+    items.insert_one(doc! { "_id": 3 }).unwrap();
+    txn.commit().unwrap();
+
+    let keys: Vec<Bson> = std::iter::from_fn(|| stream.try_next())
+        .map(|event| event.unwrap().document_key)
+        .collect();
+    assert_eq!(keys, vec![Bson::Int32(2), Bson::Int32(3)]);
+}
+
+#[test]
+fn test_watch_blocking() {
+    let db = prepare_db("test-watch-blocking").unwrap();
+    let mut stream = db.collection::<Document>("items").watch().run().unwrap();
+    assert!(stream.next_timeout(Duration::from_millis(10)).is_none());
+
+    let writer = {
+        let items = db.collection::<Document>("items");
+        thread::spawn(move || {
+            items.insert_one(doc! { "_id": 1 }).unwrap();
+        })
+    };
+
+    let event = stream.next().unwrap().unwrap();
+    assert_eq!(event.document_key, Bson::Int32(1));
+    writer.join().unwrap();
+}
+
+#[test]
+fn test_watch_across_handles() {
+    let db = prepare_db("test-watch-across-handles").unwrap();
+    let mut stream = db.collection::<Document>("items").watch().full_document(true).run().unwrap();
+
+    let writers = (0..4).map(|i| {
+        let db = db.clone();
+        thread::spawn(move || {
+            let items = db.collection::<Document>("items");
+            items.insert_one(doc! { "_id": i, "n": 0 }).unwrap();
+            items.update_one(doc! { "_id": i }, doc! { "$set": { "n": 1 } }).unwrap();
+        })
+    }).collect::<Vec<_>>();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let events: Vec<_> = std::iter::from_fn(|| stream.try_next()).collect::<Result<_>>().unwrap();
+    assert_eq!(events.len(), 8);
+    for i in 0..4 {
+        let updated = events.iter()
+            .find(|event| event.document_key == Bson::Int32(i) && event.operation_type == OperationType::Update)
+            .unwrap();
+        assert_eq!(updated.full_document, Some(doc! { "_id": i, "n": 1 }));
+    }
+}
+
+#[test]
+fn test_watch_resume_after_restart() {
+    let mut config = ConfigBuilder::new();
+    config.set_oplog_size(100);
+    let db = prepare_db_with_config("test-watch-resume", config.take()).unwrap();
+    let db_path = common::mk_db_path("test-watch-resume");
+    let token = {
+        let mut stream = db.watch().run().unwrap();
+        let items = db.collection::<Document>("items");
+        items.insert_one(doc! { "_id": 1 }).unwrap();
+        let token = stream.try_next().unwrap().unwrap().resume_token.unwrap();
+        items.update_one(doc! { "_id": 1 }, doc! { "$set": { "n": 1 } }).unwrap();
+        items.delete_one(doc! { "_id": 1 }).unwrap();
+        token
+    };
+    drop(db);
+
+    let mut config = ConfigBuilder::new();
+    config.set_oplog_size(100);
+    let db = Database::open_path_with_config(&db_path, config.take()).unwrap();
+    let mut stream = db.watch().resume_after(token).run().unwrap();
+    db.collection::<Document>("items").insert_one(doc! { "_id": 2 }).unwrap();
+
+    let events: Vec<_> = std::iter::from_fn(|| stream.try_next()).collect::<Result<_>>().unwrap();
+    let operations: Vec<_> = events.iter().map(|event| event.operation_type).collect();
+    assert_eq!(operations, vec![OperationType::Update, OperationType::Delete, OperationType::Insert]);
+    assert_eq!(events[0].update_description.as_ref().unwrap().updated_fields, doc! { "n": 1 });
+    let tokens: Vec<_> = events.iter().map(|event| event.resume_token.unwrap()).collect();
+    assert_eq!(tokens, vec![token + 1, token + 2, token + 3]);
+}
+
+#[test]
+fn test_oplog_capped() {
+    let mut config = ConfigBuilder::new();
+    config.set_oplog_size(2);
+    let db = prepare_db_with_config("test-oplog-capped", config.take()).unwrap();
+    let items = db.collection::<Document>("items");
+    for i in 0..5 {
+        items.insert_one(doc! { "_id": i }).unwrap();
+    }
+
+    let mut stream = db.watch().resume_after(3).run().unwrap();
+    let event = stream.try_next().unwrap().unwrap();
+    assert_eq!(event.document_key, Bson::Int32(3));
+    let event = stream.try_next().unwrap().unwrap();
+    assert_eq!(event.document_key, Bson::Int32(4));
+    assert!(stream.try_next().is_none());
+
+    assert!(matches!(
+        db.watch().resume_after(1).run(),
+        Err(Error::ChangeStreamHistoryLost(1)),
+    ));
+    assert!(matches!(
+        prepare_db("test-oplog-disabled").unwrap().watch().resume_after(0).run(),
+        Err(Error::ChangeStreamHistoryLost(0)),
+    ));
+}
+
+#[test]
+fn test_changes_since() {
+    let mut config = ConfigBuilder::new();
+    config.set_oplog_size(100);
+    let db = prepare_db_with_config("test-changes-since", config.take()).unwrap();
+    let items = db.collection::<Document>("items");
+    items.insert_one(doc! { "_id": 1 }).unwrap();
+    items.update_one(doc! { "_id": 1 }, doc! { "$set": { "n": 1 } }).unwrap();
+
+    let batch = db.changes_since(0).unwrap();
+    let operations: Vec<_> = batch.changes.iter().map(|event| event.operation_type).collect();
+    assert_eq!(operations, vec![OperationType::Insert, OperationType::Update]);
+    assert_eq!(batch.changes[1].full_document, Some(doc! { "_id": 1, "n": 1 }));
+    assert_eq!(batch.resume_token, batch.changes[1].resume_token.unwrap());
+
+    // nothing new
+    let empty = db.changes_since(batch.resume_token).unwrap();
+    assert!(empty.changes.is_empty());
+    assert_eq!(empty.resume_token, batch.resume_token);
+
+    // only the committed changes
+    let txn = db.start_transaction().unwrap();
+    txn.collection::<Document>("items").insert_one(doc! { "_id": 2 }).unwrap();
+    assert!(db.changes_since(batch.resume_token).unwrap().changes.is_empty());
+    txn.commit().unwrap();
+    items.delete_one(doc! { "_id": 1 }).unwrap();
+    let next = db.changes_since(batch.resume_token).unwrap();
+    let keys: Vec<_> = next.changes.iter().map(|event| event.document_key.clone()).collect();
+    assert_eq!(keys, vec![Bson::Int32(2), Bson::Int32(1)]);
+
+    assert!(matches!(
+        prepare_db("test-changes-since-disabled").unwrap().changes_since(0),
+        Err(Error::ChangeStreamHistoryLost(0)),
+    ));
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Fruit {
+    #[serde(rename = "_id")]
+    id: i32,
+    name: String,
+}
+
+#[test]
+fn test_watch_typed() {
+    let db = prepare_db("test-watch-typed").unwrap();
+    let fruits = db.collection::<Fruit>("fruits");
+    let mut stream = fruits.watch().run().unwrap();
+    let mut raw_stream = fruits.watch().with_type::<Document>().run().unwrap();
+
+    fruits.insert_one(Fruit { id: 1, name: "apple".into() }).unwrap();
+    db.collection::<Document>("fruits").insert_one(doc! { "_id": 2, "color": "red" }).unwrap();
+
+    let event = stream.try_next().unwrap().unwrap();
+    assert_eq!(event.full_document, Some(Fruit { id: 1, name: "apple".into() }));
+    assert!(stream.try_next().unwrap().is_err());
+
+    let _ = raw_stream.try_next().unwrap().unwrap();
+    let event = raw_stream.try_next().unwrap().unwrap();
+    assert_eq!(event.full_document, Some(doc! { "_id": 2, "color": "red" }));
+}
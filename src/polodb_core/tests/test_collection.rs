@@ -12,8 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use polodb_core::bson::{Document, doc};
-use polodb_core::{CollectionT, Result};
+use polodb_core::bson::{Bson, Document, doc};
+use polodb_core::{CollectionT, IndexModel, Result};
+use polodb_core::options::CreateCollectionOptions;
 mod common;
 
 use common::{
@@ -190,3 +191,133 @@ fn test_query_embedded_document() {
     });
 
 }
+
+#[test]
+fn test_list_collections() {
+    let db = prepare_db("test-list-collections").unwrap();
+    db.create_collection_with_options("logs", CreateCollectionOptions::builder()
+        .capped(true)
+        .max(100)
+        .validator(doc! { "$jsonSchema": { "required": ["msg"] } })
+        .build()
+    ).unwrap();
+    let logs = db.collection::<Document>("logs");
+    logs.create_index(IndexModel {
+        keys: doc! { "level": 1 },
+        options: None,
+    }).unwrap();
+    logs.insert_many(vec![
+        doc! { "msg": "a", "level": 1 },
+        doc! { "msg": "b", "level": 2 },
+    ]).unwrap();
+    db.collection::<Document>("plain").insert_one(doc! { "x": 1 }).unwrap();
+    db.add_validator("plain", |_| Ok(()));
+
+    let collections = db.list_collections().unwrap();
+    assert_eq!(collections.len(), 2);
+
+    let logs = &collections[0];
+    assert_eq!(logs.get_str("name").unwrap(), "logs");
+    assert_eq!(logs.get_str("type").unwrap(), "collection");
+    assert_eq!(logs.get_i64("nindexes").unwrap(), 1);
+    assert_eq!(logs.get_i64("count").unwrap(), 2);
+    assert_eq!(logs.get_i64("nativeValidators").unwrap(), 0);
+    let options = logs.get_document("options").unwrap();
+    assert!(options.get_bool("capped").unwrap());
+    assert_eq!(options.get_i64("max").unwrap(), 100);
+    assert_eq!(options.get_str("validationLevel").unwrap(), "strict");
+    assert!(options.get_document("validator").is_ok());
+    let info = logs.get_document("info").unwrap();
+    assert!(info.get_datetime("createdAt").is_ok());
+    assert!(matches!(info.get("uuid"), Some(Bson::Binary(_))));
+
+    let plain = &collections[1];
+    assert_eq!(plain.get_str("name").unwrap(), "plain");
+    assert!(plain.get_document("options").unwrap().is_empty());
+    assert_eq!(plain.get_i64("nindexes").unwrap(), 0);
+    assert_eq!(plain.get_i64("nativeValidators").unwrap(), 1);
+    assert_eq!(plain.get_i64("count").unwrap(), 1);
+}
+
+#[test]
+fn test_rename_collection() {
+    let db = prepare_db("test-rename-collection").unwrap();
+    db.create_collection_with_options("old", CreateCollectionOptions::builder()
+        .capped(true)
+        .max(2)
+        .default_sequence("seq")
+        .build()
+    ).unwrap();
+    db.add_validator("old", |doc| {
+        if doc.contains_key("bad") { Err("bad".to_string()) } else { Ok(()) }
+    });
+    let old = db.collection::<Document>("old");
+    old.create_index(IndexModel {
+        keys: doc! { "name": 1 },
+        options: None,
+    }).unwrap();
+    for name in ["a", "b", "c"] {
+        old.insert_one(doc! { "name": name }).unwrap();
+    }
+    db.collection::<Document>("taken").insert_one(doc! {}).unwrap();
+
+    assert!(matches!(
+        db.rename_collection("old", "taken"),
+        Err(polodb_core::Error::CollectionAlreadyExits(_))
+    ));
+    old.rename("new").unwrap();
+
+    assert_eq!(db.list_collection_names().unwrap(), vec!["new".to_string(), "taken".to_string()]);
+    assert_eq!(old.count_documents().unwrap(), 0);
+
+    let new = db.collection::<Document>("new");
+    assert_eq!(new.list_index_names().unwrap().len(), 1);
+    let doc = new.find_one(doc! { "name": "c" }).unwrap().unwrap();
+    assert_eq!(doc.get_i64("seq").unwrap(), 3);
+    assert!(new.find_one(doc! { "name": "a" }).unwrap().is_none());
+
+    // the capped state, the sequence and the validator follow the collection
+    new.insert_one(doc! { "name": "d" }).unwrap();
+    let names: Vec<String> = new.find(doc! {}).run().unwrap()
+        .map(|doc| doc.unwrap().get_str("name").unwrap().to_string())
+        .collect();
+    assert_eq!(names, vec!["c", "d"]);
+    assert_eq!(new.find_one(doc! { "name": "d" }).unwrap().unwrap().get_i64("seq").unwrap(), 4);
+    assert!(new.insert_one(doc! { "name": "e", "bad": true }).is_err());
+}
+
+#[test]
+fn test_clone_collection() {
+    let db = prepare_db("test-clone-collection").unwrap();
+    let source = db.collection::<Document>("source");
+    source.create_index(IndexModel {
+        keys: doc! { "name": 1 },
+        options: None,
+    }).unwrap();
+    source.insert_many((0..100).map(|i| doc! { "_id": i, "name": format!("n{}", i) })).unwrap();
+
+    assert_eq!(source.clone_to("copy").run().unwrap(), 100);
+    assert_eq!(source.clone_to("bare").indexes(false).run().unwrap(), 100);
+    assert!(matches!(
+        source.clone_to("copy").run(),
+        Err(polodb_core::Error::CollectionAlreadyExits(_))
+    ));
+
+    let copy = db.collection::<Document>("copy");
+    assert_eq!(copy.count_documents().unwrap(), 100);
+    assert_eq!(copy.list_index_names().unwrap(), source.list_index_names().unwrap());
+    assert_eq!(copy.find_one(doc! { "name": "n42" }).unwrap().unwrap().get_i32("_id").unwrap(), 42);
+    assert!(db.collection::<Document>("bare").list_index_names().unwrap().is_empty());
+
+    // the copy is independent from the source
+    copy.delete_many(doc! { "name": "n42" }).unwrap();
+    assert_eq!(copy.count_documents().unwrap(), 99);
+    assert_eq!(source.count_documents().unwrap(), 100);
+    assert!(source.find_one(doc! { "name": "n42" }).unwrap().is_some());
+
+    // in a transaction
+    let txn = db.start_transaction().unwrap();
+    txn.collection::<Document>("source").clone_to("rolled_back").run().unwrap();
+    txn.rollback().unwrap();
+    assert!(!db.list_collection_names().unwrap().contains(&"rolled_back".to_string()));
+}
@@ -0,0 +1,85 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Error};
+
+mod common;
+
+use common::prepare_db;
+
+#[test]
+fn test_kill_query() {
+    let db = prepare_db("test-kill-query").unwrap();
+    let col = db.collection::<Document>("items");
+    col.insert_many((0..100).map(|i| doc! { "i": i })).unwrap();
+
+    assert!(db.current_ops().is_empty());
+
+    let mut cursor = col.find(doc! { "i": { "$gte": 0 } }).run().unwrap();
+    assert!(cursor.next().unwrap().is_ok());
+
+    let ops = db.current_ops();
+    assert_eq!(ops.len(), 1);
+    assert_eq!(ops[0].op, "find");
+    assert_eq!(ops[0].collection.as_deref(), Some("items"));
+    assert_eq!(ops[0].filter, Some(doc! { "i": { "$gte": 0 } }));
+    assert_eq!(ops[0].comment, None);
+
+    ops[0].kill();
+    assert!(matches!(cursor.next(), Some(Err(Error::OperationKilled))));
+    assert!(cursor.next().is_none());
+
+    drop(cursor);
+    assert!(db.current_ops().is_empty());
+}
+
+#[test]
+fn test_kill_transaction() {
+    let db = prepare_db("test-kill-transaction").unwrap();
+    let txn = db.start_transaction().unwrap();
+    let col = txn.collection::<Document>("items");
+    col.insert_one(doc! { "i": 0 }).unwrap();
+
+    let ops = db.current_ops();
+    assert_eq!(ops.len(), 1);
+    assert_eq!(ops[0].op, "transaction");
+    assert!(db.kill_op(ops[0].id));
+
+    assert!(matches!(col.insert_one(doc! { "i": 1 }), Err(Error::OperationKilled)));
+    assert!(matches!(txn.commit(), Err(Error::OperationKilled)));
+    txn.rollback().unwrap();
+    drop(col);
+    drop(txn);
+
+    assert!(db.current_ops().is_empty());
+    assert!(!db.kill_op(ops[0].id));
+    assert_eq!(db.collection::<Document>("items").count_documents().unwrap(), 0);
+}
+
+#[test]
+fn test_current_op_comment() {
+    let db = prepare_db("test-current-op-comment").unwrap();
+    let col = db.collection::<Document>("items");
+    col.insert_many((0..10).map(|i| doc! { "i": i })).unwrap();
+
+    let mut cursor = col.find(doc! { "i": { "$gte": 0 }, "$comment": "search-page" }).run().unwrap();
+    assert!(cursor.next().unwrap().is_ok());
+
+    let ops = db.current_ops();
+    assert_eq!(ops.len(), 1);
+    assert_eq!(ops[0].comment, Some("search-page".into()));
+    assert_eq!(cursor.count(), 9);
+}
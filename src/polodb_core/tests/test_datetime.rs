@@ -0,0 +1,104 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::CollectionT;
+use polodb_core::bson::{doc, DateTime, Document};
+
+mod common;
+
+use common::prepare_db;
+
+#[test]
+fn test_datetime_order() {
+    let db = prepare_db("test-datetime-order").unwrap();
+    let col = db.collection::<Document>("events");
+    col.create_index(polodb_core::IndexModel {
+        keys: doc! { "at": 1 },
+        options: None,
+    }).unwrap();
+
+    // the dates before 1970 are negative
+    let dates = [-86_400_000i64, -1, 0, 1_700_000_000_000];
+    col.insert_many(dates.iter().rev().map(|millis| doc! {
+        "_id": DateTime::from_millis(*millis),
+        "at": DateTime::from_millis(*millis),
+    })).unwrap();
+
+    let ids = col.find(doc! {}).run().unwrap()
+        .map(|doc| doc.unwrap().get_datetime("_id").unwrap().timestamp_millis())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, dates);
+
+    let found = col.find_one(doc! { "at": DateTime::from_millis(-1) }).unwrap().unwrap();
+    assert_eq!(found.get_datetime("_id").unwrap().timestamp_millis(), -1);
+
+    let before = col.find(doc! { "at": { "$lt": DateTime::from_millis(0) } }).run().unwrap().count();
+    assert_eq!(before, 2);
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn test_chrono_datetime() {
+    use chrono::{TimeZone, Utc};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Event {
+        name: String,
+        #[serde(with = "polodb_core::chrono_as_datetime")]
+        at: chrono::DateTime<Utc>,
+    }
+
+    let db = prepare_db("test-chrono-datetime").unwrap();
+    let col = db.collection::<Event>("events");
+    col.insert_many((1..=3).map(|day| Event {
+        name: format!("event-{}", day),
+        at: Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap(),
+    })).unwrap();
+
+    let since = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+    let events = col.find(doc! { "at": { "$gte": since } }).sort(doc! { "at": -1 }).run().unwrap()
+        .collect::<polodb_core::Result<Vec<Event>>>()
+        .unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].name, "event-3");
+    assert_eq!(events[1].at, since);
+}
+
+#[cfg(feature = "time")]
+#[test]
+fn test_time_datetime() {
+    use serde::{Deserialize, Serialize};
+    use time::OffsetDateTime;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Event {
+        name: String,
+        #[serde(with = "polodb_core::time_as_datetime")]
+        at: OffsetDateTime,
+    }
+
+    let db = prepare_db("test-time-datetime").unwrap();
+    let col = db.collection::<Event>("events");
+    let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+    col.insert_many((0..3).map(|i| Event {
+        name: format!("event-{}", i),
+        at: start + time::Duration::hours(i),
+    })).unwrap();
+
+    let event = col.find_one(doc! { "at": start + time::Duration::hours(1) }).unwrap().unwrap();
+    assert_eq!(event.name, "event-1");
+    let count = col.find(doc! { "at": { "$gt": start } }).run().unwrap().count();
+    assert_eq!(count, 2);
+}
@@ -14,7 +14,8 @@
 
 use polodb_core::Database;
 use polodb_core::bson::{doc, Document};
-use polodb_core::CollectionT;
+use polodb_core::{CollectionT, ConfigBuilder, Error, IndexModel, StorageLayout};
+use polodb_core::options::{DeleteOptions, Durability, InsertManyOptions, UpdateOptions};
 
 mod common;
 
@@ -74,3 +75,248 @@ fn test_multi_threads() {
     assert_eq!(one.get("content").unwrap().as_str().unwrap(), "Hello");
 }
 
+
+#[test]
+fn test_drop_database() {
+    let db_path = mk_db_path("test-drop-database");
+
+    let _ = std::fs::remove_dir_all(db_path.as_path());
+
+    {
+        let db = Database::open_path(db_path.as_path().to_str().unwrap()).unwrap();
+        let books = db.collection::<Document>("books");
+        books.create_index(IndexModel {
+            keys: doc! { "title": 1 },
+            options: None,
+        }).unwrap();
+        books.insert_one(doc! { "title": "The Three-Body Problem" }).unwrap();
+        db.collection::<Document>("authors").insert_one(doc! { "name": "Liu Cixin" }).unwrap();
+
+        db.drop().unwrap();
+        assert!(db.list_collection_names().unwrap().is_empty());
+        assert!(books.find_one(doc! { "title": "The Three-Body Problem" }).unwrap().is_none());
+
+        // the database can be used again
+        books.insert_one(doc! { "title": "The Dark Forest" }).unwrap();
+        assert!(books.list_index_names().unwrap().is_empty());
+    }
+
+    {
+        let db = Database::open_path(db_path.as_path().to_str().unwrap()).unwrap();
+        assert_eq!(db.list_collection_names().unwrap(), vec!["books".to_string()]);
+        assert_eq!(db.collection::<Document>("books").count_documents().unwrap(), 1);
+    }
+}
+
+#[test]
+fn test_preload_and_pin() {
+    let db_path = mk_db_path("test-preload-and-pin");
+
+    let _ = std::fs::remove_dir_all(db_path.as_path());
+
+    {
+        let db = Database::open_path(db_path.as_path()).unwrap();
+        let settings = db.collection::<Document>("settings");
+        settings.create_index(IndexModel {
+            keys: doc! { "key": 1 },
+            options: None,
+        }).unwrap();
+        settings.insert_many((0..20).map(|i| doc! {
+            "_id": i,
+            "key": format!("key-{}", i),
+        })).unwrap();
+    }
+
+    let db = Database::open_path(db_path.as_path()).unwrap();
+    assert_eq!(db.preload("settings").unwrap(), 40);
+    assert_eq!(db.preload("settings.key_1").unwrap(), 20);
+    assert!(matches!(db.preload("settings.value_1"), Err(Error::IndexNotFound(_))));
+    assert!(matches!(db.preload("users"), Err(Error::CollectionNotFound(_))));
+
+    assert_eq!(db.pin_collection("settings").unwrap(), 40);
+    let settings = db.collection::<Document>("settings");
+    settings.insert_one(doc! { "_id": 20, "key": "key-20" }).unwrap();
+    db.compact().unwrap();
+    assert_eq!(settings.find(doc! { "key": "key-20" }).run().unwrap().count(), 1);
+    assert_eq!(settings.count_documents().unwrap(), 21);
+
+    assert!(db.unpin_collection("settings").unwrap());
+    assert!(!db.unpin_collection("settings").unwrap());
+
+    // the pins are released when the database is closed
+    db.pin_collection("settings").unwrap();
+}
+
+/// The size of the files of the database, with the ones of its subdirectories.
+fn dir_size(path: &std::path::Path) -> u64 {
+    std::fs::read_dir(path).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .map(|path| if path.is_dir() { dir_size(&path) } else { path.metadata().unwrap().len() })
+        .sum()
+}
+
+#[test]
+fn test_drop_pinned_collection() {
+    let db_path = mk_db_path("test-drop-pinned-collection");
+
+    let _ = std::fs::remove_dir_all(db_path.as_path());
+
+    let db = Database::open_path(db_path.as_path()).unwrap();
+    let logs = db.collection::<Document>("logs");
+    logs.insert_many((0..2000).map(|i| doc! {
+        "_id": i,
+        "message": format!("{}-{}", i, "x".repeat(1024)),
+    })).unwrap();
+    db.collection::<Document>("settings").insert_one(doc! { "_id": 1, "theme": "dark" }).unwrap();
+    db.compact().unwrap();
+    let size = dir_size(db_path.as_path());
+
+    db.pin_collection("logs").unwrap();
+    db.pin_collection("settings").unwrap();
+    logs.drop().unwrap();
+    db.compact().unwrap();
+
+    // the pins don't keep the files of the dropped documents
+    assert!(dir_size(db_path.as_path()) < size / 4);
+    assert!(!db.unpin_collection("logs").unwrap());
+    assert!(db.unpin_collection("settings").unwrap());
+}
+
+#[test]
+fn test_compact_collection() {
+    let db_path = mk_db_path("test-compact-collection");
+
+    let _ = std::fs::remove_dir_all(db_path.as_path());
+
+    let db = Database::open_path(db_path.as_path()).unwrap();
+    let logs = db.collection::<Document>("logs");
+    logs.create_index(IndexModel {
+        keys: doc! { "level": 1 },
+        options: None,
+    }).unwrap();
+    logs.insert_many((0..200).map(|i| doc! {
+        "_id": i,
+        "level": if i % 10 == 0 { "error" } else { "info" },
+    })).unwrap();
+    logs.delete_many(doc! { "_id": { "$lt": 150 } }).unwrap();
+    let users = db.collection::<Document>("users");
+    users.insert_one(doc! { "_id": 1, "name": "Alice" }).unwrap();
+
+    db.pin_collection("logs").unwrap();
+    logs.compact().unwrap();
+
+    assert_eq!(logs.count_documents().unwrap(), 50);
+    assert_eq!(logs.find(doc! { "level": "error" }).run().unwrap().count(), 5);
+    assert_eq!(db.preload("logs.level_1").unwrap(), 50);
+    assert_eq!(users.find_by_id(1).unwrap().unwrap().get_str("name").unwrap(), "Alice");
+
+    // nothing to compact
+    db.collection::<Document>("missing").compact().unwrap();
+}
+
+#[test]
+fn test_verify() {
+    let db_path = mk_db_path("test-verify");
+
+    let _ = std::fs::remove_dir_all(db_path.as_path());
+
+    let db = Database::open_path(db_path.as_path()).unwrap();
+    let users = db.collection::<Document>("users");
+    users.create_index(IndexModel {
+        keys: doc! { "age": 1 },
+        options: None,
+    }).unwrap();
+    users.insert_many((0..10).map(|i| doc! { "_id": i, "age": 20 + i % 3 })).unwrap();
+    users.update_many(doc! { "age": 20 }, doc! { "$set": { "age": 30 } }).unwrap();
+    users.delete_one(doc! { "_id": 1 }).unwrap();
+    db.collection::<Document>("logs").insert_one(doc! { "text": "started" }).unwrap();
+
+    let report = db.verify().unwrap();
+    assert!(report.is_ok(), "{:?}", report.issues);
+    assert!(report.keys_scanned > 19);
+    let users = report.collections.iter().find(|collection| collection.name == "users").unwrap();
+    assert_eq!(users.documents, 9);
+    assert_eq!(users.index_entries, 9);
+}
+
+#[test]
+fn test_version_info() {
+    let db_path = mk_db_path("test-version-info");
+
+    let _ = std::fs::remove_dir_all(db_path.as_path());
+
+    let info = {
+        let db = Database::open_path(db_path.as_path()).unwrap();
+        db.collection::<Document>("items").insert_one(doc! { "name": "a" }).unwrap();
+        db.version_info()
+    };
+    assert_eq!(info.created_by.as_deref(), Some(Database::get_version()));
+    assert_eq!(info.format_version, 2);
+    // the storage library is built without a compression library
+    assert!(!info.compression);
+    assert_eq!(info.page_size, 4096);
+
+    // recorded in the file, not taken from the config of the next opening
+    let mut config = ConfigBuilder::new();
+    config.set_lsm_page_size(8192);
+    let db = Database::open_path_with_config(db_path.as_path(), config.take()).unwrap();
+    assert_eq!(db.version_info(), info);
+}
+
+#[test]
+fn test_directory_layout() {
+    let db_path = mk_db_path("test-directory-layout");
+
+    let _ = std::fs::remove_dir_all(db_path.as_path());
+
+    {
+        let mut config = ConfigBuilder::new();
+        config.set_storage_layout(StorageLayout::Directory);
+        let db = Database::open_path_with_config(db_path.as_path(), config.take()).unwrap();
+        assert_eq!(db.version_info().layout, StorageLayout::Directory);
+        db.collection::<Document>("files").insert_one(doc! {
+            "_id": 1,
+            "content": "x".repeat(128 * 1024),
+        }).unwrap();
+    }
+    assert!(db_path.join("wal").is_dir());
+
+    // the layout of an existing database is kept
+    let db = Database::open_path(db_path.as_path()).unwrap();
+    assert_eq!(db.version_info().layout, StorageLayout::Directory);
+    let file = db.collection::<Document>("files").find_by_id(1).unwrap().unwrap();
+    assert_eq!(file.get_str("content").unwrap().len(), 128 * 1024);
+}
+
+#[test]
+fn test_durability() {
+    let db_path = mk_db_path("test-durability");
+
+    let _ = std::fs::remove_dir_all(db_path.as_path());
+
+    {
+        let mut config = ConfigBuilder::new();
+        config.set_durability(Durability::Buffered);
+        let db = Database::open_path_with_config(db_path.as_path(), config.take()).unwrap();
+        let events = db.collection::<Document>("events");
+        events.insert_many((0..10).map(|i| doc! { "_id": i, "kind": "telemetry" })).unwrap();
+        events.insert_many_with_options(
+            [doc! { "_id": 100, "kind": "payment" }],
+            InsertManyOptions::builder().durability(Durability::Sync).build(),
+        ).unwrap();
+        events.update_many_with_options(
+            doc! { "kind": "telemetry" },
+            doc! { "$set": { "seen": true } },
+            UpdateOptions::builder().durability(Durability::Sync).build(),
+        ).unwrap();
+        events.delete_many_with_options(
+            doc! { "_id": { "$lt": 5 } },
+            DeleteOptions::builder().durability(Durability::Buffered).build(),
+        ).unwrap();
+    }
+
+    let db = Database::open_path(db_path.as_path()).unwrap();
+    let events = db.collection::<Document>("events");
+    assert_eq!(events.count_documents().unwrap(), 6);
+    assert_eq!(events.find(doc! { "seen": true }).run().unwrap().count(), 5);
+}
@@ -0,0 +1,157 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Bson, Document};
+use polodb_core::{CollectionT, Database, Error, ErrorCode, IndexModel, Result};
+use polodb_core::options::DecodeMode;
+use polodb_core::test_utils::put_raw_document;
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+use common::prepare_db;
+
+/// A document with an element of an unknown type, such as one written by a newer version.
+const UNKNOWN_ELEMENT: [u8; 15] = [15, 0, 0, 0, 0x20, b'n', b'e', b'w', 0, 1, 2, 3, 4, 5, 0];
+
+fn prepare_items(db_name: &str) -> Database {
+    let db = prepare_db(db_name).unwrap();
+    let items = db.collection::<Document>("items");
+    items.create_index(IndexModel {
+        keys: doc! { "name": 1 },
+        options: None,
+    }).unwrap();
+    items.insert_many(vec![
+        doc! { "_id": 1, "name": "a" },
+        doc! { "_id": 2, "name": "b" },
+        doc! { "_id": 4, "name": "d" },
+    ]).unwrap();
+    put_raw_document(&db, "items", 3, &UNKNOWN_ELEMENT).unwrap();
+    db
+}
+
+fn ids(results: &[Result<Document>]) -> Vec<Option<Bson>> {
+    results.iter()
+        .map(|result| match result {
+            Ok(doc) => doc.get("_id").cloned(),
+            Err(_) => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_strict_decode() {
+    let db = prepare_items("test-strict-decode");
+    let items = db.collection::<Document>("items");
+
+    let err = items.find(doc! {}).run().unwrap().collect::<Result<Vec<Document>>>().unwrap_err();
+    assert_eq!(err.code(), ErrorCode::Encoding);
+}
+
+#[test]
+fn test_lenient_decode() {
+    let db = prepare_items("test-lenient-decode");
+    let items = db.collection::<Document>("items");
+
+    let results: Vec<Result<Document>> = items.find(doc! {})
+        .decode_mode(DecodeMode::Lenient)
+        .run()
+        .unwrap()
+        .collect();
+    assert_eq!(ids(&results), vec![Some(Bson::Int32(1)), Some(Bson::Int32(2)), None, Some(Bson::Int32(4))]);
+    match &results[2] {
+        Err(Error::DecodeFailed(failure)) => {
+            assert_eq!(failure.ns, "items");
+            assert_eq!(failure.id, Some(Bson::Int32(3)));
+            assert_eq!(failure.data, UNKNOWN_ELEMENT.to_vec());
+            assert!(!failure.reason.is_empty());
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!(results[2].as_ref().unwrap_err().code(), ErrorCode::Encoding);
+
+    // sorted in memory instead of in the order of the index
+    let results: Vec<Result<Document>> = items.find(doc! { "name": { "$ne": "b" } })
+        .sort(doc! { "name": -1 })
+        .limit(2)
+        .decode_mode(DecodeMode::Lenient)
+        .run()
+        .unwrap()
+        .collect();
+    assert_eq!(ids(&results), vec![None, Some(Bson::Int32(4)), Some(Bson::Int32(1))]);
+}
+
+#[test]
+fn test_lenient_deserialize() {
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Item {
+        name: String,
+    }
+
+    let db = prepare_db("test-lenient-deserialize").unwrap();
+    let items = db.collection::<Item>("items");
+    db.collection::<Document>("items").insert_many(vec![
+        doc! { "_id": 1, "name": "a" },
+        doc! { "_id": 2, "name": 2 },
+    ]).unwrap();
+
+    let results: Vec<Result<Item>> = items.find(doc! {}).run().unwrap().collect();
+    assert_eq!(results[0].as_ref().unwrap().name, "a");
+    assert_eq!(results[1].as_ref().unwrap_err().code(), ErrorCode::Encoding);
+
+    let results: Vec<Result<Item>> = items.find(doc! {})
+        .decode_mode(DecodeMode::Lenient)
+        .run()
+        .unwrap()
+        .collect();
+    assert_eq!(results.len(), 2);
+    match &results[1] {
+        Err(Error::DecodeFailed(failure)) => {
+            assert_eq!(failure.id, Some(Bson::Int32(2)));
+            let doc: Document = polodb_core::bson::from_slice(&failure.data).unwrap();
+            assert_eq!(doc, doc! { "_id": 2, "name": 2 });
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn test_quarantine() {
+    let db = prepare_items("test-quarantine");
+    let items = db.collection::<Document>("items");
+    let quarantine = db.collection::<Document>("quarantine");
+
+    for _ in 0..2 {
+        let docs = items.find(doc! {})
+            .decode_mode(DecodeMode::Quarantine("quarantine".into()))
+            .run()
+            .unwrap()
+            .collect::<Result<Vec<Document>>>()
+            .unwrap();
+        assert_eq!(docs.len(), 3);
+    }
+
+    // the copy of the document found again replaces the first one
+    let copies = quarantine.find(doc! {}).run().unwrap().collect::<Result<Vec<Document>>>().unwrap();
+    assert_eq!(copies.len(), 1);
+    let copy = &copies[0];
+    assert_eq!(copy.get_str("ns").unwrap(), "items");
+    assert_eq!(copy.get("id"), Some(&Bson::Int32(3)));
+    assert_eq!(copy.get_binary_generic("data").unwrap(), &UNKNOWN_ELEMENT.to_vec());
+    assert!(!copy.get_str("reason").unwrap().is_empty());
+    assert!(copy.get_datetime("quarantinedAt").is_ok());
+
+    // the document stays in the collection
+    assert!(items.find(doc! {}).run().unwrap().collect::<Result<Vec<Document>>>().is_err());
+}
@@ -0,0 +1,101 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Bson, Document};
+use polodb_core::{CollectionT, Error};
+use polodb_core::options::{CreateCollectionOptions, ModifyCollectionOptions};
+
+mod common;
+
+use common::prepare_db;
+
+#[test]
+fn test_default_values() {
+    let db = prepare_db("test-defaults-values").unwrap();
+    db.create_collection_with_options("orders", CreateCollectionOptions::builder()
+        .default_value("status", "new")
+        .default_now("createdAt")
+        .default_sequence("number")
+        .build()
+    ).unwrap();
+    let orders = db.collection::<Document>("orders");
+
+    orders.insert_one(doc! { "_id": 1 }).unwrap();
+    orders.insert_many(vec![
+        doc! { "_id": 2, "status": "paid" },
+        doc! { "_id": 3, "number": 100_i64 },
+        doc! { "_id": 4 },
+    ]).unwrap();
+
+    let docs: Vec<Document> = orders.find(doc! {}).run().unwrap().map(|doc| doc.unwrap()).collect();
+    let numbers: Vec<&Bson> = docs.iter().map(|doc| doc.get("number").unwrap()).collect();
+    assert_eq!(numbers, vec![&Bson::Int64(1), &Bson::Int64(2), &Bson::Int64(100), &Bson::Int64(3)]);
+    let statuses: Vec<&str> = docs.iter().map(|doc| doc.get_str("status").unwrap()).collect();
+    assert_eq!(statuses, vec!["new", "paid", "new", "new"]);
+    assert!(docs.iter().all(|doc| doc.get_datetime("createdAt").is_ok()));
+
+    // the defaults don't apply to the updates
+    orders.update_one(doc! { "_id": 1 }, doc! { "$unset": { "status": "" } }).unwrap();
+    let doc = orders.find_one(doc! { "_id": 1 }).unwrap().unwrap();
+    assert!(doc.get("status").is_none());
+}
+
+#[test]
+fn test_sequence_survives_rollback_and_drop() {
+    let db = prepare_db("test-defaults-sequence").unwrap();
+    db.create_collection_with_options("tickets", CreateCollectionOptions::builder()
+        .default_sequence("seq")
+        .build()
+    ).unwrap();
+
+    let txn = db.start_transaction().unwrap();
+    txn.collection::<Document>("tickets").insert_one(doc! { "_id": 0 }).unwrap();
+    txn.rollback().unwrap();
+
+    let tickets = db.collection::<Document>("tickets");
+    tickets.insert_one(doc! { "_id": 1 }).unwrap();
+    let doc = tickets.find_one(doc! { "_id": 1 }).unwrap().unwrap();
+    assert_eq!(doc.get_i64("seq").unwrap(), 1);
+
+    // a new collection with the same name starts a new sequence
+    tickets.drop().unwrap();
+    db.create_collection_with_options("tickets", CreateCollectionOptions::builder()
+        .default_sequence("seq")
+        .build()
+    ).unwrap();
+    tickets.insert_one(doc! { "_id": 2 }).unwrap();
+    let doc = tickets.find_one(doc! { "_id": 2 }).unwrap().unwrap();
+    assert_eq!(doc.get_i64("seq").unwrap(), 1);
+}
+
+#[test]
+fn test_modify_defaults() {
+    let db = prepare_db("test-defaults-modify").unwrap();
+    let col = db.collection::<Document>("test");
+    col.insert_one(doc! { "_id": 0 }).unwrap();
+
+    db.modify_collection("test", ModifyCollectionOptions::builder()
+        .default_value("tags", Bson::Array(vec![]))
+        .build()
+    ).unwrap();
+    col.insert_one(doc! { "_id": 1 }).unwrap();
+    assert!(col.find_one(doc! { "_id": 0 }).unwrap().unwrap().get("tags").is_none());
+    assert_eq!(col.find_one(doc! { "_id": 1 }).unwrap().unwrap().get_array("tags").unwrap().len(), 0);
+
+    let err = db.modify_collection("test", ModifyCollectionOptions::builder()
+        .default_value("a.b", 1)
+        .build()
+    ).unwrap_err();
+    assert!(matches!(err, Error::IllegalDefaultField(field) if field == "a.b"));
+}
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use polodb_core::{Database, CollectionT, Result};
+use polodb_core::options::{Collation, DeleteOptions};
 use polodb_core::bson::{doc, Document};
 
 mod common;
@@ -248,3 +249,76 @@ fn test_delete_issues_148() {
         assert_eq!(result[2].get("name").unwrap().as_str().unwrap(), "4");
     }
 }
+
+#[test]
+fn test_delete_many_with_options() {
+    vec![
+        prepare_db("test-delete-many-with-options").unwrap(),
+    ].iter().for_each(|db| {
+        let collection = db.collection::<Document>("test");
+        collection.insert_many((0..10).map(|i| doc! {
+            "_id": i,
+            "name": if i % 2 == 0 { "Vincent" } else { "VINCENT" },
+        })).unwrap();
+
+        let options = DeleteOptions::builder().limit(3).build();
+        let result = collection.delete_many_with_options(doc! {}, options).unwrap();
+        assert_eq!(result.deleted_count, 3);
+        assert_eq!(collection.count_documents().unwrap(), 7);
+
+        // the last documents in the order of the keys
+        let options = DeleteOptions::builder().limit(2).hint(doc! { "$natural": -1 }).build();
+        let result = collection.delete_many_with_options(doc! {}, options).unwrap();
+        assert_eq!(result.deleted_count, 2);
+        assert!(collection.find_by_id(9).unwrap().is_none());
+        assert!(collection.find_by_id(8).unwrap().is_none());
+        assert_eq!(collection.count_documents().unwrap(), 5);
+
+        let options = DeleteOptions::builder()
+            .collation(Collation::CaseInsensitive)
+            .build();
+        let result = collection.delete_many_with_options(doc! {
+            "name": "vincent",
+        }, options).unwrap();
+        assert_eq!(result.deleted_count, 5);
+        assert_eq!(collection.count_documents().unwrap(), 0);
+    });
+}
+
+#[test]
+fn test_delete_many_sorted() {
+    let db = prepare_db("test-delete-many-sorted").unwrap();
+    let logs = db.collection::<Document>("logs");
+    logs.insert_many((0..10).map(|i| doc! {
+        "_id": i,
+        "time": (i * 7) % 10,
+    })).unwrap();
+
+    // the cleanup deletes the oldest documents by chunks
+    let options = DeleteOptions::builder().sort(doc! { "time": 1 }).limit(4).build();
+    let result = logs.delete_many_with_options(doc! {}, options.clone()).unwrap();
+    assert_eq!(result.deleted_count, 4);
+    let times: Vec<i32> = logs.find(doc! {})
+        .sort(doc! { "time": 1 })
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_i32("time").unwrap())
+        .collect();
+    assert_eq!(times, vec![4, 5, 6, 7, 8, 9]);
+
+    let result = logs.delete_many_with_options(doc! { "time": { "$gt": 5 } }, options).unwrap();
+    assert_eq!(result.deleted_count, 4);
+    assert_eq!(logs.count_documents().unwrap(), 2);
+}
+
+#[test]
+fn test_delete_by_id() {
+    let db = prepare_db("test-delete-by-id").unwrap();
+    let collection = db.collection::<Document>("test");
+    collection.insert_many((0..5).map(|i| doc! { "_id": i })).unwrap();
+
+    assert_eq!(collection.delete_by_id(3).unwrap().deleted_count, 1);
+    assert_eq!(collection.delete_by_id(3).unwrap().deleted_count, 0);
+    assert!(collection.find_by_id(3).unwrap().is_none());
+    assert_eq!(collection.count_documents().unwrap(), 4);
+}
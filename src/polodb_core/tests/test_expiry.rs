@@ -0,0 +1,172 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::{Duration, Instant};
+use polodb_core::bson::{doc, DateTime, Document};
+use polodb_core::{CollectionT, IndexModel, IndexOptions, Job};
+use polodb_core::options::{CreateCollectionOptions, ModifyCollectionOptions};
+
+mod common;
+
+use common::prepare_db;
+
+fn ids(docs: Vec<Document>) -> Vec<i32> {
+    docs.iter().map(|doc| doc.get_i32("_id").unwrap()).collect()
+}
+
+fn past() -> DateTime {
+    DateTime::from_millis(DateTime::now().timestamp_millis() - 60_000)
+}
+
+fn future() -> DateTime {
+    DateTime::from_system_time(std::time::SystemTime::now() + Duration::from_secs(3600))
+}
+
+#[test]
+fn test_expired_documents_are_hidden() {
+    let db = prepare_db("test-expiry-hidden").unwrap();
+    db.create_collection_with_options("sessions", CreateCollectionOptions::builder()
+        .expire_at_field("expireAt")
+        .build()
+    ).unwrap();
+    let sessions = db.collection::<Document>("sessions");
+    sessions.create_index(IndexModel {
+        keys: doc! { "user": 1 },
+        options: None,
+    }).unwrap();
+
+    sessions.insert_many(vec![
+        doc! { "_id": 0, "user": "alice", "expireAt": past() },
+        doc! { "_id": 1, "user": "alice", "expireAt": future() },
+        doc! { "_id": 2, "user": "bob", "expireAt": past() },
+        doc! { "_id": 3, "user": "bob" },
+        doc! { "_id": 4, "user": "carol", "expireAt": "not a date" },
+    ]).unwrap();
+
+    let docs: Vec<Document> = sessions.find(doc! {}).run().unwrap().map(|doc| doc.unwrap()).collect();
+    assert_eq!(ids(docs), vec![1, 3, 4]);
+    assert_eq!(sessions.count_documents().unwrap(), 3);
+
+    // by the primary key
+    assert!(sessions.find_one(doc! { "_id": 0 }).unwrap().is_none());
+    assert!(sessions.find_one(doc! { "_id": 1 }).unwrap().is_some());
+
+    // by an index, the expired document is the first one of the key
+    let docs: Vec<Document> = sessions.find(doc! { "user": "alice" }).run().unwrap().map(|doc| doc.unwrap()).collect();
+    assert_eq!(ids(docs), vec![1]);
+    let docs: Vec<Document> = sessions.find(doc! { "user": "bob" }).run().unwrap().map(|doc| doc.unwrap()).collect();
+    assert_eq!(ids(docs), vec![3]);
+
+    // the writes don't see the expired documents either
+    let result = sessions.update_many(doc! {}, doc! { "$set": { "seen": true } }).unwrap();
+    assert_eq!(result.matched_count, 3);
+    let result = sessions.delete_many(doc! { "user": "bob" }).unwrap();
+    assert_eq!(result.deleted_count, 1);
+}
+
+#[test]
+fn test_remove_expired_documents() {
+    let db = prepare_db("test-expiry-reaper").unwrap();
+    db.create_collection_with_options("sessions", CreateCollectionOptions::builder()
+        .expire_at_field("expireAt")
+        .build()
+    ).unwrap();
+    let sessions = db.collection::<Document>("sessions");
+    sessions.create_index(IndexModel {
+        keys: doc! { "user": 1 },
+        options: None,
+    }).unwrap();
+    sessions.insert_many(vec![
+        doc! { "_id": 0, "user": "alice", "expireAt": past() },
+        doc! { "_id": 1, "user": "alice", "expireAt": future() },
+        doc! { "_id": 2, "user": "bob", "expireAt": past() },
+    ]).unwrap();
+
+    // the collections without an expiry field are left untouched
+    let others = db.collection::<Document>("others");
+    others.insert_one(doc! { "_id": 0, "expireAt": past() }).unwrap();
+
+    assert_eq!(db.remove_expired_documents().unwrap(), 2);
+    assert_eq!(db.remove_expired_documents().unwrap(), 0);
+    assert_eq!(others.count_documents().unwrap(), 1);
+
+    // removing the expiry shows the remaining documents only
+    db.modify_collection("sessions", ModifyCollectionOptions::builder()
+        .expire_at_field("")
+        .build()
+    ).unwrap();
+    let docs: Vec<Document> = sessions.find(doc! {}).run().unwrap().map(|doc| doc.unwrap()).collect();
+    assert_eq!(ids(docs), vec![1]);
+    let docs: Vec<Document> = sessions.find(doc! { "user": "bob" }).run().unwrap().map(|doc| doc.unwrap()).collect();
+    assert!(docs.is_empty());
+}
+
+#[test]
+fn test_ttl_index() {
+    let db = prepare_db("test-ttl-index").unwrap();
+    let cache = db.collection::<Document>("cache");
+    cache.create_index(IndexModel {
+        keys: doc! { "createdAt": 1 },
+        options: Some(IndexOptions::builder().expire_after_secs(30).build()),
+    }).unwrap();
+
+    // created a minute ago, and an hour later
+    cache.insert_many(vec![
+        doc! { "_id": 0, "createdAt": past() },
+        doc! { "_id": 1, "createdAt": future() },
+        doc! { "_id": 2, "createdAt": DateTime::now() },
+        doc! { "_id": 3 },
+    ]).unwrap();
+
+    let docs: Vec<Document> = cache.find(doc! {}).run().unwrap().map(|doc| doc.unwrap()).collect();
+    assert_eq!(ids(docs), vec![1, 2, 3]);
+    assert_eq!(db.remove_expired_documents().unwrap(), 1);
+
+    cache.drop_index("createdAt_1").unwrap();
+    assert_eq!(cache.count_documents().unwrap(), 3);
+}
+
+#[test]
+fn test_ttl_index_background_removal() {
+    let db = prepare_db("test-ttl-index-background-removal").unwrap();
+    let cache = db.collection::<Document>("cache");
+    cache.create_index(IndexModel {
+        keys: doc! { "createdAt": 1 },
+        options: Some(IndexOptions::builder().expire_after_secs(30).build()),
+    }).unwrap();
+    cache.insert_many(vec![
+        doc! { "_id": 0, "createdAt": past() },
+        doc! { "_id": 1, "createdAt": DateTime::now() },
+        doc! { "_id": 2, "createdAt": past() },
+    ]).unwrap();
+
+    let scheduler = db.start_scheduler();
+    scheduler.schedule("ttl", Duration::from_millis(20), Job::remove_expired()).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while scheduler.jobs()[0].runs == 0 && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    scheduler.stop();
+
+    assert_eq!(db.remove_expired_documents().unwrap(), 0);
+    let docs: Vec<Document> = cache.find(doc! {}).run().unwrap().map(|doc| doc.unwrap()).collect();
+    assert_eq!(ids(docs), vec![1]);
+
+    // rolled back with the transaction
+    cache.insert_one(doc! { "_id": 3, "createdAt": past() }).unwrap();
+    let txn = db.start_transaction().unwrap();
+    assert_eq!(txn.remove_expired_documents().unwrap(), 1);
+    txn.rollback().unwrap();
+    assert_eq!(db.remove_expired_documents().unwrap(), 1);
+}
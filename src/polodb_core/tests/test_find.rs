@@ -12,8 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use polodb_core::{Result, CollectionT};
-use polodb_core::bson::{doc, Document};
+use polodb_core::{Result, CollectionT, ErrorCode, IndexModel};
+use polodb_core::bson::{doc, Bson, Document};
 
 mod common;
 
@@ -366,3 +366,701 @@ fn test_find_sort() {
     assert_eq!(result[1].get("name").unwrap().as_str().unwrap(), "banana");
     assert_eq!(result[2].get("name").unwrap().as_str().unwrap(), "orange");
 }
+
+#[test]
+fn test_find_sort_by_index() {
+    let db = prepare_db("test-find-sort-by-index").unwrap();
+    let metrics = db.metrics();
+    metrics.enable();
+
+    let indexed = db.collection::<Document>("indexed");
+    indexed.create_index(IndexModel {
+        keys: doc! { "value": 1 },
+        options: None,
+    }).unwrap();
+    let plain = db.collection::<Document>("plain");
+
+    // the numbers of the different types are ordered by their values
+    let values = vec![
+        Bson::Int32(3), Bson::Int32(-7), Bson::Int64(-20), Bson::Int64(5),
+        Bson::Double(-2.5), Bson::Double(4.5), Bson::Double(-0.5), Bson::Int32(3),
+    ];
+    let docs = values.into_iter().enumerate()
+        .map(|(i, value)| doc! { "_id": i as i32, "value": value })
+        .chain(vec![doc! { "_id": 100 }, doc! { "_id": 101 }])
+        .collect::<Vec<Document>>();
+    indexed.insert_many(&docs).unwrap();
+    plain.insert_many(&docs).unwrap();
+
+    for direction in [1, -1] {
+        let sorted = |col: &polodb_core::Collection<Document>| col
+            .find(doc! {})
+            .sort(doc! { "value": direction })
+            .run()
+            .unwrap()
+            .map(|doc| doc.unwrap().get("_id").unwrap().clone())
+            .collect::<Vec<Bson>>();
+        assert_eq!(sorted(&indexed), sorted(&plain));
+    }
+    let ids = indexed.find(doc! {})
+        .sort(doc! { "value": 1 })
+        .limit(4)
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<i32>>();
+    assert_eq!(ids, vec![2, 1, 4, 6]);
+
+    // the latest items are read without scanning the whole collection
+    let items = db.collection::<Document>("items");
+    items.create_index(IndexModel {
+        keys: doc! { "created": 1 },
+        options: None,
+    }).unwrap();
+    items.insert_many((0..1000).map(|i| doc! {
+        "_id": i,
+        "created": bson::DateTime::from_millis(i as i64 * 1000),
+        "kind": i % 2,
+    })).unwrap();
+
+    let scanned = metrics.docs_scanned();
+    let latest = items.find(doc! { "kind": 0 })
+        .sort(doc! { "created": -1 })
+        .limit(20)
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(latest.len(), 20);
+    assert_eq!(latest[0].get_i32("_id").unwrap(), 998);
+    assert_eq!(latest[19].get_i32("_id").unwrap(), 960);
+    assert!(metrics.docs_scanned() - scanned <= 40);
+}
+
+#[test]
+fn test_paginate() {
+    let db = prepare_db("test-paginate").unwrap();
+    let collection = db.collection::<Document>("numbers");
+    collection.create_index(IndexModel {
+        keys: doc! {
+            "group": 1,
+        },
+        options: None,
+    }).unwrap();
+    collection.insert_many((0..25).rev().map(|i| doc! {
+        "_id": i,
+        "group": i % 2,
+    })).unwrap();
+
+    let mut ids = vec![];
+    let mut page_sizes = vec![];
+    let mut token: Option<String> = None;
+    loop {
+        let mut paginate = collection.paginate(doc! { "group": 0 }, 5);
+        if let Some(token) = token {
+            paginate = paginate.after(token);
+        }
+        let page = paginate.run().unwrap();
+        page_sizes.push(page.items.len());
+        ids.extend(page.items.iter().map(|doc| doc.get_i32("_id").unwrap()));
+        match page.next_token {
+            Some(next) => token = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(page_sizes, vec![5, 5, 3]);
+    assert_eq!(ids, (0..25).step_by(2).collect::<Vec<i32>>());
+
+    let page = collection.paginate(doc! {}, 25).run().unwrap();
+    assert_eq!(page.items.len(), 25);
+    assert!(page.next_token.is_none());
+
+    let err = collection.paginate(doc! {}, 5).after("not a token").run().unwrap_err();
+    assert_eq!(err.code(), ErrorCode::InvalidArgument);
+}
+
+fn read_sorted_pages(collection: &polodb_core::Collection<Document>, sort: Document, page_size: u64) -> Vec<i32> {
+    let mut ids = vec![];
+    let mut token: Option<String> = None;
+    loop {
+        let mut paginate = collection.paginate(doc! {}, page_size).sort(sort.clone());
+        if let Some(token) = token {
+            paginate = paginate.after(token);
+        }
+        let page = paginate.run().unwrap();
+        assert!(page.items.len() as u64 <= page_size);
+        ids.extend(page.items.iter().map(|doc| doc.get_i32("_id").unwrap()));
+        match page.next_token {
+            Some(next) => token = Some(next),
+            None => return ids,
+        }
+    }
+}
+
+#[test]
+fn test_paginate_sorted() {
+    let db = prepare_db("test-paginate-sorted").unwrap();
+    let indexed = db.collection::<Document>("indexed");
+    indexed.create_index(IndexModel {
+        keys: doc! { "score": 1 },
+        options: None,
+    }).unwrap();
+    let scanned = db.collection::<Document>("scanned");
+    let docs = vec![
+        doc! { "_id": 1, "score": 10 },
+        doc! { "_id": 2, "score": -3.5 },
+        doc! { "_id": 3, "score": 10 },
+        doc! { "_id": 4 },
+        doc! { "_id": 5, "score": 7i64 },
+        doc! { "_id": 6, "score": "high" },
+        doc! { "_id": 7, "score": 8.25 },
+        doc! { "_id": 8, "score": -20 },
+        doc! { "_id": 9 },
+        doc! { "_id": 10, "score": 10 },
+        doc! { "_id": 11, "score": -3.5 },
+        doc! { "_id": 12, "score": "a" },
+    ];
+    indexed.insert_many(docs.clone()).unwrap();
+    scanned.insert_many(docs).unwrap();
+
+    // the numbers of all the types by value, the documents of the same value by _id
+    let ascending = vec![8, 2, 11, 5, 7, 1, 3, 10, 12, 6, 4, 9];
+    let descending = vec![4, 9, 6, 12, 1, 3, 10, 7, 5, 2, 11, 8];
+    for page_size in [1, 2, 3, 5, 12, 20] {
+        assert_eq!(read_sorted_pages(&indexed, doc! { "score": 1 }, page_size), ascending);
+        assert_eq!(read_sorted_pages(&scanned, doc! { "score": 1 }, page_size), ascending);
+        assert_eq!(read_sorted_pages(&indexed, doc! { "score": -1 }, page_size), descending);
+        assert_eq!(read_sorted_pages(&scanned, doc! { "score": -1 }, page_size), descending);
+    }
+
+    // the token of a sort is not a token of another sort or of the pages by _id
+    let page = indexed.paginate(doc! {}, 4).sort(doc! { "score": 1 }).run().unwrap();
+    let token = page.next_token.unwrap();
+    let err = indexed.paginate(doc! {}, 4).sort(doc! { "score": -1 }).after(token.clone()).run().unwrap_err();
+    assert_eq!(err.code(), ErrorCode::InvalidArgument);
+    let err = indexed.paginate(doc! {}, 4).after(token).run().unwrap_err();
+    assert_eq!(err.code(), ErrorCode::InvalidArgument);
+    assert!(indexed.paginate(doc! {}, 4).sort(doc! { "score": 1, "_id": 1 }).run().is_err());
+}
+
+#[test]
+fn test_paginate_sorted_resume() {
+    let db_path = common::mk_db_path("test-paginate-sorted-resume");
+    let _ = std::fs::remove_dir_all(&db_path);
+    let token = {
+        let db = polodb_core::Database::open_path(&db_path).unwrap();
+        let events = db.collection::<Document>("events");
+        events.create_index(IndexModel {
+            keys: doc! { "at": 1 },
+            options: None,
+        }).unwrap();
+        events.insert_many((0..10).map(|i| doc! { "_id": i, "at": i * 10, "kind": i % 2 })).unwrap();
+        let page = events.paginate(doc! { "kind": 0 }, 2).sort(doc! { "at": -1 }).run().unwrap();
+        let ids = page.items.iter().map(|doc| doc.get_i32("_id").unwrap()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![8, 6]);
+        page.next_token.unwrap()
+    };
+
+    // the token is resumed by another handle, after the collection changed
+    let db = polodb_core::Database::open_path(&db_path).unwrap();
+    let events = db.collection::<Document>("events");
+    events.delete_one(doc! { "_id": 4 }).unwrap();
+    events.insert_many(vec![
+        doc! { "_id": 20, "at": 55, "kind": 0 },
+        doc! { "_id": 21, "at": 35, "kind": 0 },
+        doc! { "_id": 22, "at": 60, "kind": 0 },
+    ]).unwrap();
+    // moved from before the position to after it
+    events.update_one(doc! { "_id": 8 }, doc! { "$set": { "at": 5 } }).unwrap();
+
+    let page = events.paginate(doc! { "kind": 0 }, 10).sort(doc! { "at": -1 }).after(token).run().unwrap();
+    let ids = page.items.iter().map(|doc| doc.get_i32("_id").unwrap()).collect::<Vec<_>>();
+    assert_eq!(ids, vec![22, 20, 21, 2, 8, 0]);
+    assert!(page.next_token.is_none());
+}
+
+#[test]
+fn test_find_decimal() {
+    let db = prepare_db("test-find-decimal").unwrap();
+    let col = db.collection::<Document>("payments");
+    col.create_index(IndexModel {
+        keys: doc! {
+            "amount": 1,
+        },
+        options: None,
+    }).unwrap();
+
+    let decimal = |s: &str| Bson::Decimal128(s.parse().unwrap());
+    col.insert_many(vec![
+        doc! { "_id": 1, "amount": decimal("10.50") },
+        doc! { "_id": 2, "amount": decimal("0.10") },
+        doc! { "_id": 3, "amount": 3 },
+        doc! { "_id": 4, "amount": decimal("-7") },
+        doc! { "_id": 5, "amount": 2.75 },
+    ]).unwrap();
+
+    // through the index, the equal decimals have the same key
+    let found = col.find_one(doc! { "amount": decimal("10.5") }).unwrap().unwrap();
+    assert_eq!(found.get_i32("_id").unwrap(), 1);
+
+    let ids = |filter: Document| col.find(filter).run().unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<_>>();
+    // the ranges are read in the order of the index
+    assert_eq!(ids(doc! { "amount": { "$gt": 2.9 } }), vec![3, 1]);
+    assert_eq!(ids(doc! { "amount": { "$lt": 0.5 } }), vec![4, 2]);
+    assert_eq!(ids(doc! { "amount": { "$lte": decimal("0.1") } }), vec![4, 2]);
+    assert_eq!(ids(doc! { "amount": { "$in": [0.1, 3.0] } }), vec![2, 3]);
+
+    let sorted = col.find(doc! {}).sort(doc! { "amount": 1 }).run().unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(sorted, vec![4, 2, 5, 3, 1]);
+}
+
+#[test]
+fn test_exists_and_count() {
+    let db = prepare_db("test-exists-and-count").unwrap();
+    let col = db.collection::<Document>("teacher");
+    assert!(!col.exists(doc! {}).unwrap());
+    assert_eq!(col.count().unwrap(), 0);
+
+    col.insert_many((0..100).map(|i| doc! { "_id": i, "age": i % 10 })).unwrap();
+    col.create_index(IndexModel {
+        keys: doc! { "age": 1 },
+        options: None,
+    }).unwrap();
+
+    assert!(col.exists(doc! { "_id": 42 }).unwrap());
+    assert!(!col.exists(doc! { "_id": 100 }).unwrap());
+    assert!(col.exists(doc! { "age": 3 }).unwrap());
+    assert!(!col.exists(doc! { "age": { "$gt": 9 } }).unwrap());
+    assert_eq!(col.count().unwrap(), 100);
+
+    let txn = db.start_transaction().unwrap();
+    let txn_col = txn.collection::<Document>("teacher");
+    txn_col.delete_one(doc! { "_id": 42 }).unwrap();
+    assert!(!txn_col.exists(doc! { "_id": 42 }).unwrap());
+    assert_eq!(txn_col.count().unwrap(), 99);
+    txn.rollback().unwrap();
+    assert!(col.exists(doc! { "_id": 42 }).unwrap());
+}
+
+#[test]
+fn test_find_by_id() {
+    let db = prepare_db("test-find-by-id").unwrap();
+    let col = db.collection::<Document>("teacher");
+    let oid = db.new_object_id();
+    col.insert_many(vec![
+        doc! { "_id": oid, "name": "David" },
+        doc! { "_id": "harry", "name": "Harry" },
+        doc! { "_id": 3, "name": "Ann" },
+    ]).unwrap();
+
+    assert_eq!(col.find_by_id(oid).unwrap().unwrap().get_str("name").unwrap(), "David");
+    assert_eq!(col.find_by_id("harry").unwrap().unwrap().get_str("name").unwrap(), "Harry");
+    assert_eq!(col.find_by_id(3).unwrap().unwrap().get_str("name").unwrap(), "Ann");
+    assert!(col.find_by_id(4).unwrap().is_none());
+}
+
+#[test]
+fn test_find_raw() {
+    let db = prepare_db("test-find-raw").unwrap();
+    let col = db.collection::<Document>("teacher");
+    col.insert_many((0..20).map(|i| doc! {
+        "_id": i,
+        "name": format!("teacher-{}", i),
+        "age": 40 - i,
+    })).unwrap();
+
+    let all = col.find_raw(doc! {}).run().unwrap()
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(all.len(), 20);
+    assert_eq!(all[3].to_document().unwrap(), doc! { "_id": 3, "name": "teacher-3", "age": 37 });
+
+    let matched = col.find_raw(doc! { "age": { "$lt": 25 } }).run().unwrap()
+        .map(|raw| raw.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(matched, (16..20).collect::<Vec<_>>());
+
+    let page = col.find_raw(doc! {}).skip(5).limit(3).run().unwrap()
+        .map(|raw| raw.unwrap().get_str("name").unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(page, vec!["teacher-5", "teacher-6", "teacher-7"]);
+
+    let sorted = col.find_raw(doc! { "_id": { "$gte": 10 } }).sort(doc! { "age": 1 }).limit(2).run().unwrap()
+        .map(|raw| raw.unwrap().to_document().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(sorted, vec![
+        doc! { "_id": 19, "name": "teacher-19", "age": 21 },
+        doc! { "_id": 18, "name": "teacher-18", "age": 22 },
+    ]);
+}
+
+#[test]
+fn test_find_reading_fields_of_encoded_documents() {
+    let db = prepare_db("test-find-encoded-fields").unwrap();
+    let col = db.collection::<Document>("people");
+    col.insert_many((0..30).map(|i| doc! {
+        "_id": i,
+        "name": format!("person-{}", i),
+        "address": {
+            "city": if i % 3 == 0 { "Paris" } else { "Lyon" },
+            "geo": { "zip": 75000 + i },
+        },
+    })).unwrap();
+
+    let ids = |filter: Document| col.find(filter).run().unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(ids(doc! { "address.city": "Paris", "_id": { "$gte": 20 } }), vec![21, 24, 27]);
+    assert_eq!(ids(doc! { "address.geo.zip": 75021 }), vec![21]);
+    assert_eq!(ids(doc! { "$or": [{ "_id": 1 }, { "name": "person-2" }] }), vec![1, 2]);
+    assert_eq!(ids(doc! { "_id": { "$in": [3, 10, 20] }, "address.city": "Lyon" }), vec![10, 20]);
+    assert_eq!(ids(doc! { "address": "Paris" }), Vec::<i32>::new());
+
+    let doc = col.find_one(doc! { "address.geo.zip": 75005 }).unwrap().unwrap();
+    assert_eq!(doc.get_document("address").unwrap().get_str("city").unwrap(), "Lyon");
+
+    let updated = col.update_many(doc! { "address.city": "Paris" }, doc! {
+        "$set": { "visited": true },
+    }).unwrap();
+    assert_eq!(updated.modified_count, 10);
+    assert_eq!(ids(doc! { "visited": true }).len(), 10);
+
+    let deleted = col.delete_many(doc! { "address.city": "Lyon" }).unwrap();
+    assert_eq!(deleted.deleted_count, 20);
+    assert_eq!(col.count_documents().unwrap(), 10);
+}
+
+#[test]
+fn test_find_with_filter_fn() {
+    let db = prepare_db("test-find-filter-fn").unwrap();
+    let col = db.collection::<Document>("people");
+    col.create_index(IndexModel {
+        keys: doc! { "city": 1 },
+        options: None,
+    }).unwrap();
+    col.insert_many((0..30).map(|i| doc! {
+        "_id": i,
+        "name": format!("person-{}", i),
+        "city": if i % 3 == 0 { "Paris" } else { "Lyon" },
+    })).unwrap();
+
+    let ids = |cursor: polodb_core::ClientCursor<Document>| cursor
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<_>>();
+    let palindrome = |doc: &Document| {
+        let digits = doc.get_i32("_id").unwrap().to_string();
+        digits.len() > 1 && digits.chars().rev().collect::<String>() == digits
+    };
+
+    assert_eq!(ids(col.find(doc! {}).filter_fn(palindrome).run().unwrap()), vec![11, 22]);
+
+    // the documents found by the index, then checked by the predicate
+    let found = col.find(doc! { "city": "Lyon" })
+        .filter_fn(|doc| doc.get_i32("_id").unwrap() % 5 == 0)
+        .run()
+        .unwrap();
+    assert_eq!(ids(found), vec![5, 10, 20, 25]);
+
+    // the predicate is checked before the documents are sorted and limited
+    let found = col.find(doc! { "city": "Lyon" })
+        .filter_fn(|doc| doc.get_i32("_id").unwrap() % 5 == 0)
+        .sort(doc! { "_id": -1 })
+        .skip(1)
+        .limit(2)
+        .run()
+        .unwrap();
+    assert_eq!(ids(found), vec![20, 10]);
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Person {
+        name: String,
+    }
+    let people = db.collection::<Person>("people");
+    let names: Vec<String> = people.find(doc! { "city": "Paris" })
+        .filter_typed(|person: &Person| person.name.ends_with('7'))
+        .run()
+        .unwrap()
+        .map(|person| person.unwrap().name)
+        .collect();
+    assert_eq!(names, vec!["person-27"]);
+}
+
+#[test]
+fn test_find_natural_order() {
+    let db = prepare_db("test-find-natural-order").unwrap();
+    let col = db.collection::<Document>("events");
+    col.create_index(IndexModel {
+        keys: doc! { "kind": 1 },
+        options: None,
+    }).unwrap();
+    col.insert_many((0..10).map(|i| doc! { "_id": i, "kind": i % 3 })).unwrap();
+
+    let ids = |cursor: polodb_core::ClientCursor<Document>| cursor
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<i32>>();
+
+    let found = ids(col.find(doc! {}).sort(doc! { "$natural": -1 }).limit(3).run().unwrap());
+    assert_eq!(found, vec![9, 8, 7]);
+    let found = ids(col.find(doc! { "kind": 0 }).sort(doc! { "$natural": -1 }).run().unwrap());
+    assert_eq!(found, vec![9, 6, 3, 0]);
+    let found = ids(col.find(doc! { "_id": { "$gte": 5 } }).sort(doc! { "$natural": 1 }).run().unwrap());
+    assert_eq!(found, vec![5, 6, 7, 8, 9]);
+
+    // the last documents of an empty collection
+    let empty = db.collection::<Document>("empty");
+    empty.insert_one(doc! { "_id": 0 }).unwrap();
+    empty.delete_one(doc! { "_id": 0 }).unwrap();
+    assert!(ids(empty.find(doc! {}).sort(doc! { "$natural": -1 }).run().unwrap()).is_empty());
+
+    // $natural is only a direction of the scan
+    assert!(col.find(doc! {}).sort(doc! { "$natural": -1, "kind": 1 }).run().is_err());
+}
+
+#[test]
+fn test_find_collection_scan() {
+    let db = prepare_db("test-find-collection-scan").unwrap();
+    let col = db.collection::<Document>("events");
+    col.create_index(IndexModel {
+        keys: doc! { "kind": 1 },
+        options: None,
+    }).unwrap();
+    col.insert_many((0..10).map(|i| doc! { "_id": i, "kind": i % 3 })).unwrap();
+
+    let entries = std::sync::Arc::new(std::sync::Mutex::new(Vec::<u64>::new()));
+    let sink = entries.clone();
+    db.profiler().set_callback(move |entry| sink.lock().unwrap().push(entry.docs_examined));
+    db.profiler().set_slow_threshold(std::time::Duration::ZERO);
+
+    assert_eq!(col.find(doc! { "kind": 1 }).run().unwrap().count(), 3);
+    assert_eq!(col.find(doc! { "kind": 1 }).collection_scan().run().unwrap().count(), 3);
+    assert_eq!(col.find(doc! { "_id": 4 }).collection_scan().run().unwrap().count(), 1);
+    db.profiler().disable();
+
+    assert_eq!(*entries.lock().unwrap(), vec![3, 10, 10]);
+
+    // the sort isn't done by reading the index either
+    let stage = |explain: Document| explain.get_document("winningPlan").unwrap().get_str("stage").unwrap().to_string();
+    assert_eq!(stage(col.find(doc! {}).sort(doc! { "kind": 1 }).explain().unwrap()), "IXSCAN");
+    assert_eq!(stage(col.find(doc! {}).sort(doc! { "kind": 1 }).collection_scan().explain().unwrap()), "COLLSCAN");
+    let kinds: Vec<i32> = col.find(doc! {}).sort(doc! { "kind": -1 }).collection_scan().run().unwrap()
+        .map(|doc| doc.unwrap().get_i32("kind").unwrap())
+        .collect();
+    assert_eq!(kinds, vec![2, 2, 2, 1, 1, 1, 0, 0, 0, 0]);
+}
+
+#[test]
+fn test_find_size_range() {
+    let db = prepare_db("test-find-size-range").unwrap();
+    let col = db.collection::<Document>("posts");
+    col.insert_many(vec![
+        doc! { "_id": 0, "tags": [] },
+        doc! { "_id": 1, "tags": ["a"] },
+        doc! { "_id": 2, "tags": ["a", "b"] },
+        doc! { "_id": 3, "tags": ["a", "b", "c", "d", "e"] },
+        doc! { "_id": 4, "tags": ["a", "b", "c", "d", "e", "f"] },
+        doc! { "_id": 5, "tags": "a" },
+        doc! { "_id": 6 },
+    ]).unwrap();
+
+    let ids = |filter: Document| col
+        .find(filter)
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<i32>>();
+
+    assert_eq!(ids(doc! { "tags": { "$size": { "$gte": 2, "$lte": 5 } } }), vec![2, 3]);
+    assert_eq!(ids(doc! { "tags": { "$size": { "$gt": 0 } } }), vec![1, 2, 3, 4]);
+    assert_eq!(ids(doc! { "tags": { "$size": { "$lt": 2 } } }), vec![0, 1]);
+    assert_eq!(ids(doc! { "tags": { "$size": { "$eq": 6 } } }), vec![4]);
+    assert_eq!(ids(doc! { "tags": { "$size": 2i64 } }), vec![2]);
+
+    assert!(col.find(doc! { "tags": { "$size": { "$in": [1, 2] } } }).run().is_err());
+    assert!(col.find(doc! { "tags": { "$size": { "$gte": "2" } } }).run().is_err());
+}
+
+#[test]
+fn test_find_index_bounds() {
+    let db = prepare_db("test-find-index-bounds").unwrap();
+    let col = db.collection::<Document>("events");
+    col.create_index(IndexModel {
+        keys: doc! { "at": 1 },
+        options: None,
+    }).unwrap();
+    let at = |millis: i64| Bson::DateTime(polodb_core::bson::DateTime::from_millis(millis));
+    col.insert_many(vec![
+        doc! { "_id": 1, "at": at(4000), "kind": "b" },
+        doc! { "_id": 2, "at": at(1000), "kind": "a" },
+        doc! { "_id": 3, "at": at(3000), "kind": "a" },
+        doc! { "_id": 4, "at": at(2000), "kind": "b" },
+        doc! { "_id": 5, "at": at(3000), "kind": "b" },
+        doc! { "_id": 6, "kind": "a" },
+        doc! { "_id": 7, "at": "later", "kind": "a" },
+    ]).unwrap();
+
+    let ids = |cursor: polodb_core::ClientCursor<Document>| cursor
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<i32>>();
+
+    // the window is read in the order of the index, max excluded
+    let cursor = col.find(doc! {})
+        .hint("at_1")
+        .min(doc! { "at": at(2000) })
+        .max(doc! { "at": at(4000) })
+        .run()
+        .unwrap();
+    assert_eq!(ids(cursor), vec![4, 3, 5]);
+
+    let cursor = col.find(doc! { "kind": "a" })
+        .hint(doc! { "at": 1 })
+        .min(doc! { "at": at(2000) })
+        .run()
+        .unwrap();
+    assert_eq!(ids(cursor), vec![3]);
+
+    let cursor = col.find(doc! {})
+        .hint("at_1")
+        .max(doc! { "at": at(3000) })
+        .sort(doc! { "at": -1 })
+        .run()
+        .unwrap();
+    // the strings are ordered before the dates
+    assert_eq!(ids(cursor), vec![4, 2, 7]);
+
+    let cursor = col.find(doc! {})
+        .hint("at_1")
+        .min(doc! { "at": at(2000) })
+        .sort(doc! { "_id": -1 })
+        .skip(1)
+        .limit(2)
+        .run()
+        .unwrap();
+    assert_eq!(ids(cursor), vec![4, 3]);
+
+    // the hint alone doesn't bound the scan
+    let cursor = col.find(doc! { "kind": "a" }).hint("at_1").run().unwrap();
+    assert_eq!(ids(cursor).len(), 4);
+
+    let code = |result: Result<polodb_core::ClientCursor<Document>>| result.err().map(|err| err.code());
+    assert_eq!(code(col.find(doc! {}).min(doc! { "at": at(2000) }).run()), Some(ErrorCode::InvalidArgument));
+    assert_eq!(code(col.find(doc! {}).hint("at_1").min(doc! { "kind": "a" }).run()), Some(ErrorCode::InvalidArgument));
+    assert_eq!(code(col.find(doc! {}).hint(doc! { "$natural": 1 }).max(doc! { "at": at(2000) }).run()), Some(ErrorCode::InvalidArgument));
+    assert!(col.find(doc! {}).hint("kind_1").max(doc! { "kind": "a" }).run().is_err());
+}
+
+#[test]
+fn test_find_elem_match() {
+    let db = prepare_db("test-find-elem-match").unwrap();
+    let col = db.collection::<Document>("products");
+    col.insert_many(vec![
+        doc! { "_id": 1, "reviews": [{ "user": "a", "score": 5 }, { "user": "b", "score": 2 }], "sizes": [36, 41] },
+        doc! { "_id": 2, "reviews": [{ "user": "a", "score": 3 }, { "user": "b", "score": 5 }], "sizes": [38] },
+        doc! { "_id": 3, "reviews": { "user": "a", "score": 5 }, "sizes": 39 },
+        doc! { "_id": 4 },
+    ]).unwrap();
+
+    let ids = |filter: Document| col
+        .find(filter)
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<i32>>();
+
+    // a single element satisfies all the conditions
+    assert_eq!(ids(doc! { "reviews": { "$elemMatch": { "score": { "$gt": 4 }, "user": "a" } } }), vec![1]);
+    assert_eq!(ids(doc! { "reviews": { "$elemMatch": { "score": { "$gt": 4 } } } }), vec![1, 2]);
+    assert_eq!(ids(doc! { "reviews": { "$elemMatch": { "$or": [{ "score": 3 }, { "user": "c" }] } } }), vec![2]);
+    assert_eq!(ids(doc! { "sizes": { "$elemMatch": { "$gte": 37, "$lt": 40 } } }), vec![2]);
+    assert_eq!(ids(doc! { "sizes": { "$not": { "$elemMatch": { "$gte": 40 } } } }), vec![2, 3]);
+    assert_eq!(ids(doc! { "_id": { "$lt": 3 }, "sizes": { "$elemMatch": { "$in": [41, 42] } } }), vec![1]);
+
+    assert!(col.find(doc! { "sizes": { "$elemMatch": 1 } }).run().is_err());
+    assert!(col.find(doc! { "sizes": { "$elemMatch": { "$gte": 37, "size": 1 } } }).run().is_err());
+}
+
+#[test]
+fn test_find_array_operators() {
+    let db = prepare_db("test-find-array-operators").unwrap();
+    let col = db.collection::<Document>("shirts");
+    col.insert_many(vec![
+        doc! { "_id": 1, "tags": ["rojo", "azul", "verde"] },
+        doc! { "_id": 2, "tags": ["azul", "verde", "negro"] },
+        doc! { "_id": 3, "tags": ["rojo"] },
+        doc! { "_id": 4, "tags": "rojo" },
+        doc! { "_id": 5, "tags": "azul" },
+    ]).unwrap();
+
+    let ids = |filter: Document| col
+        .find(filter)
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<i32>>();
+
+    assert_eq!(ids(doc! { "tags": { "$size": 3 } }), vec![1, 2]);
+    assert_eq!(ids(doc! { "tags": { "$size": 1i64 } }), vec![3]);
+    assert_eq!(ids(doc! { "tags": { "$not": { "$size": 3 } } }), vec![3, 4, 5]);
+
+    assert_eq!(ids(doc! { "tags": { "$in": ["rojo"] } }), vec![1, 3, 4]);
+    assert_eq!(ids(doc! { "tags": { "$nin": ["rojo"] } }), vec![2, 5]);
+    assert_eq!(ids(doc! { "tags": { "$nin": ["rojo", "negro"] } }), vec![5]);
+    assert_eq!(ids(doc! { "tags": { "$not": { "$nin": ["negro"] } } }), vec![2]);
+    assert_eq!(ids(doc! { "tags": { "$size": 3, "$nin": ["negro"] } }), vec![1]);
+}
+
+#[test]
+fn test_find_dotted_path_through_arrays() {
+    let db = prepare_db("test-find-dotted-path-through-arrays").unwrap();
+    let col = db.collection::<Document>("readings");
+    col.insert_many(vec![
+        doc! { "_id": 1, "r": [{ "s": 3 }, { "s": 6 }] },
+        doc! { "_id": 2, "r": { "s": 5 } },
+        doc! { "_id": 3, "r": [{ "s": 1 }, { "t": 9 }] },
+        doc! { "_id": 4, "r": 7 },
+        doc! { "_id": 5, "r": [1, 2] },
+        doc! { "_id": 6 },
+    ]).unwrap();
+
+    let filters = vec![
+        (doc! { "r.s": 5 }, vec![2]),
+        (doc! { "r.s": 6 }, vec![1]),
+        (doc! { "r.s": { "$eq": 3 } }, vec![1]),
+        (doc! { "r.s": { "$gt": 4 } }, vec![1, 2]),
+        (doc! { "r.s": { "$gte": 6 } }, vec![1]),
+        (doc! { "r.s": { "$lt": 2 } }, vec![3]),
+        (doc! { "r.s": { "$lte": 3 } }, vec![1, 3]),
+        (doc! { "r.s": { "$in": [1, 5] } }, vec![2, 3]),
+        // each bound is met by one of the values
+        (doc! { "r.s": { "$gte": 4, "$lte": 5 } }, vec![1, 2]),
+        (doc! { "r.0.s": { "$gt": 2 } }, vec![1]),
+        (doc! { "r.t": { "$lt": 10 } }, vec![3]),
+    ];
+    let ids = |filter: Document| col
+        .find(filter)
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<i32>>();
+
+    // the equality and the operators go through the arrays the same way, and the
+    // documents whose path ends at a value, or isn't there, don't match
+    for (filter, expected) in &filters {
+        assert_eq!(&ids(filter.clone()), expected, "{}", filter);
+    }
+
+    // the same documents through the index of the path
+    col.create_index(IndexModel {
+        keys: doc! { "r.s": 1 },
+        options: None,
+    }).unwrap();
+    for (filter, expected) in filters {
+        let mut found = ids(filter.clone());
+        found.sort();
+        assert_eq!(found, expected, "{}", filter);
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Database, ProfileEntry};
+use polodb_core::options::CreateCollectionOptions;
+
+mod common;
+
+use common::{mk_db_path, prepare_db};
+
+fn names(cursor: impl Iterator<Item = polodb_core::Result<Document>>) -> Vec<String> {
+    cursor.map(|doc| doc.unwrap().get_str("name").unwrap().to_string()).collect()
+}
+
+#[test]
+fn test_fuzzy_search() {
+    let db = prepare_db("test-fuzzy-search").unwrap();
+    let products = db.collection::<Document>("products");
+    products.insert_many(vec![
+        doc! { "name": "Apple iPhone 15" },
+        doc! { "name": "Apple iPad Air" },
+        doc! { "name": "Samsung Galaxy S24" },
+        doc! { "name": ["Pixel 8", "Google phone"] },
+    ]).unwrap();
+
+    let found = names(products.find(doc! { "name": { "$fuzzy": { "$search": "iphnoe", "$maxEdits": 1 } } }).run().unwrap());
+    assert_eq!(found, vec!["Apple iPhone 15"]);
+
+    // every word of the search is found
+    let found = names(products.find(doc! { "name": { "$fuzzy": "aple ipda" } }).run().unwrap());
+    assert_eq!(found, vec!["Apple iPad Air"]);
+
+    let found = names(products.find(doc! { "name": { "$fuzzy": { "$search": "samsnug", "$maxEdits": 0 } } }).run().unwrap());
+    assert!(found.is_empty());
+    let found = names(products.find(doc! { "name": { "$fuzzy": { "$search": "samsnug", "$maxEdits": 1 } } }).run().unwrap());
+    assert_eq!(found, vec!["Samsung Galaxy S24"]);
+
+    // the strings of an array
+    let count = products.find(doc! { "name": { "$fuzzy": "gogle" } }).run().unwrap().count();
+    assert_eq!(count, 1);
+
+    let count = products.find(doc! { "name": { "$not": { "$fuzzy": "aple" } } }).run().unwrap().count();
+    assert_eq!(count, 2);
+
+    assert!(products.find(doc! { "name": { "$fuzzy": 1 } }).run().is_err());
+}
+
+#[test]
+fn test_fuzzy_index() {
+    let db_path = mk_db_path("test-fuzzy-index");
+    let _ = std::fs::remove_dir_all(db_path.as_path());
+
+    {
+        let db = Database::open_path(db_path.as_path()).unwrap();
+        db.create_collection_with_options("products", CreateCollectionOptions::builder()
+            .fuzzy_index("name")
+            .build()
+        ).unwrap();
+        let products = db.collection::<Document>("products");
+        products.insert_many((0..100).map(|i| doc! { "_id": i, "name": format!("product {}", i) })).unwrap();
+        products.insert_one(doc! { "_id": 100, "name": "Wireless keyboard" }).unwrap();
+
+        let entries = Arc::new(Mutex::new(Vec::<ProfileEntry>::new()));
+        let sink = entries.clone();
+        db.profiler().set_callback(move |entry| sink.lock().unwrap().push(entry.clone()));
+        db.profiler().set_slow_threshold(Duration::ZERO);
+
+        let found = names(products.find(doc! { "name": { "$fuzzy": "keybaord" } }).run().unwrap());
+        assert_eq!(found, vec!["Wireless keyboard"]);
+        assert_eq!(entries.lock().unwrap().last().unwrap().docs_examined, 1);
+
+        // the updated documents are found by their new words
+        products.update_one(doc! { "_id": 42 }, doc! { "$set": { "name": "Wired mouse" } }).unwrap();
+        let found = names(products.find(doc! { "name": { "$fuzzy": "mosue" } }).run().unwrap());
+        assert_eq!(found, vec!["Wired mouse"]);
+
+        // the documents found by the index are still checked by the filter
+        products.update_one(doc! { "_id": 100 }, doc! { "$set": { "name": "Wireless trackpad" } }).unwrap();
+        let found = names(products.find(doc! { "name": { "$fuzzy": "keybaord" } }).run().unwrap());
+        assert!(found.is_empty());
+        db.profiler().disable();
+    }
+
+    // the index is built again when the database is opened
+    let db = Database::open_path(db_path.as_path()).unwrap();
+    let products = db.collection::<Document>("products");
+    let found = names(products.find(doc! { "name": { "$fuzzy": "mosue" } }).run().unwrap());
+    assert_eq!(found, vec!["Wired mouse"]);
+    let found = names(products
+        .aggregate(vec![
+            doc! { "$match": { "name": { "$fuzzy": "wirles" } } },
+            doc! { "$sort": { "_id": 1 } },
+        ])
+        .run()
+        .unwrap());
+    assert_eq!(found, vec!["Wired mouse", "Wireless trackpad"]);
+
+    products.delete_many(doc! { "name": { "$fuzzy": "trackpda" } }).unwrap();
+    assert_eq!(products.count_documents().unwrap(), 100);
+}
@@ -0,0 +1,123 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use polodb_core::bson::{doc, Document};
+use polodb_core::geo::{LineString, Point, Polygon};
+use polodb_core::{CollectionT, Error, IndexModel, Result};
+
+mod common;
+
+use common::prepare_db;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Place {
+    name: String,
+    location: Point,
+}
+
+#[test]
+fn test_geo_index_validation() {
+    let db = prepare_db("test-geo-index-validation").unwrap();
+    let places = db.collection::<Document>("places");
+    places.insert_one(doc! { "_id": 1, "location": { "type": "Point", "coordinates": [2.35, 48.85] } }).unwrap();
+    places.insert_one(doc! { "_id": 2, "location": "nowhere" }).unwrap();
+
+    // the existing documents are checked
+    let index = || IndexModel {
+        keys: doc! { "location": "2dsphere" },
+        options: None,
+    };
+    assert!(matches!(places.create_index(index()).unwrap_err(), Error::InvalidGeometry(_)));
+    places.delete_one(doc! { "_id": 2 }).unwrap();
+    places.create_index(index()).unwrap();
+    assert_eq!(places.list_index_names().unwrap(), vec!["location_2dsphere".to_string()]);
+
+    places.insert_one(doc! { "_id": 3, "location": LineString::new([Point::new(0.0, 0.0), Point::new(1.0, 1.0)]) }).unwrap();
+    places.insert_one(doc! { "_id": 4, "location": Polygon::new([
+        Point::new(0.0, 0.0),
+        Point::new(0.0, 1.0),
+        Point::new(1.0, 1.0),
+    ]) }).unwrap();
+    places.insert_one(doc! { "_id": 5 }).unwrap();
+    for invalid in [
+        doc! { "location": { "type": "Point", "coordinates": [2.35, 100] } },
+        doc! { "location": { "type": "Polygon", "coordinates": [[[0, 0], [0, 1], [1, 1]]] } },
+        doc! { "location": [2.35] },
+    ] {
+        let err = places.insert_one(invalid).unwrap_err();
+        assert!(matches!(err, Error::InvalidGeometry(_)));
+    }
+    let err = places.update_one(doc! { "_id": 1 }, doc! {
+        "$set": { "location": { "type": "Point", "coordinates": [200, 0] } },
+    }).unwrap_err();
+    assert!(matches!(err, Error::InvalidGeometry(_)));
+    assert_eq!(places.count_documents().unwrap(), 4);
+
+    places.drop_index("location_2dsphere").unwrap();
+    assert!(places.list_index_names().unwrap().is_empty());
+    places.insert_one(doc! { "location": "nowhere" }).unwrap();
+}
+
+#[test]
+fn test_geo_near() {
+    let db = prepare_db("test-geo-near").unwrap();
+    let places = db.collection::<Place>("places");
+    places.insert_many(vec![
+        Place { name: "Louvre".into(), location: Point::new(2.3376, 48.8606) },
+        Place { name: "Eiffel Tower".into(), location: Point::new(2.2945, 48.8584) },
+        Place { name: "Notre-Dame".into(), location: Point::new(2.3499, 48.8530) },
+        Place { name: "Versailles".into(), location: Point::new(2.1204, 48.8049) },
+    ]).unwrap();
+    assert_eq!(places.find_one(doc! { "name": "Louvre" }).unwrap().unwrap().location, Point::new(2.3376, 48.8606));
+
+    let city_hall = Point::new(2.3522, 48.8566);
+    let nearest = db.collection::<Document>("places")
+        .aggregate(vec![doc! {
+            "$geoNear": {
+                "near": city_hall,
+                "key": "location",
+                "distanceField": "distance",
+                "maxDistance": 5000,
+            },
+        }])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    let names = nearest.iter().map(|doc| doc.get_str("name").unwrap()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["Notre-Dame", "Louvre", "Eiffel Tower"]);
+    let distance = nearest[0].get_f64("distance").unwrap();
+    assert!((distance - city_hall.distance_to(&Point::new(2.3499, 48.8530))).abs() < 1e-6);
+
+    let with_distance = db.collection::<Document>("places")
+        .aggregate(vec![
+            doc! { "$addFields": { "meters": { "$geoDistance": ["$location", [2.1204, 48.8049]] } } },
+            doc! { "$sort": { "meters": 1 } },
+            doc! { "$limit": 1 },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(with_distance[0].get_str("name").unwrap(), "Versailles");
+    assert_eq!(with_distance[0].get_f64("meters").unwrap(), 0.0);
+
+    let err = db.collection::<Document>("places")
+        .aggregate(vec![doc! { "$geoNear": { "near": city_hall, "distanceField": "distance" } }])
+        .run()
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::ValidationError(_)));
+}
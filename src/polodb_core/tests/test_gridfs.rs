@@ -0,0 +1,191 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg(feature = "gridfs")]
+
+use std::io::{Read, Seek, SeekFrom, Write};
+use polodb_core::CollectionT;
+use polodb_core::bson::{doc, Binary, DateTime, Document};
+use polodb_core::bson::spec::BinarySubtype;
+use polodb_core::options::{GridFsBucketOptions, GridFsFindOptions};
+
+mod common;
+
+use common::prepare_db;
+
+fn content(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i * 7 % 251) as u8).collect()
+}
+
+#[test]
+fn test_gridfs_upload_download() {
+    let db = prepare_db("test-gridfs-upload-download").unwrap();
+    let bucket = db.gridfs_bucket_with_options(GridFsBucketOptions::builder()
+        .bucket_name("images")
+        .chunk_size_bytes(1000)
+        .build());
+
+    let data = content(10_500);
+    let mut upload = bucket.open_upload_stream("logo.png", Some(doc! { "owner": "alice" }));
+    for part in data.chunks(333) {
+        upload.write_all(part).unwrap();
+    }
+    let id = upload.finish().unwrap();
+
+    assert_eq!(db.collection::<Document>("images_chunks").count_documents().unwrap(), 11);
+
+    let mut download = bucket.open_download_stream(id).unwrap();
+    assert_eq!(download.file().length, 10_500);
+    assert_eq!(download.file().chunk_size, 1000);
+    let mut read = Vec::new();
+    download.read_to_end(&mut read).unwrap();
+    assert_eq!(read, data);
+
+    let files = bucket.find(doc! { "metadata.owner": "alice" }).unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].id, id);
+    assert_eq!(files[0].filename, "logo.png");
+
+    bucket.rename(id, "icon.png").unwrap();
+    assert_eq!(bucket.find(doc! { "filename": "icon.png" }).unwrap().len(), 1);
+
+    let empty = bucket.upload_from_reader("empty", &b""[..], None).unwrap();
+    let mut read = Vec::new();
+    bucket.open_download_stream(empty).unwrap().read_to_end(&mut read).unwrap();
+    assert!(read.is_empty());
+
+    bucket.delete(id).unwrap();
+    assert!(bucket.open_download_stream(id).is_err());
+    assert_eq!(db.collection::<Document>("images_chunks").count_documents().unwrap(), 0);
+}
+
+#[test]
+fn test_gridfs_corrupted_chunk() {
+    let db = prepare_db("test-gridfs-corrupted-chunk").unwrap();
+    let bucket = db.gridfs_bucket_with_options(GridFsBucketOptions::builder()
+        .chunk_size_bytes(100)
+        .build());
+    let id = bucket.upload_from_reader("data.bin", &content(450)[..], None).unwrap();
+
+    let chunks = db.collection::<Document>("fs_chunks");
+    chunks.update_one(doc! { "n": 2 }, doc! {
+        "$set": {
+            "data": Binary { subtype: BinarySubtype::Generic, bytes: vec![0; 100] },
+        },
+    }).unwrap();
+
+    let mut read = Vec::new();
+    let err = bucket.open_download_stream(id).unwrap().read_to_end(&mut read).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    chunks.delete_one(doc! { "n": 3 }).unwrap();
+    let mut read = Vec::new();
+    assert!(bucket.open_download_stream(id).unwrap().read_to_end(&mut read).is_err());
+}
+
+#[test]
+fn test_gridfs_abort_upload() {
+    let db = prepare_db("test-gridfs-abort-upload").unwrap();
+    let bucket = db.gridfs_bucket_with_options(GridFsBucketOptions::builder()
+        .chunk_size_bytes(10)
+        .build());
+    let chunks = db.collection::<Document>("fs_chunks");
+
+    let mut upload = bucket.open_upload_stream("aborted", None);
+    upload.write_all(&content(35)).unwrap();
+    assert_eq!(chunks.count_documents().unwrap(), 3);
+    upload.abort().unwrap();
+    assert_eq!(chunks.count_documents().unwrap(), 0);
+
+    let mut upload = bucket.open_upload_stream("dropped", None);
+    upload.write_all(&content(25)).unwrap();
+    drop(upload);
+    assert_eq!(chunks.count_documents().unwrap(), 0);
+    assert!(bucket.find(doc! {}).unwrap().is_empty());
+}
+
+#[test]
+fn test_gridfs_query_metadata() {
+    let db = prepare_db("test-gridfs-query-metadata").unwrap();
+    let bucket = db.gridfs_bucket();
+    let before = DateTime::now();
+    let intro = bucket.upload_from_reader("intro.mp4", &content(10)[..], Some(doc! {
+        "tags": ["video", "intro"],
+    })).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    let middle = DateTime::now();
+    let talk = bucket.upload_from_reader("talk.mp4", &content(10)[..], Some(doc! {
+        "tags": ["video"],
+    })).unwrap();
+    bucket.upload_from_reader("notes.txt", &content(10)[..], None).unwrap();
+
+    let ids = |files: Vec<polodb_core::GridFsFile>| files.into_iter().map(|file| file.id).collect::<Vec<_>>();
+    assert_eq!(ids(bucket.find(doc! { "filename": "talk.mp4" }).unwrap()), vec![talk]);
+    let uploaded = bucket.find(doc! { "uploadDate": { "$gte": before, "$lt": middle } }).unwrap();
+    assert_eq!(ids(uploaded), vec![intro]);
+
+    let tagged = |options: GridFsFindOptions| ids(bucket.find_with_options(doc! {}, options).unwrap());
+    assert_eq!(tagged(GridFsFindOptions::builder().tag("video").tag("intro").build()), vec![intro]);
+    assert_eq!(tagged(GridFsFindOptions::builder().tag("audio").build()), vec![]);
+    let newest = GridFsFindOptions::builder()
+        .tag("video")
+        .sort(doc! { "uploadDate": -1 })
+        .limit(1)
+        .build();
+    assert_eq!(tagged(newest), vec![talk]);
+
+    let mut indexes = db.collection::<Document>("fs_files").list_index_names().unwrap();
+    indexes.sort();
+    assert_eq!(indexes, vec!["filename_1".to_string(), "uploadDate_1".to_string()]);
+}
+
+#[test]
+fn test_gridfs_ranged_read() {
+    let db = prepare_db("test-gridfs-ranged-read").unwrap();
+    let bucket = db.gridfs_bucket_with_options(GridFsBucketOptions::builder()
+        .chunk_size_bytes(100)
+        .build());
+    let data = content(1050);
+    let id = bucket.upload_from_reader("video.mp4", &data[..], None).unwrap();
+
+    let mut read = Vec::new();
+    bucket.open_download_stream_range(id, 250..420).unwrap().read_to_end(&mut read).unwrap();
+    assert_eq!(read, &data[250..420]);
+
+    let mut read = Vec::new();
+    bucket.open_download_stream_range(id, 1000..).unwrap().read_to_end(&mut read).unwrap();
+    assert_eq!(read, &data[1000..]);
+
+    let mut read = Vec::new();
+    bucket.open_download_stream_range(id, 2000..3000).unwrap().read_to_end(&mut read).unwrap();
+    assert!(read.is_empty());
+
+    let mut stream = bucket.open_download_stream(id).unwrap();
+    assert_eq!(stream.seek(SeekFrom::End(-50)).unwrap(), 1000);
+    let mut read = Vec::new();
+    stream.read_to_end(&mut read).unwrap();
+    assert_eq!(read, &data[1000..]);
+    stream.seek(SeekFrom::Start(99)).unwrap();
+    let mut buf = [0; 3];
+    stream.read_exact(&mut buf).unwrap();
+    assert_eq!(buf, data[99..102]);
+    assert_eq!(stream.stream_position().unwrap(), 102);
+    assert!(stream.seek(SeekFrom::Current(-200)).is_err());
+
+    // the chunks outside of the range are not read
+    db.collection::<Document>("fs_chunks").delete_one(doc! { "n": 0 }).unwrap();
+    let mut read = Vec::new();
+    bucket.open_download_stream_range(id, 100..=199).unwrap().read_to_end(&mut read).unwrap();
+    assert_eq!(read, &data[100..200]);
+}
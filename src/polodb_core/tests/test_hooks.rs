@@ -0,0 +1,93 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::sync::{Arc, Mutex};
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Error, HookEvent};
+
+mod common;
+
+use common::prepare_db;
+
+#[test]
+fn test_pre_hooks() {
+    let db = prepare_db("test-pre-hooks").unwrap();
+    db.add_pre_hook("posts", HookEvent::Insert, |doc| {
+        if !doc.contains_key("title") {
+            return Err(Error::HookRejected("title is required".to_string()));
+        }
+        doc.insert("version", 1);
+        Ok(())
+    });
+    db.add_pre_hook("posts", HookEvent::Update, |doc| {
+        let version = doc.get_i32("version").unwrap_or(0);
+        doc.insert("version", version + 1);
+        Ok(())
+    });
+    db.add_pre_hook("posts", HookEvent::Delete, |doc| {
+        if doc.get_bool("pinned").unwrap_or(false) {
+            return Err(Error::HookRejected("pinned".to_string()));
+        }
+        Ok(())
+    });
+
+    let posts = db.collection::<Document>("posts");
+    posts.insert_one(doc! { "_id": 1, "title": "hello" }).unwrap();
+    posts.insert_one(doc! { "_id": 2, "title": "pinned", "pinned": true }).unwrap();
+    assert!(matches!(posts.insert_one(doc! { "_id": 3 }), Err(Error::HookRejected(_))));
+
+    posts.update_one(doc! { "_id": 1 }, doc! { "$set": { "title": "hello world" } }).unwrap();
+    let post = posts.find_one(doc! { "_id": 1 }).unwrap().unwrap();
+    assert_eq!(post, doc! { "_id": 1, "title": "hello world", "version": 2 });
+
+    assert!(matches!(posts.delete_many(doc! {}), Err(Error::HookRejected(_))));
+    assert_eq!(posts.count_documents().unwrap(), 2);
+
+    posts.delete_one(doc! { "_id": 1 }).unwrap();
+    assert_eq!(posts.count_documents().unwrap(), 1);
+
+    db.clear_hooks("posts");
+    posts.delete_many(doc! {}).unwrap();
+    assert_eq!(posts.count_documents().unwrap(), 0);
+}
+
+#[test]
+fn test_post_hooks() {
+    let db = prepare_db("test-post-hooks").unwrap();
+    let events = Arc::new(Mutex::new(Vec::<(HookEvent, Document)>::new()));
+    for event in [HookEvent::Insert, HookEvent::Update, HookEvent::Delete] {
+        let events = events.clone();
+        db.add_post_hook("items", event, move |doc| {
+            events.lock().unwrap().push((event, doc.clone()));
+        });
+    }
+
+    let items = db.collection::<Document>("items");
+    items.insert_one(doc! { "_id": 1, "n": 1 }).unwrap();
+    items.update_many(doc! {}, doc! { "$inc": { "n": 1 } }).unwrap();
+    items.delete_one(doc! { "_id": 1 }).unwrap();
+
+    let txn = db.start_transaction().unwrap();
+    txn.collection::<Document>("items").insert_one(doc! { "_id": 2 }).unwrap();
+    assert_eq!(events.lock().unwrap().len(), 3);
+    txn.rollback().unwrap();
+
+    let events = events.lock().unwrap();
+    assert_eq!(*events, vec![
+        (HookEvent::Insert, doc! { "_id": 1, "n": 1 }),
+        (HookEvent::Update, doc! { "_id": 1, "n": 2 }),
+        (HookEvent::Delete, doc! { "_id": 1, "n": 2 }),
+    ]);
+}
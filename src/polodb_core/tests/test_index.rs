@@ -12,9 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use polodb_core::{CollectionT, IndexModel, IndexOptions, Result};
-use bson::{doc, Document};
-use crate::common::prepare_db;
+use polodb_core::{CollectionT, ConfigBuilder, Error, ErrorCode, IndexModel, IndexOptions, Result};
+use polodb_core::options::Collation;
+use bson::{doc, Bson, Document};
+use crate::common::{prepare_db, prepare_db_with_config};
 
 mod common;
 
@@ -22,15 +23,34 @@ mod common;
 fn test_create_multi_keys_index() {
     let db = prepare_db("test-create-multi-keys-index").unwrap();
     let col = db.collection::<Document>("teacher");
-    let result = col.create_index(IndexModel {
+    col.create_index(IndexModel {
         keys: doc! {
             "age": 1,
             "name": 1,
         },
         options: None,
+    }).unwrap();
+    assert_eq!(col.list_index_names().unwrap(), vec!["age_1_name_1".to_string()]);
+
+    // the geo indexes are on a single field
+    let result = col.create_index(IndexModel {
+        keys: doc! {
+            "location": "2dsphere",
+            "name": 1,
+        },
+        options: None,
     });
     assert!(result.is_err());
     assert!(result.unwrap_err().to_string().contains("only support single field indexes currently"));
+
+    let result = col.create_index(IndexModel {
+        keys: doc! {
+            "age": 1,
+            "name": -1,
+        },
+        options: None,
+    });
+    assert!(matches!(result, Err(Error::OnlySupportsAscendingOrder(_))));
 }
 
 #[test]
@@ -204,7 +224,12 @@ fn test_create_unique_index() {
         });
 
         assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("duplicate key error"));
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("duplicate key error"));
+        assert_eq!(err.code(), ErrorCode::DuplicateKey);
+        assert_eq!(err.collection(), Some("teacher"));
+        assert_eq!(err.index(), Some("name_1"));
+        assert!(err.key().unwrap().contains("David"));
     });
 }
 
@@ -394,3 +419,699 @@ fn test_issue_171() {
 
     assert_eq!(docs[0].get_str("name").unwrap(), "David");
 }
+
+#[test]
+fn test_create_index_idempotent() {
+    let db = prepare_db("test-create-index-idempotent").unwrap();
+    let col = db.collection::<Document>("teacher");
+
+    let name = col.create_index(IndexModel {
+        keys: doc! { "name": 1 },
+        options: Some(IndexOptions::builder().unique(true).build()),
+    }).unwrap();
+    assert_eq!(name, "name_1");
+
+    // the same index, with or without another name
+    let name = col.create_index(IndexModel {
+        keys: doc! { "name": 1 },
+        options: Some(IndexOptions::builder().unique(true).build()),
+    }).unwrap();
+    assert_eq!(name, "name_1");
+    let name = col.create_index(IndexModel {
+        keys: doc! { "name": 1 },
+        options: Some(IndexOptions::builder().name("by_name").unique(true).build()),
+    }).unwrap();
+    assert_eq!(name, "name_1");
+    assert_eq!(col.list_index_names().unwrap(), vec!["name_1".to_string()]);
+
+    // the name of an existing index with other options
+    let err = col.create_index(IndexModel {
+        keys: doc! { "name": 1 },
+        options: None,
+    }).unwrap_err();
+    assert!(matches!(err, Error::IndexAlreadyExists(ref name) if name == "name_1"));
+    assert_eq!(err.code(), ErrorCode::AlreadyExists);
+
+    let err = col.create_index(IndexModel {
+        keys: doc! { "age": 1 },
+        options: Some(IndexOptions::builder().partial_filter_expression(doc! { "age": { "$ne": 18 } }).build()),
+    }).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::Unsupported);
+}
+
+#[test]
+fn test_case_insensitive_index() {
+    let db = prepare_db("test-case-insensitive-index").unwrap();
+    let col = db.collection::<Document>("users");
+    col.create_index(IndexModel {
+        keys: doc! { "email": 1 },
+        options: Some(IndexOptions::builder()
+            .unique(true)
+            .collation(Collation::CaseInsensitive)
+            .build()),
+    }).unwrap();
+
+    col.insert_one(doc! { "_id": 1, "email": "Ann@example.com" }).unwrap();
+    let err = col.insert_one(doc! { "_id": 2, "email": "ann@EXAMPLE.com" }).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::DuplicateKey);
+
+    // the simple queries don't use the index of another collation
+    assert!(col.find_one(doc! { "email": "ann@example.com" }).unwrap().is_none());
+    assert!(col.find_one(doc! { "email": "Ann@example.com" }).unwrap().is_some());
+
+    let info = col.describe_index("email_1").unwrap().unwrap();
+    assert_eq!(info.options.unwrap().collation, Some(Collation::CaseInsensitive));
+}
+
+#[test]
+fn test_index_selection() {
+    let db = prepare_db("test-index-selection").unwrap();
+    let metrics = db.metrics();
+    metrics.enable();
+
+    let col = db.collection::<Document>("orders");
+    col.create_index(IndexModel {
+        keys: doc! { "status": 1 },
+        options: None,
+    }).unwrap();
+    col.create_index(IndexModel {
+        keys: doc! { "user": 1 },
+        options: None,
+    }).unwrap();
+    col.insert_many((0..500).map(|i| doc! {
+        "_id": i,
+        "status": "active",
+        "user": format!("user{}", i % 100),
+    })).unwrap();
+
+    // the index on the user finds 5 documents, the one on the status all of them
+    let scanned = metrics.docs_scanned();
+    let found = col.find(doc! { "status": "active", "user": "user7" })
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(found.len(), 5);
+    assert_eq!(metrics.docs_scanned() - scanned, 5);
+
+    let scanned = metrics.docs_scanned();
+    let found = col.aggregate(vec![
+        doc! { "$match": { "user": "user8", "status": "active" } },
+        doc! { "$count": "count" },
+    ]).run().unwrap().collect::<Result<Vec<Document>>>().unwrap();
+    assert_eq!(found[0].get_i64("count").unwrap(), 5);
+    assert_eq!(metrics.docs_scanned() - scanned, 5);
+}
+
+#[test]
+fn test_analyze() {
+    let db = prepare_db("test-analyze").unwrap();
+    let metrics = db.metrics();
+    metrics.enable();
+
+    let col = db.collection::<Document>("items");
+    col.create_index(IndexModel {
+        keys: doc! { "kind": 1 },
+        options: None,
+    }).unwrap();
+    col.create_index(IndexModel {
+        keys: doc! { "level": 1 },
+        options: None,
+    }).unwrap();
+    col.insert_many((0..300).map(|i| doc! {
+        "_id": i,
+        "kind": if i < 250 { "a" } else { "b" },
+        "level": i % 30,
+    })).unwrap();
+    col.insert_one(doc! { "_id": 300 }).unwrap();
+
+    let statistics = col.analyze().unwrap();
+    assert_eq!(statistics.documents, 301);
+
+    let kind = &statistics.indexes["kind_1"];
+    assert_eq!(kind.field, "kind");
+    assert_eq!(kind.count, 300);
+    assert_eq!(kind.distinct, 2);
+    assert_eq!(kind.most_common[0].value, Bson::String("a".into()));
+    assert_eq!(kind.most_common[0].count, 250);
+
+    let level = &statistics.indexes["level_1"];
+    assert_eq!(level.distinct, 30);
+    assert_eq!(level.most_common.len(), 10);
+    assert!(level.most_common.iter().all(|frequency| frequency.count == 10));
+
+    // the index on the level finds 10 documents, the one on the kind 50
+    let scanned = metrics.docs_scanned();
+    let found = col.find(doc! { "kind": "b", "level": 5 })
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(metrics.docs_scanned() - scanned, 10);
+}
+
+#[test]
+fn test_key_restart_interval() {
+    let mut config = ConfigBuilder::new();
+    config.set_key_restart_interval(0);
+    assert_eq!(config.get_key_restart_interval(), 1);
+    // the interval of the storage is a signed integer
+    config.set_key_restart_interval(u32::MAX);
+    assert_eq!(config.get_key_restart_interval(), i32::MAX as u32);
+    let db = prepare_db_with_config("test-key-restart-interval", config.take()).unwrap();
+
+    let col = db.collection::<Document>("pages");
+    col.create_index(IndexModel {
+        keys: doc! { "url": 1 },
+        options: None,
+    }).unwrap();
+    col.insert_many((0..500).map(|i| doc! {
+        "_id": i,
+        "url": format!("https://example.com/articles/2024/archive/page-{:04}", i),
+    })).unwrap();
+    // write the keys to the storage blocks
+    db.compact().unwrap();
+
+    let found = col.find(doc! { "url": "https://example.com/articles/2024/archive/page-0321" })
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].get_i32("_id").unwrap(), 321);
+
+    let found = col.find(doc! {})
+        .sort(doc! { "url": -1 })
+        .limit(3)
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<i32>>();
+    assert_eq!(found, vec![499, 498, 497]);
+}
+
+#[test]
+fn test_create_indexes_in_parallel() {
+    let mut config = ConfigBuilder::new();
+    config.set_index_build_parallelism(2);
+    let db = prepare_db_with_config("test-create-indexes-in-parallel", config.take()).unwrap();
+
+    let col = db.collection::<Document>("books");
+    col.insert_many((0..3000).map(|i| doc! {
+        "_id": i,
+        "isbn": format!("isbn-{}", i),
+        "year": 1900 + i % 100,
+        "author": format!("author-{}", i % 30),
+    })).unwrap();
+
+    let names = col.create_indexes(vec![
+        IndexModel {
+            keys: doc! { "isbn": 1 },
+            options: Some(IndexOptions {
+                unique: Some(true),
+                ..Default::default()
+            }),
+        },
+        IndexModel {
+            keys: doc! { "year": 1 },
+            options: None,
+        },
+        IndexModel {
+            keys: doc! { "author": 1 },
+            options: None,
+        },
+        IndexModel {
+            keys: doc! { "year": 1 },
+            options: None,
+        },
+    ]).unwrap();
+    assert_eq!(names, vec!["isbn_1", "year_1", "author_1", "year_1"]);
+    assert_eq!(col.list_index_names().unwrap().len(), 3);
+
+    let found = col.find(doc! { "isbn": "isbn-2021" })
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].get_i32("_id").unwrap(), 2021);
+    assert_eq!(col.find(doc! { "year": 1950 }).run().unwrap().count(), 30);
+    assert_eq!(col.find(doc! { "author": "author-7" }).run().unwrap().count(), 100);
+
+    // the indexes are maintained by the writes that follow
+    col.insert_one(doc! { "_id": 3000, "isbn": "isbn-3000", "year": 1950 }).unwrap();
+    assert_eq!(col.find(doc! { "year": 1950 }).run().unwrap().count(), 31);
+
+    // nothing is created when one of the indexes cannot be built
+    let result = col.create_indexes(vec![
+        IndexModel {
+            keys: doc! { "title": 1 },
+            options: None,
+        },
+        IndexModel {
+            keys: doc! { "author": 1 },
+            options: Some(IndexOptions {
+                name: Some("unique_author".to_string()),
+                unique: Some(true),
+                ..Default::default()
+            }),
+        },
+    ]);
+    assert!(matches!(result.unwrap_err(), Error::DuplicateKey(_)));
+    assert_eq!(col.list_index_names().unwrap().len(), 3);
+}
+
+#[test]
+fn test_multikey_index() {
+    let db = prepare_db("test-multikey-index").unwrap();
+    let col = db.collection::<Document>("shirts");
+    col.create_index(IndexModel {
+        keys: doc! { "tags": 1 },
+        options: None,
+    }).unwrap();
+    col.insert_many(vec![
+        doc! { "_id": 1, "tags": ["rojo", "azul", "rojo"] },
+        doc! { "_id": 2, "tags": "azul" },
+        doc! { "_id": 3, "tags": [] },
+    ]).unwrap();
+
+    let ids = |filter: Document| col.find(filter)
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<i32>>();
+
+    // each element has an entry of the index
+    assert_eq!(ids(doc! { "tags": "rojo" }), vec![1]);
+    assert_eq!(ids(doc! { "tags": "azul" }), vec![1, 2]);
+    assert_eq!(ids(doc! { "tags": "rojo" }), col.find(doc! { "tags": "rojo" }).collection_scan().run().unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<i32>>());
+
+    col.update_one(doc! { "_id": 2 }, doc! { "$set": { "tags": ["verde"] } }).unwrap();
+    col.update_one(doc! { "_id": 1 }, doc! { "$pull": { "tags": "rojo" } }).unwrap();
+    col.update_one(doc! { "_id": 3 }, doc! { "$push": { "tags": "rojo" } }).unwrap();
+    assert_eq!(ids(doc! { "tags": "rojo" }), vec![3]);
+    assert_eq!(ids(doc! { "tags": "azul" }), vec![1]);
+    assert_eq!(ids(doc! { "tags": "verde" }), vec![2]);
+    assert!(db.verify().unwrap().is_ok());
+
+    // a document is read once in the order of the index, at its least element
+    col.update_one(doc! { "_id": 1 }, doc! { "$addToSet": { "tags": { "$each": ["amarillo", "zafiro"] } } }).unwrap();
+    let sorted = ids(doc! {});
+    assert_eq!(sorted, vec![1, 2, 3]);
+    let sorted: Vec<i32> = col.find(doc! {}).sort(doc! { "tags": 1 }).run().unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect();
+    assert_eq!(sorted, vec![1, 3, 2]);
+    let sorted: Vec<i32> = col.find(doc! {}).sort(doc! { "tags": -1 }).run().unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect();
+    assert_eq!(sorted, vec![1, 2, 3]);
+
+    // the elements are unique among the documents
+    let unique = db.collection::<Document>("unique");
+    unique.create_index(IndexModel {
+        keys: doc! { "codes": 1 },
+        options: Some(IndexOptions::builder().unique(true).build()),
+    }).unwrap();
+    unique.insert_one(doc! { "_id": 1, "codes": ["a", "b", "a"] }).unwrap();
+    unique.update_one(doc! { "_id": 1 }, doc! { "$push": { "codes": "c" } }).unwrap();
+    assert!(matches!(unique.insert_one(doc! { "_id": 2, "codes": ["d", "c"] }), Err(Error::DuplicateKey(_))));
+    unique.insert_one(doc! { "_id": 2, "codes": ["d", "e"] }).unwrap();
+    assert!(matches!(unique.update_one(doc! { "_id": 2 }, doc! { "$addToSet": { "codes": "a" } }), Err(Error::DuplicateKey(_))));
+}
+
+#[test]
+fn test_compound_index() {
+    let db = prepare_db("test-compound-index").unwrap();
+    let metrics = db.metrics();
+    metrics.enable();
+
+    let col = db.collection::<Document>("products");
+    col.insert_many((0..200).map(|i| doc! {
+        "_id": i,
+        "category": format!("category{}", i % 10),
+        "tags": if i % 2 == 0 { vec!["new", "sale"] } else { vec!["old"] },
+        "price": i,
+    })).unwrap();
+    col.create_index(IndexModel {
+        keys: doc! { "category": 1, "tags": 1 },
+        options: None,
+    }).unwrap();
+
+    let ids = |filter: Document| {
+        let scanned = metrics.docs_scanned();
+        let ids = col.find(filter)
+            .run()
+            .unwrap()
+            .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+            .collect::<Vec<i32>>();
+        (ids, metrics.docs_scanned() - scanned)
+    };
+
+    // the index finds the documents by the first field alone
+    let (found, scanned) = ids(doc! { "category": "category3" });
+    assert_eq!(found.len(), 20);
+    assert_eq!(scanned, 20);
+
+    // or by both fields, an element of the array has an entry
+    let (found, scanned) = ids(doc! { "category": "category4", "tags": "sale" });
+    assert_eq!(found.len(), 20);
+    assert_eq!(scanned, 20);
+    let (found, scanned) = ids(doc! { "category": "category4", "tags": "old" });
+    assert!(found.is_empty());
+    assert_eq!(scanned, 0);
+
+    // the other conditions are checked on the documents found
+    let (found, scanned) = ids(doc! { "category": "category5", "tags": "old", "price": { "$lt": 50 } });
+    assert_eq!(found, vec![5, 15, 25, 35, 45]);
+    assert_eq!(scanned, 20);
+
+    // the second field alone is not a prefix of the index
+    let (found, scanned) = ids(doc! { "tags": "new" });
+    assert_eq!(found.len(), 100);
+    assert_eq!(scanned, 200);
+
+    col.update_many(doc! { "category": "category4" }, doc! { "$set": { "tags": ["old"] } }).unwrap();
+    assert_eq!(ids(doc! { "category": "category4", "tags": "old" }).0.len(), 20);
+    assert!(ids(doc! { "category": "category4", "tags": "sale" }).0.is_empty());
+    col.delete_many(doc! { "category": "category4", "tags": "old" }).unwrap();
+    assert!(ids(doc! { "category": "category4" }).0.is_empty());
+    assert!(db.verify().unwrap().is_ok());
+
+    // the combination of the values is unique, a missing field is null
+    let unique = db.collection::<Document>("unique");
+    unique.create_index(IndexModel {
+        keys: doc! { "user": 1, "day": 1 },
+        options: Some(IndexOptions::builder().unique(true).build()),
+    }).unwrap();
+    unique.insert_one(doc! { "_id": 1, "user": "a", "day": 1 }).unwrap();
+    unique.insert_one(doc! { "_id": 2, "user": "a", "day": 2 }).unwrap();
+    unique.insert_one(doc! { "_id": 3, "user": "b", "day": 1 }).unwrap();
+    unique.insert_one(doc! { "_id": 4, "user": "b" }).unwrap();
+    assert!(matches!(unique.insert_one(doc! { "_id": 5, "user": "a", "day": 1 }), Err(Error::DuplicateKey(_))));
+    assert!(matches!(unique.insert_one(doc! { "_id": 5, "user": "b", "day": null }), Err(Error::DuplicateKey(_))));
+    assert!(matches!(unique.update_one(doc! { "_id": 2 }, doc! { "$set": { "day": 1 } }), Err(Error::DuplicateKey(_))));
+    assert!(db.verify().unwrap().is_ok());
+
+    // the entry of a missing field is null, but the filter doesn't find the documents missing it
+    col.insert_many(vec![
+        doc! { "_id": 300, "tags": ["new"] },
+        doc! { "_id": 301, "category": null, "tags": ["old"] },
+    ]).unwrap();
+    for filter in [doc! { "category": null }, doc! { "category": null, "tags": "new" }] {
+        let explain = col.find(filter.clone()).explain().unwrap();
+        assert_eq!(explain.get_document("winningPlan").unwrap().get_str("stage").unwrap(), "IXSCAN");
+        let scanned: Vec<i32> = col.find(filter.clone()).collection_scan().run().unwrap()
+            .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+            .collect();
+        assert_eq!(ids(filter).0, scanned);
+    }
+    assert_eq!(ids(doc! { "category": null }).0, vec![301]);
+
+    // a compound index has no expiration
+    let result = col.create_index(IndexModel {
+        keys: doc! { "category": 1, "created": 1 },
+        options: Some(IndexOptions::builder().expire_after_secs(60).build()),
+    });
+    assert!(matches!(result, Err(Error::UnsupportedIndexOption(_))));
+}
+
+#[test]
+fn test_index_range_scan() {
+    let db = prepare_db("test-index-range-scan").unwrap();
+    let metrics = db.metrics();
+    metrics.enable();
+
+    let col = db.collection::<Document>("events");
+    col.create_index(IndexModel {
+        keys: doc! { "created_at": 1 },
+        options: None,
+    }).unwrap();
+    col.insert_many((0..1000).map(|i| doc! {
+        "_id": i,
+        "created_at": (i * 7) % 1000,
+        "kind": if i % 2 == 0 { "click" } else { "view" },
+    })).unwrap();
+    col.insert_many(vec![
+        doc! { "_id": 1000, "created_at": "later" },
+        doc! { "_id": 1002 },
+    ]).unwrap();
+    col.insert_many((0..5).map(|i: i32| doc! {
+        "_id": 2000 + i,
+        "created_at": bson::DateTime::from_millis(1_700_000_000_000 + i64::from(i) * 1000),
+    })).unwrap();
+
+    let created = |find: polodb_core::action::Find<Document>| {
+        let scanned = metrics.docs_scanned();
+        let values = find.run()
+            .unwrap()
+            .map(|doc| doc.unwrap().get("created_at").unwrap().clone())
+            .collect::<Vec<Bson>>();
+        (values, metrics.docs_scanned() - scanned)
+    };
+
+    // the lower bound starts the scan of the index, which stops after the last number
+    let (found, scanned) = created(col.find(doc! { "created_at": { "$gt": 990 } }));
+    assert_eq!(found.len(), 9);
+    assert_eq!(scanned, 9);
+    // both sides bound the scan
+    let (found, scanned) = created(col.find(doc! { "created_at": { "$gte": 100.5, "$lt": 110 } }));
+    assert_eq!(found.len(), 9);
+    assert_eq!(scanned, 9);
+    let (found, scanned) = created(col.find(doc! { "created_at": { "$gt": 0.5, "$lte": 2.5 } }));
+    assert_eq!(found, vec![Bson::Int32(1), Bson::Int32(2)]);
+    assert_eq!(scanned, 2);
+    // without a lower bound, the scan starts at the least number
+    let (found, scanned) = created(col.find(doc! { "created_at": { "$lte": 2.5 } }));
+    assert_eq!(found, vec![Bson::Int32(0), Bson::Int32(1), Bson::Int32(2)]);
+    assert_eq!(scanned, 3);
+    // the range of dates only reads the dates
+    let (found, scanned) = created(col.find(doc! {
+        "created_at": { "$gte": bson::DateTime::from_millis(1_700_000_002_000) },
+    }));
+    assert_eq!(found.len(), 3);
+    assert_eq!(scanned, 3);
+    let (found, scanned) = created(col.find(doc! { "created_at": { "$gte": "a" } }));
+    assert_eq!(found, vec![Bson::String("later".to_string())]);
+    assert_eq!(scanned, 1);
+
+    // the sort walks the index backward from the upper bound
+    let (found, scanned) = created(col.find(doc! { "created_at": { "$lte": 500.0 } })
+        .sort(doc! { "created_at": -1 })
+        .limit(3));
+    assert_eq!(found, vec![Bson::Int32(500), Bson::Int32(499), Bson::Int32(498)]);
+    assert_eq!(scanned, 3);
+
+    let ids = |filter: Document| col.find(filter)
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect::<Vec<i32>>();
+    assert_eq!(ids(doc! { "created_at": { "$lt": 6.0 } }).len(), 6);
+    assert_eq!(ids(doc! { "created_at": { "$gt": 4, "$lt": 6 } }), vec![715]);
+
+    // the elements of an array may each be in one side of the range,
+    // only one side bounds the scan of the multikey index
+    col.insert_one(doc! { "_id": 1001, "created_at": [5, 995] }).unwrap();
+    let (found, scanned) = created(col.find(doc! { "created_at": { "$gte": 100.5, "$lt": 110 } }));
+    assert_eq!(found.len(), 10);
+    assert!(scanned > 10);
+    // a document is found once, by one of the elements in the range
+    assert_eq!(ids(doc! { "created_at": { "$lt": 6.0 } }).len(), 7);
+    assert_eq!(ids(doc! { "created_at": { "$gt": 4, "$lt": 6 } }), vec![715, 1001]);
+
+    // the same documents as the scan of the collection
+    for filter in [
+        doc! { "created_at": { "$gte": 250, "$lt": 300 }, "kind": "click" },
+        doc! { "created_at": { "$gt": 998 } },
+        doc! { "created_at": { "$gte": 5, "$lte": 5 } },
+        doc! { "created_at": { "$gte": 400, "$lt": 407 } },
+        doc! { "created_at": { "$gt": 10, "$lt": 20.5 } },
+        doc! { "created_at": { "$lt": 6 } },
+        doc! { "created_at": { "$gte": "a" } },
+        doc! { "created_at": { "$lt": "m" } },
+        doc! { "created_at": { "$lt": bson::DateTime::from_millis(1_700_000_001_000) } },
+    ] {
+        let mut found = ids(filter.clone());
+        found.sort();
+        let scanned: Vec<i32> = col.find(filter).collection_scan().run().unwrap()
+            .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+            .collect();
+        assert_eq!(found, scanned);
+    }
+    assert!(ids(doc! { "created_at": { "$gte": 400, "$lt": 407 } }).contains(&1001));
+    // a range only holds the values of the type of its bound
+    assert!(!ids(doc! { "created_at": { "$lt": 6 } }).contains(&1000));
+}
+
+#[test]
+fn test_find_explain() {
+    let db = prepare_db("test-find-explain").unwrap();
+    let col = db.collection::<Document>("events");
+    col.insert_many((0..100).map(|i| doc! { "_id": i, "kind": if i % 4 == 0 { "click" } else { "view" }, "at": i })).unwrap();
+
+    let explain = col.find(doc! { "kind": "click" }).explain().unwrap();
+    assert_eq!(explain.get_str("namespace").unwrap(), "events");
+    assert_eq!(explain.get_document("winningPlan").unwrap().get_str("stage").unwrap(), "COLLSCAN");
+    let stats = explain.get_document("executionStats").unwrap();
+    assert_eq!(stats.get_i64("nReturned").unwrap(), 25);
+    assert_eq!(stats.get_i64("docsExamined").unwrap(), 100);
+    assert_eq!(explain.get_i64("estimatedDocsExamined").unwrap(), 100);
+    assert!(!explain.get_str("program").unwrap().is_empty());
+
+    let explain = col.find(doc! { "_id": 42 }).explain().unwrap();
+    let plan = explain.get_document("winningPlan").unwrap();
+    assert_eq!(plan.get_str("stage").unwrap(), "IDHACK");
+    assert_eq!(plan.get_i32("_id").unwrap(), 42);
+    assert_eq!(explain.get_document("executionStats").unwrap().get_i64("nReturned").unwrap(), 1);
+
+    col.create_index(IndexModel {
+        keys: doc! { "kind": 1 },
+        options: None,
+    }).unwrap();
+    col.create_index(IndexModel {
+        keys: doc! { "at": 1 },
+        options: None,
+    }).unwrap();
+
+    let explain = col.find(doc! { "kind": "click" }).explain().unwrap();
+    let plan = explain.get_document("winningPlan").unwrap();
+    assert_eq!(plan.get_str("stage").unwrap(), "IXSCAN");
+    assert_eq!(plan.get_str("indexName").unwrap(), "kind_1");
+    let stats = explain.get_document("executionStats").unwrap();
+    assert_eq!(stats.get_i64("nReturned").unwrap(), 25);
+    assert_eq!(stats.get_i64("docsExamined").unwrap(), 25);
+
+    let explain = col.find(doc! { "at": { "$gte": 10, "$lt": 20 } }).explain().unwrap();
+    let plan = explain.get_document("winningPlan").unwrap();
+    assert_eq!(plan.get_str("stage").unwrap(), "IXSCAN");
+    assert_eq!(plan.get_str("indexName").unwrap(), "at_1");
+    assert_eq!(plan.get_str("direction").unwrap(), "forward");
+    let bounds = plan.get_document("indexBounds").unwrap();
+    assert_eq!(bounds.get_i32("min").unwrap(), 10);
+    assert_eq!(bounds.get_i32("max").unwrap(), 20);
+    assert!(!bounds.get_bool("minExcluded").unwrap());
+    assert!(!bounds.get_bool("maxIncluded").unwrap());
+    let stats = explain.get_document("executionStats").unwrap();
+    assert_eq!(stats.get_i64("nReturned").unwrap(), 10);
+    assert_eq!(stats.get_i64("docsExamined").unwrap(), 10);
+}
+
+#[test]
+fn test_partial_index() {
+    let db = prepare_db("test-partial-index").unwrap();
+    let col = db.collection::<Document>("users");
+    col.insert_many(vec![
+        doc! { "_id": 1, "age": 10, "email": "a@example.com", "active": false },
+        doc! { "_id": 2, "age": 30, "email": "a@example.com", "active": false },
+        doc! { "_id": 3, "age": 40, "email": "b@example.com", "active": true },
+    ]).unwrap();
+    col.create_index(IndexModel {
+        keys: doc! { "age": 1 },
+        options: Some(IndexOptions::builder().partial_filter_expression(doc! { "age": { "$gte": 18 } }).build()),
+    }).unwrap();
+    // unique among the active users only
+    col.create_index(IndexModel {
+        keys: doc! { "email": 1 },
+        options: Some(IndexOptions::builder()
+            .unique(true)
+            .partial_filter_expression(doc! { "active": true })
+            .build()),
+    }).unwrap();
+
+    let plan = |filter: Document| {
+        let explain = col.find(filter).explain().unwrap();
+        explain.get_document("winningPlan").unwrap().get_str("stage").unwrap().to_string()
+    };
+    let ids = |filter: Document| -> Vec<i32> {
+        col.find(filter).run().unwrap().map(|doc| doc.unwrap().get_i32("_id").unwrap()).collect()
+    };
+    assert_eq!(plan(doc! { "age": 30 }), "IXSCAN");
+    assert_eq!(ids(doc! { "age": 30 }), vec![2]);
+    assert_eq!(plan(doc! { "age": 10 }), "COLLSCAN");
+    assert_eq!(ids(doc! { "age": 10 }), vec![1]);
+    assert_eq!(plan(doc! { "age": { "$gte": 20 } }), "IXSCAN");
+    assert_eq!(ids(doc! { "age": { "$gte": 20 } }), vec![2, 3]);
+    assert_eq!(plan(doc! { "age": { "$gte": 5 } }), "COLLSCAN");
+    assert_eq!(ids(doc! { "age": { "$gte": 5 } }), vec![1, 2, 3]);
+    assert_eq!(plan(doc! { "email": "a@example.com", "active": true }), "IXSCAN");
+    assert!(ids(doc! { "email": "a@example.com", "active": true }).is_empty());
+    assert_eq!(plan(doc! { "email": "a@example.com" }), "COLLSCAN");
+
+    // the sort isn't made by the partial index
+    let sorted: Vec<i32> = col.find(doc! {}).sort(doc! { "age": -1 }).run().unwrap()
+        .map(|doc| doc.unwrap().get_i32("age").unwrap())
+        .collect();
+    assert_eq!(sorted, vec![40, 30, 10]);
+
+    // the entries follow the documents in and out of the filter
+    col.update_one(doc! { "_id": 2 }, doc! { "$set": { "age": 12 } }).unwrap();
+    col.update_one(doc! { "_id": 1 }, doc! { "$set": { "age": 50 } }).unwrap();
+    assert_eq!(ids(doc! { "age": { "$gte": 20 } }), vec![3, 1]);
+    col.insert_one(doc! { "_id": 4, "email": "b@example.com", "active": false }).unwrap();
+    let err = col.insert_one(doc! { "_id": 5, "email": "b@example.com", "active": true }).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::DuplicateKey);
+    let err = col.update_one(doc! { "_id": 1 }, doc! { "$set": { "email": "b@example.com", "active": true } }).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::DuplicateKey);
+    assert!(db.verify().unwrap().is_ok());
+
+    // a TTL index expires the documents by their field, whatever the filter
+    let err = col.create_index(IndexModel {
+        keys: doc! { "created": 1 },
+        options: Some(IndexOptions::builder()
+            .expire_after_secs(60)
+            .partial_filter_expression(doc! { "active": true })
+            .build()),
+    }).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::Unsupported);
+
+    // a missing field matches no condition, null included, like the filter of a query
+    let tasks = db.collection::<Document>("tasks");
+    tasks.insert_many(vec![
+        doc! { "_id": 1, "owner": "a" },
+        doc! { "_id": 2, "owner": "a", "closed": null },
+        doc! { "_id": 3, "owner": "a", "closed": 5 },
+        doc! { "_id": 4, "owner": "b", "closed": null },
+    ]).unwrap();
+    tasks.create_index(IndexModel {
+        keys: doc! { "owner": 1 },
+        options: Some(IndexOptions::builder().partial_filter_expression(doc! { "closed": null }).build()),
+    }).unwrap();
+    for filter in [
+        doc! { "owner": "a", "closed": null },
+        doc! { "owner": "a", "closed": { "$in": [null, 5] } },
+        doc! { "owner": "a", "closed": { "$eq": null } },
+    ] {
+        let indexed: Vec<i32> = tasks.find(filter.clone()).run().unwrap()
+            .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+            .collect();
+        let scanned: Vec<i32> = tasks.find(filter).collection_scan().run().unwrap()
+            .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+            .collect();
+        assert_eq!(indexed, scanned);
+    }
+    let explain = tasks.find(doc! { "owner": "a", "closed": null }).explain().unwrap();
+    assert_eq!(explain.get_document("winningPlan").unwrap().get_str("indexName").unwrap(), "owner_1");
+}
+
+#[test]
+fn test_sparse_index() {
+    let db = prepare_db("test-sparse-index").unwrap();
+    let col = db.collection::<Document>("items");
+    col.create_index(IndexModel {
+        keys: doc! { "sync_token": 1 },
+        options: Some(IndexOptions::builder().sparse(true).unique(true).build()),
+    }).unwrap();
+    col.insert_many((0..20).map(|i| match i % 10 {
+        0 => doc! { "_id": i, "sync_token": format!("t{}", i) },
+        _ => doc! { "_id": i },
+    })).unwrap();
+
+    let stats = col.analyze().unwrap();
+    assert_eq!(stats.indexes["sync_token_1"].count, 2);
+    let found = col.find_one(doc! { "sync_token": "t10" }).unwrap().unwrap();
+    assert_eq!(found.get_i32("_id").unwrap(), 10);
+}
@@ -18,12 +18,13 @@ use std::path::PathBuf;
 use bson::Document;
 use bson::spec::ElementType;
 use serde::{Deserialize, Serialize};
-use polodb_core::{Database, Result, CollectionT};
+use polodb_core::{ConfigBuilder, Database, Error, IndexModel, IndexOptions, ObjectIdCounterMode, ObjectIdExt, Result, CollectionT};
+use polodb_core::options::InsertManyOptions;
 use polodb_core::bson::{doc, Bson};
 
 mod common;
 
-use common::prepare_db;
+use common::{prepare_db, prepare_db_with_config};
 use polodb_core::test_utils::mk_db_path;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -287,3 +288,118 @@ fn test_insert_persist() {
         assert_eq!(result.len() as u64, i as u64 + 1);
     }
 }
+
+#[test]
+fn test_insert_many_unordered() {
+    vec![
+        prepare_db("test-insert-many-unordered").unwrap(),
+    ].iter().for_each(|db| {
+        let col = db.collection::<Document>("users");
+        col.create_index(IndexModel {
+            keys: doc! {
+                "email": 1,
+            },
+            options: Some(IndexOptions {
+                unique: Some(true),
+                ..Default::default()
+            }),
+        }).unwrap();
+
+        let docs = vec![
+            doc! { "_id": 1, "email": "a@polodb.org" },
+            doc! { "_id": 2, "email": "a@polodb.org" },
+            doc! { "_id": 3, "email": "b@polodb.org" },
+            doc! { "_id": 4, "email": "b@polodb.org" },
+            doc! { "_id": 5, "email": "c@polodb.org" },
+        ];
+
+        let err = col.insert_many(&docs).unwrap_err();
+        assert!(matches!(err, Error::DuplicateKey(_)));
+        assert_eq!(col.count_documents().unwrap(), 0);
+
+        let options = InsertManyOptions::builder().ordered(false).build();
+        let result = col.insert_many_with_options(&docs, options).unwrap();
+        assert!(result.has_write_errors());
+        assert_eq!(result.inserted_ids.len(), 3);
+        assert_eq!(result.inserted_ids.get(&0), Some(&Bson::Int32(1)));
+        assert_eq!(result.inserted_ids.get(&2), Some(&Bson::Int32(3)));
+        assert_eq!(result.inserted_ids.get(&4), Some(&Bson::Int32(5)));
+        assert_eq!(result.write_errors.len(), 2);
+        assert!(matches!(result.write_errors.get(&1), Some(Error::DuplicateKey(_))));
+        assert!(matches!(result.write_errors.get(&3), Some(Error::DuplicateKey(_))));
+
+        // the failed documents left neither data nor index entries behind
+        assert_eq!(col.count_documents().unwrap(), 3);
+        assert!(col.find_one(doc! { "_id": 2 }).unwrap().is_none());
+        let found = col.find_one(doc! { "email": "b@polodb.org" }).unwrap().unwrap();
+        assert_eq!(found.get_i32("_id").unwrap(), 3);
+    });
+}
+
+#[test]
+fn test_insert_with_object_id_config() {
+    let mut config = ConfigBuilder::new();
+    config
+        .set_object_id_machine_id(0xabcdef)
+        .set_object_id_process_id(42)
+        .set_object_id_counter_mode(ObjectIdCounterMode::Monotonic);
+    let db = prepare_db_with_config("test-insert-object-id-config", config.take()).unwrap();
+    let col = db.collection::<Document>("test");
+
+    let result = col.insert_many((0..100).map(|i| doc! { "value": i })).unwrap();
+    let ids = (0..100)
+        .map(|i| result.inserted_ids[&i].as_object_id().unwrap())
+        .collect::<Vec<_>>();
+    for id in &ids {
+        assert_eq!(id.machine_id(), 0xabcdef);
+        assert_eq!(id.process_id(), 42);
+    }
+    assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(db.new_object_id() > ids[99]);
+}
+
+#[test]
+fn test_bulk_load() {
+    vec![
+        prepare_db("test-bulk-load").unwrap(),
+    ].iter().for_each(|db| {
+        let col = db.collection::<Document>("users");
+        col.create_index(IndexModel {
+            keys: doc! { "email": 1 },
+            options: Some(IndexOptions::builder().unique(true).build()),
+        }).unwrap();
+        col.create_index(IndexModel {
+            keys: doc! { "group": 1 },
+            options: None,
+        }).unwrap();
+        col.insert_one(doc! { "_id": -1, "email": "first@polodb.org", "group": 0 }).unwrap();
+
+        let docs = (0..1000).rev().map(|i| doc! {
+            "_id": i,
+            "email": format!("user{}@polodb.org", i),
+            "group": i % 10,
+        });
+        let result = col.bulk_load(docs).unwrap();
+        assert_eq!(result.inserted_ids.len(), 1000);
+        assert_eq!(col.count_documents().unwrap(), 1001);
+
+        let found = col.find_one(doc! { "email": "user42@polodb.org" }).unwrap().unwrap();
+        assert_eq!(found.get_i32("_id").unwrap(), 42);
+        let group = col.find(doc! { "group": 0 }).run().unwrap()
+            .collect::<Result<Vec<Document>>>().unwrap();
+        assert_eq!(group.len(), 101);
+
+        // the rebuilt unique index holds the loaded documents
+        let err = col.insert_one(doc! { "email": "user7@polodb.org" }).unwrap_err();
+        assert!(matches!(err, Error::DuplicateKey(_)));
+
+        // a duplicate key fails the whole load
+        let err = col.bulk_load(vec![
+            doc! { "_id": 2000, "email": "a@polodb.org" },
+            doc! { "_id": 2001, "email": "first@polodb.org" },
+        ]).unwrap_err();
+        assert!(matches!(err, Error::DuplicateKey(_)));
+        assert_eq!(col.count_documents().unwrap(), 1001);
+        assert!(col.find_one(doc! { "_id": 2000 }).unwrap().is_none());
+    });
+}
@@ -0,0 +1,137 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Bson, Document};
+use polodb_core::{BsonStrictness, CollectionT, ConfigBuilder, DocumentLimit, Error};
+use polodb_core::options::{CreateCollectionOptions, ModifyCollectionOptions};
+
+mod common;
+
+use common::{prepare_db, prepare_db_with_config};
+
+fn nested(depth: usize) -> Document {
+    let mut doc = doc! { "leaf": 1 };
+    for _ in 1..depth {
+        doc = doc! { "child": doc };
+    }
+    doc
+}
+
+fn assert_limit(err: Error, limit: DocumentLimit, max: u64) {
+    match err {
+        Error::DocumentLimitExceeded(err) => {
+            assert_eq!(err.limit, limit);
+            assert_eq!(err.max, max);
+            assert!(err.actual > max);
+        }
+        err => panic!("unexpected error: {:?}", err),
+    }
+}
+
+#[test]
+fn test_database_limits() {
+    let mut config = ConfigBuilder::new();
+    config.set_max_document_size(64).set_max_document_depth(3);
+    let db = prepare_db_with_config("test-limits-database", config.take()).unwrap();
+    let col = db.collection::<Document>("test");
+
+    col.insert_one(doc! { "_id": 0, "a": { "b": [1, 2] } }).unwrap();
+
+    let err = col.insert_one(doc! { "_id": 1, "a": { "b": [{ "c": 1 }] } }).unwrap_err();
+    assert_limit(err, DocumentLimit::Depth, 3);
+
+    let err = col.insert_one(doc! { "_id": 2, "text": "x".repeat(64) }).unwrap_err();
+    assert_limit(err, DocumentLimit::Size, 64);
+
+    let err = col.update_one(doc! { "_id": 0 }, doc! {
+        "$set": { "text": "x".repeat(64) },
+    }).unwrap_err();
+    assert_limit(err, DocumentLimit::Size, 64);
+
+    assert_eq!(col.count_documents().unwrap(), 1);
+    let doc = col.find_one(doc! { "_id": 0 }).unwrap().unwrap();
+    assert!(doc.get("text").is_none());
+}
+
+#[test]
+fn test_collection_limits() {
+    let mut config = ConfigBuilder::new();
+    config.set_max_document_depth(2);
+    let db = prepare_db_with_config("test-limits-collection", config.take()).unwrap();
+    db.create_collection_with_options("deep", CreateCollectionOptions::builder()
+        .max_document_depth(8)
+        .build()
+    ).unwrap();
+
+    db.collection::<Document>("deep").insert_one(nested(8)).unwrap();
+    let err = db.collection::<Document>("deep").insert_one(nested(9)).unwrap_err();
+    assert_limit(err, DocumentLimit::Depth, 8);
+
+    let err = db.collection::<Document>("other").insert_one(nested(3)).unwrap_err();
+    assert_limit(err, DocumentLimit::Depth, 2);
+
+    // 0 falls back to the limit of the database
+    db.modify_collection("deep", ModifyCollectionOptions::builder()
+        .max_document_depth(0)
+        .build()
+    ).unwrap();
+    let err = db.collection::<Document>("deep").insert_one(nested(3)).unwrap_err();
+    assert_limit(err, DocumentLimit::Depth, 2);
+}
+
+#[test]
+fn test_no_limits_by_default() {
+    let db = prepare_db("test-limits-default").unwrap();
+    let col = db.collection::<Document>("test");
+    col.insert_one(nested(64)).unwrap();
+    col.insert_one(doc! { "text": "x".repeat(1 << 20) }).unwrap();
+    assert_eq!(col.count_documents().unwrap(), 2);
+}
+
+#[test]
+fn test_bson_strictness() {
+    let mut config = ConfigBuilder::new();
+    config.set_bson_strictness(BsonStrictness::Reject);
+    let db = prepare_db_with_config("test-bson-strictness-reject", config.take()).unwrap();
+    let col = db.collection::<Document>("test");
+
+    col.insert_one(doc! { "_id": 0, "values": [1.5, 2.5] }).unwrap();
+    let err = col.insert_one(doc! { "_id": 1, "values": [1.5, f64::NAN] }).unwrap_err();
+    match err {
+        Error::InvalidBson(err) => {
+            assert_eq!(err.ns, "test");
+            assert_eq!(err.path, "values.1");
+        }
+        err => panic!("unexpected error: {:?}", err),
+    }
+    let err = col.update_one(doc! { "_id": 0 }, doc! {
+        "$set": { "meta.kind": Bson::Undefined },
+    }).unwrap_err();
+    assert!(matches!(err, Error::InvalidBson(err) if err.path == "meta.kind"));
+    assert_eq!(col.count_documents().unwrap(), 1);
+
+    let mut config = ConfigBuilder::new();
+    config.set_bson_strictness(BsonStrictness::Sanitize);
+    let db = prepare_db_with_config("test-bson-strictness-sanitize", config.take()).unwrap();
+    let col = db.collection::<Document>("test");
+
+    col.insert_one(doc! { "_id": 0, "tag": Bson::Symbol("red".into()), "score": f64::NAN }).unwrap();
+    col.update_one(doc! { "_id": 0 }, doc! { "$set": { "old": Bson::Undefined } }).unwrap();
+    assert_eq!(col.find_one(doc! { "_id": 0 }).unwrap().unwrap(), doc! {
+        "_id": 0,
+        "tag": "red",
+        "score": Bson::Null,
+        "old": Bson::Null,
+    });
+}
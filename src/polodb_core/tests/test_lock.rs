@@ -0,0 +1,85 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use polodb_core::Database;
+
+mod common;
+
+use common::prepare_db;
+
+#[test]
+fn test_advisory_lock() {
+    let db = prepare_db("test-advisory-lock").unwrap();
+    let first = db.lock("report-job").owner("first");
+    let second = db.lock("report-job").owner("second");
+
+    assert!(first.holder().unwrap().is_none());
+    assert!(first.try_acquire(Duration::from_secs(60)).unwrap());
+    assert!(!second.try_acquire(Duration::from_secs(60)).unwrap());
+    assert!(!second.release().unwrap());
+    let holder = second.holder().unwrap().unwrap();
+    assert_eq!(holder.owner, "first");
+
+    // acquired again by the owner, the lock is extended
+    assert!(first.try_acquire(Duration::from_secs(120)).unwrap());
+    let extended = first.holder().unwrap().unwrap();
+    assert_eq!(extended.acquired_at, holder.acquired_at);
+    assert!(extended.expires_at > holder.expires_at);
+
+    // the other locks are independent
+    assert!(db.lock("cleanup-job").try_acquire(Duration::from_secs(60)).unwrap());
+
+    assert!(first.release().unwrap());
+    assert!(second.try_acquire(Duration::from_secs(60)).unwrap());
+}
+
+#[test]
+fn test_advisory_lock_expiry() {
+    let db_path = polodb_core::test_utils::mk_db_path("test-advisory-lock-expiry");
+    {
+        let db = Database::open_path(&db_path).unwrap();
+        assert!(db.lock("job").owner("crashed").try_acquire(Duration::from_millis(50)).unwrap());
+    }
+
+    // persisted, then taken over once expired
+    let db = Database::open_path(&db_path).unwrap();
+    let lock = db.lock("job").owner("next");
+    assert!(!lock.try_acquire(Duration::from_secs(60)).unwrap());
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(lock.holder().unwrap().is_none());
+    assert!(lock.try_acquire(Duration::from_secs(60)).unwrap());
+    assert_eq!(lock.holder().unwrap().unwrap().owner, "next");
+}
+
+#[test]
+fn test_advisory_lock_concurrent() {
+    let db = prepare_db("test-advisory-lock-concurrent").unwrap();
+    let acquired = Arc::new(AtomicUsize::new(0));
+    let threads = (0..8).map(|_| {
+        let db = db.clone();
+        let acquired = acquired.clone();
+        std::thread::spawn(move || {
+            if db.lock("singleton").try_acquire(Duration::from_secs(60)).unwrap() {
+                acquired.fetch_add(1, Ordering::SeqCst);
+            }
+        })
+    }).collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(acquired.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,97 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Database, Error, IndexModel};
+
+mod common;
+
+use common::prepare_db;
+
+#[test]
+fn test_logical_databases_are_separated() {
+    let db = prepare_db("test-logical-databases-are-separated").unwrap();
+    db.collection::<Document>("settings").insert_one(doc! { "theme": "dark" }).unwrap();
+    let tenant_a = db.database("tenant_a");
+    let tenant_b = db.database("tenant_b");
+    tenant_a.collection::<Document>("orders").insert_many(vec![
+        doc! { "_id": 1, "total": 10 },
+        doc! { "_id": 2, "total": 20 },
+    ]).unwrap();
+    tenant_a.create_collection("customers").unwrap();
+    tenant_b.collection::<Document>("orders").insert_one(doc! { "_id": 1, "total": 99 }).unwrap();
+
+    // the same names, different collections
+    let a_orders = tenant_a.collection::<Document>("orders");
+    assert_eq!(a_orders.count_documents().unwrap(), 2);
+    assert_eq!(tenant_b.collection::<Document>("orders").find_one(doc! { "_id": 1 }).unwrap().unwrap().get_i32("total").unwrap(), 99);
+    assert_eq!(db.collection::<Document>("orders").count_documents().unwrap(), 0);
+
+    assert_eq!(tenant_a.list_collection_names().unwrap(), vec!["customers".to_string(), "orders".to_string()]);
+    assert_eq!(tenant_b.list_collection_names().unwrap(), vec!["orders".to_string()]);
+    assert_eq!(db.list_collection_names().unwrap(), vec!["settings".to_string()]);
+    assert_eq!(db.list_database_names().unwrap(), vec!["tenant_a".to_string(), "tenant_b".to_string()]);
+    let listed = tenant_a.list_collections().unwrap();
+    assert_eq!(listed.iter().map(|info| info.get_str("name").unwrap()).collect::<Vec<_>>(), vec!["customers", "orders"]);
+    assert_eq!(db.list_collections().unwrap().len(), 1);
+
+    // a transaction covers the logical databases by the qualified names
+    let txn = db.start_transaction().unwrap();
+    txn.collection::<Document>("tenant_a.orders").delete_one(doc! { "_id": 1 }).unwrap();
+    txn.collection::<Document>("tenant_b.orders").insert_one(doc! { "_id": 2, "total": 1 }).unwrap();
+    txn.commit().unwrap();
+    assert_eq!(a_orders.count_documents().unwrap(), 1);
+    assert_eq!(tenant_b.collection::<Document>("orders").count_documents().unwrap(), 2);
+
+    tenant_a.rename_collection("customers", "clients").unwrap();
+    assert_eq!(tenant_a.list_collection_names().unwrap(), vec!["clients".to_string(), "orders".to_string()]);
+
+    for name in ["a.b.c", ".orders", "tenant_a."] {
+        let err = db.collection::<Document>(name).insert_one(doc! {}).unwrap_err();
+        assert!(matches!(err, Error::IllegalCollectionName(_)), "{}", name);
+    }
+}
+
+#[test]
+fn test_logical_database_stats_and_drop() {
+    let db_path = polodb_core::test_utils::mk_db_path("test-logical-database-stats-and-drop");
+    {
+        let db = Database::open_path(&db_path).unwrap();
+        let tenant = db.database("tenant_a");
+        let orders = tenant.collection::<Document>("orders");
+        orders.insert_many((0..10).map(|i| doc! { "_id": i, "customer": format!("c{}", i % 3) })).unwrap();
+        orders.create_index(IndexModel {
+            keys: doc! { "customer": 1 },
+            options: None,
+        }).unwrap();
+        tenant.collection::<Document>("customers").insert_one(doc! { "name": "c0" }).unwrap();
+        db.database("tenant_b").collection::<Document>("orders").insert_one(doc! { "_id": 1 }).unwrap();
+    }
+
+    let db = Database::open_path(&db_path).unwrap();
+    let tenant = db.database("tenant_a");
+    let stats = tenant.stats().unwrap();
+    assert_eq!(stats.get_str("db").unwrap(), "tenant_a");
+    assert_eq!(stats.get_i64("collections").unwrap(), 2);
+    assert_eq!(stats.get_i64("objects").unwrap(), 11);
+    assert_eq!(stats.get_i64("indexes").unwrap(), 1);
+    assert!(stats.get_i64("dataSize").unwrap() > 0);
+    assert!(stats.get_i64("indexSize").unwrap() > 0);
+
+    tenant.drop().unwrap();
+    assert!(tenant.list_collection_names().unwrap().is_empty());
+    assert_eq!(tenant.stats().unwrap().get_i64("objects").unwrap(), 0);
+    assert_eq!(db.list_database_names().unwrap(), vec!["tenant_b".to_string()]);
+    assert_eq!(db.database("tenant_b").collection::<Document>("orders").count_documents().unwrap(), 1);
+}
@@ -0,0 +1,119 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Database, Error, Result};
+
+mod common;
+
+use common::prepare_db;
+
+fn all(db: &Database, name: &str) -> Vec<Document> {
+    db.collection::<Document>(name)
+        .find(doc! {})
+        .sort(doc! { "_id": 1 })
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap()
+}
+
+fn count(db: &Database, name: &str) -> i64 {
+    let docs = all(db, name);
+    assert_eq!(docs.len(), 1);
+    docs[0].get_i64("count").unwrap()
+}
+
+#[test]
+fn test_incremental_materialized_view() {
+    let db = prepare_db("test-incremental-materialized-view").unwrap();
+    let orders = db.collection::<Document>("orders");
+    orders.insert_many(vec![
+        doc! { "_id": 1, "status": "paid", "price": 10, "discount": -2, "card": "4242" },
+        doc! { "_id": 2, "status": "pending", "price": 3, "discount": 0, "card": "1111" },
+    ]).unwrap();
+
+    db.create_materialized_view("paid_orders", "orders", vec![
+        doc! { "$match": { "status": "paid" } },
+        doc! { "$addFields": { "rebate": { "$abs": "$discount" } } },
+        doc! { "$unset": "card" },
+    ]).unwrap();
+    assert_eq!(all(&db, "paid_orders"), vec![
+        doc! { "_id": 1, "status": "paid", "price": 10, "discount": -2, "rebate": 2 },
+    ]);
+
+    orders.insert_one(doc! { "_id": 3, "status": "paid", "price": 4, "discount": -1, "card": "0000" }).unwrap();
+    orders.update_one(doc! { "_id": 2 }, doc! { "$set": { "status": "paid" } }).unwrap();
+    orders.update_one(doc! { "_id": 1 }, doc! { "$set": { "discount": -3 } }).unwrap();
+    let rebates = all(&db, "paid_orders").iter()
+        .map(|doc| (doc.get_i32("_id").unwrap(), doc.get_i32("rebate").unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(rebates, vec![(1, 3), (2, 0), (3, 1)]);
+
+    orders.update_many(doc! { "price": { "$lt": 5 } }, doc! { "$set": { "status": "refunded" } }).unwrap();
+    orders.delete_one(doc! { "_id": 1 }).unwrap();
+    assert!(all(&db, "paid_orders").is_empty());
+
+    // the view is maintained by the committed transactions only
+    orders.insert_one(doc! { "_id": 4, "status": "paid", "price": 1, "discount": 0 }).unwrap();
+    let txn = db.start_transaction().unwrap();
+    txn.collection::<Document>("orders").delete_many(doc! {}).unwrap();
+    txn.rollback().unwrap();
+    assert_eq!(all(&db, "paid_orders").len(), 1);
+    orders.delete_many(doc! {}).unwrap();
+    assert!(all(&db, "paid_orders").is_empty());
+}
+
+#[test]
+fn test_grouped_materialized_view() {
+    let db_path = polodb_core::test_utils::mk_db_path("test-grouped-materialized-view");
+    {
+        let db = Database::open_path(&db_path).unwrap();
+        db.collection::<Document>("sales").insert_many(vec![
+            doc! { "region": "north", "amount": 10 },
+            doc! { "region": "south", "amount": 5 },
+            doc! { "region": "north", "amount": 7 },
+        ]).unwrap();
+        db.create_materialized_view("north_sales", "sales", vec![
+            doc! { "$match": { "region": "north" } },
+            doc! { "$count": "count" },
+        ]).unwrap();
+        assert_eq!(count(&db, "north_sales"), 2);
+        let err = db.create_materialized_view("north_sales", "sales", vec![]).unwrap_err();
+        assert!(matches!(err, Error::CollectionAlreadyExits(_)));
+    }
+
+    // the views are maintained after the database is opened again
+    let db = Database::open_path(&db_path).unwrap();
+    let sales = db.collection::<Document>("sales");
+    sales.insert_one(doc! { "region": "north", "amount": 1 }).unwrap();
+    assert_eq!(count(&db, "north_sales"), 3);
+    sales.delete_one(doc! { "amount": 10 }).unwrap();
+    assert_eq!(count(&db, "north_sales"), 2);
+
+    // through the renames of the source and of the view
+    db.rename_collection("sales", "sales_2024").unwrap();
+    db.rename_collection("north_sales", "north").unwrap();
+    db.collection::<Document>("sales_2024").insert_one(doc! { "region": "north", "amount": 2 }).unwrap();
+    assert_eq!(count(&db, "north"), 3);
+
+    db.collection::<Document>("north").insert_one(doc! { "count": 100 }).unwrap();
+    db.refresh_materialized_view("north").unwrap();
+    assert_eq!(all(&db, "north").len(), 1);
+    assert!(matches!(db.refresh_materialized_view("sales_2024").unwrap_err(), Error::NotMaterializedView(_)));
+
+    db.collection::<Document>("north").drop().unwrap();
+    db.collection::<Document>("sales_2024").insert_one(doc! { "region": "north", "amount": 1 }).unwrap();
+    assert!(!db.list_collection_names().unwrap().contains(&"north".to_string()));
+}
@@ -0,0 +1,55 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, ConfigBuilder};
+
+mod common;
+
+use common::prepare_db_with_config;
+
+#[test]
+fn test_operation_metrics() {
+    let mut config = ConfigBuilder::new();
+    config.set_enable_statistics(true);
+    let db = prepare_db_with_config("test-operation-metrics", config.take()).unwrap();
+    db.metrics().enable();
+
+    let col = db.collection::<Document>("items");
+    col.insert_many((0..100).map(|i| doc! { "i": i })).unwrap();
+    col.insert_one(doc! { "i": 100 }).unwrap();
+
+    let found = col.find(doc! { "i": { "$gte": 90 } }).run().unwrap().count();
+    assert_eq!(found, 11);
+    col.update_many(doc! { "i": 1 }, doc! { "$set": { "one": true } }).unwrap();
+    col.delete_one(doc! { "i": 2 }).unwrap();
+
+    let metrics = db.metrics();
+    assert_eq!(metrics.operation_count("insert"), 2);
+    assert_eq!(metrics.operation_count("find"), 1);
+    assert_eq!(metrics.operation_count("update"), 1);
+    assert_eq!(metrics.operation_count("delete"), 1);
+    assert!(metrics.docs_scanned() >= 101);
+    assert_eq!(metrics.docs_returned(), 11);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.operation("insert").unwrap().latency.count, 2);
+    let storage = snapshot.storage.as_ref().unwrap();
+    assert!(storage.wal_bytes_written > 0);
+
+    let text = snapshot.to_prometheus();
+    assert!(text.contains("polodb_operation_duration_seconds_count{op=\"find\"} 1"));
+    assert!(text.contains("polodb_wal_bytes_written_total"));
+}
@@ -0,0 +1,171 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+#![cfg(feature = "derive")]
+
+use polodb_core::{CollectionT, Model, PoloModel};
+use polodb_core::bson::{doc, Bson, Document};
+use polodb_core::query::filter;
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+use common::prepare_db;
+
+#[derive(Debug, Serialize, Deserialize, PoloModel)]
+#[serde(rename_all = "camelCase")]
+#[polo(collection = "users")]
+struct User {
+    #[serde(rename = "_id")]
+    #[polo(id)]
+    id: i64,
+    #[polo(unique)]
+    email: String,
+    #[polo(index)]
+    display_name: String,
+    age: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, PoloModel)]
+#[polo(collection = "devices")]
+struct Device {
+    #[serde(rename = "_id", with = "polodb_core::uuid_as_binary")]
+    #[polo(id)]
+    id: uuid::Uuid,
+    name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PoloModel)]
+struct AuditEntry {
+    message: String,
+}
+
+#[test]
+fn test_model_definition() {
+    assert_eq!(User::COLLECTION_NAME, "users");
+    assert_eq!(User::ID, "_id");
+    assert_eq!(User::EMAIL, "email");
+    assert_eq!(User::DISPLAY_NAME, "displayName");
+
+    let indexes = User::indexes();
+    assert_eq!(indexes.len(), 2);
+    assert_eq!(indexes[0].keys, doc! { "email": 1 });
+    assert_eq!(indexes[0].options.as_ref().unwrap().unique, Some(true));
+    assert_eq!(indexes[1].keys, doc! { "displayName": 1 });
+
+    let user = User {
+        id: 10,
+        email: "a@polodb.org".into(),
+        display_name: "A".into(),
+        age: 20,
+    };
+    assert_eq!(user.id(), Some(Bson::Int64(10)));
+
+    assert_eq!(AuditEntry::COLLECTION_NAME, "audit_entry");
+    assert!(AuditEntry::indexes().is_empty());
+    assert_eq!(AuditEntry { message: "x".into() }.id(), None);
+}
+
+#[test]
+fn test_collection_for() {
+    let db = prepare_db("test-collection-for").unwrap();
+    let users = db.collection_for::<User>().unwrap();
+    assert_eq!(users.name(), "users");
+
+    let mut index_names = users.list_index_names().unwrap();
+    index_names.sort();
+    assert_eq!(index_names, vec!["displayName_1".to_string(), "email_1".to_string()]);
+
+    users.insert_many(vec![
+        User { id: 1, email: "a@polodb.org".into(), display_name: "A".into(), age: 20 },
+        User { id: 2, email: "b@polodb.org".into(), display_name: "B".into(), age: 30 },
+    ]).unwrap();
+
+    let user = users.find_one(doc! { User::DISPLAY_NAME: "B" }).unwrap().unwrap();
+    assert_eq!(user.id, 2);
+
+    let err = users.insert_one(User {
+        id: 3,
+        email: "a@polodb.org".into(),
+        display_name: "C".into(),
+        age: 40,
+    }).unwrap_err();
+    assert!(err.to_string().contains("duplicate key"));
+
+    // indexes already exist
+    let users = db.collection_for::<User>().unwrap();
+    assert_eq!(users.count_documents().unwrap(), 2);
+}
+
+#[test]
+fn test_uuid_id() {
+    let db = prepare_db("test-uuid-id").unwrap();
+    let devices = db.collection_for::<Device>().unwrap();
+
+    let ids = (0..10).map(|_| uuid::Uuid::new_v4()).collect::<Vec<_>>();
+    devices.insert_many(ids.iter().enumerate().map(|(i, id)| Device {
+        id: *id,
+        name: format!("device-{}", i),
+    })).unwrap();
+
+    let device = Device { id: ids[0], name: "device-0".into() };
+    assert_eq!(device.id(), Some(Bson::from(ids[0])));
+
+    let found = devices.find_one(doc! { "_id": ids[3] }).unwrap().unwrap();
+    assert_eq!(found.name, "device-3");
+
+    // ordered by the bytes of the UUIDs
+    let mut sorted = ids.clone();
+    sorted.sort();
+    let all = devices.find(doc! {}).run().unwrap()
+        .map(|device| device.unwrap().id)
+        .collect::<Vec<_>>();
+    assert_eq!(all, sorted);
+
+    let raw = db.collection::<polodb_core::bson::Document>("devices");
+    let doc = raw.find_one(doc! { "name": "device-5" }).unwrap().unwrap();
+    assert!(matches!(doc.get("_id"), Some(Bson::Binary(bin)) if bin.subtype == polodb_core::bson::spec::BinarySubtype::Uuid));
+}
+
+#[derive(Debug, Serialize, Deserialize, PoloModel)]
+#[serde(rename_all = "camelCase")]
+struct Customer {
+    full_name: String,
+    age: i32,
+    tags: Vec<String>,
+    referrer: Option<String>,
+}
+
+#[test]
+fn test_typed_filter() {
+    let db = prepare_db("test-typed-filter").unwrap();
+    let customers = db.collection_for::<Customer>().unwrap();
+    customers.insert_many(vec![
+        Customer { full_name: "Ann".into(), age: 31, tags: vec!["vip".into()], referrer: None },
+        Customer { full_name: "Bob".into(), age: 17, tags: vec![], referrer: Some("Ann".into()) },
+        Customer { full_name: "Carol".into(), age: 45, tags: vec!["vip".into(), "new".into()], referrer: Some("Bob".into()) },
+    ]).unwrap();
+
+    let fields = Customer::fields();
+    assert_eq!(fields.full_name().path(), "fullName");
+
+    let names = |filter: Document| -> Vec<String> {
+        customers.find(filter).run().unwrap().map(|c| c.unwrap().full_name).collect()
+    };
+    assert_eq!(names(filter(fields.age().gt(18).and(fields.tags().size(1)))), vec!["Ann"]);
+    assert_eq!(names(filter(fields.age().lt(18).or(fields.full_name().matches("^C", "")))), vec!["Bob", "Carol"]);
+    assert_eq!(names(filter(fields.referrer().eq("Bob".to_string()))), vec!["Carol"]);
+    assert_eq!(names(fields.full_name().not_in(["Ann", "Bob"]).into()), vec!["Carol"]);
+}
@@ -0,0 +1,63 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+#![cfg(feature = "parquet")]
+
+use polodb_core::CollectionT;
+use polodb_core::bson::{doc, DateTime, Document};
+
+mod common;
+
+use common::{mk_db_path, prepare_db};
+
+#[test]
+fn test_parquet_export_and_import() {
+    let db = prepare_db("test-parquet-export-and-import").unwrap();
+    let telemetry = db.collection::<Document>("telemetry");
+
+    let docs = (0..1000).map(|i| doc! {
+        "seq": i as i64,
+        "sensor": format!("sensor-{}", i % 7),
+        "reading": i as f64 / 10.0,
+        "at": DateTime::from_millis(1_700_000_000_000 + i as i64),
+        "location": {
+            "zone": i % 4,
+        },
+    });
+    telemetry.insert_many(docs).unwrap();
+
+    let mut parquet_path = mk_db_path("test-parquet-export-and-import");
+    parquet_path.set_extension("parquet");
+    let _ = std::fs::remove_file(&parquet_path);
+
+    let rows = telemetry
+        .to_arrow(doc! { "seq": { "$gte": 500_i64 } })
+        .projection(["seq", "sensor", "reading", "at", "location.zone"])
+        .write_parquet(&parquet_path)
+        .unwrap();
+    assert_eq!(rows, 500);
+
+    let archive = db.collection::<Document>("archive");
+    let result = archive.import_parquet(&parquet_path).unwrap();
+    assert_eq!(result.inserted_ids.len(), 500);
+    assert_eq!(archive.count_documents().unwrap(), 500);
+
+    let item = archive.find_one(doc! { "seq": 510_i64 }).unwrap().unwrap();
+    assert_eq!(item.get_str("sensor").unwrap(), "sensor-6");
+    assert_eq!(item.get_f64("reading").unwrap(), 51.0);
+    assert_eq!(item.get_datetime("at").unwrap().timestamp_millis(), 1_700_000_000_510);
+    assert_eq!(item.get_document("location").unwrap().get_i32("zone").unwrap(), 2);
+    assert!(item.get_object_id("_id").is_ok());
+}
@@ -0,0 +1,123 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, ProfileEntry, DEFAULT_PROFILE_COLLECTION};
+
+mod common;
+
+use common::prepare_db;
+
+#[test]
+fn test_profiler_callback() {
+    let db = prepare_db("test-profiler-callback").unwrap();
+    let col = db.collection::<Document>("items");
+    col.insert_many((0..100).map(|i| doc! { "i": i, "even": i % 2 == 0 })).unwrap();
+
+    let entries = Arc::new(Mutex::new(Vec::<ProfileEntry>::new()));
+    let sink = entries.clone();
+    db.profiler().set_callback(move |entry| sink.lock().unwrap().push(entry.clone()));
+    db.profiler().set_slow_threshold(Duration::ZERO);
+
+    let found = col.find(doc! { "even": true }).run().unwrap().count();
+    assert_eq!(found, 50);
+    col.update_many(doc! { "i": { "$lt": 10 } }, doc! { "$set": { "small": true } }).unwrap();
+
+    db.profiler().disable();
+    col.delete_many(doc! {}).unwrap();
+
+    let entries = entries.lock().unwrap();
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(entries[0].op, "find");
+    assert_eq!(entries[0].collection, "items");
+    assert_eq!(entries[0].filter, Some(doc! { "even": true }));
+    assert_eq!(entries[0].docs_examined, 100);
+    assert_eq!(entries[0].docs_returned, 50);
+    assert!(!entries[0].plan.is_empty());
+
+    assert_eq!(entries[1].op, "update");
+    assert_eq!(entries[1].docs_examined, 100);
+}
+
+#[test]
+fn test_profiler_threshold_and_collection() {
+    let db = prepare_db("test-profiler-collection").unwrap();
+    let col = db.collection::<Document>("items");
+    col.insert_many((0..10).map(|i| doc! { "i": i })).unwrap();
+
+    db.profiler().set_slow_threshold(Duration::from_secs(3600));
+    col.find(doc! {}).run().unwrap().for_each(drop);
+    assert_eq!(db.collection::<Document>(DEFAULT_PROFILE_COLLECTION).count_documents().unwrap(), 0);
+
+    db.profiler().set_slow_threshold(Duration::ZERO);
+    col.find(doc! { "i": 3 }).run().unwrap().for_each(drop);
+
+    let profile = db.collection::<Document>(DEFAULT_PROFILE_COLLECTION);
+    let entries = profile.find(doc! {}).run().unwrap().collect::<polodb_core::Result<Vec<Document>>>().unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].get_str("op").unwrap(), "find");
+    assert_eq!(entries[0].get_str("ns").unwrap(), "items");
+    assert_eq!(entries[0].get_document("filter").unwrap(), &doc! { "i": 3 });
+    assert_eq!(entries[0].get_i64("docsExamined").unwrap(), 10);
+    assert_eq!(entries[0].get_i64("nreturned").unwrap(), 1);
+}
+
+#[test]
+fn test_profiler_comment() {
+    let db = prepare_db("test-profiler-comment").unwrap();
+    let col = db.collection::<Document>("items");
+    col.insert_many((0..10).map(|i| doc! { "_id": i, "i": i })).unwrap();
+
+    let entries = Arc::new(Mutex::new(Vec::<ProfileEntry>::new()));
+    let sink = entries.clone();
+    db.profiler().set_callback(move |entry| sink.lock().unwrap().push(entry.clone()));
+    db.profiler().set_slow_threshold(Duration::ZERO);
+
+    let found = col.find(doc! { "i": { "$lt": 5 }, "$comment": "dashboard" }).run().unwrap().count();
+    assert_eq!(found, 5);
+    let found = col.find(doc! { "_id": 3, "$comment": "by-id" }).run().unwrap().count();
+    assert_eq!(found, 1);
+    let found = col.find(doc! {}).comment("report").limit(2).run().unwrap().count();
+    assert_eq!(found, 2);
+    col.update_many(
+        doc! { "i": { "$gte": 8 }, "$comment": "cleanup" },
+        doc! { "$set": { "old": true } },
+    ).unwrap();
+    db.collection::<Document>("items")
+        .aggregate(vec![doc! { "$match": { "i": 1 } }])
+        .comment(doc! { "feature": "stats" })
+        .run()
+        .unwrap()
+        .for_each(drop);
+    col.find(doc! {}).run().unwrap().for_each(drop);
+
+    db.profiler().disable();
+
+    let entries = entries.lock().unwrap();
+    let comments = entries.iter().map(|entry| entry.comment.clone()).collect::<Vec<_>>();
+    assert_eq!(comments, vec![
+        Some("dashboard".into()),
+        Some("by-id".into()),
+        Some("report".into()),
+        Some("cleanup".into()),
+        Some(doc! { "feature": "stats" }.into()),
+        None,
+    ]);
+    assert_eq!(entries[0].to_document().get_str("comment").unwrap(), "dashboard");
+    assert!(entries[5].to_document().get("comment").is_none());
+}
@@ -0,0 +1,123 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, ConfigBuilder, Result};
+
+mod common;
+
+use common::prepare_db_with_config;
+
+fn find_codes(db: &polodb_core::Database, filter: Document) -> Vec<String> {
+    db.collection::<Document>("countries")
+        .find(filter)
+        .sort(doc! { "code": 1 })
+        .run()
+        .unwrap()
+        .map(|doc| doc.map(|doc| doc.get_str("code").unwrap().to_string()))
+        .collect::<Result<Vec<String>>>()
+        .unwrap()
+}
+
+#[test]
+fn test_query_cache() {
+    let mut config = ConfigBuilder::new();
+    config.set_query_cache_size(64 * 1024);
+    let db = prepare_db_with_config("test-query-cache", config.take()).unwrap();
+    let countries = db.collection::<Document>("countries");
+    countries.insert_many(vec![
+        doc! { "_id": 1, "code": "FR", "region": "europe" },
+        doc! { "_id": 2, "code": "DE", "region": "europe" },
+        doc! { "_id": 3, "code": "JP", "region": "asia" },
+    ]).unwrap();
+
+    let metrics = db.metrics();
+    metrics.enable();
+
+    assert_eq!(find_codes(&db, doc! { "region": "europe" }), vec!["DE", "FR"]);
+    assert_eq!(metrics.query_cache_misses(), 1);
+    assert_eq!(find_codes(&db, doc! { "region": "europe" }), vec!["DE", "FR"]);
+    assert_eq!(metrics.query_cache_hits(), 1);
+
+    // another filter or other options are another query
+    assert_eq!(find_codes(&db, doc! { "region": "asia" }), vec!["JP"]);
+    let first = countries.find(doc! { "region": "europe" }).sort(doc! { "code": 1 }).limit(1).run().unwrap()
+        .collect::<Result<Vec<Document>>>().unwrap();
+    assert_eq!(first.len(), 1);
+    assert_eq!(metrics.query_cache_misses(), 3);
+
+    // a write to another collection keeps the results
+    db.collection::<Document>("orders").insert_one(doc! { "country": "FR" }).unwrap();
+    assert_eq!(find_codes(&db, doc! { "region": "europe" }), vec!["DE", "FR"]);
+    assert_eq!(metrics.query_cache_hits(), 2);
+
+    // a write to the collection removes them
+    countries.insert_one(doc! { "_id": 4, "code": "IT", "region": "europe" }).unwrap();
+    assert_eq!(find_codes(&db, doc! { "region": "europe" }), vec!["DE", "FR", "IT"]);
+    assert_eq!(metrics.query_cache_hits(), 2);
+    assert_eq!(find_codes(&db, doc! { "region": "europe" }), vec!["DE", "FR", "IT"]);
+    assert_eq!(metrics.query_cache_hits(), 3);
+
+    // a transaction reads its own writes, the cache is invalidated by its commit
+    let txn = db.start_transaction().unwrap();
+    txn.collection::<Document>("countries").delete_one(doc! { "code": "FR" }).unwrap();
+    let in_txn = txn.collection::<Document>("countries").find(doc! { "region": "europe" }).run().unwrap()
+        .collect::<Result<Vec<Document>>>().unwrap();
+    assert_eq!(in_txn.len(), 2);
+    assert_eq!(find_codes(&db, doc! { "region": "europe" }), vec!["DE", "FR", "IT"]);
+    txn.commit().unwrap();
+    assert_eq!(find_codes(&db, doc! { "region": "europe" }), vec!["DE", "IT"]);
+
+    // a result given partially is not cached
+    let misses = metrics.query_cache_misses();
+    let mut cursor = countries.find(doc! {}).run().unwrap();
+    assert!(cursor.next().is_some());
+    drop(cursor);
+    assert_eq!(countries.find(doc! {}).run().unwrap().count(), 3);
+    assert_eq!(metrics.query_cache_misses(), misses + 2);
+
+    // the finds with a filter_fn are not cached
+    let found = countries.find(doc! {}).filter_fn(|doc| doc.get_str("code").unwrap() == "JP").run().unwrap().count();
+    assert_eq!(found, 1);
+    assert_eq!(metrics.query_cache_misses(), misses + 2);
+}
+
+#[test]
+fn test_query_cache_ttl() {
+    let mut config = ConfigBuilder::new();
+    config.set_query_cache_size(64 * 1024);
+    config.set_query_cache_ttl(Some(Duration::from_millis(50)));
+    let db = prepare_db_with_config("test-query-cache-ttl", config.take()).unwrap();
+    db.collection::<Document>("countries").insert_one(doc! { "_id": 1, "code": "FR", "region": "europe" }).unwrap();
+    db.create_view("european_countries", "countries", vec![doc! { "$match": { "region": "europe" } }]).unwrap();
+
+    let metrics = db.metrics();
+    metrics.enable();
+
+    assert_eq!(find_codes(&db, doc! {}), vec!["FR"]);
+    assert_eq!(find_codes(&db, doc! {}), vec!["FR"]);
+    assert_eq!(metrics.query_cache_hits(), 1);
+    std::thread::sleep(Duration::from_millis(80));
+    assert_eq!(find_codes(&db, doc! {}), vec!["FR"]);
+    assert_eq!(metrics.query_cache_hits(), 1);
+
+    // the documents of a view change with its source, they are not cached
+    let view = db.collection::<Document>("european_countries");
+    assert_eq!(view.find(doc! {}).run().unwrap().count(), 1);
+    assert_eq!(view.find(doc! {}).run().unwrap().count(), 1);
+    assert_eq!(metrics.query_cache_hits(), 1);
+    db.collection::<Document>("countries").insert_one(doc! { "_id": 2, "code": "DE", "region": "europe" }).unwrap();
+    assert_eq!(view.find(doc! {}).run().unwrap().count(), 2);
+}
@@ -0,0 +1,67 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, ConfigBuilder, IndexModel};
+
+mod common;
+
+use common::prepare_db_with_config;
+
+#[test]
+fn test_record_cache() {
+    let mut config = ConfigBuilder::new();
+    config.set_record_cache_size(64 * 1024);
+    let db = prepare_db_with_config("test-record-cache", config.take()).unwrap();
+    let items = db.collection::<Document>("items");
+    items.create_index(IndexModel {
+        keys: doc! { "sku": 1 },
+        options: None,
+    }).unwrap();
+    items.insert_many((0..100).map(|i| doc! {
+        "_id": i,
+        "sku": format!("sku-{}", i),
+        "description": "a description repeated in every document",
+    })).unwrap();
+
+    let metrics = db.metrics();
+    metrics.enable();
+
+    assert_eq!(items.find_by_id(42).unwrap().unwrap().get_str("sku").unwrap(), "sku-42");
+    assert_eq!(metrics.record_cache_misses(), 1);
+    assert_eq!(items.find_by_id(42).unwrap().unwrap().get_str("sku").unwrap(), "sku-42");
+    assert_eq!(metrics.record_cache_hits(), 1);
+
+    // the documents found by an index are cached by their key too
+    let found = items.find_one(doc! { "sku": "sku-42" }).unwrap().unwrap();
+    assert_eq!(found.get_i32("_id").unwrap(), 42);
+    assert_eq!(metrics.record_cache_hits(), 2);
+
+    // the updated document is read again from the storage
+    items.update_one(doc! { "_id": 42 }, doc! { "$set": { "sku": "sku-42b" } }).unwrap();
+    assert_eq!(items.find_by_id(42).unwrap().unwrap().get_str("sku").unwrap(), "sku-42b");
+    assert_eq!(items.find_by_id(42).unwrap().unwrap().get_str("sku").unwrap(), "sku-42b");
+
+    // a transaction reads its own writes, not the cached documents
+    let txn = db.start_transaction().unwrap();
+    let txn_items = txn.collection::<Document>("items");
+    txn_items.update_one(doc! { "_id": 42 }, doc! { "$set": { "sku": "sku-42c" } }).unwrap();
+    assert_eq!(txn_items.find_by_id(42).unwrap().unwrap().get_str("sku").unwrap(), "sku-42c");
+    assert_eq!(items.find_by_id(42).unwrap().unwrap().get_str("sku").unwrap(), "sku-42b");
+    txn.commit().unwrap();
+    assert_eq!(items.find_by_id(42).unwrap().unwrap().get_str("sku").unwrap(), "sku-42c");
+
+    items.delete_one(doc! { "_id": 42 }).unwrap();
+    assert!(items.find_by_id(42).unwrap().is_none());
+}
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use bson::{doc, Document, Regex};
-use polodb_core::CollectionT;
+use polodb_core::{CollectionT, IndexModel};
 
 mod common;
 
@@ -104,3 +104,33 @@ fn test_regex_error() {
         assert!(res.next().unwrap().is_err());
     });
 }
+
+#[test]
+fn test_regex_equality() {
+    let db = prepare_db("test-regex-equality").unwrap();
+    let collection = db.collection::<Document>("articles");
+    collection.create_index(IndexModel {
+        keys: doc! { "author": 1 },
+        options: None,
+    }).unwrap();
+    collection.insert_many(vec![
+        doc! { "_id": "a1", "author": "Alice", "tags": ["rust", "database"] },
+        doc! { "_id": "a2", "author": "alfred", "tags": ["python"] },
+        doc! { "_id": "a3", "author": "Bob", "tags": "Rustacean" },
+        doc! { "_id": "a4", "author": 42, "tags": [] },
+    ]).unwrap();
+
+    let ids = |filter: Document| collection
+        .find(filter)
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_str("_id").unwrap().to_string())
+        .collect::<Vec<String>>();
+
+    let al = Regex { pattern: "^al".into(), options: "i".into() };
+    assert_eq!(ids(doc! { "author": al.clone() }), vec!["a1", "a2"]);
+    assert_eq!(ids(doc! { "tags": Regex { pattern: "^rust".into(), options: "i".into() } }), vec!["a1", "a3"]);
+    assert_eq!(ids(doc! { "tags": Regex { pattern: "^data".into(), options: "".into() } }), vec!["a1"]);
+    assert_eq!(ids(doc! { "_id": Regex { pattern: "[23]$".into(), options: "".into() } }), vec!["a2", "a3"]);
+    assert_eq!(ids(doc! { "_id": "a2", "author": al }), vec!["a2"]);
+}
@@ -0,0 +1,125 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::TcpListener;
+use std::time::Duration;
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, ConfigBuilder, Database, Error, IndexModel, ReplicaState};
+
+mod common;
+
+use common::{prepare_db, prepare_db_with_config};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn prepare_primary(name: &str) -> Database {
+    let mut config = ConfigBuilder::new();
+    config.set_oplog_size(10000);
+    prepare_db_with_config(name, config.take()).unwrap()
+}
+
+/// The token of the last change committed by the primary.
+fn last_token(primary: &Database) -> u64 {
+    let mut stream = primary.watch().resume_after(0).run().unwrap();
+    let mut token = 0;
+    while let Some(event) = stream.try_next() {
+        token = event.unwrap().resume_token.unwrap();
+    }
+    token
+}
+
+#[test]
+fn test_replication() {
+    let primary = prepare_primary("test-replication-primary");
+    let users = primary.collection::<Document>("users");
+    users.create_index(IndexModel {
+        keys: doc! { "email": 1 },
+        options: None,
+    }).unwrap();
+    users.insert_many((0..2500).map(|i| doc! {
+        "_id": i,
+        "email": format!("user{}@example.com", i),
+    })).unwrap();
+
+    let server = primary.serve_replication(TcpListener::bind("127.0.0.1:0").unwrap()).unwrap();
+    let replica_db = prepare_db("test-replication-replica").unwrap();
+    replica_db.collection::<Document>("stale").insert_one(doc! { "_id": 1 }).unwrap();
+    let replica = replica_db.replicate_from(server.local_addr()).unwrap();
+
+    // the initial sync copies the documents and the indexes
+    assert!(replica.wait_for(last_token(&primary), TIMEOUT));
+    assert_eq!(replica_db.list_collection_names().unwrap(), vec!["users".to_string()]);
+    let replica_users = replica_db.collection::<Document>("users");
+    assert_eq!(replica_users.count_documents().unwrap(), 2500);
+    assert_eq!(replica_users.list_index_names().unwrap(), vec!["email_1".to_string()]);
+
+    // then the changes
+    users.update_one(doc! { "_id": 7 }, doc! { "$set": { "email": "seven@example.com" } }).unwrap();
+    users.delete_many(doc! { "_id": { "$gte": 2000 } }).unwrap();
+    primary.collection::<Document>("logs").insert_one(doc! { "_id": 1, "text": "started" }).unwrap();
+    assert!(replica.wait_for(last_token(&primary), TIMEOUT));
+    assert_eq!(replica_users.count_documents().unwrap(), 2000);
+    let user = replica_users.find_one(doc! { "email": "seven@example.com" }).unwrap().unwrap();
+    assert_eq!(user.get_i32("_id").unwrap(), 7);
+    assert!(replica_db.collection::<Document>("logs").find_by_id(1).unwrap().is_some());
+
+    // the replica is read-only
+    let err = replica_users.insert_one(doc! { "_id": -1 }).unwrap_err();
+    assert!(matches!(err, Error::ReadOnlyReplica));
+
+    let status = replica.status();
+    assert_eq!(status.state, ReplicaState::Streaming);
+    assert_eq!(status.lag(), 0);
+    assert!(status.last_contact.is_some());
+
+    replica.stop();
+    replica_users.insert_one(doc! { "_id": -1 }).unwrap();
+    server.stop();
+}
+
+#[test]
+fn test_replication_resume() {
+    let primary = prepare_primary("test-replication-resume-primary");
+    let items = primary.collection::<Document>("items");
+    items.insert_many((0..10).map(|i| doc! { "_id": i })).unwrap();
+    let server = primary.serve_replication(TcpListener::bind("127.0.0.1:0").unwrap()).unwrap();
+
+    let replica_db = prepare_db("test-replication-resume-replica").unwrap();
+    let replica = replica_db.replicate_from(server.local_addr()).unwrap();
+    assert!(replica.wait_for(last_token(&primary), TIMEOUT));
+    replica.stop();
+
+    // written while the replica is stopped
+    items.insert_many((10..20).map(|i| doc! { "_id": i })).unwrap();
+    replica_db.collection::<Document>("local").insert_one(doc! { "_id": 1 }).unwrap();
+
+    // the replica resumes after the last change it applied, without syncing again
+    let replica = replica_db.replicate_from(server.local_addr()).unwrap();
+    assert!(replica.wait_for(last_token(&primary), TIMEOUT));
+    assert_eq!(replica_db.collection::<Document>("items").count_documents().unwrap(), 20);
+    assert_eq!(replica_db.collection::<Document>("local").count_documents().unwrap(), 1);
+}
+
+#[test]
+fn test_replication_without_oplog() {
+    let primary = prepare_db("test-replication-no-oplog-primary").unwrap();
+    let server = primary.serve_replication(TcpListener::bind("127.0.0.1:0").unwrap()).unwrap();
+    let replica_db = prepare_db("test-replication-no-oplog-replica").unwrap();
+    let replica = replica_db.replicate_from(server.local_addr()).unwrap();
+
+    std::thread::sleep(Duration::from_millis(200));
+    let status = replica.status();
+    assert_ne!(status.state, ReplicaState::Streaming);
+    assert!(status.last_error.is_some());
+}
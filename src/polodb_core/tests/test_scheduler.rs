@@ -0,0 +1,102 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use polodb_core::bson::{doc, DateTime, Document};
+use polodb_core::{CollectionT, Error, Job, Result};
+
+mod common;
+
+use common::prepare_db;
+
+#[test]
+fn test_scheduled_jobs_run() {
+    let db = prepare_db("test-scheduled-jobs-run").unwrap();
+    let events = db.collection::<Document>("events");
+    let day = Duration::from_secs(86400);
+    events.insert_many(vec![
+        doc! { "kind": "click", "created_at": DateTime::from_system_time(SystemTime::now() - day * 40) },
+        doc! { "kind": "click", "created_at": DateTime::now() },
+        doc! { "kind": "view", "created_at": DateTime::now() },
+    ]).unwrap();
+
+    let scheduler = db.start_scheduler();
+    let counter = Arc::new(AtomicU32::new(0));
+    let job_counter = counter.clone();
+    scheduler.schedule("tick", Duration::from_millis(20), Job::new(move |_txn| {
+        job_counter.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    })).unwrap();
+    scheduler.schedule("purge", Duration::from_secs(3600), Job::purge_older_than("events", "created_at", day * 30)).unwrap();
+    scheduler.schedule("rollup", Duration::from_secs(3600), Job::aggregate_into(
+        "events",
+        vec![doc! { "$match": { "kind": "click" } }, doc! { "$count": "clicks" }],
+        "event_counts",
+    )).unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while counter.load(Ordering::SeqCst) < 3 && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(counter.load(Ordering::SeqCst) >= 3);
+
+    // the jobs with a long interval are run on demand
+    assert!(scheduler.run_now("purge").unwrap());
+    assert_eq!(events.count_documents().unwrap(), 2);
+    assert!(scheduler.run_now("rollup").unwrap());
+    assert!(scheduler.run_now("rollup").unwrap());
+    let counts = db.collection::<Document>("event_counts")
+        .find(doc! {})
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(counts.len(), 1);
+    assert_eq!(counts[0].get_i64("clicks").unwrap(), 1);
+    assert!(!scheduler.run_now("missing").unwrap());
+
+    let jobs = scheduler.jobs();
+    assert_eq!(jobs.iter().map(|job| job.name.as_str()).collect::<Vec<_>>(), vec!["tick", "purge", "rollup"]);
+    assert_eq!(jobs[2].runs, 2);
+    assert!(jobs[2].last_run.is_some() && jobs[2].last_error.is_none());
+
+    assert!(scheduler.unschedule("tick"));
+    assert!(!scheduler.unschedule("tick"));
+    let runs = counter.load(Ordering::SeqCst);
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(counter.load(Ordering::SeqCst) <= runs + 1);
+    scheduler.stop();
+}
+
+#[test]
+fn test_failed_job_is_rolled_back() {
+    let db = prepare_db("test-failed-job-is-rolled-back").unwrap();
+    let scheduler = db.start_scheduler();
+    scheduler.schedule("broken", Duration::from_secs(3600), Job::new(|txn| {
+        txn.collection::<Document>("logs").insert_one(doc! { "message": "half done" })?;
+        Err(Error::ValidationError("the job failed".into()))
+    })).unwrap();
+
+    assert!(matches!(scheduler.run_now("broken").unwrap_err(), Error::ValidationError(_)));
+    assert_eq!(db.collection::<Document>("logs").count_documents().unwrap(), 0);
+    let status = &scheduler.jobs()[0];
+    assert_eq!((status.runs, status.failures), (1, 1));
+    assert_eq!(status.last_error.as_deref(), Some("validation error: the job failed"));
+    assert!(status.next_run > SystemTime::now() + Duration::from_secs(3000));
+
+    let err = scheduler.schedule("never", Duration::ZERO, Job::new(|_| Ok(()))).unwrap_err();
+    assert!(matches!(err, Error::ValidationError(_)));
+}
@@ -0,0 +1,92 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Database, Error, ErrorCode, IndexModel, IndexOptions};
+
+mod common;
+
+use common::{mk_db_path, prepare_db};
+
+fn export_countries(name: &str) -> std::path::PathBuf {
+    let db = prepare_db(name).unwrap();
+    let countries = db.collection::<Document>("countries");
+    countries.create_index(IndexModel {
+        keys: doc! { "code": 1 },
+        options: Some(IndexOptions {
+            name: Some("code_unique".to_string()),
+            unique: Some(true),
+            ..Default::default()
+        }),
+    }).unwrap();
+    countries.insert_many((0..500).map(|i| doc! {
+        "_id": i,
+        "code": format!("C{:03}", i),
+        "population": i * 1000,
+    })).unwrap();
+    countries.delete_many(doc! { "_id": { "$gte": 400 } }).unwrap();
+
+    let path = mk_db_path(name).with_extension("snapshot");
+    db.export_snapshot(&path).unwrap();
+    // the database stays writable, the snapshot doesn't change with it
+    countries.insert_one(doc! { "_id": 1000, "code": "NEW" }).unwrap();
+    path
+}
+
+#[test]
+fn test_export_and_open_snapshot() {
+    let path = export_countries("test-export-and-open-snapshot");
+    let exported = std::fs::read(&path).unwrap();
+
+    let db = Database::open_snapshot(&path).unwrap();
+    let countries = db.collection::<Document>("countries");
+    assert_eq!(countries.count_documents().unwrap(), 400);
+    assert!(countries.find_one(doc! { "code": "NEW" }).unwrap().is_none());
+    assert_eq!(countries.find_one(doc! { "code": "C042" }).unwrap().unwrap().get_i32("population").unwrap(), 42000);
+    assert!(countries.describe_index("code_unique").unwrap().unwrap().is_unique());
+
+    let err = countries.insert_one(doc! { "code": "DE" }).unwrap_err();
+    assert!(matches!(err, Error::ReadOnlySnapshot));
+    assert_eq!(err.code(), ErrorCode::Unsupported);
+    assert!(countries.delete_many(doc! {}).is_err());
+    assert!(db.create_collection("cities").is_err());
+
+    // opened again, by several handles at once
+    let other = Database::open_snapshot(&path).unwrap();
+    assert_eq!(other.collection::<Document>("countries").count_documents().unwrap(), 400);
+    drop(db);
+    drop(other);
+    assert_eq!(std::fs::read(&path).unwrap(), exported);
+}
+
+#[test]
+fn test_open_damaged_snapshot() {
+    let path = export_countries("test-open-damaged-snapshot");
+    let exported = std::fs::read(&path).unwrap();
+
+    let damaged = path.with_extension("damaged");
+    let mut bytes = exported.clone();
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 0x55;
+    std::fs::write(&damaged, &bytes).unwrap();
+    let err = Database::open_snapshot(&damaged).err().unwrap();
+    assert!(matches!(err, Error::InvalidSnapshot(_)));
+    assert_eq!(err.code(), ErrorCode::Corruption);
+
+    std::fs::write(&damaged, &exported[..exported.len() - 10]).unwrap();
+    assert!(matches!(Database::open_snapshot(&damaged).err().unwrap(), Error::InvalidSnapshot(_)));
+
+    std::fs::write(&damaged, b"not a snapshot").unwrap();
+    assert!(matches!(Database::open_snapshot(&damaged).err().unwrap(), Error::InvalidSnapshot(_)));
+}
@@ -0,0 +1,113 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+
+#![cfg(feature = "sql")]
+
+use polodb_core::CollectionT;
+use polodb_core::bson::{doc, Document};
+use polodb_core::sql::SqlResult;
+
+mod common;
+
+use common::prepare_db;
+
+fn rows(result: SqlResult) -> Vec<Document> {
+    match result {
+        SqlResult::Rows(rows) => rows,
+        other => panic!("expected rows, got: {:?}", other),
+    }
+}
+
+#[test]
+fn test_sql_select() {
+    let db = prepare_db("test-sql-select").unwrap();
+    db.sql("INSERT INTO users (name, age, city) VALUES \
+        ('Alice', 30, 'Paris'), ('Bob', 25, 'Berlin'), ('Carol', 35, 'Paris'), ('Dave', 40, NULL)").unwrap();
+
+    let result = rows(db.sql(
+        "SELECT name, age AS years FROM users WHERE city = 'Paris' AND age >= 30 ORDER BY age DESC"
+    ).unwrap());
+    assert_eq!(result, vec![
+        doc! { "name": "Carol", "years": 35_i64 },
+        doc! { "name": "Alice", "years": 30_i64 },
+    ]);
+
+    let result = rows(db.sql("SELECT name FROM users ORDER BY name LIMIT 2 OFFSET 1").unwrap());
+    assert_eq!(result, vec![doc! { "name": "Bob" }, doc! { "name": "Carol" }]);
+
+    let result = rows(db.sql("SELECT name FROM users WHERE name LIKE 'C%' OR age < 26 ORDER BY name").unwrap());
+    assert_eq!(result, vec![doc! { "name": "Bob" }, doc! { "name": "Carol" }]);
+
+    let result = rows(db.sql("SELECT name FROM users WHERE age NOT IN (25, 30) ORDER BY name").unwrap());
+    assert_eq!(result, vec![doc! { "name": "Carol" }, doc! { "name": "Dave" }]);
+
+    let result = rows(db.sql("SELECT COUNT(*) FROM users WHERE age BETWEEN 26 AND 40").unwrap());
+    assert_eq!(result, vec![doc! { "count": 3_i64 }]);
+
+    assert!(db.sql("SELECT city, COUNT(*) FROM users GROUP BY city").is_err());
+}
+
+#[test]
+fn test_sql_join() {
+    let db = prepare_db("test-sql-join").unwrap();
+    db.collection::<Document>("authors").insert_many(vec![
+        doc! { "_id": 1, "name": "Tolkien" },
+        doc! { "_id": 2, "name": "Austen" },
+    ]).unwrap();
+    db.collection::<Document>("books").insert_many(vec![
+        doc! { "title": "The Hobbit", "author_id": 1 },
+        doc! { "title": "Emma", "author_id": 2 },
+        doc! { "title": "Anonymous", "author_id": 3 },
+    ]).unwrap();
+
+    let result = rows(db.sql(
+        "SELECT b.title, a.name FROM books b JOIN authors a ON b.author_id = a._id ORDER BY b.title"
+    ).unwrap());
+    assert_eq!(result, vec![
+        doc! { "title": "Emma", "a.name": "Austen" },
+        doc! { "title": "The Hobbit", "a.name": "Tolkien" },
+    ]);
+
+    let result = rows(db.sql(
+        "SELECT title FROM books LEFT JOIN authors ON books.author_id = authors._id ORDER BY title"
+    ).unwrap());
+    assert_eq!(result.len(), 3);
+}
+
+#[test]
+fn test_sql_update_and_delete() {
+    let db = prepare_db("test-sql-update-and-delete").unwrap();
+    db.sql("INSERT INTO items (name, qty) VALUES ('a', 1), ('b', 2), ('c', 3)").unwrap();
+
+    let result = db.sql("UPDATE items SET qty = qty + 10, tag = 'big' WHERE qty > 1").unwrap();
+    match result {
+        SqlResult::Update(result) => assert_eq!(result.modified_count, 2),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let result = rows(db.sql("SELECT name, qty, tag FROM items ORDER BY name").unwrap());
+    assert_eq!(result, vec![
+        doc! { "name": "a", "qty": 1_i64 },
+        doc! { "name": "b", "qty": 12_i64, "tag": "big" },
+        doc! { "name": "c", "qty": 13_i64, "tag": "big" },
+    ]);
+
+    let result = db.sql("DELETE FROM items WHERE tag IS NOT NULL").unwrap();
+    match result {
+        SqlResult::Delete(result) => assert_eq!(result.deleted_count, 2),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!(db.collection::<Document>("items").count_documents().unwrap(), 1);
+}
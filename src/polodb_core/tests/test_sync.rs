@@ -0,0 +1,199 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Bson, DateTime, Document};
+use polodb_core::sync::{ConflictPolicy, SyncBatch, SyncChange, SyncRemote, SyncReport};
+use polodb_core::options::CreateCollectionOptions;
+use polodb_core::{CollectionT, ConfigBuilder, Database, Result};
+
+mod common;
+
+use common::prepare_db_with_config;
+
+/// A remote keeping the changes in memory, the checkpoint is the number of changes seen.
+#[derive(Default)]
+struct MemoryRemote {
+    log: Vec<SyncChange>,
+}
+
+impl MemoryRemote {
+
+    fn write(&mut self, collection: &str, id: i32, document: Option<Document>) {
+        self.log.push(SyncChange {
+            collection: collection.to_string(),
+            id: Bson::Int32(id),
+            document,
+            updated_at: DateTime::now(),
+            crdt: None,
+        });
+    }
+
+    fn get(&self, collection: &str, id: i32) -> Option<Document> {
+        self.log.iter()
+            .rev()
+            .find(|change| change.collection == collection && change.id == Bson::Int32(id))
+            .and_then(|change| change.document.clone())
+    }
+
+}
+
+impl SyncRemote for MemoryRemote {
+
+    fn push(&mut self, changes: &[SyncChange]) -> Result<()> {
+        self.log.extend_from_slice(changes);
+        Ok(())
+    }
+
+    fn pull(&mut self, collections: &[String], checkpoint: Option<&Bson>) -> Result<SyncBatch> {
+        let after = checkpoint.and_then(Bson::as_i64).unwrap_or(0) as usize;
+        Ok(SyncBatch {
+            changes: self.log[after..].iter()
+                .filter(|change| collections.contains(&change.collection))
+                .cloned()
+                .collect(),
+            checkpoint: Some(Bson::Int64(self.log.len() as i64)),
+        })
+    }
+
+}
+
+fn prepare(name: &str) -> Database {
+    let mut config = ConfigBuilder::new();
+    config.set_oplog_size(1000);
+    prepare_db_with_config(name, config.take()).unwrap()
+}
+
+#[test]
+fn test_sync() {
+    let db = prepare("test-sync");
+    let notes = db.collection::<Document>("notes");
+    notes.insert_many(vec![
+        doc! { "_id": 1, "text": "one" },
+        doc! { "_id": 2, "text": "two" },
+    ]).unwrap();
+    db.collection::<Document>("local").insert_one(doc! { "_id": 1 }).unwrap();
+
+    let mut remote = MemoryRemote::default();
+    remote.write("notes", 3, Some(doc! { "_id": 3, "text": "three" }));
+
+    let engine = db.sync_engine(["notes"]);
+    let report = engine.sync(&mut remote).unwrap();
+    assert_eq!(report, SyncReport { pushed: 2, pulled: 1, conflicts: 0 });
+    assert_eq!(notes.count_documents().unwrap(), 3);
+    assert_eq!(remote.get("notes", 1).unwrap().get_str("text").unwrap(), "one");
+    assert!(remote.log.iter().all(|change| change.collection == "notes"));
+
+    // nothing changed, the changes pulled and pushed are not sent back
+    let report = engine.sync(&mut remote).unwrap();
+    assert_eq!(report, SyncReport::default());
+
+    notes.update_one(doc! { "_id": 1 }, doc! { "$set": { "text": "uno" } }).unwrap();
+    notes.delete_one(doc! { "_id": 2 }).unwrap();
+    remote.write("notes", 3, None);
+    let report = engine.sync(&mut remote).unwrap();
+    assert_eq!(report, SyncReport { pushed: 2, pulled: 1, conflicts: 0 });
+    assert_eq!(remote.get("notes", 1).unwrap().get_str("text").unwrap(), "uno");
+    assert!(remote.get("notes", 2).is_none());
+    assert!(notes.find_by_id(3).unwrap().is_none());
+    assert_eq!(engine.sync(&mut remote).unwrap(), SyncReport::default());
+}
+
+#[test]
+fn test_sync_conflicts() {
+    let db = prepare("test-sync-conflicts");
+    let notes = db.collection::<Document>("notes");
+    notes.insert_many(vec![
+        doc! { "_id": 1, "text": "one", "tags": ["a"] },
+        doc! { "_id": 2, "text": "two" },
+    ]).unwrap();
+    let mut remote = MemoryRemote::default();
+    db.sync_engine(["notes"]).sync(&mut remote).unwrap();
+
+    // the remote wrote last
+    notes.update_one(doc! { "_id": 2 }, doc! { "$set": { "text": "local" } }).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    remote.write("notes", 2, Some(doc! { "_id": 2, "text": "remote" }));
+    let report = db.sync_engine(["notes"]).sync(&mut remote).unwrap();
+    assert_eq!(report, SyncReport { pushed: 0, pulled: 0, conflicts: 1 });
+    assert_eq!(notes.find_by_id(2).unwrap().unwrap().get_str("text").unwrap(), "remote");
+
+    // merged, the result is pushed
+    notes.update_one(doc! { "_id": 1 }, doc! { "$set": { "tags": ["a", "local"] } }).unwrap();
+    remote.write("notes", 1, Some(doc! { "_id": 1, "text": "one", "tags": ["a", "remote"] }));
+    let engine = db.sync_engine(["notes"]).conflict_policy(ConflictPolicy::Custom(Box::new(|conflict| {
+        assert_eq!(conflict.base.as_ref().unwrap().get_array("tags").unwrap().len(), 1);
+        let mut merged = conflict.remote.document.clone()?;
+        let mut tags = merged.get_array("tags").unwrap().clone();
+        tags.push(Bson::String("local".into()));
+        merged.insert("tags", tags);
+        Some(merged)
+    })));
+    let report = engine.sync(&mut remote).unwrap();
+    assert_eq!(report, SyncReport { pushed: 1, pulled: 0, conflicts: 1 });
+    let expected = doc! { "_id": 1, "text": "one", "tags": ["a", "remote", "local"] };
+    assert_eq!(notes.find_by_id(1).unwrap().unwrap(), expected);
+    assert_eq!(remote.get("notes", 1).unwrap(), expected);
+    assert_eq!(engine.sync(&mut remote).unwrap(), SyncReport::default());
+}
+
+#[test]
+fn test_sync_crdt() {
+    let devices = [prepare("test-sync-crdt-a"), prepare("test-sync-crdt-b")];
+    for db in &devices {
+        db.create_collection_with_options("notes", CreateCollectionOptions::builder().crdt(true).build()).unwrap();
+    }
+    let [a, b] = &devices;
+    let mut remote = MemoryRemote::default();
+    a.collection::<Document>("notes").insert_one(doc! {
+        "_id": 1,
+        "title": "draft",
+        "body": "",
+        "tags": ["todo"],
+    }).unwrap();
+    a.sync_engine(["notes"]).sync(&mut remote).unwrap();
+    b.sync_engine(["notes"]).sync(&mut remote).unwrap();
+
+    // both devices edit the note offline
+    a.collection::<Document>("notes").update_one(doc! { "_id": 1 }, doc! {
+        "$set": { "title": "groceries", "tags": ["todo", "home"] },
+    }).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    b.collection::<Document>("notes").update_one(doc! { "_id": 1 }, doc! {
+        "$set": { "body": "milk", "tags": ["shopping"] },
+    }).unwrap();
+
+    a.sync_engine(["notes"]).sync(&mut remote).unwrap();
+    let report = b.sync_engine(["notes"]).sync(&mut remote).unwrap();
+    assert_eq!(report, SyncReport { pushed: 1, pulled: 0, conflicts: 1 });
+    a.sync_engine(["notes"]).sync(&mut remote).unwrap();
+
+    let expected = doc! {
+        "_id": 1,
+        "title": "groceries",
+        "body": "milk",
+        "tags": ["home", "shopping"],
+    };
+    for db in &devices {
+        assert_eq!(db.collection::<Document>("notes").find_by_id(1).unwrap().unwrap(), expected);
+    }
+    assert_eq!(remote.get("notes", 1).unwrap(), expected);
+    assert!(remote.log.last().unwrap().crdt.is_some());
+}
+
+#[test]
+fn test_sync_without_oplog() {
+    let db = common::prepare_db("test-sync-without-oplog").unwrap();
+    let err = db.sync_engine(["notes"]).sync(&mut MemoryRemote::default()).unwrap_err();
+    assert!(matches!(err, polodb_core::Error::Sync(_)));
+}
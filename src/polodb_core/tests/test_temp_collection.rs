@@ -0,0 +1,80 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Error, IndexModel};
+
+mod common;
+
+use common::prepare_db;
+
+#[test]
+fn test_temp_collection() {
+    let db = prepare_db("test-temp-collection").unwrap();
+    let orders = db.collection::<Document>("orders");
+    orders.insert_many((0..100).map(|i| doc! { "_id": i, "customer": i % 10, "amount": i })).unwrap();
+
+    // the same name as a collection of the database
+    let totals = db.create_temp_collection::<Document>("orders").unwrap();
+    totals.create_index(IndexModel {
+        keys: doc! { "customer": 1 },
+        options: None,
+    }).unwrap();
+    for order in orders.find(doc! {}).run().unwrap() {
+        let order = order.unwrap();
+        let customer = order.get_i32("customer").unwrap();
+        let amount = order.get_i32("amount").unwrap();
+        let updated = totals.update_one(doc! { "customer": customer }, doc! {
+            "$inc": { "total": amount },
+        }).unwrap();
+        if updated.matched_count == 0 {
+            totals.insert_one(doc! { "customer": customer, "total": amount }).unwrap();
+        }
+    }
+    assert_eq!(totals.name(), "orders");
+    assert_eq!(totals.count_documents().unwrap(), 10);
+    let total = totals.find_one(doc! { "customer": 3 }).unwrap().unwrap();
+    assert_eq!(total.get_i32("total").unwrap(), (0..100).filter(|i| i % 10 == 3).sum::<i32>());
+
+    // nothing is written to the database
+    assert_eq!(orders.count_documents().unwrap(), 100);
+    assert_eq!(db.list_collection_names().unwrap(), vec!["orders".to_string()]);
+
+    // a handle doesn't see the documents of another
+    let other = db.create_temp_collection::<Document>("orders").unwrap();
+    assert_eq!(other.count_documents().unwrap(), 0);
+}
+
+#[test]
+fn test_temp_collection_in_transaction() {
+    let db = prepare_db("test-temp-collection-in-transaction").unwrap();
+    let txn = db.start_transaction().unwrap();
+    let temp = txn.create_temp_collection::<Document>("staging").unwrap();
+    temp.insert_many((0..10).map(|i| doc! { "_id": i })).unwrap();
+    for doc in temp.find(doc! { "_id": { "$lt": 5 } }).run().unwrap() {
+        txn.collection::<Document>("items").insert_one(doc.unwrap()).unwrap();
+    }
+    txn.commit().unwrap();
+    assert_eq!(db.collection::<Document>("items").count_documents().unwrap(), 5);
+
+    // removed with the transaction
+    let err = temp.count_documents().unwrap_err();
+    assert!(matches!(err, Error::DbIsClosed));
+
+    let txn = db.start_transaction().unwrap();
+    let temp = txn.create_temp_collection::<Document>("staging").unwrap();
+    temp.insert_one(doc! { "_id": 1 }).unwrap();
+    txn.rollback().unwrap();
+    assert!(matches!(temp.count_documents().unwrap_err(), Error::DbIsClosed));
+}
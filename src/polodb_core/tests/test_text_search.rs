@@ -0,0 +1,102 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Database};
+
+mod common;
+
+use common::prepare_db;
+
+fn prepare_articles(db: &Database) {
+    let articles = db.collection::<Document>("articles");
+    articles.insert_many(vec![
+        doc! { "_id": 1, "title": "Rust embedded database", "body": "A database written in Rust" },
+        doc! { "_id": 2, "title": "Cooking pasta", "body": "Boil the water, add the pasta" },
+        doc! { "_id": 3, "title": "Database", "tags": ["storage", "rust"] },
+        doc! { "_id": 4, "title": "Gardening", "body": "Nothing about databases here" },
+    ]).unwrap();
+}
+
+#[test]
+fn test_text_search() {
+    let db = prepare_db("test-text-search").unwrap();
+    prepare_articles(&db);
+    let articles = db.collection::<Document>("articles");
+
+    let mut ids: Vec<i32> = articles
+        .find(doc! { "$text": { "$search": "RUST" } })
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect();
+    ids.sort();
+    assert_eq!(ids, vec![1, 3]);
+
+    // the score is not returned unless it's asked for
+    let found = articles.find_one(doc! { "$text": { "$search": "pasta" } }).unwrap().unwrap();
+    assert_eq!(found.get_i32("_id").unwrap(), 2);
+    assert!(!found.keys().any(|key| key.starts_with('$')));
+
+    // the other conditions of the filter apply too
+    let found: Vec<Document> = articles
+        .find(doc! { "$text": { "$search": "database" }, "_id": { "$gt": 1 } })
+        .run()
+        .unwrap()
+        .collect::<polodb_core::Result<_>>()
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].get_i32("_id").unwrap(), 3);
+}
+
+#[test]
+fn test_text_search_ranking() {
+    let db = prepare_db("test-text-search-ranking").unwrap();
+    prepare_articles(&db);
+    let articles = db.collection::<Document>("articles");
+
+    let ids: Vec<i32> = articles
+        .find(doc! { "$text": { "$search": "rust database" } })
+        .sort(doc! { "score": { "$meta": "textScore" } })
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect();
+    assert_eq!(ids, vec![1, 3]);
+
+    let ranked: Vec<Document> = articles
+        .aggregate(vec![
+            doc! { "$match": { "$text": { "$search": "rust database" } } },
+            doc! { "$sort": { "score": { "$meta": "textScore" } } },
+            doc! { "$addFields": { "score": { "$meta": "textScore" } } },
+        ])
+        .run()
+        .unwrap()
+        .collect::<polodb_core::Result<_>>()
+        .unwrap();
+    assert_eq!(ranked.len(), 2);
+    let first_score = ranked[0].get_f64("score").unwrap();
+    let second_score = ranked[1].get_f64("score").unwrap();
+    assert!(first_score > second_score);
+    assert!(!ranked[0].keys().any(|key| key.starts_with('$')));
+
+    // only the documents scored at least as the minimum
+    let ids: Vec<i32> = articles
+        .find(doc! { "$text": { "$search": "rust database", "$minScore": (first_score + second_score) / 2.0 } })
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+        .collect();
+    assert_eq!(ids, vec![1]);
+}
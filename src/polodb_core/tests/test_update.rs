@@ -12,8 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use polodb_core::options::UpdateOptions;
-use polodb_core::{CollectionT, Database, Result};
+use polodb_core::options::{Collation, UpdateOptions};
+use polodb_core::{CollectionT, Database, Error, IndexModel, Result};
 use polodb_core::bson::{Document, doc};
 
 mod common;
@@ -300,3 +300,248 @@ fn test_upsert() {
     // assert_eq!(hobbies.len(), 1);
     // assert_eq!(hobbies[0].as_str().unwrap(), "reading");
 }
+
+#[test]
+fn test_update_with_options() {
+    vec![
+        prepare_db("test-update-with-options").unwrap(),
+    ].iter().for_each(|db| {
+        let collection = db.collection::<Document>("test");
+        collection.create_index(IndexModel {
+            keys: doc! {
+                "team": 1,
+            },
+            options: None,
+        }).unwrap();
+
+        collection.insert_many(vec![
+            doc! { "_id": 1, "name": "Alice", "team": "a" },
+            doc! { "_id": 2, "name": "alice", "team": "b" },
+            doc! { "_id": 3, "name": "ALICE", "team": "a" },
+            doc! { "_id": 4, "name": "Bob", "team": "a" },
+        ]).unwrap();
+
+        let result = collection.update_many_with_options(doc! {
+            "name": "alice",
+        }, doc! {
+            "$set": { "found": true },
+        }, UpdateOptions::builder().collation(Collation::CaseInsensitive).build()).unwrap();
+        assert_eq!(result.modified_count, 3);
+
+        let result = collection.update_many_with_options(doc! {
+            "found": true,
+        }, doc! {
+            "$set": { "limited": true },
+        }, UpdateOptions::builder().limit(2).build()).unwrap();
+        assert_eq!(result.matched_count, 2);
+        assert_eq!(collection.find(doc! { "limited": true }).run().unwrap().count(), 2);
+
+        // the documents of the index not matching the rest
+        // of the filter are skipped
+        let result = collection.update_many_with_options(doc! {
+            "team": "a",
+            "name": { "$ne": "Bob" },
+        }, doc! {
+            "$set": { "hinted": true },
+        }, UpdateOptions::builder().hint(doc! { "team": 1 }).build()).unwrap();
+        assert_eq!(result.modified_count, 2);
+        assert!(collection.find_one(doc! { "_id": 4, "hinted": true }).unwrap().is_none());
+
+        let err = collection.update_many_with_options(doc! {
+            "team": "a",
+        }, doc! {
+            "$set": { "hinted": false },
+        }, UpdateOptions::builder().hint("name_1").build()).unwrap_err();
+        assert!(matches!(err, Error::IndexNotFound(_)));
+    });
+}
+
+#[test]
+fn test_update_sorted() {
+    let db = prepare_db("test-update-sorted").unwrap();
+    let jobs = db.collection::<Document>("jobs");
+    jobs.insert_many((0..6).map(|i| doc! {
+        "_id": i,
+        "priority": (i * 5) % 6,
+        "done": false,
+    })).unwrap();
+
+    let result = jobs.update_many_with_options(doc! { "done": false }, doc! {
+        "$set": { "done": true },
+    }, UpdateOptions::builder().sort(doc! { "priority": -1 }).limit(2).build()).unwrap();
+    assert_eq!(result.matched_count, 2);
+    assert_eq!(result.modified_count, 2);
+    let mut done: Vec<i32> = jobs.find(doc! { "done": true })
+        .run()
+        .unwrap()
+        .map(|doc| doc.unwrap().get_i32("priority").unwrap())
+        .collect();
+    done.sort();
+    assert_eq!(done, vec![4, 5]);
+
+    // the first document in the order
+    jobs.update_one_with_options(doc! { "done": false }, doc! {
+        "$set": { "next": true },
+    }, UpdateOptions::builder().sort(doc! { "priority": 1 }).build()).unwrap();
+    let next = jobs.find_one(doc! { "next": true }).unwrap().unwrap();
+    assert_eq!(next.get_i32("priority").unwrap(), 0);
+}
+
+#[test]
+fn test_update_large_document_by_delta() {
+    let db = prepare_db("test-update-by-delta").unwrap();
+    let metrics = db.metrics();
+    metrics.enable();
+
+    let col = db.collection::<Document>("messages");
+    let body = "lorem ipsum ".repeat(200);
+    col.insert_many((0..10).map(|i| doc! {
+        "_id": i,
+        "body": body.clone(),
+        "read": false,
+        "draft": true,
+    })).unwrap();
+
+    col.update_many(doc! {}, doc! {
+        "$set": { "read": true },
+    }).unwrap();
+    col.update_one(doc! { "_id": 3 }, doc! {
+        "$unset": { "draft": "" },
+        "$push": { "readers": "alice" },
+    }).unwrap();
+    col.update_one(doc! { "_id": 3 }, doc! {
+        "$push": { "readers": "bob" },
+    }).unwrap();
+    assert_eq!(metrics.delta_updates(), 12);
+
+    let doc = col.find_one(doc! { "_id": 3 }).unwrap().unwrap();
+    assert_eq!(doc, doc! {
+        "_id": 3,
+        "body": body.clone(),
+        "read": true,
+        "readers": ["alice", "bob"],
+    });
+    assert_eq!(doc.keys().collect::<Vec<_>>(), vec!["_id", "body", "read", "readers"]);
+    assert_eq!(col.count_documents().unwrap(), 10);
+    assert_eq!(col.find(doc! { "read": true }).run().unwrap().count(), 10);
+
+    // the deltas written by a transaction are read by it
+    let txn = db.start_transaction().unwrap();
+    let txn_col = txn.collection::<Document>("messages");
+    txn_col.update_one(doc! { "_id": 5 }, doc! {
+        "$set": { "read": false },
+    }).unwrap();
+    assert!(!txn_col.find_one(doc! { "_id": 5 }).unwrap().unwrap().get_bool("read").unwrap());
+    txn.rollback().unwrap();
+    assert!(col.find_one(doc! { "_id": 5 }).unwrap().unwrap().get_bool("read").unwrap());
+
+    // a small document is written as a whole
+    col.insert_one(doc! { "_id": 100, "read": false }).unwrap();
+    col.update_one(doc! { "_id": 100 }, doc! {
+        "$set": { "read": true },
+    }).unwrap();
+    assert_eq!(metrics.delta_updates(), 13);
+
+    db.compact().unwrap();
+    let doc = col.find_one(doc! { "_id": 3 }).unwrap().unwrap();
+    assert_eq!(doc.get_array("readers").unwrap().len(), 2);
+    assert_eq!(doc.get_str("body").unwrap(), body);
+}
+
+#[test]
+fn test_update_array_operators() {
+    let db = prepare_db("test-update-array-operators").unwrap();
+    let col = db.collection::<Document>("test");
+    col.insert_one(doc! { "_id": 1, "tags": ["a", "b"], "scores": [3, 8, 6, 9] }).unwrap();
+    let get = |key: &str| col.find_one(doc! { "_id": 1 }).unwrap().unwrap().get(key).cloned().unwrap();
+
+    col.update_one(doc! { "_id": 1 }, doc! { "$push": { "tags": { "$each": ["c", "a"] } } }).unwrap();
+    assert_eq!(get("tags"), bson::bson!(["a", "b", "c", "a"]));
+
+    col.update_one(doc! { "_id": 1 }, doc! { "$pull": { "tags": "a", "scores": { "$gte": 8 } } }).unwrap();
+    assert_eq!(get("tags"), bson::bson!(["b", "c"]));
+    assert_eq!(get("scores"), bson::bson!([3, 6]));
+
+    let result = col.update_one(doc! { "_id": 1 }, doc! { "$addToSet": { "tags": "b" } }).unwrap();
+    assert_eq!(result.modified_count, 0);
+    col.update_one(doc! { "_id": 1 }, doc! { "$addToSet": { "tags": { "$each": ["d", "c", "e"] }, "empty": { "$each": [] } } }).unwrap();
+    assert_eq!(get("tags"), bson::bson!(["b", "c", "d", "e"]));
+    assert_eq!(get("empty"), bson::bson!([]));
+
+    col.update_one(doc! { "_id": 1 }, doc! { "$pop": { "tags": -1 } }).unwrap();
+    assert_eq!(get("tags"), bson::bson!(["c", "d", "e"]));
+
+    col.update_one(doc! { "_id": 1 }, doc! { "$set": { "reviews": [{ "user": "a", "score": 2 }, { "user": "b", "score": 5 }] } }).unwrap();
+    col.update_one(doc! { "_id": 1 }, doc! { "$pull": { "reviews": { "score": { "$lt": 3 } } } }).unwrap();
+    assert_eq!(get("reviews"), bson::bson!([{ "user": "b", "score": 5 }]));
+
+    assert!(col.update_one(doc! { "_id": 1 }, doc! { "$push": { "tags": { "$each": "f" } } }).is_err());
+    assert!(col.update_one(doc! { "_id": 1 }, doc! { "$addToSet": { "_id": 2 } }).is_err());
+    assert!(col.update_one(doc! { "_id": 1 }, doc! { "$pull": { "tags": { "$unknown": 1 } } }).is_err());
+}
+
+#[test]
+fn test_update_positional() {
+    let db = prepare_db("test-update-positional").unwrap();
+    let col = db.collection::<Document>("orders");
+    col.create_index(IndexModel {
+        keys: doc! { "items.sku": 1 },
+        options: None,
+    }).unwrap();
+    col.insert_many(vec![
+        doc! {
+            "_id": 1,
+            "items": [
+                { "sku": "A1", "qty": 1 },
+                { "sku": "B2", "qty": 2 },
+            ],
+            "grades": [80, 85, 90],
+        },
+        doc! {
+            "_id": 2,
+            "items": [{ "sku": "B2", "qty": 3 }],
+        },
+    ]).unwrap();
+    let get = |id: i32| col.find_one(doc! { "_id": id }).unwrap().unwrap();
+
+    // the filter matches the documents by the fields of the elements
+    let found = col.find(doc! { "items.sku": "B2" }).run().unwrap().count();
+    assert_eq!(found, 2);
+    let found = col.find(doc! { "_id": 1, "grades": { "$gt": 85 } }).run().unwrap().count();
+    assert_eq!(found, 1);
+
+    col.update_one(doc! { "_id": 1, "items.sku": "B2" }, doc! { "$set": { "items.$.qty": 5 } }).unwrap();
+    assert_eq!(get(1).get("items").cloned().unwrap(), bson::bson!([
+        { "sku": "A1", "qty": 1 },
+        { "sku": "B2", "qty": 5 },
+    ]));
+
+    col.update_one(
+        doc! { "_id": 1, "items": { "$elemMatch": { "qty": { "$lt": 5 } } } },
+        doc! { "$inc": { "items.$.qty": 10 } },
+    ).unwrap();
+    col.update_one(doc! { "_id": 1, "grades": 85 }, doc! { "$set": { "grades.$": 86 } }).unwrap();
+    col.update_many(doc! {}, doc! { "$mul": { "items.$[].qty": 2 } }).unwrap();
+    let order = get(1);
+    assert_eq!(order.get("items").cloned().unwrap(), bson::bson!([
+        { "sku": "A1", "qty": 22 },
+        { "sku": "B2", "qty": 10 },
+    ]));
+    assert_eq!(order.get("grades").cloned().unwrap(), bson::bson!([80, 86, 90]));
+    assert_eq!(get(2).get("items").cloned().unwrap(), bson::bson!([{ "sku": "B2", "qty": 6 }]));
+
+    // the dotted paths are fields of the embedded documents, and positions of the arrays
+    col.update_one(doc! { "_id": 2 }, doc! { "$set": { "items.0.sku": "C3", "meta.source": "web" } }).unwrap();
+    let order = get(2);
+    assert_eq!(order.get_document("meta").unwrap(), &doc! { "source": "web" });
+    assert_eq!(col.find_one(doc! { "items.sku": "C3" }).unwrap().unwrap().get_i32("_id").unwrap(), 2);
+    assert!(col.find_one(doc! { "items.sku": "B2", "_id": 2 }).unwrap().is_none());
+    assert!(db.verify().unwrap().is_ok());
+
+    let err = col.update_one(doc! { "_id": 1 }, doc! { "$set": { "items.$.qty": 1 } }).unwrap_err();
+    assert!(matches!(err, Error::InvalidUpdatePath(_)));
+    let err = col.update_one(doc! { "_id": 1 }, doc! { "$set": { "items.$[i].qty": 1 } }).unwrap_err();
+    assert!(matches!(err, Error::InvalidUpdatePath(_)));
+    let err = col.update_one(doc! { "_id": 1 }, doc! { "$set": { "items.sku.x": 1 } }).unwrap_err();
+    assert!(matches!(err, Error::InvalidUpdatePath(_)));
+}
@@ -0,0 +1,221 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Database, Error};
+use polodb_core::options::{
+    CreateCollectionOptions,
+    ModifyCollectionOptions,
+    ValidationAction,
+    ValidationLevel,
+};
+
+mod common;
+
+use common::prepare_db;
+
+fn patient_validator() -> Document {
+    doc! {
+        "$jsonSchema": {
+            "bsonType": "object",
+            "required": ["name", "age"],
+            "properties": {
+                "name": { "bsonType": "string", "minLength": 1 },
+                "age": { "bsonType": "int", "minimum": 0 },
+            },
+        },
+    }
+}
+
+#[test]
+fn test_validation_on_insert_and_update() {
+    let db = prepare_db("test-validation").unwrap();
+    db.create_collection_with_options("patients", CreateCollectionOptions::builder()
+        .validator(patient_validator())
+        .build()
+    ).unwrap();
+
+    let patients = db.collection::<Document>("patients");
+    patients.insert_one(doc! { "_id": 1, "name": "Ann", "age": 30 }).unwrap();
+    assert!(matches!(
+        patients.insert_one(doc! { "_id": 2, "name": "Bob" }),
+        Err(Error::DocumentValidationFailed(_)),
+    ));
+    assert!(matches!(
+        patients.insert_many(vec![
+            doc! { "_id": 3, "name": "Cid", "age": 1 },
+            doc! { "_id": 4, "name": "", "age": 1 },
+        ]),
+        Err(Error::DocumentValidationFailed(_)),
+    ));
+    assert_eq!(patients.count_documents().unwrap(), 1);
+
+    assert!(matches!(
+        patients.update_one(doc! { "_id": 1 }, doc! { "$set": { "age": -1 } }),
+        Err(Error::DocumentValidationFailed(_)),
+    ));
+    assert!(matches!(
+        patients.update_one(doc! { "_id": 1 }, doc! { "$unset": { "name": "" } }),
+        Err(Error::DocumentValidationFailed(_)),
+    ));
+    patients.update_one(doc! { "_id": 1 }, doc! { "$inc": { "age": 1 } }).unwrap();
+    let patient = patients.find_one(doc! { "_id": 1 }).unwrap().unwrap();
+    assert_eq!(patient, doc! { "_id": 1, "name": "Ann", "age": 31 });
+}
+
+#[test]
+fn test_validation_persisted() {
+    let db_path = common::mk_db_path("test-validation-persisted");
+    let _ = std::fs::remove_dir_all(&db_path);
+    {
+        let db = Database::open_path(&db_path).unwrap();
+        db.create_collection_with_options("patients", CreateCollectionOptions::builder()
+            .validator(patient_validator())
+            .build()
+        ).unwrap();
+    }
+    let db = Database::open_path(&db_path).unwrap();
+    let patients = db.collection::<Document>("patients");
+    assert!(matches!(
+        patients.insert_one(doc! { "name": "Bob" }),
+        Err(Error::DocumentValidationFailed(_)),
+    ));
+}
+
+#[test]
+fn test_validation_level_and_action() {
+    let db = prepare_db("test-validation-level").unwrap();
+    let patients = db.collection::<Document>("patients");
+    patients.insert_one(doc! { "_id": 1, "name": "Ann" }).unwrap();
+    patients.insert_one(doc! { "_id": 2, "name": "Bob", "age": 3 }).unwrap();
+
+    db.modify_collection("patients", ModifyCollectionOptions::builder()
+        .validator(patient_validator())
+        .validation_level(ValidationLevel::Moderate)
+        .build()
+    ).unwrap();
+
+    // the invalid documents can still be updated
+    patients.update_one(doc! { "_id": 1 }, doc! { "$set": { "name": "Anna" } }).unwrap();
+    assert!(matches!(
+        patients.update_one(doc! { "_id": 2 }, doc! { "$set": { "age": "three" } }),
+        Err(Error::DocumentValidationFailed(_)),
+    ));
+
+    db.modify_collection("patients", ModifyCollectionOptions::builder()
+        .validation_action(ValidationAction::Warn)
+        .build()
+    ).unwrap();
+    patients.insert_one(doc! { "_id": 3 }).unwrap();
+
+    db.modify_collection("patients", ModifyCollectionOptions::builder()
+        .validation_action(ValidationAction::Error)
+        .validation_level(ValidationLevel::Off)
+        .build()
+    ).unwrap();
+    patients.insert_one(doc! { "_id": 4 }).unwrap();
+
+    db.modify_collection("patients", ModifyCollectionOptions::builder()
+        .validator(doc! {})
+        .build()
+    ).unwrap();
+    patients.insert_one(doc! { "_id": 5 }).unwrap();
+    assert_eq!(patients.count_documents().unwrap(), 5);
+}
+
+#[test]
+fn test_invalid_validator() {
+    let db = prepare_db("test-invalid-validator").unwrap();
+    let result = db.create_collection_with_options("items", CreateCollectionOptions::builder()
+        .validator(doc! { "$jsonSchema": { "bsonType": "integer" } })
+        .build()
+    );
+    assert!(matches!(result, Err(Error::InvalidJsonSchema(_))));
+    let result = db.create_collection_with_options("items", CreateCollectionOptions::builder()
+        .validator(doc! { "name": { "$exists": true } })
+        .build()
+    );
+    assert!(matches!(result, Err(Error::InvalidJsonSchema(_))));
+    assert!(matches!(
+        db.modify_collection("missing", ModifyCollectionOptions::default()),
+        Err(Error::CollectionNotFound(_)),
+    ));
+}
+
+#[test]
+fn test_json_schema_query() {
+    let db = prepare_db("test-json-schema-query").unwrap();
+    let patients = db.collection::<Document>("patients");
+    patients.insert_many(vec![
+        doc! { "_id": 1, "name": "Ann", "age": 30 },
+        doc! { "_id": 2, "name": "Bob" },
+        doc! { "_id": 3, "name": "", "age": 1 },
+        doc! { "_id": 4, "name": "Dan", "age": 40 },
+    ]).unwrap();
+
+    let ids = |filter: Document| -> Vec<i32> {
+        patients.find(filter).run().unwrap()
+            .map(|doc| doc.unwrap().get_i32("_id").unwrap())
+            .collect()
+    };
+    let schema = patient_validator();
+
+    // the legacy documents which would be rejected by the validator
+    assert_eq!(ids(schema.clone()), vec![1, 4]);
+    assert_eq!(ids(doc! { "$nor": [schema.clone()] }), vec![2, 3]);
+
+    // with the other conditions of the query
+    let mut filter = schema.clone();
+    filter.insert("age", doc! { "$gt": 35 });
+    assert_eq!(ids(filter), vec![4]);
+    assert_eq!(ids(doc! { "$or": [schema.clone(), { "_id": 3 }] }), vec![1, 3, 4]);
+
+    let count = patients.update_many(doc! { "$nor": [schema] }, doc! { "$set": { "legacy": true } })
+        .unwrap()
+        .modified_count;
+    assert_eq!(count, 2);
+
+    assert!(matches!(
+        patients.find(doc! { "$jsonSchema": { "bsonType": "integer" } }).run(),
+        Err(Error::InvalidJsonSchema(_)),
+    ));
+}
+
+#[test]
+fn test_validator_closure() {
+    let db = prepare_db("test-validator-closure").unwrap();
+    db.add_validator("bookings", |doc| {
+        match (doc.get_i32("start"), doc.get_i32("end")) {
+            (Ok(start), Ok(end)) if start > end => Err(format!("start {} is after end {}", start, end)),
+            _ => Ok(()),
+        }
+    });
+    let bookings = db.collection::<Document>("bookings");
+
+    bookings.insert_one(doc! { "_id": 1, "start": 1, "end": 2 }).unwrap();
+    let err = bookings.insert_one(doc! { "_id": 2, "start": 3, "end": 2 }).unwrap_err();
+    assert!(matches!(err, Error::DocumentValidationFailed(reason) if reason == "start 3 is after end 2"));
+
+    let err = bookings.update_one(doc! { "_id": 1 }, doc! { "$set": { "start": 5 } }).unwrap_err();
+    assert!(matches!(err, Error::DocumentValidationFailed(_)));
+    assert_eq!(bookings.find_one(doc! { "_id": 1 }).unwrap().unwrap().get_i32("start").unwrap(), 1);
+
+    // the validators survive the removal of the hooks
+    db.clear_hooks("bookings");
+    assert!(bookings.insert_one(doc! { "_id": 3, "start": 3, "end": 2 }).is_err());
+
+    db.clear_validators("bookings");
+    bookings.insert_one(doc! { "_id": 3, "start": 3, "end": 2 }).unwrap();
+    assert_eq!(bookings.count_documents().unwrap(), 2);
+}
@@ -0,0 +1,171 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Database, Error, IndexModel, IndexOptions, Result, Vector, VectorSimilarity};
+
+mod common;
+
+use common::prepare_db;
+
+fn vector_index(dimensions: u32) -> IndexModel {
+    IndexModel {
+        keys: doc! { "embedding": "vector" },
+        options: Some(IndexOptions::builder().dimensions(dimensions).build()),
+    }
+}
+
+fn search(db: &Database, stage: Document) -> Vec<Document> {
+    db.collection::<Document>("notes")
+        .aggregate(vec![
+            doc! { "$vectorSearch": stage },
+            doc! { "$addFields": { "score": { "$meta": "vectorSearchScore" } } },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap()
+}
+
+fn ids(docs: &[Document]) -> Vec<i32> {
+    docs.iter().map(|doc| doc.get_i32("_id").unwrap()).collect()
+}
+
+#[test]
+fn test_vector_index_validation() {
+    let db = prepare_db("test-vector-index-validation").unwrap();
+    let notes = db.collection::<Document>("notes");
+    notes.insert_one(doc! { "_id": 1, "embedding": [1.0, 0.0] }).unwrap();
+
+    let err = notes.create_index(IndexModel {
+        keys: doc! { "embedding": "vector" },
+        options: None,
+    }).unwrap_err();
+    assert!(matches!(err, Error::InvalidVector(_)));
+    assert!(matches!(notes.create_index(vector_index(3)).unwrap_err(), Error::InvalidVector(_)));
+    notes.delete_one(doc! { "_id": 1 }).unwrap();
+    notes.create_index(vector_index(3)).unwrap();
+    assert_eq!(notes.list_index_names().unwrap(), vec!["embedding_vector".to_string()]);
+
+    notes.insert_one(doc! { "_id": 2, "embedding": Vector::new([1.0, 0.0, 0.0]) }).unwrap();
+    notes.insert_one(doc! { "_id": 3 }).unwrap();
+    for invalid in [
+        doc! { "embedding": [1.0, 0.0] },
+        doc! { "embedding": [1.0, "0", 0.0] },
+        doc! { "embedding": "cats" },
+    ] {
+        assert!(matches!(notes.insert_one(invalid).unwrap_err(), Error::InvalidVector(_)));
+    }
+    let err = notes.update_one(doc! { "_id": 2 }, doc! {
+        "$set": { "embedding": [1, 2, 3, 4] },
+    }).unwrap_err();
+    assert!(matches!(err, Error::InvalidVector(_)));
+    assert_eq!(notes.count_documents().unwrap(), 2);
+
+    notes.drop_index("embedding_vector").unwrap();
+    assert!(notes.list_index_names().unwrap().is_empty());
+    notes.insert_one(doc! { "embedding": "cats" }).unwrap();
+}
+
+#[test]
+fn test_vector_search() {
+    let db = prepare_db("test-vector-search").unwrap();
+    let notes = db.collection::<Document>("notes");
+    notes.create_index(vector_index(3)).unwrap();
+    notes.insert_many(vec![
+        doc! { "_id": 1, "topic": "pets", "embedding": [0.9, 0.1, 0.0] },
+        doc! { "_id": 2, "topic": "pets", "embedding": [0.7, 0.3, 0.1] },
+        doc! { "_id": 3, "topic": "money", "embedding": [0.0, 0.2, 0.9] },
+        doc! { "_id": 4, "topic": "money", "embedding": [0.1, 0.9, 0.3] },
+        doc! { "_id": 5, "topic": "none" },
+    ]).unwrap();
+
+    let found = search(&db, doc! {
+        "index": "embedding_vector",
+        "path": "embedding",
+        "queryVector": [1.0, 0.0, 0.0],
+        "limit": 3,
+    });
+    assert_eq!(ids(&found), vec![1, 2, 4]);
+    let score = found[0].get_f64("score").unwrap();
+    assert!(score > found[1].get_f64("score").unwrap());
+    assert!(found.iter().all(|doc| !doc.contains_key("$vectorSearchScore")));
+
+    // filtered, then scored by the updated vectors
+    notes.update_one(doc! { "_id": 3 }, doc! { "$set": { "embedding": [1.0, 0.0, 0.1] } }).unwrap();
+    notes.delete_one(doc! { "_id": 1 }).unwrap();
+    let found = search(&db, doc! {
+        "index": "embedding_vector",
+        "queryVector": [1.0, 0.0, 0.0],
+        "numCandidates": 10,
+        "limit": 2,
+        "filter": { "topic": "money" },
+    });
+    assert_eq!(ids(&found), vec![3, 4]);
+
+    // the most similar first when sorted by score
+    let sorted = db.collection::<Document>("notes")
+        .aggregate(vec![
+            doc! { "$vectorSearch": { "index": "embedding_vector", "queryVector": [0.0, 1.0, 0.0], "limit": 2 } },
+            doc! { "$sort": { "score": { "$meta": "vectorSearchScore" } } },
+        ])
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(sorted[0].get_i32("_id").unwrap(), 4);
+
+    for (stage, expected) in [
+        (doc! { "index": "missing", "queryVector": [1.0, 0.0, 0.0], "limit": 1 }, "IndexNotFound"),
+        (doc! { "index": "embedding_vector", "queryVector": [1.0, 0.0], "limit": 1 }, "InvalidVector"),
+        (doc! { "index": "embedding_vector", "queryVector": [1.0, 0.0, 0.0] }, "ValidationError"),
+    ] {
+        let err = notes.aggregate(vec![doc! { "$vectorSearch": stage }]).run().err().unwrap();
+        assert!(format!("{:?}", err).starts_with(expected), "{:?}", err);
+    }
+}
+
+#[test]
+fn test_vector_index_reopen() {
+    let db_path = polodb_core::test_utils::mk_db_path("test-vector-index-reopen");
+    {
+        let db = Database::open_path(&db_path).unwrap();
+        let notes = db.collection::<Document>("notes");
+        notes.create_index(IndexModel {
+            keys: doc! { "embedding": "vector" },
+            options: Some(IndexOptions::builder()
+                .name("by_embedding")
+                .dimensions(2)
+                .similarity(VectorSimilarity::Euclidean)
+                .build()),
+        }).unwrap();
+        let docs = (0..500).map(|i| doc! {
+            "_id": i,
+            "embedding": [(i % 25) as f64, (i / 25) as f64],
+        });
+        notes.insert_many(docs).unwrap();
+    }
+
+    // the graph is built again from the documents
+    let db = Database::open_path(&db_path).unwrap();
+    let found = search(&db, doc! {
+        "index": "by_embedding",
+        "queryVector": [3.0, 4.0],
+        "limit": 5,
+    });
+    assert_eq!(found[0].get_i32("_id").unwrap(), 4 * 25 + 3);
+    assert_eq!(found[0].get_f64("score").unwrap(), 1.0);
+    assert_eq!(found.len(), 5);
+    assert!(found[1..].iter().all(|doc| doc.get_f64("score").unwrap() == 0.5));
+}
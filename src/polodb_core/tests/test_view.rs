@@ -0,0 +1,140 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use polodb_core::bson::{doc, Document};
+use polodb_core::{CollectionT, Database, Error, IndexModel, Result};
+
+mod common;
+
+use common::prepare_db;
+
+fn names(docs: Vec<Document>) -> Vec<String> {
+    docs.iter().map(|doc| doc.get_str("name").unwrap().to_string()).collect()
+}
+
+fn prepare_users(db: &Database) {
+    db.collection::<Document>("users").insert_many(vec![
+        doc! { "_id": 1, "name": "Ada", "age": 36, "active": true, "password": "a" },
+        doc! { "_id": 2, "name": "Brian", "age": 24, "active": false, "password": "b" },
+        doc! { "_id": 3, "name": "Carla", "age": 51, "active": true, "password": "c" },
+        doc! { "_id": 4, "name": "Dan", "age": 19, "active": true, "password": "d" },
+    ]).unwrap();
+}
+
+#[test]
+fn test_view_query() {
+    let db = prepare_db("test-view-query").unwrap();
+    prepare_users(&db);
+    db.create_view("active_users", "users", vec![
+        doc! { "$match": { "active": true } },
+        doc! { "$unset": ["password", "active"] },
+    ]).unwrap();
+    let view = db.collection::<Document>("active_users");
+
+    let all = view.find(doc! {}).run().unwrap().collect::<Result<Vec<Document>>>().unwrap();
+    assert_eq!(names(all), vec!["Ada", "Carla", "Dan"]);
+    assert!(view.find(doc! { "password": "a" }).run().unwrap().next().is_none());
+    assert_eq!(view.count_documents().unwrap(), 3);
+
+    // the filter and the options of the caller are run on the documents of the view
+    let found = view.find(doc! { "age": { "$gt": 20 } })
+        .sort(doc! { "age": -1 })
+        .limit(1)
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(found, vec![doc! { "_id": 3, "name": "Carla", "age": 51 }]);
+    let found = view.find(doc! {})
+        .filter_fn(|doc| doc.get_str("name").unwrap().len() == 3)
+        .run()
+        .unwrap()
+        .collect::<Result<Vec<Document>>>()
+        .unwrap();
+    assert_eq!(names(found), vec!["Ada", "Dan"]);
+    let counted = view.aggregate(vec![
+        doc! { "$match": { "age": { "$lt": 40 } } },
+        doc! { "$count": "count" },
+    ]).run().unwrap().collect::<Result<Vec<Document>>>().unwrap();
+    assert_eq!(counted, vec![doc! { "count": 2_i64 }]);
+
+    // the current documents of the source
+    db.collection::<Document>("users").update_one(doc! { "_id": 2 }, doc! { "$set": { "active": true } }).unwrap();
+    assert_eq!(view.find_one(doc! { "_id": 2 }).unwrap(), Some(doc! { "_id": 2, "name": "Brian", "age": 24 }));
+
+    // a view of a view
+    db.create_view("young_users", "active_users", vec![doc! { "$match": { "age": { "$lt": 30 } } }]).unwrap();
+    let found = db.collection::<Document>("young_users").find(doc! {}).run().unwrap()
+        .collect::<Result<Vec<Document>>>().unwrap();
+    assert_eq!(names(found), vec!["Brian", "Dan"]);
+}
+
+#[test]
+fn test_view_is_read_only() {
+    let db = prepare_db("test-view-is-read-only").unwrap();
+    prepare_users(&db);
+    db.create_view("public_users", "users", vec![doc! { "$unset": "password" }]).unwrap();
+    let view = db.collection::<Document>("public_users");
+
+    assert!(matches!(view.insert_one(doc! { "name": "Eve" }).unwrap_err(), Error::ReadOnlyView(_)));
+    assert!(matches!(
+        view.update_many(doc! {}, doc! { "$set": { "age": 1 } }).unwrap_err(),
+        Error::ReadOnlyView(_),
+    ));
+    assert!(matches!(view.delete_one(doc! { "_id": 1 }).unwrap_err(), Error::ReadOnlyView(_)));
+    assert!(matches!(view.delete_many(doc! {}).unwrap_err(), Error::ReadOnlyView(_)));
+    let err = view.create_index(IndexModel {
+        keys: doc! { "name": 1 },
+        options: None,
+    }).unwrap_err();
+    assert!(matches!(err, Error::ReadOnlyView(_)));
+    assert_eq!(db.collection::<Document>("users").count_documents().unwrap(), 4);
+
+    // a view can't be computed from itself
+    assert!(matches!(
+        db.create_view("users", "public_users", vec![]).unwrap_err(),
+        Error::CollectionAlreadyExits(_),
+    ));
+    db.create_view("a", "b", vec![]).unwrap();
+    assert!(matches!(db.create_view("b", "a", vec![]).unwrap_err(), Error::IllegalCollectionName(_)));
+    assert!(matches!(db.create_view("c", "c", vec![]).unwrap_err(), Error::IllegalCollectionName(_)));
+
+    let listed = db.list_collections().unwrap()
+        .into_iter()
+        .find(|info| info.get_str("name").unwrap() == "public_users")
+        .unwrap();
+    assert_eq!(listed.get_str("type").unwrap(), "view");
+    assert_eq!(listed.get_document("options").unwrap().get_str("viewOn").unwrap(), "users");
+}
+
+#[test]
+fn test_view_rename_and_reopen() {
+    let db_path = polodb_core::test_utils::mk_db_path("test-view-rename-and-reopen");
+    {
+        let db = Database::open_path(&db_path).unwrap();
+        prepare_users(&db);
+        db.create_view("public_users", "users", vec![doc! { "$unset": "password" }]).unwrap();
+        db.rename_collection("users", "accounts").unwrap();
+        db.rename_collection("public_users", "people").unwrap();
+    }
+
+    let db = Database::open_path(&db_path).unwrap();
+    let found = db.collection::<Document>("people").find(doc! { "age": { "$lt": 30 } }).run().unwrap()
+        .collect::<Result<Vec<Document>>>().unwrap();
+    assert_eq!(names(found.clone()), vec!["Brian", "Dan"]);
+    assert!(found.iter().all(|doc| !doc.contains_key("password")));
+
+    db.collection::<Document>("people").drop().unwrap();
+    assert_eq!(db.collection::<Document>("accounts").count_documents().unwrap(), 4);
+}
@@ -2,5 +2,5 @@
 mod transaction;
 mod transaction_inner;
 
-pub(crate) use transaction_inner::TransactionInner;
+pub(crate) use transaction_inner::{ReadOnly, TransactionInner};
 pub use transaction::Transaction;
@@ -12,24 +12,32 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::{Arc, Weak};
+use std::sync::{Arc, Mutex, Weak};
 use serde::Serialize;
-use crate::{TransactionalCollection};
+use crate::{Collection, TransactionalCollection};
 use crate::db::db_inner::DatabaseInner;
 use super::transaction_inner::TransactionInner;
+use crate::current_op::OpGuard;
+use crate::errors::Error;
+use crate::temp::ScratchSpace;
 
 #[derive(Clone)]
 pub struct Transaction {
     db: Weak<DatabaseInner>,
     inner: Arc<TransactionInner>,
+    _guard: Arc<OpGuard>,
+    /// The temporary collections removed when the transaction ends.
+    temps: Arc<Mutex<Vec<ScratchSpace>>>,
 }
 
 impl Transaction {
 
-    pub(crate) fn new(db: Weak<DatabaseInner>, inner: TransactionInner) -> Transaction {
+    pub(crate) fn new(db: Weak<DatabaseInner>, inner: TransactionInner, guard: OpGuard) -> Transaction {
         Transaction {
             db,
             inner: Arc::new(inner),
+            _guard: Arc::new(guard),
+            temps: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -43,13 +51,49 @@ impl Transaction {
         TransactionalCollection::new(self.db.clone(), col_name, self.inner.as_ref().clone())
     }
 
+    /// Creates a temporary collection living until the transaction is committed,
+    /// rolled back or dropped, see [`crate::TempCollection`]. The writes to it are
+    /// made at once and are not undone by a rollback. After the transaction ends,
+    /// the operations on the returned handle fail with [`Error::DbIsClosed`].
+    pub fn create_temp_collection<T: Serialize>(&self, name: &str) -> crate::Result<Collection<T>> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        let scratch = ScratchSpace::open(&db)?;
+        let collection = scratch.create_collection(name)?;
+        self.temps.lock().unwrap().push(scratch);
+        Ok(collection)
+    }
+
+    /// Execute a SQL statement in the transaction, see [`crate::sql`]
+    /// for the supported subset.
+    #[cfg(feature = "sql")]
+    pub fn sql(&self, sql: &str) -> crate::Result<crate::sql::SqlResult> {
+        crate::sql::execute(self, sql)
+    }
+
+    /// Identify the user making the writes of the transaction in the audit log,
+    /// see [`crate::AuditLog`].
+    pub fn set_user(&self, user: Option<String>) {
+        self.inner.set_user(user);
+    }
+
+    /// Delete the expired documents of the collections created with an
+    /// `expire_at_field` or with a TTL index in the transaction,
+    /// see [`crate::Database::remove_expired_documents`].
+    pub fn remove_expired_documents(&self) -> crate::Result<u64> {
+        let db = self.db.upgrade().ok_or(Error::DbIsClosed)?;
+        db.remove_expired_with_session(&self.inner)
+    }
+
     #[inline]
     pub fn commit(&self) -> crate::Result<()> {
-        self.inner.commit()
+        self.inner.commit()?;
+        self.temps.lock().unwrap().clear();
+        Ok(())
     }
 
     #[inline]
     pub fn rollback(&self) -> crate::Result<()> {
+        self.temps.lock().unwrap().clear();
         self.inner.rollback()
     }
 
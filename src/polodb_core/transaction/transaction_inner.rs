@@ -12,12 +12,40 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::cursor::Cursor;
 use crate::db::RocksDBTransaction;
+use crate::record_cache::RecordCache;
+use crate::query_cache::QueryCache;
+use crate::Error;
+
+type CommitCallback = Box<dyn FnOnce() + Send>;
+
+/// Why the writes of a transaction are refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ReadOnly {
+    /// The database applies the changes of a primary.
+    Replica,
+    /// The database is an opened snapshot file.
+    Snapshot,
+}
 
 #[derive(Clone)]
 pub(crate) struct TransactionInner {
     pub(crate) rocksdb_txn: RocksDBTransaction,
     auto_commit: bool,
+    /// Refuse the writes, the transaction belongs to a replica or to a snapshot.
+    read_only: Option<ReadOnly>,
+    killed: Arc<AtomicBool>,
+    on_commit: Arc<Mutex<Vec<CommitCallback>>>,
+    savepoints: Arc<Mutex<Vec<usize>>>,
+    user: Arc<Mutex<Option<String>>>,
+    record_cache: Option<RecordCache>,
+    query_cache: Option<QueryCache>,
+    /// The keys written, removed from the record cache and whose collections
+    /// are removed from the query cache once committed.
+    written: Arc<Mutex<Vec<Vec<u8>>>>,
 }
 
 impl TransactionInner {
@@ -26,6 +54,45 @@ impl TransactionInner {
         TransactionInner {
             rocksdb_txn,
             auto_commit: true,
+            read_only: None,
+            killed: Arc::new(AtomicBool::new(false)),
+            on_commit: Arc::new(Mutex::new(Vec::new())),
+            savepoints: Arc::new(Mutex::new(Vec::new())),
+            user: Arc::new(Mutex::new(None)),
+            record_cache: None,
+            query_cache: None,
+            written: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub(crate) fn with_record_cache(mut self, record_cache: Option<RecordCache>) -> TransactionInner {
+        self.record_cache = record_cache;
+        self
+    }
+
+    pub(crate) fn with_query_cache(mut self, query_cache: Option<QueryCache>) -> TransactionInner {
+        self.query_cache = query_cache;
+        self
+    }
+
+    pub(crate) fn with_read_only(mut self, read_only: Option<ReadOnly>) -> TransactionInner {
+        self.read_only = read_only;
+        self
+    }
+
+    #[inline]
+    fn track_write(&self, key: &[u8]) {
+        if self.record_cache.is_some() || self.query_cache.is_some() {
+            self.written.lock().unwrap().push(key.to_vec());
+        }
+    }
+
+    /// The record cache, unless the transaction has written: the documents
+    /// it reads may be its own writes, which are not cached.
+    pub(crate) fn record_cache(&self) -> Option<&RecordCache> {
+        match &self.record_cache {
+            Some(cache) if self.written.lock().unwrap().is_empty() => Some(cache),
+            _ => None,
         }
     }
 
@@ -39,31 +106,159 @@ impl TransactionInner {
         self.auto_commit
     }
 
+    pub(crate) fn kill_flag(&self) -> Arc<AtomicBool> {
+        self.killed.clone()
+    }
+
+    #[inline]
+    pub(crate) fn check_killed(&self) -> crate::Result<()> {
+        if self.killed.load(Ordering::Relaxed) {
+            return Err(Error::OperationKilled);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn check_writable(&self) -> crate::Result<()> {
+        self.check_killed()?;
+        match self.read_only {
+            Some(ReadOnly::Replica) => Err(Error::ReadOnlyReplica),
+            Some(ReadOnly::Snapshot) => Err(Error::ReadOnlySnapshot),
+            None => Ok(()),
+        }
+    }
+
     #[inline]
     pub fn put(&self, key: &[u8], value: &[u8]) -> crate::Result<()> {
+        self.check_writable()?;
+        self.track_write(key);
         self.rocksdb_txn.set(key, value)
     }
 
+    /// Write a delta of the value, applied by the merge operator of the storage.
+    #[inline]
+    pub(crate) fn merge(&self, key: &[u8], delta: &[u8]) -> crate::Result<()> {
+        self.check_writable()?;
+        self.track_write(key);
+        self.rocksdb_txn.merge(key, delta)
+    }
+
     #[inline]
     pub fn delete(&self, key: &[u8]) -> crate::Result<()> {
+        self.check_writable()?;
+        self.track_write(key);
         self.rocksdb_txn.delete(key)
     }
 
-    #[inline]
+    /// Move the keys starting with `from` so they start with `to`, keeping their values.
+    pub(crate) fn move_prefix(&self, from: Vec<u8>, to: &[u8]) -> crate::Result<()> {
+        self.rewrite_prefix(from, to, true)?;
+        Ok(())
+    }
+
+    /// Copy the keys starting with `from` to keys starting with `to`,
+    /// return the number of copied keys.
+    pub(crate) fn copy_prefix(&self, from: Vec<u8>, to: &[u8]) -> crate::Result<u64> {
+        self.rewrite_prefix(from, to, false)
+    }
+
+    /// Delete the keys starting with `prefix`, return the number of deleted keys.
+    pub(crate) fn delete_prefix(&self, prefix: Vec<u8>) -> crate::Result<u64> {
+        let mut cursor = Cursor::new(prefix, self.rocksdb_txn.new_iterator());
+        cursor.reset()?;
+        let mut count = 0;
+        while cursor.has_next() {
+            if let Some(key) = cursor.peek_key() {
+                self.delete(key.as_ref())?;
+                count += 1;
+            }
+            cursor.next()?;
+        }
+        Ok(count)
+    }
+
+    fn rewrite_prefix(&self, from: Vec<u8>, to: &[u8], remove: bool) -> crate::Result<u64> {
+        let from_len = from.len();
+        let mut cursor = Cursor::new(from, self.rocksdb_txn.new_iterator());
+        cursor.reset()?;
+        let mut count = 0;
+        while cursor.has_next() {
+            if let Some(key) = cursor.peek_key() {
+                let mut new_key = to.to_vec();
+                new_key.extend_from_slice(&key[from_len..]);
+                let value = cursor.copy_data()?;
+                self.put(&new_key, value.as_ref())?;
+                if remove {
+                    self.delete(key.as_ref())?;
+                }
+                count += 1;
+            }
+            cursor.next()?;
+        }
+        Ok(count)
+    }
+
+    pub(crate) fn set_user(&self, user: Option<String>) {
+        *self.user.lock().unwrap() = user;
+    }
+
+    pub(crate) fn user(&self) -> Option<String> {
+        self.user.lock().unwrap().clone()
+    }
+
+    /// Run the callback after the transaction is committed,
+    /// the callback is dropped if the transaction is rolled back.
+    pub(crate) fn on_commit(&self, callback: CommitCallback) {
+        self.on_commit.lock().unwrap().push(callback);
+    }
+
+    /// Mark the current state of the transaction, so the writes made after
+    /// can be undone by `rollback_to_savepoint` without aborting it.
+    pub(crate) fn set_savepoint(&self) {
+        let callbacks = self.on_commit.lock().unwrap().len();
+        self.savepoints.lock().unwrap().push(callbacks);
+        self.rocksdb_txn.set_savepoint();
+    }
+
+    /// Undo the writes and the commit callbacks since the last savepoint.
+    pub(crate) fn rollback_to_savepoint(&self) -> crate::Result<()> {
+        let callbacks = self.savepoints.lock().unwrap().pop()
+            .expect("internal: no savepoint to rollback to");
+        self.on_commit.lock().unwrap().truncate(callbacks);
+        self.rocksdb_txn.rollback_to_savepoint()
+    }
+
     pub fn commit(&self) -> crate::Result<()> {
-        self.rocksdb_txn.commit()
+        self.check_killed()?;
+        self.rocksdb_txn.commit()?;
+        let written = std::mem::take(&mut *self.written.lock().unwrap());
+        if !written.is_empty() {
+            if let Some(cache) = &self.record_cache {
+                cache.invalidate(&written);
+            }
+            if let Some(cache) = &self.query_cache {
+                cache.invalidate(&written);
+            }
+        }
+        let callbacks = std::mem::take(&mut *self.on_commit.lock().unwrap());
+        for callback in callbacks {
+            callback();
+        }
+        Ok(())
     }
 
     pub(crate) fn auto_commit(&self) -> crate::Result<()> {
         if self.auto_commit {
-            self.rocksdb_txn.commit()
+            self.commit()
         } else {
             Ok(())
         }
     }
 
-    #[inline]
     pub fn rollback(&self) -> crate::Result<()> {
+        self.on_commit.lock().unwrap().clear();
+        self.savepoints.lock().unwrap().clear();
+        self.written.lock().unwrap().clear();
         self.rocksdb_txn.rollback()
     }
 
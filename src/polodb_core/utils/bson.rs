@@ -13,14 +13,29 @@
 // limitations under the License.
 
 use std::cmp::Ordering;
+use std::convert::TryFrom;
 use std::io::{BufRead, Read, Write};
-use bson::{Bson, DateTime, Decimal128, Document, Timestamp};
+use bson::{Binary, Bson, DateTime, Decimal128, Document, RawBsonRef, RawDocument, Timestamp};
 use bson::oid::ObjectId;
-use bson::spec::ElementType;
+use bson::spec::{BinarySubtype, ElementType};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use bson::ser::Error as BsonErr;
 use bson::ser::Result as BsonResult;
 use crate::{Error, Result};
+use crate::utils::decimal::Decimal;
+
+/// The smallest key greater than all the keys starting with `prefix`,
+/// empty if there is none.
+pub fn prefix_upper_bound(prefix: &[u8]) -> Vec<u8> {
+    let mut upper_bound = prefix.to_vec();
+    while let Some(last) = upper_bound.pop() {
+        if last < u8::MAX {
+            upper_bound.push(last + 1);
+            break;
+        }
+    }
+    upper_bound
+}
 
 pub fn stacked_key<'a, T: IntoIterator<Item = &'a Bson>>(keys: T) -> Result<Vec<u8>> {
     let mut result = Vec::<u8>::new();
@@ -79,9 +94,10 @@ pub fn stacked_key_bytes<W: Write>(writer: &mut W, key: &Bson) -> Result<()> {
         Bson::DateTime(dt) => {
             writer.write_u8(ElementType::DateTime as u8)?;
 
-            let t = dt.timestamp_millis();
+            // flip the sign bit, so the dates before 1970 are ordered first
+            let t = (dt.timestamp_millis() as u64) ^ (1 << 63);
 
-            writer.write_i64::<BigEndian>(t)?;
+            writer.write_u64::<BigEndian>(t)?;
         }
         Bson::Symbol(str) => {
             writer.write_u8(ElementType::Symbol as u8)?;
@@ -93,13 +109,20 @@ pub fn stacked_key_bytes<W: Write>(writer: &mut W, key: &Bson) -> Result<()> {
         Bson::Decimal128(dcl) => {
             writer.write_u8(ElementType::Decimal128 as u8)?;
 
-            let bytes = dcl.bytes();
-
-            writer.write_all(&bytes)?;
+            // the equal decimals, such as 1.0 and 1.00, have the same key
+            Decimal::from_decimal128(dcl).write_key(writer)?;
         }
         Bson::Undefined => {
             writer.write_u8(ElementType::Undefined as u8)?;
         }
+        Bson::Binary(bin) => {
+            writer.write_u8(ElementType::Binary as u8)?;
+
+            // ordered by the length first, then the subtype, such as MongoDB
+            writer.write_u32::<BigEndian>(bin.bytes.len() as u32)?;
+            writer.write_u8(bin.subtype.into())?;
+            writer.write_all(&bin.bytes)?;
+        }
 
         _ => {
             let val = format!("{:?}", key);
@@ -111,6 +134,16 @@ pub fn stacked_key_bytes<W: Write>(writer: &mut W, key: &Bson) -> Result<()> {
 }
 
 pub fn split_stacked_keys(buffer: &[u8]) -> Result<Vec<Bson>> {
+    split_keys(buffer, false)
+}
+
+/// Split the keys written in the format 1 of the files, where the dates were
+/// their signed milliseconds and the decimals their 16 bytes.
+pub(crate) fn split_legacy_stacked_keys(buffer: &[u8]) -> Result<Vec<Bson>> {
+    split_keys(buffer, true)
+}
+
+fn split_keys(buffer: &[u8], legacy: bool) -> Result<Vec<Bson>> {
     let mut result = Vec::<Bson>::new();
     let mut reader = buffer;
 
@@ -151,21 +184,33 @@ pub fn split_stacked_keys(buffer: &[u8]) -> Result<Vec<Bson>> {
             let mut bytes = [0u8; 12];
             reader.read_exact(&mut bytes)?;
             result.push(Bson::ObjectId(ObjectId::from_bytes(bytes)));
-        } else if ch == ElementType::DateTime as u8 {
+        } else if ch == ElementType::DateTime as u8 && legacy {
             let val = reader.read_i64::<BigEndian>()?;
-            let datetime = DateTime::from_millis(val);
+            result.push(Bson::DateTime(DateTime::from_millis(val)));
+        } else if ch == ElementType::DateTime as u8 {
+            let val = reader.read_u64::<BigEndian>()? ^ (1 << 63);
+            let datetime = DateTime::from_millis(val as i64);
             result.push(Bson::DateTime(datetime));
         } else if ch == ElementType::Symbol as u8 {
             let mut bytes = Vec::<u8>::new();
             reader.read_until(0, &mut bytes)?;
             bytes.pop();
             result.push(Bson::Symbol(String::from_utf8(bytes)?));
-        } else if ch == ElementType::Decimal128 as u8 {
+        } else if ch == ElementType::Decimal128 as u8 && legacy {
             let mut bytes = [0u8; 16];
             reader.read_exact(&mut bytes)?;
             result.push(Bson::Decimal128(Decimal128::from_bytes(bytes)));
+        } else if ch == ElementType::Decimal128 as u8 {
+            let decimal = Decimal::read_key(&mut reader)?;
+            result.push(Bson::Decimal128(decimal.to_decimal128()));
         } else if ch == ElementType::Undefined as u8 {
             result.push(Bson::Undefined);
+        } else if ch == ElementType::Binary as u8 {
+            let len = reader.read_u32::<BigEndian>()?;
+            let subtype = BinarySubtype::from(reader.read_u8()?);
+            let mut bytes = vec![0u8; len as usize];
+            reader.read_exact(&mut bytes)?;
+            result.push(Bson::Binary(Binary { subtype, bytes }));
         } else {
             return Err(Error::UnknownBsonElementType(ch));
         }
@@ -174,6 +219,15 @@ pub fn split_stacked_keys(buffer: &[u8]) -> Result<Vec<Bson>> {
     Ok(result)
 }
 
+/// The rank of the type of a value, the numbers of all the types are compared with each other:
+/// the ranges of the filters only hold the values of the rank of their bounds.
+pub fn type_rank(value: &Bson) -> u8 {
+    match value {
+        Bson::Double(_) | Bson::Int32(_) | Bson::Int64(_) | Bson::Decimal128(_) => ElementType::Double as u8,
+        _ => value.element_type() as u8,
+    }
+}
+
 pub fn value_cmp(a: &Bson, b: &Bson) -> BsonResult<Ordering> {
     match (a, b) {
         (Bson::Null, Bson::Null) => Ok(Ordering::Equal),
@@ -210,6 +264,12 @@ pub fn value_cmp(a: &Bson, b: &Bson) -> BsonResult<Ordering> {
         (Bson::Binary(b1), Bson::Binary(b2)) => Ok(b1.bytes.cmp(&b2.bytes)),
         (Bson::String(str1), Bson::String(str2)) => Ok(str1.cmp(str2)),
         (Bson::ObjectId(oid1), Bson::ObjectId(oid2)) => Ok(oid1.cmp(oid2)),
+        (Bson::Decimal128(_), _) | (_, Bson::Decimal128(_)) => {
+            match (Decimal::from_number(a), Decimal::from_number(b)) {
+                (Some(d1), Some(d2)) => Ok(d1.total_cmp(&d2)),
+                _ => Ok((a.element_type() as u8).cmp(&(b.element_type() as u8))),
+            }
+        }
         _ => {
             // compare the numeric type
             let a_type = a.element_type() as u8;
@@ -223,6 +283,9 @@ pub fn value_cmp(a: &Bson, b: &Bson) -> BsonResult<Ordering> {
     }
 }
 
+/// The value of a field by its path, such as `a.b`. The path goes through the arrays:
+/// `items.0` is the first element of `items`, and `items.sku` is the array of
+/// the `sku` fields of the documents of `items`.
 pub fn try_get_document_value(doc: &Document, key: &str) -> Option<Bson> {
     let keys = key.split('.').collect::<Vec<&str>>();
     let keys_slice = keys.as_slice();
@@ -230,24 +293,68 @@ pub fn try_get_document_value(doc: &Document, key: &str) -> Option<Bson> {
 }
 
 fn try_get_document_by_slices(doc: &Document, keys: &[&str]) -> Option<Bson> {
-    let first = keys.first();
-    first
-        .and_then(|first_str| {
-            let remains = &keys[1..];
-            let value = doc.get(first_str);
-            match value {
-                Some(Bson::Document(doc)) => {
-                    try_get_document_by_slices(doc, remains)
-                }
-                Some(v) => {
-                    if remains.is_empty() {
-                        return Some(v.clone());
-                    }
-                    None
+    let (first, remains) = keys.split_first()?;
+    try_get_value_by_slices(doc.get(first)?, remains)
+}
+
+fn try_get_value_by_slices(value: &Bson, keys: &[&str]) -> Option<Bson> {
+    match value {
+        _ if keys.is_empty() => Some(value.clone()),
+        Bson::Document(doc) => try_get_document_by_slices(doc, keys),
+        Bson::Array(arr) => try_get_array_by_slices(arr, keys),
+        _ => None,
+    }
+}
+
+fn try_get_array_by_slices(arr: &[Bson], keys: &[&str]) -> Option<Bson> {
+    let (first, remains) = keys.split_first()?;
+    if let Ok(index) = first.parse::<usize>() {
+        return try_get_value_by_slices(arr.get(index)?, remains);
+    }
+    let mut values = Vec::new();
+    for item in arr {
+        if let Bson::Document(doc) = item {
+            match try_get_document_by_slices(doc, keys) {
+                Some(Bson::Array(items)) => values.extend(items),
+                Some(value) => values.push(value),
+                None => (),
+            }
+        }
+    }
+    if values.is_empty() {
+        None
+    } else {
+        Some(Bson::Array(values))
+    }
+}
+
+/// The same as [`try_get_document_value`], but reads the value from the encoding
+/// of the document without decoding the other fields.
+pub fn try_get_raw_document_value(doc: &RawDocument, key: &str) -> Result<Option<Bson>> {
+    let mut current = doc;
+    let mut keys = key.split('.').peekable();
+    while let Some(key) = keys.next() {
+        let value = current.get(key).map_err(bson::de::Error::from)?;
+        match value {
+            Some(RawBsonRef::Document(doc)) if keys.peek().is_some() => {
+                current = doc;
+            }
+            Some(RawBsonRef::Array(arr)) if keys.peek().is_some() => {
+                let arr = Bson::try_from(RawBsonRef::Array(arr)).map_err(bson::de::Error::from)?;
+                let remains = keys.collect::<Vec<&str>>();
+                return Ok(try_get_value_by_slices(&arr, &remains));
+            }
+            Some(v) => {
+                if keys.peek().is_some() {
+                    return Ok(None);
                 }
-                _ => None,
+                let value = Bson::try_from(v).map_err(bson::de::Error::from)?;
+                return Ok(Some(value));
             }
-        })
+            None => return Ok(None),
+        }
+    }
+    Ok(None)
 }
 
 pub fn bson_datetime_now() -> DateTime {
@@ -257,9 +364,10 @@ pub fn bson_datetime_now() -> DateTime {
 #[cfg(test)]
 mod tests {
     use std::cmp::Ordering;
-    use bson::{Bson, doc, Timestamp};
+    use bson::{Binary, Bson, doc, Timestamp};
+    use bson::spec::BinarySubtype;
     use bson::oid::ObjectId;
-    use crate::utils::bson::{split_stacked_keys, stacked_key, value_cmp};
+    use crate::utils::bson::{split_stacked_keys, stacked_key, try_get_document_value, try_get_raw_document_value, value_cmp};
 
     #[test]
     fn test_value_cmp() {
@@ -269,6 +377,12 @@ mod tests {
         assert_eq!(value_cmp(&Bson::Int64(2), &Bson::Int32(3)).unwrap(), Ordering::Less);
         assert_eq!(value_cmp(&Bson::Int64(2), &Bson::Int32(1)).unwrap(), Ordering::Greater);
         assert_eq!(value_cmp(&Bson::Int64(1), &Bson::Int32(1)).unwrap(), Ordering::Equal);
+
+        let decimal = |s: &str| Bson::Decimal128(s.parse().unwrap());
+        assert_eq!(value_cmp(&decimal("1.0"), &decimal("1.00")).unwrap(), Ordering::Equal);
+        assert_eq!(value_cmp(&decimal("0.1"), &Bson::Double(0.1)).unwrap(), Ordering::Equal);
+        assert_eq!(value_cmp(&decimal("2.5"), &Bson::Int32(3)).unwrap(), Ordering::Less);
+        assert_eq!(value_cmp(&Bson::Int64(-3), &decimal("-2.5")).unwrap(), Ordering::Less);
     }
 
     #[test]
@@ -280,6 +394,14 @@ mod tests {
         assert_eq!(super::try_get_document_value(&doc!{"a": { "b": 1 }}, "a.c"), None);
         assert_eq!(super::try_get_document_value(&doc!{"a": { "b": { "c": 1 }}}, "a.b.c"), Some(Bson::Int32(1)));
         assert_eq!(super::try_get_document_value(&doc!{"a": { "b": { "c": 1 }}}, "a.b.d"), None);
+        assert_eq!(super::try_get_document_value(&doc!{"a": { "b": { "c": 1 }}}, "a.b"), Some(Bson::from(doc!{ "c": 1 })));
+        let items = doc! { "items": [{ "sku": "A1", "tags": ["x", "y"] }, 1, { "sku": "B2", "tags": "z" }] };
+        assert_eq!(super::try_get_document_value(&items, "items.sku"), Some(Bson::from(vec!["A1", "B2"])));
+        assert_eq!(super::try_get_document_value(&items, "items.tags"), Some(Bson::from(vec!["x", "y", "z"])));
+        assert_eq!(super::try_get_document_value(&items, "items.2.sku"), Some(Bson::from("B2")));
+        assert_eq!(super::try_get_document_value(&items, "items.1"), Some(Bson::Int32(1)));
+        assert_eq!(super::try_get_document_value(&items, "items.qty"), None);
+        assert_eq!(super::try_get_document_value(&items, "items.3"), None);
     }
 
     #[test]
@@ -295,6 +417,15 @@ mod tests {
             Bson::Boolean(true),
             Bson::Timestamp(Timestamp { time: 42, increment: 42 }),
             Bson::DateTime(super::bson_datetime_now()),
+            Bson::Binary(Binary {
+                subtype: BinarySubtype::Uuid,
+                bytes: uuid::Uuid::new_v4().as_bytes().to_vec(),
+            }),
+            Bson::Binary(Binary {
+                subtype: BinarySubtype::Generic,
+                bytes: vec![],
+            }),
+            Bson::Decimal128("-1.5".parse().unwrap()),
         ];
         let stacked = stacked_key(&values).unwrap();
         let slices = split_stacked_keys(&stacked).unwrap();
@@ -306,4 +437,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_raw_document_value() {
+        let doc = doc! {
+            "name": "Alice",
+            "address": {
+                "city": "Paris",
+                "geo": { "lat": 48.85 },
+            },
+            "tags": ["a", "b"],
+            "items": [{ "sku": "A1" }, { "sku": "B2" }],
+        };
+        let raw = bson::RawDocumentBuf::from_document(&doc).unwrap();
+        for key in ["name", "address", "address.city", "address.geo.lat", "address.zip", "name.first", "tags", "tags.0", "items.sku", "items.1.sku", "missing"] {
+            assert_eq!(
+                try_get_raw_document_value(&raw, key).unwrap(),
+                try_get_document_value(&doc, key),
+                "{}", key,
+            );
+        }
+    }
+
 }
@@ -0,0 +1,258 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The value of a Decimal128, to compare it with the other numbers
+//! and to encode it in the keys.
+
+use std::cmp::Ordering;
+use std::io::{Read, Write};
+use bson::{Bson, Decimal128};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use crate::Result;
+
+const EXPONENT_BIAS: i32 = 6176;
+const MAX_EXPONENT: i32 = 6111;
+const MAX_DIGITS: u32 = 34;
+const MAX_COEFFICIENT: u128 = 10u128.pow(MAX_DIGITS) - 1;
+
+// the classes of the values, in the order of the keys
+const CLASS_NAN: u8 = 0;
+const CLASS_NEGATIVE_INFINITY: u8 = 1;
+const CLASS_NEGATIVE: u8 = 2;
+const CLASS_ZERO: u8 = 3;
+const CLASS_POSITIVE: u8 = 4;
+const CLASS_POSITIVE_INFINITY: u8 = 5;
+
+/// A number as `coefficient * 10^exponent`, without trailing zeros in the coefficient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Decimal {
+    NaN,
+    Infinity { negative: bool },
+    Finite { negative: bool, coefficient: u128, exponent: i32 },
+}
+
+impl Decimal {
+
+    pub(crate) fn from_decimal128(value: &Decimal128) -> Decimal {
+        let bits = u128::from_le_bytes(value.bytes());
+        let negative = bits >> 127 == 1;
+        let (coefficient, exponent) = if (bits >> 125) & 0b11 == 0b11 {
+            match (bits >> 122) & 0b11111 {
+                0b11111 => return Decimal::NaN,
+                0b11110 => return Decimal::Infinity { negative },
+                // the coefficient exceeds the maximum, the value is zero
+                _ => (0, ((bits >> 111) & 0x3FFF) as i32 - EXPONENT_BIAS),
+            }
+        } else {
+            let coefficient = bits & ((1u128 << 113) - 1);
+            let coefficient = if coefficient > MAX_COEFFICIENT { 0 } else { coefficient };
+            (coefficient, ((bits >> 113) & 0x3FFF) as i32 - EXPONENT_BIAS)
+        };
+        Decimal::finite(negative, coefficient, exponent)
+    }
+
+    pub(crate) fn from_i64(value: i64) -> Decimal {
+        Decimal::finite(value < 0, value.unsigned_abs() as u128, 0)
+    }
+
+    /// The decimal of the shortest representation of the double, so 0.1 equals the decimal 0.1.
+    pub(crate) fn from_f64(value: f64) -> Decimal {
+        if value.is_nan() {
+            return Decimal::NaN;
+        }
+        if value.is_infinite() {
+            return Decimal::Infinity { negative: value < 0.0 };
+        }
+        // such as "-1.25e-3"
+        let repr = format!("{:e}", value);
+        let (mantissa, exponent) = repr.split_once('e').unwrap();
+        let negative = mantissa.starts_with('-');
+        let mantissa = mantissa.trim_start_matches('-');
+        let fraction_digits = mantissa.split_once('.').map_or(0, |(_, fraction)| fraction.len());
+        let coefficient = mantissa.replace('.', "").parse::<u128>().unwrap();
+        let exponent = exponent.parse::<i32>().unwrap() - fraction_digits as i32;
+        Decimal::finite(negative, coefficient, exponent)
+    }
+
+    /// The decimal of a numeric value.
+    pub(crate) fn from_number(value: &Bson) -> Option<Decimal> {
+        match value {
+            Bson::Int32(i) => Some(Decimal::from_i64(*i as i64)),
+            Bson::Int64(i) => Some(Decimal::from_i64(*i)),
+            Bson::Double(d) => Some(Decimal::from_f64(*d)),
+            Bson::Decimal128(d) => Some(Decimal::from_decimal128(d)),
+            _ => None,
+        }
+    }
+
+    fn finite(negative: bool, mut coefficient: u128, mut exponent: i32) -> Decimal {
+        if coefficient == 0 {
+            return Decimal::Finite { negative: false, coefficient: 0, exponent: 0 };
+        }
+        while coefficient.is_multiple_of(10) {
+            coefficient /= 10;
+            exponent += 1;
+        }
+        Decimal::Finite { negative, coefficient, exponent }
+    }
+
+    pub(crate) fn to_decimal128(self) -> Decimal128 {
+        let bits = match self {
+            Decimal::NaN => 0b11111u128 << 122,
+            Decimal::Infinity { negative } => ((negative as u128) << 127) | (0b11110u128 << 122),
+            Decimal::Finite { negative, mut coefficient, mut exponent } => {
+                // the trailing zeros may push the exponent beyond the maximum
+                if exponent > MAX_EXPONENT {
+                    coefficient *= 10u128.pow((exponent - MAX_EXPONENT) as u32);
+                    exponent = MAX_EXPONENT;
+                }
+                ((negative as u128) << 127) | (((exponent + EXPONENT_BIAS) as u128) << 113) | coefficient
+            }
+        };
+        Decimal128::from_bytes(bits.to_le_bytes())
+    }
+
+    fn class(&self) -> u8 {
+        match self {
+            Decimal::NaN => CLASS_NAN,
+            Decimal::Infinity { negative: true } => CLASS_NEGATIVE_INFINITY,
+            Decimal::Infinity { negative: false } => CLASS_POSITIVE_INFINITY,
+            Decimal::Finite { coefficient: 0, .. } => CLASS_ZERO,
+            Decimal::Finite { negative: true, .. } => CLASS_NEGATIVE,
+            Decimal::Finite { negative: false, .. } => CLASS_POSITIVE,
+        }
+    }
+
+    /// The exponent of the most significant digit, and the coefficient
+    /// aligned on 34 digits, they compare as the magnitude of the value.
+    fn magnitude(coefficient: u128, exponent: i32) -> (i32, u128) {
+        let digits = coefficient.ilog10() + 1;
+        (exponent + digits as i32 - 1, coefficient * 10u128.pow(MAX_DIGITS - digits))
+    }
+
+    /// NaN is less than every number, as in MongoDB.
+    pub(crate) fn total_cmp(&self, other: &Decimal) -> Ordering {
+        let ord = self.class().cmp(&other.class());
+        if ord != Ordering::Equal {
+            return ord;
+        }
+        match (self, other) {
+            (
+                Decimal::Finite { negative, coefficient: c1, exponent: e1 },
+                Decimal::Finite { coefficient: c2, exponent: e2, .. },
+            ) if *c1 != 0 => {
+                let ord = Decimal::magnitude(*c1, *e1).cmp(&Decimal::magnitude(*c2, *e2));
+                if *negative { ord.reverse() } else { ord }
+            }
+            _ => Ordering::Equal,
+        }
+    }
+
+    /// Write the key of the value, the keys compare byte by byte as the values.
+    pub(crate) fn write_key<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_u8(self.class())?;
+        if let Decimal::Finite { negative, coefficient, exponent } = *self {
+            if coefficient != 0 {
+                let (adjusted, aligned) = Decimal::magnitude(coefficient, exponent);
+                let mut bytes = [0u8; 18];
+                bytes[..2].copy_from_slice(&((adjusted + EXPONENT_BIAS) as u16).to_be_bytes());
+                bytes[2..].copy_from_slice(&aligned.to_be_bytes());
+                if negative {
+                    bytes.iter_mut().for_each(|b| *b = !*b);
+                }
+                writer.write_all(&bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn read_key<R: Read>(reader: &mut R) -> Result<Decimal> {
+        let class = reader.read_u8()?;
+        let negative = class == CLASS_NEGATIVE;
+        let decimal = match class {
+            CLASS_NAN => Decimal::NaN,
+            CLASS_NEGATIVE_INFINITY => Decimal::Infinity { negative: true },
+            CLASS_POSITIVE_INFINITY => Decimal::Infinity { negative: false },
+            CLASS_NEGATIVE | CLASS_POSITIVE => {
+                let mut bytes = [0u8; 18];
+                reader.read_exact(&mut bytes)?;
+                if negative {
+                    bytes.iter_mut().for_each(|b| *b = !*b);
+                }
+                let mut slice = &bytes[..];
+                let adjusted = slice.read_u16::<BigEndian>()? as i32 - EXPONENT_BIAS;
+                let aligned = slice.read_u128::<BigEndian>()?;
+                Decimal::finite(negative, aligned, adjusted - (MAX_DIGITS as i32 - 1))
+            }
+            _ => Decimal::finite(false, 0, 0),
+        };
+        Ok(decimal)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use std::str::FromStr;
+    use bson::Decimal128;
+    use super::Decimal;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_decimal128(&Decimal128::from_str(s).unwrap())
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(dec("1.50"), Decimal::Finite { negative: false, coefficient: 15, exponent: -1 });
+        assert_eq!(dec("-0.00"), dec("0"));
+        assert_eq!(dec("NaN"), Decimal::NaN);
+        assert_eq!(dec("-Infinity"), Decimal::Infinity { negative: true });
+        assert_eq!(Decimal::from_f64(0.1), dec("0.1"));
+        assert_eq!(Decimal::from_f64(-1234.5), dec("-1.2345E+3"));
+        assert_eq!(Decimal::from_i64(-100), dec("-100"));
+        assert_eq!(dec("1.25").to_decimal128().to_string(), "1.25");
+    }
+
+    #[test]
+    fn test_order() {
+        let sorted = [
+            dec("NaN"),
+            dec("-Infinity"),
+            dec("-1E+10"),
+            dec("-2.5"),
+            dec("-0.001"),
+            dec("0"),
+            dec("1E-30"),
+            dec("0.1"),
+            dec("1.000000000000000000000000000000001"),
+            dec("2"),
+            dec("10"),
+            dec("9.999999999999999999999999999999999E+6144"),
+            dec("Infinity"),
+        ];
+        for (i, a) in sorted.iter().enumerate() {
+            let mut key_a = vec![];
+            a.write_key(&mut key_a).unwrap();
+            assert_eq!(Decimal::read_key(&mut key_a.as_slice()).unwrap(), *a);
+            for (j, b) in sorted.iter().enumerate() {
+                let mut key_b = vec![];
+                b.write_key(&mut key_b).unwrap();
+                assert_eq!(a.total_cmp(b), i.cmp(&j), "{:?} {:?}", a, b);
+                assert_eq!(key_a.cmp(&key_b), i.cmp(&j), "{:?} {:?}", a, b);
+            }
+        }
+        assert_eq!(dec("1.0").total_cmp(&dec("1.00")), Ordering::Equal);
+    }
+}
@@ -0,0 +1,537 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use bson::{Bson, Document};
+use serde::{Deserialize, Serialize};
+use crate::coll::collection_info::{CollectionSpecification, VectorIndexInfo};
+use crate::cursor::Cursor;
+use crate::transaction::TransactionInner;
+use crate::{Error, Result};
+
+/// The field keeping the score of a document found by `$vectorSearch`
+/// while it goes through the pipeline, it's removed from the results.
+pub(crate) const VECTOR_SCORE_FIELD: &str = "$vectorSearchScore";
+
+/// The neighbors of a node on the upper layers of the graph, twice as many on the bottom layer.
+const MAX_NEIGHBORS: usize = 16;
+
+/// The number of nodes considered for the neighbors of a new node.
+const EF_CONSTRUCTION: usize = 100;
+
+/// How the vectors of an index are compared. The score of a document
+/// found by `$vectorSearch` is between 0 and 1 for the normalized vectors,
+/// the higher the closer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VectorSimilarity {
+    /// `(1 + cosine) / 2`
+    #[default]
+    Cosine,
+    /// `1 / (1 + distance)`
+    Euclidean,
+    /// `(1 + dot product) / 2`, the same order as cosine for the normalized vectors.
+    DotProduct,
+}
+
+impl VectorSimilarity {
+
+    pub(crate) fn score(&self, a: &[f32], b: &[f32]) -> f64 {
+        let dot = || a.iter().zip(b).map(|(x, y)| *x as f64 * *y as f64).sum::<f64>();
+        match self {
+            VectorSimilarity::Cosine => {
+                let norm = |v: &[f32]| v.iter().map(|x| *x as f64 * *x as f64).sum::<f64>().sqrt();
+                let norms = norm(a) * norm(b);
+                let cosine = if norms == 0.0 { 0.0 } else { dot() / norms };
+                (1.0 + cosine) / 2.0
+            }
+            VectorSimilarity::Euclidean => {
+                let distance = a.iter().zip(b)
+                    .map(|(x, y)| (*x as f64 - *y as f64).powi(2))
+                    .sum::<f64>()
+                    .sqrt();
+                1.0 / (1.0 + distance)
+            }
+            VectorSimilarity::DotProduct => (1.0 + dot()) / 2.0,
+        }
+    }
+
+}
+
+/// A vector of a `"vector"` index, stored as an array of doubles.
+///
+/// ```rust
+/// use polodb_core::{Database, CollectionT, IndexModel, IndexOptions, Vector};
+/// use polodb_core::bson::{doc, Document};
+///
+/// # let db_path = polodb_core::test_utils::mk_db_path("doc-test-vector");
+/// let db = Database::open_path(db_path).unwrap();
+/// let notes = db.collection::<Document>("notes");
+/// notes.create_index(IndexModel {
+///     keys: doc! { "embedding": "vector" },
+///     options: Some(IndexOptions::builder().dimensions(3).build()),
+/// }).unwrap();
+/// notes.insert_one(doc! { "text": "cats", "embedding": Vector::new([0.9, 0.1, 0.0]) }).unwrap();
+/// notes.insert_one(doc! { "text": "taxes", "embedding": Vector::new([0.0, 0.2, 0.9]) }).unwrap();
+/// assert!(notes.insert_one(doc! { "embedding": [1.0, 0.0] }).is_err());
+///
+/// let found = notes.aggregate(vec![
+///     doc! { "$vectorSearch": {
+///         "index": "embedding_vector",
+///         "queryVector": [1.0, 0.0, 0.0],
+///         "limit": 1,
+///     } },
+///     doc! { "$addFields": { "score": { "$meta": "vectorSearchScore" } } },
+/// ]).run().unwrap().next().unwrap().unwrap();
+/// assert_eq!(found.get_str("text").unwrap(), "cats");
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Vector(Vec<f32>);
+
+impl Vector {
+
+    pub fn new(values: impl Into<Vec<f32>>) -> Vector {
+        Vector(values.into())
+    }
+
+    #[inline]
+    pub fn dimensions(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[f32] {
+        &self.0
+    }
+
+    /// The values of an array of numbers.
+    pub(crate) fn from_bson(value: &Bson) -> Option<Vector> {
+        let arr = match value {
+            Bson::Array(arr) => arr,
+            _ => return None,
+        };
+        let mut values = Vec::with_capacity(arr.len());
+        for item in arr {
+            let value = match item {
+                Bson::Double(value) => *value as f32,
+                Bson::Int32(value) => *value as f32,
+                Bson::Int64(value) => *value as f32,
+                _ => return None,
+            };
+            if !value.is_finite() {
+                return None;
+            }
+            values.push(value);
+        }
+        Some(Vector(values))
+    }
+
+}
+
+impl From<Vec<f32>> for Vector {
+    fn from(values: Vec<f32>) -> Self {
+        Vector(values)
+    }
+}
+
+impl From<Vector> for Bson {
+    fn from(vector: Vector) -> Self {
+        Bson::Array(vector.0.into_iter().map(|value| Bson::Double(value as f64)).collect())
+    }
+}
+
+/// Check the values of the fields of the vector indexes, a document
+/// without the field or with a null is not indexed.
+pub(crate) fn check_fields<'a>(indexes: impl IntoIterator<Item = &'a VectorIndexInfo>, doc: &Document) -> Result<()> {
+    for info in indexes {
+        let value = match crate::utils::bson::try_get_document_value(doc, &info.field) {
+            None | Some(Bson::Null) => continue,
+            Some(value) => value,
+        };
+        match Vector::from_bson(&value) {
+            Some(vector) if vector.dimensions() == info.dimensions as usize => (),
+            Some(vector) => {
+                return Err(Error::InvalidVector(format!(
+                    "'{}' has {} dimensions instead of {}", info.field, vector.dimensions(), info.dimensions,
+                )));
+            }
+            None => {
+                return Err(Error::InvalidVector(format!("'{}' is not an array of numbers", info.field)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Is the value `{ "$meta": "vectorSearchScore" }`, standing for the score of the document.
+pub(crate) fn is_vector_search_score(doc: &Document) -> bool {
+    doc.len() == 1 && matches!(doc.get("$meta"), Some(Bson::String(meta)) if meta == "vectorSearchScore")
+}
+
+/// A node of the graph, by its distance to the searched vector.
+#[derive(Clone, Copy)]
+struct Candidate {
+    distance: f64,
+    node: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance.total_cmp(&other.distance).then(self.node.cmp(&other.node))
+    }
+}
+
+struct Node {
+    id: Bson,
+    vector: Vec<f32>,
+    /// The neighbors of the node on each of its layers, from the bottom one.
+    neighbors: Vec<Vec<usize>>,
+}
+
+/// A hierarchical navigable small world graph of the vectors.
+struct Hnsw {
+    similarity: VectorSimilarity,
+    nodes: Vec<Node>,
+    entry: Option<usize>,
+    /// The state of the generator of the layers of the nodes.
+    seed: u64,
+}
+
+impl Hnsw {
+
+    fn new(similarity: VectorSimilarity) -> Hnsw {
+        Hnsw {
+            similarity,
+            nodes: Vec::new(),
+            entry: None,
+            seed: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    fn distance(&self, vector: &[f32], node: usize) -> f64 {
+        1.0 - self.similarity.score(vector, &self.nodes[node].vector)
+    }
+
+    /// The top layer of a new node, each layer holding `1 / MAX_NEIGHBORS` of the nodes of the one below.
+    fn random_level(&mut self) -> usize {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        let uniform = ((self.seed >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        (-uniform.ln() / (MAX_NEIGHBORS as f64).ln()) as usize
+    }
+
+    fn max_neighbors(layer: usize) -> usize {
+        if layer == 0 { MAX_NEIGHBORS * 2 } else { MAX_NEIGHBORS }
+    }
+
+    /// The `ef` nodes of the layer closest to the vector, reached from the entry points, the closest first.
+    fn search_layer(&self, vector: &[f32], entry_points: &[Candidate], ef: usize, layer: usize) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entry_points.iter().map(|c| c.node).collect();
+        let mut candidates: BinaryHeap<std::cmp::Reverse<Candidate>> = entry_points.iter()
+            .map(|c| std::cmp::Reverse(*c))
+            .collect();
+        let mut found: BinaryHeap<Candidate> = entry_points.iter().copied().collect();
+
+        while let Some(std::cmp::Reverse(candidate)) = candidates.pop() {
+            let furthest = found.peek().map(|c| c.distance).unwrap_or(f64::INFINITY);
+            if candidate.distance > furthest && found.len() >= ef {
+                break;
+            }
+            let neighbors = match self.nodes[candidate.node].neighbors.get(layer) {
+                Some(neighbors) => neighbors,
+                None => continue,
+            };
+            for neighbor in neighbors {
+                if !visited.insert(*neighbor) {
+                    continue;
+                }
+                let distance = self.distance(vector, *neighbor);
+                let furthest = found.peek().map(|c| c.distance).unwrap_or(f64::INFINITY);
+                if found.len() < ef || distance < furthest {
+                    let next = Candidate { distance, node: *neighbor };
+                    candidates.push(std::cmp::Reverse(next));
+                    found.push(next);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+
+        found.into_sorted_vec()
+    }
+
+    /// The entry point of the bottom layer, found by descending the layers above `layer`.
+    fn descend(&self, vector: &[f32], layer: usize) -> Option<Vec<Candidate>> {
+        let entry = self.entry?;
+        let mut entry_points = vec![Candidate { distance: self.distance(vector, entry), node: entry }];
+        let top = self.nodes[entry].neighbors.len() - 1;
+        for current in ((layer + 1)..=top).rev() {
+            entry_points = self.search_layer(vector, &entry_points, 1, current);
+        }
+        Some(entry_points)
+    }
+
+    fn insert(&mut self, id: Bson, vector: Vec<f32>) {
+        let level = self.random_level();
+        let node = self.nodes.len();
+        let mut entry_points = match self.descend(&vector, level) {
+            Some(entry_points) => entry_points,
+            None => {
+                self.nodes.push(Node { id, vector, neighbors: vec![Vec::new(); level + 1] });
+                self.entry = Some(node);
+                return;
+            }
+        };
+        let top = self.nodes[self.entry.unwrap()].neighbors.len() - 1;
+
+        let mut neighbors = vec![Vec::new(); level + 1];
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&vector, &entry_points, EF_CONSTRUCTION, layer);
+            neighbors[layer] = found.iter()
+                .take(Hnsw::max_neighbors(layer))
+                .map(|c| c.node)
+                .collect();
+            entry_points = found;
+        }
+        self.nodes.push(Node { id, vector, neighbors });
+
+        for layer in 0..=level.min(top) {
+            for neighbor in self.nodes[node].neighbors[layer].clone() {
+                self.nodes[neighbor].neighbors[layer].push(node);
+                if self.nodes[neighbor].neighbors[layer].len() > Hnsw::max_neighbors(layer) {
+                    self.prune(neighbor, layer);
+                }
+            }
+        }
+        if level > top {
+            self.entry = Some(node);
+        }
+    }
+
+    /// Keep the closest neighbors of the node on the layer.
+    fn prune(&mut self, node: usize, layer: usize) {
+        let vector = &self.nodes[node].vector;
+        let mut neighbors: Vec<Candidate> = self.nodes[node].neighbors[layer].iter()
+            .map(|neighbor| Candidate { distance: self.distance(vector, *neighbor), node: *neighbor })
+            .collect();
+        neighbors.sort();
+        neighbors.truncate(Hnsw::max_neighbors(layer));
+        self.nodes[node].neighbors[layer] = neighbors.into_iter().map(|c| c.node).collect();
+    }
+
+    /// The ids of the nodes approximately closest to the vector, the closest first.
+    fn search(&self, vector: &[f32], count: usize) -> Vec<&Bson> {
+        let entry_points = match self.descend(vector, 0) {
+            Some(entry_points) => entry_points,
+            None => return Vec::new(),
+        };
+        self.search_layer(vector, &entry_points, count, 0)
+            .into_iter()
+            .map(|c| &self.nodes[c.node].id)
+            .collect()
+    }
+
+}
+
+/// The HNSW graph of the vectors of a `"vector"` index, giving the documents
+/// close to the vector of a `$vectorSearch` without comparing all of them.
+///
+/// Like the fuzzy indexes, the vectors are only added: the documents found are
+/// read again and scored by their current vector, a document deleted since is left out.
+pub(crate) struct CollectionVectorIndex {
+    info: VectorIndexInfo,
+    inner: RwLock<Hnsw>,
+}
+
+impl CollectionVectorIndex {
+
+    fn new(info: &VectorIndexInfo) -> CollectionVectorIndex {
+        CollectionVectorIndex {
+            info: info.clone(),
+            inner: RwLock::new(Hnsw::new(info.similarity)),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn info(&self) -> &VectorIndexInfo {
+        &self.info
+    }
+
+    /// Add the vector of the field of the document.
+    pub(crate) fn insert_doc(&self, doc: &Document) {
+        let vector = match crate::utils::bson::try_get_document_value(doc, &self.info.field)
+            .as_ref()
+            .and_then(Vector::from_bson)
+        {
+            Some(vector) if vector.dimensions() == self.info.dimensions as usize => vector,
+            _ => return,
+        };
+        let id = match doc.get("_id") {
+            Some(id) => id.clone(),
+            None => return,
+        };
+        self.inner.write().unwrap().insert(id, vector.0);
+    }
+
+    /// The ids of the documents whose vectors may be the `count` closest to the vector.
+    pub(crate) fn candidates(&self, vector: &[f32], count: usize) -> Vec<Bson> {
+        let inner = self.inner.read().unwrap();
+        let mut seen = HashSet::new();
+        inner.search(vector, count)
+            .into_iter()
+            .filter(|id| seen.insert(crate::utils::bson::stacked_key([*id]).unwrap_or_default()))
+            .cloned()
+            .collect()
+    }
+
+}
+
+/// The vector indexes of a collection, by their names.
+type CollectionVectorIndexes = HashMap<String, Arc<CollectionVectorIndex>>;
+
+/// The vector indexes of the collections, kept in memory and built when the database is opened.
+#[derive(Clone, Default)]
+pub(crate) struct VectorIndexRegistry {
+    inner: Arc<RwLock<HashMap<String, CollectionVectorIndexes>>>,
+}
+
+impl VectorIndexRegistry {
+
+    pub(crate) fn new() -> VectorIndexRegistry {
+        VectorIndexRegistry::default()
+    }
+
+    pub(crate) fn get(&self, col_name: &str, index_name: &str) -> Option<Arc<CollectionVectorIndex>> {
+        self.inner.read().unwrap().get(col_name)?.get(index_name).cloned()
+    }
+
+    /// The indexes of the collection matching its specification.
+    pub(crate) fn indexes(&self, col_spec: &CollectionSpecification) -> Vec<Arc<CollectionVectorIndex>> {
+        col_spec.vector_indexes.iter()
+            .filter_map(|(name, info)| {
+                self.get(col_spec.name(), name).filter(|index| index.info() == info)
+            })
+            .collect()
+    }
+
+    /// Read the documents of the collection to fill the index.
+    pub(crate) fn build(&self, txn: &TransactionInner, col_name: &str, index_name: &str, info: &VectorIndexInfo) -> Result<()> {
+        let index = CollectionVectorIndex::new(info);
+        let mut cursor = Cursor::new_with_str_prefix(col_name, txn.rocksdb_txn.new_iterator())?;
+        cursor.reset()?;
+        while cursor.has_next() {
+            let doc = bson::from_slice::<Document>(cursor.copy_data()?.as_ref())?;
+            index.insert_doc(&doc);
+            cursor.next()?;
+        }
+        self.inner.write().unwrap()
+            .entry(col_name.to_string())
+            .or_default()
+            .insert(index_name.to_string(), Arc::new(index));
+        Ok(())
+    }
+
+    /// Share the indexes of a collection renamed by an uncommitted transaction with its new name,
+    /// so the writes of the transaction to the new name are added to them.
+    pub(crate) fn alias(&self, old_name: &str, new_name: &str) {
+        let mut inner = self.inner.write().unwrap();
+        if let Some(indexes) = inner.get(old_name).cloned() {
+            inner.insert(new_name.to_string(), indexes);
+        }
+    }
+
+    pub(crate) fn remove_index(&self, col_name: &str, index_name: &str) {
+        if let Some(indexes) = self.inner.write().unwrap().get_mut(col_name) {
+            indexes.remove(index_name);
+        }
+    }
+
+    pub(crate) fn remove(&self, col_name: &str) {
+        self.inner.write().unwrap().remove(col_name);
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{doc, Bson};
+    use crate::coll::collection_info::VectorIndexInfo;
+    use super::{CollectionVectorIndex, VectorSimilarity};
+
+    #[test]
+    fn test_vector_similarity() {
+        assert_eq!(VectorSimilarity::Cosine.score(&[1.0, 0.0], &[2.0, 0.0]), 1.0);
+        assert_eq!(VectorSimilarity::Cosine.score(&[1.0, 0.0], &[0.0, 1.0]), 0.5);
+        assert_eq!(VectorSimilarity::Cosine.score(&[1.0, 0.0], &[-1.0, 0.0]), 0.0);
+        assert_eq!(VectorSimilarity::Euclidean.score(&[0.0, 0.0], &[3.0, 4.0]), 1.0 / 6.0);
+        assert_eq!(VectorSimilarity::DotProduct.score(&[1.0, 0.0], &[0.5, 1.0]), 0.75);
+    }
+
+    #[test]
+    fn test_vector_index_recall() {
+        let info = VectorIndexInfo {
+            field: "v".to_string(),
+            dimensions: 8,
+            similarity: VectorSimilarity::Euclidean,
+        };
+        let index = CollectionVectorIndex::new(&info);
+        // pseudo-random points in the unit cube
+        let vector = |i: i32| -> Vec<f32> {
+            (0..8).map(|d| {
+                // splitmix64
+                let mut x = (i as u64 * 8 + d as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                x ^= x >> 31;
+                (x >> 40) as f32 / (1u64 << 24) as f32
+            }).collect()
+        };
+        for i in 0..2000 {
+            index.insert_doc(&doc! { "_id": i, "v": vector(i).into_iter().map(|x| x as f64).collect::<Vec<f64>>() });
+        }
+
+        let mut hits = 0;
+        for q in 0..20 {
+            let query = vector(5000 + q);
+            let mut exact: Vec<(f64, i32)> = (0..2000)
+                .map(|i| (1.0 - info.similarity.score(&query, &vector(i)), i))
+                .collect();
+            exact.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let found = index.candidates(&query, 50);
+            let found: Vec<&Bson> = found.iter().take(10).collect();
+            hits += exact.iter().take(10).filter(|(_, i)| found.contains(&&Bson::Int32(*i))).count();
+        }
+        assert!(hits >= 180, "recall of {} / 200", hits);
+    }
+
+}
@@ -0,0 +1,302 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use bson::{Bson, Document};
+use crate::coll::collection_info::CollectionSpecification;
+use crate::db::db_inner::DatabaseInner;
+use crate::db::RocksDBIterator;
+use crate::index::IndexHelper;
+use crate::transaction::TransactionInner;
+use crate::utils::str::escape_binary_to_string;
+use crate::Result;
+
+/// The result of [`crate::Database::verify`].
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    /// The number of keys read from the storage.
+    pub keys_scanned: u64,
+    pub collections: Vec<CollectionVerifyReport>,
+    /// The problems found, empty if the database is consistent.
+    pub issues: Vec<VerifyIssue>,
+}
+
+impl VerifyReport {
+
+    #[inline]
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+}
+
+/// The documents and the index entries of a collection checked by [`crate::Database::verify`].
+#[derive(Debug, Clone)]
+pub struct CollectionVerifyReport {
+    pub name: String,
+    pub documents: u64,
+    pub index_entries: u64,
+}
+
+/// A problem found by [`crate::Database::verify`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyIssue {
+    /// The storage failed to read the keys, such as a block with a wrong checksum.
+    Storage(String),
+    /// The keys are not read in increasing order.
+    UnorderedKey { key: Vec<u8> },
+    /// The document can't be decoded.
+    CorruptDocument { collection: String, key: Vec<u8>, error: String },
+    /// The document has no `_id`, or it is stored under the key of another one.
+    MismatchedKey { collection: String, key: Vec<u8> },
+    /// The index has no entry for the document.
+    MissingIndexEntry { collection: String, index: String, id: Bson },
+    /// The entry of the index doesn't belong to a document.
+    DanglingIndexEntry { collection: String, index: String, key: Vec<u8> },
+    /// Several documents have the same value in a unique index.
+    DuplicateKey { collection: String, index: String, value: Bson },
+}
+
+impl fmt::Display for VerifyIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let escape = |key: &[u8]| escape_binary_to_string(key).unwrap_or_default();
+        match self {
+            VerifyIssue::Storage(err) => write!(f, "storage error: {}", err),
+            VerifyIssue::UnorderedKey { key } => write!(f, "unordered key: {}", escape(key)),
+            VerifyIssue::CorruptDocument { collection, key, error } => {
+                write!(f, "corrupt document {} in {}: {}", escape(key), collection, error)
+            }
+            VerifyIssue::MismatchedKey { collection, key } => {
+                write!(f, "document {} in {} doesn't match its key", escape(key), collection)
+            }
+            VerifyIssue::MissingIndexEntry { collection, index, id } => {
+                write!(f, "document {} in {} is missing from index {}", id, collection, index)
+            }
+            VerifyIssue::DanglingIndexEntry { collection, index, key } => {
+                write!(f, "entry {} of index {} in {} has no document", escape(key), index, collection)
+            }
+            VerifyIssue::DuplicateKey { collection, index, value } => {
+                write!(f, "value {} is duplicated in unique index {} in {}", value, index, collection)
+            }
+        }
+    }
+}
+
+/// Read every key of the storage, then check the documents of each collection
+/// against their keys and the entries of their indexes.
+pub(crate) fn verify_database(db: &DatabaseInner, txn: &TransactionInner) -> Result<VerifyReport> {
+    let mut report = VerifyReport::default();
+    scan_storage(txn, &mut report);
+    if !report.is_ok() {
+        // the collections can't be read reliably
+        return Ok(report);
+    }
+
+    for name in db.list_collection_names_with_session(txn)? {
+        if let Some(col_spec) = db.get_collection_meta_by_name_advanced_auto(&name, false, txn)? {
+            let collection = verify_collection(txn, &col_spec, &mut report.issues)?;
+            report.collections.push(collection);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Read all the keys in order, the iterator checks the checksums of the blocks it reads.
+fn scan_storage(txn: &TransactionInner, report: &mut VerifyReport) {
+    let iter = txn.rocksdb_txn.new_iterator();
+    iter.seek_to_first();
+    let mut previous: Option<Vec<u8>> = None;
+    while iter.valid() {
+        let key = match iter.copy_key() {
+            Ok(key) => key,
+            Err(err) => {
+                report.issues.push(VerifyIssue::Storage(err.to_string()));
+                return;
+            }
+        };
+        if previous.as_ref().is_some_and(|previous| previous >= &key) {
+            report.issues.push(VerifyIssue::UnorderedKey { key: key.clone() });
+        }
+        report.keys_scanned += 1;
+        previous = Some(key);
+        iter.next();
+    }
+    if let Err(err) = iter.error() {
+        report.issues.push(VerifyIssue::Storage(err.to_string()));
+    }
+}
+
+/// Call `f` with the keys and the values starting with `prefix`.
+fn scan_prefix<F>(txn: &TransactionInner, prefix: &[u8], mut f: F) -> Result<()>
+where
+    F: FnMut(&RocksDBIterator, Vec<u8>) -> Result<()>,
+{
+    let iter = txn.rocksdb_txn.new_iterator();
+    iter.seek(prefix);
+    while iter.valid() {
+        let key = iter.copy_key()?;
+        if !key.starts_with(prefix) {
+            break;
+        }
+        f(&iter, key)?;
+        iter.next();
+    }
+    iter.error()
+}
+
+fn verify_collection(
+    txn: &TransactionInner,
+    col_spec: &CollectionSpecification,
+    issues: &mut Vec<VerifyIssue>,
+) -> Result<CollectionVerifyReport> {
+    let col_name = col_spec.name();
+    let mut result = CollectionVerifyReport {
+        name: col_name.to_string(),
+        documents: 0,
+        index_entries: 0,
+    };
+
+    // the keys the entries of each index should have
+    let mut expected = col_spec.indexes.keys()
+        .map(|index_name| (index_name.clone(), BTreeMap::<Vec<u8>, Bson>::new()))
+        .collect::<HashMap<String, BTreeMap<Vec<u8>, Bson>>>();
+    // the values found in the unique indexes
+    let mut unique_values = HashSet::<Vec<u8>>::new();
+
+    let b_col_name = Bson::String(col_name.to_string());
+    let prefix = crate::utils::bson::stacked_key([&b_col_name])?;
+    scan_prefix(txn, &prefix, |iter, key| {
+        result.documents += 1;
+        let doc = match bson::from_slice::<Document>(iter.copy_data()?.as_ref()) {
+            Ok(doc) => doc,
+            Err(err) => {
+                issues.push(VerifyIssue::CorruptDocument {
+                    collection: col_name.to_string(),
+                    key,
+                    error: err.to_string(),
+                });
+                return Ok(());
+            }
+        };
+        let pkey = match doc.get("_id") {
+            Some(pkey) => pkey,
+            None => {
+                issues.push(VerifyIssue::MismatchedKey { collection: col_name.to_string(), key });
+                return Ok(());
+            }
+        };
+        if crate::utils::bson::stacked_key([&b_col_name, pkey])? != key {
+            issues.push(VerifyIssue::MismatchedKey { collection: col_name.to_string(), key });
+        }
+        for (index_name, index_info) in &col_spec.indexes {
+            for values in IndexHelper::index_values(&doc, index_info) {
+                if index_info.is_unique() {
+                    let value_key = IndexHelper::make_index_key(col_name, index_name, &values, None)?;
+                    if !unique_values.insert(value_key) {
+                        issues.push(VerifyIssue::DuplicateKey {
+                            collection: col_name.to_string(),
+                            index: index_name.clone(),
+                            value: IndexHelper::entry_value(&values),
+                        });
+                    }
+                }
+                let index_key = IndexHelper::make_index_key(col_name, index_name, &values, Some(pkey))?;
+                expected.get_mut(index_name).unwrap().insert(index_key, pkey.clone());
+            }
+        }
+        Ok(())
+    })?;
+
+    for index_name in col_spec.indexes.keys() {
+        let mut expected_keys = expected.remove(index_name).unwrap_or_default();
+        let index_prefix = IndexHelper::index_prefix(col_name, index_name)?;
+        scan_prefix(txn, &index_prefix, |_, key| {
+            result.index_entries += 1;
+            if expected_keys.remove(&key).is_none() {
+                issues.push(VerifyIssue::DanglingIndexEntry {
+                    collection: col_name.to_string(),
+                    index: index_name.clone(),
+                    key,
+                });
+            }
+            Ok(())
+        })?;
+        for id in expected_keys.into_values() {
+            issues.push(VerifyIssue::MissingIndexEntry {
+                collection: col_name.to_string(),
+                index: index_name.clone(),
+                id,
+            });
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{doc, Bson};
+    use crate::db::db_inner::DatabaseInner;
+    use crate::index::{IndexHelper, IndexModel, IndexOptions};
+    use crate::test_utils::mk_db_path;
+    use crate::Config;
+    use super::VerifyIssue;
+
+    #[test]
+    fn test_verify_inconsistencies() {
+        let db = DatabaseInner::open_file(&mk_db_path("test-verify-inconsistencies"), Config::default()).unwrap();
+        let txn = db.start_transaction().unwrap();
+        db.create_index("users", IndexModel {
+            keys: doc! { "email": 1 },
+            options: Some(IndexOptions::builder().unique(true).build()),
+        }, &txn).unwrap();
+        for i in 0..5 {
+            db.insert_one("users", doc! { "_id": i, "email": format!("u{}@example.com", i) }, &txn).unwrap();
+        }
+        txn.commit().unwrap();
+
+        let report = db.verify().unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.collections[0].documents, 5);
+        assert_eq!(report.collections[0].index_entries, 5);
+
+        // written behind the back of the indexes
+        let txn = db.start_transaction().unwrap();
+        let removed = IndexHelper::make_index_key("users", "email_1", &["u1@example.com".into()], Some(&Bson::Int32(1))).unwrap();
+        txn.delete(&removed).unwrap();
+        let dangling = IndexHelper::make_index_key("users", "email_1", &["ghost@example.com".into()], Some(&Bson::Int32(9))).unwrap();
+        txn.put(&dangling, &[bson::spec::ElementType::Null as u8]).unwrap();
+        let key = crate::utils::bson::stacked_key([&Bson::String("users".into()), &Bson::Int32(7)]).unwrap();
+        let misplaced = bson::to_vec(&doc! { "_id": 8, "email": "u0@example.com" }).unwrap();
+        txn.put(&key, &misplaced).unwrap();
+        txn.commit().unwrap();
+
+        let report = db.verify().unwrap();
+        assert_eq!(report.issues, vec![
+            VerifyIssue::MismatchedKey { collection: "users".into(), key },
+            VerifyIssue::DuplicateKey {
+                collection: "users".into(),
+                index: "email_1".into(),
+                value: "u0@example.com".into(),
+            },
+            VerifyIssue::DanglingIndexEntry { collection: "users".into(), index: "email_1".into(), key: dangling },
+            VerifyIssue::MissingIndexEntry { collection: "users".into(), index: "email_1".into(), id: Bson::Int32(8) },
+            VerifyIssue::MissingIndexEntry { collection: "users".into(), index: "email_1".into(), id: Bson::Int32(1) },
+        ]);
+    }
+
+}
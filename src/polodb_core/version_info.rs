@@ -0,0 +1,468 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bson::{Binary, Bson};
+use bson::spec::BinarySubtype;
+use serde::{Deserialize, Serialize};
+use crate::errors::{DuplicateKeyError, VersionMismatchError};
+use crate::db::RocksDBWrapper;
+use crate::transaction::TransactionInner;
+use crate::utils::bson::{prefix_upper_bound, split_legacy_stacked_keys, stacked_key};
+use crate::{Config, Database, Error, Result, StorageLayout};
+
+const DB_INFO_KEY: &str = "$DB_INFO";
+const MIGRATION_KEY: &str = "$DB_MIGRATION";
+/// The prefix of the keys staged by a migration under their new keys.
+const MIGRATION_PREFIX: &str = "$MIGRATION";
+
+/// The number of keys moved by each transaction of a migration.
+const MIGRATION_BATCH_SIZE: usize = 1024;
+
+/// The version of the layout of the keys and the documents on the disk.
+///
+/// The format 2 orders the dates before 1970 first, and gives the equal decimals,
+/// such as 1.0 and 1.00, the same key.
+pub(crate) const FORMAT_VERSION: u32 = 2;
+
+/// The information recorded when the database file was created,
+/// returned by [`crate::Database::version_info`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionInfo {
+    /// The version of the crate that created the file, `None` if it was
+    /// created by a version before the information was recorded.
+    pub created_by: Option<String>,
+    /// The version of the on-disk format, the keys of a file in an older format
+    /// are migrated when it is opened.
+    pub format_version: u32,
+    /// Whether the storage compresses the blocks it writes, it follows the build
+    /// of the storage library, so it is brought up to date on every opening.
+    pub compression: bool,
+    /// The page size configured when the file was created.
+    pub page_size: u32,
+    /// The layout of the files of the database.
+    #[serde(default)]
+    pub layout: StorageLayout,
+}
+
+fn db_info_key() -> Result<Vec<u8>> {
+    stacked_key([&Bson::String(DB_INFO_KEY.to_string())])
+}
+
+/// The progress of a migration of the keys, recorded by each of its transactions,
+/// an interrupted migration resumes from it when the file is opened again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MigrationProgress {
+    /// The last key read in the format 1, the keys up to it are staged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_key: Option<Binary>,
+    /// All the keys are staged, they are moved to their new keys.
+    #[serde(default)]
+    staged: bool,
+}
+
+/// Read the information of the database, recording it first if the file has none.
+///
+/// A file with keys but without the information was created by an older version,
+/// so its creator is unknown, and its keys are in the format 1.
+pub(crate) fn load_or_init(
+    txn: &TransactionInner,
+    config: &Config,
+    storage: &RocksDBWrapper,
+) -> Result<VersionInfo> {
+    let key = db_info_key()?;
+    if let Some(buf) = txn.rocksdb_txn.get(&key)? {
+        let mut info: VersionInfo = bson::from_slice(&buf)?;
+        if info.format_version > FORMAT_VERSION {
+            return Err(Error::VersionMismatch(Box::new(VersionMismatchError {
+                actual_version: info.format_version.to_be_bytes(),
+                expect_version: FORMAT_VERSION.to_be_bytes(),
+            })));
+        }
+        let mut changed = false;
+        if info.format_version < FORMAT_VERSION {
+            migrate_keys(txn, storage)?;
+            info.format_version = FORMAT_VERSION;
+            changed = true;
+        }
+        if info.compression != storage.compression() {
+            info.compression = storage.compression();
+            changed = true;
+        }
+        if changed {
+            txn.put(&key, &bson::to_vec(&info)?)?;
+            txn.commit()?;
+        }
+        return Ok(info);
+    }
+
+    let iter = txn.rocksdb_txn.new_iterator();
+    iter.seek_to_first();
+    let is_new = !iter.valid();
+    iter.error()?;
+    if !is_new {
+        migrate_keys(txn, storage)?;
+    }
+
+    let info = VersionInfo {
+        created_by: if is_new {
+            Some(Database::get_version().to_string())
+        } else {
+            None
+        },
+        format_version: FORMAT_VERSION,
+        compression: storage.compression(),
+        page_size: config.lsm_page_size,
+        layout: storage.layout(),
+    };
+    txn.put(&key, &bson::to_vec(&info)?)?;
+    txn.commit()?;
+    Ok(info)
+}
+
+/// Move the keys holding a date or a decimal from their encoding in the format 1
+/// to the current one: the keys of the documents, and the entries of the indexes,
+/// which are ordered by the new keys.
+///
+/// The keys are moved by batches, each one committed with the progress of the migration:
+/// they are all staged under their new keys first, as a new key may be the old key of
+/// another one, then moved to their new keys. The progress is removed by `txn`, with
+/// the format of the file, so a migration stopped at any point resumes where it stopped.
+fn migrate_keys(txn: &TransactionInner, storage: &RocksDBWrapper) -> Result<()> {
+    let progress_key = stacked_key([&Bson::String(MIGRATION_KEY.to_string())])?;
+    let staging_prefix = stacked_key([&Bson::String(MIGRATION_PREFIX.to_string())])?;
+    let mut progress = match txn.rocksdb_txn.get(&progress_key)? {
+        Some(buf) => bson::from_slice::<MigrationProgress>(&buf)?,
+        None => MigrationProgress::default(),
+    };
+
+    while !progress.staged {
+        let batch = TransactionInner::new(storage.begin_transaction()?);
+        let last_key = progress.last_key.take().map(|key| key.bytes);
+        let (last_key, staged) = stage_keys(&batch, &staging_prefix, last_key, MIGRATION_BATCH_SIZE)?;
+        progress.last_key = last_key.map(|bytes| Binary { subtype: BinarySubtype::Generic, bytes });
+        progress.staged = staged;
+        batch.put(&progress_key, &bson::to_vec(&progress)?)?;
+        batch.commit()?;
+    }
+
+    loop {
+        let batch = TransactionInner::new(storage.begin_transaction()?);
+        let moved = move_staged_keys(&batch, &staging_prefix, MIGRATION_BATCH_SIZE)?;
+        batch.commit()?;
+        if moved < MIGRATION_BATCH_SIZE {
+            break;
+        }
+    }
+
+    txn.delete(&progress_key)
+}
+
+/// Stage the next `batch_size` keys to move after `last_key` under their new keys,
+/// return the last key read, and whether all the keys are staged.
+fn stage_keys(
+    txn: &TransactionInner,
+    staging_prefix: &[u8],
+    last_key: Option<Vec<u8>>,
+    batch_size: usize,
+) -> Result<(Option<Vec<u8>>, bool)> {
+    let mut moved = Vec::new();
+    let mut last_key = last_key;
+    let staged = {
+        let iter = txn.rocksdb_txn.new_iterator();
+        match &last_key {
+            Some(key) => {
+                iter.seek(key);
+                if iter.valid() && iter.copy_key()? == *key {
+                    iter.next();
+                }
+            }
+            None => iter.seek_to_first(),
+        }
+        while iter.valid() && moved.len() < batch_size {
+            let old_key = iter.copy_key()?;
+            if old_key.starts_with(staging_prefix) {
+                iter.seek(&prefix_upper_bound(staging_prefix));
+                continue;
+            }
+            if let Ok(values) = split_legacy_stacked_keys(&old_key) {
+                if values.iter().any(|value| matches!(value, Bson::DateTime(_) | Bson::Decimal128(_))) {
+                    let new_key = stacked_key(&values)?;
+                    if new_key != old_key {
+                        moved.push((old_key.clone(), new_key, values, iter.copy_data()?));
+                    }
+                }
+            }
+            last_key = Some(old_key);
+            iter.next();
+        }
+        iter.error()?;
+        !iter.valid()
+    };
+
+    for (old_key, new_key, values, data) in &moved {
+        let mut staged_key = staging_prefix.to_vec();
+        staged_key.extend_from_slice(new_key);
+        // two documents whose ids are equal decimals, such as 1.0 and 1.00, would have
+        // the same key, the entries of the indexes end with the ids, they collide with them
+        if let [Bson::String(ns), id] = values.as_slice() {
+            if txn.rocksdb_txn.get(&staged_key)?.is_some() {
+                return Err(DuplicateKeyError {
+                    name: "_id".to_string(),
+                    key: id.to_string(),
+                    ns: ns.clone(),
+                }.into());
+            }
+        }
+        txn.delete(old_key)?;
+        txn.put(&staged_key, data)?;
+    }
+    Ok((last_key, staged))
+}
+
+/// Move the next `batch_size` staged keys to their new keys, return the number of keys moved.
+fn move_staged_keys(txn: &TransactionInner, staging_prefix: &[u8], batch_size: usize) -> Result<usize> {
+    let mut staged = Vec::new();
+    {
+        let iter = txn.rocksdb_txn.new_iterator();
+        iter.seek(staging_prefix);
+        while iter.valid() && staged.len() < batch_size {
+            let key = iter.copy_key()?;
+            if !key.starts_with(staging_prefix) {
+                break;
+            }
+            staged.push((key, iter.copy_data()?));
+            iter.next();
+        }
+        iter.error()?;
+    }
+
+    for (staged_key, data) in &staged {
+        txn.delete(staged_key)?;
+        txn.put(&staged_key[staging_prefix.len()..], data)?;
+    }
+    Ok(staged.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{doc, Binary, Bson, DateTime, Document};
+    use bson::spec::{BinarySubtype, ElementType};
+    use crate::test_utils::mk_db_path;
+    use crate::utils::bson::{split_stacked_keys, stacked_key, stacked_key_bytes};
+    use crate::transaction::TransactionInner;
+    use crate::{CollectionT, Database, IndexModel};
+    use super::{db_info_key, move_staged_keys, stage_keys, MigrationProgress, FORMAT_VERSION, MIGRATION_KEY, MIGRATION_PREFIX};
+
+    fn decimal(s: &str) -> Bson {
+        Bson::Decimal128(s.parse().unwrap())
+    }
+
+    /// The key in the format 1.
+    fn legacy_key(values: &[Bson]) -> Vec<u8> {
+        let mut key = Vec::new();
+        for value in values {
+            match value {
+                Bson::DateTime(dt) => {
+                    key.push(ElementType::DateTime as u8);
+                    key.extend_from_slice(&dt.timestamp_millis().to_be_bytes());
+                }
+                Bson::Decimal128(dcl) => {
+                    key.push(ElementType::Decimal128 as u8);
+                    key.extend_from_slice(&dcl.bytes());
+                }
+                _ => stacked_key_bytes(&mut key, value).unwrap(),
+            }
+        }
+        key
+    }
+
+    /// Rewrite all the keys of the database in the format 1, in the returned transaction.
+    fn write_legacy_keys(db: &Database) -> TransactionInner {
+        let txn = db.inner().start_transaction().unwrap();
+        let mut keys = Vec::new();
+        {
+            let iter = txn.rocksdb_txn.new_iterator();
+            iter.seek_to_first();
+            while iter.valid() {
+                keys.push((iter.copy_key().unwrap(), iter.copy_data().unwrap()));
+                iter.next();
+            }
+        }
+        for (key, data) in keys {
+            txn.delete(&key).unwrap();
+            txn.put(&legacy_key(&split_stacked_keys(&key).unwrap()), &data).unwrap();
+        }
+        txn
+    }
+
+    #[test]
+    fn test_migrate_format_1() {
+        let db_path = mk_db_path("test-migrate-format-1");
+        {
+            let db = Database::open_path(db_path.as_path()).unwrap();
+            let prices = db.collection::<Document>("prices");
+            prices.create_index(IndexModel {
+                keys: doc! { "amount": 1 },
+                options: None,
+            }).unwrap();
+            prices.insert_many(vec![
+                doc! { "_id": decimal("1.50"), "amount": decimal("-10") },
+                doc! { "_id": decimal("-2"), "amount": decimal("2.5") },
+                doc! { "_id": 3, "amount": decimal("0.1") },
+            ]).unwrap();
+
+            // as written in the format 1
+            let txn = write_legacy_keys(&db);
+            let mut info = db.version_info();
+            info.format_version = 1;
+            txn.put(&db_info_key().unwrap(), &bson::to_vec(&info).unwrap()).unwrap();
+            txn.commit().unwrap();
+        }
+
+        let db = Database::open_path(db_path.as_path()).unwrap();
+        assert_eq!(db.version_info().format_version, FORMAT_VERSION);
+
+        let prices = db.collection::<Document>("prices");
+        assert!(prices.find_by_id(decimal("1.5")).unwrap().is_some());
+        assert!(prices.find_by_id(decimal("-2")).unwrap().is_some());
+        // the negative decimals are first in the index
+        let ids = prices.find(doc! { "amount": { "$lt": 1.0 } })
+            .sort(doc! { "amount": 1 })
+            .run()
+            .unwrap()
+            .map(|doc| doc.unwrap().get("_id").unwrap().clone())
+            .collect::<Vec<Bson>>();
+        assert_eq!(ids, vec![decimal("1.50"), Bson::Int32(3)]);
+        let explain = prices.find(doc! { "amount": { "$lt": 1.0 } })
+            .sort(doc! { "amount": 1 })
+            .explain()
+            .unwrap();
+        assert_eq!(explain.get_document("winningPlan").unwrap().get_str("stage").unwrap(), "IXSCAN");
+
+        // migrated once
+        drop(prices);
+        drop(db);
+        let db = Database::open_path(db_path.as_path()).unwrap();
+        assert_eq!(db.collection::<Document>("prices").count_documents().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_migrate_file_without_information() {
+        let db_path = mk_db_path("test-migrate-file-without-information");
+        {
+            let db = Database::open_path(db_path.as_path()).unwrap();
+            let events = db.collection::<Document>("events");
+            events.create_index(IndexModel {
+                keys: doc! { "at": 1 },
+                options: None,
+            }).unwrap();
+            events.insert_many(vec![
+                doc! { "_id": decimal("1.50"), "at": DateTime::from_millis(-1000) },
+                doc! { "_id": decimal("-2"), "at": DateTime::from_millis(1000) },
+                doc! { "_id": DateTime::from_millis(-5), "at": DateTime::from_millis(0) },
+            ]).unwrap();
+
+            // as written by a version before the information of the file
+            let txn = write_legacy_keys(&db);
+            txn.delete(&db_info_key().unwrap()).unwrap();
+            txn.commit().unwrap();
+        }
+
+        let db = Database::open_path(db_path.as_path()).unwrap();
+        assert_eq!(db.version_info().format_version, FORMAT_VERSION);
+        assert_eq!(db.version_info().created_by, None);
+
+        let events = db.collection::<Document>("events");
+        assert!(events.find_by_id(decimal("1.5")).unwrap().is_some());
+        assert!(events.find_by_id(decimal("-2")).unwrap().is_some());
+        assert!(events.find_by_id(DateTime::from_millis(-5)).unwrap().is_some());
+        // the dates before 1970 are first in the index
+        let ids = events.find(doc! { "at": { "$lt": DateTime::from_millis(500) } })
+            .sort(doc! { "at": 1 })
+            .run()
+            .unwrap()
+            .map(|doc| doc.unwrap().get("_id").unwrap().clone())
+            .collect::<Vec<Bson>>();
+        assert_eq!(ids, vec![decimal("1.50"), Bson::DateTime(DateTime::from_millis(-5))]);
+        let explain = events.find(doc! { "at": { "$lt": DateTime::from_millis(500) } })
+            .sort(doc! { "at": 1 })
+            .explain()
+            .unwrap();
+        assert_eq!(explain.get_document("winningPlan").unwrap().get_str("stage").unwrap(), "IXSCAN");
+
+        // migrated once
+        drop(events);
+        drop(db);
+        let db = Database::open_path(db_path.as_path()).unwrap();
+        assert_eq!(db.collection::<Document>("events").count_documents().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_resume_migration() {
+        // stopped while the keys are staged, and while they are moved to their new keys
+        for (name, staged_batches, moved_batches) in [("staging", 1, 0), ("moving", 3, 1)] {
+            let db_path = mk_db_path(&format!("test-resume-migration-{}", name));
+            {
+                let db = Database::open_path(db_path.as_path()).unwrap();
+                let prices = db.collection::<Document>("prices");
+                prices.create_index(IndexModel {
+                    keys: doc! { "amount": 1 },
+                    options: None,
+                }).unwrap();
+                prices.insert_many((0..10).map(|i| doc! {
+                    "_id": decimal(&format!("{}.50", i)),
+                    "amount": decimal(&format!("-{}", i)),
+                })).unwrap();
+                let txn = write_legacy_keys(&db);
+                txn.delete(&db_info_key().unwrap()).unwrap();
+                txn.commit().unwrap();
+
+                let progress_key = stacked_key([&Bson::String(MIGRATION_KEY.to_string())]).unwrap();
+                let staging_prefix = stacked_key([&Bson::String(MIGRATION_PREFIX.to_string())]).unwrap();
+                let mut last_key = None;
+                let mut staged = false;
+                for _ in 0..staged_batches {
+                    let txn = db.inner().start_transaction().unwrap();
+                    (last_key, staged) = stage_keys(&txn, &staging_prefix, last_key, 8).unwrap();
+                    let progress = MigrationProgress {
+                        last_key: last_key.clone().map(|bytes| Binary { subtype: BinarySubtype::Generic, bytes }),
+                        staged,
+                    };
+                    txn.put(&progress_key, &bson::to_vec(&progress).unwrap()).unwrap();
+                    txn.commit().unwrap();
+                }
+                assert_eq!(staged, staged_batches > 1);
+                for _ in 0..moved_batches {
+                    let txn = db.inner().start_transaction().unwrap();
+                    assert_eq!(move_staged_keys(&txn, &staging_prefix, 8).unwrap(), 8);
+                    txn.commit().unwrap();
+                }
+            }
+
+            let db = Database::open_path(db_path.as_path()).unwrap();
+            assert_eq!(db.version_info().format_version, FORMAT_VERSION);
+            let prices = db.collection::<Document>("prices");
+            assert_eq!(prices.count_documents().unwrap(), 10);
+            assert!(prices.find_by_id(decimal("3.5")).unwrap().is_some());
+            let ids = prices.find(doc! { "amount": { "$lt": decimal("-7.5") } })
+                .run()
+                .unwrap()
+                .map(|doc| doc.unwrap().get("_id").unwrap().clone())
+                .collect::<Vec<Bson>>();
+            assert_eq!(ids, vec![decimal("9.50"), decimal("8.50")]);
+            assert!(db.verify().unwrap().is_ok());
+        }
+    }
+
+}
@@ -0,0 +1,165 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use bson::{doc, Bson, Document};
+use crate::coll::collection_info::{MaterializedViewInfo, ViewInfo};
+
+/// The stages computing the result of a source document from this document alone.
+const DOCUMENT_STAGES: [&str; 2] = ["$addFields", "$unset"];
+
+impl MaterializedViewInfo {
+
+    /// Whether each document of the view is computed from the source document with the same `_id`:
+    /// the pipeline is an optional `$match` followed by stages transforming the documents one by one.
+    /// The other views are computed again from all the source documents.
+    pub(crate) fn is_incremental(&self) -> bool {
+        self.pipeline.iter().enumerate().all(|(i, stage)| {
+            stage.len() == 1 && stage.keys().all(|key| {
+                DOCUMENT_STAGES.contains(&key.as_str()) || (i == 0 && key == "$match")
+            })
+        })
+    }
+
+    /// The pipeline of an incremental view computing the result of the source document `id`.
+    pub(crate) fn document_pipeline(&self, id: &Bson) -> Vec<Document> {
+        let mut pipeline = self.pipeline.clone();
+        let by_id = doc! { "_id": id.clone() };
+        match pipeline.first_mut().and_then(|stage| stage.get_mut("$match")) {
+            Some(condition) => {
+                *condition = Bson::Document(doc! { "$and": [condition.clone(), by_id] });
+            }
+            None => pipeline.insert(0, doc! { "$match": by_id }),
+        }
+        pipeline
+    }
+
+}
+
+impl ViewInfo {
+
+    /// The pipeline run on the source when the view is queried by `pipeline`:
+    /// the stages of the view, followed by the stages of the query. The residual filter
+    /// of the query is checked after its `$match`, on the documents of the view.
+    pub(crate) fn compose(&self, pipeline: Vec<Document>, has_filter_fn: bool) -> Vec<Document> {
+        let mut result = self.pipeline.clone();
+        let mut stages = pipeline.into_iter().peekable();
+        if let Some(stage) = stages.next_if(|stage| stage.len() == 1 && stage.contains_key("$match")) {
+            if !matches!(stage.get("$match"), Some(Bson::Document(query)) if query.is_empty()) {
+                result.push(stage);
+            }
+        }
+        if has_filter_fn {
+            result.push(doc! { "$filterFn": Bson::Null });
+        }
+        result.extend(stages);
+        // only the first $match reads the source with the plan of the query
+        if !result.first().is_some_and(|stage| stage.contains_key("$match")) {
+            result.insert(0, doc! { "$match": {} });
+        }
+        result
+    }
+
+}
+
+/// The names and the definitions of the views computed from a collection.
+type SourceViews = Vec<(String, Arc<MaterializedViewInfo>)>;
+
+/// The materialized views of the collections, by the name of their source collection,
+/// kept in memory and loaded when the database is opened.
+#[derive(Clone, Default)]
+pub(crate) struct ViewRegistry {
+    inner: Arc<RwLock<HashMap<String, SourceViews>>>,
+}
+
+impl ViewRegistry {
+
+    pub(crate) fn new() -> ViewRegistry {
+        ViewRegistry::default()
+    }
+
+    /// The names and the definitions of the views computed from the collection.
+    pub(crate) fn views_of(&self, source: &str) -> SourceViews {
+        match self.inner.read().unwrap().get(source) {
+            Some(views) => views.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    pub(crate) fn register(&self, name: &str, info: MaterializedViewInfo) {
+        self.unregister(name);
+        self.inner.write().unwrap()
+            .entry(info.source.clone())
+            .or_default()
+            .push((name.to_string(), Arc::new(info)));
+    }
+
+    pub(crate) fn unregister(&self, name: &str) {
+        let mut inner = self.inner.write().unwrap();
+        for views in inner.values_mut() {
+            views.retain(|(view, _)| view != name);
+        }
+        inner.retain(|_, views| !views.is_empty());
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+    use crate::coll::collection_info::{MaterializedViewInfo, ViewInfo};
+
+    fn view(pipeline: Vec<bson::Document>) -> MaterializedViewInfo {
+        MaterializedViewInfo {
+            source: "orders".to_string(),
+            pipeline,
+        }
+    }
+
+    #[test]
+    fn test_incremental_views() {
+        assert!(view(vec![]).is_incremental());
+        assert!(view(vec![
+            doc! { "$match": { "status": "paid" } },
+            doc! { "$addFields": { "rebate": { "$abs": "$discount" } } },
+            doc! { "$unset": "card" },
+        ]).is_incremental());
+        assert!(!view(vec![doc! { "$addFields": {} }, doc! { "$match": {} }]).is_incremental());
+        assert!(!view(vec![doc! { "$group": { "_id": "$status" } }]).is_incremental());
+
+        let pipeline = view(vec![doc! { "$match": { "status": "paid" } }]).document_pipeline(&1.into());
+        assert_eq!(pipeline, vec![doc! { "$match": { "$and": [{ "status": "paid" }, { "_id": 1 }] } }]);
+        let pipeline = view(vec![doc! { "$unset": "card" }]).document_pipeline(&1.into());
+        assert_eq!(pipeline, vec![doc! { "$match": { "_id": 1 } }, doc! { "$unset": "card" }]);
+    }
+
+    #[test]
+    fn test_compose_view_pipeline() {
+        let view = ViewInfo {
+            source: "orders".to_string(),
+            pipeline: vec![doc! { "$unset": "card" }],
+        };
+        let pipeline = view.compose(vec![doc! { "$match": { "status": "paid" } }, doc! { "$limit": 1 }], true);
+        assert_eq!(pipeline, vec![
+            doc! { "$match": {} },
+            doc! { "$unset": "card" },
+            doc! { "$match": { "status": "paid" } },
+            doc! { "$filterFn": null },
+            doc! { "$limit": 1 },
+        ]);
+        assert_eq!(view.compose(vec![doc! { "$match": {} }], false), vec![doc! { "$match": {} }, doc! { "$unset": "card" }]);
+    }
+
+}
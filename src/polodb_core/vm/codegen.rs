@@ -14,19 +14,23 @@
 
 
 use super::label::{JumpTableRecord, Label, LabelSlot};
-use crate::coll::collection_info::CollectionSpecification;
+use crate::coll::collection_info::{CollectionSpecification, IndexInfo};
 use crate::errors::{mk_invalid_query_field};
-use crate::index::INDEX_PREFIX;
+use crate::index::{IndexBounds, IndexHelper, IndexOrder, INDEX_PREFIX};
 use crate::vm::op::DbOp;
-use crate::vm::subprogram::SubProgramIndexItem;
-use crate::vm::SubProgram;
+use crate::vm::subprogram::{AccessPath, SubProgramIndexItem};
+use crate::vm::{QueryPlan, SubProgram};
+use crate::options::Collation;
 use crate::{Error, Result};
 use bson::spec::{BinarySubtype, ElementType};
 use bson::{Array, Binary, Bson, Document};
 use crate::vm::aggregation_codegen_context::{AggregationCodeGenContext, PipelineItem};
 use crate::vm::global_variable::{GlobalVariable, GlobalVariableSlot};
 use crate::vm::operators::OpRegistry;
-use crate::vm::update_operators::{IncOperator, MaxOperator, MinOperator, MulOperator, PopOperator, PushOperator, RenameOperator, SetOperator, UnsetOperator, UpdateOperator};
+use crate::vm::update_operators::{
+    AddToSetOperator, IncOperator, MaxOperator, MinOperator, MulOperator, PopOperator, PullOperator,
+    PushOperator, RenameOperator, SetOperator, UnsetOperator, UpdateOperator,
+};
 use crate::vm::vm_add_fields::VmFuncAddFields;
 use crate::vm::vm_count::VmFuncCount;
 use crate::vm::vm_external_func::VmExternalFunc;
@@ -35,15 +39,30 @@ use crate::vm::vm_limit::VmFuncLimit;
 use crate::vm::vm_skip::VmFuncSkip;
 use crate::vm::vm_sort::VmFuncSort;
 use crate::vm::vm_unset::VmFuncUnset;
+use crate::vm::vm_text::VmFuncText;
+use crate::vm::vm_geo_near::VmFuncGeoNear;
+use crate::vm::vm_lookup::VmFuncLookup;
+use crate::vm::vm_project::VmFuncProject;
+use crate::vm::vm_unwind::VmFuncUnwind;
+use crate::vm::vm_filter_fn::{ResidualFilter, VmFuncFilterFn};
+use crate::fuzzy::FuzzySearch;
+use crate::elem_match::ElemMatch;
 
 const JUMP_TABLE_DEFAULT_SIZE: usize = 8;
 const PATH_DEFAULT_SIZE: usize = 8;
 
+/// Emitted when the scan ends, before the cursor is closed.
+type BeforeClose = Box<dyn FnOnce(&mut Codegen) -> Result<()>>;
+
 pub(super) struct Codegen {
     program: Box<SubProgram>,
     jump_table: Vec<JumpTableRecord>,
     skip_annotation: bool,
     is_write: bool,
+    hint: Option<String>,
+    index: Option<String>,
+    collection_scan: bool,
+    residual_filter: Option<ResidualFilter>,
     paths: Vec<String>,
     op_registry: OpRegistry,
 }
@@ -55,11 +74,97 @@ impl Codegen {
             jump_table: Vec::with_capacity(JUMP_TABLE_DEFAULT_SIZE),
             skip_annotation,
             is_write,
+            hint: None,
+            index: None,
+            collection_scan: false,
+            residual_filter: None,
             paths: Vec::with_capacity(PATH_DEFAULT_SIZE),
             op_registry: OpRegistry,
         }
     }
 
+    pub(super) fn set_plan(&mut self, plan: &QueryPlan) {
+        self.hint = plan.hint.clone();
+        self.index = plan.index.clone();
+        self.collection_scan = plan.collection_scan;
+        self.program.backward = plan.backward;
+        self.residual_filter = plan.residual_filter.clone();
+        self.program.collation = plan.collation;
+    }
+
+    pub(super) fn set_index_order(&mut self, index_order: Option<IndexOrder>) {
+        self.program.index_order = index_order;
+    }
+
+    /// Bound the scan in the order of an index by the range of the query on the field
+    /// of the index, such as `{ "created": { "$gte": DateTime } }`. Without an order,
+    /// the documents the primary key or an index can't find by their values are read
+    /// by a bounded scan of an index whose field is in a range of the query.
+    pub(super) fn set_index_range(&mut self, col_spec: &CollectionSpecification, query: &Document) {
+        let collation = self.program.collation;
+        let range_of = |index_info: &IndexInfo| -> Option<IndexBounds> {
+            // the bounds are compared like the keys of the index
+            if index_info.collation() != collation {
+                return None;
+            }
+            let key = index_info.keys.keys().next()?;
+            match query.get(key) {
+                Some(Bson::Document(condition)) => IndexBounds::from_condition(index_info, condition),
+                _ => None,
+            }
+        };
+        if let Some(order) = &self.program.index_order {
+            self.program.index_bounds = range_of(&order.index_info);
+            return;
+        }
+        if self.collection_scan || !self.scans_collection(col_spec, query) {
+            return;
+        }
+        for (index_name, index_info) in &col_spec.indexes {
+            if matches!(&self.hint, Some(hint) if hint != index_name) || !index_info.covers(query) {
+                continue;
+            }
+            if let Some(bounds) = range_of(index_info) {
+                self.program.index_order = Some(IndexOrder {
+                    index_name: index_name.clone(),
+                    index_info: index_info.clone(),
+                    reverse: false,
+                });
+                self.program.index_bounds = Some(bounds);
+                return;
+            }
+        }
+    }
+
+    pub(super) fn set_access(&mut self, access: AccessPath) {
+        self.program.access = access;
+    }
+
+    /// Scan the collection in the order of the primary keys, or in the reverse order.
+    pub(super) fn set_natural_order(&mut self, backward: bool) {
+        self.collection_scan = true;
+        self.program.backward = backward;
+    }
+
+    /// Whether the query is run by a scan of the collection,
+    /// instead of finding the documents by the primary key or an index.
+    pub(super) fn scans_collection(&self, col_spec: &CollectionSpecification, query: &Document) -> bool {
+        if self.collection_scan {
+            return true;
+        }
+        let by_pkey = query.get("_id").is_some_and(|id_value| self.finds_by_pkey(id_value));
+        !by_pkey && index_candidates(col_spec, query, self.hint.as_deref(), self.program.collation).is_empty()
+    }
+
+    /// Whether the document of the `_id` of a query is read by its primary key.
+    fn finds_by_pkey(&self, id_value: &Bson) -> bool {
+        // the keys are compared byte by byte
+        let collated = self.program.collation != Collation::Simple
+            && id_value.element_type() == ElementType::String;
+        let regex = id_value.element_type() == ElementType::RegularExpression;
+        id_value.element_type() != ElementType::EmbeddedDocument && !collated && !regex
+    }
+
     fn unify_labels(&mut self) {
         for record in &self.jump_table {
             let pos = (record.begin_loc + record.offset) as usize;
@@ -145,6 +250,7 @@ impl Codegen {
         pkey: Bson,
         query: &Document,
         result_callback: F,
+        before_close: Option<BeforeClose>,
     ) -> Result<()>
     where
         F: FnOnce(&mut Codegen) -> Result<()>,
@@ -161,22 +267,56 @@ impl Codegen {
 
         self.emit_label(close_label);
         self.emit(DbOp::Pop);
+
+        if let Some(before_close) = before_close {
+            before_close(self)?;
+        }
+
         self.emit(DbOp::Close);
         self.emit(DbOp::Halt);
 
         self.emit_label(result_label);
-        for (key, value) in query.iter() {
-            if key == "_id" {
-                continue;
-            }
+        let remain_query: Document = query.iter()
+            .filter(|(key, _)| *key != "_id" && *key != "$comment")
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        let is_operator = |key: &str, value: &Bson| {
+            key.starts_with('$') || matches!(value, Bson::Document(doc) if doc.keys().any(|key| key.starts_with('$')))
+        };
+        if remain_query.iter().any(|(key, value)| is_operator(key, value)) {
+            // the operators are compared like a scan
+            let compare_fun = self.new_label();
+            let compare_fun_clean = self.new_label();
+            let matched_label = self.new_label();
+
+            self.emit(DbOp::Dup);
+            self.emit_goto(DbOp::Call, compare_fun);
+            self.emit_u32(1);
+            self.emit_goto(DbOp::IfFalse, close_label);
+            self.emit_goto(DbOp::Goto, matched_label);
+
+            self.emit_label_with_name(compare_fun, "compare_function");
+            self.emit_standard_query_doc(&remain_query, matched_label, compare_fun_clean)?;
+            self.emit_label_with_name(compare_fun_clean, "compare_function_clean");
+            self.emit_ret(0);
+
+            self.emit_label(matched_label);
 
+            result_callback(self)?;
+
+            self.emit_goto(DbOp::Goto, close_label);
+
+            return Ok(());
+        }
+
+        for (key, value) in remain_query.iter() {
             let key_static_id = self.push_static(Bson::String(key.clone()));
             let value_static_id = self.push_static(value.clone());
 
             self.emit_goto2(DbOp::GetField, key_static_id, close_label); // push a value1
             self.emit_push_value(value_static_id); // push a value2
 
-            self.emit(DbOp::Equal);
+            self.emit(equality_op(value));
             // if not equal，go to next
             self.emit_goto(DbOp::IfFalse, close_label);
 
@@ -196,25 +336,26 @@ impl Codegen {
         col_spec: &CollectionSpecification,
         query: &Document,
         result_callback: F,
-        before_close: Option<Box<dyn FnOnce(&mut Codegen) -> Result<()>>>,
+        mut before_close: Option<BeforeClose>,
         is_many: bool,
     ) -> Result<()>
     where
         F: FnOnce(&mut Codegen) -> Result<()>,
     {
-        let try_pkey_result = self.try_query_by_pkey(col_spec, query, result_callback)?;
+        let try_pkey_result = self.try_query_by_pkey(col_spec, query, result_callback, &mut before_close)?;
         if try_pkey_result.is_none() {
             return Ok(());
         }
 
         let result_callback: F = try_pkey_result.unwrap();
 
-        let try_index_result = self.try_query_by_index(col_spec, query, result_callback)?;
+        let try_index_result = self.try_query_by_index(col_spec, query, result_callback, &mut before_close)?;
         if try_index_result.is_none() {
             return Ok(());
         }
 
         self.emit_open(col_spec._id.clone().into());
+        self.program.access = AccessPath::CollectionScan(col_spec._id.clone());
 
         let result_callback: F = try_index_result.unwrap();
 
@@ -287,14 +428,20 @@ impl Codegen {
         col_spec: &CollectionSpecification,
         query: &Document,
         result_callback: F,
+        before_close: &mut Option<BeforeClose>,
     ) -> Result<Option<F>>
     where
         F: FnOnce(&mut Codegen) -> Result<()>,
     {
+        if self.collection_scan {
+            return Ok(Some(result_callback));
+        }
+
         if let Some(id_value) = query.get("_id") {
-            if id_value.element_type() != ElementType::EmbeddedDocument {
+            if self.finds_by_pkey(id_value) {
                 self.emit_open(col_spec._id.clone().into());
-                self.emit_query_layout_has_pkey(id_value.clone(), query, result_callback)?;
+                self.program.access = AccessPath::PrimaryKey(id_value.clone());
+                self.emit_query_layout_has_pkey(id_value.clone(), query, result_callback, before_close.take())?;
                 return Ok(None);
             }
         }
@@ -307,36 +454,45 @@ impl Codegen {
         col_spec: &CollectionSpecification,
         query: &Document,
         result_callback: F,
+        before_close: &mut Option<BeforeClose>,
     ) -> Result<Option<F>>
     where
         F: FnOnce(&mut Codegen) -> Result<()>,
     {
-        if self.is_write {
+        if self.is_write || self.collection_scan {
             return Ok(Some(result_callback));
         }
 
-        let index_meta = &col_spec.indexes;
-        for (index_name, index_info) in index_meta {
-            let (key, _order) = index_info.keys.iter().next().unwrap();
-            // the key is ellipse representation, such as "a.b.c"
-            // the query is supposed to be ellipse too, such as
-            // { "a.b.c": 1 }
-            let test_result = query.get(key);
-            if let Some(query_doc) = test_result {
-                if query_doc.element_type() != ElementType::EmbeddedDocument {
-                    let mut remain_query = query.clone();
+        let mut candidates = index_candidates(col_spec, query, self.hint.as_deref(), self.program.collation);
+        // the index chosen by the planner, or the first one
+        let position = self.index.as_ref()
+            .and_then(|index| candidates.iter().position(|candidate| candidate.index_name == index))
+            .unwrap_or(0);
+        if position < candidates.len() {
+            let candidate = candidates.swap_remove(position);
+            let mut remain_query = query.clone();
+            for (key, value) in candidate.keys.iter().zip(&candidate.values) {
+                // a compound index has a null entry for a missing field, the filter
+                // checks the null values not to find the documents missing it
+                if *value != Bson::Null {
                     remain_query.remove(key);
-
-                    self.indeed_emit_query_by_index(
-                        col_spec._id.as_str(),
-                        index_name.as_str(),
-                        query_doc,
-                        &remain_query,
-                        result_callback,
-                    )?;
-                    return Ok(None);
                 }
             }
+            self.program.access = AccessPath::Index {
+                col_name: col_spec._id.clone(),
+                index_name: candidate.index_name.to_string(),
+                values: candidate.values.clone(),
+            };
+
+            self.indeed_emit_query_by_index(
+                col_spec._id.as_str(),
+                candidate.index_name,
+                &candidate.lookup_value(),
+                &remain_query,
+                result_callback,
+                before_close.take(),
+            )?;
+            return Ok(None);
         }
 
         Ok(Some(result_callback))
@@ -349,6 +505,7 @@ impl Codegen {
         query_value: &Bson,
         remain_query: &Document,
         result_callback: F,
+        before_close: Option<BeforeClose>,
     ) -> Result<()>
     where
         F: FnOnce(&mut Codegen) -> Result<()>,
@@ -389,23 +546,38 @@ impl Codegen {
         self.emit(DbOp::Pop); // pop the collection name
         self.emit(DbOp::Pop); // pop the query value
 
+        if let Some(before_close) = before_close {
+            before_close(self)?;
+        }
+
         self.emit(DbOp::Close);
         self.emit(DbOp::Halt);
 
         self.emit_label(result_label);
-        for (key, value) in remain_query.iter() {
-            let key_static_id = self.push_static(Bson::String(key.clone()));
-            let value_static_id = self.push_static(value.clone());
+        if !remain_query.is_empty() {
+            // the rest of the query is compared like a scan,
+            // a document not matching it moves to the next index value
+            let compare_fun = self.new_label();
+            let compare_fun_clean = self.new_label();
+            let not_found_label = self.new_label();
+            let matched_label = self.new_label();
+
+            self.emit(DbOp::Dup);
+            self.emit_goto(DbOp::Call, compare_fun);
+            self.emit_u32(1);
+            self.emit_goto(DbOp::IfFalse, not_found_label);
+            self.emit_goto(DbOp::Goto, matched_label);
 
-            self.emit_goto2(DbOp::GetField, key_static_id, close_label); // push a value1
-            self.emit_push_value(value_static_id); // push a value2
+            self.emit_label_with_name(not_found_label, "not_this_item");
+            self.emit(DbOp::Pop);
+            self.emit_goto(DbOp::Goto, next_label);
 
-            self.emit(DbOp::Equal);
-            // if not equal，go to next
-            self.emit_goto(DbOp::IfFalse, close_label);
+            self.emit_label_with_name(compare_fun, "compare_function");
+            self.emit_standard_query_doc(remain_query, matched_label, compare_fun_clean)?;
+            self.emit_label_with_name(compare_fun_clean, "compare_function_clean");
+            self.emit_ret(0);
 
-            self.emit(DbOp::Pop); // pop a value2
-            self.emit(DbOp::Pop); // pop a value1
+            self.emit_label(matched_label);
         }
 
         result_callback(self)?;
@@ -519,7 +691,62 @@ impl Codegen {
         Ok(())
     }
 
-    // case1: "$and" | "$or" -> [ Document ]
+    // none of the queries matches
+    fn emit_logic_nor(
+        &mut self,
+        arr: &Array,
+        ret_label: Label,
+    ) -> Result<()> {
+        let cmp_label = self.new_label();
+        let matched_label = self.new_label();
+        let next_label = self.new_label();
+        self.emit_goto(DbOp::Goto, cmp_label);
+
+        let mut functions = Vec::<Label>::new();
+        for (index, item_doc_value) in arr.iter().enumerate() {
+            let path_msg = format!("[{}]", index);
+            crate::path_hint!(self, path_msg, {
+                let item_doc = crate::try_unwrap_document!("$nor", item_doc_value);
+
+                let query_label = self.new_label();
+                let ret_label = self.new_label();
+
+                self.emit_label(query_label);
+                self.emit_standard_query_doc(
+                    item_doc,
+                    ret_label,
+                    ret_label
+                )?;
+
+                self.emit_label(ret_label);
+                self.emit_ret(0);
+
+                functions.push(query_label);
+            });
+        }
+
+        self.emit_label(cmp_label);
+        for fun in functions {
+            self.emit_goto(DbOp::Call, fun);
+            self.emit_u32(0);
+            self.emit_goto(DbOp::IfTrue, matched_label);
+        }
+        self.emit(DbOp::StoreR0_2);
+        self.emit_u8(1);
+        self.emit_goto(DbOp::Goto, next_label);
+
+        self.emit_label(matched_label);
+        self.emit(DbOp::StoreR0_2);
+        self.emit_u8(0);
+        self.emit_goto(DbOp::Goto, ret_label);
+
+        self.emit_label(next_label);
+
+        Ok(())
+    }
+
+    // case1: "$and" | "$or" | "$nor" -> [ Document ]
+    // case2: "$comment" -> any value
     // case3: "_id" -> Document
     fn emit_query_tuple(
         &mut self,
@@ -547,6 +774,34 @@ impl Codegen {
                     )?;
                 }
 
+                "$nor" => {
+                    let sub_arr = crate::try_unwrap_array!("$nor", value);
+                    self.emit_logic_nor(
+                        sub_arr.as_ref(),
+                        not_found_label,
+                    )?;
+                }
+
+                // the whole document conforms to the schema
+                "$jsonSchema" => {
+                    let schema = crate::try_unwrap_document!("$jsonSchema", value);
+                    crate::schema::check_schema(schema)?;
+
+                    let schema_static_id = self.push_static(value.clone());
+                    self.emit_push_value(schema_static_id);
+
+                    self.emit(DbOp::JsonSchema);
+                    self.emit_goto(DbOp::IfFalse, not_found_label);
+
+                    self.emit(DbOp::Pop);
+                }
+
+                // only tags the operation, matches every document
+                "$comment" => {
+                    self.emit(DbOp::StoreR0_2);
+                    self.emit_u8(1);
+                }
+
                 _ => {
                     return Err(Error::InvalidField(mk_invalid_query_field(
                         self.last_key().into(),
@@ -579,7 +834,7 @@ impl Codegen {
                     let value_static_id = self.push_static(value.clone());
                     self.emit_push_value(value_static_id); // push a value2
 
-                    self.emit(DbOp::Equal);
+                    self.emit(equality_op(value));
                     // if not equal，go to next
                     self.emit_goto(DbOp::IfFalse, not_found_label);
 
@@ -591,14 +846,11 @@ impl Codegen {
         Ok(())
     }
 
-    fn recursively_get_field(&mut self, key: &str, get_field_failed_label: Label) -> usize {
-        let slices: Vec<&str> = key.split('.').collect();
-        for slice in &slices {
-            let str_ref: &str = slice;
-            let current_stat_id = self.push_static(str_ref.into());
-            self.emit_goto2(DbOp::GetField, current_stat_id, get_field_failed_label);
-        }
-        slices.len()
+    // push the value of the field, a dotted path goes through the arrays
+    // the same way as for the equality
+    fn emit_get_field(&mut self, key: &str, get_field_failed_label: Label) {
+        let key_static_id = self.push_static(key.into());
+        self.emit_goto2(DbOp::GetField, key_static_id, get_field_failed_label);
     }
 
     fn emit_logical(&mut self, op: DbOp, is_in_not: bool) {
@@ -608,6 +860,63 @@ impl Codegen {
         }
     }
 
+    // { "$size": { "$gte": 2, "$lte": 5 } }
+    // the arrays with a length within the bounds, the other values don't match
+    fn emit_query_size_range(
+        &mut self,
+        key: &str,
+        is_in_not: bool,
+        not_found_label: Label,
+        range: &Document,
+    ) -> Result<()> {
+        let mut bounds = Vec::with_capacity(range.len());
+        for (op_key, bound) in range.iter() {
+            let op = match op_key.as_str() {
+                "$eq" => DbOp::Equal,
+                "$gt" => DbOp::Greater,
+                "$gte" => DbOp::GreaterEqual,
+                "$lt" => DbOp::Less,
+                "$lte" => DbOp::LessEqual,
+                _ => {
+                    return Err(Error::InvalidField(mk_invalid_query_field(
+                        op_key.clone(),
+                        self.gen_path(),
+                    )))
+                }
+            };
+            if !matches!(bound, Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_)) {
+                return Err(Error::InvalidField(mk_invalid_query_field(
+                    op_key.clone(),
+                    self.gen_path(),
+                )));
+            }
+            bounds.push((op, bound));
+        }
+
+        self.emit_get_field(key, not_found_label);
+        self.emit(DbOp::ArraySize);
+
+        // the size is null if the value is not an array
+        self.emit_logical(DbOp::EqualNull, true);
+        self.emit_goto(DbOp::IfFalse, not_found_label);
+
+        for (op, bound) in bounds {
+            let bound_static_id = self.push_static(bound.clone());
+            self.emit_push_value(bound_static_id);
+
+            self.emit_logical(op, is_in_not);
+
+            self.emit_goto(DbOp::IfFalse, not_found_label);
+
+            self.emit(DbOp::Pop);
+        }
+
+        self.emit(DbOp::Pop2);
+        self.emit_u32(2);
+
+        Ok(())
+    }
+
     fn emit_query_tuple_document_kv(
         &mut self,
         key: &str,
@@ -618,7 +927,7 @@ impl Codegen {
     ) -> Result<()> {
         match sub_key {
             "$eq" => {
-                let field_size = self.recursively_get_field(key, not_found_label);
+                self.emit_get_field(key, not_found_label);
 
                 let stat_val_id = self.push_static(sub_value.clone());
                 self.emit_push_value(stat_val_id);
@@ -628,11 +937,11 @@ impl Codegen {
                 self.emit_goto(DbOp::IfFalse, not_found_label);
 
                 self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
+                self.emit_u32(2);
             }
 
             "$gt" => {
-                let field_size = self.recursively_get_field(key, not_found_label);
+                self.emit_get_field(key, not_found_label);
 
                 let stat_val_id = self.push_static(sub_value.clone());
                 self.emit_push_value(stat_val_id);
@@ -641,11 +950,11 @@ impl Codegen {
                 self.emit_goto(DbOp::IfFalse, not_found_label);
 
                 self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
+                self.emit_u32(2);
             }
 
             "$gte" => {
-                let field_size = self.recursively_get_field(key, not_found_label);
+                self.emit_get_field(key, not_found_label);
 
                 let stat_val_id = self.push_static(sub_value.clone());
                 self.emit_push_value(stat_val_id);
@@ -654,7 +963,7 @@ impl Codegen {
                 self.emit_goto(DbOp::IfFalse, not_found_label);
 
                 self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
+                self.emit_u32(2);
             }
 
             // check the value is array
@@ -669,7 +978,7 @@ impl Codegen {
                     }
                 }
 
-                let field_size = self.recursively_get_field(key, not_found_label);
+                self.emit_get_field(key, not_found_label);
 
                 let stat_val_id = self.push_static(sub_value.clone());
                 self.emit_push_value(stat_val_id);
@@ -678,11 +987,11 @@ impl Codegen {
                 self.emit_goto(DbOp::IfFalse, not_found_label);
 
                 self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
+                self.emit_u32(2);
             }
 
             "$lt" => {
-                let field_size = self.recursively_get_field(key, not_found_label);
+                self.emit_get_field(key, not_found_label);
 
                 let stat_val_id = self.push_static(sub_value.clone());
                 self.emit_push_value(stat_val_id);
@@ -691,11 +1000,11 @@ impl Codegen {
                 self.emit_goto(DbOp::IfFalse, not_found_label);
 
                 self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
+                self.emit_u32(2);
             }
 
             "$lte" => {
-                let field_size = self.recursively_get_field(key, not_found_label);
+                self.emit_get_field(key, not_found_label);
 
                 let stat_val_id = self.push_static(sub_value.clone());
                 self.emit_push_value(stat_val_id);
@@ -705,21 +1014,21 @@ impl Codegen {
                 self.emit_goto(DbOp::IfFalse, not_found_label);
 
                 self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
+                self.emit_u32(2);
             }
 
             "$ne" => {
-                let field_size = self.recursively_get_field(key, not_found_label);
+                self.emit_get_field(key, not_found_label);
 
                 let stat_val_id = self.push_static(sub_value.clone());
                 self.emit_push_value(stat_val_id);
-                self.emit_logical(DbOp::Equal, is_in_not);
+                self.emit_logical(DbOp::Equal, !is_in_not);
 
                 // if equal，go to next
                 self.emit_goto(DbOp::IfFalse, not_found_label);
 
                 self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
+                self.emit_u32(2);
             }
 
             "$nin" => {
@@ -733,21 +1042,26 @@ impl Codegen {
                     }
                 }
 
-                let field_size = self.recursively_get_field(key, not_found_label);
+                self.emit_get_field(key, not_found_label);
 
                 let stat_val_id = self.push_static(sub_value.clone());
                 self.emit_push_value(stat_val_id);
-                self.emit_logical(DbOp::In, is_in_not);
+                self.emit_logical(DbOp::In, !is_in_not);
 
-                self.emit_goto(DbOp::IfTrue, not_found_label);
+                // if in the array，go to next
+                self.emit_goto(DbOp::IfFalse, not_found_label);
 
                 self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
+                self.emit_u32(2);
             }
 
             "$size" => {
                 let expected_size = match sub_value {
+                    Bson::Int32(i) => i64::from(*i),
                     Bson::Int64(i) => *i,
+                    Bson::Document(range) => {
+                        return self.emit_query_size_range(key, is_in_not, not_found_label, range);
+                    }
                     _ => {
                         return Err(Error::InvalidField(mk_invalid_query_field(
                             self.last_key().into(),
@@ -756,7 +1070,7 @@ impl Codegen {
                     }
                 };
 
-                let field_size = self.recursively_get_field(key, not_found_label);
+                self.emit_get_field(key, not_found_label);
                 self.emit(DbOp::ArraySize);
 
                 let expect_size_stat_id = self.push_static(Bson::from(expected_size));
@@ -766,8 +1080,9 @@ impl Codegen {
 
                 self.emit_goto(DbOp::IfFalse, not_found_label);
 
+                // the value, its size and the expected size
                 self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
+                self.emit_u32(3);
             }
 
             "$regex" => {
@@ -781,7 +1096,7 @@ impl Codegen {
                     }
                 }
 
-                let field_size = self.recursively_get_field(key, not_found_label);
+                self.emit_get_field(key, not_found_label);
 
                 let stat_val_id = self.push_static(sub_value.clone());
                 self.emit_push_value(stat_val_id);
@@ -792,7 +1107,54 @@ impl Codegen {
                 self.emit_goto(DbOp::IfFalse, not_found_label);
 
                 self.emit(DbOp::Pop2);
-                self.emit_u32((field_size + 1) as u32);
+                self.emit_u32(2);
+            }
+
+            "$fuzzy" => {
+                if FuzzySearch::parse(sub_value).is_none() {
+                    return Err(Error::InvalidField(mk_invalid_query_field(
+                        self.last_key().into(),
+                        self.gen_path(),
+                    )))
+                }
+
+                self.emit_get_field(key, not_found_label);
+
+                let stat_val_id = self.push_static(sub_value.clone());
+                self.emit_push_value(stat_val_id);
+
+                self.emit_logical(DbOp::Fuzzy, is_in_not);
+
+                self.emit_goto(DbOp::IfFalse, not_found_label);
+
+                self.emit(DbOp::Pop2);
+                self.emit_u32(2);
+            }
+
+            // an element of the array matches all the conditions
+            "$elemMatch" => {
+                let valid = match sub_value {
+                    Bson::Document(query) => ElemMatch::parse(query),
+                    _ => Err(self.last_key().into()),
+                };
+                if let Err(field) = valid {
+                    return Err(Error::InvalidField(mk_invalid_query_field(
+                        field,
+                        self.gen_path(),
+                    )))
+                }
+
+                self.emit_get_field(key, not_found_label);
+
+                let stat_val_id = self.push_static(sub_value.clone());
+                self.emit_push_value(stat_val_id);
+
+                self.emit_logical(DbOp::ElemMatch, is_in_not);
+
+                self.emit_goto(DbOp::IfFalse, not_found_label);
+
+                self.emit(DbOp::Pop2);
+                self.emit_u32(2);
             }
 
             "$not" => {
@@ -963,6 +1325,49 @@ impl Codegen {
                         let external_func: Box<dyn VmExternalFunc> = VmFuncUnset::compile(&mut self.paths, value)?;
                         self.emit_external_func(external_func, stage_ctx_item, next_fun);
                     }
+                    "$project" => {
+                        let next_fun = ctx.items[index + 1].next_label;
+                        let external_func = VmFuncProject::compile(
+                            &mut self.paths,
+                            self.op_registry.clone(),
+                            value,
+                        )?;
+                        self.emit_external_func(external_func, stage_ctx_item, next_fun);
+                    }
+                    "$unwind" => {
+                        let next_fun = ctx.items[index + 1].next_label;
+                        let external_func: Box<dyn VmExternalFunc> = VmFuncUnwind::compile(&mut self.paths, value)?;
+                        self.emit_external_func(external_func, stage_ctx_item, next_fun);
+                    }
+                    "$filterFn" => {
+                        let filter = match &self.residual_filter {
+                            Some(filter) => filter.clone(),
+                            None => return Err(Error::UnknownAggregationOperation(key.clone())),
+                        };
+                        let next_fun = ctx.items[index + 1].next_label;
+                        let external_func: Box<dyn VmExternalFunc> = Box::new(VmFuncFilterFn::new(filter));
+                        self.emit_external_func(external_func, stage_ctx_item, next_fun);
+                    }
+                    "$text" => {
+                        let next_fun = ctx.items[index + 1].next_label;
+                        let external_func: Box<dyn VmExternalFunc> = VmFuncText::compile(&mut self.paths, value)?;
+                        self.emit_external_func(external_func, stage_ctx_item, next_fun);
+                    }
+                    "$geoNear" => {
+                        let next_fun = ctx.items[index + 1].next_label;
+                        let external_func = VmFuncGeoNear::compile(&mut self.paths, value)?;
+                        self.emit_external_func(external_func, stage_ctx_item, next_fun);
+                    }
+                    "$lookup" => {
+                        let next_fun = ctx.items[index + 1].next_label;
+                        let external_func = VmFuncLookup::compile(&mut self.paths, value)?;
+                        self.emit_external_func(external_func, stage_ctx_item, next_fun);
+                    }
+                    "$match" => {
+                        let query = crate::try_unwrap_document!("$match", value);
+                        let next_fun = ctx.items[index + 1].next_label;
+                        self.emit_match_stage(query, stage_ctx_item, next_fun)?;
+                    }
                     _ => {
                         return Err(Error::UnknownAggregationOperation(key.clone()));
                     }
@@ -973,6 +1378,38 @@ impl Codegen {
         Ok(())
     }
 
+    /// A `$match` following another stage passes on the documents matching the query.
+    /// The end of the documents isn't passed on, the next stages are told by the pipeline.
+    fn emit_match_stage(&mut self, query: &Document, stage_ctx_item: &PipelineItem, next_fun: Label) -> Result<()> {
+        let compare_fun = self.new_label();
+        let compare_fun_clean = self.new_label();
+        let skip_label = self.new_label();
+
+        self.emit_label(stage_ctx_item.next_label);
+        self.emit(DbOp::EqualNull);
+        self.emit_goto(DbOp::IfTrue, skip_label);
+
+        self.emit(DbOp::Dup);
+        self.emit_goto(DbOp::Call, compare_fun);
+        self.emit_u32(1);
+        self.emit_goto(DbOp::IfFalse, skip_label);
+
+        self.emit(DbOp::Dup);
+        self.emit_goto(DbOp::Call, next_fun);
+        self.emit_u32(1);
+
+        self.emit_label(skip_label);
+        self.emit_ret(0);
+
+        self.emit_label_with_name(compare_fun, "match_function");
+        self.emit_standard_query_doc(query, compare_fun_clean, compare_fun_clean)?;
+
+        self.emit_label_with_name(compare_fun_clean, "match_function_clean");
+        self.emit_ret(0);
+
+        Ok(())
+    }
+
     fn emit_external_func(&mut self, external_func: Box<dyn VmExternalFunc>, stage_ctx_item: &PipelineItem, next_fun: Label) {
         let external_func_id = self.push_external_func(external_func);
         let go_next = self.new_label();
@@ -1038,14 +1475,14 @@ impl Codegen {
         self.emit(DbOp::DeleteCurrent);
     }
 
-    pub(super) fn emit_update_operation(&mut self, update: &Document) -> Result<()> {
+    pub(super) fn emit_update_operation(&mut self, query: &Document, update: &Document) -> Result<()> {
         self.emit(DbOp::IncR2);
         self.emit(DbOp::StoreR0_2);
         self.emit_u8(0);
 
         for (key, value) in update.iter() {
             crate::path_hint!(self, key.clone(), {
-                self.emit_update_operation_kv(key, value)?;
+                self.emit_update_operation_kv(query, key, value)?;
             });
         }
 
@@ -1066,61 +1503,85 @@ impl Codegen {
         self.emit_u32(id);
     }
 
-    fn emit_update_operation_kv(&mut self, key: &str, value: &Bson) -> Result<()> {
+    fn emit_update_operation_kv(&mut self, query: &Document, key: &str, value: &Bson) -> Result<()> {
         match key {
             "$inc" => {
                 let doc = crate::try_unwrap_document!("$inc", value);
 
-                let op = IncOperator::compile(doc.clone())?;
+                let op = IncOperator::compile(doc.clone(), query)?;
                 self.emit_update_operator(Box::new(op));
             }
 
             "$set" => {
                 let doc = crate::try_unwrap_document!("$set", value);
 
-                let op = SetOperator::compile(doc.clone())?;
+                let op = SetOperator::compile(doc.clone(), query)?;
                 self.emit_update_operator(Box::new(op));
             }
 
             "$max" => {
                 let doc = crate::try_unwrap_document!("$max", value);
 
-                let op = MaxOperator::compile(doc.clone())?;
+                let op = MaxOperator::compile(doc.clone(), query)?;
                 self.emit_update_operator(Box::new(op));
             }
 
             "$min" => {
                 let doc = crate::try_unwrap_document!("$min", value);
 
-                let op = MinOperator::compile(doc.clone())?;
+                let op = MinOperator::compile(doc.clone(), query)?;
                 self.emit_update_operator(Box::new(op));
             }
 
             "$mul" => {
                 let doc = crate::try_unwrap_document!("$mul", value);
 
-                let op = MulOperator::compile(doc.clone())?;
+                let op = MulOperator::compile(doc.clone(), query)?;
                 self.emit_update_operator(Box::new(op));
             }
 
             "$rename" => {
                 let doc = crate::try_unwrap_document!("$set", value);
 
-                let op = RenameOperator::compile(doc.clone())?;
+                let op = RenameOperator::compile(doc.clone(), query)?;
                 self.emit_update_operator(Box::new(op));
             }
 
             "$unset" => {
                 let doc = crate::try_unwrap_document!("$unset", value);
 
-                let op = UnsetOperator::compile(doc)?;
+                let op = UnsetOperator::compile(doc, query)?;
                 self.emit_update_operator(Box::new(op));
             }
 
             "$push" => {
                 let doc = crate::try_unwrap_document!("$push", value);
 
-                let op = PushOperator::compile(doc.clone())?;
+                let op = PushOperator::compile(
+                    doc.clone(),
+                    query,
+                    self.last_key().to_string(),
+                    self.gen_path(),
+                )?;
+                self.emit_update_operator(Box::new(op));
+            }
+
+            "$addToSet" => {
+                let doc = crate::try_unwrap_document!("$addToSet", value);
+
+                let op = AddToSetOperator::compile(
+                    doc.clone(),
+                    query,
+                    self.last_key().to_string(),
+                    self.gen_path(),
+                )?;
+                self.emit_update_operator(Box::new(op));
+            }
+
+            "$pull" => {
+                let doc = crate::try_unwrap_document!("$pull", value);
+
+                let op = PullOperator::compile(doc.clone(), query, self.gen_path())?;
                 self.emit_update_operator(Box::new(op));
             }
 
@@ -1129,6 +1590,7 @@ impl Codegen {
 
                 let op = PopOperator::compile(
                     doc.clone(),
+                    query,
                     self.last_key().to_string(),
                     self.gen_path(),
                 )?;
@@ -1239,3 +1701,82 @@ impl Codegen {
             .push(JumpTableRecord::new(record_loc, 5, label.pos()));
     }
 }
+
+/// An index able to find the documents of a query, and the values it looks up.
+pub(crate) struct IndexCandidate<'a> {
+    pub index_name: &'a str,
+    /// The first fields of the index, all of them compared to a value by the query.
+    pub keys: Vec<&'a str>,
+    pub values: Vec<Bson>,
+}
+
+impl IndexCandidate<'_> {
+
+    /// The value looked up by the index, the values of the first fields
+    /// of a compound index are looked up as an array.
+    pub(crate) fn lookup_value(&self) -> Bson {
+        IndexHelper::entry_value(&self.values)
+    }
+
+}
+
+/// The comparison of a field with the value of `{ field: value }`,
+/// a regular expression matches the strings like `$regex`.
+fn equality_op(value: &Bson) -> DbOp {
+    match value {
+        Bson::RegularExpression(_) => DbOp::Regex,
+        _ => DbOp::Equal,
+    }
+}
+
+/// The indexes able to find the documents of the query by its values,
+/// in the order of their creation. A compound index is used when the query
+/// gives the values of its first fields, such as `{ "category": "book" }`
+/// for the index `{ "category": 1, "tags": 1 }`.
+pub(crate) fn index_candidates<'a>(
+    col_spec: &'a CollectionSpecification,
+    query: &'a Document,
+    hint: Option<&str>,
+    collation: Collation,
+) -> Vec<IndexCandidate<'a>> {
+    let mut result = Vec::new();
+    for (index_name, index_info) in &col_spec.indexes {
+        if matches!(hint, Some(hint) if hint != index_name) {
+            continue;
+        }
+        // the keys of the index are compared under its own collation
+        if index_info.collation() != collation || !index_info.covers(query) {
+            continue;
+        }
+        let mut keys = Vec::new();
+        let mut values = Vec::new();
+        // the key is ellipse representation, such as "a.b.c"
+        // the query is supposed to be ellipse too, such as
+        // { "a.b.c": 1 }
+        for key in index_info.keys.keys() {
+            let query_doc = match query.get(key) {
+                Some(query_doc) => query_doc,
+                None => break,
+            };
+            // a regular expression matches the strings, it's not a key of the index,
+            // neither is an array, its elements are
+            let found_by_key = !matches!(
+                query_doc.element_type(),
+                ElementType::EmbeddedDocument | ElementType::RegularExpression | ElementType::Array,
+            );
+            if !found_by_key {
+                break;
+            }
+            keys.push(key.as_str());
+            values.push(collation.collate(query_doc).into_owned());
+        }
+        if !keys.is_empty() {
+            result.push(IndexCandidate {
+                index_name,
+                keys,
+                values,
+            });
+        }
+    }
+    result
+}
@@ -28,7 +28,16 @@ mod vm_sort;
 mod vm_limit;
 mod vm_unset;
 mod vm_add_fields;
+mod vm_text;
+mod vm_geo_near;
+mod vm_lookup;
+mod vm_project;
+mod vm_unwind;
+mod vm_filter_fn;
 mod update_operators;
 
-pub(crate) use subprogram::SubProgram;
+pub(crate) use subprogram::{natural_direction, AccessPath, QueryPlan, SubProgram};
+pub(crate) use vm_filter_fn::ResidualFilter;
+pub(crate) use codegen::index_candidates;
 pub(crate) use vm::{VM, VmState};
+pub(crate) use op::{field_cmp, regex_matches, value_in, DbOp};
@@ -14,6 +14,8 @@
 
 use std::cmp::Ordering;
 use bson::Bson;
+use crate::options::Collation;
+use crate::utils::bson::type_rank;
 
 #[repr(u8)]
 #[derive(Copy, Clone, Eq, PartialEq)]
@@ -192,6 +194,16 @@ pub enum DbOp {
     // op1. index info id: 4 bytes
     DeleteIndex,
 
+    // update the index of the value updated on the stack,
+    // only changing the entries of the values which changed
+    //
+    // top-1 is the updated value
+    // top-2 is the value before the update
+    //
+    // 5 byte
+    // op1. index info id: 4 bytes
+    UpdateIndex,
+
     // duplicate the top of the stack
     Dup,
 
@@ -215,6 +227,18 @@ pub enum DbOp {
     LessEqual,
     Regex,
 
+    // check if the words of top1 are close to the words of the $fuzzy search top0
+    // the result is stored in r0
+    Fuzzy,
+
+    // check if an element of the array top1 matches the $elemMatch query top0
+    // the result is stored in r0
+    ElemMatch,
+
+    // check if the document top1 conforms to the $jsonSchema top0
+    // the result is stored in r0
+    JsonSchema,
+
     Not,
 
     // check if top0 is in top2
@@ -303,6 +327,59 @@ pub enum DbOp {
 
 }
 
+/// Whether `value` is equal to one of `values`, or an element of `value` if it's an array.
+pub(crate) fn value_in(value: &Bson, values: &[Bson], collation: Collation) -> bool {
+    let is_in = |value: &Bson| {
+        let value = collation.collate(value);
+        values.iter().any(|item| {
+            matches!(crate::utils::bson::value_cmp(&value, &collation.collate(item)), Ok(Ordering::Equal))
+        })
+    };
+    match value {
+        Bson::Array(arr) => is_in(value) || arr.iter().any(is_in),
+        _ => is_in(value),
+    }
+}
+
+/// Whether the value of a field satisfies the comparison `op` with `operand`, the way
+/// the filter compares them: an array is equal to the values of its elements, like the
+/// entries of its index, and is in a range if one of its elements is. The documents
+/// and the arrays are equal to the same ones. A range only holds the values of the type
+/// of its bound, the numbers of all the types being of the same type, like the runs of
+/// the keys of an index.
+pub(crate) fn field_cmp(op: DbOp, value: &Bson, operand: &Bson, collation: Collation) -> crate::Result<bool> {
+    let value = collation.collate(value);
+    let operand = collation.collate(operand);
+    match (op, value.as_ref(), operand.as_ref()) {
+        (DbOp::Equal, Bson::Document(_), Bson::Document(_)) | (DbOp::Equal, Bson::Array(_), Bson::Array(_)) => {
+            Ok(value == operand)
+        }
+        (DbOp::Equal, Bson::Array(arr), operand) => Ok(value_in(operand, arr, collation)),
+        (_, Bson::Array(arr), operand) if !matches!(operand, Bson::Array(_)) => {
+            Ok(arr.iter().any(|item| range_cmp(op, &collation.collate(item), operand).unwrap_or(false)))
+        }
+        _ => range_cmp(op, &value, &operand),
+    }
+}
+
+fn range_cmp(op: DbOp, value: &Bson, operand: &Bson) -> crate::Result<bool> {
+    let is_range = matches!(op, DbOp::Greater | DbOp::GreaterEqual | DbOp::Less | DbOp::LessEqual);
+    if is_range && type_rank(value) != type_rank(operand) {
+        return Ok(false);
+    }
+    generic_cmp(op, value, operand)
+}
+
+/// Whether the value of a field matches the regular expression: a string, one of
+/// the strings of an array, or the text of the other values.
+pub(crate) fn regex_matches(re: &regex::Regex, value: &Bson) -> bool {
+    match value {
+        Bson::String(s) => re.is_match(s),
+        Bson::Array(arr) => arr.iter().any(|item| matches!(item, Bson::String(s) if re.is_match(s))),
+        other => re.is_match(&other.to_string()),
+    }
+}
+
 pub(crate) fn generic_cmp(op: DbOp, val1: &Bson, val2: &Bson) -> crate::Result<bool> {
     let ord = crate::utils::bson::value_cmp(val1, val2)?;
     let result = matches!(
@@ -72,15 +72,6 @@ impl AbsOperator {
 }
 
 impl VmOperator for AbsOperator {
-    fn initial_value(&self) -> Bson {
-        match self.inner {
-            OperatorExpr::Constant(ref v) => v.clone(),
-            OperatorExpr::Expr(ref op) =>
-                Self::bson_abs(op.initial_value()),
-            OperatorExpr::Alias(_) => Bson::Null,
-        }
-    }
-
     fn next(&self, input: &Bson) -> Bson {
         match self.inner {
             OperatorExpr::Constant(ref v) => v.clone(),
@@ -95,13 +86,4 @@ impl VmOperator for AbsOperator {
             }
         }
     }
-
-    fn complete(&self) -> Bson {
-        match self.inner {
-            OperatorExpr::Constant(ref v) => v.clone(),
-            OperatorExpr::Expr(ref op) =>
-                Self::bson_abs(op.complete()),
-            OperatorExpr::Alias(_) => Bson::Null,
-        }
-    }
 }
@@ -0,0 +1,134 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::convert::TryFrom;
+use bson::Bson;
+use crate::vm::operators::{OpRegistry, OperatorExpr, VmOperator};
+use crate::{Result, Error};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Arithmetic {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Mod,
+}
+
+impl Arithmetic {
+
+    fn name(self) -> &'static str {
+        match self {
+            Arithmetic::Add => "$add",
+            Arithmetic::Subtract => "$subtract",
+            Arithmetic::Multiply => "$multiply",
+            Arithmetic::Divide => "$divide",
+            Arithmetic::Mod => "$mod",
+        }
+    }
+
+    /// The result of the operation on two numbers, `None` if one of them is not a number,
+    /// or for a division by zero. The integers stay integers as long as they don't overflow:
+    /// two `Int32` give an `Int32` if the result fits, otherwise an `Int64`, then a `Double`.
+    pub(crate) fn apply(self, a: &Bson, b: &Bson) -> Option<Bson> {
+        let integer = |value: &Bson| match value {
+            Bson::Int32(v) => Some(*v as i64),
+            Bson::Int64(v) => Some(*v),
+            _ => None,
+        };
+        let double = |value: &Bson| match value {
+            Bson::Int32(v) => Some(*v as f64),
+            Bson::Int64(v) => Some(*v as f64),
+            Bson::Double(v) => Some(*v),
+            _ => None,
+        };
+        if let (Some(x), Some(y), false) = (integer(a), integer(b), self == Arithmetic::Divide) {
+            let result = match self {
+                Arithmetic::Add => x.checked_add(y),
+                Arithmetic::Subtract => x.checked_sub(y),
+                Arithmetic::Multiply => x.checked_mul(y),
+                _ => x.checked_rem(y),
+            };
+            return match result {
+                Some(v) if matches!((a, b), (Bson::Int32(_), Bson::Int32(_))) => {
+                    Some(i32::try_from(v).map(Bson::Int32).unwrap_or(Bson::Int64(v)))
+                }
+                Some(v) => Some(Bson::Int64(v)),
+                None if self == Arithmetic::Mod => None,
+                None => self.apply_double(x as f64, y as f64),
+            };
+        }
+        self.apply_double(double(a)?, double(b)?)
+    }
+
+    fn apply_double(self, x: f64, y: f64) -> Option<Bson> {
+        let result = match self {
+            Arithmetic::Add => x + y,
+            Arithmetic::Subtract => x - y,
+            Arithmetic::Multiply => x * y,
+            Arithmetic::Divide | Arithmetic::Mod if y == 0.0 => return None,
+            Arithmetic::Divide => x / y,
+            Arithmetic::Mod => x % y,
+        };
+        Some(Bson::Double(result))
+    }
+
+}
+
+/// `$add`, `$subtract`, `$multiply`, `$divide` and `$mod` of an array of expressions.
+/// The result is null if an argument is null, missing or not a number.
+pub(crate) struct ArithmeticOperator {
+    arithmetic: Arithmetic,
+    args: Vec<OperatorExpr>,
+}
+
+impl ArithmeticOperator {
+
+    pub(crate) fn compile(paths: &mut Vec<String>, registry: OpRegistry, arithmetic: Arithmetic, v: &Bson) -> Result<Box<dyn VmOperator>> {
+        let args = match v {
+            Bson::Array(args) => args,
+            _ => {
+                return Err(Error::ValidationError(format!("{} requires an array of arguments", arithmetic.name())));
+            }
+        };
+        let variadic = matches!(arithmetic, Arithmetic::Add | Arithmetic::Multiply);
+        if (variadic && args.is_empty()) || (!variadic && args.len() != 2) {
+            let expected = if variadic { "at least 1" } else { "exactly 2" };
+            return Err(Error::ValidationError(format!("{} takes {} arguments", arithmetic.name(), expected)));
+        }
+        let mut exprs = Vec::with_capacity(args.len());
+        for (index, arg) in args.iter().enumerate() {
+            let expr = crate::path_hint_3!(paths, index.to_string(), {
+                OperatorExpr::compile(paths, &registry, arg)?
+            });
+            exprs.push(expr);
+        }
+        Ok(Box::new(ArithmeticOperator {
+            arithmetic,
+            args: exprs,
+        }))
+    }
+
+}
+
+impl VmOperator for ArithmeticOperator {
+    fn next(&self, input: &Bson) -> Bson {
+        let mut values = self.args.iter().map(|arg| arg.evaluate(input));
+        let first = values.next().unwrap_or(Bson::Null);
+        values
+            .try_fold(first, |result, value| self.arithmetic.apply(&result, &value))
+            .filter(|result| matches!(result, Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_)))
+            .unwrap_or(Bson::Null)
+    }
+}
@@ -0,0 +1,56 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bson::Bson;
+use crate::vm::operators::{OpRegistry, OperatorExpr, VmOperator};
+use crate::{Result, Error};
+
+/// `$concat` of an array of string expressions, null if one of them is null, missing or not a string.
+pub(crate) struct ConcatOperator {
+    args: Vec<OperatorExpr>,
+}
+
+impl ConcatOperator {
+
+    pub(crate) fn compile(paths: &mut Vec<String>, registry: OpRegistry, v: &Bson) -> Result<Box<dyn VmOperator>> {
+        let args = match v {
+            Bson::Array(args) => args,
+            _ => return Err(Error::ValidationError("$concat requires an array of arguments".to_string())),
+        };
+        let mut exprs = Vec::with_capacity(args.len());
+        for (index, arg) in args.iter().enumerate() {
+            let expr = crate::path_hint_3!(paths, index.to_string(), {
+                OperatorExpr::compile(paths, &registry, arg)?
+            });
+            exprs.push(expr);
+        }
+        Ok(Box::new(ConcatOperator {
+            args: exprs,
+        }))
+    }
+
+}
+
+impl VmOperator for ConcatOperator {
+    fn next(&self, input: &Bson) -> Bson {
+        let mut result = String::new();
+        for arg in &self.args {
+            match arg.evaluate(input) {
+                Bson::String(s) => result.push_str(&s),
+                _ => return Bson::Null,
+            }
+        }
+        Bson::String(result)
+    }
+}
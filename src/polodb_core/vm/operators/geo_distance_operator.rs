@@ -0,0 +1,73 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use bson::Bson;
+use crate::geo::{Geometry, Point};
+use crate::vm::operators::{OperatorExpr, VmOperator};
+use crate::{Result, Error};
+
+/// `$geoDistance: [<geometry>, <point>]`, the distance in meters,
+/// null if the first argument is not a geometry.
+pub(crate) struct GeoDistanceOperator {
+    geometry: OperatorExpr,
+    point: Point,
+}
+
+impl GeoDistanceOperator {
+
+    pub(crate) fn compile(v: &Bson) -> Result<Box<dyn VmOperator>> {
+        let invalid = || Error::UnknownAggregationOperation("$geoDistance".to_string());
+        let (geometry, point) = match v {
+            Bson::Array(args) if args.len() == 2 => (&args[0], &args[1]),
+            _ => return Err(invalid()),
+        };
+        let geometry = match geometry {
+            Bson::String(field_name) => match field_name.strip_prefix("$") {
+                Some(stripped_field_name) => OperatorExpr::Alias(stripped_field_name.to_string()),
+                None => return Err(invalid()),
+            },
+            _ => OperatorExpr::Constant(geometry.clone()),
+        };
+        let point = match Geometry::from_bson(point)? {
+            Geometry::Point(point) => point,
+            _ => return Err(invalid()),
+        };
+        Ok(Box::new(GeoDistanceOperator {
+            geometry,
+            point,
+        }))
+    }
+
+    fn distance(&self, geometry: Option<Bson>) -> Bson {
+        match geometry.and_then(|value| Geometry::from_bson(&value).ok()) {
+            Some(geometry) => Bson::Double(geometry.distance_to(&self.point)),
+            None => Bson::Null,
+        }
+    }
+
+}
+
+impl VmOperator for GeoDistanceOperator {
+    fn next(&self, input: &Bson) -> Bson {
+        let geometry = match &self.geometry {
+            OperatorExpr::Constant(v) => Some(v.clone()),
+            OperatorExpr::Expr(op) => Some(op.next(input)),
+            OperatorExpr::Alias(field_name) => match input {
+                Bson::Document(doc) => crate::geo::field_value(doc, field_name).cloned(),
+                _ => None,
+            },
+        };
+        self.distance(geometry)
+    }
+}
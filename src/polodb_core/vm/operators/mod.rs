@@ -1,17 +1,17 @@
 mod sum_operator;
 mod op_registry;
 mod abs_operator;
+mod geo_distance_operator;
+mod concat_operator;
+mod arithmetic_operator;
 
 use bson::Bson;
+use crate::Result;
 
 pub(crate) trait VmOperator {
 
-    fn initial_value(&self) -> Bson;
-
     fn next(&self, input: &Bson) -> Bson;
 
-    fn complete(&self) -> Bson;
-
 }
 
 pub(crate) enum OperatorExpr {
@@ -20,6 +20,41 @@ pub(crate) enum OperatorExpr {
     Alias(String),
 }
 
+impl OperatorExpr {
+
+    /// An argument of an operator: a field such as `"$qty"`, an operator
+    /// such as `{ "$abs": "$qty" }`, or a constant.
+    pub(crate) fn compile(paths: &mut Vec<String>, registry: &OpRegistry, v: &Bson) -> Result<OperatorExpr> {
+        let expr = match v {
+            Bson::String(field_name) => match field_name.strip_prefix('$') {
+                Some(stripped_field_name) => OperatorExpr::Alias(stripped_field_name.to_string()),
+                None => OperatorExpr::Constant(v.clone()),
+            },
+            Bson::Document(doc) if doc.keys().next().is_some_and(|key| key.starts_with('$')) => {
+                OperatorExpr::Expr(registry.compile(paths, v)?)
+            }
+            _ => OperatorExpr::Constant(v.clone()),
+        };
+        Ok(expr)
+    }
+
+    /// The value of the expression for the document `input`, null if a field is missing.
+    pub(crate) fn evaluate(&self, input: &Bson) -> Bson {
+        match self {
+            OperatorExpr::Constant(v) => v.clone(),
+            OperatorExpr::Expr(op) => op.next(input),
+            OperatorExpr::Alias(field_name) => match input {
+                Bson::Document(doc) => crate::utils::bson::try_get_document_value(doc, field_name),
+                _ => None,
+            }.unwrap_or(Bson::Null),
+        }
+    }
+
+}
+
 pub(crate) use sum_operator::SumOperator;
 pub(crate) use abs_operator::AbsOperator;
+pub(crate) use geo_distance_operator::GeoDistanceOperator;
+pub(crate) use concat_operator::ConcatOperator;
+pub(crate) use arithmetic_operator::{Arithmetic, ArithmeticOperator};
 pub(crate) use op_registry::OpRegistry;
@@ -15,7 +15,9 @@
 use bson::{Bson, Document};
 use crate::{Error, Result};
 use crate::errors::mk_invalid_aggregate_field;
-use crate::vm::operators::{AbsOperator, SumOperator, VmOperator};
+use crate::vm::operators::{
+    AbsOperator, Arithmetic, ArithmeticOperator, ConcatOperator, GeoDistanceOperator, SumOperator, VmOperator,
+};
 
 // Reference: https://www.mongodb.com/docs/manual/reference/operator/aggregation/
 #[derive(Clone)]
@@ -39,8 +41,15 @@ impl OpRegistry {
         let (op_name, op_value) = doc.iter().next().ok_or(Error::ValidationError("Operator should have exactly one field".to_string()))?;
         let op = crate::path_hint_3!(paths, op_name.clone(), {
             match op_name.as_str() {
-                "$sum" => SumOperator::compile(op_value),
+                "$sum" => SumOperator::compile(paths, self.clone(), op_value)?,
                 "$abs" => AbsOperator::compile(paths, self.clone(), op_value)?,
+                "$geoDistance" => GeoDistanceOperator::compile(op_value)?,
+                "$concat" => ConcatOperator::compile(paths, self.clone(), op_value)?,
+                "$add" => ArithmeticOperator::compile(paths, self.clone(), Arithmetic::Add, op_value)?,
+                "$subtract" => ArithmeticOperator::compile(paths, self.clone(), Arithmetic::Subtract, op_value)?,
+                "$multiply" => ArithmeticOperator::compile(paths, self.clone(), Arithmetic::Multiply, op_value)?,
+                "$divide" => ArithmeticOperator::compile(paths, self.clone(), Arithmetic::Divide, op_value)?,
+                "$mod" => ArithmeticOperator::compile(paths, self.clone(), Arithmetic::Mod, op_value)?,
                 _ => {
                     let invalid_err = mk_invalid_aggregate_field(paths);
                     return Err(Error::InvalidField(invalid_err))
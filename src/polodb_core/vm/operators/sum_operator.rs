@@ -12,36 +12,53 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::cell::Cell;
 use bson::Bson;
-use crate::vm::operators::VmOperator;
+use crate::vm::operators::{Arithmetic, OpRegistry, OperatorExpr, VmOperator};
+use crate::Result;
 
+/// `$sum` of an expression, or of an array of expressions. The values
+/// which are not numbers are ignored, as well as the elements of an array
+/// which are not numbers if there is a single expression.
 pub(crate) struct SumOperator {
-    inner: Cell<i64>,
+    args: Vec<OperatorExpr>,
 }
 
 impl SumOperator {
 
-    pub(crate) fn compile(_v: &Bson) -> Box<dyn VmOperator> {
-        Box::new(SumOperator {
-            inner: Cell::new(0),
+    pub(crate) fn compile(paths: &mut Vec<String>, registry: OpRegistry, v: &Bson) -> Result<Box<dyn VmOperator>> {
+        let args = match v {
+            Bson::Array(args) => {
+                let mut exprs = Vec::with_capacity(args.len());
+                for (index, arg) in args.iter().enumerate() {
+                    let expr = crate::path_hint_3!(paths, index.to_string(), {
+                        OperatorExpr::compile(paths, &registry, arg)?
+                    });
+                    exprs.push(expr);
+                }
+                exprs
+            }
+            _ => vec![OperatorExpr::compile(paths, &registry, v)?],
+        };
+        Ok(Box::new(SumOperator {
+            args,
+        }))
+    }
+
+    /// Add the numbers of `values` to `sum`.
+    pub(crate) fn add_numbers<'a>(sum: Bson, values: impl IntoIterator<Item = &'a Bson>) -> Bson {
+        values.into_iter().fold(sum, |sum, value| {
+            Arithmetic::Add.apply(&sum, value).unwrap_or(sum)
         })
     }
 
 }
 
 impl VmOperator for SumOperator {
-    fn initial_value(&self) -> Bson {
-        Bson::Int64(self.inner.get())
-    }
-
-    fn next(&self, _input: &Bson) -> Bson {
-        let next = self.inner.get() + 1;
-        self.inner.set(next);
-        Bson::Int64(next)
-    }
-
-    fn complete(&self) -> Bson {
-        Bson::Int64(self.inner.get())
+    fn next(&self, input: &Bson) -> Bson {
+        let values = self.args.iter().map(|arg| arg.evaluate(input)).collect::<Vec<Bson>>();
+        match values.as_slice() {
+            [Bson::Array(items)] => SumOperator::add_numbers(Bson::Int32(0), items),
+            _ => SumOperator::add_numbers(Bson::Int32(0), &values),
+        }
     }
 }
@@ -19,7 +19,7 @@ use crate::coll::collection_info::{CollectionSpecification, IndexInfo};
 use crate::utils::str::escape_binary_to_string;
 use crate::vm::codegen::Codegen;
 use crate::{Result};
-use bson::{Bson, Document};
+use bson::{doc, Bson, Document};
 use indexmap::IndexMap;
 use std::fmt;
 use std::rc::Rc;
@@ -28,6 +28,52 @@ use crate::vm::aggregation_codegen_context::AggregationCodeGenContext;
 use crate::vm::global_variable::GlobalVariableSlot;
 use crate::vm::update_operators::UpdateOperator;
 use crate::vm::vm_external_func::VmExternalFunc;
+use crate::vm::vm_text::TEXT_SCORE_FIELD;
+use crate::vm::vm_filter_fn::ResidualFilter;
+use crate::options::Collation;
+use crate::index::{IndexBounds, IndexOrder};
+
+/// The choices of the caller on how the query of a program is executed.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct QueryPlan {
+    /// Only this index may be used to find the documents.
+    pub hint: Option<String>,
+    /// The index chosen by the planner when several indexes can find the documents.
+    pub index: Option<String>,
+    pub collation: Collation,
+    /// Scan the collection in the order of the primary keys, or of `index_order`
+    /// if it's given, even if the primary key or an index could find the documents.
+    pub collection_scan: bool,
+    /// Scan the collection in the reverse order of the primary keys.
+    pub backward: bool,
+    /// The predicate checked on the documents found by the query of a pipeline.
+    pub residual_filter: Option<ResidualFilter>,
+    /// The documents failing to decode are set aside instead of failing the program,
+    /// they are read by the scan of the collection, not in the order of an index.
+    pub lenient: bool,
+    /// The documents are read in the order of this index, not in the order
+    /// of the primary keys, unless the pipeline sorts them in another order.
+    pub index_order: Option<IndexOrder>,
+}
+
+/// How a program finds the documents of the collection, described by the explain of a find.
+#[derive(Debug, Clone)]
+pub(crate) enum AccessPath {
+    /// The collection doesn't exist.
+    Empty,
+    /// The documents are given by the first stage of the pipeline, such as `$collStats`.
+    Values,
+    /// The documents are read in the order of the primary keys,
+    /// or of the index of [`SubProgram::index_order`].
+    CollectionScan(String),
+    PrimaryKey(Bson),
+    /// The documents having the `values` for the first keys of the index.
+    Index {
+        col_name: String,
+        index_name: String,
+        values: Vec<Bson>,
+    },
+}
 
 pub(crate) struct SubProgramIndexItem {
     pub col_name: String,
@@ -42,6 +88,14 @@ pub(crate) struct SubProgram {
     pub(super) index_infos: Vec<SubProgramIndexItem>,
     pub(crate) external_funcs: Vec<Box<dyn VmExternalFunc>>,
     pub(crate) update_operators: Vec<Box<dyn UpdateOperator>>,
+    pub(super) collation: Collation,
+    /// The scan of the collection reads the documents in the order of this index.
+    pub(crate) index_order: Option<IndexOrder>,
+    /// The scan in the order of the index only reads the documents within these bounds.
+    pub(crate) index_bounds: Option<IndexBounds>,
+    /// The scan of the collection reads the documents in the reverse order of the primary keys.
+    pub(crate) backward: bool,
+    pub(crate) access: AccessPath,
 }
 
 impl SubProgram {
@@ -54,6 +108,45 @@ impl SubProgram {
             index_infos: Vec::new(),
             external_funcs: Vec::new(),
             update_operators: Vec::new(),
+            collation: Collation::Simple,
+            index_order: None,
+            index_bounds: None,
+            backward: false,
+            access: AccessPath::Empty,
+        }
+    }
+
+    /// How the program finds the documents, such as `{ "stage": "IXSCAN", "indexName": "age_1" }`,
+    /// the scan in the order of an index being within `bounds`.
+    pub(crate) fn explain_plan(&self, bounds: Option<&IndexBounds>) -> Document {
+        let direction = |reverse: bool| if reverse { "backward" } else { "forward" };
+        match (&self.access, &self.index_order) {
+            (AccessPath::Empty, _) => doc! { "stage": "EOF" },
+            (AccessPath::Values, _) => doc! { "stage": "VALUES" },
+            (AccessPath::PrimaryKey(id), _) => doc! { "stage": "IDHACK", "_id": id.clone() },
+            (AccessPath::Index { index_name, values, .. }, _) => doc! {
+                "stage": "IXSCAN",
+                "indexName": index_name.as_str(),
+                "keyValues": values.clone(),
+            },
+            (AccessPath::CollectionScan(_), Some(order)) => {
+                let mut plan = doc! {
+                    "stage": "IXSCAN",
+                    "indexName": order.index_name.as_str(),
+                    "keyPattern": order.index_info.keys.iter()
+                        .map(|(key, value)| (key.clone(), Bson::Int32(*value as i32)))
+                        .collect::<Document>(),
+                    "direction": direction(order.reverse),
+                };
+                if let Some(bounds) = bounds {
+                    plan.insert("indexBounds", bounds.to_document());
+                }
+                plan
+            }
+            (AccessPath::CollectionScan(_), None) => doc! {
+                "stage": "COLLSCAN",
+                "direction": direction(self.backward),
+            },
         }
     }
 
@@ -65,16 +158,34 @@ impl SubProgram {
         codegen.take()
     }
 
+    #[cfg(test)]
     pub(crate) fn compile_query(
         col_spec: &CollectionSpecification,
         query: &Document,
         skip_annotation: bool,
     ) -> Result<SubProgram> {
+        SubProgram::compile_query_with_plan(col_spec, query, skip_annotation, &QueryPlan::default())
+    }
+
+    pub(crate) fn compile_query_with_plan(
+        col_spec: &CollectionSpecification,
+        query: &Document,
+        skip_annotation: bool,
+        plan: &QueryPlan,
+    ) -> Result<SubProgram> {
+        if query.contains_key("$text") || plan.residual_filter.is_some() {
+            let pipeline = vec![doc! { "$match": query.clone() }];
+            return SubProgram::compile_aggregate_with_match(col_spec, pipeline, skip_annotation, plan);
+        }
         if query.is_empty() {
             return SubProgram::compile_query_all(col_spec, skip_annotation);
         }
 
         let mut codegen = Codegen::new(skip_annotation, false);
+        codegen.set_plan(plan);
+        if !plan.lenient {
+            codegen.set_index_range(col_spec, query);
+        }
 
         codegen.emit_query_layout(
             col_spec,
@@ -97,8 +208,10 @@ impl SubProgram {
         update: &Document,
         skip_annotation: bool,
         is_many: bool,
+        plan: &QueryPlan,
     ) -> Result<SubProgram> {
         let mut codegen = Codegen::new(skip_annotation, true);
+        codegen.set_plan(plan);
 
         let has_indexes = !col_spec.indexes.is_empty();
         let index_item_id: u32 = if has_indexes {
@@ -114,16 +227,17 @@ impl SubProgram {
             col_spec,
             query,
             |codegen| -> Result<()> {
+                // the document before the update is kept to update the index
                 if has_indexes {
-                    codegen.emit(DbOp::DeleteIndex);
-                    codegen.emit_u32(index_item_id);
+                    codegen.emit(DbOp::Dup);
                 }
 
-                codegen.emit_update_operation(update)?;
+                codegen.emit_update_operation(query, update)?;
 
                 if has_indexes {
-                    codegen.emit(DbOp::InsertIndex);
+                    codegen.emit(DbOp::UpdateIndex);
                     codegen.emit_u32(index_item_id);
+                    codegen.emit(DbOp::Pop);
                 }
 
                 codegen.emit(DbOp::Pop);
@@ -142,8 +256,10 @@ impl SubProgram {
         query: Option<&Document>,
         skip_annotation: bool,
         is_many: bool,
+        plan: &QueryPlan,
     ) -> Result<SubProgram> {
         let mut codegen = Codegen::new(skip_annotation, true);
+        codegen.set_plan(plan);
 
         let has_indexes = !col_spec.indexes.is_empty();
         let index_item_id: u32 = if has_indexes {
@@ -242,6 +358,7 @@ impl SubProgram {
         let close_label = codegen.new_label();
 
         codegen.emit_open(col_name.into());
+        codegen.set_access(AccessPath::CollectionScan(col_name.to_string()));
 
         codegen.emit_goto(DbOp::Rewind, close_label);
 
@@ -264,10 +381,20 @@ impl SubProgram {
     }
 
     // If the first pipeline is $match, the process can be optimized.
+    #[cfg(test)]
     pub(crate) fn compile_aggregate(
         col_spec: &CollectionSpecification,
         pipeline: impl IntoIterator<Item = Document>,
         skip_annotation: bool,
+    ) -> Result<SubProgram> {
+        SubProgram::compile_aggregate_with_plan(col_spec, pipeline, skip_annotation, &QueryPlan::default())
+    }
+
+    pub(crate) fn compile_aggregate_with_plan(
+        col_spec: &CollectionSpecification,
+        pipeline: impl IntoIterator<Item = Document>,
+        skip_annotation: bool,
+        plan: &QueryPlan,
     ) -> Result<SubProgram> {
         let pipeline_vec: Vec<Document> = pipeline.into_iter().collect();
         if pipeline_vec.is_empty() {
@@ -276,7 +403,15 @@ impl SubProgram {
 
         let first = pipeline_vec.first().unwrap();
         if first.len() == 1 && first.contains_key("$match") {
-            return SubProgram::compile_aggregate_with_match(col_spec, pipeline_vec, skip_annotation);
+            return SubProgram::compile_aggregate_with_match(col_spec, pipeline_vec, skip_annotation, plan);
+        }
+        if let Some(Bson::Document(sort)) = first.get("$sort") {
+            // an empty $match lets the scan read the documents in the order of an index
+            if natural_order(first).is_some() || (first.len() == 1 && IndexOrder::find(&col_spec.indexes, sort).is_some()) {
+                let mut pipeline_vec = pipeline_vec;
+                pipeline_vec.insert(0, doc! { "$match": {} });
+                return SubProgram::compile_aggregate_with_match(col_spec, pipeline_vec, skip_annotation, plan);
+            }
         }
 
         let mut codegen = Codegen::new(skip_annotation, false);
@@ -290,6 +425,7 @@ impl SubProgram {
         codegen.emit_aggregation_before_query(&mut ctx, &pipeline_vec)?;
 
         codegen.emit_open(col_spec.name().into());
+        codegen.set_access(AccessPath::CollectionScan(col_spec.name().to_string()));
 
         codegen.emit_goto(DbOp::Rewind, close_label);
 
@@ -315,14 +451,16 @@ impl SubProgram {
     // If the first pipeline is $match, the process will leverage the index.
     pub(crate) fn compile_aggregate_with_match(
         col_spec: &CollectionSpecification,
-        pipeline_vec: Vec<Document>,
+        mut pipeline_vec: Vec<Document>,
         skip_annotation: bool,
+        plan: &QueryPlan,
     ) -> Result<SubProgram> {
         let mut codegen = Codegen::new(skip_annotation, false);
+        codegen.set_plan(plan);
         let first_doc = pipeline_vec.first().unwrap();
         let query_doc_value = first_doc.get("$match").unwrap();
-        let query_doc = match query_doc_value {
-            Bson::Document(doc) => doc,
+        let mut query_doc = match query_doc_value {
+            Bson::Document(doc) => doc.clone(),
             t => {
                 let name = format!("{}", t);
                 return Err(FieldTypeUnexpectedStruct {
@@ -333,6 +471,50 @@ impl SubProgram {
             },
         };
 
+        // the documents found are scored by the $text search stage,
+        // the score is removed once the pipeline doesn't need it anymore
+        if let Some(text) = query_doc.remove("$text") {
+            pipeline_vec.insert(1, doc! { "$text": text });
+            pipeline_vec.push(doc! { "$unset": TEXT_SCORE_FIELD });
+        }
+
+        // the documents are read in the order they are stored in, scanning the collection
+        if let Some(backward) = pipeline_vec.get(1).and_then(natural_order) {
+            pipeline_vec.remove(1);
+            codegen.set_natural_order(backward);
+        }
+
+        // the $sort following a scan of the collection is done by reading
+        // the documents in the order of an index, instead of buffering them,
+        // a scan of the collection asked by the caller reads no index it wasn't given
+        let index_order = match pipeline_vec.get(1).and_then(|stage| stage.get("$sort")) {
+            Some(Bson::Document(sort)) if pipeline_vec[1].len() == 1 && !plan.lenient && codegen.scans_collection(col_spec, &query_doc) => {
+                match &plan.index_order {
+                    Some(order) => IndexOrder::find(std::iter::once((&order.index_name, &order.index_info)), sort),
+                    None if plan.collection_scan => None,
+                    None => IndexOrder::find(&col_spec.indexes, sort),
+                }
+            }
+            _ => None,
+        };
+        if index_order.is_some() {
+            pipeline_vec.remove(1);
+            codegen.set_index_order(index_order);
+        } else if plan.index_order.is_some() {
+            codegen.set_index_order(plan.index_order.clone());
+        }
+        // the range of the query on the field of an index bounds its scan
+        if !plan.lenient {
+            codegen.set_index_range(col_spec, &query_doc);
+        }
+
+        // the documents found are checked by the residual filter first,
+        // unless the pipeline tells where to check them
+        let has_filter_fn = pipeline_vec.iter().any(|stage| stage.contains_key("$filterFn"));
+        if plan.residual_filter.is_some() && !has_filter_fn {
+            pipeline_vec.insert(1, doc! { "$filterFn": Bson::Null });
+        }
+
         let ctx_ref = Rc::new(RefCell::new(AggregationCodeGenContext::default()));
         let ctx_ref2 = ctx_ref.clone();
         {
@@ -341,7 +523,7 @@ impl SubProgram {
         }
         codegen.emit_query_layout(
             col_spec,
-            query_doc,
+            &query_doc,
             |codegen: &mut Codegen| -> Result<()> {
                 let ctx_ref = ctx_ref.clone();
                 let mut ctx = ctx_ref.borrow_mut();
@@ -359,6 +541,37 @@ impl SubProgram {
         Ok(codegen.take())
     }
 
+    /// Compile a pipeline running on the documents provided by the source stage,
+    /// such as `$collStats`, instead of the documents of a collection.
+    pub(crate) fn compile_aggregate_with_values(
+        values: Vec<Document>,
+        pipeline: &[Document],
+        skip_annotation: bool,
+    ) -> Result<SubProgram> {
+        let mut codegen = Codegen::new(skip_annotation, false);
+        codegen.set_access(AccessPath::Values);
+        let pipeline_fun = codegen.new_label();
+
+        let mut ctx = AggregationCodeGenContext::default();
+        codegen.emit_aggregation_before_query(&mut ctx, pipeline)?;
+
+        for value in values {
+            let static_id = codegen.push_static(Bson::Document(value));
+            codegen.emit_push_value(static_id);
+            codegen.emit_goto(DbOp::Call, pipeline_fun);
+            codegen.emit_u32(1);
+        }
+
+        codegen.emit_aggregation_before_close(&ctx)?;
+        codegen.emit(DbOp::Halt);
+
+        codegen.emit_label(pipeline_fun);
+        codegen.emit_aggregation_pipeline(&mut ctx, pipeline)?;
+        codegen.emit_ret(0);
+
+        Ok(codegen.take())
+    }
+
 }
 
 fn open_bson_to_str(val: &Bson) -> Result<String> {
@@ -379,6 +592,30 @@ fn open_bson_to_str(val: &Bson) -> Result<String> {
     Ok(result)
 }
 
+/// The direction of a `{ "$sort": { "$natural": 1 | -1 } }` stage,
+/// true when the documents are read in the reverse order.
+pub(crate) fn natural_order(stage: &Document) -> Option<bool> {
+    if stage.len() != 1 {
+        return None;
+    }
+    match stage.get("$sort") {
+        Some(Bson::Document(sort)) if sort.len() == 1 => {
+            natural_direction(sort.get("$natural")?)
+        }
+        _ => None,
+    }
+}
+
+/// Whether the `$natural` order `value` is the reverse order.
+pub(crate) fn natural_direction(value: &Bson) -> Option<bool> {
+    match value {
+        Bson::Int32(order) => Some(*order < 0),
+        Bson::Int64(order) => Some(*order < 0),
+        Bson::Double(order) => Some(*order < 0.0),
+        _ => None,
+    }
+}
+
 impl fmt::Display for SubProgram {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 
@@ -536,6 +773,13 @@ impl fmt::Display for SubProgram {
                         pc += 5;
                     }
 
+                    DbOp::UpdateIndex => {
+                        let index = begin.add(pc + 1).cast::<u32>().read();
+                        let index_info = &self.index_infos[index as usize];
+                        writeln!(f, "{}: UpdateIndex(\"{}\")", pc, index_info.col_name)?;
+                        pc += 5;
+                    }
+
                     DbOp::Dup => {
                         writeln!(f, "{}: Dup", pc)?;
                         pc += 1;
@@ -582,6 +826,16 @@ impl fmt::Display for SubProgram {
                         pc += 1;
                     }
 
+                    DbOp::Fuzzy => {
+                        writeln!(f, "{}: Fuzzy", pc)?;
+                        pc += 1;
+                    }
+
+                    DbOp::JsonSchema => {
+                        writeln!(f, "{}: JsonSchema", pc)?;
+                        pc += 1;
+                    }
+
                     DbOp::Not => {
                         writeln!(f, "{}: Not", pc)?;
                         pc += 1;
@@ -742,7 +996,7 @@ impl fmt::Display for SubProgram {
 #[cfg(test)]
 mod tests {
     use crate::coll::collection_info::{CollectionSpecification, IndexInfo};
-    use crate::vm::SubProgram;
+    use crate::vm::{QueryPlan, SubProgram};
     use bson::{doc, Regex};
     use indexmap::indexmap;
     use polodb_line_diff::assert_eq;
@@ -933,12 +1187,12 @@ mod tests {
 
         col_spec.indexes.insert(
             "age_1".into(),
-            IndexInfo {
-                keys: indexmap! {
+            IndexInfo::new(
+                indexmap! {
                     "age".into() => 1,
                 },
-                options: None,
-            },
+                None,
+            ),
         );
 
         let test_doc = doc! {
@@ -967,15 +1221,30 @@ mod tests {
 43: Halt
 
 44: Label(1)
-49: GetField("name", 35)
-58: PushValue("Vincent Chan")
-63: Equal
-64: FalseJump(35)
-69: Pop
-70: Pop
-71: ResultRow
-72: Pop
-73: Goto(25)
+49: Dup
+50: Call(80, 1)
+59: FalseJump(69)
+64: Goto(113)
+
+69: Label(5, "not_this_item")
+74: Pop
+75: Goto(25)
+
+80: Label(3, "compare_function")
+85: GetField("name", 107)
+94: PushValue("Vincent Chan")
+99: Equal
+100: FalseJump(107)
+105: Pop
+106: Pop
+
+107: Label(4, "compare_function_clean")
+112: Ret0
+
+113: Label(6)
+118: ResultRow
+119: Pop
+120: Goto(25)
 "#;
         assert_eq!(expect, actual);
     }
@@ -1222,20 +1491,19 @@ mod tests {
 75: Goto(43)
 
 80: Label(0, "compare_function")
-85: GetField("age", 144)
+85: GetField("age", 135)
 94: PushValue(3)
 99: Greater
-100: FalseJump(144)
+100: FalseJump(135)
 105: Pop2(2)
-110: GetField("child", 144)
-119: GetField("age", 144)
-128: PushValue([1, 2])
-133: In
-134: FalseJump(144)
-139: Pop2(3)
-
-144: Label(1, "compare_function_clean")
-149: Ret0
+110: GetField("child.age", 135)
+119: PushValue([1, 2])
+124: In
+125: FalseJump(135)
+130: Pop2(2)
+
+135: Label(1, "compare_function_clean")
+140: Ret0
 "#;
         assert_eq!(expect, actual);
     }
@@ -1324,7 +1592,7 @@ mod tests {
             },
         };
         let program =
-            SubProgram::compile_update(&col_spec, &query_doc, &update_doc, false, true)
+            SubProgram::compile_update(&col_spec, &query_doc, &update_doc, false, true, &QueryPlan::default())
                 .unwrap();
         let actual = format!("Program:\n\n{}", program);
 
@@ -1383,12 +1651,12 @@ mod tests {
 
         col_spec.indexes.insert(
             "age_1".into(),
-            IndexInfo {
-                keys: indexmap! {
+            IndexInfo::new(
+                indexmap! {
                     "age".into() => 1,
                 },
-                options: None,
-            },
+                None,
+            ),
         );
 
         let query_doc = doc! {
@@ -1402,7 +1670,7 @@ mod tests {
             },
         };
         let program =
-            SubProgram::compile_update(&col_spec, &query_doc, &update_doc, false, true)
+            SubProgram::compile_update(&col_spec, &query_doc, &update_doc, false, true, &QueryPlan::default())
                 .unwrap();
         let actual = format!("Program:\n\n{}", program);
 
@@ -1410,10 +1678,10 @@ mod tests {
 
 0: OpenWrite("test")
 5: Rewind(25)
-10: Goto(73)
+10: Goto(70)
 
 15: Label(3)
-20: Next(73)
+20: Next(70)
 
 25: Label(6, "close")
 30: Close
@@ -1424,30 +1692,31 @@ mod tests {
 38: Goto(15)
 
 43: Label(4, "result")
-48: DeleteIndex("test")
-53: IncR2
-54: StoreR0_2(0)
-56: CallUpdateOperator(set)
-61: UpdateCurrent
-62: InsertIndex("test")
-67: Pop
-68: Goto(15)
-
-73: Label(2, "compare")
-78: Dup
-79: Call(98, 1)
-88: FalseJump(32)
-93: Goto(43)
-
-98: Label(0, "compare_function")
-103: GetField("_id", 128)
-112: PushValue(3)
-117: Greater
-118: FalseJump(128)
-123: Pop2(2)
-
-128: Label(1, "compare_function_clean")
-133: Ret0
+48: Dup
+49: IncR2
+50: StoreR0_2(0)
+52: CallUpdateOperator(set)
+57: UpdateCurrent
+58: UpdateIndex("test")
+63: Pop
+64: Pop
+65: Goto(15)
+
+70: Label(2, "compare")
+75: Dup
+76: Call(95, 1)
+85: FalseJump(32)
+90: Goto(43)
+
+95: Label(0, "compare_function")
+100: GetField("_id", 125)
+109: PushValue(3)
+114: Greater
+115: FalseJump(125)
+120: Pop2(2)
+
+125: Label(1, "compare_function_clean")
+130: Ret0
 "#;
         assert_eq!(expect, actual);
     }
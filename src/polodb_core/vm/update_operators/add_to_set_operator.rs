@@ -0,0 +1,87 @@
+use std::cmp::Ordering;
+use bson::{Bson, Document};
+use crate::vm::update_operators::{FieldPath, UpdateOperator, UpdateResult};
+use crate::{Error, Result};
+use crate::errors::{mk_invalid_query_field, CannotApplyOperationForTypes};
+
+pub(crate) struct AddToSetOperator {
+    items: Vec<(FieldPath, Vec<Bson>)>,
+}
+
+impl AddToSetOperator {
+
+    pub fn compile(doc: Document, query: &Document, name: String, path: String) -> Result<AddToSetOperator> {
+        <dyn UpdateOperator>::validate_key(&doc)?;
+        let mut items = Vec::with_capacity(doc.len());
+        for (key, value) in doc.iter() {
+            let values = match <dyn UpdateOperator>::values_to_add(value) {
+                Some(values) => values,
+                None => {
+                    return Err(Error::InvalidField(mk_invalid_query_field(
+                        name,
+                        path,
+                    )))
+                }
+            };
+            items.push((FieldPath::compile(key, query)?, values));
+        }
+        Ok(AddToSetOperator {
+            items
+        })
+    }
+
+}
+
+/// The numbers of different types are the same element, such as 1 and 1.0.
+fn contains_value(arr: &[Bson], value: &Bson) -> bool {
+    arr.iter().any(|item| {
+        item == value || matches!(crate::utils::bson::value_cmp(item, value), Ok(Ordering::Equal))
+    })
+}
+
+impl UpdateOperator for AddToSetOperator {
+
+    fn name(&self) -> &str {
+        "addToSet"
+    }
+
+    fn update(&self, value: &mut Bson) -> Result<UpdateResult> {
+        let doc = value.as_document_mut().unwrap();
+
+        let mut updated = false;
+        for (path, values) in self.items.iter() {
+            for mut slot in path.slots(doc, true)? {
+                let target = slot.get().unwrap_or(&Bson::Null);
+                let mut arr = match target.clone() {
+                    Bson::Array(arr) => arr,
+                    Bson::Null => Vec::new(),
+                    _ => {
+                        return Err(CannotApplyOperationForTypes {
+                            op_name: "$addToSet".into(),
+                            field_name: path.as_str().into(),
+                            field_type: target.to_string(),
+                            target_type: Bson::Array(values.clone()).to_string(),
+                        }
+                            .into());
+                    }
+                };
+                let len = arr.len();
+                for value in values {
+                    if !contains_value(&arr, value) {
+                        arr.push(value.clone());
+                    }
+                }
+                // the field is created even if there is nothing to add
+                if arr.len() == len && slot.get().is_some() {
+                    continue;
+                }
+                slot.set(Bson::Array(arr));
+                updated = true;
+            }
+        }
+
+        Ok(UpdateResult {
+            updated,
+        })
+    }
+}
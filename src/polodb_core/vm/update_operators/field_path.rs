@@ -0,0 +1,246 @@
+use bson::{Bson, Document};
+use crate::elem_match::ElemMatch;
+use crate::options::Collation;
+use crate::{Error, Result};
+
+/// The path of a field updated by an operator, such as `"qty"`, `"items.0.qty"`,
+/// `"items.$.qty"` for the element of `items` matched by the query,
+/// or `"items.$[].qty"` for all the elements of `items`.
+pub(crate) struct FieldPath {
+    path: String,
+    segments: Vec<Segment>,
+}
+
+enum Segment {
+    Key(String),
+    /// `$`, the first element satisfying all the conditions of the query on the array.
+    Matched(Vec<ElemMatch>),
+    /// `$[]`, all the elements of the array.
+    All,
+}
+
+/// The place of a value updated by an operator.
+pub(crate) enum Slot<'a> {
+    Field(&'a mut Document, String),
+    Element(&'a mut Bson),
+}
+
+impl FieldPath {
+
+    pub(crate) fn compile(path: &str, query: &Document) -> Result<FieldPath> {
+        let mut segments = Vec::new();
+        let mut has_matched = false;
+        for (i, key) in path.split('.').enumerate() {
+            let segment = match key {
+                "$" => {
+                    if has_matched || i == 0 {
+                        return Err(invalid_path(path, "the positional operator '$' must appear once after an array"));
+                    }
+                    has_matched = true;
+                    let array_path = path.split('.').take(i).collect::<Vec<&str>>().join(".");
+                    Segment::Matched(positional_conditions(path, &array_path, query)?)
+                }
+                "$[]" => {
+                    if i == 0 {
+                        return Err(invalid_path(path, "the positional operator '$[]' must appear after an array"));
+                    }
+                    Segment::All
+                }
+                "" => return Err(invalid_path(path, "empty field name")),
+                _ if key.starts_with('$') => {
+                    return Err(invalid_path(path, &format!("unsupported positional operator '{}'", key)));
+                }
+                _ => Segment::Key(key.to_string()),
+            };
+            segments.push(segment);
+        }
+        Ok(FieldPath {
+            path: path.to_string(),
+            segments,
+        })
+    }
+
+    #[inline]
+    pub(crate) fn as_str(&self) -> &str {
+        &self.path
+    }
+
+    pub(crate) fn is_positional(&self) -> bool {
+        self.segments.iter().any(|segment| !matches!(segment, Segment::Key(_)))
+    }
+
+    /// The places of the field in the document. With `create`, the missing
+    /// documents on the path are created, otherwise the path stops there.
+    pub(crate) fn slots<'a>(&self, doc: &'a mut Document, create: bool) -> Result<Vec<Slot<'a>>> {
+        let mut slots = Vec::new();
+        self.collect_in_document(doc, 0, create, &mut slots)?;
+        Ok(slots)
+    }
+
+    fn collect_in_document<'a>(&self, doc: &'a mut Document, depth: usize, create: bool, slots: &mut Vec<Slot<'a>>) -> Result<()> {
+        let key = match &self.segments[depth] {
+            Segment::Key(key) => key,
+            _ => return Err(invalid_path(&self.path, "the positional operator needs an array")),
+        };
+        if depth + 1 == self.segments.len() {
+            slots.push(Slot::Field(doc, key.clone()));
+            return Ok(());
+        }
+        if !doc.contains_key(key) {
+            if !create {
+                return Ok(());
+            }
+            doc.insert(key.clone(), Document::new());
+        }
+        let value = doc.get_mut(key).unwrap();
+        self.collect_in_value(value, depth + 1, create, slots)
+    }
+
+    fn collect_in_value<'a>(&self, value: &'a mut Bson, depth: usize, create: bool, slots: &mut Vec<Slot<'a>>) -> Result<()> {
+        match value {
+            Bson::Document(doc) => self.collect_in_document(doc, depth, create, slots),
+            Bson::Array(arr) => self.collect_in_array(arr, depth, create, slots),
+            _ if create => Err(invalid_path(&self.path, &format!("cannot create a field in {}", value))),
+            _ => Ok(()),
+        }
+    }
+
+    fn collect_in_array<'a>(&self, arr: &'a mut Vec<Bson>, depth: usize, create: bool, slots: &mut Vec<Slot<'a>>) -> Result<()> {
+        let position = match &self.segments[depth] {
+            Segment::Key(key) => match key.parse::<usize>() {
+                Ok(index) => Some(index),
+                Err(_) if create => {
+                    return Err(invalid_path(&self.path, &format!("cannot create the field '{}' in an array", key)));
+                }
+                Err(_) => return Ok(()),
+            },
+            Segment::Matched(conditions) => {
+                let index = arr.iter().position(|item| {
+                    conditions.iter().all(|condition| condition.matches_element(item, Collation::Simple))
+                });
+                match index {
+                    Some(index) => Some(index),
+                    None => return Err(invalid_path(&self.path, "no element of the array matches the query")),
+                }
+            }
+            Segment::All => None,
+        };
+        let is_last = depth + 1 == self.segments.len();
+        let items: Vec<&'a mut Bson> = match position {
+            Some(index) => {
+                if index >= arr.len() {
+                    if !create {
+                        return Ok(());
+                    }
+                    arr.resize(index + 1, Bson::Null);
+                    if !is_last {
+                        arr[index] = Bson::Document(Document::new());
+                    }
+                }
+                vec![&mut arr[index]]
+            }
+            None => arr.iter_mut().collect(),
+        };
+        for item in items {
+            if is_last {
+                slots.push(Slot::Element(item));
+            } else {
+                self.collect_in_value(item, depth + 1, create, slots)?;
+            }
+        }
+        Ok(())
+    }
+
+}
+
+impl Slot<'_> {
+
+    pub(crate) fn get(&self) -> Option<&Bson> {
+        match self {
+            Slot::Field(doc, key) => doc.get(key),
+            Slot::Element(value) => Some(value),
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self) -> Option<&mut Bson> {
+        match self {
+            Slot::Field(doc, key) => doc.get_mut(key.as_str()),
+            Slot::Element(value) => Some(value),
+        }
+    }
+
+    pub(crate) fn set(&mut self, value: Bson) {
+        match self {
+            Slot::Field(doc, key) => {
+                doc.insert(key.clone(), value);
+            }
+            Slot::Element(item) => {
+                **item = value;
+            }
+        }
+    }
+
+    /// Remove the field, the element of an array is replaced by null to keep the positions.
+    pub(crate) fn unset(&mut self) -> Option<Bson> {
+        match self {
+            Slot::Field(doc, key) => doc.remove(key.as_str()),
+            Slot::Element(item) => Some(std::mem::replace(*item, Bson::Null)),
+        }
+    }
+
+}
+
+/// The conditions of the query on the elements of the array at `array_path`,
+/// such as `{ "items.sku": "A1" }` or `{ "items": { "$elemMatch": { "qty": { "$gt": 5 } } } }`.
+fn positional_conditions(path: &str, array_path: &str, query: &Document) -> Result<Vec<ElemMatch>> {
+    let mut conditions = Vec::new();
+    collect_positional_conditions(path, array_path, query, &mut conditions)?;
+    if conditions.is_empty() {
+        return Err(invalid_path(path, "the positional operator did not find the match needed from the query"));
+    }
+    Ok(conditions)
+}
+
+fn collect_positional_conditions(path: &str, array_path: &str, query: &Document, conditions: &mut Vec<ElemMatch>) -> Result<()> {
+    let parse_error = |field: String| invalid_path(path, &format!("invalid condition '{}' of the query", field));
+    for (key, value) in query {
+        if key == "$and" {
+            if let Bson::Array(items) = value {
+                for item in items {
+                    if let Bson::Document(item) = item {
+                        collect_positional_conditions(path, array_path, item, conditions)?;
+                    }
+                }
+            }
+        } else if key == array_path {
+            match value {
+                Bson::Document(doc) if doc.keys().next().is_some_and(|key| key.starts_with('$')) => {
+                    if let Some(Bson::Document(elem_match)) = doc.get("$elemMatch") {
+                        conditions.push(ElemMatch::parse(elem_match).map_err(parse_error)?);
+                    }
+                    // the other operators, such as $size, are on the array itself
+                    let element_conditions: Document = doc.iter()
+                        .filter(|(key, _)| matches!(key.as_str(), "$eq" | "$gt" | "$gte" | "$lt" | "$lte" | "$in" | "$regex"))
+                        .map(|(key, value)| (key.clone(), value.clone()))
+                        .collect();
+                    if !element_conditions.is_empty() {
+                        conditions.push(ElemMatch::parse(&element_conditions).map_err(parse_error)?);
+                    }
+                }
+                Bson::Array(_) => (),
+                _ => conditions.push(ElemMatch::parse_element(value).map_err(parse_error)?),
+            }
+        } else if let Some(field) = key.strip_prefix(array_path).and_then(|rest| rest.strip_prefix('.')) {
+            if field.split('.').next().is_some_and(|key| key.parse::<usize>().is_err()) {
+                let mut query = Document::new();
+                query.insert(field, value.clone());
+                conditions.push(ElemMatch::parse(&query).map_err(parse_error)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn invalid_path(path: &str, reason: &str) -> Error {
+    Error::InvalidUpdatePath(format!("'{}': {}", path, reason))
+}
@@ -1,18 +1,18 @@
 use bson::{Bson, Document};
-use crate::vm::update_operators::{UpdateOperator, UpdateResult};
+use crate::vm::update_operators::{FieldPath, Slot, UpdateOperator, UpdateResult};
 use crate::{Error, Result};
 use crate::errors::CannotApplyOperationForTypes;
 
 pub(crate) struct IncOperator {
-    doc: Document,
+    items: Vec<(FieldPath, Bson)>,
 }
 
 impl IncOperator {
 
-    pub fn compile(doc: Document) -> Result<IncOperator> {
+    pub fn compile(doc: Document, query: &Document) -> Result<IncOperator> {
         <dyn UpdateOperator>::validate_key(&doc)?;
         Ok(IncOperator {
-            doc
+            items: <dyn UpdateOperator>::compile_fields(doc, query)?,
         })
     }
 
@@ -41,19 +41,19 @@ impl IncOperator {
         Ok(val)
     }
 
-    fn inc_field(doc: &mut Document, key: &str, value: Bson) -> Result<()> {
-        match doc.get(key) {
+    fn inc_field(slot: &mut Slot, key: &str, value: Bson) -> Result<()> {
+        match slot.get() {
             Some(Bson::Null) => {
                 return Err(Error::IncrementNullField);
             }
 
             Some(original_value) => {
                 let result = IncOperator::inc_numeric(key, original_value, &value)?;
-                doc.insert::<String, Bson>(key.into(), result);
+                slot.set(result);
             }
 
             None => {
-                doc.insert::<String, Bson>(key.into(), value);
+                slot.set(value);
             }
         }
         Ok(())
@@ -71,9 +71,11 @@ impl UpdateOperator for IncOperator {
         let doc = value.as_document_mut().unwrap();
 
         let mut updated = false;
-        for (k, v) in self.doc.iter() {
-            updated = true;
-            IncOperator::inc_field(doc, k.as_str(), v.clone())?;
+        for (path, v) in self.items.iter() {
+            for mut slot in path.slots(doc, true)? {
+                updated = true;
+                IncOperator::inc_field(&mut slot, path.as_str(), v.clone())?;
+            }
         }
 
         Ok(UpdateResult {
@@ -1,18 +1,18 @@
 use bson::{Bson, Document};
 use crate::Result;
 use crate::vm::op::{generic_cmp, DbOp};
-use crate::vm::update_operators::{UpdateOperator, UpdateResult};
+use crate::vm::update_operators::{FieldPath, UpdateOperator, UpdateResult};
 
 pub(crate) struct MinOperator {
-    doc: Document,
+    items: Vec<(FieldPath, Bson)>,
 }
 
 impl MinOperator {
 
-    pub fn compile(doc: Document) -> Result<MinOperator> {
+    pub fn compile(doc: Document, query: &Document) -> Result<MinOperator> {
         <dyn UpdateOperator>::validate_key(&doc)?;
         Ok(MinOperator {
-            doc
+            items: <dyn UpdateOperator>::compile_fields(doc, query)?,
         })
     }
 
@@ -27,12 +27,14 @@ impl UpdateOperator for MinOperator {
         let doc = value.as_document_mut().unwrap();
 
         let mut updated = false;
-        for (k, v) in self.doc.iter() {
-            let current_val = doc.get(k).unwrap_or(&Bson::Null);
-            let cmp = generic_cmp(DbOp::Less, v, current_val)?;
-            if cmp {
-                doc.insert(k.clone(), v.clone());
-                updated = true;
+        for (path, v) in self.items.iter() {
+            for mut slot in path.slots(doc, true)? {
+                let current_val = slot.get().unwrap_or(&Bson::Null);
+                let cmp = generic_cmp(DbOp::Less, v, current_val)?;
+                if cmp {
+                    slot.set(v.clone());
+                    updated = true;
+                }
             }
         }
 
@@ -7,6 +7,9 @@ mod push_operator;
 mod pop_operator;
 mod min_operator;
 mod max_operator;
+mod add_to_set_operator;
+mod pull_operator;
+mod field_path;
 
 use bson::{Bson, Document};
 use crate::Result;
@@ -33,6 +36,24 @@ impl dyn UpdateOperator {
         Ok(())
     }
 
+    /// The paths of the fields of an operator with their values.
+    pub(crate) fn compile_fields(doc: Document, query: &Document) -> Result<Vec<(FieldPath, Bson)>> {
+        doc.into_iter()
+            .map(|(key, value)| Ok((FieldPath::compile(&key, query)?, value)))
+            .collect()
+    }
+
+    /// The values added to an array: the elements of `{ "$each": [...] }`, or the value itself.
+    pub(crate) fn values_to_add(value: &Bson) -> Option<Vec<Bson>> {
+        match value {
+            Bson::Document(doc) if doc.contains_key("$each") => match doc.get("$each") {
+                Some(Bson::Array(values)) if doc.len() == 1 => Some(values.clone()),
+                _ => None,
+            },
+            _ => Some(vec![value.clone()]),
+        }
+    }
+
 }
 
 pub(crate) use set_operator::SetOperator;
@@ -44,3 +65,6 @@ pub(crate) use push_operator::PushOperator;
 pub(crate) use pop_operator::PopOperator;
 pub(crate) use min_operator::MinOperator;
 pub(crate) use max_operator::MaxOperator;
+pub(crate) use add_to_set_operator::AddToSetOperator;
+pub(crate) use pull_operator::PullOperator;
+pub(crate) use field_path::{FieldPath, Slot};
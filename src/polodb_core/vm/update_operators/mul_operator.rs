@@ -1,18 +1,18 @@
 use bson::{Bson, Document};
 use crate::errors::CannotApplyOperationForTypes;
-use crate::vm::update_operators::{UpdateOperator, UpdateResult};
+use crate::vm::update_operators::{FieldPath, Slot, UpdateOperator, UpdateResult};
 use crate::Result;
 
 pub(crate) struct MulOperator {
-    doc: Document
+    items: Vec<(FieldPath, Bson)>,
 }
 
 impl MulOperator {
 
-    pub fn compile(doc: Document) -> Result<MulOperator> {
+    pub fn compile(doc: Document, query: &Document) -> Result<MulOperator> {
         <dyn UpdateOperator>::validate_key(&doc)?;
         Ok(MulOperator {
-            doc
+            items: <dyn UpdateOperator>::compile_fields(doc, query)?,
         })
     }
 
@@ -41,15 +41,15 @@ impl MulOperator {
         Ok(val)
     }
 
-    fn mul_field(doc: &mut Document, key: &str, value: Bson) -> Result<()> {
-        match doc.get(key) {
+    fn mul_field(slot: &mut Slot, key: &str, value: Bson) -> Result<()> {
+        match slot.get() {
             Some(original_value) => {
                 let new_value = MulOperator::mul_numeric(key, original_value, &value)?;
-                doc.insert::<String, Bson>(key.into(), new_value);
+                slot.set(new_value);
             }
 
             None => {
-                doc.insert::<String, Bson>(key.into(), value);
+                slot.set(value);
             }
         }
         Ok(())
@@ -67,9 +67,11 @@ impl UpdateOperator for MulOperator {
             let doc = value.as_document_mut().unwrap();
 
             let mut updated = false;
-            for (k, v) in self.doc.iter() {
-                MulOperator::mul_field(doc, k, v.clone())?;
-                updated = true;
+            for (path, v) in self.items.iter() {
+                for mut slot in path.slots(doc, true)? {
+                    MulOperator::mul_field(&mut slot, path.as_str(), v.clone())?;
+                    updated = true;
+                }
             }
 
             Ok(UpdateResult {
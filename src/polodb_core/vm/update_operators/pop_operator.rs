@@ -1,17 +1,16 @@
 use bson::{Bson, Document};
-use indexmap::IndexMap;
 use crate::{Result, Error};
 use crate::errors::mk_invalid_query_field;
-use crate::vm::update_operators::{UpdateOperator, UpdateResult};
+use crate::vm::update_operators::{FieldPath, UpdateOperator, UpdateResult};
 
 pub(crate) struct PopOperator {
-    pop_map: IndexMap<String, bool>,
+    pop_map: Vec<(FieldPath, bool)>,
 }
 
 impl PopOperator {
 
-    pub fn compile(doc: Document, name: String, path: String) -> Result<PopOperator> {
-        let mut pop_map = IndexMap::new();
+    pub fn compile(doc: Document, query: &Document, name: String, path: String) -> Result<PopOperator> {
+        let mut pop_map = Vec::with_capacity(doc.len());
         for (key, value) in doc.iter() {
             let num = match value {
                 Bson::Int32(i) => *i as i64,
@@ -24,8 +23,8 @@ impl PopOperator {
                 }
             };
             let val = match num {
-                -1 => false,
-                1 => true,
+                -1 => true,
+                1 => false,
                 _ => {
                     return Err(Error::InvalidField(mk_invalid_query_field(
                         name,
@@ -33,7 +32,7 @@ impl PopOperator {
                     )))
                 }
             };
-            pop_map.insert(key.clone(), val);
+            pop_map.push((FieldPath::compile(key, query)?, val));
         }
         Ok(PopOperator {
             pop_map,
@@ -52,32 +51,34 @@ impl UpdateOperator for PopOperator {
         let doc = value.as_document_mut().unwrap();
 
         let mut updated = false;
-        for (k, is_first) in self.pop_map.iter() {
-            let target = doc.get(k).unwrap_or(&Bson::Null);
-            let result = match target.clone() {
-                Bson::Array(mut arr) => {
-                    if arr.is_empty() {
-                        continue;
+        for (path, is_first) in self.pop_map.iter() {
+            for mut slot in path.slots(doc, true)? {
+                let target = slot.get().unwrap_or(&Bson::Null);
+                let result = match target.clone() {
+                    Bson::Array(mut arr) => {
+                        if arr.is_empty() {
+                            continue;
+                        }
+                        if *is_first {
+                            arr.remove(0);
+                        } else {
+                            arr.pop();
+                        }
+                        Bson::Array(arr)
                     }
-                    if *is_first {
-                        arr.remove(0);
-                    } else {
-                        arr.pop();
+                    Bson::Null => {
+                        Bson::Array(Vec::new())
                     }
-                    Bson::Array(arr)
-                }
-                Bson::Null => {
-                    Bson::Array(Vec::new())
-                }
-                _ => {
-                    return Err(Error::InvalidField(mk_invalid_query_field(
-                        self.name().to_string(),
-                        k.to_string()
-                    )))
-                }
-            };
-            doc.insert(k.clone(), result);
-            updated = true;
+                    _ => {
+                        return Err(Error::InvalidField(mk_invalid_query_field(
+                            self.name().to_string(),
+                            path.as_str().to_string()
+                        )))
+                    }
+                };
+                slot.set(result);
+                updated = true;
+            }
         }
 
         Ok(UpdateResult {
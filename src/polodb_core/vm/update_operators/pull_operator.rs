@@ -0,0 +1,67 @@
+use bson::{Bson, Document};
+use crate::elem_match::ElemMatch;
+use crate::options::Collation;
+use crate::vm::update_operators::{FieldPath, UpdateOperator, UpdateResult};
+use crate::{Error, Result};
+use crate::errors::{mk_invalid_query_field, CannotApplyOperationForTypes};
+
+/// Remove the elements of the arrays equal to a value, or matching the conditions
+/// like an `$elemMatch`, such as `{ "$gte": 6 }` or `{ "score": 8 }` for the documents.
+pub(crate) struct PullOperator {
+    items: Vec<(FieldPath, ElemMatch)>,
+}
+
+impl PullOperator {
+
+    pub fn compile(doc: Document, query: &Document, path: String) -> Result<PullOperator> {
+        <dyn UpdateOperator>::validate_key(&doc)?;
+        let mut items = Vec::with_capacity(doc.len());
+        for (key, value) in doc.iter() {
+            let condition = ElemMatch::parse_element(value).map_err(|field| {
+                Error::InvalidField(mk_invalid_query_field(field, path.clone()))
+            })?;
+            items.push((FieldPath::compile(key, query)?, condition));
+        }
+        Ok(PullOperator {
+            items
+        })
+    }
+
+}
+
+impl UpdateOperator for PullOperator {
+
+    fn name(&self) -> &str {
+        "pull"
+    }
+
+    fn update(&self, value: &mut Bson) -> Result<UpdateResult> {
+        let doc = value.as_document_mut().unwrap();
+
+        let mut updated = false;
+        for (path, condition) in self.items.iter() {
+            for mut slot in path.slots(doc, false)? {
+                let arr = match slot.get_mut() {
+                    Some(Bson::Array(arr)) => arr,
+                    None => continue,
+                    Some(target) => {
+                        return Err(CannotApplyOperationForTypes {
+                            op_name: "$pull".into(),
+                            field_name: path.as_str().into(),
+                            field_type: target.to_string(),
+                            target_type: "array".into(),
+                        }
+                            .into());
+                    }
+                };
+                let len = arr.len();
+                arr.retain(|item| !condition.matches_element(item, Collation::Simple));
+                updated |= arr.len() != len;
+            }
+        }
+
+        Ok(UpdateResult {
+            updated,
+        })
+    }
+}
@@ -1,18 +1,31 @@
 use bson::{Bson, Document};
-use crate::vm::update_operators::{UpdateOperator, UpdateResult};
-use crate::Result;
-use crate::errors::CannotApplyOperationForTypes;
+use crate::vm::update_operators::{FieldPath, UpdateOperator, UpdateResult};
+use crate::{Error, Result};
+use crate::errors::{mk_invalid_query_field, CannotApplyOperationForTypes};
 
 pub(crate) struct PushOperator {
-    doc: Document,
+    items: Vec<(FieldPath, Vec<Bson>)>,
 }
 
 impl PushOperator {
 
-    pub fn compile(doc: Document) -> Result<PushOperator> {
+    pub fn compile(doc: Document, query: &Document, name: String, path: String) -> Result<PushOperator> {
         <dyn UpdateOperator>::validate_key(&doc)?;
+        let mut items = Vec::with_capacity(doc.len());
+        for (key, value) in doc.iter() {
+            let values = match <dyn UpdateOperator>::values_to_add(value) {
+                Some(values) => values,
+                None => {
+                    return Err(Error::InvalidField(mk_invalid_query_field(
+                        name,
+                        path,
+                    )))
+                }
+            };
+            items.push((FieldPath::compile(key, query)?, values));
+        }
         Ok(PushOperator {
-            doc
+            items
         })
     }
 
@@ -28,28 +41,30 @@ impl UpdateOperator for PushOperator {
         let doc = value.as_document_mut().unwrap();
 
         let mut updated = false;
-        for (k, v) in self.doc.iter() {
-            let target = doc.get(k).unwrap_or(&Bson::Null);
-            let result = match target.clone() {
-                Bson::Array(mut arr) => {
-                    arr.push(v.clone());
-                    Bson::Array(arr)
-                }
-                Bson::Null => {
-                    Bson::Array(vec![v.clone()])
-                }
-                _ => {
-                    return Err(CannotApplyOperationForTypes {
-                        op_name: "$push".into(),
-                        field_name: k.into(),
-                        field_type: target.to_string(),
-                        target_type: v.to_string(),
+        for (path, values) in self.items.iter() {
+            for mut slot in path.slots(doc, true)? {
+                let target = slot.get().unwrap_or(&Bson::Null);
+                let result = match target.clone() {
+                    Bson::Array(mut arr) => {
+                        arr.extend(values.iter().cloned());
+                        Bson::Array(arr)
                     }
-                        .into());
-                }
-            };
-            doc.insert(k.clone(), result);
-            updated = true;
+                    Bson::Null => {
+                        Bson::Array(values.clone())
+                    }
+                    _ => {
+                        return Err(CannotApplyOperationForTypes {
+                            op_name: "$push".into(),
+                            field_name: path.as_str().into(),
+                            field_type: target.to_string(),
+                            target_type: Bson::Array(values.clone()).to_string(),
+                        }
+                            .into());
+                    }
+                };
+                slot.set(result);
+                updated = true;
+            }
         }
 
         Ok(UpdateResult {
@@ -1,17 +1,18 @@
 use bson::{Bson, Document};
 use crate::errors::FieldTypeUnexpectedStruct;
-use crate::vm::update_operators::{UpdateOperator, UpdateResult};
-use crate::Result;
+use crate::vm::update_operators::{FieldPath, UpdateOperator, UpdateResult};
+use crate::{Error, Result};
 
 pub(crate) struct RenameOperator {
-    doc: Document
+    items: Vec<(FieldPath, FieldPath)>,
 }
 
 impl RenameOperator {
 
-    pub fn compile(doc: Document) -> Result<RenameOperator> {
+    pub fn compile(doc: Document, query: &Document) -> Result<RenameOperator> {
+        let mut items = Vec::with_capacity(doc.len());
         for (key, value) in doc.iter() {
-            let _new_name = match value {
+            let new_name = match value {
                 Bson::String(new_name) => new_name.as_str(),
                 t => {
                     let name = format!("{}", t);
@@ -23,21 +24,27 @@ impl RenameOperator {
                         .into());
                 }
             };
+            let old_path = FieldPath::compile(key, query)?;
+            let new_path = FieldPath::compile(new_name, query)?;
+            for path in [&old_path, &new_path] {
+                if path.is_positional() {
+                    return Err(Error::InvalidUpdatePath(format!("'{}': $rename does not support the positional operators", path.as_str())));
+                }
+            }
+            items.push((old_path, new_path));
         }
         Ok(RenameOperator {
-            doc
+            items
         })
     }
 
-    fn rename_field(doc: &mut Document, key: &str, new_key: &str) -> Result<()> {
-        match doc.remove(key) {
-            Some(value) => {
-                doc.insert(new_key, value);
-            }
-
-            None => {
-                doc.insert(new_key, Bson::Null);
-            }
+    fn rename_field(doc: &mut Document, old_path: &FieldPath, new_path: &FieldPath) -> Result<()> {
+        let value = old_path.slots(doc, false)?
+            .into_iter()
+            .next()
+            .and_then(|mut slot| slot.unset());
+        for mut slot in new_path.slots(doc, true)? {
+            slot.set(value.clone().unwrap_or(Bson::Null));
         }
         Ok(())
     }
@@ -54,12 +61,8 @@ impl UpdateOperator for RenameOperator {
         let doc = value.as_document_mut().unwrap();
 
         let mut updated = false;
-        for (k, v) in self.doc.iter() {
-            let new_name = match v {
-                Bson::String(new_name) => new_name.as_str(),
-                _ => unreachable!()
-            };
-            RenameOperator::rename_field(doc, k, new_name)?;
+        for (old_path, new_path) in self.items.iter() {
+            RenameOperator::rename_field(doc, old_path, new_path)?;
             updated = true;
         }
 
@@ -1,17 +1,17 @@
 use bson::{Bson, Document};
-use crate::vm::update_operators::{UpdateOperator, UpdateResult};
+use crate::vm::update_operators::{FieldPath, UpdateOperator, UpdateResult};
 use crate::Result;
 
 pub(crate) struct SetOperator {
-    doc: Document
+    items: Vec<(FieldPath, Bson)>,
 }
 
 impl SetOperator {
 
-    pub fn compile(doc: Document) -> Result<SetOperator> {
+    pub fn compile(doc: Document, query: &Document) -> Result<SetOperator> {
         <dyn UpdateOperator>::validate_key(&doc)?;
         Ok(SetOperator {
-            doc
+            items: <dyn UpdateOperator>::compile_fields(doc, query)?,
         })
     }
 
@@ -27,9 +27,11 @@ impl UpdateOperator for SetOperator {
         let doc = value.as_document_mut().unwrap();
 
         let mut updated = false;
-        for (k, v) in self.doc.iter() {
-            doc.insert(k.clone(), v.clone());
-            updated = true;
+        for (path, v) in self.items.iter() {
+            for mut slot in path.slots(doc, true)? {
+                slot.set(v.clone());
+                updated = true;
+            }
         }
 
         Ok(UpdateResult {
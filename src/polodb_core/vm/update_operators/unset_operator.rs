@@ -1,15 +1,17 @@
 use bson::{Bson, Document};
 use crate::Result;
-use crate::vm::update_operators::{UpdateOperator, UpdateResult};
+use crate::vm::update_operators::{FieldPath, UpdateOperator, UpdateResult};
 
 pub(crate) struct UnsetOperator {
-    fields: Vec<String>,
+    fields: Vec<FieldPath>,
 }
 
 impl UnsetOperator {
 
-    pub fn compile(doc: &Document) -> Result<UnsetOperator> {
-        let fields = doc.keys().map(|k| k.to_string()).collect();
+    pub fn compile(doc: &Document, query: &Document) -> Result<UnsetOperator> {
+        let fields = doc.keys()
+            .map(|k| FieldPath::compile(k, query))
+            .collect::<Result<Vec<FieldPath>>>()?;
         Ok(UnsetOperator {
             fields
         })
@@ -27,8 +29,10 @@ impl UpdateOperator for UnsetOperator {
 
         let mut updated = false;
         for field in &self.fields {
-            doc.remove(field);
-            updated = true;
+            for mut slot in field.slots(doc, false)? {
+                slot.unset();
+                updated = true;
+            }
         }
 
         Ok(UpdateResult {
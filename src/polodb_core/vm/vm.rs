@@ -14,17 +14,32 @@
 
 use crate::cursor::Cursor;
 use crate::errors::{
-    FieldTypeUnexpectedStruct, RegexError, UnexpectedTypeForOpStruct,
+    DecodeError, UnexpectedTypeForOpStruct,
 };
-use crate::index::{IndexHelper, IndexHelperOperation, make_index_key_with_query_key};
+use crate::index::{IndexHelper, IndexBounds, IndexHelperOperation, IndexOrderScan, IndexPosition, make_index_key_with_query_key};
 use crate::transaction::TransactionInner;
-use crate::vm::op::{generic_cmp, DbOp};
+use crate::vm::op::{field_cmp, regex_matches, value_in, DbOp};
 use crate::vm::SubProgram;
+use crate::current_op::OpGuard;
+use crate::hooks::{CollectionHooks, HookEvent};
+use crate::change_stream::ChangePublisher;
+use crate::coll::collection_info::{DocumentLimits, ValidationInfo, VectorIndexInfo};
+use crate::strictness::BsonStrictness;
+use crate::options::ValidationLevel;
+use crate::expiry::Expiry;
+use crate::bloom::CollectionBloom;
+use crate::fuzzy::{CollectionFuzzyIndex, FuzzySearch};
+use crate::vector::CollectionVectorIndex;
+use crate::db::document_delta::{encoded_delta, DELTA_MIN_DOCUMENT_SIZE};
+use crate::profiler::ProfileRecorder;
+use crate::record_cache::RecordCache;
 use crate::{Error, Metrics, Result};
-use bson::{Bson, Document};
-use regex::RegexBuilder;
+use bson::{Bson, Document, RawDocument};
+use crate::elem_match::{build_regex, ElemMatch};
 use std::cell::Cell;
+use std::sync::Arc;
 use std::cmp::Ordering;
+use std::time::{Duration, Instant};
 use crate::vm::vm_external_func::VmExternalFuncStatus;
 
 macro_rules! try_vm {
@@ -64,12 +79,61 @@ impl Default for VMFrame {
     }
 }
 
+/// The documents of a collection the VM fails to decode, set aside
+/// instead of failing the program.
+struct LenientDecode {
+    ns: String,
+    failures: Vec<DecodeError>,
+}
+
+impl LenientDecode {
+
+    /// Decode a document read at `key`, none if it is set aside.
+    fn decode(lenient: &mut Option<LenientDecode>, data: &[u8], key: Option<&[u8]>) -> Result<Option<Document>> {
+        let err = match bson::from_slice::<Document>(data) {
+            Ok(doc) => return Ok(Some(doc)),
+            Err(err) => err,
+        };
+        let lenient = match lenient {
+            Some(lenient) => lenient,
+            None => return Err(err.into()),
+        };
+        // the key is the name of the collection followed by the primary key
+        let id = key
+            .and_then(|key| crate::utils::bson::split_stacked_keys(key).ok())
+            .and_then(|mut keys| keys.pop());
+        lenient.failures.push(DecodeError {
+            ns: lenient.ns.clone(),
+            id,
+            data: data.to_vec(),
+            reason: err.to_string(),
+        });
+        Ok(None)
+    }
+
+}
+
+/// The document an entry of an index refers to.
+enum IndexedDocument {
+    Found(Bson),
+    /// The document fails to decode, it is set aside.
+    SetAside,
+    Missing,
+}
+
 pub(crate) struct VM {
     txn: TransactionInner,
     pub(crate) state: VmState,
     pc: *const u8,
     r0: i32, // usually the logic register
     r1: Option<Cursor>,
+    /// The cursor is opened to write, it must stay on the documents found.
+    writable: bool,
+    /// The generation of the record cache read before the cursor was opened,
+    /// the documents read by the cursor are cached at this generation.
+    cache_generation: Option<u64>,
+    /// Replaces the cursor when the collection is read in the order of an index.
+    ordered: Option<IndexOrderScan>,
     pub(crate) r2: i64, // usually the counter
     r3: usize,
     pub(crate) r4: i64,
@@ -79,13 +143,60 @@ pub(crate) struct VM {
     global_vars: Vec<Bson>,
     index_value: Option<Bson>,
     metrics: Metrics,
+    op: Option<&'static str>,
+    elapsed: Duration,
+    docs_examined: u64,
+    docs_returned: u64,
+    profile: Option<ProfileRecorder>,
+    op_guard: Option<OpGuard>,
+    hooks: Option<Arc<CollectionHooks>>,
+    changes: Option<ChangePublisher>,
+    validation: Option<(String, ValidationInfo)>,
+    capped: bool,
+    expiry: Option<Expiry>,
+    /// The bloom filter of the collection, with the prefixes of the indexes on its field.
+    bloom: Option<(Arc<CollectionBloom>, Vec<Vec<u8>>)>,
+    /// The fuzzy index of the collection, the updated documents are added to it.
+    fuzzy_index: Option<Arc<CollectionFuzzyIndex>>,
+    /// The encoded primary keys of the only documents the scan of the collection reads,
+    /// in the reverse order of the keys.
+    candidates: Option<Vec<Vec<u8>>>,
+    /// Keep the encoding of the documents read, the rows are the documents as they are stored.
+    raw_rows: bool,
+    /// Don't decode the documents read, nothing compares them.
+    lazy_rows: bool,
+    raw_document: Option<Vec<u8>>,
+    /// The document read by the scan, kept encoded while the filter only reads
+    /// its fields and decoded when it matches.
+    encoded: Option<Vec<u8>>,
+    /// The slots of the stack holding the encoded document.
+    encoded_slots: Vec<usize>,
+    /// The buffer of a document read before, reused to read the next one.
+    spare_buffer: Vec<u8>,
+    limits: Option<(String, DocumentLimits)>,
+    strictness: Option<(String, BsonStrictness)>,
+    /// The fields of the `2dsphere` indexes, holding GeoJSON geometries.
+    geo_fields: Vec<String>,
+    /// The `"vector"` indexes of the collection, the updated vectors are checked and added to their graphs.
+    vector_indexes: Vec<VectorIndexInfo>,
+    vector_graphs: Vec<Arc<CollectionVectorIndex>>,
+    /// The `_id` of the documents updated or deleted, for the materialized views of the collection.
+    written_ids: Option<Vec<Bson>>,
+    write_limit: Option<u64>,
+    resume_after: Option<Vec<u8>>,
+    index_resume: Option<IndexPosition>,
+    index_bounds: Option<IndexBounds>,
+    lenient: Option<LenientDecode>,
 }
 
 unsafe impl Send for VM {}
 unsafe impl Sync for VM {}
 
 impl VM {
-    pub(crate) fn new(txn: TransactionInner, program: SubProgram, metrics: Metrics) -> VM {
+    pub(crate) fn new(txn: TransactionInner, mut program: SubProgram, metrics: Metrics) -> VM {
+        for func in &mut program.external_funcs {
+            func.set_transaction(&txn);
+        }
         let stack = Vec::with_capacity(STACK_SIZE);
         let pc = program.instructions.as_ptr();
         let mut global_vars = Vec::<Bson>::new();
@@ -100,6 +211,9 @@ impl VM {
             pc,
             r0: 0,
             r1: None,
+            writable: false,
+            cache_generation: None,
+            ordered: None,
             r2: 0,
             r3: 0,
             r4: 0,
@@ -109,7 +223,239 @@ impl VM {
             global_vars,
             index_value: None,
             metrics,
+            op: None,
+            elapsed: Duration::ZERO,
+            docs_examined: 0,
+            docs_returned: 0,
+            profile: None,
+            op_guard: None,
+            hooks: None,
+            changes: None,
+            validation: None,
+            capped: false,
+            expiry: None,
+            bloom: None,
+            fuzzy_index: None,
+            candidates: None,
+            raw_rows: false,
+            lazy_rows: false,
+            raw_document: None,
+            encoded: None,
+            encoded_slots: Vec::new(),
+            spare_buffer: Vec::new(),
+            limits: None,
+            strictness: None,
+            geo_fields: Vec::new(),
+            vector_indexes: Vec::new(),
+            vector_graphs: Vec::new(),
+            written_ids: None,
+            write_limit: None,
+            resume_after: None,
+            index_resume: None,
+            index_bounds: None,
+            lenient: None,
+        }
+    }
+
+    /// Name the operation executed by the VM, the statistics of the
+    /// operation are reported to the metrics when the VM is dropped.
+    pub(crate) fn set_op(&mut self, op: &'static str) {
+        self.op = Some(op);
+    }
+
+    /// How the program finds the documents, see [`SubProgram::explain_plan`].
+    pub(crate) fn explain_plan(&self) -> Document {
+        let bounds = self.index_bounds.as_ref().or(self.program.index_bounds.as_ref());
+        self.program.explain_plan(bounds)
+    }
+
+    /// The number of documents read from the storage so far.
+    pub(crate) fn docs_examined(&self) -> u64 {
+        self.docs_examined
+    }
+
+    pub(crate) fn set_profile(&mut self, recorder: ProfileRecorder) {
+        self.profile = Some(recorder);
+    }
+
+    pub(crate) fn set_op_guard(&mut self, guard: OpGuard) {
+        self.op_guard = Some(guard);
+    }
+
+    /// Run the hooks of the collection on the updated and deleted documents.
+    pub(crate) fn set_hooks(&mut self, hooks: Arc<CollectionHooks>) {
+        self.hooks = Some(hooks);
+    }
+
+    /// Publish the updated and deleted documents to the change streams of the collection.
+    pub(crate) fn set_change_publisher(&mut self, publisher: ChangePublisher) {
+        self.changes = Some(publisher);
+    }
+
+    /// Validate the updated documents of the collection.
+    pub(crate) fn set_validation(&mut self, col_name: &str, validation: ValidationInfo) {
+        self.validation = Some((col_name.to_string(), validation));
+    }
+
+    /// Check the updated documents against the limits of the collection.
+    pub(crate) fn set_limits(&mut self, col_name: &str, limits: DocumentLimits) {
+        self.limits = Some((col_name.to_string(), limits));
+    }
+
+    /// Check the values of the updated documents of the collection.
+    pub(crate) fn set_strictness(&mut self, col_name: &str, strictness: BsonStrictness) {
+        self.strictness = Some((col_name.to_string(), strictness));
+    }
+
+    /// Check the values of the `2dsphere` indexed fields of the updated documents.
+    pub(crate) fn set_geo_fields(&mut self, fields: Vec<String>) {
+        self.geo_fields = fields;
+    }
+
+    /// Check the values of the `"vector"` indexed fields of the updated documents,
+    /// and add them to the graphs of the indexes.
+    pub(crate) fn set_vector_indexes(&mut self, indexes: Vec<VectorIndexInfo>, graphs: Vec<Arc<CollectionVectorIndex>>) {
+        self.vector_indexes = indexes;
+        self.vector_graphs = graphs;
+    }
+
+    /// Keep the `_id` of the documents updated or deleted, returned by `take_written_ids`.
+    pub(crate) fn track_written_ids(&mut self) {
+        self.written_ids = Some(Vec::new());
+    }
+
+    pub(crate) fn take_written_ids(&mut self) -> Vec<Bson> {
+        self.written_ids.take().unwrap_or_default()
+    }
+
+    /// Stop moving the cursor once `limit` documents are updated or deleted.
+    pub(crate) fn set_write_limit(&mut self, limit: u64) {
+        self.write_limit = Some(limit);
+    }
+
+    /// Add the updated documents to the fuzzy index of the collection.
+    pub(crate) fn set_fuzzy_index(&mut self, index: Arc<CollectionFuzzyIndex>) {
+        self.fuzzy_index = Some(index);
+    }
+
+    /// Only read the documents at the encoded primary keys `pkeys` when scanning the collection,
+    /// the keys are in the reverse order.
+    pub(crate) fn set_candidates(&mut self, pkeys: Vec<Vec<u8>>) {
+        self.candidates = Some(pkeys);
+    }
+
+    /// Start the scan of the collection after the primary key `pkey`,
+    /// in the encoding of the keys.
+    pub(crate) fn set_resume_after(&mut self, pkey: Vec<u8>) {
+        self.resume_after = Some(pkey);
+    }
+
+    /// Start the scan in the order of an index after the document at `position`.
+    pub(crate) fn set_index_resume(&mut self, position: IndexPosition) {
+        self.index_resume = Some(position);
+    }
+
+    /// Only read the documents within `bounds` in the scan in the order of an index.
+    pub(crate) fn set_index_bounds(&mut self, bounds: IndexBounds) {
+        self.index_bounds = Some(bounds);
+    }
+
+    #[inline]
+    fn write_limit_reached(&self) -> bool {
+        matches!(self.write_limit, Some(limit) if self.r2 >= limit as i64)
+    }
+
+    /// Skip the documents hidden by the expiry when reading the collection.
+    pub(crate) fn set_expiry(&mut self, expiry: Expiry) {
+        self.expiry = Some(expiry);
+    }
+
+    /// Answer the lookups of the values missing from the bloom filter without reading
+    /// the storage, and add the values of the updated documents to the filter.
+    pub(crate) fn set_bloom_filter(&mut self, bloom: Arc<CollectionBloom>, index_prefixes: Vec<Vec<u8>>) {
+        self.bloom = Some((bloom, index_prefixes));
+    }
+
+    /// Keep the encoding of the documents read for the rows. If `lazy`, the documents
+    /// are not decoded at all, the program must not read their fields.
+    pub(crate) fn set_raw_rows(&mut self, lazy: bool) {
+        self.raw_rows = true;
+        self.lazy_rows = lazy;
+    }
+
+    /// Set aside the documents of the collection `ns` failing to decode instead of
+    /// failing the program, they are taken by [`VM::take_decode_failures`].
+    /// The program must not read the collection in the order of an index.
+    pub(crate) fn set_lenient(&mut self, ns: String) {
+        self.lenient = Some(LenientDecode {
+            ns,
+            failures: Vec::new(),
+        });
+    }
+
+    /// The documents set aside since the last call.
+    pub(crate) fn take_decode_failures(&mut self) -> Vec<DecodeError> {
+        match &mut self.lenient {
+            Some(lenient) => std::mem::take(&mut lenient.failures),
+            None => Vec::new(),
+        }
+    }
+
+    /// The encoding of the current row, taken once.
+    pub(crate) fn take_raw_document(&mut self) -> Option<Vec<u8>> {
+        self.raw_document.take()
+    }
+
+    /// Keep the encoding of the current row if the rows are raw,
+    /// otherwise its buffer is reused by the next read.
+    #[inline]
+    fn keep_raw_document(&mut self, data: Vec<u8>) {
+        if self.raw_rows {
+            self.raw_document = Some(data);
+        } else {
+            self.spare_buffer = data;
+        }
+    }
+
+    /// Whether the bloom filter tells the value looked up by the cursor is not in the collection.
+    fn definitely_absent(&self, value: &Bson, by_primary_key: bool) -> bool {
+        let (bloom, index_prefixes) = match &self.bloom {
+            Some(bloom) => bloom,
+            None => return false,
+        };
+        let covered = if by_primary_key {
+            bloom.field() == "_id"
+        } else {
+            let prefix = &self.r1.as_ref().unwrap().prefix_bytes;
+            index_prefixes.iter().any(|index_prefix| index_prefix == prefix)
+        };
+        if !covered || bloom.may_contain(value) {
+            return false;
+        }
+        self.metrics.add_bloom_filter_skip();
+        true
+    }
+
+    #[inline]
+    fn skips(&self, value: &Bson) -> bool {
+        match (&self.expiry, value) {
+            (Some(expiry), Bson::Document(doc)) => expiry.skips(doc),
+            _ => false,
+        }
+    }
+
+    /// Forbid the updates changing the size of the documents of a capped collection.
+    pub(crate) fn set_capped(&mut self) {
+        self.capped = true;
+    }
+
+    fn check_killed(&self) -> Result<()> {
+        if let Some(guard) = &self.op_guard {
+            if guard.is_killed() {
+                return Err(Error::OperationKilled);
+            }
         }
+        self.txn.check_killed()
     }
 
     fn prefix_bytes_from_bson(val: Bson) -> Result<Vec<u8>> {
@@ -127,53 +473,294 @@ impl VM {
     }
 
     fn open_read(&mut self, prefix: Bson) -> Result<()> {
+        if let (Some(order), Bson::String(col_name)) = (&self.program.index_order, &prefix) {
+            let mut scan = IndexOrderScan::new(&self.txn, col_name, order.clone())?;
+            // the bounds given by the caller, or the ones of the range of the query
+            if let Some(bounds) = self.index_bounds.take().or_else(|| self.program.index_bounds.clone()) {
+                scan.set_bounds(bounds)?;
+            }
+            if let Some(position) = self.index_resume.take() {
+                scan.resume_after(&position)?;
+            }
+            self.ordered = Some(scan);
+        }
+
+        // before the iterator sees the storage, a write committed after is not missed
+        self.cache_generation = self.record_cache().map(|cache| cache.generation());
         let db_iter = self.txn.rocksdb_txn.new_iterator();
         db_iter.seek_to_first();
 
         let prefix_bytes = VM::prefix_bytes_from_bson(prefix)?;
 
-        self.r1 = Some(Cursor::new(prefix_bytes, db_iter));
+        let mut cursor = Cursor::new(prefix_bytes, db_iter);
+        cursor.set_backward(self.program.backward);
+        self.r1 = Some(cursor);
         Ok(())
     }
 
     fn open_write(&mut self, prefix: Bson) -> Result<()> {
+        self.writable = true;
         let db_iter = self.txn.rocksdb_txn.new_iterator();
         db_iter.seek_to_first();
 
         let prefix_bytes = VM::prefix_bytes_from_bson(prefix)?;
 
-        self.r1 = Some(Cursor::new(prefix_bytes, db_iter));
+        let mut cursor = Cursor::new(prefix_bytes, db_iter);
+        cursor.set_backward(self.program.backward);
+        self.r1 = Some(cursor);
         Ok(())
     }
 
     fn reset_cursor(&mut self, is_empty: &Cell<bool>) -> Result<()> {
+        if self.ordered.is_some() {
+            let found = self.read_ordered_document()?;
+            is_empty.set(!found);
+            return Ok(());
+        }
+        if self.candidates.is_some() {
+            let found = self.read_candidate_document()?;
+            is_empty.set(!found);
+            return Ok(());
+        }
         let cursor = self.r1.as_mut().unwrap();
-        cursor.reset()?;
-        if cursor.has_next() {
-            let item = cursor.copy_data()?;
-            let doc = bson::from_slice(item.as_ref())?;
-            self.stack.push(Bson::Document(doc));
-            is_empty.set(false);
-        } else {
-            is_empty.set(true);
+        match self.resume_after.take() {
+            Some(pkey) => {
+                if cursor.reset_by_pkey_buf(&pkey)? {
+                    cursor.next()?;
+                }
+            }
+            None => cursor.reset()?,
         }
+        let found = self.read_current_document()?;
+        is_empty.set(!found);
         Ok(())
     }
 
-    fn find_by_primary_key(&mut self) -> Result<bool> {
-        let cursor = self.r1.as_mut().unwrap();
+    /// Push the document at the cursor, or the first following one
+    /// not hidden by the expiry, return false at the end of the collection.
+    fn read_current_document(&mut self) -> Result<bool> {
+        loop {
+            let cursor = self.r1.as_mut().unwrap();
+            if !cursor.has_next() {
+                return Ok(false);
+            }
+            let mut item = std::mem::take(&mut self.spare_buffer);
+            cursor.copy_data_into(&mut item)?;
+            self.docs_examined += 1;
+            if self.lazy_rows && self.expiry.is_none() {
+                // nothing reads the document, the row is its encoding
+                self.stack.push(Bson::Document(Document::new()));
+                self.raw_document = Some(item);
+                return Ok(true);
+            }
+            if self.expiry.is_none() && self.lenient.is_none() {
+                // the fields are read from the encoding until the document is needed
+                self.decode_encoded()?;
+                self.stack.push(Bson::Document(Document::new()));
+                self.encoded = Some(item);
+                self.encoded_slots.push(self.stack.len() - 1);
+                return Ok(true);
+            }
+            let key = self.r1.as_ref().unwrap().peek_key();
+            let doc = match LenientDecode::decode(&mut self.lenient, &item, key)? {
+                Some(doc) => Bson::Document(doc),
+                None => {
+                    self.spare_buffer = item;
+                    self.r1.as_mut().unwrap().next()?;
+                    continue;
+                }
+            };
+            if self.skips(&doc) {
+                self.spare_buffer = item;
+                self.r1.as_mut().unwrap().next()?;
+                continue;
+            }
+            self.stack.push(doc);
+            self.keep_raw_document(item);
+            return Ok(true);
+        }
+    }
 
+    #[inline]
+    fn is_encoded(&self, slot: usize) -> bool {
+        self.encoded.is_some() && self.encoded_slots.contains(&slot)
+    }
+
+    /// Put the decoded document in the slots of the stack still holding it.
+    fn decode_encoded(&mut self) -> Result<()> {
+        let data = match self.encoded.take() {
+            Some(data) => data,
+            None => return Ok(()),
+        };
+        let doc: Document = bson::from_slice(data.as_ref())?;
+        let len = self.stack.len();
+        // the last slot takes the document, the others a copy
+        let mut remaining = self.encoded_slots.iter().filter(|slot| **slot < len).count();
+        let mut doc = Some(doc);
+        for slot in self.encoded_slots.drain(..).filter(|slot| *slot < len) {
+            remaining -= 1;
+            let value = if remaining == 0 {
+                doc.take().unwrap()
+            } else {
+                doc.as_ref().unwrap().clone()
+            };
+            self.stack[slot] = Bson::Document(value);
+        }
+        self.keep_raw_document(data);
+        Ok(())
+    }
+
+    /// Decode the document read by the scan before the instruction reads it,
+    /// only reading its fields doesn't need it.
+    unsafe fn prepare_encoded(&mut self, op: DbOp) -> Result<()> {
+        let len = self.stack.len();
+        self.encoded_slots.retain(|slot| *slot < len);
+        if self.encoded_slots.is_empty() {
+            if let Some(data) = self.encoded.take() {
+                self.spare_buffer = data;
+            }
+            return Ok(());
+        }
+        // the number of values on the top of the stack read by the instruction
+        let read = match op {
+            DbOp::Goto | DbOp::Label | DbOp::Inc | DbOp::IncR2 | DbOp::IfTrue | DbOp::IfFalse
+            | DbOp::Rewind | DbOp::Next | DbOp::NextIndexValue
+            | DbOp::PushValue | DbOp::PushNull | DbOp::PushTrue | DbOp::PushFalse
+            | DbOp::PushDocument | DbOp::PushR0 | DbOp::StoreR0_2 | DbOp::GetField | DbOp::Dup
+            | DbOp::Pop | DbOp::Pop2 | DbOp::Not | DbOp::Close
+            | DbOp::SaveStackPos | DbOp::RecoverStackPos | DbOp::Call | DbOp::Ret0
+            | DbOp::ExternalIsCompleted | DbOp::LoadGlobal | DbOp::_EOF | DbOp::Halt => 0,
+            DbOp::StoreR0 | DbOp::EqualNull => 1,
+            DbOp::Equal | DbOp::Greater | DbOp::GreaterEqual | DbOp::Less | DbOp::LessEqual
+            | DbOp::In | DbOp::Regex | DbOp::Fuzzy | DbOp::ElemMatch | DbOp::JsonSchema => 2,
+            DbOp::Ret | DbOp::IfFalseRet => self.pc.add(1).cast::<u32>().read() as usize,
+            _ => len,
+        };
+        if self.encoded_slots.iter().any(|slot| slot + read >= len) {
+            self.decode_encoded()?;
+        }
+        Ok(())
+    }
+
+    /// Push the next document in the order of the index, skipping the ones hidden by the expiry.
+    fn read_ordered_document(&mut self) -> Result<bool> {
+        loop {
+            let (doc, data) = match self.ordered.as_mut().unwrap().next_document()? {
+                Some((doc, data)) => (Bson::Document(doc), data),
+                None => return Ok(false),
+            };
+            self.docs_examined += 1;
+            if self.skips(&doc) {
+                continue;
+            }
+            self.stack.push(doc);
+            self.keep_raw_document(data);
+            return Ok(true);
+        }
+    }
+
+    /// Push the document at the next candidate primary key, skipping the ones
+    /// deleted since and the ones hidden by the expiry.
+    fn read_candidate_document(&mut self) -> Result<bool> {
+        loop {
+            let pkey = match self.candidates.as_mut().unwrap().pop() {
+                Some(pkey) => pkey,
+                None => return Ok(false),
+            };
+            let cursor = self.r1.as_mut().unwrap();
+            if !cursor.reset_by_pkey_buf(&pkey)? {
+                continue;
+            }
+            let mut item = std::mem::take(&mut self.spare_buffer);
+            cursor.copy_data_into(&mut item)?;
+            self.docs_examined += 1;
+            let key = self.r1.as_ref().unwrap().peek_key();
+            let doc = match LenientDecode::decode(&mut self.lenient, &item, key)? {
+                Some(doc) => Bson::Document(doc),
+                None => {
+                    self.spare_buffer = item;
+                    continue;
+                }
+            };
+            if self.skips(&doc) {
+                self.spare_buffer = item;
+                continue;
+            }
+            self.stack.push(doc);
+            self.keep_raw_document(item);
+            return Ok(true);
+        }
+    }
+
+    /// A stage of the pipeline takes no more documents, such as an exhausted `$limit`.
+    fn scan_exhausted(&self) -> bool {
+        self.program.external_funcs.iter().any(|func| func.is_exhausted())
+    }
+
+    /// The record cache, if the documents found may be read from it.
+    fn record_cache(&self) -> Option<RecordCache> {
+        self.txn.record_cache().cloned()
+    }
+
+    /// Read the document at `key` from the record cache into `buf`.
+    fn read_cached(&self, cache: &RecordCache, key: &[u8], buf: &mut Vec<u8>) -> bool {
+        let hit = cache.get(key, buf);
+        if hit {
+            self.metrics.add_record_cache_hit();
+        } else {
+            self.metrics.add_record_cache_miss();
+        }
+        hit
+    }
+
+    fn find_by_primary_key(&mut self) -> Result<bool> {
         let top_index = self.stack.len() - 1;
         let op = &self.stack[top_index];
 
-        let result = cursor.reset_by_pkey(op)?;
-        if !result {
+        if self.definitely_absent(op, true) {
             return Ok(false);
         }
+        let mut key = self.r1.as_ref().unwrap().prefix_bytes.clone();
+        key.extend_from_slice(&crate::utils::bson::stacked_key([op])?);
+
+        // a write updates the document at the cursor, it must be read from the storage
+        let cache = if self.writable {
+            None
+        } else {
+            self.record_cache()
+        };
+        let mut buf = std::mem::take(&mut self.spare_buffer);
+        let hit = match &cache {
+            Some(cache) => self.read_cached(cache, &key, &mut buf),
+            None => false,
+        };
+        if !hit {
+            let generation = self.cache_generation;
+            let cursor = self.r1.as_mut().unwrap();
+            if !cursor.reset_by_key(&key)? {
+                self.spare_buffer = buf;
+                return Ok(false);
+            }
+            cursor.copy_data_into(&mut buf)?;
+            if let (Some(cache), Some(generation)) = (&cache, generation) {
+                cache.insert(&key, &buf, generation);
+            }
+        }
 
-        let buf = cursor.copy_data()?;
-        let doc = bson::from_slice(buf.as_ref())?;
-        self.stack.push(Bson::Document(doc));
+        self.docs_examined += 1;
+        let doc = match LenientDecode::decode(&mut self.lenient, &buf, Some(&key))? {
+            Some(doc) => Bson::Document(doc),
+            None => {
+                self.spare_buffer = buf;
+                return Ok(false);
+            }
+        };
+        if self.skips(&doc) {
+            self.spare_buffer = buf;
+            return Ok(false);
+        }
+        self.stack.push(doc);
+        self.keep_raw_document(buf);
         Ok(true)
     }
 
@@ -181,26 +768,37 @@ impl VM {
         let stack_len = self.stack.len();
         // let col_name = self.stack[stack_len - 1].as_str().expect("col_name must be string").to_string();
         let query_value = &self.stack[stack_len - 2];
+        self.index_value = Some(query_value.clone());
 
+        if self.definitely_absent(query_value, false) {
+            return Ok(false);
+        }
         let cursor = self.r1.as_mut().unwrap();
         let result = cursor.reset_by_index_value(query_value)?;
-        self.index_value = Some(query_value.clone());
 
         if !result {
             return Ok(false);
         }
 
-        let key = cursor.peek_key().expect("key must exist");
+        let key = cursor.peek_key().expect("key must exist").to_vec();
+
+        let index_value = match self.read_index_value_by_index_key(key.as_ref())? {
+            IndexedDocument::Found(doc) => doc,
+            IndexedDocument::SetAside => {
+                self.next_index_value()?;
+                return Ok(self.r0 == 1);
+            }
+            IndexedDocument::Missing => return Ok(false),
+        };
 
-        let index_value = self.read_index_value_by_index_key(key.as_ref())?;
+        self.metrics.add_find_by_index_count();
 
-        if index_value.is_none() {
-            return Ok(false);
+        if self.skips(&index_value) {
+            self.next_index_value()?;
+            return Ok(self.r0 == 1);
         }
 
-        self.stack.push(index_value.unwrap());
-
-        self.metrics.add_find_by_index_count();
+        self.stack.push(index_value);
 
         Ok(true)
     }
@@ -208,7 +806,7 @@ impl VM {
     fn read_index_value_by_index_key(
         &mut self,
         index_key: &[u8],
-    ) -> Result<Option<Bson>> {
+    ) -> Result<IndexedDocument> {
         let slices = crate::utils::bson::split_stacked_keys(index_key)?;
         let pkey = slices.last().expect("pkey must exist");
 
@@ -216,35 +814,61 @@ impl VM {
 
         let pkey_in_kv = crate::utils::bson::stacked_key(vec![col_name, pkey])?;
 
-        let db_iter = self.txn.rocksdb_txn.new_iterator();
-        db_iter.seek_to_first();
-
-        db_iter.seek(pkey_in_kv.as_slice());
+        let cache = self.record_cache();
+        let mut buf = std::mem::take(&mut self.spare_buffer);
+        let hit = match &cache {
+            Some(cache) => self.read_cached(cache, &pkey_in_kv, &mut buf),
+            None => false,
+        };
+        if !hit {
+            let generation = cache.as_ref().map(RecordCache::generation);
+            let db_iter = self.txn.rocksdb_txn.new_iterator();
+            db_iter.seek(pkey_in_kv.as_slice());
+
+            if !db_iter.valid() {
+                self.spare_buffer = buf;
+                return Ok(IndexedDocument::Missing);
+            }
+            let current_key = db_iter.copy_key()?;
 
-        if !db_iter.valid() {
-            return Ok(None);
-        }
-        let current_key = db_iter.copy_key()?;
+            if current_key.as_slice().cmp(pkey_in_kv.as_slice()) != Ordering::Equal {
+                self.spare_buffer = buf;
+                return Ok(IndexedDocument::Missing);
+            }
 
-        if current_key.as_slice().cmp(pkey_in_kv.as_slice()) != Ordering::Equal {
-            return Ok(None);
+            db_iter.copy_data_into(&mut buf)?;
+            if let (Some(cache), Some(generation)) = (&cache, generation) {
+                cache.insert(&pkey_in_kv, &buf, generation);
+            }
         }
 
-        let buf = db_iter.copy_data()?;
-        let doc = bson::from_slice(buf.as_ref())?;
+        self.docs_examined += 1;
+        let doc = match LenientDecode::decode(&mut self.lenient, &buf, Some(&pkey_in_kv))? {
+            Some(doc) => doc,
+            None => {
+                self.spare_buffer = buf;
+                return Ok(IndexedDocument::SetAside);
+            }
+        };
+        self.keep_raw_document(buf);
 
-        Ok(Some(Bson::Document(doc)))
+        Ok(IndexedDocument::Found(Bson::Document(doc)))
     }
 
     fn next(&mut self) -> Result<()> {
+        self.check_killed()?;
+        if self.ordered.is_some() {
+            self.r0 = self.read_ordered_document()? as i32;
+            return Ok(());
+        }
+        if self.candidates.is_some() {
+            self.r0 = self.read_candidate_document()? as i32;
+            return Ok(());
+        }
         let cursor = self.r1.as_mut().unwrap();
         cursor.next()?;
 
-        if cursor.has_next() {
-            let bytes = cursor.copy_data()?;
-            let doc = bson::from_slice(bytes.as_ref())?;
-            self.stack.push(Bson::Document(doc));
-
+        if self.read_current_document()? {
             debug_assert!(
                 self.stack.len() <= 64,
                 "stack too large: {}",
@@ -260,41 +884,57 @@ impl VM {
     }
 
     fn next_index_value(&mut self) -> Result<()> {
-        let cursor = self.r1.as_mut().unwrap();
-        cursor.next()?;
-        let current_key = cursor.peek_key();
-        if current_key.is_none() {
-            self.r0 = 0;
-            return Ok(());
-        }
+        self.check_killed()?;
+        loop {
+            let cursor = self.r1.as_mut().unwrap();
+            cursor.next()?;
+            let current_key = cursor.peek_key();
+            if current_key.is_none() {
+                self.r0 = 0;
+                return Ok(());
+            }
 
-        let index_value = self.index_value.as_ref().expect("index_value must exist");
+            let index_value = self.index_value.as_ref().expect("index_value must exist");
 
-        let key_buffer = make_index_key_with_query_key(cursor.prefix_bytes.as_slice(), index_value)?;
+            let key_buffer = make_index_key_with_query_key(cursor.prefix_bytes.as_slice(), index_value)?;
 
-        let current_key = current_key.unwrap();
-        if !current_key.starts_with(key_buffer.as_slice()) {
-            self.r0 = 0;
-            return Ok(());
-        }
+            let current_key = current_key.unwrap().to_vec();
+            if !current_key.starts_with(key_buffer.as_slice()) {
+                self.r0 = 0;
+                return Ok(());
+            }
 
-        let value_opt = self.read_index_value_by_index_key(current_key.as_ref())?;
-        if value_opt.is_none() {
-            self.r0 = 0;
-            return Ok(());
-        }
+            let value = match self.read_index_value_by_index_key(current_key.as_ref())? {
+                IndexedDocument::Found(doc) => doc,
+                IndexedDocument::SetAside => continue,
+                IndexedDocument::Missing => {
+                    self.r0 = 0;
+                    return Ok(());
+                }
+            };
+            if self.skips(&value) {
+                continue;
+            }
 
-        self.stack.push(value_opt.unwrap());
+            self.stack.push(value);
 
-        self.r0 = 1;
+            self.r0 = 1;
 
-        Ok(())
+            return Ok(());
+        }
     }
 
     pub(crate) fn stack_top(&self) -> &Bson {
         &self.stack[self.stack.len() - 1]
     }
 
+    /// Move the row out of the stack instead of copying it,
+    /// the program only pops the row after giving it.
+    pub(crate) fn take_stack_top(&mut self) -> Bson {
+        let len = self.stack.len();
+        std::mem::replace(&mut self.stack[len - 1], Bson::Null)
+    }
+
     #[inline]
     fn reset_location(&mut self, location: u32) {
         unsafe {
@@ -319,11 +959,6 @@ impl VM {
         Ok(())
     }
 
-    fn array_size(&mut self) -> Result<usize> {
-        let top = self.stack.len() - 1;
-        let doc = crate::try_unwrap_array!("ArraySize", &self.stack[top]);
-        Ok(doc.len())
-    }
 
     fn array_push(&mut self) -> Result<()> {
         let st = self.stack.len();
@@ -388,19 +1023,96 @@ impl VM {
             return Ok(());
         }
         let top_index = self.stack.len() - 1;
+        if let Some(hooks) = &self.hooks {
+            let doc = self.stack[top_index].as_document_mut().unwrap();
+            hooks.run_pre(HookEvent::Update, doc)?;
+        }
+        if let Some((col_name, strictness)) = &self.strictness {
+            let doc = self.stack[top_index].as_document_mut().unwrap();
+            crate::strictness::check(*strictness, col_name, doc)?;
+        }
         let top_value = &self.stack[top_index];
 
         let txn = &self.txn;
         let doc = top_value.as_document().unwrap();
         let doc_buf = bson::to_vec(doc)?;
 
+        if let Some((col_name, limits)) = &self.limits {
+            limits.check(col_name, doc, doc_buf.len())?;
+        }
+        crate::geo::check_fields(&self.geo_fields, doc)?;
+        crate::vector::check_fields(&self.vector_indexes, doc)?;
+
+        if self.capped {
+            let old_size = self.r1.as_ref().unwrap().copy_data()?.len();
+            if old_size != doc_buf.len() {
+                return Err(Error::CappedCollection("cannot change the size of a document".to_string()));
+            }
+        }
+
+        let needs_old_doc = self.changes.is_some() || matches!(
+            &self.validation,
+            Some((_, validation)) if validation.validation_level == ValidationLevel::Moderate,
+        );
+        let old_doc = if needs_old_doc {
+            let data = self.r1.as_ref().unwrap().copy_data()?;
+            Some(bson::from_slice::<Document>(&data)?)
+        } else {
+            None
+        };
+
+        if let Some((col_name, validation)) = &self.validation {
+            validation.validate(col_name, old_doc.as_ref(), doc)?;
+        }
+        if let Some(hooks) = &self.hooks {
+            hooks.validate(doc)?;
+        }
+
+        // a few fields changed in a large document are written as a delta
+        let delta = if doc_buf.len() >= DELTA_MIN_DOCUMENT_SIZE {
+            match &old_doc {
+                Some(old_doc) => encoded_delta(old_doc, doc, doc_buf.len())?,
+                None => {
+                    let data = self.r1.as_ref().unwrap().copy_data()?;
+                    let old_doc = bson::from_slice::<Document>(&data)?;
+                    encoded_delta(&old_doc, doc, doc_buf.len())?
+                }
+            }
+        } else {
+            None
+        };
+
         let updated = {
             let cursor = self.r1.as_mut().unwrap();
-            cursor.update_current(txn, &doc_buf)?
+            match &delta {
+                Some(delta) => cursor.merge_current(txn, delta)?,
+                None => cursor.update_current(txn, &doc_buf)?,
+            }
         };
+        if updated && delta.is_some() {
+            self.metrics.add_delta_update();
+        }
 
         if updated {
             self.r4 += 1;
+            if let Some((bloom, _)) = &self.bloom {
+                bloom.insert_doc(doc);
+            }
+            if let Some(index) = &self.fuzzy_index {
+                index.insert_doc(doc);
+            }
+            for graph in &self.vector_graphs {
+                graph.insert_doc(doc);
+            }
+            if let (Some(ids), Some(id)) = (&mut self.written_ids, doc.get("_id")) {
+                ids.push(id.clone());
+            }
+            if let Some(hooks) = &self.hooks {
+                hooks.defer_post(txn, HookEvent::Update, doc);
+            }
+            if let (Some(publisher), Some(old_doc)) = (&self.changes, &old_doc) {
+                publisher.defer_update(txn, old_doc, doc)?;
+            }
         }
 
         Ok(())
@@ -454,8 +1166,35 @@ impl VM {
         Ok(())
     }
 
+    fn update_index(&mut self, index_info_id: u32) -> Result<()> {
+        let info = &self.program.index_infos[index_info_id as usize];
+
+        let index_meta = &info.indexes;
+
+        let old_doc = self.stack[self.stack.len() - 2].as_document().unwrap();
+        let new_doc = self.stack[self.stack.len() - 1].as_document().unwrap();
+        let pkey = new_doc.get("_id").unwrap();
+        let txn = &self.txn;
+
+        for (index_name, index_info) in index_meta {
+            IndexHelper::update_with_index_info(
+                old_doc,
+                new_doc,
+                info.col_name.as_str(),
+                pkey,
+                index_name.as_str(),
+                index_info,
+                txn,
+            )?;
+        }
+
+        Ok(())
+    }
+
     fn ret(&mut self, return_size: usize) {
         let frame = self.frames.pop().unwrap();
+        // the slots of the frame are reused by the values returned
+        self.encoded_slots.retain(|slot| *slot < frame.stack_begin_pos);
 
         let clone_start_pos = self.stack.len() - return_size;
         for i in 0..return_size {
@@ -483,13 +1222,30 @@ impl VM {
     }
 
     pub(crate) fn execute(&mut self) -> Result<()> {
+        if self.op.is_none() || (self.profile.is_none() && !self.metrics.is_enabled()) {
+            return self.execute_program();
+        }
+        let start = Instant::now();
+        let result = self.execute_program();
+        self.elapsed += start.elapsed();
+        if self.state == VmState::HasRow {
+            self.docs_returned += 1;
+        }
+        result
+    }
+
+    fn execute_program(&mut self) -> Result<()> {
         if self.state == VmState::Halt {
             return Err(Error::VmIsHalt);
         }
+        try_vm!(self, self.check_killed());
         self.state = VmState::Running;
         unsafe {
             loop {
                 let op = self.pc.cast::<DbOp>().read();
+                if self.encoded.is_some() {
+                    try_vm!(self, self.prepare_encoded(op));
+                }
                 match op {
                     DbOp::Goto => {
                         let location = self.pc.add(1).cast::<u32>().read();
@@ -568,7 +1324,11 @@ impl VM {
                     }
 
                     DbOp::Next => {
-                        try_vm!(self, self.next());
+                        if self.write_limit_reached() || self.scan_exhausted() {
+                            self.r0 = 0;
+                        } else {
+                            try_vm!(self, self.next());
+                        }
                         if self.r0 != 0 {
                             let location = self.pc.add(1).cast::<u32>().read();
                             self.reset_location(location);
@@ -578,7 +1338,11 @@ impl VM {
                     }
 
                     DbOp::NextIndexValue => {
-                        try_vm!(self, self.next_index_value());
+                        if self.write_limit_reached() {
+                            self.r0 = 0;
+                        } else {
+                            try_vm!(self, self.next_index_value());
+                        }
                         if self.r0 != 0 {
                             let location = self.pc.add(1).cast::<u32>().read();
                             self.reset_location(location);
@@ -647,22 +1411,36 @@ impl VM {
 
                         let key = self.borrow_static(key_stat_id as usize);
                         let key_name = key.as_str().unwrap();
-                        let top = &self.stack[self.stack.len() - 1];
-                        let doc = match top {
-                            Bson::Document(doc) => doc,
-                            _ => {
-                                let name = format!("{}", top);
-                                let err = FieldTypeUnexpectedStruct {
-                                    field_name: key_name.into(),
-                                    expected_ty: "Document".into(),
-                                    actual_ty: name,
-                                };
-                                self.state = VmState::Halt;
-                                return Err(err.into());
+                        let encoded_value = match &self.encoded {
+                            Some(item) if self.encoded_slots.contains(&(self.stack.len() - 1)) => {
+                                let value = RawDocument::from_bytes(item)
+                                    .map_err(|err| Error::from(bson::de::Error::from(err)))
+                                    .and_then(|raw| crate::utils::bson::try_get_raw_document_value(raw, key_name));
+                                Some(value)
                             }
+                            _ => None,
+                        };
+                        if let Some(value) = encoded_value {
+                            match try_vm!(self, value) {
+                                Some(val) => {
+                                    self.r0 = 1;
+                                    self.stack.push(val);
+                                    self.pc = self.pc.add(9);
+                                }
+                                None => {
+                                    self.r0 = 0;
+                                    self.reset_location(location);
+                                }
+                            }
+                            continue;
+                        }
+                        // a value which isn't a document has no field
+                        let value = match &self.stack[self.stack.len() - 1] {
+                            Bson::Document(doc) => crate::utils::bson::try_get_document_value(doc, key_name),
+                            _ => None,
                         };
 
-                        match crate::utils::bson::try_get_document_value(doc, key_name) {
+                        match value {
                             Some(val) => {
                                 self.r0 = 1;
                                 self.stack.push(val);
@@ -703,10 +1481,14 @@ impl VM {
                         self.pc = self.pc.add(5);
                     }
 
+                    // the values which are not arrays have no size
                     DbOp::ArraySize => {
-                        let size = try_vm!(self, self.array_size());
+                        let size = match self.stack_top() {
+                            Bson::Array(arr) => Bson::from(arr.len() as i64),
+                            _ => Bson::Null,
+                        };
 
-                        self.stack.push(Bson::from(size as i64));
+                        self.stack.push(size);
 
                         self.pc = self.pc.add(1);
                     }
@@ -736,6 +1518,11 @@ impl VM {
                     }
 
                     DbOp::DeleteCurrent => {
+                        if let Some(hooks) = &self.hooks {
+                            let top_index = self.stack.len() - 1;
+                            let doc = self.stack[top_index].as_document_mut().unwrap();
+                            try_vm!(self, hooks.run_pre(HookEvent::Delete, doc));
+                        }
                         let txn = &self.txn;
                         let deleted = {
                             let cursor = self.r1.as_mut().unwrap();
@@ -749,6 +1536,16 @@ impl VM {
                         };
                         if deleted {
                             self.r2 += 1;
+                            let doc = self.stack[self.stack.len() - 1].as_document().unwrap();
+                            if let Some(hooks) = &self.hooks {
+                                hooks.defer_post(&self.txn, HookEvent::Delete, doc);
+                            }
+                            if let Some(publisher) = &self.changes {
+                                try_vm!(self, publisher.defer_delete(&self.txn, doc));
+                            }
+                            if let (Some(ids), Some(id)) = (&mut self.written_ids, doc.get("_id")) {
+                                ids.push(id.clone());
+                            }
                         }
 
                         self.pc = self.pc.add(1);
@@ -770,7 +1567,18 @@ impl VM {
                         self.pc = self.pc.add(5);
                     }
 
+                    DbOp::UpdateIndex => {
+                        let index_info_id = self.pc.add(1).cast::<u32>().read();
+
+                        self.update_index(index_info_id)?;
+
+                        self.pc = self.pc.add(5);
+                    }
+
                     DbOp::Dup => {
+                        if self.is_encoded(self.stack.len() - 1) {
+                            self.encoded_slots.push(self.stack.len());
+                        }
                         self.stack.push(self.stack.last().unwrap().clone());
                         self.pc = self.pc.add(1);
                     }
@@ -797,7 +1605,7 @@ impl VM {
                         let val1 = &self.stack[self.stack.len() - 2];
                         let val2 = &self.stack[self.stack.len() - 1];
 
-                        let cmp = try_vm!(self, generic_cmp(op, val1, val2));
+                        let cmp = try_vm!(self, field_cmp(op, val1, val2, self.program.collation));
 
                         self.r0 = if cmp { 1 } else { 0 };
 
@@ -808,20 +1616,13 @@ impl VM {
                     // -1: Array
                     // -2: value
                     //
-                    // check value in Array
+                    // check value, or an element of value, in Array
                     DbOp::In => {
                         let top1 = &self.stack[self.stack.len() - 1];
                         let top2 = &self.stack[self.stack.len() - 2];
 
-                        self.r0 = 0;
-
-                        for item in top1.as_array().unwrap().iter() {
-                            let cmp_result = crate::utils::bson::value_cmp(top2, item);
-                            if let Ok(Ordering::Equal) = cmp_result {
-                                self.r0 = 1;
-                                break;
-                            }
-                        }
+                        let found = value_in(top2, top1.as_array().unwrap(), self.program.collation);
+                        self.r0 = if found { 1 } else { 0 };
 
                         self.pc = self.pc.add(1);
                     }
@@ -839,46 +1640,9 @@ impl VM {
                         self.r0 = 0;
 
                         if let Bson::RegularExpression(re) = val2 {
-                            let mut re_build = RegexBuilder::new(re.pattern.as_str());
-                            for char in re.options.chars() {
-                                match char {
-                                    'i' => {
-                                        re_build.case_insensitive(true);
-                                    }
-                                    'm' => {
-                                        re_build.multi_line(true);
-                                    }
-                                    's' => {
-                                        re_build.dot_matches_new_line(true);
-                                    }
-                                    'u' => {
-                                        re_build.unicode(true);
-                                    }
-                                    'U' => {
-                                        re_build.swap_greed(true);
-                                    }
-                                    'x' => {
-                                        re_build.ignore_whitespace(true);
-                                    }
-                                    _ => {
-                                        return Err(Error::from(RegexError {
-                                            error: format!("unknown regex option: {}", char),
-                                            expression: re.pattern.clone(),
-                                            options: re.options.clone(),
-                                        }));
-                                    }
-                                }
-                            }
-
-                            let re_build = re_build.build().map_err(|err| {
-                                Error::from(RegexError {
-                                    error: format!("regex build error: {err}"),
-                                    expression: re.pattern.clone(),
-                                    options: re.options.clone(),
-                                })
-                            })?;
+                            let re_build = build_regex(re)?;
 
-                            if re_build.is_match(&val1.to_string()) {
+                            if regex_matches(&re_build, val1) {
                                 self.r0 = 1;
                             }
                         }
@@ -886,6 +1650,43 @@ impl VM {
                         self.pc = self.pc.add(1);
                     }
 
+                    DbOp::Fuzzy => {
+                        let val1 = &self.stack[self.stack.len() - 2];
+                        let val2 = &self.stack[self.stack.len() - 1];
+
+                        let matched = FuzzySearch::parse(val2).is_some_and(|search| search.matches(val1));
+                        self.r0 = if matched { 1 } else { 0 };
+
+                        self.pc = self.pc.add(1);
+                    }
+
+                    DbOp::ElemMatch => {
+                        let val1 = &self.stack[self.stack.len() - 2];
+                        let val2 = &self.stack[self.stack.len() - 1];
+
+                        let matched = match val2 {
+                            Bson::Document(query) => ElemMatch::parse(query)
+                                .is_ok_and(|elem_match| elem_match.matches(val1, self.program.collation)),
+                            _ => false,
+                        };
+                        self.r0 = if matched { 1 } else { 0 };
+
+                        self.pc = self.pc.add(1);
+                    }
+
+                    DbOp::JsonSchema => {
+                        let val1 = &self.stack[self.stack.len() - 2];
+                        let val2 = &self.stack[self.stack.len() - 1];
+
+                        let conforms = match val2 {
+                            Bson::Document(schema) => crate::schema::validate(schema, val1).is_ok(),
+                            _ => false,
+                        };
+                        self.r0 = if conforms { 1 } else { 0 };
+
+                        self.pc = self.pc.add(1);
+                    }
+
                     DbOp::Not =>{
                         self.r0 = if self.r0 == 0 {
                             1
@@ -922,6 +1723,7 @@ impl VM {
 
                     DbOp::Close => {
                         self.r1 = None;
+                        self.ordered = None;
                         self.txn.auto_commit()?;
 
                         self.pc = self.pc.add(1);
@@ -1032,6 +1834,7 @@ impl VM {
 
                     DbOp::_EOF | DbOp::Halt => {
                         self.r1 = None;
+                        self.ordered = None;
                         self.state = VmState::Halt;
                         return Ok(());
                     }
@@ -1044,5 +1847,12 @@ impl VM {
 impl Drop for VM {
     fn drop(&mut self) {
         self.r1 = None;
+        self.ordered = None;
+        if let Some(op) = self.op {
+            self.metrics.record_operation(op, self.elapsed, self.docs_examined, self.docs_returned);
+        }
+        if let Some(profile) = self.profile.take() {
+            profile.finish(&self.program, self.elapsed, self.docs_examined, self.docs_returned);
+        }
     }
 }
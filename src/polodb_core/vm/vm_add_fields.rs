@@ -16,6 +16,8 @@ use bson::Bson;
 use indexmap::IndexMap;
 use crate::vm::operators::{OpRegistry, OperatorExpr};
 use crate::vm::vm_external_func::{VmExternalFunc, VmExternalFuncStatus};
+use crate::vm::vm_text::{VmFuncText, TEXT_SCORE_FIELD};
+use crate::vector::VECTOR_SCORE_FIELD;
 use crate::{Result, Error};
 use crate::errors::mk_invalid_aggregate_field;
 
@@ -32,6 +34,12 @@ impl VmFuncAddFields {
                 for (k, v) in doc.iter() {
                     let op = crate::path_hint_3!(paths, k.clone(), {
                         match v {
+                            Bson::Document(v) if VmFuncText::is_text_score(v) => {
+                                OperatorExpr::Alias(TEXT_SCORE_FIELD.to_string())
+                            }
+                            Bson::Document(v) if crate::vector::is_vector_search_score(v) => {
+                                OperatorExpr::Alias(VECTOR_SCORE_FIELD.to_string())
+                            }
                             Bson::Document(v) => {
                                 let op = registry.compile_doc(paths, v)?;
                                 OperatorExpr::Expr(op)
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use bson::Bson;
+use crate::transaction::TransactionInner;
 use crate::Result;
 
 pub(crate) enum VmExternalFuncStatus {
@@ -24,4 +25,12 @@ pub(crate) trait VmExternalFunc {
     fn name(&self) -> &str;
     fn call(&self, args: &[Bson]) -> Result<VmExternalFuncStatus>;
     fn is_completed(&self) -> bool;
+
+    /// No more document passes the function, the scan of the collection can stop.
+    fn is_exhausted(&self) -> bool {
+        false
+    }
+
+    /// Give the function the transaction of the program, to read the other collections.
+    fn set_transaction(&mut self, _txn: &TransactionInner) {}
 }
@@ -0,0 +1,67 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::sync::Arc;
+use bson::{Bson, Document};
+use crate::vm::vm_external_func::{VmExternalFunc, VmExternalFuncStatus};
+use crate::{Error, Result};
+
+/// A predicate written in Rust, checked on the documents found by the query.
+#[derive(Clone)]
+pub(crate) struct ResidualFilter(pub(crate) Arc<dyn Fn(&Document) -> bool + Send + Sync>);
+
+impl fmt::Debug for ResidualFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ResidualFilter")
+    }
+}
+
+/// Drops the documents rejected by the residual filter of the plan,
+/// before the stages following the query.
+pub(crate) struct VmFuncFilterFn {
+    filter: ResidualFilter,
+}
+
+impl VmFuncFilterFn {
+    pub(crate) fn new(filter: ResidualFilter) -> VmFuncFilterFn {
+        VmFuncFilterFn {
+            filter,
+        }
+    }
+}
+
+impl VmExternalFunc for VmFuncFilterFn {
+    fn name(&self) -> &str {
+        "filterFn"
+    }
+
+    fn call(&self, args: &[Bson]) -> Result<VmExternalFuncStatus> {
+        let arg0 = &args[0];
+        let doc = match arg0 {
+            Bson::Null => return Ok(VmExternalFuncStatus::Next(Bson::Null)),
+            Bson::Document(doc) => doc,
+            _ => return Err(Error::UnknownAggregationOperation("Invalid argument for filter_fn".to_string())),
+        };
+        if (self.filter.0)(doc) {
+            Ok(VmExternalFuncStatus::Next(arg0.clone()))
+        } else {
+            Ok(VmExternalFuncStatus::Continue)
+        }
+    }
+
+    fn is_completed(&self) -> bool {
+        true
+    }
+}
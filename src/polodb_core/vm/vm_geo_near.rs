@@ -0,0 +1,130 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use bson::{Bson, Document};
+use crate::geo::{Geometry, Point};
+use crate::vm::vm_external_func::{VmExternalFunc, VmExternalFuncStatus};
+use crate::{Result, Error};
+use crate::errors::mk_invalid_aggregate_field;
+
+/// `$geoNear`: the documents whose `key` holds a geometry, with their distance to
+/// the `near` point in `distanceField`, from the nearest.
+pub(crate) struct VmFuncGeoNear {
+    near: Point,
+    key: String,
+    distance_field: String,
+    min_distance: Option<f64>,
+    max_distance: Option<f64>,
+    buffer: RefCell<Vec<(f64, Document)>>,
+    sorted: Cell<bool>,
+    idx: AtomicUsize,
+}
+
+impl VmFuncGeoNear {
+
+    pub(crate) fn compile(paths: &mut Vec<String>, val: &Bson) -> Result<Box<dyn VmExternalFunc>> {
+        let options = match val {
+            Bson::Document(doc) => doc,
+            _ => {
+                let invalid_err = mk_invalid_aggregate_field(paths);
+                return Err(Error::InvalidField(invalid_err));
+            }
+        };
+        let near = match options.get("near").map(Geometry::from_bson) {
+            Some(Ok(Geometry::Point(point))) => point,
+            Some(Err(err)) => return Err(err),
+            _ => return Err(Error::ValidationError("$geoNear requires a 'near' point".into())),
+        };
+        let key = match options.get_str("key") {
+            Ok(key) => key.to_string(),
+            Err(_) => return Err(Error::ValidationError("$geoNear requires the 'key' of the geometries".into())),
+        };
+        let distance_field = match options.get_str("distanceField") {
+            Ok(field) => field.to_string(),
+            Err(_) => return Err(Error::ValidationError("$geoNear requires a 'distanceField'".into())),
+        };
+        Ok(Box::new(VmFuncGeoNear {
+            near,
+            key,
+            distance_field,
+            min_distance: VmFuncGeoNear::distance_option(options, "minDistance")?,
+            max_distance: VmFuncGeoNear::distance_option(options, "maxDistance")?,
+            buffer: RefCell::new(Vec::new()),
+            sorted: Cell::new(false),
+            idx: AtomicUsize::new(0),
+        }))
+    }
+
+    fn distance_option(options: &Document, name: &str) -> Result<Option<f64>> {
+        match options.get(name) {
+            None => Ok(None),
+            Some(Bson::Double(value)) => Ok(Some(*value)),
+            Some(Bson::Int32(value)) => Ok(Some(*value as f64)),
+            Some(Bson::Int64(value)) => Ok(Some(*value as f64)),
+            Some(_) => Err(Error::ValidationError(format!("$geoNear '{}' must be a number of meters", name))),
+        }
+    }
+
+    fn sort_buffer(&self) {
+        if !self.sorted.get() {
+            self.buffer.borrow_mut().sort_by(|(a, _), (b, _)| a.total_cmp(b));
+            self.sorted.set(true);
+        }
+    }
+
+}
+
+impl VmExternalFunc for VmFuncGeoNear {
+    fn name(&self) -> &str {
+        "geoNear"
+    }
+
+    fn call(&self, args: &[Bson]) -> Result<VmExternalFuncStatus> {
+        match &args[0] {
+            Bson::Document(doc) => {
+                let geometry = crate::geo::field_value(doc, &self.key)
+                    .and_then(|value| Geometry::from_bson(value).ok());
+                if let Some(geometry) = geometry {
+                    let distance = geometry.distance_to(&self.near);
+                    let in_range = self.min_distance.is_none_or(|min| distance >= min)
+                        && self.max_distance.is_none_or(|max| distance <= max);
+                    if in_range {
+                        let mut doc = doc.clone();
+                        doc.insert(self.distance_field.clone(), distance);
+                        self.buffer.borrow_mut().push((distance, doc));
+                    }
+                }
+                Ok(VmExternalFuncStatus::Continue)
+            }
+            Bson::Null => {
+                self.sort_buffer();
+                let idx = self.idx.fetch_add(1, Ordering::Relaxed);
+                let buffer = self.buffer.borrow();
+                let next = match buffer.get(idx) {
+                    Some((_, doc)) => doc.clone().into(),
+                    None => Bson::Null,
+                };
+                Ok(VmExternalFuncStatus::Next(next))
+            }
+            _ => Err(Error::ValidationError("Invalid $geoNear value".into())),
+        }
+    }
+
+    fn is_completed(&self) -> bool {
+        let idx = self.idx.load(Ordering::Relaxed);
+        idx >= self.buffer.borrow().len()
+    }
+}
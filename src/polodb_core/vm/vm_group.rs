@@ -12,42 +12,189 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Mutex;
-use bson::Bson;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use bson::{Bson, Document};
 use crate::vm::vm_external_func::{VmExternalFunc, VmExternalFuncStatus};
 use crate::{Result, Error};
+use crate::errors::mk_invalid_aggregate_field;
 use indexmap::IndexMap;
-use crate::vm::operators::{OpRegistry, VmOperator};
+use crate::vm::operators::{OpRegistry, OperatorExpr, SumOperator};
 
 const NAME: &str = "group";
 
 // Reference: https://www.mongodb.com/docs/manual/reference/operator/aggregation/group/
 pub(crate) struct VmFuncGroup {
-    is_completed: AtomicBool,
-    inner: Mutex<VmFuncGroupInner>,
+    group_id: GroupId,
+    accumulators: Vec<(String, Accumulator, OperatorExpr)>,
+    /// The states of the accumulators of each group, by the serialized `_id` of the group,
+    /// so the numbers of different types, such as `1` and `1.0`, are different groups.
+    groups: RefCell<IndexMap<Vec<u8>, Group>>,
+    /// The result documents, once all the documents are grouped.
+    buffer: RefCell<Vec<Document>>,
+    is_grouped: AtomicBool,
+    idx: AtomicUsize,
 }
 
-struct VmFuncGroupInner {
-    group_values: IndexMap<String, Bson>,
-    operators: HashMap<String, Box<dyn VmOperator>>,
+struct Group {
+    id: Bson,
+    states: Vec<AccumulatorState>,
+}
+
+enum GroupId {
+    Expr(OperatorExpr),
+    /// A document of expressions, such as `{ "year": "$year", "month": "$month" }`.
+    Fields(Vec<(String, OperatorExpr)>),
+}
+
+#[derive(Clone, Copy)]
+enum Accumulator {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Push,
+    AddToSet,
+    First,
+    Last,
+}
+
+enum AccumulatorState {
+    Value(Bson),
+    Average { sum: f64, count: u64 },
+    Values(Vec<Bson>),
+}
+
+impl Accumulator {
+
+    fn from_name(name: &str) -> Option<Accumulator> {
+        let accumulator = match name {
+            "$sum" => Accumulator::Sum,
+            "$avg" => Accumulator::Avg,
+            "$min" => Accumulator::Min,
+            "$max" => Accumulator::Max,
+            "$push" => Accumulator::Push,
+            "$addToSet" => Accumulator::AddToSet,
+            "$first" => Accumulator::First,
+            "$last" => Accumulator::Last,
+            _ => return None,
+        };
+        Some(accumulator)
+    }
+
+    fn initial_state(self) -> AccumulatorState {
+        match self {
+            Accumulator::Sum => AccumulatorState::Value(Bson::Int32(0)),
+            Accumulator::Avg => AccumulatorState::Average { sum: 0.0, count: 0 },
+            Accumulator::Push | Accumulator::AddToSet | Accumulator::First => AccumulatorState::Values(Vec::new()),
+            Accumulator::Min | Accumulator::Max | Accumulator::Last => AccumulatorState::Value(Bson::Null),
+        }
+    }
+
+    fn accumulate(self, state: &mut AccumulatorState, value: Bson) {
+        match (self, state) {
+            (Accumulator::Sum, AccumulatorState::Value(sum)) => {
+                let items = match &value {
+                    Bson::Array(items) => items.as_slice(),
+                    _ => std::slice::from_ref(&value),
+                };
+                *sum = SumOperator::add_numbers(sum.clone(), items);
+            }
+            (Accumulator::Avg, AccumulatorState::Average { sum, count }) => {
+                let number = match value {
+                    Bson::Int32(v) => v as f64,
+                    Bson::Int64(v) => v as f64,
+                    Bson::Double(v) => v,
+                    _ => return,
+                };
+                *sum += number;
+                *count += 1;
+            }
+            // null and the missing values are ignored
+            (Accumulator::Min | Accumulator::Max, AccumulatorState::Value(current)) => {
+                if value.as_null().is_some() {
+                    return;
+                }
+                let replace = current.as_null().is_some() || match crate::utils::bson::value_cmp(&value, current) {
+                    Ok(std::cmp::Ordering::Less) => matches!(self, Accumulator::Min),
+                    Ok(std::cmp::Ordering::Greater) => matches!(self, Accumulator::Max),
+                    _ => false,
+                };
+                if replace {
+                    *current = value;
+                }
+            }
+            (Accumulator::Push, AccumulatorState::Values(values)) => values.push(value),
+            (Accumulator::AddToSet, AccumulatorState::Values(values)) => {
+                if !values.contains(&value) {
+                    values.push(value);
+                }
+            }
+            (Accumulator::First, AccumulatorState::Values(values)) => {
+                if values.is_empty() {
+                    values.push(value);
+                }
+            }
+            (Accumulator::Last, AccumulatorState::Value(last)) => *last = value,
+            _ => unreachable!("the state doesn't belong to the accumulator"),
+        }
+    }
+
+    fn result(self, state: AccumulatorState) -> Bson {
+        match (self, state) {
+            (_, AccumulatorState::Average { count: 0, .. }) => Bson::Null,
+            (_, AccumulatorState::Average { sum, count }) => Bson::Double(sum / count as f64),
+            (Accumulator::First, AccumulatorState::Values(values)) => {
+                values.into_iter().next().unwrap_or(Bson::Null)
+            }
+            (_, AccumulatorState::Values(values)) => Bson::Array(values),
+            (_, AccumulatorState::Value(value)) => value,
+        }
+    }
+
 }
 
 impl VmFuncGroup {
 
-    fn compile_command(
+    fn compile_group_id(paths: &mut Vec<String>, registry: &OpRegistry, value: &Bson) -> Result<GroupId> {
+        let group_id = match value {
+            Bson::Document(doc) if !doc.keys().next().is_some_and(|key| key.starts_with('$')) => {
+                let mut fields = Vec::with_capacity(doc.len());
+                for (k, v) in doc.iter() {
+                    let expr = crate::path_hint_3!(paths, k.clone(), {
+                        OperatorExpr::compile(paths, registry, v)?
+                    });
+                    fields.push((k.clone(), expr));
+                }
+                GroupId::Fields(fields)
+            }
+            _ => GroupId::Expr(OperatorExpr::compile(paths, registry, value)?),
+        };
+        Ok(group_id)
+    }
+
+    fn compile_accumulator(
         paths: &mut Vec<String>,
-        registry: OpRegistry,
-        key: &str,
+        registry: &OpRegistry,
         value: &Bson,
-        group_values: &mut IndexMap<String, Bson>,
-        operators: &mut HashMap<String, Box<dyn VmOperator>>,
-    ) -> Result<()> {
-        let op = registry.compile(paths, value)?;
-        group_values.insert(key.into(), op.initial_value());
-        operators.insert(key.into(), op);
-        Ok(())
+    ) -> Result<(Accumulator, OperatorExpr)> {
+        let (name, operand) = match value {
+            Bson::Document(doc) if doc.len() == 1 => doc.iter().next().unwrap(),
+            _ => {
+                let invalid_err = mk_invalid_aggregate_field(paths);
+                return Err(Error::InvalidField(invalid_err));
+            }
+        };
+        crate::path_hint_3!(paths, name.clone(), {
+            let accumulator = match Accumulator::from_name(name) {
+                Some(accumulator) => accumulator,
+                None => {
+                    let invalid_err = mk_invalid_aggregate_field(paths);
+                    return Err(Error::InvalidField(invalid_err));
+                }
+            };
+            Ok((accumulator, OperatorExpr::compile(paths, registry, operand)?))
+        })
     }
 
     pub(crate) fn compile(
@@ -56,44 +203,79 @@ impl VmFuncGroup {
         value: &Bson,
     ) -> Result<Box<dyn VmExternalFunc>> {
         let doc = crate::try_unwrap_document!("$group", value);
-        let mut group_values = IndexMap::new();
-        let mut operators = HashMap::new();
+        let mut group_id = None;
+        let mut accumulators = Vec::new();
 
-        let mut found_id = false;
         for (k, v) in doc.iter() {
             crate::path_hint_2!(paths, k.clone(), {
-                group_values.insert(k.clone(), v.clone());
-                let k_str = k.as_str();
-                if k_str == "_id" {
-                    found_id = true;
+                if k == "_id" {
+                    group_id = Some(VmFuncGroup::compile_group_id(paths, &registry, v)?);
                     paths.pop();
                     continue;
                 }
-
-                VmFuncGroup::compile_command(
-                    paths,
-                    registry.clone(),
-                    k_str,
-                    v,
-                    &mut group_values,
-                    &mut operators,
-                )?;
+                let (accumulator, operand) = VmFuncGroup::compile_accumulator(paths, &registry, v)?;
+                accumulators.push((k.clone(), accumulator, operand));
             });
         }
-        if !found_id {
-            let err_msg = "Field '_id' is required for $group".to_string();
-            return Err(Error::ValidationError(err_msg));
-        }
+        let group_id = match group_id {
+            Some(group_id) => group_id,
+            None => {
+                let err_msg = "Field '_id' is required for $group".to_string();
+                return Err(Error::ValidationError(err_msg));
+            }
+        };
 
         let result = VmFuncGroup {
-            is_completed: AtomicBool::new(false),
-            inner: Mutex::new(VmFuncGroupInner {
-                group_values,
-                operators,
-            }),
+            group_id,
+            accumulators,
+            groups: RefCell::new(IndexMap::new()),
+            buffer: RefCell::new(Vec::new()),
+            is_grouped: AtomicBool::new(false),
+            idx: AtomicUsize::new(0),
         };
         Ok(Box::new(result))
     }
+
+    fn group_id_of(&self, input: &Bson) -> Bson {
+        match &self.group_id {
+            GroupId::Expr(expr) => expr.evaluate(input),
+            GroupId::Fields(fields) => {
+                let doc = fields.iter()
+                    .map(|(k, expr)| (k.clone(), expr.evaluate(input)))
+                    .collect::<Document>();
+                Bson::Document(doc)
+            }
+        }
+    }
+
+    fn accumulate(&self, input: &Bson) -> Result<()> {
+        let id = self.group_id_of(input);
+        let key = bson::to_vec(&bson::doc! { "_id": id.clone() })?;
+        let mut groups = self.groups.borrow_mut();
+        let group = groups.entry(key).or_insert_with(|| Group {
+            id,
+            states: self.accumulators.iter()
+                .map(|(_, accumulator, _)| accumulator.initial_state())
+                .collect(),
+        });
+        for ((_, accumulator, operand), state) in self.accumulators.iter().zip(group.states.iter_mut()) {
+            accumulator.accumulate(state, operand.evaluate(input));
+        }
+        Ok(())
+    }
+
+    fn complete_groups(&self) {
+        let groups = std::mem::take(&mut *self.groups.borrow_mut());
+        let mut buffer = self.buffer.borrow_mut();
+        for group in groups.into_values() {
+            let mut result = Document::new();
+            result.insert("_id", group.id);
+            for ((field, accumulator, _), state) in self.accumulators.iter().zip(group.states) {
+                result.insert(field.clone(), accumulator.result(state));
+            }
+            buffer.push(result);
+        }
+    }
 }
 
 impl VmExternalFunc for VmFuncGroup {
@@ -103,38 +285,26 @@ impl VmExternalFunc for VmFuncGroup {
 
     fn call(&self, args: &[Bson]) -> Result<VmExternalFuncStatus> {
         let arg0 = &args[0];
-        let mut inner = self.inner.lock().unwrap();
-        if arg0.as_null().is_some() {  // complete
-            self.is_completed.store(true, Ordering::Relaxed);
-            let mut result = bson::Document::new();
-            for (k, value) in inner.group_values.iter() {
-                if k == "_id" {
-                    result.insert(k.clone(), value.clone());
-                    continue;
+        match arg0 {
+            Bson::Document(_) => {
+                self.accumulate(arg0)?;
+                Ok(VmExternalFuncStatus::Continue)
+            }
+            Bson::Null => {
+                if !self.is_grouped.swap(true, Ordering::Relaxed) {
+                    self.complete_groups();
                 }
-                let op = inner.operators.get(k).expect("Operator not found");
-                let final_value = op.complete();
-                result.insert(k.clone(), final_value);
+                let idx = self.idx.fetch_add(1, Ordering::Relaxed);
+                let buffer = self.buffer.borrow();
+                let next = buffer.get(idx).cloned().map(Bson::Document).unwrap_or(Bson::Null);
+                Ok(VmExternalFuncStatus::Next(next))
             }
-            return Ok(VmExternalFuncStatus::Next(result.into()));
+            _ => Err(Error::UnknownAggregationOperation("Invalid argument for $group".to_string())),
         }
-
-        let next_map = inner.group_values
-            .iter()
-            .map(|(k, v)| {
-                if k == "_id" {
-                    return (k.clone(), v.clone());
-                }
-                let op = inner.operators.get(k).expect("Operator not found");
-                let next = op.next(v);
-                (k.clone(), next)
-            })
-            .collect::<IndexMap<String, Bson>>();
-        inner.group_values = next_map;
-        Ok(VmExternalFuncStatus::Continue)
     }
 
     fn is_completed(&self) -> bool {
-        true
+        let idx = self.idx.load(Ordering::Relaxed);
+        idx >= self.buffer.borrow().len()
     }
 }
@@ -0,0 +1,218 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cell::OnceCell;
+use std::collections::HashSet;
+use bson::{Bson, Document};
+use crate::coll::collection_info::CollectionSpecification;
+use crate::cursor::Cursor;
+use crate::db::db_inner::DatabaseInner;
+use crate::index::IndexHelper;
+use crate::options::Collation;
+use crate::transaction::TransactionInner;
+use crate::vm::op::value_in;
+use crate::vm::vm_external_func::{VmExternalFunc, VmExternalFuncStatus};
+use crate::{Error, Result};
+use crate::errors::mk_invalid_aggregate_field;
+
+/// `$lookup`: the documents of the `from` collection whose `foreignField` equals
+/// the `localField` of the document, in the array of its field `as`.
+///
+/// The documents are found by the primary key if `foreignField` is `_id`,
+/// or by an index whose first field is `foreignField`, otherwise
+/// the `from` collection is scanned for each document.
+pub(crate) struct VmFuncLookup {
+    from: String,
+    local_field: String,
+    foreign_field: String,
+    as_field: String,
+    txn: Option<TransactionInner>,
+    /// The way to find the documents, chosen on the first document.
+    source: OnceCell<LookupSource>,
+}
+
+enum LookupSource {
+    /// The collection doesn't exist.
+    Empty,
+    PrimaryKey,
+    Index(String),
+    Scan,
+}
+
+impl VmFuncLookup {
+
+    pub(crate) fn compile(paths: &mut Vec<String>, val: &Bson) -> Result<Box<dyn VmExternalFunc>> {
+        let options = match val {
+            Bson::Document(doc) => doc,
+            _ => {
+                let invalid_err = mk_invalid_aggregate_field(paths);
+                return Err(Error::InvalidField(invalid_err));
+            }
+        };
+        if let Some(key) = options.keys().find(|key| !matches!(key.as_str(), "from" | "localField" | "foreignField" | "as")) {
+            return Err(Error::ValidationError(format!("$lookup doesn't support the option '{}'", key)));
+        }
+        let option = |name: &str| match options.get_str(name) {
+            Ok(value) if !value.is_empty() => Ok(value.to_string()),
+            _ => Err(Error::ValidationError(format!("$lookup requires the string '{}'", name))),
+        };
+        Ok(Box::new(VmFuncLookup {
+            from: option("from")?,
+            local_field: option("localField")?,
+            foreign_field: option("foreignField")?,
+            as_field: option("as")?,
+            txn: None,
+            source: OnceCell::new(),
+        }))
+    }
+
+    fn txn(&self) -> &TransactionInner {
+        self.txn.as_ref().expect("the transaction of $lookup must be set")
+    }
+
+    fn choose_source(&self) -> Result<LookupSource> {
+        let col_spec = match DatabaseInner::read_collection_spec(self.txn(), &self.from) {
+            Ok(col_spec) => col_spec,
+            Err(Error::CollectionNotFound(_)) => return Ok(LookupSource::Empty),
+            Err(err) => return Err(err),
+        };
+        if col_spec.view.is_some() {
+            return Err(Error::ValidationError(format!("$lookup can't read the view '{}'", self.from)));
+        }
+        if self.foreign_field == "_id" {
+            return Ok(LookupSource::PrimaryKey);
+        }
+        Ok(VmFuncLookup::index_of(&col_spec, &self.foreign_field)
+            .map(LookupSource::Index)
+            .unwrap_or(LookupSource::Scan))
+    }
+
+    /// The index whose entries start with the values of `field` of all the documents, as they are stored.
+    fn index_of(col_spec: &CollectionSpecification, field: &str) -> Option<String> {
+        col_spec.indexes.iter()
+            .find(|(_, info)| {
+                info.keys.keys().next().is_some_and(|key| key == field)
+                    && info.collation() == Collation::Simple
+                    && info.partial_filter_expression().is_none()
+            })
+            .map(|(name, _)| name.clone())
+    }
+
+    /// Add the documents equal to `value` which are not found yet.
+    fn find_documents(&self, value: &Bson, seen: &mut HashSet<Vec<u8>>, found: &mut Vec<Document>) -> Result<()> {
+        let source = match self.source.get() {
+            Some(source) => source,
+            None => {
+                let source = self.choose_source()?;
+                self.source.get_or_init(|| source)
+            }
+        };
+        match source {
+            LookupSource::Empty => (),
+            // the documents missing the field are not in the index
+            LookupSource::PrimaryKey | LookupSource::Index(_) if *value == Bson::Null => {
+                self.scan_documents(value, seen, found)?;
+            }
+            LookupSource::PrimaryKey => {
+                let key = crate::utils::bson::stacked_key([&Bson::String(self.from.clone()), value])?;
+                if let Some(buf) = self.txn().rocksdb_txn.get(&key)? {
+                    if seen.insert(key) {
+                        found.push(bson::from_slice(&buf)?);
+                    }
+                }
+            }
+            LookupSource::Index(index_name) => {
+                let prefix = IndexHelper::make_index_key(&self.from, index_name, std::slice::from_ref(value), None)?;
+                let mut cursor = Cursor::new(prefix, self.txn().rocksdb_txn.new_iterator());
+                cursor.reset()?;
+                while cursor.has_next() {
+                    let index_key = cursor.peek_key().expect("key must exist");
+                    let slices = crate::utils::bson::split_stacked_keys(index_key)?;
+                    let pkey = slices.last().expect("pkey must exist");
+                    let key = crate::utils::bson::stacked_key([&Bson::String(self.from.clone()), pkey])?;
+                    // the entries of a document with an array are found once
+                    if !seen.contains(&key) {
+                        if let Some(buf) = self.txn().rocksdb_txn.get(&key)? {
+                            found.push(bson::from_slice(&buf)?);
+                            seen.insert(key);
+                        }
+                    }
+                    cursor.next()?;
+                }
+            }
+            LookupSource::Scan => self.scan_documents(value, seen, found)?,
+        }
+        Ok(())
+    }
+
+    fn scan_documents(&self, value: &Bson, seen: &mut HashSet<Vec<u8>>, found: &mut Vec<Document>) -> Result<()> {
+        let mut cursor = Cursor::new_with_str_prefix(self.from.clone(), self.txn().rocksdb_txn.new_iterator())?;
+        cursor.reset()?;
+        while cursor.has_next() {
+            let key = cursor.peek_key().expect("key must exist").to_vec();
+            if !seen.contains(&key) {
+                let doc = bson::from_slice::<Document>(cursor.copy_data()?.as_ref())?;
+                // like the filter, an array equals the values of its elements
+                let foreign_value = crate::utils::bson::try_get_document_value(&doc, &self.foreign_field)
+                    .unwrap_or(Bson::Null);
+                if value_in(&foreign_value, std::slice::from_ref(value), Collation::Simple) {
+                    found.push(doc);
+                    seen.insert(key);
+                }
+            }
+            cursor.next()?;
+        }
+        Ok(())
+    }
+
+}
+
+impl VmExternalFunc for VmFuncLookup {
+    fn name(&self) -> &str {
+        "lookup"
+    }
+
+    fn call(&self, args: &[Bson]) -> Result<VmExternalFuncStatus> {
+        let arg0 = &args[0];
+        if arg0.as_null().is_some() {
+            return Ok(VmExternalFuncStatus::Next(Bson::Null));
+        }
+        let mut doc = match arg0 {
+            Bson::Document(doc) => doc.clone(),
+            _ => return Err(Error::UnknownAggregationOperation("Invalid argument for $lookup".to_string())),
+        };
+        // a missing field equals null, an array any of its elements
+        let local_value = crate::utils::bson::try_get_document_value(&doc, &self.local_field)
+            .unwrap_or(Bson::Null);
+        let values = match local_value {
+            Bson::Array(values) => values,
+            value => vec![value],
+        };
+        let mut seen = HashSet::new();
+        let mut found = Vec::new();
+        for value in &values {
+            self.find_documents(value, &mut seen, &mut found)?;
+        }
+        doc.insert(self.as_field.clone(), found.into_iter().map(Bson::Document).collect::<Vec<Bson>>());
+        Ok(VmExternalFuncStatus::Next(Bson::Document(doc)))
+    }
+
+    fn is_completed(&self) -> bool {
+        true
+    }
+
+    fn set_transaction(&mut self, txn: &TransactionInner) {
+        self.txn = Some(txn.clone());
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use bson::{Bson, Document};
+use crate::vm::operators::{OpRegistry, OperatorExpr};
+use crate::vm::update_operators::FieldPath;
+use crate::vm::vm_external_func::{VmExternalFunc, VmExternalFuncStatus};
+use crate::{Result, Error};
+use crate::errors::mk_invalid_aggregate_field;
+
+/// `$project`: the document with only the included and computed fields, such as
+/// `{ "name": 1, "total": { "$multiply": ["$price", "$qty"] } }`, or without
+/// the excluded fields, such as `{ "card": 0 }`. The `_id` is included unless it's excluded.
+pub(crate) struct VmFuncProject {
+    exclude_id: bool,
+    projection: Projection,
+}
+
+enum Projection {
+    Include(Vec<(FieldPath, ProjectedField)>),
+    Exclude(Vec<FieldPath>),
+}
+
+enum ProjectedField {
+    Included,
+    Computed(OperatorExpr),
+}
+
+impl VmFuncProject {
+
+    pub(crate) fn compile(paths: &mut Vec<String>, registry: OpRegistry, value: &Bson) -> Result<Box<dyn VmExternalFunc>> {
+        let doc = match value {
+            Bson::Document(doc) if !doc.is_empty() => doc,
+            _ => {
+                let invalid_err = mk_invalid_aggregate_field(paths);
+                return Err(Error::InvalidField(invalid_err));
+            }
+        };
+        let mut exclude_id = false;
+        let mut included = Vec::new();
+        let mut excluded = Vec::new();
+        for (k, v) in doc.iter() {
+            crate::path_hint_2!(paths, k.clone(), {
+                if k.split('.').any(|key| key.is_empty() || key.starts_with('$')) {
+                    let invalid_err = mk_invalid_aggregate_field(paths);
+                    return Err(Error::InvalidField(invalid_err));
+                }
+                let field_path = FieldPath::compile(k, &Document::new())?;
+                match VmFuncProject::flag(v) {
+                    Some(false) if k == "_id" => exclude_id = true,
+                    Some(false) => excluded.push(field_path),
+                    Some(true) if k == "_id" => (),
+                    Some(true) => included.push((field_path, ProjectedField::Included)),
+                    None => {
+                        let expr = OperatorExpr::compile(paths, &registry, v)?;
+                        included.push((field_path, ProjectedField::Computed(expr)));
+                    }
+                }
+            });
+        }
+        let projection = match (included.is_empty(), excluded.is_empty()) {
+            (_, true) => Projection::Include(included),
+            (true, false) => Projection::Exclude(excluded),
+            (false, false) => {
+                let err_msg = format!("$project can't exclude the field '{}' with the included fields", excluded[0].as_str());
+                return Err(Error::ValidationError(err_msg));
+            }
+        };
+        Ok(Box::new(VmFuncProject {
+            exclude_id,
+            projection,
+        }))
+    }
+
+    /// Whether the value includes (`1`, `true`) or excludes (`0`, `false`)
+    /// the field, `None` for the value of a computed field.
+    fn flag(value: &Bson) -> Option<bool> {
+        match value {
+            Bson::Boolean(b) => Some(*b),
+            Bson::Int32(v) => Some(*v != 0),
+            Bson::Int64(v) => Some(*v != 0),
+            Bson::Double(v) => Some(*v != 0.0),
+            _ => None,
+        }
+    }
+
+    fn project(&self, input: &Bson, mut doc: Document) -> Result<Document> {
+        let mut result = match &self.projection {
+            Projection::Include(fields) => {
+                let mut result = Document::new();
+                if let Some(id) = doc.get("_id") {
+                    result.insert("_id", id.clone());
+                }
+                for (field_path, field) in fields {
+                    let value = match field {
+                        ProjectedField::Included => {
+                            match crate::utils::bson::try_get_document_value(&doc, field_path.as_str()) {
+                                Some(value) => value,
+                                None => continue,
+                            }
+                        }
+                        ProjectedField::Computed(expr) => expr.evaluate(input),
+                    };
+                    for mut slot in field_path.slots(&mut result, true)? {
+                        slot.set(value.clone());
+                    }
+                }
+                result
+            }
+            Projection::Exclude(fields) => {
+                for field_path in fields {
+                    for mut slot in field_path.slots(&mut doc, false)? {
+                        slot.unset();
+                    }
+                }
+                doc
+            }
+        };
+        if self.exclude_id {
+            result.remove("_id");
+        }
+        Ok(result)
+    }
+
+}
+
+impl VmExternalFunc for VmFuncProject {
+    fn name(&self) -> &str {
+        "project"
+    }
+
+    fn call(&self, args: &[Bson]) -> Result<VmExternalFuncStatus> {
+        let arg0 = &args[0];
+        if arg0.as_null().is_some() {
+            return Ok(VmExternalFuncStatus::Next(Bson::Null));
+        }
+        let doc = match arg0 {
+            Bson::Document(doc) => doc.clone(),
+            _ => return Err(Error::UnknownAggregationOperation("Invalid argument for $project".to_string())),
+        };
+        let result = self.project(arg0, doc)?;
+        Ok(VmExternalFuncStatus::Next(Bson::Document(result)))
+    }
+
+    fn is_completed(&self) -> bool {
+        true
+    }
+}
@@ -17,6 +17,8 @@ use std::collections::HashMap;
 use std::sync::atomic::AtomicUsize;
 use bson::{Bson, Document};
 use crate::vm::vm_external_func::{VmExternalFunc, VmExternalFuncStatus};
+use crate::vm::vm_text::{VmFuncText, TEXT_SCORE_FIELD};
+use crate::vector::VECTOR_SCORE_FIELD;
 use crate::{Result, Error};
 use crate::errors::mk_invalid_aggregate_field;
 
@@ -32,9 +34,21 @@ impl VmFuncSort {
             Bson::Document(doc) => {
                 let mut result = HashMap::default();
                 for (k, v) in doc.iter() {
+                    if k == "$natural" {
+                        return Err(Error::ValidationError("$natural must be the only key of the $sort following the $match".into()));
+                    }
                     let order = match v {
                         Bson::Int32(val) => *val as i8,
                         Bson::Int64(val) => *val as i8,
+                        // the best scored documents of a $text search first
+                        Bson::Document(meta) if VmFuncText::is_text_score(meta) => {
+                            result.insert(TEXT_SCORE_FIELD.to_string(), -1);
+                            continue;
+                        }
+                        Bson::Document(meta) if crate::vector::is_vector_search_score(meta) => {
+                            result.insert(VECTOR_SCORE_FIELD.to_string(), -1);
+                            continue;
+                        }
                         _ => return Err(Error::ValidationError("Invalid sort value".into()))
                     };
                     result.insert(k.clone(), order);
@@ -0,0 +1,133 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use bson::{Bson, Document};
+use crate::vm::vm_external_func::{VmExternalFunc, VmExternalFuncStatus};
+use crate::{Error, Result};
+use crate::errors::mk_invalid_aggregate_field;
+
+/// The field keeping the score of a document found by `$text`
+/// while it goes through the pipeline, it's removed from the results.
+pub(crate) const TEXT_SCORE_FIELD: &str = "$textScore";
+
+/// Scores the documents by the terms of a `$text` search found
+/// in their strings, the documents without any of them are dropped.
+///
+/// The score of a string is the number of terms found in it,
+/// weighted up to twice for the shorter strings, the score of
+/// a document is the sum of the scores of its strings.
+pub(crate) struct VmFuncText {
+    terms: HashSet<String>,
+    min_score: f64,
+}
+
+impl VmFuncText {
+    pub(crate) fn compile(paths: &mut Vec<String>, val: &Bson) -> Result<Box<dyn VmExternalFunc>> {
+        let doc = match val {
+            Bson::Document(doc) => doc,
+            _ => {
+                let invalid_err = mk_invalid_aggregate_field(paths);
+                return Err(Error::InvalidField(invalid_err));
+            }
+        };
+        let mut terms = HashSet::new();
+        let mut min_score = 0.0;
+        for (k, v) in doc.iter() {
+            crate::path_hint_2!(paths, k.clone(), {
+                match (k.as_str(), v) {
+                    ("$search", Bson::String(search)) => {
+                        terms.extend(tokenize(search));
+                    }
+                    ("$minScore", Bson::Int32(score)) => min_score = *score as f64,
+                    ("$minScore", Bson::Int64(score)) => min_score = *score as f64,
+                    ("$minScore", Bson::Double(score)) => min_score = *score,
+                    _ => {
+                        let invalid_err = mk_invalid_aggregate_field(paths);
+                        return Err(Error::InvalidField(invalid_err));
+                    }
+                }
+            });
+        }
+        Ok(Box::new(VmFuncText {
+            terms,
+            min_score,
+        }))
+    }
+
+    /// Is the value `{ "$meta": "textScore" }`, standing for the score of the document.
+    pub(crate) fn is_text_score(doc: &Document) -> bool {
+        doc.len() == 1 && matches!(doc.get("$meta"), Some(Bson::String(meta)) if meta == "textScore")
+    }
+
+    fn score(&self, value: &Bson) -> f64 {
+        match value {
+            Bson::String(s) => {
+                let mut words = 0;
+                let mut found = 0;
+                for word in tokenize(s) {
+                    words += 1;
+                    if self.terms.contains(&word) {
+                        found += 1;
+                    }
+                }
+                if found == 0 {
+                    return 0.0;
+                }
+                found as f64 * (0.5 + 0.5 / words as f64)
+            }
+            Bson::Document(doc) => {
+                doc.iter()
+                    .filter(|(k, _)| k.as_str() != TEXT_SCORE_FIELD)
+                    .map(|(_, v)| self.score(v))
+                    .sum()
+            }
+            Bson::Array(arr) => arr.iter().map(|v| self.score(v)).sum(),
+            _ => 0.0,
+        }
+    }
+}
+
+fn tokenize(s: &str) -> impl Iterator<Item = String> + '_ {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+}
+
+impl VmExternalFunc for VmFuncText {
+    fn name(&self) -> &str {
+        "text"
+    }
+
+    fn call(&self, args: &[Bson]) -> Result<VmExternalFuncStatus> {
+        let arg0 = &args[0];
+        if arg0.as_null().is_some() {
+            return Ok(VmExternalFuncStatus::Next(Bson::Null));
+        }
+        let mut doc = match arg0 {
+            Bson::Document(doc) => doc.clone(),
+            _ => return Err(Error::UnknownAggregationOperation("Invalid argument for $text".to_string())),
+        };
+        let score = self.score(arg0);
+        if score <= 0.0 || score < self.min_score {
+            return Ok(VmExternalFuncStatus::Continue);
+        }
+        doc.insert(TEXT_SCORE_FIELD, score);
+        Ok(VmExternalFuncStatus::Next(Bson::Document(doc)))
+    }
+
+    fn is_completed(&self) -> bool {
+        true
+    }
+}
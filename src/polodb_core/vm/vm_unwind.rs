@@ -0,0 +1,146 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use bson::{Bson, Document};
+use crate::vm::update_operators::FieldPath;
+use crate::vm::vm_external_func::{VmExternalFunc, VmExternalFuncStatus};
+use crate::{Result, Error};
+use crate::errors::mk_invalid_aggregate_field;
+
+/// `$unwind`: a document for each element of the array of the field `path`.
+///
+/// The documents of an array are returned one by one: the first one for the
+/// input document, then the others as the VM calls the stage again with null
+/// while it's not completed. A null is the end of the input once they are all returned.
+/// With `preserveNullAndEmptyArrays`, the documents without elements are returned
+/// as they are, without the field if it's an empty array.
+pub(crate) struct VmFuncUnwind {
+    field_path: FieldPath,
+    include_array_index: Option<String>,
+    preserve_null_and_empty_arrays: bool,
+    pending: RefCell<VecDeque<Document>>,
+}
+
+impl VmFuncUnwind {
+
+    pub(crate) fn compile(paths: &mut Vec<String>, val: &Bson) -> Result<Box<dyn VmExternalFunc>> {
+        let (path, options) = match val {
+            Bson::String(path) => (path.as_str(), None),
+            Bson::Document(options) => {
+                if let Some(key) = options.keys().find(|key| !matches!(key.as_str(), "path" | "includeArrayIndex" | "preserveNullAndEmptyArrays")) {
+                    return Err(Error::ValidationError(format!("$unwind doesn't support the option '{}'", key)));
+                }
+                match options.get_str("path") {
+                    Ok(path) => (path, Some(options)),
+                    Err(_) => return Err(Error::ValidationError("$unwind requires the string 'path'".to_string())),
+                }
+            }
+            _ => {
+                let invalid_err = mk_invalid_aggregate_field(paths);
+                return Err(Error::InvalidField(invalid_err));
+            }
+        };
+        let field_path = match path.strip_prefix('$') {
+            Some(field) if !field.split('.').any(|key| key.is_empty() || key.starts_with('$')) => {
+                FieldPath::compile(field, &Document::new())?
+            }
+            _ => {
+                return Err(Error::ValidationError(format!("the path '{}' of $unwind must be a field starting with '$'", path)));
+            }
+        };
+        let include_array_index = match options.and_then(|options| options.get("includeArrayIndex")) {
+            None => None,
+            Some(Bson::String(name)) if !name.is_empty() && !name.starts_with('$') => Some(name.clone()),
+            Some(_) => return Err(Error::ValidationError("$unwind requires a field name for 'includeArrayIndex'".to_string())),
+        };
+        let preserve_null_and_empty_arrays = match options.and_then(|options| options.get("preserveNullAndEmptyArrays")) {
+            None => false,
+            Some(Bson::Boolean(preserve)) => *preserve,
+            Some(_) => return Err(Error::ValidationError("$unwind requires a boolean for 'preserveNullAndEmptyArrays'".to_string())),
+        };
+        Ok(Box::new(VmFuncUnwind {
+            field_path,
+            include_array_index,
+            preserve_null_and_empty_arrays,
+            pending: RefCell::new(VecDeque::new()),
+        }))
+    }
+
+    fn unwind(&self, doc: &Document) -> Result<()> {
+        let mut pending = self.pending.borrow_mut();
+        let value = crate::utils::bson::try_get_document_value(doc, self.field_path.as_str());
+        let items = match value {
+            Some(Bson::Array(items)) if !items.is_empty() => items,
+            None | Some(Bson::Null) | Some(Bson::Array(_)) if !self.preserve_null_and_empty_arrays => {
+                return Ok(());
+            }
+            // a value which is not an array is like an array of this element
+            _ => {
+                let mut doc = doc.clone();
+                if value.as_ref().is_some_and(|value| matches!(value, Bson::Array(_))) {
+                    for mut slot in self.field_path.slots(&mut doc, false)? {
+                        slot.unset();
+                    }
+                }
+                if let Some(name) = &self.include_array_index {
+                    doc.insert(name.clone(), Bson::Null);
+                }
+                pending.push_back(doc);
+                return Ok(());
+            }
+        };
+        for (index, item) in items.into_iter().enumerate() {
+            let mut doc = doc.clone();
+            for mut slot in self.field_path.slots(&mut doc, false)? {
+                slot.set(item.clone());
+            }
+            if let Some(name) = &self.include_array_index {
+                doc.insert(name.clone(), Bson::Int64(index as i64));
+            }
+            pending.push_back(doc);
+        }
+        Ok(())
+    }
+
+}
+
+impl VmExternalFunc for VmFuncUnwind {
+    fn name(&self) -> &str {
+        "unwind"
+    }
+
+    fn call(&self, args: &[Bson]) -> Result<VmExternalFuncStatus> {
+        let arg0 = &args[0];
+        match arg0 {
+            Bson::Document(doc) => self.unwind(doc)?,
+            // the end of the input, once the documents of the last array are returned
+            Bson::Null if self.pending.borrow().is_empty() => {
+                return Ok(VmExternalFuncStatus::Next(Bson::Null));
+            }
+            Bson::Null => (),
+            _ => return Err(Error::UnknownAggregationOperation("Invalid argument for $unwind".to_string())),
+        }
+        match self.pending.borrow_mut().pop_front() {
+            Some(doc) => Ok(VmExternalFuncStatus::Next(Bson::Document(doc))),
+            None => Ok(VmExternalFuncStatus::Continue),
+        }
+    }
+
+    fn is_completed(&self) -> bool {
+        self.pending.borrow().is_empty()
+    }
+}
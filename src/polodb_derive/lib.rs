@@ -0,0 +1,325 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! Derive macros for PoloDB.
+//!
+//! Use the `derive` feature of `polodb_core` instead of depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Fields, LitStr};
+
+/// Derive `polodb_core::Model` for a struct.
+///
+/// Struct attributes:
+/// - `#[polo(collection = "users")]`: the collection name, the snake case of the struct name by default.
+///
+/// Field attributes:
+/// - `#[polo(id)]`: the field is the primary key, it must be serialized as `_id`.
+///   A `uuid::Uuid` key is best stored as a binary with `#[serde(with = "polodb_core::uuid_as_binary")]`.
+/// - `#[polo(index)]`: create an ascending index on the field.
+/// - `#[polo(unique)]`: create a unique ascending index on the field.
+///
+/// An associated constant holding the path of each field is generated,
+/// named after the field in upper case, e.g. `User::EMAIL`.
+///
+/// The typed fields of the filters are returned by `User::fields()`,
+/// e.g. `User::fields().age().gt(18)`, see `polodb_core::query`.
+#[proc_macro_derive(PoloModel, attributes(polo))]
+pub fn derive_polo_model(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+struct ModelField {
+    ident: syn::Ident,
+    ty: syn::Type,
+    path: String,
+    is_id: bool,
+    index: bool,
+    unique: bool,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = &input.ident;
+
+    let data = match &input.data {
+        Data::Struct(data) => data,
+        _ => return Err(syn::Error::new_spanned(ident, "PoloModel can only be derived for structs")),
+    };
+    let fields = match &data.fields {
+        Fields::Named(fields) => fields,
+        _ => return Err(syn::Error::new_spanned(ident, "PoloModel requires named fields")),
+    };
+
+    let mut collection_name = to_snake_case(&ident.to_string());
+    for attr in polo_attrs(&input.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("collection") {
+                collection_name = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("unknown polo attribute"))
+            }
+        })?;
+    }
+
+    let rename_all = serde_rename_all(&input.attrs)?;
+
+    let mut model_fields = Vec::<ModelField>::new();
+    for field in fields.named.iter() {
+        let field_ident = field.ident.clone().unwrap();
+        let field_name = field_ident.to_string();
+        let field_name = field_name.trim_start_matches("r#");
+        let path = match serde_rename(&field.attrs)? {
+            Some(name) => name,
+            None => match &rename_all {
+                Some(rule) => apply_rename_rule(rule, field_name),
+                None => field_name.to_string(),
+            },
+        };
+
+        let mut model_field = ModelField {
+            ident: field_ident,
+            ty: field.ty.clone(),
+            path,
+            is_id: false,
+            index: false,
+            unique: false,
+        };
+        for attr in polo_attrs(&field.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("id") {
+                    model_field.is_id = true;
+                } else if meta.path.is_ident("index") {
+                    model_field.index = true;
+                } else if meta.path.is_ident("unique") {
+                    model_field.index = true;
+                    model_field.unique = true;
+                } else {
+                    return Err(meta.error("unknown polo attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        if model_field.is_id && model_field.path != "_id" {
+            return Err(syn::Error::new_spanned(
+                &field.ident,
+                "the id field must be serialized as `_id`, add #[serde(rename = \"_id\")]",
+            ));
+        }
+        model_fields.push(model_field);
+    }
+
+    if model_fields.iter().filter(|f| f.is_id).count() > 1 {
+        return Err(syn::Error::new_spanned(ident, "only one field can be marked as #[polo(id)]"));
+    }
+
+    let consts = model_fields.iter().map(|f| {
+        let const_ident = syn::Ident::new(
+            &f.ident.to_string().trim_start_matches("r#").to_uppercase(),
+            Span::call_site(),
+        );
+        let path = &f.path;
+        quote! {
+            pub const #const_ident: &'static str = #path;
+        }
+    });
+
+    let indexes = model_fields.iter().filter(|f| f.index && !f.is_id).map(|f| {
+        let path = &f.path;
+        let unique = f.unique;
+        quote! {
+            ::polodb_core::IndexModel {
+                keys: ::polodb_core::bson::doc! { #path: 1 },
+                options: ::std::option::Option::Some(::polodb_core::IndexOptions {
+                    unique: ::std::option::Option::Some(#unique),
+                    ..::std::default::Default::default()
+                }),
+            }
+        }
+    });
+
+    let id_fn = match model_fields.iter().find(|f| f.is_id) {
+        Some(_) => {
+            // serialize the whole struct to honor the serde attributes of the field,
+            // such as `#[serde(with = "polodb_core::uuid_as_binary")]`
+            quote! {
+                fn id(&self) -> ::std::option::Option<::polodb_core::bson::Bson> {
+                    ::polodb_core::bson::to_document(self).ok()?.remove("_id")
+                }
+            }
+        }
+        None => quote! {},
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let vis = &input.vis;
+    let fields_ident = syn::Ident::new(&format!("{}Fields", ident), Span::call_site());
+    let field_fns = model_fields.iter().map(|f| {
+        let field_ident = &f.ident;
+        let ty = &f.ty;
+        let path = &f.path;
+        quote! {
+            pub fn #field_ident(&self) -> ::polodb_core::query::Field<#ty> {
+                ::polodb_core::query::Field::new(#path)
+            }
+        }
+    });
+    let generic_params = &input.generics.params;
+
+    Ok(quote! {
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #(#consts)*
+
+            /// The typed fields of the model, to build the filters of the queries.
+            pub fn fields() -> #fields_ident #ty_generics {
+                #fields_ident {
+                    _model: ::std::marker::PhantomData,
+                }
+            }
+        }
+
+        /// The typed fields of the model, see `polodb_core::query`.
+        #vis struct #fields_ident <#generic_params> #where_clause {
+            _model: ::std::marker::PhantomData<fn() -> #ident #ty_generics>,
+        }
+
+        impl #impl_generics ::std::clone::Clone for #fields_ident #ty_generics #where_clause {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl #impl_generics ::std::marker::Copy for #fields_ident #ty_generics #where_clause {}
+
+        impl #impl_generics #fields_ident #ty_generics #where_clause {
+            #(#field_fns)*
+        }
+
+        impl #impl_generics ::polodb_core::Model for #ident #ty_generics #where_clause {
+            const COLLECTION_NAME: &'static str = #collection_name;
+
+            fn indexes() -> ::std::vec::Vec<::polodb_core::IndexModel> {
+                ::std::vec![#(#indexes),*]
+            }
+
+            #id_fn
+        }
+    })
+}
+
+fn polo_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("polo"))
+}
+
+fn serde_attr_value(attrs: &[Attribute], key: &str) -> syn::Result<Option<String>> {
+    let mut result = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.input.peek(syn::Token![=]) {
+                let is_key = meta.path.is_ident(key);
+                let value = meta.value()?;
+                if is_key {
+                    result = Some(value.parse::<LitStr>()?.value());
+                } else {
+                    // skip the value of other serde attributes
+                    value.parse::<syn::Expr>()?;
+                }
+            } else if meta.input.peek(syn::token::Paren) {
+                meta.parse_nested_meta(|nested| {
+                    if nested.input.peek(syn::Token![=]) {
+                        nested.value()?.parse::<syn::Expr>()?;
+                    }
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(result)
+}
+
+fn serde_rename(attrs: &[Attribute]) -> syn::Result<Option<String>> {
+    serde_attr_value(attrs, "rename")
+}
+
+fn serde_rename_all(attrs: &[Attribute]) -> syn::Result<Option<String>> {
+    serde_attr_value(attrs, "rename_all")
+}
+
+fn split_words(name: &str) -> Vec<String> {
+    let mut words = Vec::<String>::new();
+    let mut current = String::new();
+    for ch in name.chars() {
+        if ch == '_' || ch == '-' {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+        } else if ch.is_uppercase() && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+            current.push(ch);
+        } else {
+            current.push(ch);
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words.into_iter().map(|w| w.to_lowercase()).collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn to_snake_case(name: &str) -> String {
+    split_words(name).join("_")
+}
+
+/// Apply the serde `rename_all` rule to a field name.
+fn apply_rename_rule(rule: &str, name: &str) -> String {
+    let words = split_words(name);
+    match rule {
+        "lowercase" => name.to_lowercase(),
+        "UPPERCASE" => name.to_uppercase(),
+        "PascalCase" => words.iter().map(|w| capitalize(w)).collect(),
+        "camelCase" => {
+            let mut result = String::new();
+            for (i, word) in words.iter().enumerate() {
+                if i == 0 {
+                    result.push_str(word);
+                } else {
+                    result.push_str(&capitalize(word));
+                }
+            }
+            result
+        }
+        "snake_case" => words.join("_"),
+        "SCREAMING_SNAKE_CASE" => words.join("_").to_uppercase(),
+        "kebab-case" => words.join("-"),
+        "SCREAMING-KEBAB-CASE" => words.join("-").to_uppercase(),
+        _ => name.to_string(),
+    }
+}
@@ -0,0 +1,445 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The JNI library of the JVM bindings, implementing the native methods of
+//! `org.polodb.NativeLib` on the C ABI of `polodb_capi`.
+//!
+//! The strings are passed as UTF-8 byte arrays rather than Java strings, whose
+//! native encoding is the modified UTF-8 of JNI. The handles are passed as longs.
+//! An error of PoloDB throws a `org.polodb.PoloDBException` holding its code.
+
+#![allow(clippy::missing_safety_doc)]
+
+mod sys;
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use polodb_capi::*;
+use sys::{jbyte, jbyteArray, jclass, jint, jlong, jlongArray, jsize, jvalue, JNIEnv};
+
+const EXCEPTION_CLASS: &[u8] = b"org/polodb/PoloDBException\0";
+const EXCEPTION_CONSTRUCTOR: &[u8] = b"<init>\0";
+const EXCEPTION_SIGNATURE: &[u8] = b"(I[B)V\0";
+/// The code of `InvalidArgument`, for the errors of the arguments found by the library.
+const INVALID_ARGUMENT: c_int = 2;
+
+struct Env(*mut JNIEnv);
+
+impl Env {
+
+    /// The string of the byte array, null if the array is null.
+    unsafe fn string_arg(&self, array: jbyteArray, name: &str) -> Result<Option<CString>, ()> {
+        if array.is_null() {
+            return Ok(None);
+        }
+        let len = ((**self.0).GetArrayLength)(self.0, array);
+        let mut bytes = vec![0_u8; len as usize];
+        ((**self.0).GetByteArrayRegion)(self.0, array, 0, len, bytes.as_mut_ptr() as *mut jbyte);
+        match CString::new(bytes) {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => {
+                self.throw_message(INVALID_ARGUMENT, &format!("'{}' holds a nul character", name));
+                Err(())
+            }
+        }
+    }
+
+    unsafe fn byte_array(&self, bytes: &[u8]) -> jbyteArray {
+        let array = ((**self.0).NewByteArray)(self.0, bytes.len() as jsize);
+        if !array.is_null() {
+            ((**self.0).SetByteArrayRegion)(self.0, array, 0, bytes.len() as jsize, bytes.as_ptr() as *const jbyte);
+        }
+        array
+    }
+
+    unsafe fn long_array(&self, values: &[jlong]) -> jlongArray {
+        let array = ((**self.0).NewLongArray)(self.0, values.len() as jsize);
+        if !array.is_null() {
+            ((**self.0).SetLongArrayRegion)(self.0, array, 0, values.len() as jsize, values.as_ptr());
+        }
+        array
+    }
+
+    /// The byte array of a string returned by PoloDB, freeing the string.
+    unsafe fn take_string(&self, value: *mut c_char) -> jbyteArray {
+        if value.is_null() {
+            return ptr::null_mut();
+        }
+        let array = self.byte_array(CStr::from_ptr(value).to_bytes());
+        polodb_free_string(value);
+        array
+    }
+
+    /// Check the code returned by PoloDB, throwing its error if it's not 0.
+    unsafe fn check(&self, code: c_int) -> Result<(), ()> {
+        if code == 0 {
+            return Ok(());
+        }
+        let message = polodb_last_error();
+        let message = if message.is_null() {
+            "unknown error".to_string()
+        } else {
+            CStr::from_ptr(message).to_string_lossy().into_owned()
+        };
+        self.throw_message(code, &message);
+        Err(())
+    }
+
+    unsafe fn throw_message(&self, code: c_int, message: &str) {
+        let env = self.0;
+        let class: jclass = ((**env).FindClass)(env, EXCEPTION_CLASS.as_ptr() as *const c_char);
+        if class.is_null() {
+            // FindClass threw NoClassDefFoundError
+            return;
+        }
+        let constructor = ((**env).GetMethodID)(
+            env,
+            class,
+            EXCEPTION_CONSTRUCTOR.as_ptr() as *const c_char,
+            EXCEPTION_SIGNATURE.as_ptr() as *const c_char,
+        );
+        let message = self.byte_array(message.as_bytes());
+        if constructor.is_null() || message.is_null() {
+            return;
+        }
+        let args = [jvalue { i: code as jint }, jvalue { l: message }];
+        let exception = ((**env).NewObjectA)(env, class, constructor, args.as_ptr());
+        if !exception.is_null() {
+            ((**env).Throw)(env, exception);
+            ((**env).DeleteLocalRef)(env, exception);
+        }
+        ((**env).DeleteLocalRef)(env, message);
+        ((**env).DeleteLocalRef)(env, class);
+    }
+
+}
+
+fn ptr_or_null(value: &Option<CString>) -> *const c_char {
+    value.as_ref().map_or(ptr::null(), |value| value.as_ptr())
+}
+
+fn db_arg(db: jlong) -> *const PldbDatabase {
+    db as usize as *const PldbDatabase
+}
+
+fn cursor_arg(cursor: jlong) -> *mut PldbCursor {
+    cursor as usize as *mut PldbCursor
+}
+
+/// The string arguments of a native method, converted before the call.
+macro_rules! strings {
+    ($env:expr, $($name:ident),+ ; $fail:expr) => {
+        $(
+            let $name = match $env.string_arg($name, stringify!($name)) {
+                Ok(value) => value,
+                Err(()) => return $fail,
+            };
+        )+
+    };
+    ($env:expr, $($name:ident),+) => {
+        $(
+            let $name = match $env.string_arg($name, stringify!($name)) {
+                Ok(value) => value,
+                Err(()) => return,
+            };
+        )+
+    };
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_version(env: *mut JNIEnv, _class: jclass) -> jbyteArray {
+    Env(env).byte_array(CStr::from_ptr(polodb_version()).to_bytes())
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_open(env: *mut JNIEnv, _class: jclass, path: jbyteArray) -> jlong {
+    let env = Env(env);
+    strings!(env, path; 0);
+    let mut db = ptr::null_mut();
+    match env.check(polodb_open(ptr_or_null(&path), &mut db)) {
+        Ok(()) => db as usize as jlong,
+        Err(()) => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_openSnapshot(env: *mut JNIEnv, _class: jclass, path: jbyteArray) -> jlong {
+    let env = Env(env);
+    strings!(env, path; 0);
+    let mut db = ptr::null_mut();
+    match env.check(polodb_open_snapshot(ptr_or_null(&path), &mut db)) {
+        Ok(()) => db as usize as jlong,
+        Err(()) => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_close(_env: *mut JNIEnv, _class: jclass, db: jlong) {
+    polodb_close(db_arg(db) as *mut PldbDatabase);
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_listCollectionNames(env: *mut JNIEnv, _class: jclass, db: jlong) -> jbyteArray {
+    let env = Env(env);
+    let mut names = ptr::null_mut();
+    match env.check(polodb_list_collection_names(db_arg(db), &mut names)) {
+        Ok(()) => env.take_string(names),
+        Err(()) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_createCollection(env: *mut JNIEnv, _class: jclass, db: jlong, name: jbyteArray) {
+    let env = Env(env);
+    strings!(env, name);
+    let _ = env.check(polodb_create_collection(db_arg(db), ptr_or_null(&name)));
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_dropCollection(env: *mut JNIEnv, _class: jclass, db: jlong, name: jbyteArray) {
+    let env = Env(env);
+    strings!(env, name);
+    let _ = env.check(polodb_drop_collection(db_arg(db), ptr_or_null(&name)));
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_insertOne(
+    env: *mut JNIEnv,
+    _class: jclass,
+    db: jlong,
+    col: jbyteArray,
+    doc: jbyteArray,
+) -> jbyteArray {
+    let env = Env(env);
+    strings!(env, col, doc; ptr::null_mut());
+    let mut id = ptr::null_mut();
+    match env.check(polodb_insert_one(db_arg(db), ptr_or_null(&col), ptr_or_null(&doc), &mut id)) {
+        Ok(()) => env.take_string(id),
+        Err(()) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_insertMany(
+    env: *mut JNIEnv,
+    _class: jclass,
+    db: jlong,
+    col: jbyteArray,
+    docs: jbyteArray,
+) -> jbyteArray {
+    let env = Env(env);
+    strings!(env, col, docs; ptr::null_mut());
+    let mut ids = ptr::null_mut();
+    match env.check(polodb_insert_many(db_arg(db), ptr_or_null(&col), ptr_or_null(&docs), &mut ids)) {
+        Ok(()) => env.take_string(ids),
+        Err(()) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_find(
+    env: *mut JNIEnv,
+    _class: jclass,
+    db: jlong,
+    col: jbyteArray,
+    filter: jbyteArray,
+    options: jbyteArray,
+) -> jlong {
+    let env = Env(env);
+    strings!(env, col, filter, options; 0);
+    let mut cursor = ptr::null_mut();
+    let code = polodb_find(db_arg(db), ptr_or_null(&col), ptr_or_null(&filter), ptr_or_null(&options), &mut cursor);
+    match env.check(code) {
+        Ok(()) => cursor as usize as jlong,
+        Err(()) => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_findOne(
+    env: *mut JNIEnv,
+    _class: jclass,
+    db: jlong,
+    col: jbyteArray,
+    filter: jbyteArray,
+) -> jbyteArray {
+    let env = Env(env);
+    strings!(env, col, filter; ptr::null_mut());
+    let mut doc = ptr::null_mut();
+    match env.check(polodb_find_one(db_arg(db), ptr_or_null(&col), ptr_or_null(&filter), &mut doc)) {
+        Ok(()) => env.take_string(doc),
+        Err(()) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_aggregate(
+    env: *mut JNIEnv,
+    _class: jclass,
+    db: jlong,
+    col: jbyteArray,
+    pipeline: jbyteArray,
+) -> jlong {
+    let env = Env(env);
+    strings!(env, col, pipeline; 0);
+    let mut cursor = ptr::null_mut();
+    match env.check(polodb_aggregate(db_arg(db), ptr_or_null(&col), ptr_or_null(&pipeline), &mut cursor)) {
+        Ok(()) => cursor as usize as jlong,
+        Err(()) => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_cursorNext(env: *mut JNIEnv, _class: jclass, cursor: jlong) -> jbyteArray {
+    let env = Env(env);
+    let mut doc = ptr::null_mut();
+    match env.check(polodb_cursor_next(cursor_arg(cursor), &mut doc)) {
+        Ok(()) => env.take_string(doc),
+        Err(()) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_cursorFree(_env: *mut JNIEnv, _class: jclass, cursor: jlong) {
+    polodb_cursor_free(cursor_arg(cursor));
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_countDocuments(
+    env: *mut JNIEnv,
+    _class: jclass,
+    db: jlong,
+    col: jbyteArray,
+) -> jlong {
+    let env = Env(env);
+    strings!(env, col; 0);
+    let mut count = 0;
+    match env.check(polodb_count_documents(db_arg(db), ptr_or_null(&col), &mut count)) {
+        Ok(()) => count as jlong,
+        Err(()) => 0,
+    }
+}
+
+type UpdateFn = unsafe extern "C" fn(
+    *const PldbDatabase,
+    *const c_char,
+    *const c_char,
+    *const c_char,
+    *mut u64,
+    *mut u64,
+) -> c_int;
+
+unsafe fn update(
+    env: Env,
+    call: UpdateFn,
+    db: jlong,
+    col: jbyteArray,
+    filter: jbyteArray,
+    update: jbyteArray,
+) -> jlongArray {
+    strings!(env, col, filter, update; ptr::null_mut());
+    let (mut matched, mut modified) = (0, 0);
+    let code = call(db_arg(db), ptr_or_null(&col), ptr_or_null(&filter), ptr_or_null(&update), &mut matched, &mut modified);
+    match env.check(code) {
+        Ok(()) => env.long_array(&[matched as jlong, modified as jlong]),
+        Err(()) => ptr::null_mut(),
+    }
+}
+
+/// Returns the matched and the modified counts.
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_updateOne(
+    env: *mut JNIEnv,
+    _class: jclass,
+    db: jlong,
+    col: jbyteArray,
+    filter: jbyteArray,
+    doc: jbyteArray,
+) -> jlongArray {
+    update(Env(env), polodb_update_one, db, col, filter, doc)
+}
+
+/// Returns the matched and the modified counts.
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_updateMany(
+    env: *mut JNIEnv,
+    _class: jclass,
+    db: jlong,
+    col: jbyteArray,
+    filter: jbyteArray,
+    doc: jbyteArray,
+) -> jlongArray {
+    update(Env(env), polodb_update_many, db, col, filter, doc)
+}
+
+type DeleteFn = unsafe extern "C" fn(*const PldbDatabase, *const c_char, *const c_char, *mut u64) -> c_int;
+
+unsafe fn delete(env: Env, call: DeleteFn, db: jlong, col: jbyteArray, filter: jbyteArray) -> jlong {
+    strings!(env, col, filter; 0);
+    let mut deleted = 0;
+    match env.check(call(db_arg(db), ptr_or_null(&col), ptr_or_null(&filter), &mut deleted)) {
+        Ok(()) => deleted as jlong,
+        Err(()) => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_deleteOne(
+    env: *mut JNIEnv,
+    _class: jclass,
+    db: jlong,
+    col: jbyteArray,
+    filter: jbyteArray,
+) -> jlong {
+    delete(Env(env), polodb_delete_one, db, col, filter)
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_deleteMany(
+    env: *mut JNIEnv,
+    _class: jclass,
+    db: jlong,
+    col: jbyteArray,
+    filter: jbyteArray,
+) -> jlong {
+    delete(Env(env), polodb_delete_many, db, col, filter)
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_createIndex(
+    env: *mut JNIEnv,
+    _class: jclass,
+    db: jlong,
+    col: jbyteArray,
+    keys: jbyteArray,
+    options: jbyteArray,
+) {
+    let env = Env(env);
+    strings!(env, col, keys, options);
+    let _ = env.check(polodb_create_index(db_arg(db), ptr_or_null(&col), ptr_or_null(&keys), ptr_or_null(&options)));
+}
+
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_polodb_NativeLib_dropIndex(
+    env: *mut JNIEnv,
+    _class: jclass,
+    db: jlong,
+    col: jbyteArray,
+    name: jbyteArray,
+) {
+    let env = Env(env);
+    strings!(env, col, name);
+    let _ = env.check(polodb_drop_index(db_arg(db), ptr_or_null(&col), ptr_or_null(&name)));
+}
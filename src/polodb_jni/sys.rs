@@ -0,0 +1,113 @@
+// Copyright 2024 Vincent Chan
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The types of `jni.h` used by the library. The table of the functions of the
+//! environment only names the functions called, the others are padding.
+
+#![allow(non_camel_case_types, non_snake_case)]
+
+use std::os::raw::{c_char, c_void};
+
+pub type jint = i32;
+pub type jlong = i64;
+pub type jbyte = i8;
+pub type jsize = jint;
+
+pub enum _jobject {}
+pub type jobject = *mut _jobject;
+pub type jclass = jobject;
+pub type jthrowable = jobject;
+pub type jbyteArray = jobject;
+pub type jlongArray = jobject;
+
+pub enum _jmethodID {}
+pub type jmethodID = *mut _jmethodID;
+
+#[repr(C)]
+pub union jvalue {
+    pub i: jint,
+    pub j: jlong,
+    pub l: jobject,
+}
+
+pub type JNIEnv = *const JNINativeInterface;
+
+type Padding<const N: usize> = [*const c_void; N];
+
+/// `struct JNINativeInterface_`, up to `SetLongArrayRegion`, the comments
+/// giving the index of the functions in the table.
+#[repr(C)]
+pub struct JNINativeInterface {
+    _reserved: Padding<6>,
+    /// 6
+    pub FindClass: unsafe extern "system" fn(env: *mut JNIEnv, name: *const c_char) -> jclass,
+    _padding_7: Padding<6>,
+    /// 13
+    pub Throw: unsafe extern "system" fn(env: *mut JNIEnv, obj: jthrowable) -> jint,
+    _padding_14: Padding<9>,
+    /// 23
+    pub DeleteLocalRef: unsafe extern "system" fn(env: *mut JNIEnv, obj: jobject),
+    _padding_24: Padding<6>,
+    /// 30
+    pub NewObjectA: unsafe extern "system" fn(
+        env: *mut JNIEnv,
+        class: jclass,
+        method: jmethodID,
+        args: *const jvalue,
+    ) -> jobject,
+    _padding_31: Padding<2>,
+    /// 33
+    pub GetMethodID: unsafe extern "system" fn(
+        env: *mut JNIEnv,
+        class: jclass,
+        name: *const c_char,
+        sig: *const c_char,
+    ) -> jmethodID,
+    _padding_34: Padding<137>,
+    /// 171
+    pub GetArrayLength: unsafe extern "system" fn(env: *mut JNIEnv, array: jobject) -> jsize,
+    _padding_172: Padding<4>,
+    /// 176
+    pub NewByteArray: unsafe extern "system" fn(env: *mut JNIEnv, len: jsize) -> jbyteArray,
+    _padding_177: Padding<3>,
+    /// 180
+    pub NewLongArray: unsafe extern "system" fn(env: *mut JNIEnv, len: jsize) -> jlongArray,
+    _padding_181: Padding<19>,
+    /// 200
+    pub GetByteArrayRegion: unsafe extern "system" fn(
+        env: *mut JNIEnv,
+        array: jbyteArray,
+        start: jsize,
+        len: jsize,
+        buf: *mut jbyte,
+    ),
+    _padding_201: Padding<7>,
+    /// 208
+    pub SetByteArrayRegion: unsafe extern "system" fn(
+        env: *mut JNIEnv,
+        array: jbyteArray,
+        start: jsize,
+        len: jsize,
+        buf: *const jbyte,
+    ),
+    _padding_209: Padding<3>,
+    /// 212
+    pub SetLongArrayRegion: unsafe extern "system" fn(
+        env: *mut JNIEnv,
+        array: jlongArray,
+        start: jsize,
+        len: jsize,
+        buf: *const jlong,
+    ),
+}